@@ -24,7 +24,7 @@ use tokio::runtime::TaskExecutor;
 use tokio::timer::Interval;
 use sysinfo::{get_current_pid, ProcessExt, System, SystemExt};
 use network::{SyncState, SyncProvider};
-use client::BlockchainEvents;
+use client::{BlockchainEvents, Notification};
 use runtime_primitives::traits::{Header, As};
 
 const TIMER_INTERVAL_MS: u64 = 5000;
@@ -79,12 +79,63 @@ pub fn start<C>(service: &Service<C>, exit: ::exit_future::Exit, handle: TaskExe
 				"system.interval";
 				"status" => format!("{}{}", status, target),
 				"peers" => num_peers,
+				"chain_mismatches" => sync_status.chain_mismatches,
+				"stalls" => sync_status.stalls,
 				"height" => best_number,
 				"best" => ?hash,
 				"txcount" => txpool_status.ready,
 				"cpu" => cpu_usage,
 				"memory" => memory
 			);
+
+			if let Some(stalled_for) = sync_status.stalled_for {
+				telemetry!(
+					"system.stall";
+					"height" => best_number,
+					"peers" => num_peers,
+					"stalled_secs" => stalled_for.as_secs()
+				);
+			}
+
+			let priorities = txpool.priority_stats();
+			telemetry!(
+				"txpool.priorities";
+				"included" => ?priorities.included.ranges(),
+				"dropped" => ?priorities.dropped.ranges()
+			);
+
+			for (peer, stats) in &sync_status.light_serving {
+				telemetry!(
+					"network.light_serving";
+					"peer" => peer,
+					"proofs" => stats.proofs,
+					"bytes" => stats.bytes,
+					"cpu_ms" => stats.cpu_time.as_secs() * 1000 + stats.cpu_time.subsec_millis() as u64
+				);
+			}
+
+			for &(kind, ref metrics) in &sync_status.on_demand_metrics {
+				telemetry!(
+					"light.fetcher";
+					"kind" => kind.name(),
+					"requests" => metrics.requests,
+					"responses" => metrics.responses,
+					"invalid_responses" => metrics.invalid_responses,
+					"timeouts" => metrics.timeouts,
+					"failures" => metrics.failures,
+					"latency_ms" => ?metrics.latency.buckets
+				);
+			}
+
+			for &(peer, ref stats) in &sync_status.on_demand_peers {
+				telemetry!(
+					"light.fetcher_peer";
+					"peer" => peer,
+					"valid_responses" => stats.valid_responses,
+					"invalid_responses" => stats.invalid_responses,
+					"timeouts" => stats.timeouts
+				);
+			}
 		} else {
 			warn!("Error getting best block information");
 		}
@@ -93,8 +144,11 @@ pub fn start<C>(service: &Service<C>, exit: ::exit_future::Exit, handle: TaskExe
 	});
 
 	let client = service.client();
-	let display_block_import = client.import_notification_stream().for_each(|n| {
-		info!(target: "substrate", "Imported #{} ({})", n.header.number(), n.hash);
+	let display_block_import = client.import_notification_stream("informant").for_each(|n| {
+		match n {
+			Notification::Item(n) => info!(target: "substrate", "Imported #{} ({})", n.header.number(), n.hash),
+			Notification::Lagged(missed) => info!(target: "substrate", "Imported {} blocks, not displayed", missed),
+		}
 		Ok(())
 	});
 
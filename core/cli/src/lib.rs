@@ -39,6 +39,7 @@ extern crate substrate_network as network;
 extern crate substrate_network_libp2p as network_libp2p;
 extern crate sr_primitives as runtime_primitives;
 extern crate substrate_service as service;
+extern crate substrate_logger as logger;
 extern crate substrate_primitives as primitives;
 #[macro_use]
 extern crate slog;	// needed until we can reexport `slog_info` from `substrate_telemetry`
@@ -53,11 +54,14 @@ extern crate clap;
 extern crate error_chain;
 #[macro_use]
 extern crate log;
+#[cfg(test)]
+extern crate tempdir;
 
 pub mod error;
 pub mod informant;
 mod panic_hook;
 
+use client::backend::CompactionProfile;
 use network_libp2p::Protocol;
 use runtime_primitives::traits::As;
 use service::{
@@ -74,6 +78,7 @@ use std::fs::File;
 use std::net::{Ipv4Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 use names::{Generator, Name};
 use regex::Regex;
 
@@ -201,6 +206,12 @@ where
 		return Ok(Action::ExecutedInternally);
 	}
 
+	if let Some(matches) = matches.subcommand_matches("verify-spec") {
+		let spec = load_spec(&matches, spec_factory)?;
+		verify_spec::<F>(matches, spec)?;
+		return Ok(Action::ExecutedInternally);
+	}
+
 	if let Some(matches) = matches.subcommand_matches("export-blocks") {
 		let spec = load_spec(&matches, spec_factory)?;
 		export_blocks::<F, _>(matches, spec, exit.into_exit())?;
@@ -213,19 +224,60 @@ where
 		return Ok(Action::ExecutedInternally);
 	}
 
+	if let Some(matches) = matches.subcommand_matches("export-light-snapshot") {
+		let spec = load_spec(&matches, spec_factory)?;
+		export_light_snapshot::<F>(matches, spec)?;
+		return Ok(Action::ExecutedInternally);
+	}
+
 	if let Some(matches) = matches.subcommand_matches("revert") {
 		let spec = load_spec(&matches, spec_factory)?;
 		revert_chain::<F>(matches, spec)?;
 		return Ok(Action::ExecutedInternally);
 	}
 
+	if let Some(matches) = matches.subcommand_matches("compact-db") {
+		let spec = load_spec(&matches, spec_factory)?;
+		compact_db::<F, _>(matches, spec, exit.into_exit())?;
+		return Ok(Action::ExecutedInternally);
+	}
+
+	if let Some(matches) = matches.subcommand_matches("migrate-db") {
+		let spec = load_spec(&matches, spec_factory)?;
+		migrate_db::<F>(matches, spec)?;
+		return Ok(Action::ExecutedInternally);
+	}
+
+	if let Some(matches) = matches.subcommand_matches("check-db") {
+		let spec = load_spec(&matches, spec_factory)?;
+		check_db::<F>(matches, spec)?;
+		return Ok(Action::ExecutedInternally);
+	}
+
+	if let Some(matches) = matches.subcommand_matches("generate-node-key") {
+		generate_node_key(matches)?;
+		return Ok(Action::ExecutedInternally);
+	}
+
 	if let Some(matches) = matches.subcommand_matches("purge-chain") {
 		let spec = load_spec(&matches, spec_factory)?;
 		purge_chain::<F>(matches, spec)?;
 		return Ok(Action::ExecutedInternally);
 	}
 
-	let spec = load_spec(&matches, spec_factory)?;
+	let mut spec = load_spec(&matches, spec_factory)?;
+	if let Some(url) = matches.value_of("fork") {
+		let number = match matches.value_of("fork-block") {
+			Some(number) => Some(number.parse()
+				.map_err(|_| error::ErrorKind::Input("Invalid --fork-block argument".to_owned()))?),
+			None => None,
+		};
+		let mut seeds: Vec<String> = matches.values_of("key").unwrap_or_default().map(str::to_owned).collect();
+		if seeds.is_empty() {
+			seeds.push("Alice".into());
+		}
+		spec = service::chain_ops::fork_spec(&spec, url, number, &seeds)?;
+	}
 	let mut config = service::Configuration::default_with_spec(spec);
 
 	config.impl_name = impl_name;
@@ -251,6 +303,7 @@ where
 		.into();
 
 	config.database_path = db_path(&base_path, config.chain_spec.id()).to_string_lossy().into();
+	config.database_read_only = matches.is_present("db-read-only");
 
 	config.pruning = match matches.value_of("pruning") {
 		Some("archive") => PruningMode::ArchiveAll,
@@ -258,12 +311,16 @@ where
 		Some(s) => PruningMode::keep_blocks(s.parse()
 			.map_err(|_| error::ErrorKind::Input("Invalid pruning mode specified".to_owned()))?),
 	};
+	config.archive_fallback = matches.value_of("archive-fallback-url").map(str::to_owned);
 
 	let role =
 		if matches.is_present("light") {
 			config.block_execution_strategy = service::ExecutionStrategy::NativeWhenPossible;
-			service::Roles::LIGHT
-		} else if matches.is_present("validator") || matches.is_present("dev") {
+			match matches.is_present("light-serve") {
+				true => service::Roles::LIGHT | service::Roles::LIGHT_SERVER,
+				false => service::Roles::LIGHT,
+			}
+		} else if matches.is_present("validator") || matches.is_present("dev") || matches.is_present("fork") {
 			config.block_execution_strategy = service::ExecutionStrategy::Both;
 			service::Roles::AUTHORITY
 		} else {
@@ -290,7 +347,7 @@ where
 		config.network.reserved_nodes.extend(matches
 			 .values_of("reserved-nodes")
 			 .map_or(Default::default(), |v| v.map(|n| n.to_owned()).collect::<Vec<_>>()));
-		if !config.network.reserved_nodes.is_empty() {
+		if !config.network.reserved_nodes.is_empty() || matches.is_present("fork") {
 			config.network.non_reserved_mode = NonReservedPeerMode::Deny;
 		}
 
@@ -317,7 +374,10 @@ where
 		config.network.use_secret = match matches.value_of("node-key").map(H256::from_str) {
 			Some(Ok(secret)) => Some(secret.into()),
 			Some(Err(err)) => return Err(format!("Error parsing node key: {}", err).into()),
-			None => None,
+			None => match matches.value_of("node-key-file") {
+				Some(path) => Some(read_node_key_file(path)?),
+				None => None,
+			},
 		};
 
 		let in_peers = match matches.value_of("in-peers") {
@@ -334,15 +394,121 @@ where
 	}
 
 	config.keys = matches.values_of("key").unwrap_or_default().map(str::to_owned).collect();
-	if matches.is_present("dev") {
+	if matches.is_present("dev") || (matches.is_present("fork") && config.keys.is_empty()) {
 		config.keys.push("Alice".into());
 	}
+	config.instant_seal = matches.is_present("instant-seal");
+	config.manual_seal = config.instant_seal
+		|| matches.is_present("manual-seal")
+		|| matches.is_present("dev")
+		|| matches.is_present("fork");
+	config.skip_changes_tries_on_sync = matches.is_present("skip-changes-tries-on-sync");
+	if config.skip_changes_tries_on_sync && !config.pruning.is_archive() {
+		// skipped tries are built from the states of their parent blocks, pruned by the time the sync is over
+		return Err(error::ErrorKind::Input("--skip-changes-tries-on-sync requires --pruning archive".to_owned()).into());
+	}
+	config.persist_transaction_pool = matches.is_present("persist-pool");
+	if let Some(secs) = matches.value_of("compact-db-when-idle") {
+		config.idle_compaction = Some(Duration::from_secs(
+			secs.parse().map_err(|_| "Invalid idle period specified")?
+		));
+	}
+	if let Some(s) = matches.value_of("fork-choice") {
+		config.fork_choice = match s {
+			"longest-chain" => service::ForkChoiceRule::LongestChain,
+			"first-seen" => service::ForkChoiceRule::FirstSeen,
+			_ => return Err(error::ErrorKind::Input("Invalid fork choice rule specified".to_owned()).into()),
+		};
+	}
+	config.light_snapshot = matches.value_of("light-snapshot").map(str::to_owned);
+	config.light_checkpoint = matches.value_of("light-checkpoint").map(str::to_owned);
+	if let Some(providers) = matches.value_of("light-head-providers") {
+		config.light_head_providers = providers.parse().map_err(|_| "Invalid light-head-providers value specified.")?;
+	}
+	if let Some(size) = matches.value_of("light-read-cache-size") {
+		config.light_read_cache_size = size.parse().map_err(|_| "Invalid light-read-cache-size value specified.")?;
+	}
+	config.light_headers_pruning = match matches.value_of("light-keep-headers") {
+		Some("all") => service::HeadersPruning::KeepAll,
+		None => service::HeadersPruning::default(),
+		Some(s) => service::HeadersPruning::KeepRecent(s.parse()
+			.map_err(|_| error::ErrorKind::Input("Invalid light-keep-headers value specified".to_owned()))?),
+	};
+	if let Some(retries) = matches.value_of("light-fetch-retries") {
+		config.light_fetcher.max_retries = retries.parse().map_err(|_| "Invalid light-fetch-retries value specified.")?;
+	}
+	if let Some(backoff) = matches.value_of("light-fetch-backoff") {
+		config.light_fetcher.backoff_base = Duration::from_millis(
+			backoff.parse().map_err(|_| "Invalid light-fetch-backoff value specified.")?
+		);
+	}
+	if let Some(timeout) = matches.value_of("light-fetch-timeout") {
+		config.light_fetcher.request_timeout = Duration::from_secs(
+			timeout.parse().map_err(|_| "Invalid light-fetch-timeout value specified.")?
+		);
+	}
+	config.light_storage_polling = matches.is_present("light-storage-polling");
+	config.light_fallback_nodes = matches.values_of("light-fallback-nodes").unwrap_or_default().map(str::to_owned).collect();
+	if let Some(budget) = matches.value_of("proof-budget") {
+		config.proof_budget = Some(budget.parse().map_err(|_| "Invalid proof-budget value specified.")?);
+	}
+	if let Some(timeout) = matches.value_of("stall-timeout") {
+		let mut watchdog = service::WatchdogConfig::default();
+		watchdog.stall_timeout = Duration::from_secs(
+			timeout.parse().map_err(|_| "Invalid stall-timeout value specified.")?
+		);
+		if let Some(percent) = matches.value_of("stall-rotated-peers") {
+			watchdog.rotated_peers = percent.parse().map_err(|_| "Invalid stall-rotated-peers value specified.")?;
+		}
+		config.watchdog = Some(watchdog);
+	}
+	if let Some(budget) = matches.value_of("memory-budget") {
+		let budget = budget.parse().map_err(|_| "Invalid memory-budget value specified.")?;
+		config.memory_budget = Some(service::MemoryBudget::new(budget));
+	}
+	config.offchain_http.allowed_hosts = matches.values_of("offchain-http-allow")
+		.unwrap_or_default().map(str::to_owned).collect();
+	config.offchain_http.denied_hosts = matches.values_of("offchain-http-deny")
+		.unwrap_or_default().map(str::to_owned).collect();
+
+	for hash in matches.values_of("bad-block").unwrap_or_default() {
+		let hash = H256::from_str(hash.trim_left_matches("0x"))
+			.map_err(|err| format!("Error parsing bad block hash {}: {}", hash, err))?;
+		config.bad_blocks.push(hash.as_ref().to_vec().into());
+	}
+	for fork_block in matches.values_of("fork-block").unwrap_or_default() {
+		let mut parts = fork_block.splitn(2, ':');
+		let number = parts.next().and_then(|number| number.parse::<u64>().ok())
+			.ok_or_else(|| format!("Error parsing fork block number {}", fork_block))?;
+		let hash = parts.next().unwrap_or_default();
+		let hash = H256::from_str(hash.trim_left_matches("0x"))
+			.map_err(|err| format!("Error parsing fork block hash {}: {}", fork_block, err))?;
+		config.fork_blocks.push((number, hash.as_ref().to_vec().into()));
+	}
 
 	let rpc_interface: &str = if matches.is_present("rpc-external") { "0.0.0.0" } else { "127.0.0.1" };
 	let ws_interface: &str = if matches.is_present("ws-external") { "0.0.0.0" } else { "127.0.0.1" };
 
 	config.rpc_http = Some(parse_address(&format!("{}:{}", rpc_interface, 9933), "rpc-port", &matches)?);
 	config.rpc_ws = Some(parse_address(&format!("{}:{}", ws_interface, 9944), "ws-port", &matches)?);
+	config.rpc_unsafe = matches.is_present("rpc-unsafe");
+	config.rpc_rate_limits.per_connection = match matches.value_of("rpc-rate-limit-connection") {
+		Some(limit) => Some(limit.parse().map_err(|_| "Invalid rpc-rate-limit-connection value specified.")?),
+		None => None,
+	};
+	config.rpc_rate_limits.per_ip = match matches.value_of("rpc-rate-limit-ip") {
+		Some(limit) => Some(limit.parse().map_err(|_| "Invalid rpc-rate-limit-ip value specified.")?),
+		None => None,
+	};
+	for proxy in matches.values_of("rpc-trusted-proxy").unwrap_or_default() {
+		config.rpc_rate_limits.trusted_proxies.push(proxy.parse().map_err(|_| "Invalid rpc-trusted-proxy value specified.")?);
+	}
+	if let Some(threads) = matches.value_of("rpc-execution-threads") {
+		config.rpc_execution_pool.threads = threads.parse().map_err(|_| "Invalid rpc-execution-threads value specified.")?;
+	}
+	if let Some(queue) = matches.value_of("rpc-execution-queue") {
+		config.rpc_execution_pool.max_queued = queue.parse().map_err(|_| "Invalid rpc-execution-queue value specified.")?;
+	}
 
 	// Override telemetry
 	if matches.is_present("no-telemetry") {
@@ -359,17 +525,31 @@ fn build_spec<F>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>)
 {
 	info!("Building chain spec");
 	let raw = matches.is_present("raw");
-	let json = service::chain_ops::build_spec::<FactoryGenesis<F>>(spec, raw)?;
+	let json = service::chain_ops::build_spec::<F>(spec, raw)?;
 	print!("{}", json);
 	Ok(())
 }
 
+fn verify_spec<F>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let expected = match matches.value_of("root") {
+		Some(root) => Some(H256::from_str(root.trim_left_matches("0x"))
+			.map_err(|err| format!("Error parsing genesis state root {}: {}", root, err))?),
+		None => None,
+	};
+	let root = service::chain_ops::verify_spec::<F>(&spec, expected)?;
+	info!("Genesis state root of {} matches: {:?}", spec.name(), root);
+	Ok(())
+}
+
 fn export_blocks<F, E>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>, exit: E) -> error::Result<()>
 	where F: ServiceFactory, E: Future<Item=(),Error=()> + Send + 'static,
 {
 	let base_path = base_path(matches);
 	let mut config = service::Configuration::default_with_spec(spec);
 	config.database_path = db_path(&base_path, config.chain_spec.id()).to_string_lossy().into();
+	config.database_read_only = matches.is_present("db-read-only");
 	info!("DB path: {}", config.database_path);
 	let from: u64 = match matches.value_of("from") {
 		Some(v) => v.parse().map_err(|_| "Invalid --from argument")?,
@@ -423,6 +603,23 @@ fn import_blocks<F, E>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesi
 	Ok(service::chain_ops::import_blocks::<F, _, _>(config, exit, file)?)
 }
 
+fn export_light_snapshot<F>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let base_path = base_path(matches);
+	let mut config = service::Configuration::default_with_spec(spec);
+	config.database_path = db_path(&base_path, config.chain_spec.id()).to_string_lossy().into();
+	config.database_read_only = matches.is_present("db-read-only");
+	info!("DB path: {}", config.database_path);
+
+	let file: Box<Write> = match matches.value_of("OUTPUT") {
+		Some(filename) => Box::new(File::create(filename)?),
+		None => Box::new(stdout()),
+	};
+
+	Ok(service::chain_ops::export_light_snapshot::<F, _>(config, file)?)
+}
+
 fn revert_chain<F>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>) -> error::Result<()>
 	where F: ServiceFactory,
 {
@@ -438,6 +635,46 @@ fn revert_chain<F>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>
 	Ok(service::chain_ops::revert_chain::<F>(config, As::sa(blocks))?)
 }
 
+fn compact_db<F, E>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>, exit: E) -> error::Result<()>
+	where F: ServiceFactory, E: Future<Item=(),Error=()> + Send + 'static,
+{
+	let base_path = base_path(matches);
+	let mut config = service::Configuration::default_with_spec(spec);
+	config.database_path = db_path(&base_path, config.chain_spec.id()).to_string_lossy().into();
+
+	let mut profile = CompactionProfile::default();
+	if let Some(size) = matches.value_of("batch-size") {
+		profile.batch_size = size.parse().map_err(|_| "Invalid batch size specified")?;
+	}
+	if let Some(delay) = matches.value_of("batch-delay") {
+		profile.batch_delay = Some(Duration::from_millis(delay.parse().map_err(|_| "Invalid batch delay specified")?));
+	}
+
+	Ok(service::chain_ops::compact_db::<F, _>(config, exit, profile)?)
+}
+
+fn migrate_db<F>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let base_path = base_path(matches);
+	let mut config = service::Configuration::default_with_spec(spec);
+	config.database_path = db_path(&base_path, config.chain_spec.id()).to_string_lossy().into();
+	config.database_read_only = matches.is_present("db-read-only");
+
+	Ok(service::chain_ops::migrate_db::<F>(config, matches.is_present("dry-run"))?)
+}
+
+fn check_db<F>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let base_path = base_path(matches);
+	let mut config = service::Configuration::default_with_spec(spec);
+	config.database_path = db_path(&base_path, config.chain_spec.id()).to_string_lossy().into();
+	config.database_read_only = matches.is_present("db-read-only");
+
+	Ok(service::chain_ops::check_db::<F>(config)?)
+}
+
 fn purge_chain<F>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>) -> error::Result<()>
 	where F: ServiceFactory,
 {
@@ -462,6 +699,39 @@ fn purge_chain<F>(matches: &clap::ArgMatches, spec: ChainSpec<FactoryGenesis<F>>
 	Ok(())
 }
 
+fn generate_node_key(matches: &clap::ArgMatches) -> error::Result<()> {
+	let secret = network_libp2p::generate_node_key();
+	let peer_id = network_libp2p::node_key_peer_id(&secret)?;
+	let hex_secret = format!("{:x}", H256::from(secret));
+
+	match matches.value_of("OUTPUT") {
+		Some(path) => {
+			network_libp2p::write_node_key_file(path, hex_secret.as_bytes())?;
+			println!("{}", peer_id.to_base58());
+		},
+		None => {
+			println!("{}", hex_secret);
+			eprintln!("{}", peer_id.to_base58());
+		},
+	}
+
+	Ok(())
+}
+
+fn read_node_key_file(path: &str) -> error::Result<network_libp2p::Secret> {
+	let content = fs::read(path)?;
+	if content.len() == 32 {
+		let mut secret = network_libp2p::Secret::default();
+		secret.copy_from_slice(&content);
+		return Ok(secret);
+	}
+
+	let hex_secret = String::from_utf8_lossy(&content);
+	H256::from_str(hex_secret.trim().trim_left_matches("0x"))
+		.map(Into::into)
+		.map_err(|err| format!("Error parsing node key file {}: {}", path, err).into())
+}
+
 fn parse_address(default: &str, port_param: &str, matches: &clap::ArgMatches) -> Result<SocketAddr, String> {
 	let mut address: SocketAddr = default.parse().ok().ok_or_else(|| format!("Invalid address specified for --{}.", port_param))?;
 	if let Some(port) = matches.value_of(port_param) {
@@ -513,22 +783,18 @@ fn default_base_path() -> PathBuf {
 fn init_logger(pattern: &str) {
 	use ansi_term::Colour;
 
-	let mut builder = env_logger::Builder::new();
-	// Disable info logging by default for some modules:
-	builder.filter(Some("ws"), log::LevelFilter::Off);
-	builder.filter(Some("hyper"), log::LevelFilter::Warn);
-	// Enable info for others.
-	builder.filter(None, log::LevelFilter::Info);
-
+	// Disable info logging by default for some modules, enable info for others.
+	let mut directives = vec!["ws=off,hyper=warn,info".to_owned()];
 	if let Ok(lvl) = std::env::var("RUST_LOG") {
-		builder.parse(&lvl);
+		directives.push(lvl);
 	}
+	directives.push(pattern.to_owned());
+	let directives: Vec<_> = directives.iter().map(String::as_str).collect();
 
-	builder.parse(pattern);
 	let isatty = atty::is(atty::Stream::Stderr);
 	let enable_color = isatty;
 
-	builder.format(move |buf, record| {
+	let format = move |buf: &mut env_logger::fmt::Formatter, record: &log::Record| {
 		let timestamp = time::strftime("%Y-%m-%d %H:%M:%S", &time::now()).expect("Error formatting log timestamp");
 
 		let mut output = if log::max_level() <= log::LevelFilter::Info {
@@ -547,9 +813,9 @@ fn init_logger(pattern: &str) {
 			println!("{}", output);
 		}
 		writeln!(buf, "{}", output)
-	});
+	};
 
-	builder.init();
+	logger::init(&directives, format).expect("Logger is initialized only once; qed");
 }
 
 fn kill_color(s: &str) -> String {
@@ -577,4 +843,16 @@ mod tests {
 		assert!(is_node_name_valid("www.visit.me").is_err());
 		assert!(is_node_name_valid("email@domain").is_err());
 	}
+
+	#[test]
+	fn generated_node_key_file_is_read_back() {
+		let dir = tempdir::TempDir::new("node-key").unwrap();
+		let path = dir.path().join("key");
+		let path = path.to_str().unwrap();
+		let secret = network_libp2p::generate_node_key();
+		let hex_secret = format!("{:x}", H256::from(secret));
+		network_libp2p::write_node_key_file(path, hex_secret.as_bytes()).unwrap();
+
+		assert_eq!(read_node_key_file(path).unwrap(), secret);
+	}
 }
@@ -0,0 +1,65 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Read latency of the state trie nodes, stored with and without compression.
+
+#[macro_use]
+extern crate criterion;
+use criterion::Criterion;
+criterion_group!(benches, benchmark);
+criterion_main!(benches);
+
+extern crate kvdb;
+extern crate kvdb_memorydb;
+extern crate substrate_client_db;
+
+use kvdb::KeyValueDB;
+use substrate_client_db::compression::{encode_node, decode_node};
+
+const COLUMN: Option<u32> = Some(0);
+const KEY: &[u8] = b"node";
+
+/// Leaf node with the code-like payload: short repetitive sequences with some noise.
+fn node(len: usize) -> Vec<u8> {
+	let mut seed = 0x2545f491u32;
+	let mut node = vec![1];
+	node.extend((0..len - 1).map(|i| {
+		seed ^= seed << 13;
+		seed ^= seed >> 17;
+		seed ^= seed << 5;
+		if seed % 8 == 0 { seed as u8 } else { (i % 64) as u8 }
+	}));
+	node
+}
+
+fn bench_read(c: &mut Criterion, name: &str, len: usize, threshold: usize) {
+	let db = kvdb_memorydb::create(1);
+	let mut transaction = db.transaction();
+	transaction.put(COLUMN, KEY, &encode_node(&node(len), threshold));
+	db.write(transaction).unwrap();
+
+	c.bench_function(&format!("read {} node of {} bytes", name, len), move |b| b.iter(|| {
+		let value = db.get(COLUMN, KEY).unwrap().unwrap();
+		decode_node(value.to_vec()).unwrap()
+	}));
+}
+
+fn benchmark(c: &mut Criterion) {
+	for len in &[1024 + 1, 16 * 1024, 256 * 1024, 1024 * 1024] {
+		bench_read(c, "raw", *len, ::std::usize::MAX);
+		bench_read(c, "compressed", *len, 0);
+	}
+}
@@ -0,0 +1,114 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Transparent compression of large state trie nodes.
+//!
+//! Nodes are still stored under the hash of their uncompressed encoding, so the trie roots
+//! are not affected. Compressed node is prefixed with the empty trie header byte. The only
+//! valid trie node starting with this byte is the empty trie node itself, which is never
+//! compressed, so uncompressed nodes (including those written before compression has been
+//! introduced) are read as is.
+
+use std::borrow::Cow;
+
+use snap;
+
+/// Nodes larger than this number of bytes are compressed before being written to the database.
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Prefix of the compressed node.
+const COMPRESSED_NODE_PREFIX: u8 = 0;
+
+/// Encode trie node for writing to the database. Node is compressed if it is larger than
+/// the `threshold` and compression actually saves some space.
+pub fn encode_node(node: &[u8], threshold: usize) -> Cow<[u8]> {
+	if node.len() <= threshold {
+		return Cow::Borrowed(node);
+	}
+
+	match snap::Encoder::new().compress_vec(node) {
+		Ok(ref compressed) if compressed.len() + 1 < node.len() => {
+			let mut encoded = Vec::with_capacity(compressed.len() + 1);
+			encoded.push(COMPRESSED_NODE_PREFIX);
+			encoded.extend_from_slice(compressed);
+			Cow::Owned(encoded)
+		},
+		_ => Cow::Borrowed(node),
+	}
+}
+
+/// Decode trie node that has been read from the database.
+pub fn decode_node(value: Vec<u8>) -> Result<Vec<u8>, String> {
+	if value.len() > 1 && value[0] == COMPRESSED_NODE_PREFIX {
+		snap::Decoder::new().decompress_vec(&value[1..])
+			.map_err(|e| format!("Error decompressing state trie node: {}", e))
+	} else {
+		Ok(value)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn compressible_node(len: usize) -> Vec<u8> {
+		// leaf node header followed by the repetitive payload
+		let mut node = vec![1];
+		node.extend((0..len - 1).map(|i| (i % 16) as u8));
+		node
+	}
+
+	#[test]
+	fn small_nodes_are_not_compressed() {
+		let node = compressible_node(COMPRESSION_THRESHOLD);
+		assert_eq!(encode_node(&node, COMPRESSION_THRESHOLD), Cow::Borrowed(&node[..]));
+		assert_eq!(decode_node(node.clone()).unwrap(), node);
+	}
+
+	#[test]
+	fn large_nodes_are_compressed() {
+		let node = compressible_node(COMPRESSION_THRESHOLD * 4);
+		let encoded = encode_node(&node, COMPRESSION_THRESHOLD).into_owned();
+		assert_eq!(encoded[0], COMPRESSED_NODE_PREFIX);
+		assert!(encoded.len() < node.len());
+		assert_eq!(decode_node(encoded).unwrap(), node);
+	}
+
+	#[test]
+	fn incompressible_nodes_are_stored_as_is() {
+		let mut seed = 0x2545f491u32;
+		let node: Vec<u8> = (0..COMPRESSION_THRESHOLD * 4).map(|_| {
+			seed ^= seed << 13;
+			seed ^= seed >> 17;
+			seed ^= seed << 5;
+			seed as u8
+		}).collect();
+		let encoded = encode_node(&node, COMPRESSION_THRESHOLD).into_owned();
+		assert_eq!(encoded, node);
+		assert_eq!(decode_node(encoded).unwrap(), node);
+	}
+
+	#[test]
+	fn empty_trie_node_is_read_as_is() {
+		assert_eq!(encode_node(&[0], 0), Cow::Borrowed(&[0u8][..]));
+		assert_eq!(decode_node(vec![0]).unwrap(), vec![0]);
+	}
+
+	#[test]
+	fn corrupted_node_is_rejected() {
+		assert!(decode_node(vec![COMPRESSED_NODE_PREFIX, 0xff, 0xff, 0xff]).is_err());
+	}
+}
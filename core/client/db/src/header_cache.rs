@@ -0,0 +1,163 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cache of the block headers read by hash.
+//!
+//! Headers are keyed by hash, so cached headers never become stale, unless the block is reverted.
+
+use std::collections::{HashMap, VecDeque};
+use client::memory_budget::{MemoryAccount, MemoryBudget};
+use runtime_primitives::traits::Block as BlockT;
+
+/// Default size of the cached headers, in bytes.
+pub const DEFAULT_HEADER_CACHE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Weight of the header cache in the shared memory budget.
+pub const HEADER_CACHE_WEIGHT: usize = 1;
+
+/// Bounded cache of the headers. When the size limit is reached, the oldest headers are evicted
+/// first.
+pub struct HeaderCache<Block: BlockT> {
+	headers: HashMap<Block::Hash, (Block::Header, usize)>,
+	order: VecDeque<Block::Hash>,
+	size: usize,
+	limit: usize,
+	account: Option<MemoryAccount>,
+}
+
+impl<Block: BlockT> HeaderCache<Block> {
+	/// Create new cache, keeping at most `limit` bytes of encoded headers.
+	pub fn new(limit: usize) -> Self {
+		HeaderCache {
+			headers: HashMap::new(),
+			order: VecDeque::new(),
+			size: 0,
+			limit,
+			account: None,
+		}
+	}
+
+	/// Account the cached headers in the shared memory budget. The cache is shrunk below its own
+	/// limit when the budget is exceeded.
+	pub fn set_memory_budget(&mut self, budget: &MemoryBudget) {
+		self.account = Some(budget.register("header_cache", HEADER_CACHE_WEIGHT));
+	}
+
+	/// Size of the cached headers, in bytes.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	/// Get cached header. The cache is shrunk first if the memory budget has asked it to.
+	pub fn get(&mut self, hash: &Block::Hash) -> Option<Block::Header> {
+		self.apply_memory_request();
+		self.headers.get(hash).map(|&(ref header, _)| header.clone())
+	}
+
+	/// Insert header of given encoded size into the cache.
+	pub fn insert(&mut self, hash: Block::Hash, header: Block::Header, size: usize) {
+		if size > self.limit || self.headers.contains_key(&hash) {
+			return;
+		}
+
+		self.apply_memory_request();
+
+		self.size += size;
+		self.headers.insert(hash, (header, size));
+		self.order.push_back(hash);
+		let limit = self.limit;
+		self.shrink(limit);
+
+		self.report_memory_usage();
+	}
+
+	/// Shrink the cache to the size it has been asked to shrink to by the memory budget, if any.
+	fn apply_memory_request(&mut self) {
+		let requested = self.account.as_ref().and_then(|account| account.requested_size());
+		if let Some(requested) = requested {
+			self.shrink(requested);
+			self.report_memory_usage();
+		}
+	}
+
+	/// Report the size of the cache to the memory budget, shrinking the cache to its share if
+	/// the budget is exceeded.
+	fn report_memory_usage(&mut self) {
+		let share = self.account.as_ref().and_then(|account| account.report(self.size));
+		if let Some(share) = share {
+			self.shrink(share);
+			if let Some(ref account) = self.account {
+				account.report(self.size);
+			}
+		}
+	}
+
+	/// Remove all cached headers.
+	pub fn clear(&mut self) {
+		self.headers.clear();
+		self.order.clear();
+		self.size = 0;
+		if let Some(ref account) = self.account {
+			account.report(0);
+		}
+	}
+
+	fn shrink(&mut self, limit: usize) {
+		while self.size > limit {
+			let evicted = self.order.pop_front()
+				.expect("size is only non-zero when there are cached headers; qed");
+			if let Some((_, size)) = self.headers.remove(&evicted) {
+				self.size -= size;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use runtime_primitives::testing::{Block as RawBlock, ExtrinsicWrapper, Header};
+
+	type Block = RawBlock<ExtrinsicWrapper<u64>>;
+
+	fn header(number: u64) -> Header {
+		Header {
+			parent_hash: Default::default(),
+			number,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		}
+	}
+
+	#[test]
+	fn header_cache_is_bounded() {
+		let mut cache = HeaderCache::<Block>::new(10);
+		cache.insert(1.into(), header(1), 4);
+		cache.insert(2.into(), header(2), 4);
+		assert_eq!(cache.get(&1.into()), Some(header(1)));
+
+		cache.insert(3.into(), header(3), 4);
+		assert_eq!(cache.get(&1.into()), None);
+		assert_eq!(cache.get(&2.into()), Some(header(2)));
+		assert_eq!(cache.get(&3.into()), Some(header(3)));
+		assert_eq!(cache.size(), 8);
+
+		cache.clear();
+		assert_eq!(cache.get(&2.into()), None);
+		assert_eq!(cache.size(), 0);
+	}
+}
@@ -39,6 +39,7 @@ extern crate parity_codec as codec;
 extern crate substrate_executor as executor;
 extern crate substrate_state_db as state_db;
 extern crate substrate_trie as trie;
+extern crate snap;
 
 #[macro_use]
 extern crate log;
@@ -52,31 +53,40 @@ extern crate substrate_test_client as test_client;
 #[cfg(test)]
 extern crate kvdb_memorydb;
 
+pub mod compression;
 pub mod light;
 
 mod cache;
+mod header_cache;
+mod migration;
+mod prefetch;
+mod trie_node_cache;
 mod utils;
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::path::PathBuf;
 use std::io;
 
-use client::backend::NewBlockState;
+use client::backend::{NewBlockState, CompactionProfile, MaintenanceProgress};
 use codec::{Decode, Encode};
 use hash_db::Hasher;
 use kvdb::{KeyValueDB, DBTransaction};
 use trie::MemoryDB;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
 use primitives::{H256, AuthorityId, Blake2Hasher, ChangesTrieConfiguration};
 use primitives::storage::well_known_keys;
 use runtime_primitives::{generic::BlockId, Justification, StorageMap, ChildrenStorageMap};
-use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, As, NumberFor, Zero, Digest, DigestItem};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, As, NumberFor, Zero, One, Digest, DigestItem};
 use runtime_primitives::BuildStorage;
 use state_machine::backend::Backend as StateBackend;
 use executor::RuntimeInfo;
 use state_machine::{CodeExecutor, DBValue, ExecutionStrategy};
-use utils::{Meta, db_err, meta_keys, open_database, read_db, block_id_to_lookup_key, read_meta};
+use utils::{Meta, db_err, meta_keys, open_database, ensure_writable, read_db, block_id_to_lookup_key, read_meta};
 use client::LeafSet;
+use client::memory_budget::MemoryBudget;
+use header_cache::{HeaderCache, DEFAULT_HEADER_CACHE_SIZE};
+use trie_node_cache::{TrieNodeCache, DEFAULT_TRIE_NODE_CACHE_SIZE};
 use state_db::StateDb;
 pub use state_db::PruningMode;
 
@@ -94,6 +104,33 @@ pub struct DatabaseSettings {
 	pub path: PathBuf,
 	/// Pruning mode.
 	pub pruning: PruningMode,
+	/// Open the existing database without writing to it: neither schema migrations nor pruning
+	/// are applied, and importing blocks or writing auxiliary data fails. Opening fails if the
+	/// database doesn't exist. Meant for the analytics processes running heavy queries on the
+	/// database of the running node: the checkpoint of the database is opened instead, because
+	/// RocksDB locks the database and `kvdb-rocksdb` has no read-only or secondary open.
+	pub read_only: bool,
+	/// Memory budget shared with the other caches of the node. The trie node and header caches are
+	/// shrunk when the budget is exceeded.
+	pub memory_budget: Option<MemoryBudget>,
+}
+
+/// Apply the schema migrations the existing database is missing, or only list them if `dry_run`
+/// is set. Returns the versions and descriptions of the migrations. Migrations are also applied
+/// when the database is opened.
+pub fn migrate_database(settings: &DatabaseSettings, dry_run: bool) -> Result<Vec<(u32, &'static str)>, client::error::Error> {
+	utils::migrate_database(settings, utils::COLUMN_META, dry_run)
+}
+
+/// Compact the database, reclaiming the space left by pruned data. The database is locked while
+/// it's open, so compaction fails if a node is running on it. Returns `false` if compaction has
+/// been aborted.
+pub fn compact_database(
+	settings: &DatabaseSettings,
+	profile: &CompactionProfile,
+	progress: &MaintenanceProgress,
+) -> Result<bool, client::error::Error> {
+	utils::compact_database(settings, profile, progress)
 }
 
 /// Create an instance of db-backed client.
@@ -125,6 +162,8 @@ mod columns {
 	pub const JUSTIFICATION: Option<u32> = Some(6);
 	pub const CHANGES_TRIE: Option<u32> = Some(7);
 	pub const AUX: Option<u32> = Some(8);
+	/// maps numbers of the best chain blocks to their hashes
+	pub const CANONICAL_INDEX: Option<u32> = Some(9);
 }
 
 struct PendingBlock<Block: BlockT> {
@@ -150,19 +189,32 @@ pub struct BlockchainDb<Block: BlockT> {
 	db: Arc<KeyValueDB>,
 	meta: RwLock<Meta<NumberFor<Block>, Block::Hash>>,
 	leaves: RwLock<LeafSet<Block::Hash, NumberFor<Block>>>,
+	gap: RwLock<Option<(NumberFor<Block>, NumberFor<Block>)>>,
+	header_cache: Mutex<HeaderCache<Block>>,
 }
 
 impl<Block: BlockT> BlockchainDb<Block> {
 	fn new(db: Arc<KeyValueDB>) -> Result<Self, client::error::Error> {
 		let meta = read_meta::<Block>(&*db, columns::META, columns::HEADER)?;
 		let leaves = LeafSet::read_from_db(&*db, columns::META, meta_keys::LEAF_PREFIX)?;
+		let gap = match db.get(columns::META, meta_keys::BLOCK_GAP).map_err(db_err)? {
+			Some(gap) => Some(Decode::decode(&mut &gap[..])
+				.ok_or_else(|| client::error::ErrorKind::Backend("Error decoding block gap".into()))?),
+			None => None,
+		};
 		Ok(BlockchainDb {
 			db,
 			leaves: RwLock::new(leaves),
 			meta: RwLock::new(meta),
+			gap: RwLock::new(gap),
+			header_cache: Mutex::new(HeaderCache::new(DEFAULT_HEADER_CACHE_SIZE)),
 		})
 	}
 
+	fn is_in_gap(&self, number: NumberFor<Block>) -> bool {
+		self.gap.read().map_or(false, |(start, end)| number >= start && number <= end)
+	}
+
 	fn update_meta(
 		&self,
 		hash: Block::Hash,
@@ -190,7 +242,20 @@ impl<Block: BlockT> BlockchainDb<Block> {
 
 impl<Block: BlockT> client::blockchain::HeaderBackend<Block> for BlockchainDb<Block> {
 	fn header(&self, id: BlockId<Block>) -> Result<Option<Block::Header>, client::error::Error> {
-		::utils::read_header(&*self.db, columns::HASH_LOOKUP, columns::HEADER, id)
+		// the number may point to another block after reorg, so only lookups by hash are cached
+		let hash = match id {
+			BlockId::Hash(hash) => hash,
+			BlockId::Number(_) => return ::utils::read_header(&*self.db, columns::HASH_LOOKUP, columns::HEADER, id),
+		};
+		if let Some(header) = self.header_cache.lock().get(&hash) {
+			return Ok(Some(header));
+		}
+
+		let header = ::utils::read_header(&*self.db, columns::HASH_LOOKUP, columns::HEADER, id)?;
+		if let Some(ref header) = header {
+			self.header_cache.lock().insert(hash, header.clone(), header.encode().len());
+		}
+		Ok(header)
 	}
 
 	fn info(&self) -> Result<client::blockchain::Info<Block>, client::error::Error> {
@@ -212,7 +277,7 @@ impl<Block: BlockT> client::blockchain::HeaderBackend<Block> for BlockchainDb<Bl
 				columns::HEADER,
 				id
 			)?.is_some(),
-			BlockId::Number(n) => n <= self.meta.read().best_number,
+			BlockId::Number(n) => n <= self.meta.read().best_number && !self.is_in_gap(n),
 		};
 		match exists {
 			true => Ok(client::blockchain::BlockStatus::InChain),
@@ -230,10 +295,7 @@ impl<Block: BlockT> client::blockchain::HeaderBackend<Block> for BlockchainDb<Bl
 	}
 
 	fn hash(&self, number: NumberFor<Block>) -> Result<Option<Block::Hash>, client::error::Error> {
-		self.header(BlockId::Number(number)).and_then(|maybe_header| match maybe_header {
-			Some(header) => Ok(Some(header.hash().clone())),
-			None => Ok(None),
-		})
+		utils::read_canonical_hash::<Block>(&*self.db, columns::CANONICAL_INDEX, number)
 	}
 }
 
@@ -269,6 +331,10 @@ impl<Block: BlockT> client::blockchain::Backend<Block> for BlockchainDb<Block> {
 	fn leaves(&self) -> Result<Vec<Block::Hash>, client::error::Error> {
 		Ok(self.leaves.read().hashes())
 	}
+
+	fn gap(&self) -> Result<Option<(NumberFor<Block>, NumberFor<Block>)>, client::error::Error> {
+		Ok(*self.gap.read())
+	}
 }
 
 /// Database transaction
@@ -353,12 +419,21 @@ where Block: BlockT<Hash=H256>,
 struct StorageDb<Block: BlockT> {
 	pub db: Arc<KeyValueDB>,
 	pub state_db: StateDb<Block::Hash, H256>,
+	pub node_cache: Mutex<TrieNodeCache>,
 }
 
 impl<Block: BlockT> state_machine::Storage<Blake2Hasher> for StorageDb<Block> {
 	fn get(&self, key: &H256) -> Result<Option<DBValue>, String> {
-		self.state_db.get(&key.0.into(), self).map(|r| r.map(|v| DBValue::from_slice(&v)))
-			.map_err(|e| format!("Database backend error: {:?}", e))
+		if let Some(node) = self.node_cache.lock().get(key) {
+			return Ok(Some(node));
+		}
+
+		let node = self.state_db.get(&key.0.into(), self).map(|r| r.map(|v| DBValue::from_slice(&v)))
+			.map_err(|e| format!("Database backend error: {:?}", e))?;
+		if let Some(ref node) = node {
+			self.node_cache.lock().insert(*key, node.clone());
+		}
+		Ok(node)
 	}
 }
 
@@ -367,7 +442,12 @@ impl<Block: BlockT> state_db::HashDb for StorageDb<Block> {
 	type Hash = H256;
 
 	fn get(&self, key: &H256) -> Result<Option<Vec<u8>>, Self::Error> {
-		self.db.get(columns::STATE, &key[..]).map(|r| r.map(|v| v.to_vec()))
+		match self.db.get(columns::STATE, &key[..])? {
+			Some(value) => compression::decode_node(value.to_vec())
+				.map(Some)
+				.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+			None => Ok(None),
+		}
 	}
 }
 
@@ -455,6 +535,13 @@ pub struct Backend<Block: BlockT> {
 	changes_tries_storage: DbChangesTrieStorage<Block>,
 	blockchain: BlockchainDb<Block>,
 	canonicalization_delay: u64,
+	read_only: bool,
+	path: Option<PathBuf>,
+	/// Held for reading by the writes and for writing by the maintenance.
+	maintenance_lock: RwLock<()>,
+	/// Number of writes waiting for the maintenance to complete.
+	pending_writes: AtomicUsize,
+	prefetch: prefetch::PrefetchWorker,
 }
 
 impl<Block: BlockT> Backend<Block> {
@@ -464,7 +551,58 @@ impl<Block: BlockT> Backend<Block> {
 	pub fn new(config: DatabaseSettings, canonicalization_delay: u64) -> Result<Self, client::error::Error> {
 		let db = open_database(&config, columns::META, "full")?;
 
-		Backend::from_kvdb(db as Arc<_>, config.pruning, canonicalization_delay)
+		let mut backend = Backend::from_kvdb(db as Arc<_>, config.pruning, canonicalization_delay)?;
+		backend.read_only = config.read_only;
+		backend.path = Some(config.path);
+		if let Some(ref budget) = config.memory_budget {
+			backend.storage.node_cache.lock().set_memory_budget(budget);
+			backend.blockchain.header_cache.lock().set_memory_budget(budget);
+		}
+		Ok(backend)
+	}
+
+	/// Check consistency of the database: every block of the best chain must be indexed by its
+	/// number and looked up by its hash, and no blocks above the best block may be indexed.
+	/// Returns the descriptions of the found inconsistencies.
+	pub fn check_integrity(&self) -> Result<Vec<String>, client::error::Error> {
+		use client::blockchain::HeaderBackend;
+
+		let best_number = self.blockchain.meta.read().best_number;
+		let mut issues = Vec::new();
+		for n in 0..best_number.as_() + 1 {
+			let number: NumberFor<Block> = As::sa(n);
+			if self.blockchain.is_in_gap(number) {
+				continue;
+			}
+
+			let indexed = utils::read_canonical_hash::<Block>(&*self.storage.db, columns::CANONICAL_INDEX, number)?;
+			let hash = match self.blockchain.header(BlockId::Number(number))? {
+				Some(header) => header.hash(),
+				None => {
+					issues.push(format!("Best chain block #{} is missing", number));
+					continue;
+				},
+			};
+			match indexed {
+				Some(indexed) if indexed != hash => issues.push(format!(
+					"Block #{} is indexed as {:?}, but the best chain block is {:?}", number, indexed, hash
+				)),
+				Some(_) => (),
+				None => issues.push(format!("Best chain block #{} ({:?}) is not indexed", number, hash)),
+			}
+			if self.blockchain.number(hash)? != Some(number) {
+				issues.push(format!("Best chain block #{} ({:?}) is not looked up by its hash", number, hash));
+			}
+		}
+
+		for (key, _) in self.storage.db.iter(columns::CANONICAL_INDEX) {
+			let number: NumberFor<Block> = utils::lookup_key_to_number(&key)?;
+			if number > best_number {
+				issues.push(format!("Block #{} above the best block #{} is indexed", number, best_number));
+			}
+		}
+
+		Ok(issues)
 	}
 
 	#[cfg(test)]
@@ -488,6 +626,7 @@ impl<Block: BlockT> Backend<Block> {
 		let storage_db = StorageDb {
 			db: db.clone(),
 			state_db,
+			node_cache: Mutex::new(TrieNodeCache::new(DEFAULT_TRIE_NODE_CACHE_SIZE)),
 		};
 		let changes_tries_storage = DbChangesTrieStorage {
 			db,
@@ -500,9 +639,24 @@ impl<Block: BlockT> Backend<Block> {
 			changes_tries_storage,
 			blockchain,
 			canonicalization_delay,
+			read_only: false,
+			path: None,
+			maintenance_lock: RwLock::new(()),
+			pending_writes: AtomicUsize::new(0),
+			prefetch: prefetch::PrefetchWorker::new(),
 		})
 	}
 
+	/// Check that the database is writable and wait until the maintenance completes. The maintenance
+	/// is aborted while the write is waiting.
+	fn lock_for_write(&self) -> Result<RwLockReadGuard<()>, client::error::Error> {
+		ensure_writable(self.read_only)?;
+		self.pending_writes.fetch_add(1, AtomicOrdering::SeqCst);
+		let guard = self.maintenance_lock.read();
+		self.pending_writes.fetch_sub(1, AtomicOrdering::SeqCst);
+		Ok(guard)
+	}
+
 	// performs forced canonicaliziation with a delay after importning a non-finalized block.
 	fn force_delayed_canonicalize(
 		&self,
@@ -516,7 +670,8 @@ impl<Block: BlockT> Backend<Block> {
 		if number_u64 > self.canonicalization_delay {
 			let new_canonical = number_u64 - self.canonicalization_delay;
 
-			if new_canonical <= self.storage.state_db.best_canonical() {
+			// states of blocks in the gap are never imported
+			if new_canonical <= self.storage.state_db.best_canonical() || self.blockchain.is_in_gap(As::sa(new_canonical)) {
 				return Ok(())
 			}
 
@@ -575,11 +730,57 @@ impl<Block: BlockT> Backend<Block> {
 
 		Ok(())
 	}
+
+	// write block of the gap, which is the parent of the lowest block above the gap.
+	fn fill_gap(
+		&self,
+		pending_block: PendingBlock<Block>,
+		gap: (NumberFor<Block>, NumberFor<Block>),
+	) -> Result<(), client::error::Error> {
+		let (gap_start, gap_end) = gap;
+		let hash = pending_block.header.hash();
+		let number = *pending_block.header.number();
+
+		let above_gap = ::client::blockchain::HeaderBackend::<Block>::header(&self.blockchain, BlockId::Number(gap_end + One::one()))?
+			.ok_or_else(|| client::error::ErrorKind::UnknownBlock(format!("above the gap #{}", gap_end + One::one())))?;
+		if number != gap_end || hash != *above_gap.parent_hash() {
+			return Err(client::error::ErrorKind::Backend(
+				format!("Block #{} ({:?}) is not the next block of the gap [{}..{}]", number, hash, gap_start, gap_end)
+			).into());
+		}
+
+		let mut transaction = DBTransaction::new();
+		let lookup_key = ::utils::number_to_lookup_key(number);
+		transaction.put(columns::HEADER, &lookup_key, &pending_block.header.encode());
+		if let Some(body) = pending_block.body {
+			transaction.put(columns::BODY, &lookup_key, &body.encode());
+		}
+		if let Some(justification) = pending_block.justification {
+			transaction.put(columns::JUSTIFICATION, &lookup_key, &justification.encode());
+		}
+		transaction.put(columns::HASH_LOOKUP, hash.as_ref(), &lookup_key);
+		transaction.put(columns::CANONICAL_INDEX, &lookup_key, hash.as_ref());
+
+		let new_gap = if gap_start == gap_end {
+			transaction.delete(columns::META, meta_keys::BLOCK_GAP);
+			None
+		} else {
+			let new_gap = (gap_start, gap_end - One::one());
+			transaction.put(columns::META, meta_keys::BLOCK_GAP, &new_gap.encode());
+			Some(new_gap)
+		};
+
+		debug!(target: "db", "DB Commit gap block {:?} ({})", hash, number);
+		self.storage.db.write(transaction).map_err(db_err)?;
+		*self.blockchain.gap.write() = new_gap;
+
+		Ok(())
+	}
 }
 
 fn apply_state_commit(transaction: &mut DBTransaction, commit: state_db::CommitSet<H256>) {
 	for (key, val) in commit.data.inserted.into_iter() {
-		transaction.put(columns::STATE, &key[..], &val);
+		transaction.put(columns::STATE, &key[..], &compression::encode_node(&val, compression::COMPRESSION_THRESHOLD));
 	}
 	for key in commit.data.deleted.into_iter() {
 		transaction.delete(columns::STATE, &key[..]);
@@ -611,9 +812,18 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 	fn commit_operation(&self, mut operation: Self::BlockImportOperation)
 		-> Result<(), client::error::Error>
 	{
+		let _write = self.lock_for_write()?;
 		let mut transaction = DBTransaction::new();
 
 		if let Some(pending_block) = operation.pending_block {
+			let gap = *self.blockchain.gap.read();
+			if let Some((gap_start, gap_end)) = gap {
+				let number = *pending_block.header.number();
+				if number >= gap_start && number <= gap_end {
+					return self.fill_gap(pending_block, (gap_start, gap_end));
+				}
+			}
+
 			let hash = pending_block.header.hash();
 			let parent_hash = *pending_block.header.parent_hash();
 			let number = pending_block.header.number().clone();
@@ -626,11 +836,28 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 				::utils::number_and_hash_to_lookup_key(number, hash)
 			};
 
+			let mut new_gap = None;
 			if pending_block.leaf_state.is_best() {
 				let meta = self.blockchain.meta.read();
 
-				// cannot find tree route with empty DB.
-				if meta.best_hash != Default::default() {
+				// the chain continues from the block that is not a descendant of the best block
+				// (e.g. imported along with its state), so blocks in between are missing.
+				let opens_gap = meta.best_hash != Default::default()
+					&& number > meta.best_number + One::one()
+					&& ::client::blockchain::HeaderBackend::<Block>::header(&self.blockchain, BlockId::Hash(parent_hash))?.is_none();
+
+				if opens_gap {
+					if gap.is_some() {
+						return Err(client::error::ErrorKind::Backend(
+							format!("Cannot import #{} before the block gap is filled", number)
+						).into());
+					}
+
+					let gap = (meta.best_number + One::one(), number - One::one());
+					transaction.put(columns::META, meta_keys::BLOCK_GAP, &gap.encode());
+					new_gap = Some(gap);
+				} else if meta.best_hash != Default::default() {
+					// cannot find tree route with empty DB.
 					let tree_route = ::client::blockchain::tree_route(
 						&self.blockchain,
 						BlockId::Hash(meta.best_hash),
@@ -640,7 +867,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 					// uncanonicalize
 					for retracted in tree_route.retracted() {
 						if retracted.hash == meta.finalized_hash {
-							warn!("Potential safety failure: reverting finalized block {:?}",
+							warn!(target: "db", "Potential safety failure: reverting finalized block {:?}",
 								(&retracted.number, &retracted.hash));
 
 							return Err(::client::error::ErrorKind::NotInFinalizedChain.into());
@@ -676,6 +903,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 						}
 
 						transaction.put(columns::HASH_LOOKUP, retracted.hash.as_ref(), &new_lookup_key);
+						transaction.delete(columns::CANONICAL_INDEX, &prev_lookup_key);
 					}
 
 					// canonicalize
@@ -710,10 +938,12 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 						}
 
 						transaction.put(columns::HASH_LOOKUP, enacted.hash.as_ref(), &new_lookup_key);
+						transaction.put(columns::CANONICAL_INDEX, &new_lookup_key, enacted.hash.as_ref());
 					}
 				}
 
 				transaction.put(columns::META, meta_keys::BEST_BLOCK, &lookup_key);
+				transaction.put(columns::CANONICAL_INDEX, &lookup_key, hash.as_ref());
 			}
 
 			transaction.put(columns::HEADER, &lookup_key, &pending_block.header.encode());
@@ -777,6 +1007,10 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 				drop(leaves);
 			}
 
+			if new_gap.is_some() {
+				*self.blockchain.gap.write() = new_gap;
+			}
+
 			self.blockchain.update_meta(
 				hash.clone(),
 				number.clone(),
@@ -787,14 +1021,21 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 		Ok(())
 	}
 
-	fn finalize_block(&self, block: BlockId<Block>) -> Result<(), client::error::Error> {
+	fn finalize_block(&self, block: BlockId<Block>, justification: Option<Justification>) -> Result<(), client::error::Error> {
 		use runtime_primitives::traits::Header;
 
+		let _write = self.lock_for_write()?;
+
 		if let Some(header) = ::client::blockchain::HeaderBackend::header(&self.blockchain, block)? {
 			let mut transaction = DBTransaction::new();
 			// TODO: ensure best chain contains this block.
 			let hash = header.hash();
 			self.note_finalized(&mut transaction, &header, hash.clone())?;
+			if let Some(justification) = justification {
+				let lookup_key = block_id_to_lookup_key::<Block>(&*self.storage.db, columns::HASH_LOOKUP, BlockId::Hash(hash))?
+					.ok_or_else(|| client::error::ErrorKind::UnknownBlock(format!("Cannot finalize block {:?}", block)))?;
+				transaction.put(columns::JUSTIFICATION, &lookup_key, &justification.encode());
+			}
 			self.storage.db.write(transaction).map_err(db_err)?;
 			self.blockchain.update_meta(hash, header.number().clone(), false, true);
 			Ok(())
@@ -807,8 +1048,22 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 		Some(&self.changes_tries_storage)
 	}
 
+	fn changes_tries_blocks_to_keep(&self) -> Option<u64> {
+		self.changes_tries_storage.min_blocks_to_keep
+	}
+
+	fn insert_changes_trie(&self, _block: BlockId<Block>, update: MemoryDB<Blake2Hasher>) -> Result<(), client::error::Error> {
+		let _write = self.lock_for_write()?;
+		let mut transaction = DBTransaction::new();
+		self.changes_tries_storage.commit(&mut transaction, update);
+		self.storage.db.write(transaction).map_err(db_err)?;
+		Ok(())
+	}
+
 	fn revert(&self, n: NumberFor<Block>) -> Result<NumberFor<Block>, client::error::Error> {
 		use client::blockchain::HeaderBackend;
+
+		let _write = self.lock_for_write()?;
 		let mut best = self.blockchain.info()?.best_number;
 		for c in 0 .. n.as_() {
 			if best == As::sa(0) {
@@ -818,7 +1073,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 			match self.storage.state_db.revert_one() {
 				Some(commit) => {
 					apply_state_commit(&mut transaction, commit);
-					let _removed = best.clone();
+					let removed = best.clone();
 					best -= As::sa(1);
 					let header = self.blockchain.header(BlockId::Number(best))?.ok_or_else(
 						|| client::error::ErrorKind::UnknownBlock(
@@ -827,7 +1082,9 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 					let lookup_key = ::utils::number_to_lookup_key(header.number().clone());
 					transaction.put(columns::META, meta_keys::BEST_BLOCK, &lookup_key);
 					transaction.delete(columns::HASH_LOOKUP, header.hash().as_ref());
+					transaction.delete(columns::CANONICAL_INDEX, &::utils::number_to_lookup_key(removed));
 					self.storage.db.write(transaction).map_err(db_err)?;
+					self.blockchain.header_cache.lock().clear();
 					self.blockchain.update_meta(header.hash().clone(), best.clone(), true, false);
 					self.blockchain.leaves.write().revert(header.hash().clone(), header.number().clone(), header.parent_hash().clone());
 				}
@@ -867,6 +1124,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 	fn insert_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a (&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>
 		(&self, insert: I, delete: D) -> Result<(), client::error::Error>
 	{
+		let _write = self.lock_for_write()?;
 		let mut transaction = DBTransaction::new();
 		for (k, v) in insert {
 			transaction.put(columns::AUX, k, v);
@@ -881,6 +1139,61 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 	fn get_aux(&self, key: &[u8]) -> Result<Option<Vec<u8>>, client::error::Error> {
 		Ok(self.storage.db.get(columns::AUX, key).map(|r| r.map(|v| v.to_vec())).map_err(db_err)?)
 	}
+
+	fn prefetch_state(&self, block: BlockId<Block>, keys: &[Vec<u8>]) -> Result<(), client::error::Error> {
+		if keys.is_empty() {
+			return Ok(());
+		}
+
+		let root = *self.state_at(block)?.root();
+		self.prefetch.prefetch(|| DbState::new(self.storage.clone(), root), keys);
+		Ok(())
+	}
+
+	fn maintenance(&self, profile: CompactionProfile, progress: &MaintenanceProgress) -> Result<bool, client::error::Error> {
+		struct AbortOnWrite<'a> {
+			progress: &'a MaintenanceProgress,
+			pending_writes: &'a AtomicUsize,
+		}
+
+		impl<'a> MaintenanceProgress for AbortOnWrite<'a> {
+			fn on_progress(&self, column: u32, columns: u32, entries: u64) -> bool {
+				self.pending_writes.load(AtomicOrdering::SeqCst) == 0
+					&& self.progress.on_progress(column, columns, entries)
+			}
+		}
+
+		ensure_writable(self.read_only)?;
+		let path = match self.path {
+			Some(ref path) => path,
+			None => return Ok(true),
+		};
+
+		// writes are blocked until the compacted copy replaces the database, while reads go on,
+		// since the content doesn't change. Only the reads issued while the database files are
+		// swapped find no data
+		let _maintenance = self.maintenance_lock.write();
+		utils::compact_open_database(&*self.storage.db, path, &profile, &AbortOnWrite {
+			progress,
+			pending_writes: &self.pending_writes,
+		})
+	}
+
+	fn pin_block(&self, hash: &Block::Hash) -> Result<(), client::error::Error> {
+		use client::blockchain::HeaderBackend as BcHeaderBackend;
+
+		match self.blockchain.header(BlockId::Hash(*hash))? {
+			Some(ref hdr) if !self.storage.state_db.is_pruned(hdr.number().as_()) => {
+				self.storage.state_db.pin(hash);
+				Ok(())
+			},
+			_ => Err(client::error::ErrorKind::UnknownBlock(format!("{:?}", hash)).into()),
+		}
+	}
+
+	fn unpin_block(&self, hash: &Block::Hash) {
+		self.storage.state_db.unpin(hash);
+	}
 }
 
 impl<Block> client::backend::LocalBackend<Block, Blake2Hasher> for Backend<Block>
@@ -1001,6 +1314,36 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn prefetch_state_warms_trie_node_cache() {
+		let db = Backend::<Block>::new_test(2, 0);
+		let mut op = db.begin_operation(BlockId::Hash(Default::default())).unwrap();
+		let mut header = Header {
+			number: 0,
+			parent_hash: Default::default(),
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+
+		let storage = vec![
+			(vec![1, 3, 5], vec![2, 4, 6]),
+			(vec![1, 2, 3], vec![9, 9, 9]),
+		];
+		header.state_root = op.old_state.storage_root(storage
+			.iter()
+			.cloned()
+			.map(|(x, y)| (x, Some(y)))
+		).0.into();
+		op.reset_storage(storage.iter().cloned().collect(), Default::default()).unwrap();
+		op.set_block_data(header.clone(), Some(vec![]), None, NewBlockState::Best).unwrap();
+		db.commit_operation(op).unwrap();
+
+		*db.storage.node_cache.lock() = TrieNodeCache::new(DEFAULT_TRIE_NODE_CACHE_SIZE);
+		db.prefetch_state(BlockId::Number(0), &[vec![1, 3, 5], vec![1, 2, 3]]).unwrap();
+		assert!(db.storage.node_cache.lock().get(&header.state_root).is_some());
+	}
+
 	#[test]
 	fn set_state_data() {
 		let db = Backend::<Block>::new_test(2, 0);
@@ -1081,6 +1424,49 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn large_state_values_are_compressed() {
+		let backend = Backend::<Block>::new_test(0, 0);
+		let large_value: Vec<u8> = (0..compression::COMPRESSION_THRESHOLD * 4).map(|i| i as u8).collect();
+
+		let mut op = backend.begin_operation(BlockId::Hash(Default::default())).unwrap();
+		let mut header = Header {
+			number: 0,
+			parent_hash: Default::default(),
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+
+		let storage = vec![
+			(vec![1, 3, 5], vec![2, 4, 6]),
+			(vec![1, 2, 3], large_value.clone()),
+		];
+
+		header.state_root = op.old_state.storage_root(storage
+			.iter()
+			.cloned()
+			.map(|(x, y)| (x, Some(y)))
+		).0.into();
+
+		op.reset_storage(storage.iter().cloned().collect(), Default::default()).unwrap();
+		op.set_block_data(
+			header,
+			Some(vec![]),
+			None,
+			NewBlockState::Best,
+		).unwrap();
+
+		backend.commit_operation(op).unwrap();
+
+		assert!(backend.storage.db.iter(::columns::STATE)
+			.any(|(_, value)| value.len() > 1 && value[0] == 0 && value.len() < large_value.len()));
+
+		let state = backend.state_at(BlockId::Number(0)).unwrap();
+		assert_eq!(state.storage(&[1, 3, 5]).unwrap(), Some(vec![2, 4, 6]));
+		assert_eq!(state.storage(&[1, 2, 3]).unwrap(), Some(large_value));
+	}
+
 	#[test]
 	fn delete_only_when_negative_rc() {
 		let key;
@@ -1186,8 +1572,8 @@ mod tests {
 			assert!(backend.storage.db.get(::columns::STATE, &key.0[..]).unwrap().is_none());
 		}
 
-		backend.finalize_block(BlockId::Number(1)).unwrap();
-		backend.finalize_block(BlockId::Number(2)).unwrap();
+		backend.finalize_block(BlockId::Number(1), None).unwrap();
+		backend.finalize_block(BlockId::Number(2), None).unwrap();
 		assert!(backend.storage.db.get(::columns::STATE, &key.0[..]).unwrap().is_none());
 	}
 
@@ -1372,6 +1758,173 @@ mod tests {
 		test_client::trait_tests::test_blockchain_query_by_number_gets_canonical(backend);
 	}
 
+	#[test]
+	fn import_simulation_matches_in_memory_backend() {
+		use test_client::import_simulation::{canned_blocks, event_log, simulate_import, ImportMode};
+
+		let blocks = canned_blocks();
+		let backend: Arc<Backend<test_client::runtime::Block>> = Arc::new(Backend::new_test(20, 20));
+		let client = test_client::new_with_backend(backend, false);
+		let events = simulate_import(&client, &blocks, ImportMode::Full).unwrap();
+		let in_mem_events = simulate_import(&test_client::new(), &blocks, ImportMode::Full).unwrap();
+		assert_eq!(event_log(&events), event_log(&in_mem_events));
+	}
+
+	#[test]
+	fn changes_tries_are_backfilled_within_state_pruning_window() {
+		use test_client::TestClient;
+		use test_client::consensus::BlockOrigin;
+
+		let remote_client = test_client::new_with_changes_trie();
+		let blocks: Vec<_> = (0..6).map(|_| {
+			let block = remote_client.new_block().unwrap().bake().unwrap();
+			remote_client.justify_and_import(BlockOrigin::Own, block.clone()).unwrap();
+			block
+		}).collect();
+
+		let backend: Arc<Backend<test_client::runtime::Block>> = Arc::new(Backend::new_test(2, 0));
+		let client = test_client::new_with_backend(backend, true);
+		client.set_skip_changes_tries_on_sync(true);
+		for block in blocks {
+			client.justify_and_import(BlockOrigin::NetworkInitialSync, block).unwrap();
+		}
+
+		// only tries of the blocks whose parent state is kept are built, the rest is dropped.
+		// the digest trie of block #4 is built from the tries of blocks #1..#3
+		let parent_state_kept = |number: u64| client.state_at(&BlockId::Number(number - 1)).is_ok();
+		let buildable = (1..7u64)
+			.filter(|&number| parent_state_kept(number) && (number != 4 || (1..4).all(|input| parent_state_kept(input))))
+			.count() as u64;
+		assert!(buildable > 0 && buildable < 6);
+		assert_eq!(client.backfill_changes_tries(16).unwrap(), buildable);
+		assert_eq!(client.backfill_changes_tries(16).unwrap(), 0);
+	}
+
+	#[test]
+	fn pinned_block_state_is_not_pruned() {
+		let backend = Backend::<Block>::new_test(1, 0);
+		let block0 = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+		let block1 = insert_header(&backend, 1, block0, Vec::new(), Default::default());
+		backend.pin_block(&block1).unwrap();
+		backend.pin_block(&block1).unwrap();
+
+		let block2 = insert_header(&backend, 2, block1, Vec::new(), Default::default());
+		let block3 = insert_header(&backend, 3, block2, Vec::new(), Default::default());
+		assert!(backend.state_at(BlockId::Hash(block1)).is_ok());
+
+		backend.unpin_block(&block1);
+		let block4 = insert_header(&backend, 4, block3, Vec::new(), Default::default());
+		assert!(backend.state_at(BlockId::Hash(block1)).is_ok());
+
+		backend.unpin_block(&block1);
+		insert_header(&backend, 5, block4, Vec::new(), Default::default());
+		assert!(backend.state_at(BlockId::Hash(block1)).is_err());
+		assert!(backend.pin_block(&block1).is_err());
+	}
+
+	#[test]
+	fn block_gap_is_tracked_until_filled() {
+		use client::blockchain::{Backend as BlockchainBackend, BlockStatus};
+		use utils::NUM_COLUMNS;
+
+		let db = Arc::new(::kvdb_memorydb::create(NUM_COLUMNS));
+		let backend = Backend::<Block>::from_kvdb(db.clone() as Arc<_>, PruningMode::ArchiveAll, 0).unwrap();
+		let import = |header: &Header| {
+			let mut op = backend.begin_operation(BlockId::Hash(Default::default())).unwrap();
+			op.set_block_data(header.clone(), None, None, NewBlockState::Best).unwrap();
+			backend.commit_operation(op)
+		};
+
+		let mut headers: Vec<Header> = Vec::new();
+		for number in 0..6 {
+			let parent_hash = headers.last().map(|h| h.hash()).unwrap_or_default();
+			headers.push(Header {
+				number,
+				parent_hash,
+				state_root: Default::default(),
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			});
+		}
+
+		// chain is continued from #4, so #2 and #3 are missing
+		import(&headers[0]).unwrap();
+		import(&headers[1]).unwrap();
+		import(&headers[4]).unwrap();
+		import(&headers[5]).unwrap();
+		assert_eq!(backend.blockchain().gap().unwrap(), Some((2, 3)));
+		assert_eq!(backend.blockchain().info().unwrap().best_hash, headers[5].hash());
+		assert_eq!(backend.blockchain().status(BlockId::Number(2)).unwrap(), BlockStatus::Unknown);
+		assert_eq!(backend.blockchain().status(BlockId::Number(4)).unwrap(), BlockStatus::InChain);
+		assert_eq!(BlockchainDb::<Block>::new(db.clone() as Arc<_>).unwrap().gap().unwrap(), Some((2, 3)));
+
+		// gap is filled in reverse order
+		assert!(import(&headers[2]).is_err());
+		import(&headers[3]).unwrap();
+		assert_eq!(backend.blockchain().gap().unwrap(), Some((2, 2)));
+		import(&headers[2]).unwrap();
+		assert_eq!(backend.blockchain().gap().unwrap(), None);
+		assert_eq!(BlockchainDb::<Block>::new(db as Arc<_>).unwrap().gap().unwrap(), None);
+
+		assert_eq!(backend.blockchain().hash(2).unwrap(), Some(headers[2].hash()));
+		assert_eq!(backend.blockchain().number(headers[3].hash()).unwrap(), Some(3));
+		assert_eq!(backend.blockchain().info().unwrap().best_hash, headers[5].hash());
+	}
+
+	#[test]
+	fn canonical_index_follows_reorgs() {
+		let backend = Backend::<Block>::new_test(0, 0);
+		let block0 = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+		let block1 = insert_header(&backend, 1, block0, Vec::new(), Default::default());
+		let block2 = insert_header(&backend, 2, block1, Vec::new(), Default::default());
+		let block3 = insert_header(&backend, 3, block2, Vec::new(), Default::default());
+		assert_eq!(backend.blockchain().hash(3).unwrap(), Some(block3));
+
+		// fork at #2 becomes the best chain
+		let fork2 = insert_header(&backend, 2, block1, Vec::new(), H256::from([2; 32]));
+		assert_eq!(backend.blockchain().hash(1).unwrap(), Some(block1));
+		assert_eq!(backend.blockchain().hash(2).unwrap(), Some(fork2));
+		assert_eq!(backend.blockchain().hash(3).unwrap(), None);
+		assert!(backend.check_integrity().unwrap().is_empty());
+
+		// corrupted index is reported
+		let mut transaction = DBTransaction::new();
+		transaction.put(columns::CANONICAL_INDEX, &::utils::number_to_lookup_key(2u64), block2.as_ref());
+		transaction.put(columns::CANONICAL_INDEX, &::utils::number_to_lookup_key(3u64), block3.as_ref());
+		transaction.delete(columns::CANONICAL_INDEX, &::utils::number_to_lookup_key(1u64));
+		backend.storage.db.write(transaction).unwrap();
+		assert_eq!(backend.check_integrity().unwrap().len(), 3);
+	}
+
+	#[test]
+	fn canonical_index_is_built_by_migration() {
+		use migration::{migrate, migrations};
+
+		let backend = Backend::<Block>::new_test(0, 0);
+		let mut hashes = vec![insert_header(&backend, 0, Default::default(), Vec::new(), Default::default())];
+		for number in 1..4 {
+			let parent = hashes[number as usize - 1];
+			hashes.push(insert_header(&backend, number, parent, Vec::new(), Default::default()));
+		}
+		let fork2 = insert_header(&backend, 2, hashes[1], Vec::new(), H256::from([2; 32]));
+		let fork3 = insert_header(&backend, 3, fork2, Vec::new(), H256::from([3; 32]));
+
+		// database of the previous version
+		let db = backend.storage.db.clone();
+		let mut transaction = DBTransaction::new();
+		for (key, _) in db.iter(columns::CANONICAL_INDEX) {
+			transaction.delete(columns::CANONICAL_INDEX, &key);
+		}
+		transaction.put(columns::META, meta_keys::DB_VERSION, &1u32.encode());
+		db.write(transaction).unwrap();
+		assert_eq!(backend.check_integrity().unwrap().len(), 4);
+
+		assert_eq!(migrate(&*db, columns::META, migrations("full").unwrap(), false).unwrap().len(), 1);
+		assert!(backend.check_integrity().unwrap().is_empty());
+		assert_eq!(backend.blockchain().hash(0).unwrap(), Some(hashes[0]));
+		assert_eq!(backend.blockchain().hash(3).unwrap(), Some(fork3));
+	}
+
 	#[test]
 	fn test_aux() {
 		let backend: Backend<test_client::runtime::Block> = Backend::new_test(0, 0);
@@ -1381,4 +1934,153 @@ mod tests {
 		backend.insert_aux(&[], &[&b"test"[..]]).unwrap();
 		assert!(backend.get_aux(b"test").unwrap().is_none());
 	}
+
+	#[test]
+	fn read_only_backend_rejects_writes() {
+		use utils::ReadOnlyDb;
+
+		let backend = Backend::<Block>::new_test(0, 0);
+		let genesis = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+		backend.insert_aux(&[(&b"test"[..], &b"hello"[..])], &[]).unwrap();
+
+		let db = Arc::new(ReadOnlyDb::new(backend.storage.db.clone()));
+		assert!(db.write(DBTransaction::new()).is_err());
+		let mut read_only = Backend::<Block>::from_kvdb(db as Arc<_>, PruningMode::keep_blocks(0), 0).unwrap();
+		read_only.read_only = true;
+		assert_eq!(read_only.blockchain().hash(0).unwrap(), Some(genesis));
+		assert_eq!(read_only.get_aux(b"test").unwrap(), Some(b"hello".to_vec()));
+
+		assert!(read_only.insert_aux(&[], &[&b"test"[..]]).is_err());
+		assert!(read_only.finalize_block(BlockId::Number(0), None).is_err());
+		let mut op = read_only.begin_operation(BlockId::Number(0)).unwrap();
+		let header = Header {
+			number: 1,
+			parent_hash: genesis,
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		op.set_block_data(header, None, None, NewBlockState::Best).unwrap();
+		assert!(read_only.commit_operation(op).is_err());
+		assert_eq!(read_only.blockchain().info().unwrap().best_number, 0);
+		assert_eq!(backend.get_aux(b"test").unwrap(), Some(b"hello".to_vec()));
+	}
+
+	#[test]
+	fn missing_database_is_not_created_in_read_only_mode() {
+		let path = ::std::env::temp_dir().join(format!("substrate-missing-read-only-db-{}", ::std::process::id()));
+		let settings = DatabaseSettings {
+			cache_size: None,
+			path: path.clone(),
+			pruning: PruningMode::ArchiveAll,
+			read_only: true,
+			memory_budget: None,
+		};
+		assert!(Backend::<Block>::new(settings, 0).is_err());
+		assert!(!path.exists());
+	}
+
+	#[test]
+	fn database_is_compacted_only_when_not_in_use() {
+		struct Complete;
+
+		impl MaintenanceProgress for Complete {
+			fn on_progress(&self, _column: u32, _columns: u32, _entries: u64) -> bool {
+				true
+			}
+		}
+
+		let path = ::std::env::temp_dir().join(format!("substrate-compacted-db-{}", ::std::process::id()));
+		let settings = || DatabaseSettings {
+			cache_size: None,
+			path: path.clone(),
+			pruning: PruningMode::ArchiveAll,
+			read_only: false,
+			memory_budget: None,
+		};
+		let profile = CompactionProfile::default();
+		assert!(compact_database(&settings(), &profile, &Complete).is_err());
+
+		let backend = Backend::<Block>::new(settings(), 0).unwrap();
+		backend.insert_aux(&[(&b"test"[..], &b"hello"[..])], &[]).unwrap();
+		assert!(compact_database(&settings(), &profile, &Complete).is_err());
+		drop(backend);
+
+		assert!(compact_database(&settings(), &profile, &Complete).unwrap());
+		let backend = Backend::<Block>::new(settings(), 0).unwrap();
+		assert_eq!(backend.get_aux(b"test").unwrap(), Some(b"hello".to_vec()));
+		drop(backend);
+		::std::fs::remove_dir_all(&path).unwrap();
+	}
+
+	#[test]
+	fn database_in_use_is_compacted_by_maintenance() {
+		struct Progress(bool);
+
+		impl MaintenanceProgress for Progress {
+			fn on_progress(&self, _column: u32, _columns: u32, _entries: u64) -> bool {
+				self.0
+			}
+		}
+
+		let path = ::std::env::temp_dir().join(format!("substrate-maintained-db-{}", ::std::process::id()));
+		let backend = Backend::<Block>::new(DatabaseSettings {
+			cache_size: None,
+			path: path.clone(),
+			pruning: PruningMode::ArchiveAll,
+			read_only: false,
+			memory_budget: None,
+		}, 0).unwrap();
+		backend.insert_aux(&[(&b"test"[..], &b"hello"[..])], &[]).unwrap();
+		let profile = CompactionProfile { batch_size: 1, batch_delay: None };
+
+		// aborted by the observer
+		assert!(!backend.maintenance(profile.clone(), &Progress(false)).unwrap());
+		assert!(!path.with_extension("compacted").exists());
+
+		// aborted by the waiting write
+		backend.pending_writes.fetch_add(1, AtomicOrdering::SeqCst);
+		assert!(!backend.maintenance(profile.clone(), &Progress(true)).unwrap());
+		backend.pending_writes.fetch_sub(1, AtomicOrdering::SeqCst);
+
+		// the compacted database replaces the open one and is written to afterwards
+		assert!(backend.maintenance(profile, &Progress(true)).unwrap());
+		assert_eq!(backend.get_aux(b"test").unwrap(), Some(b"hello".to_vec()));
+		backend.insert_aux(&[(&b"test2"[..], &b"world"[..])], &[]).unwrap();
+		assert_eq!(backend.get_aux(b"test2").unwrap(), Some(b"world".to_vec()));
+		drop(backend);
+		::std::fs::remove_dir_all(&path).unwrap();
+	}
+
+	#[test]
+	fn copy_columns_moves_all_entries_unless_aborted() {
+		use std::cell::Cell;
+		use utils::{copy_columns, NUM_COLUMNS};
+
+		struct AbortAfter(Cell<usize>);
+
+		impl MaintenanceProgress for AbortAfter {
+			fn on_progress(&self, _column: u32, _columns: u32, _entries: u64) -> bool {
+				let remaining = self.0.get();
+				self.0.set(remaining.saturating_sub(1));
+				remaining > 1
+			}
+		}
+
+		let from = ::kvdb_memorydb::create(NUM_COLUMNS);
+		let mut transaction = DBTransaction::new();
+		for i in 0..5u8 {
+			transaction.put(columns::AUX, &[i], &[i]);
+		}
+		from.write(transaction).unwrap();
+		let profile = CompactionProfile { batch_size: 2, batch_delay: None };
+
+		let to = ::kvdb_memorydb::create(NUM_COLUMNS);
+		assert!(!copy_columns(&from, &to, &profile, &AbortAfter(Cell::new(1))).unwrap());
+		assert_eq!(to.iter(columns::AUX).count(), 2);
+
+		let to = ::kvdb_memorydb::create(NUM_COLUMNS);
+		assert!(copy_columns(&from, &to, &profile, &AbortAfter(Cell::new(usize::max_value()))).unwrap());
+		assert_eq!(to.iter(columns::AUX).collect::<Vec<_>>(), from.iter(columns::AUX).collect::<Vec<_>>());
+	}
 }
@@ -26,14 +26,16 @@ use client::blockchain::{BlockStatus, Cache as BlockchainCache,
 	HeaderBackend as BlockchainHeaderBackend, Info as BlockchainInfo};
 use client::{cht, LeafSet};
 use client::error::{ErrorKind as ClientErrorKind, Result as ClientResult};
-use client::light::blockchain::Storage as LightBlockchainStorage;
+use client::light::backend::LATEST_JUSTIFICATION_KEY;
+use client::light::blockchain::{HeadersPruning, Storage as LightBlockchainStorage};
 use codec::{Decode, Encode};
 use primitives::{AuthorityId, Blake2Hasher};
+use runtime_primitives::Justification;
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT,
 	Zero, One, As, NumberFor};
 use cache::{DbCacheSync, DbCache, ComplexBlockId};
-use utils::{meta_keys, Meta, db_err, number_to_lookup_key, open_database,
+use utils::{meta_keys, Meta, db_err, number_to_lookup_key, open_database, ensure_writable,
 	read_db, block_id_to_lookup_key, read_meta};
 use DatabaseSettings;
 
@@ -43,6 +45,8 @@ pub(crate) mod columns {
 	pub const HEADER: Option<u32> = Some(2);
 	pub const CACHE: Option<u32> = Some(3);
 	pub const CHT: Option<u32> = Some(4);
+	pub const AUX: Option<u32> = Some(5);
+	pub const JUSTIFICATION: Option<u32> = Some(6);
 }
 
 /// Light blockchain storage. Stores most recent headers + CHTs for older headers.
@@ -52,6 +56,25 @@ pub struct LightStorage<Block: BlockT> {
 	meta: RwLock<Meta<<<Block as BlockT>::Header as HeaderT>::Number, Block::Hash>>,
 	leaves: RwLock<LeafSet<Block::Hash, NumberFor<Block>>>,
 	cache: DbCacheSync<Block>,
+	headers_pruning: RwLock<HeadersPruning>,
+	read_only: bool,
+}
+
+/// Compact snapshot of the light client database. It holds everything that is required to verify
+/// the chain forward from the last finalized block, so the light client could be bootstrapped from
+/// the snapshot instead of syncing headers from genesis.
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct LightSnapshot<Header, Hash> {
+	/// Genesis block header.
+	pub genesis: Header,
+	/// Roots of all CHTs, starting from CHT#0.
+	pub cht_roots: Vec<Hash>,
+	/// Finalized headers that are not (yet) covered by CHTs. The last header is the last finalized header.
+	pub headers: Vec<Header>,
+	/// Authorities set at the last finalized block, if known.
+	pub authorities: Option<Vec<AuthorityId>>,
+	/// The latest known justification, along with the hash of the block it justifies.
+	pub justification: Option<(Hash, Justification)>,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -70,7 +93,9 @@ impl<Block> LightStorage<Block>
 	pub fn new(config: DatabaseSettings) -> ClientResult<Self> {
 		let db = open_database(&config, columns::META, "light")?;
 
-		Self::from_kvdb(db as Arc<_>)
+		let mut storage = Self::from_kvdb(db as Arc<_>)?;
+		storage.read_only = config.read_only;
+		Ok(storage)
 	}
 
 	#[cfg(test)]
@@ -98,6 +123,8 @@ impl<Block> LightStorage<Block>
 			meta: RwLock::new(meta),
 			cache: DbCacheSync(RwLock::new(cache)),
 			leaves: RwLock::new(leaves),
+			headers_pruning: RwLock::new(HeadersPruning::default()),
+			read_only: false,
 		})
 	}
 
@@ -199,7 +226,9 @@ impl<Block: BlockT> LightStorage<Block> {
 		let lookup_key = ::utils::number_to_lookup_key(header.number().clone());
 		transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &lookup_key);
 
-		// build new CHT if required
+		// build new CHT if required. The CHT isn't built if some of its headers are unknown, i.e.
+		// when the light client is started from the checkpoint
+		let mut built_cht = None;
 		if let Some(new_cht_number) = cht::is_build_required(cht::SIZE, *header.number()) {
 			let new_cht_start: NumberFor<Block> = cht::start_number(cht::SIZE, new_cht_number);
 			let new_cht_root = cht::compute_root::<Block::Header, Blake2Hasher, _>(
@@ -209,17 +238,30 @@ impl<Block: BlockT> LightStorage<Block> {
 
 			if let Some(new_cht_root) = new_cht_root {
 				transaction.put(columns::CHT, &number_to_lookup_key(new_cht_start), new_cht_root.as_ref());
+				built_cht = Some(new_cht_number);
+			}
+		}
 
-				let mut prune_block = new_cht_start;
-				let new_cht_end = cht::end_number(cht::SIZE, new_cht_number);
-				trace!(target: "db", "Replacing blocks [{}..{}] with CHT#{}", new_cht_start, new_cht_end, new_cht_number);
-
-				while prune_block <= new_cht_end {
+		// prune headers of the CHT that has become prunable. The headers are kept if the CHT
+		// hasn't been built, because they couldn't be proved later
+		let number = header.number().as_();
+		let pruning = *self.headers_pruning.read();
+		if number != 0 && prunable_chts(pruning, number) > prunable_chts(pruning, number - 1) {
+			let prune_cht_number: NumberFor<Block> = As::sa(prunable_chts(pruning, number) - 1);
+			let mut prune_block = cht::start_number(cht::SIZE, prune_cht_number);
+			let prune_cht_end = cht::end_number(cht::SIZE, prune_cht_number);
+			let has_cht_root = built_cht == Some(prune_cht_number)
+				|| self.db.get(columns::CHT, &number_to_lookup_key(prune_block)).map_err(db_err)?.is_some();
+			if has_cht_root {
+				trace!(target: "db", "Replacing blocks [{}..{}] with CHT#{}", prune_block, prune_cht_end, prune_cht_number);
+
+				while prune_block <= prune_cht_end {
 					if let Some(hash) = self.hash(prune_block)? {
 						let lookup_key = block_id_to_lookup_key::<Block>(&*self.db, columns::HASH_LOOKUP, BlockId::Number(prune_block))?
 							.expect("retrieved hash for `prune_block` right above. therefore retrieving lookup key must succeed. q.e.d.");
 						transaction.delete(columns::HASH_LOOKUP, hash.as_ref());
 						transaction.delete(columns::HEADER, &lookup_key);
+						transaction.delete(columns::JUSTIFICATION, hash.as_ref());
 					}
 					prune_block += NumberFor::<Block>::one();
 				}
@@ -230,6 +272,160 @@ impl<Block: BlockT> LightStorage<Block> {
 	}
 }
 
+/// Number of the first CHTs which headers are pruned once the block is finalized. CHT#n is built
+/// when the first block of CHT#(n + 2) is finalized.
+fn prunable_chts(pruning: HeadersPruning, finalized: u64) -> u64 {
+	let depth = match pruning {
+		HeadersPruning::KeepAll => return 0,
+		HeadersPruning::KeepRecent(depth) => depth,
+	};
+
+	let built_chts = match cht::block_to_cht_number(cht::SIZE, finalized) {
+		Some(finalized_cht) => finalized_cht.saturating_sub(1),
+		None => 0,
+	};
+	let behind_depth_chts = finalized.saturating_sub(depth) / cht::SIZE;
+	::std::cmp::min(built_chts, behind_depth_chts)
+}
+
+impl<Block: BlockT> LightStorage<Block> {
+	/// Export snapshot of the finalized part of the database.
+	pub fn export_snapshot(&self) -> ClientResult<LightSnapshot<Block::Header, Block::Hash>> {
+		let (finalized_hash, finalized_number) = {
+			let meta = self.meta.read();
+			(meta.finalized_hash, meta.finalized_number)
+		};
+
+		let genesis = self.header(BlockId::Number(Zero::zero()))?
+			.ok_or_else(|| ClientErrorKind::Backend("Unable to export snapshot of empty database".into()))?;
+
+		let mut cht_roots = Vec::new();
+		loop {
+			let cht_start: NumberFor<Block> = cht::start_number(cht::SIZE, As::sa(cht_roots.len() as u64));
+			let cht_root = match self.db.get(columns::CHT, &number_to_lookup_key(cht_start)).map_err(db_err)? {
+				Some(cht_root) => Block::Hash::decode(&mut &*cht_root)
+					.ok_or_else(|| ClientErrorKind::Backend(format!("Error decoding CHT root at {}", cht_start)))?,
+				None => break,
+			};
+			cht_roots.push(cht_root);
+		}
+
+		let mut headers = Vec::new();
+		let mut number: NumberFor<Block> = cht::start_number(cht::SIZE, As::sa(cht_roots.len() as u64));
+		while number <= finalized_number {
+			let header = self.header(BlockId::Number(number))?
+				.ok_or_else(|| ClientErrorKind::UnknownBlock(format!("Finalized header {}", number)))?;
+			headers.push(header);
+			number += One::one();
+		}
+
+		let authorities = self.cache.authorities_at(BlockId::Hash(finalized_hash));
+		let justification = match self.get_aux(LATEST_JUSTIFICATION_KEY)? {
+			Some(justification) => Some(Decode::decode(&mut &justification[..])
+				.ok_or_else(|| ClientErrorKind::Backend("Error decoding latest justification".into()))?),
+			None => None,
+		};
+
+		Ok(LightSnapshot {
+			genesis,
+			cht_roots,
+			headers,
+			authorities,
+			justification,
+		})
+	}
+
+	/// Import snapshot into the empty database. The snapshot is checked to be consistent, but
+	/// otherwise it is trusted, so it must come from the trusted source (e.g. be bundled with the app).
+	pub fn import_snapshot(&self, snapshot: LightSnapshot<Block::Header, Block::Hash>) -> ClientResult<()> {
+		ensure_writable(self.read_only)?;
+		let invalid_snapshot = |reason: &str| ClientErrorKind::Backend(format!("Invalid light snapshot: {}", reason)).into();
+
+		if self.meta.read().best_hash != Default::default() {
+			return Err(ClientErrorKind::Backend("Unable to import snapshot into non-empty database".into()).into());
+		}
+
+		let LightSnapshot { genesis, cht_roots, headers, authorities, justification } = snapshot;
+		if *genesis.number() != Zero::zero() {
+			return Err(invalid_snapshot("genesis header number is not zero"));
+		}
+
+		let genesis_hash = genesis.hash();
+		let first_number: NumberFor<Block> = cht::start_number(cht::SIZE, As::sa(cht_roots.len() as u64));
+		let mut parent_hash = None;
+		for (index, header) in headers.iter().enumerate() {
+			if *header.number() != first_number + As::sa(index as u64) {
+				return Err(invalid_snapshot("headers are not sequential"));
+			}
+			let expected_parent_hash = match parent_hash {
+				Some(parent_hash) => Some(parent_hash),
+				None if cht_roots.is_empty() => Some(genesis_hash),
+				None => None,
+			};
+			if expected_parent_hash.map(|h| h != *header.parent_hash()).unwrap_or(false) {
+				return Err(invalid_snapshot("headers are not chained"));
+			}
+			parent_hash = Some(header.hash());
+		}
+
+		let mut transaction = DBTransaction::new();
+		transaction.put(columns::META, meta_keys::GENESIS_HASH, genesis_hash.as_ref());
+		for (cht_number, cht_root) in cht_roots.iter().enumerate() {
+			let cht_start: NumberFor<Block> = cht::start_number(cht::SIZE, As::sa(cht_number as u64));
+			transaction.put(columns::CHT, &number_to_lookup_key(cht_start), cht_root.as_ref());
+		}
+		for header in ::std::iter::once(&genesis).chain(headers.iter()) {
+			let lookup_key = number_to_lookup_key(*header.number());
+			transaction.put(columns::HEADER, &lookup_key, &header.encode());
+			transaction.put(columns::HASH_LOOKUP, header.hash().as_ref(), &lookup_key);
+		}
+
+		let last_header = headers.last().unwrap_or(&genesis);
+		let last_hash = last_header.hash();
+		let last_number = *last_header.number();
+		let last_lookup_key = number_to_lookup_key(last_number);
+		transaction.put(columns::META, meta_keys::BEST_BLOCK, &last_lookup_key);
+		transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &last_lookup_key);
+
+		if let Some(justification) = justification {
+			transaction.put(columns::AUX, LATEST_JUSTIFICATION_KEY, &justification.encode());
+		}
+
+		{
+			let mut leaves = self.leaves.write();
+			let displaced_leaf = leaves.import(last_hash, last_number, *last_header.parent_hash());
+
+			// the cache of the empty database is pointing to the default block, so we're
+			// 'finalizing' the last header right after it
+			let mut cache = self.cache.0.write();
+			let cache_ops = cache.transaction(&mut transaction)
+				.on_block_insert(
+					ComplexBlockId::new(Default::default(), Zero::zero()),
+					ComplexBlockId::new(last_hash, last_number),
+					authorities,
+					true,
+				)?
+				.into_ops();
+
+			debug!(target: "db", "Light DB Commit snapshot {:?} ({})", last_hash, last_number);
+			let write_result = self.db.write(transaction).map_err(db_err);
+			if let Err(e) = write_result {
+				if let Some(displaced_leaf) = displaced_leaf {
+					leaves.undo(displaced_leaf);
+				}
+				return Err(e);
+			}
+
+			cache.commit(cache_ops);
+		}
+
+		self.update_meta(genesis_hash, Zero::zero(), true, true);
+		self.update_meta(last_hash, last_number, true, true);
+
+		Ok(())
+	}
+}
+
 impl<Block> LightBlockchainStorage<Block> for LightStorage<Block>
 	where Block: BlockT,
 {
@@ -237,8 +433,10 @@ impl<Block> LightBlockchainStorage<Block> for LightStorage<Block>
 		&self,
 		header: Block::Header,
 		authorities: Option<Vec<AuthorityId>>,
+		justification: Option<Justification>,
 		leaf_state: NewBlockState,
 	) -> ClientResult<()> {
+		ensure_writable(self.read_only)?;
 		let mut transaction = DBTransaction::new();
 
 		let hash = header.hash();
@@ -268,7 +466,7 @@ impl<Block> LightBlockchainStorage<Block> for LightStorage<Block>
 					for retracted in tree_route.retracted() {
 						if retracted.hash == meta.finalized_hash {
 							// TODO: can we recover here?
-							warn!("Safety failure: reverting finalized block {:?}",
+							warn!(target: "db", "Safety failure: reverting finalized block {:?}",
 								(&retracted.number, &retracted.hash));
 						}
 
@@ -312,6 +510,10 @@ impl<Block> LightBlockchainStorage<Block> for LightStorage<Block>
 
 		transaction.put(columns::HEADER, &lookup_key, &header.encode());
 		transaction.put(columns::HASH_LOOKUP, hash.as_ref(), &lookup_key);
+		if let Some(justification) = justification {
+			// justifications are keyed by hash => they aren't moved on reorgs
+			transaction.put(columns::JUSTIFICATION, hash.as_ref(), &justification.encode());
+		}
 
 		let finalized = match leaf_state {
 			NewBlockState::Final => true,
@@ -336,7 +538,7 @@ impl<Block> LightBlockchainStorage<Block> for LightStorage<Block>
 				)?
 				.into_ops();
 
-			debug!("Light DB Commit {:?} ({})", hash, number);
+			debug!(target: "db", "Light DB Commit {:?} ({})", hash, number);
 			let write_result = self.db.write(transaction).map_err(db_err);
 			if let Err(e) = write_result {
 				// revert leaves set update if there was one.
@@ -354,6 +556,59 @@ impl<Block> LightBlockchainStorage<Block> for LightStorage<Block>
 		Ok(())
 	}
 
+	fn import_checkpoint(&self, header: Block::Header, authorities: Vec<AuthorityId>) -> ClientResult<()> {
+		ensure_writable(self.read_only)?;
+
+		let hash = header.hash();
+		let number = *header.number();
+		let (genesis_hash, best_number) = {
+			let meta = self.meta.read();
+			(meta.genesis_hash, meta.best_number)
+		};
+		if genesis_hash == Default::default() || !best_number.is_zero() || number.is_zero() {
+			return Err(ClientErrorKind::Backend("Checkpoint is only imported right after the genesis block".into()).into());
+		}
+
+		let mut transaction = DBTransaction::new();
+		let lookup_key = number_to_lookup_key(number);
+		transaction.put(columns::HEADER, &lookup_key, &header.encode());
+		transaction.put(columns::HASH_LOOKUP, hash.as_ref(), &lookup_key);
+		transaction.put(columns::META, meta_keys::BEST_BLOCK, &lookup_key);
+		transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &lookup_key);
+
+		{
+			let mut leaves = self.leaves.write();
+			let displaced_leaf = leaves.import(hash, number, *header.parent_hash());
+
+			// the checkpoint is 'finalized' right after the genesis block, the headers in between
+			// are never known
+			let mut cache = self.cache.0.write();
+			let cache_ops = cache.transaction(&mut transaction)
+				.on_block_insert(
+					ComplexBlockId::new(genesis_hash, Zero::zero()),
+					ComplexBlockId::new(hash, number),
+					Some(authorities),
+					true,
+				)?
+				.into_ops();
+
+			debug!(target: "db", "Light DB Commit checkpoint {:?} ({})", hash, number);
+			let write_result = self.db.write(transaction).map_err(db_err);
+			if let Err(e) = write_result {
+				if let Some(displaced_leaf) = displaced_leaf {
+					leaves.undo(displaced_leaf);
+				}
+				return Err(e);
+			}
+
+			cache.commit(cache_ops);
+		}
+
+		self.update_meta(hash, number, true, true);
+
+		Ok(())
+	}
+
 	fn cht_root(&self, cht_size: u64, block: <<Block as BlockT>::Header as HeaderT>::Number) -> ClientResult<Block::Hash> {
 		let no_cht_for_block = || ClientErrorKind::Backend(format!("CHT for block {} not exists", block)).into();
 
@@ -364,12 +619,20 @@ impl<Block> LightBlockchainStorage<Block> for LightStorage<Block>
 			.and_then(|hash| Block::Hash::decode(&mut &*hash).ok_or_else(no_cht_for_block))
 	}
 
-	fn finalize_header(&self, id: BlockId<Block>) -> ClientResult<()> {
+	fn set_headers_pruning(&self, pruning: HeadersPruning) {
+		*self.headers_pruning.write() = pruning;
+	}
+
+	fn finalize_header(&self, id: BlockId<Block>, justification: Option<Justification>) -> ClientResult<()> {
+		ensure_writable(self.read_only)?;
 		if let Some(header) = self.header(id)? {
 			let mut transaction = DBTransaction::new();
 			// TODO: ensure best chain contains this block.
 			let hash = header.hash();
 			let number = *header.number();
+			if let Some(justification) = justification {
+				transaction.put(columns::JUSTIFICATION, hash.as_ref(), &justification.encode());
+			}
 			self.note_finalized(&mut transaction, &header, hash.clone())?;
 			{
 				let mut cache = self.cache.0.write();
@@ -391,13 +654,73 @@ impl<Block> LightBlockchainStorage<Block> for LightStorage<Block>
 		}
 	}
 
+	fn revert_best(&self) -> ClientResult<()> {
+		ensure_writable(self.read_only)?;
+		let (hash, number) = {
+			let meta = self.meta.read();
+			if meta.best_number.is_zero() || meta.best_number <= meta.finalized_number {
+				return Err(ClientErrorKind::Backend(format!("Unable to revert finalized block {:?}", meta.best_hash)).into());
+			}
+			(meta.best_hash, meta.best_number)
+		};
+		let header = self.header(BlockId::Hash(hash))?
+			.ok_or_else(|| ClientErrorKind::UnknownBlock(format!("{:?}", hash)))?;
+		let parent_hash = *header.parent_hash();
+		let parent_number = number - One::one();
+
+		let mut transaction = DBTransaction::new();
+		transaction.delete(columns::HEADER, &::utils::number_to_lookup_key(number));
+		transaction.delete(columns::HASH_LOOKUP, hash.as_ref());
+		transaction.delete(columns::JUSTIFICATION, hash.as_ref());
+		transaction.put(columns::META, meta_keys::BEST_BLOCK, &::utils::number_to_lookup_key(parent_number));
+
+		debug!(target: "db", "Light DB revert {:?} ({})", hash, number);
+		self.db.write(transaction).map_err(db_err)?;
+		self.leaves.write().revert(hash, number, parent_hash);
+		self.update_meta(parent_hash, parent_number, true, false);
+
+		Ok(())
+	}
+
 	fn last_finalized(&self) -> ClientResult<Block::Hash> {
 		Ok(self.meta.read().finalized_hash.clone())
 	}
 
+	fn justification(&self, id: BlockId<Block>) -> ClientResult<Option<Justification>> {
+		let hash = match id {
+			BlockId::Hash(hash) => hash,
+			BlockId::Number(number) => match self.hash(number)? {
+				Some(hash) => hash,
+				None => return Ok(None),
+			},
+		};
+		match self.db.get(columns::JUSTIFICATION, hash.as_ref()).map_err(db_err)? {
+			Some(justification) => Decode::decode(&mut &justification[..])
+				.map(Some)
+				.ok_or_else(|| ClientErrorKind::Backend("Error decoding justification".into()).into()),
+			None => Ok(None),
+		}
+	}
+
 	fn cache(&self) -> Option<&BlockchainCache<Block>> {
 		None
 	}
+
+	fn insert_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a (&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>(&self, insert: I, delete: D) -> ClientResult<()> {
+		ensure_writable(self.read_only)?;
+		let mut transaction = DBTransaction::new();
+		for (k, v) in insert {
+			transaction.put(columns::AUX, k, v);
+		}
+		for k in delete {
+			transaction.delete(columns::AUX, k);
+		}
+		self.db.write(transaction).map_err(db_err)
+	}
+
+	fn get_aux(&self, key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		self.db.get(columns::AUX, key).map(|r| r.map(|v| v.to_vec())).map_err(db_err)
+	}
 }
 
 #[cfg(test)]
@@ -427,7 +750,7 @@ pub(crate) mod tests {
 	) -> Hash {
 		let header = prepare_header(parent, number, extrinsics_root);
 		let hash = header.hash();
-		db.import_header(header, authorities, NewBlockState::Best).unwrap();
+		db.import_header(header, authorities, None, NewBlockState::Best).unwrap();
 		hash
 	}
 
@@ -439,7 +762,7 @@ pub(crate) mod tests {
 	) -> Hash {
 		let header = prepare_header(parent, number, Default::default());
 		let hash = header.hash();
-		db.import_header(header, authorities, NewBlockState::Best).unwrap();
+		db.import_header(header, authorities, None, NewBlockState::Best).unwrap();
 		hash
 	}
 
@@ -451,7 +774,7 @@ pub(crate) mod tests {
 	) -> Hash {
 		let header = prepare_header(parent, number, Default::default());
 		let hash = header.hash();
-		db.import_header(header, authorities, NewBlockState::Final).unwrap();
+		db.import_header(header, authorities, None, NewBlockState::Final).unwrap();
 		hash
 	}
 
@@ -463,7 +786,7 @@ pub(crate) mod tests {
 	) -> Hash {
 		let header = prepare_header(parent, number, Default::default());
 		let hash = header.hash();
-		db.import_header(header, authorities, NewBlockState::Normal).unwrap();
+		db.import_header(header, authorities, None, NewBlockState::Normal).unwrap();
 		hash
 	}
 
@@ -529,6 +852,48 @@ pub(crate) mod tests {
 		assert_eq!(db.db.iter(columns::HASH_LOOKUP).count(), 2);
 	}
 
+	#[test]
+	fn justification_is_stored_along_with_header() {
+		let db = LightStorage::new_test();
+		let genesis_hash = insert_final_block(&db, &Default::default(), 0, None);
+		let header1 = prepare_header(&genesis_hash, 1, Default::default());
+		let hash1 = header1.hash();
+		db.import_header(header1, None, Some(vec![42]), NewBlockState::Final).unwrap();
+		let hash2 = insert_block(&db, &hash1, 2, None);
+
+		assert_eq!(db.justification(BlockId::Hash(hash1)).unwrap(), Some(vec![42]));
+		assert_eq!(db.justification(BlockId::Number(1)).unwrap(), Some(vec![42]));
+		assert_eq!(db.justification(BlockId::Hash(hash2)).unwrap(), None);
+		assert_eq!(db.justification(BlockId::Number(3)).unwrap(), None);
+
+		// justification that is received after the import is stored when the header is finalized
+		db.finalize_header(BlockId::Number(2), Some(vec![43])).unwrap();
+		assert_eq!(db.justification(BlockId::Hash(hash2)).unwrap(), Some(vec![43]));
+	}
+
+	#[test]
+	fn headers_are_preserved_when_storage_is_reopened() {
+		let kvdb = Arc::new(::kvdb_memorydb::create(::utils::NUM_COLUMNS));
+		let (genesis_hash, hash1, hash2) = {
+			let db = LightStorage::<Block>::from_kvdb(kvdb.clone() as Arc<_>).unwrap();
+			let genesis_hash = insert_final_block(&db, &Default::default(), 0, None);
+			let hash1 = insert_final_block(&db, &genesis_hash, 1, None);
+			let hash2 = insert_block(&db, &hash1, 2, None);
+			(genesis_hash, hash1, hash2)
+		};
+
+		let db = LightStorage::<Block>::from_kvdb(kvdb as Arc<_>).unwrap();
+		let info = db.info().unwrap();
+		assert_eq!(info.genesis_hash, genesis_hash);
+		assert_eq!(info.best_hash, hash2);
+		assert_eq!(info.best_number, 2);
+		assert_eq!(info.finalized_hash, hash1);
+		assert_eq!(info.finalized_number, 1);
+		assert_eq!(db.last_finalized().unwrap(), hash1);
+		assert_eq!(db.hash(1).unwrap(), Some(hash1));
+		assert_eq!(db.header(BlockId::Hash(hash2)).unwrap().map(|header| header.hash()), Some(hash2));
+	}
+
 	#[test]
 	fn finalized_ancient_headers_are_replaced_with_cht() {
 		let db = LightStorage::new_test();
@@ -561,15 +926,81 @@ pub(crate) mod tests {
 
 		// now finalize the block.
 		for i in (0..(cht::SIZE + cht::SIZE)).map(|i| i + 1) {
-			db.finalize_header(BlockId::Number(i)).unwrap();
+			db.finalize_header(BlockId::Number(i), None).unwrap();
 		}
-		db.finalize_header(BlockId::Hash(prev_hash)).unwrap();
+		db.finalize_header(BlockId::Hash(prev_hash), None).unwrap();
 		assert_eq!(db.db.iter(columns::HEADER).count(), (1 + cht::SIZE + 1) as usize);
 		assert_eq!(db.db.iter(columns::HASH_LOOKUP).count(), (1 + cht::SIZE + 1) as usize);
 		assert_eq!(db.db.iter(columns::CHT).count(), 1);
 		assert!((0..cht::SIZE).all(|i| db.db.get(columns::HEADER, &number_to_lookup_key(1 + i)).unwrap().is_none()));
 	}
 
+	#[test]
+	fn headers_are_pruned_according_to_configured_depth() {
+		let count_headers = |db: &LightStorage<Block>| db.db.iter(columns::HEADER).count() as u64;
+		let insert_and_finalize = |db: &LightStorage<Block>, prev_hash, numbers: ::std::ops::Range<u64>| {
+			let mut prev_hash = prev_hash;
+			for number in numbers {
+				prev_hash = insert_block(db, &prev_hash, number, None);
+				db.finalize_header(BlockId::Hash(prev_hash), None).unwrap();
+			}
+			prev_hash
+		};
+
+		// headers of CHT#0 are kept until the finalized block is 2 * SIZE blocks ahead
+		let db = LightStorage::new_test();
+		db.set_headers_pruning(HeadersPruning::KeepRecent(2 * cht::SIZE));
+		let genesis_hash = insert_final_block(&db, &Default::default(), 0, None);
+		let prev_hash = insert_and_finalize(&db, genesis_hash, 1..2 * cht::SIZE + 2);
+		assert_eq!(db.db.iter(columns::CHT).count(), 1);
+		assert_eq!(count_headers(&db), 2 + 2 * cht::SIZE);
+		let prev_hash = insert_and_finalize(&db, prev_hash, 2 * cht::SIZE + 2..3 * cht::SIZE);
+		assert_eq!(count_headers(&db), 3 * cht::SIZE);
+		insert_and_finalize(&db, prev_hash, 3 * cht::SIZE..3 * cht::SIZE + 1);
+		assert_eq!(count_headers(&db), 1 + 2 * cht::SIZE);
+		assert!(db.header(BlockId::Number(cht::SIZE)).unwrap().is_none());
+		assert!(db.header(BlockId::Number(cht::SIZE + 1)).unwrap().is_some());
+
+		// all headers are kept, but CHTs are still built
+		let db = LightStorage::new_test();
+		db.set_headers_pruning(HeadersPruning::KeepAll);
+		let genesis_hash = insert_final_block(&db, &Default::default(), 0, None);
+		insert_and_finalize(&db, genesis_hash, 1..3 * cht::SIZE + 2);
+		assert_eq!(db.db.iter(columns::CHT).count(), 2);
+		assert_eq!(count_headers(&db), 2 + 3 * cht::SIZE);
+	}
+
+	#[test]
+	fn light_storage_is_started_from_checkpoint() {
+		let db = LightStorage::new_test();
+		let checkpoint_number = cht::SIZE + 2;
+		let checkpoint = prepare_header(&Hash::random(), checkpoint_number, Default::default());
+		let checkpoint_hash = checkpoint.hash();
+
+		// checkpoint is only imported right after the genesis block
+		assert!(db.import_checkpoint(checkpoint.clone(), vec![[1u8; 32].into()]).is_err());
+		let genesis_hash = insert_final_block(&db, &Default::default(), 0, None);
+		db.import_checkpoint(checkpoint.clone(), vec![[1u8; 32].into()]).unwrap();
+		assert!(db.import_checkpoint(checkpoint, vec![[1u8; 32].into()]).is_err());
+
+		let info = db.info().unwrap();
+		assert_eq!(info.genesis_hash, genesis_hash);
+		assert_eq!((info.best_hash, info.best_number), (checkpoint_hash, checkpoint_number));
+		assert_eq!((info.finalized_hash, info.finalized_number), (checkpoint_hash, checkpoint_number));
+		assert_eq!(db.cache().authorities_at(BlockId::Hash(checkpoint_hash)), Some(vec![[1u8; 32].into()]));
+
+		// blocks are imported on top of the checkpoint. CHT#0 and CHT#1 can't be built, so the
+		// headers of CHT#1 that are known are never pruned
+		let mut prev_hash = checkpoint_hash;
+		for number in checkpoint_number + 1..3 * cht::SIZE + 2 {
+			prev_hash = insert_final_block(&db, &prev_hash, number, None);
+		}
+		assert_eq!(db.db.iter(columns::CHT).count(), 0);
+		assert!(db.cht_root(cht::SIZE, checkpoint_number).is_err());
+		assert_eq!(db.hash(checkpoint_number).unwrap(), Some(checkpoint_hash));
+		assert_eq!(db.last_finalized().unwrap(), prev_hash);
+	}
+
 	#[test]
 	fn get_cht_fails_for_genesis_block() {
 		assert!(LightStorage::<Block>::new_test().cht_root(cht::SIZE, 0).is_err());
@@ -588,7 +1019,7 @@ pub(crate) mod tests {
 		let mut prev_hash = insert_final_block(&db, &Default::default(), 0, None);
 		for i in 1..1 + cht::SIZE + cht::SIZE + 1 {
 			prev_hash = insert_block(&db, &prev_hash, i as u64, None);
-			db.finalize_header(BlockId::Hash(prev_hash)).unwrap();
+			db.finalize_header(BlockId::Hash(prev_hash), None).unwrap();
 		}
 
 		let cht_root_1 = db.cht_root(cht::SIZE, cht::start_number(cht::SIZE, 0)).unwrap();
@@ -760,7 +1191,7 @@ pub(crate) mod tests {
 
 		{
 			// finalize block hash6_1
-			db.finalize_header(BlockId::Hash(hash6_1)).unwrap();
+			db.finalize_header(BlockId::Hash(hash6_1), None).unwrap();
 			assert_eq!(db.cache().authorities_at(BlockId::Hash(hash6)), None);
 			assert_eq!(db.cache().authorities_at(BlockId::Hash(hash7)), None);
 			assert_eq!(db.cache().authorities_at(BlockId::Hash(hash8)), None);
@@ -769,7 +1200,7 @@ pub(crate) mod tests {
 			assert_eq!(db.cache().authorities_at(BlockId::Hash(hash6_1_2)), Some(vec![[6u8; 32].into()]));
 			assert_eq!(db.cache().authorities_at(BlockId::Hash(hash6_2)), Some(vec![[4u8; 32].into()]));
 			// finalize block hash6_2
-			db.finalize_header(BlockId::Hash(hash6_2)).unwrap();
+			db.finalize_header(BlockId::Hash(hash6_2), None).unwrap();
 			assert_eq!(db.cache().authorities_at(BlockId::Hash(hash6)), None);
 			assert_eq!(db.cache().authorities_at(BlockId::Hash(hash7)), None);
 			assert_eq!(db.cache().authorities_at(BlockId::Hash(hash8)), None);
@@ -779,4 +1210,87 @@ pub(crate) mod tests {
 			assert_eq!(db.cache().authorities_at(BlockId::Hash(hash6_2)), Some(vec![[4u8; 32].into()]));
 		}
 	}
+
+	#[test]
+	fn best_header_is_reverted() {
+		let db = LightStorage::new_test();
+		let genesis_hash = insert_final_block(&db, &Default::default(), 0, None);
+		let hash1 = insert_block(&db, &genesis_hash, 1, None);
+		let hash2 = insert_block(&db, &hash1, 2, None);
+
+		db.revert_best().unwrap();
+		assert_eq!(db.info().unwrap().best_hash, hash1);
+		assert_eq!(db.info().unwrap().best_number, 1);
+		assert_eq!(db.header(BlockId::Hash(hash2)).unwrap(), None);
+		assert_eq!(db.header(BlockId::Number(2)).unwrap(), None);
+		assert_eq!(db.leaves.read().hashes(), vec![hash1]);
+
+		// the chain may be continued from the new best block
+		let hash2 = insert_block(&db, &hash1, 2, None);
+		assert_eq!(db.info().unwrap().best_hash, hash2);
+
+		// finalized blocks are never reverted
+		db.finalize_header(BlockId::Hash(hash2), None).unwrap();
+		assert!(db.revert_best().is_err());
+		assert_eq!(db.info().unwrap().best_hash, hash2);
+	}
+
+	#[test]
+	fn aux_store_works() {
+		let db = LightStorage::<Block>::new_test();
+		assert!(db.get_aux(b"test").unwrap().is_none());
+		db.insert_aux(&[(&b"test"[..], &b"hello"[..])], &[]).unwrap();
+		assert_eq!(b"hello", &db.get_aux(b"test").unwrap().unwrap()[..]);
+		db.insert_aux(&[], &[&b"test"[..]]).unwrap();
+		assert!(db.get_aux(b"test").unwrap().is_none());
+	}
+
+	#[test]
+	fn snapshot_export_import_works() {
+		let db = LightStorage::new_test();
+
+		// insert 1 + SIZE + SIZE + 1 finalized blocks so that CHT#0 is created
+		let mut prev_hash = insert_final_block(&db, &Default::default(), 0, None);
+		for i in 1..1 + cht::SIZE + cht::SIZE + 1 {
+			let authorities = if i == cht::SIZE { Some(vec![[1u8; 32].into()]) } else { None };
+			prev_hash = insert_final_block(&db, &prev_hash, i as u64, authorities);
+		}
+		let justification = (prev_hash, vec![42u8]);
+		db.insert_aux(&[(LATEST_JUSTIFICATION_KEY, &justification.encode()[..])], &[]).unwrap();
+
+		let snapshot = db.export_snapshot().unwrap();
+		assert_eq!(snapshot.cht_roots, vec![db.cht_root(cht::SIZE, 1).unwrap()]);
+		assert_eq!(snapshot.headers.len(), (cht::SIZE + 1) as usize);
+		assert_eq!(snapshot.authorities, Some(vec![[1u8; 32].into()]));
+		assert_eq!(snapshot.justification, Some(justification.clone()));
+
+		// snapshot is only imported into the empty database
+		assert!(db.import_snapshot(Decode::decode(&mut &snapshot.encode()[..]).unwrap()).is_err());
+
+		let new_db = LightStorage::<Block>::new_test();
+		new_db.import_snapshot(Decode::decode(&mut &snapshot.encode()[..]).unwrap()).unwrap();
+		assert_eq!(new_db.info().unwrap().best_hash, prev_hash);
+		assert_eq!(new_db.info().unwrap().finalized_hash, prev_hash);
+		assert_eq!(new_db.info().unwrap().genesis_hash, db.info().unwrap().genesis_hash);
+		assert_eq!(new_db.cht_root(cht::SIZE, 1).unwrap(), db.cht_root(cht::SIZE, 1).unwrap());
+		assert_eq!(new_db.cache().authorities_at(BlockId::Hash(prev_hash)), Some(vec![[1u8; 32].into()]));
+		assert_eq!(new_db.export_snapshot().unwrap(), snapshot);
+
+		// the light client is able to continue from the snapshot
+		let next_hash = insert_final_block(&new_db, &prev_hash, 2 + cht::SIZE + cht::SIZE, None);
+		assert_eq!(new_db.info().unwrap().finalized_hash, next_hash);
+	}
+
+	#[test]
+	fn inconsistent_snapshot_is_rejected() {
+		let db = LightStorage::new_test();
+		let mut prev_hash = insert_final_block(&db, &Default::default(), 0, None);
+		for i in 1..4 {
+			prev_hash = insert_final_block(&db, &prev_hash, i, None);
+		}
+
+		let mut snapshot = db.export_snapshot().unwrap();
+		snapshot.headers.remove(1);
+		assert!(LightStorage::<Block>::new_test().import_snapshot(snapshot).is_err());
+	}
 }
@@ -0,0 +1,253 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Versioned schema upgrades of the database.
+//!
+//! The schema version is kept in the meta column. Databases created before the version has been
+//! introduced have version 0. Pending migrations are applied in order when the database is
+//! opened. Every migration is applied in batches, and the position of the last written batch is
+//! committed together with it, so the interrupted migration is resumed from that position.
+
+use std::mem::size_of;
+use kvdb::{KeyValueDB, DBTransaction};
+
+use client;
+use codec::{Decode, Encode};
+use utils::{db_err, lookup_key_to_number, meta_keys, ShortBlockLookupKey};
+
+/// Number of the hash lookup entries visited in a single batch of the canonical index migration.
+const CANONICAL_INDEX_BATCH: usize = 10_000;
+
+/// Schema upgrade of the database.
+pub struct Migration {
+	/// Version of the database after the migration is applied.
+	pub version: u32,
+	/// Human-readable description of the migration.
+	pub description: &'static str,
+	/// Write next batch of the migration to the transaction, starting at given position (`None`
+	/// for the first batch). Returns the position of the next batch, or `None` if the migration
+	/// is complete.
+	pub apply: fn(&KeyValueDB, &mut DBTransaction, Option<&[u8]>) -> client::error::Result<Option<Vec<u8>>>,
+}
+
+/// Migrations of the full node database, ordered by version.
+const FULL_MIGRATIONS: &[Migration] = &[
+	Migration {
+		version: 1,
+		description: "Record the database schema version",
+		apply: record_version,
+	},
+	Migration {
+		version: 2,
+		description: "Index hashes of the best chain blocks by number",
+		apply: index_canonical_hashes,
+	},
+];
+
+/// Migrations of the light client database, ordered by version.
+const LIGHT_MIGRATIONS: &[Migration] = &[
+	Migration {
+		version: 1,
+		description: "Record the database schema version",
+		apply: record_version,
+	},
+];
+
+fn record_version(_: &KeyValueDB, _: &mut DBTransaction, _: Option<&[u8]>) -> client::error::Result<Option<Vec<u8>>> {
+	Ok(None)
+}
+
+// the best chain blocks are keyed by the short lookup key, so their hashes are found by the
+// hash lookup entries pointing to the short keys.
+fn index_canonical_hashes(db: &KeyValueDB, transaction: &mut DBTransaction, position: Option<&[u8]>) -> client::error::Result<Option<Vec<u8>>> {
+	let best: u64 = match db.get(::columns::META, meta_keys::BEST_BLOCK).map_err(db_err)? {
+		Some(best) => lookup_key_to_number(&best)?,
+		None => return Ok(None),
+	};
+
+	let entries = db.iter(::columns::HASH_LOOKUP)
+		.skip_while(|&(ref hash, _)| position.map_or(false, |position| &hash[..] < position));
+	for (visited, (hash, lookup_key)) in entries.enumerate() {
+		if visited == CANONICAL_INDEX_BATCH {
+			return Ok(Some(hash.into_vec()));
+		}
+		if lookup_key.len() == size_of::<ShortBlockLookupKey>() && lookup_key_to_number::<u64>(&lookup_key)? <= best {
+			transaction.put(::columns::CANONICAL_INDEX, &lookup_key, &hash);
+		}
+	}
+	Ok(None)
+}
+
+/// Migrations of the database of given type.
+pub fn migrations(db_type: &str) -> client::error::Result<&'static [Migration]> {
+	match db_type {
+		"full" => Ok(FULL_MIGRATIONS),
+		"light" => Ok(LIGHT_MIGRATIONS),
+		_ => Err(client::error::ErrorKind::Backend(format!("Unknown database type: {}", db_type)).into()),
+	}
+}
+
+/// Version of the newly created database, to which all migrations are applied.
+pub fn latest_version(migrations: &[Migration]) -> u32 {
+	migrations.last().map_or(0, |migration| migration.version)
+}
+
+/// Read the schema version of the database.
+pub fn read_version(db: &KeyValueDB, col_meta: Option<u32>) -> client::error::Result<u32> {
+	match db.get(col_meta, meta_keys::DB_VERSION).map_err(db_err)? {
+		Some(version) => u32::decode(&mut &version[..])
+			.ok_or_else(|| client::error::ErrorKind::Backend("Error decoding database version".into()).into()),
+		None => Ok(0),
+	}
+}
+
+/// Apply the migrations the database is missing and return them. If `dry_run` is set, the
+/// migrations are only returned.
+pub fn migrate<'a>(
+	db: &KeyValueDB,
+	col_meta: Option<u32>,
+	migrations: &'a [Migration],
+	dry_run: bool,
+) -> client::error::Result<Vec<&'a Migration>> {
+	let version = read_version(db, col_meta)?;
+	let latest = latest_version(migrations);
+	if version > latest {
+		return Err(client::error::ErrorKind::Backend(format!(
+			"Database version {} is newer than the latest supported version {}", version, latest
+		)).into());
+	}
+
+	let pending: Vec<_> = migrations.iter().filter(|migration| migration.version > version).collect();
+	if dry_run {
+		return Ok(pending);
+	}
+
+	let mut progress = match db.get(col_meta, meta_keys::MIGRATION_PROGRESS).map_err(db_err)? {
+		Some(progress) => Some(<(u32, Vec<u8>)>::decode(&mut &progress[..])
+			.ok_or_else(|| client::error::Error::from(client::error::ErrorKind::Backend("Error decoding migration progress".into())))?),
+		None => None,
+	};
+	for migration in &pending {
+		let mut position = progress.take()
+			.and_then(|(version, position)| if version == migration.version { Some(position) } else { None });
+		match position {
+			Some(_) => info!(target: "db", "Resuming migration to version {}: {}", migration.version, migration.description),
+			None => info!(target: "db", "Migrating database to version {}: {}", migration.version, migration.description),
+		}
+
+		loop {
+			let mut transaction = DBTransaction::new();
+			let next = (migration.apply)(db, &mut transaction, position.as_ref().map(|position| &position[..]))?;
+			match next {
+				Some(ref next) => transaction.put(col_meta, meta_keys::MIGRATION_PROGRESS, &(migration.version, next.clone()).encode()),
+				None => {
+					transaction.delete(col_meta, meta_keys::MIGRATION_PROGRESS);
+					transaction.put(col_meta, meta_keys::DB_VERSION, &migration.version.encode());
+				},
+			}
+			db.write(transaction).map_err(db_err)?;
+
+			position = match next {
+				Some(next) => Some(next),
+				None => break,
+			};
+		}
+	}
+
+	Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use utils::{COLUMN_META, NUM_COLUMNS};
+
+	const DATA: Option<u32> = Some(1);
+
+	/// Doubles the values of the data column, one entry per batch.
+	fn double_values(db: &KeyValueDB, transaction: &mut DBTransaction, position: Option<&[u8]>) -> client::error::Result<Option<Vec<u8>>> {
+		let mut entries = db.iter(DATA).skip_while(|&(ref key, _)| position.map_or(false, |position| &key[..] < position));
+		match entries.next() {
+			Some((key, value)) => {
+				transaction.put(DATA, &key, &[value[0] * 2]);
+				Ok(entries.next().map(|(key, _)| key.into_vec()))
+			},
+			None => Ok(None),
+		}
+	}
+
+	const MIGRATIONS: &[Migration] = &[
+		Migration { version: 1, description: "first", apply: record_version },
+		Migration { version: 2, description: "second", apply: double_values },
+	];
+
+	fn db_with_data() -> ::kvdb_memorydb::InMemory {
+		let db = ::kvdb_memorydb::create(NUM_COLUMNS);
+		let mut transaction = DBTransaction::new();
+		for key in 1u8..4 {
+			transaction.put(DATA, &[key], &[key]);
+		}
+		db.write(transaction).unwrap();
+		db
+	}
+
+	fn values(db: &KeyValueDB) -> Vec<u8> {
+		db.iter(DATA).map(|(_, value)| value[0]).collect()
+	}
+
+	#[test]
+	fn pending_migrations_are_applied() {
+		let db = db_with_data();
+		assert_eq!(read_version(&db, COLUMN_META).unwrap(), 0);
+
+		let pending = migrate(&db, COLUMN_META, MIGRATIONS, true).unwrap();
+		assert_eq!(pending.iter().map(|m| m.version).collect::<Vec<_>>(), vec![1, 2]);
+		assert_eq!(read_version(&db, COLUMN_META).unwrap(), 0);
+		assert_eq!(values(&db), vec![1, 2, 3]);
+
+		assert_eq!(migrate(&db, COLUMN_META, MIGRATIONS, false).unwrap().len(), 2);
+		assert_eq!(read_version(&db, COLUMN_META).unwrap(), 2);
+		assert_eq!(values(&db), vec![2, 4, 6]);
+		assert!(db.get(COLUMN_META, meta_keys::MIGRATION_PROGRESS).unwrap().is_none());
+
+		// nothing is applied twice
+		assert!(migrate(&db, COLUMN_META, MIGRATIONS, false).unwrap().is_empty());
+		assert_eq!(values(&db), vec![2, 4, 6]);
+	}
+
+	#[test]
+	fn interrupted_migration_is_resumed() {
+		let db = db_with_data();
+		let mut transaction = DBTransaction::new();
+		transaction.put(COLUMN_META, meta_keys::DB_VERSION, &1u32.encode());
+		transaction.put(COLUMN_META, meta_keys::MIGRATION_PROGRESS, &(2u32, vec![2u8]).encode());
+		db.write(transaction).unwrap();
+
+		assert_eq!(migrate(&db, COLUMN_META, MIGRATIONS, false).unwrap().len(), 1);
+		assert_eq!(values(&db), vec![1, 4, 6]);
+		assert_eq!(read_version(&db, COLUMN_META).unwrap(), 2);
+	}
+
+	#[test]
+	fn newer_database_is_rejected() {
+		let db = db_with_data();
+		let mut transaction = DBTransaction::new();
+		transaction.put(COLUMN_META, meta_keys::DB_VERSION, &3u32.encode());
+		db.write(transaction).unwrap();
+
+		assert!(migrate(&db, COLUMN_META, MIGRATIONS, true).is_err());
+	}
+}
@@ -0,0 +1,112 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Worker reading the state ahead of the block execution, to warm up the caches.
+//!
+//! The worker threads are started once and live as long as the backend. The keys are split into
+//! chunks read in parallel. The number of queued chunks is bounded: prefetching is only an
+//! optimization, so the chunks that don't fit into the queue are skipped.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, mpsc};
+use std::thread;
+
+use parking_lot::Mutex;
+use state_machine::backend::Backend as StateBackend;
+
+use DbState;
+
+/// Number of threads reading the state in parallel.
+const PREFETCH_THREADS: usize = 4;
+/// Maximal number of the chunks of keys waiting to be read.
+const MAX_QUEUED_CHUNKS: usize = 16;
+
+/// Chunk of keys to read from the state.
+struct Chunk {
+	state: DbState,
+	keys: Vec<Vec<u8>>,
+	done: mpsc::Sender<()>,
+}
+
+/// Long-lived worker reading the state in background threads.
+pub struct PrefetchWorker {
+	// `SyncSender` isn't `Sync`, while the backend is shared between threads
+	sender: Mutex<mpsc::SyncSender<Chunk>>,
+}
+
+impl PrefetchWorker {
+	/// Start the worker threads. The threads are stopped once the worker is dropped.
+	pub fn new() -> Self {
+		let (sender, receiver) = mpsc::sync_channel::<Chunk>(MAX_QUEUED_CHUNKS);
+		let receiver = Arc::new(Mutex::new(receiver));
+		for index in 0..PREFETCH_THREADS {
+			let receiver = receiver.clone();
+			let spawned = thread::Builder::new()
+				.name(format!("db-prefetch-{}", index))
+				.spawn(move || loop {
+					let chunk = match receiver.lock().recv() {
+						Ok(chunk) => chunk,
+						Err(_) => break,
+					};
+					let read = panic::catch_unwind(AssertUnwindSafe(|| for key in &chunk.keys {
+						if let Err(e) = chunk.state.storage(key) {
+							debug!(target: "db", "Error prefetching state key {:?}: {}", key, e);
+						}
+					}));
+					if read.is_err() {
+						warn!(target: "db", "Prefetching state has panicked");
+					}
+					let _ = chunk.done.send(());
+				});
+			if let Err(e) = spawned {
+				warn!(target: "db", "Failed to spawn state prefetch thread: {:?}", e);
+			}
+		}
+
+		PrefetchWorker {
+			sender: Mutex::new(sender),
+		}
+	}
+
+	/// Read the keys from the state returned by `state`, waiting until the queued chunks are read.
+	pub fn prefetch<F: Fn() -> DbState>(&self, state: F, keys: &[Vec<u8>]) {
+		if keys.is_empty() {
+			return;
+		}
+
+		let chunk_size = (keys.len() + PREFETCH_THREADS - 1) / PREFETCH_THREADS;
+		let (done, read) = mpsc::channel();
+		let mut queued = 0;
+		{
+			let sender = self.sender.lock();
+			for keys in keys.chunks(chunk_size) {
+				let chunk = Chunk { state: state(), keys: keys.to_vec(), done: done.clone() };
+				match sender.try_send(chunk) {
+					Ok(()) => queued += 1,
+					Err(mpsc::TrySendError::Full(chunk)) =>
+						debug!(target: "db", "Prefetch queue is full, skipping {} keys", chunk.keys.len()),
+					Err(mpsc::TrySendError::Disconnected(_)) => break,
+				}
+			}
+		}
+
+		for _ in 0..queued {
+			if read.recv().is_err() {
+				break;
+			}
+		}
+	}
+}
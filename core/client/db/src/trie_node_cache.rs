@@ -0,0 +1,188 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cache of the state trie nodes.
+//!
+//! Trie nodes are addressed by their hash, so cached nodes never become stale.
+
+use std::collections::{HashMap, VecDeque};
+use client::memory_budget::{MemoryAccount, MemoryBudget};
+use primitives::H256;
+use state_machine::DBValue;
+
+/// Default size of the cached nodes, in bytes.
+pub const DEFAULT_TRIE_NODE_CACHE_SIZE: usize = 32 * 1024 * 1024;
+
+/// Weight of the trie node cache in the shared memory budget.
+pub const TRIE_NODE_CACHE_WEIGHT: usize = 4;
+
+/// Bounded cache of the trie nodes. When the size limit is reached, the oldest nodes
+/// are evicted first.
+pub struct TrieNodeCache {
+	nodes: HashMap<H256, DBValue>,
+	order: VecDeque<H256>,
+	size: usize,
+	limit: usize,
+	account: Option<MemoryAccount>,
+}
+
+impl TrieNodeCache {
+	/// Create new cache, keeping at most `limit` bytes of nodes.
+	pub fn new(limit: usize) -> Self {
+		TrieNodeCache {
+			nodes: HashMap::new(),
+			order: VecDeque::new(),
+			size: 0,
+			limit,
+			account: None,
+		}
+	}
+
+	/// Account the cached nodes in the shared memory budget. The cache is shrunk below its own
+	/// limit when the budget is exceeded.
+	pub fn set_memory_budget(&mut self, budget: &MemoryBudget) {
+		self.account = Some(budget.register("trie_node_cache", TRIE_NODE_CACHE_WEIGHT));
+	}
+
+	/// Size of the cached nodes, in bytes.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	/// Get cached node. The cache is shrunk first if the memory budget has asked it to.
+	pub fn get(&mut self, hash: &H256) -> Option<DBValue> {
+		self.apply_memory_request();
+		self.nodes.get(hash).cloned()
+	}
+
+	/// Insert node into the cache.
+	pub fn insert(&mut self, hash: H256, node: DBValue) {
+		if node.len() > self.limit || self.nodes.contains_key(&hash) {
+			return;
+		}
+
+		self.apply_memory_request();
+
+		self.size += node.len();
+		self.nodes.insert(hash, node);
+		self.order.push_back(hash);
+		let limit = self.limit;
+		self.shrink(limit);
+
+		self.report_memory_usage();
+	}
+
+	/// Shrink the cache to the size it has been asked to shrink to by the memory budget, if any.
+	fn apply_memory_request(&mut self) {
+		let requested = self.account.as_ref().and_then(|account| account.requested_size());
+		if let Some(requested) = requested {
+			self.shrink(requested);
+			self.report_memory_usage();
+		}
+	}
+
+	/// Report the size of the cache to the memory budget, shrinking the cache to its share if
+	/// the budget is exceeded.
+	fn report_memory_usage(&mut self) {
+		let share = self.account.as_ref().and_then(|account| account.report(self.size));
+		if let Some(share) = share {
+			self.shrink(share);
+			if let Some(ref account) = self.account {
+				account.report(self.size);
+			}
+		}
+	}
+
+	fn shrink(&mut self, limit: usize) {
+		while self.size > limit {
+			let evicted = self.order.pop_front()
+				.expect("size is only non-zero when there are cached nodes; qed");
+			if let Some(node) = self.nodes.remove(&evicted) {
+				self.size -= node.len();
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn trie_node_cache_is_bounded() {
+		let mut cache = TrieNodeCache::new(10);
+		cache.insert(H256::from(1), DBValue::from_slice(&[1; 4]));
+		cache.insert(H256::from(2), DBValue::from_slice(&[2; 4]));
+		assert_eq!(cache.get(&H256::from(1)), Some(DBValue::from_slice(&[1; 4])));
+
+		cache.insert(H256::from(3), DBValue::from_slice(&[3; 4]));
+		assert_eq!(cache.get(&H256::from(1)), None);
+		assert_eq!(cache.get(&H256::from(2)), Some(DBValue::from_slice(&[2; 4])));
+		assert_eq!(cache.get(&H256::from(3)), Some(DBValue::from_slice(&[3; 4])));
+
+		cache.insert(H256::from(4), DBValue::from_slice(&[4; 11]));
+		assert_eq!(cache.get(&H256::from(4)), None);
+	}
+
+	#[test]
+	fn trie_node_cache_is_shrunk_to_memory_budget() {
+		let budget = MemoryBudget::new(10);
+		let other = budget.register("other", TRIE_NODE_CACHE_WEIGHT);
+		assert_eq!(other.report(4), None);
+
+		let mut cache = TrieNodeCache::new(100);
+		cache.set_memory_budget(&budget);
+		cache.insert(H256::from(1), DBValue::from_slice(&[1; 4]));
+		cache.insert(H256::from(2), DBValue::from_slice(&[2; 4]));
+		assert_eq!(cache.size(), 4);
+		assert_eq!(cache.get(&H256::from(1)), None);
+		assert!(budget.used() <= budget.limit());
+	}
+
+	#[test]
+	fn trie_node_cache_is_shrunk_when_other_component_exceeds_memory_budget() {
+		let budget = MemoryBudget::new(10);
+		let mut cache = TrieNodeCache::new(100);
+		cache.set_memory_budget(&budget);
+		cache.insert(H256::from(1), DBValue::from_slice(&[1; 4]));
+		cache.insert(H256::from(2), DBValue::from_slice(&[2; 4]));
+
+		let other = budget.register("other", TRIE_NODE_CACHE_WEIGHT);
+		assert_eq!(other.report(4), None);
+		cache.insert(H256::from(3), DBValue::from_slice(&[3; 1]));
+		assert_eq!(cache.get(&H256::from(1)), None);
+		assert_eq!(cache.get(&H256::from(2)), Some(DBValue::from_slice(&[2; 4])));
+		assert_eq!(cache.size(), 5);
+	}
+
+	#[test]
+	fn trie_node_cache_is_shrunk_on_read() {
+		let budget = MemoryBudget::new(10);
+		let mut cache = TrieNodeCache::new(100);
+		cache.set_memory_budget(&budget);
+		cache.insert(H256::from(1), DBValue::from_slice(&[1; 4]));
+		cache.insert(H256::from(2), DBValue::from_slice(&[2; 4]));
+
+		// the cache is accounted with its size until it has shrunk
+		let other = budget.register("other", TRIE_NODE_CACHE_WEIGHT);
+		assert_eq!(other.report(4), None);
+		assert_eq!(budget.used(), 12);
+		assert_eq!(cache.get(&H256::from(2)), Some(DBValue::from_slice(&[2; 4])));
+		assert_eq!(cache.get(&H256::from(1)), None);
+		assert_eq!(cache.size(), 4);
+		assert_eq!(budget.used(), 8);
+	}
+}
@@ -18,21 +18,24 @@
 //! full and light storages.
 
 use std::sync::Arc;
-use std::io;
+use std::{fs, io, process};
+use std::path::{Path, PathBuf};
 
 use kvdb::{KeyValueDB, DBTransaction};
 use kvdb_rocksdb::{Database, DatabaseConfig};
 
 use client;
-use codec::Decode;
+use client::backend::{CompactionProfile, MaintenanceProgress};
+use codec::{Decode, Encode};
 use trie::DBValue;
 use runtime_primitives::generic::BlockId;
-use runtime_primitives::traits::{As, Block as BlockT, Header as HeaderT, Zero};
+use runtime_primitives::traits::{As, Block as BlockT, Header as HeaderT, NumberFor, Zero};
 use DatabaseSettings;
+use migration;
 
 /// Number of columns in the db. Must be the same for both full && light dbs.
 /// Otherwise RocksDb will fail to open database && check its type.
-pub const NUM_COLUMNS: u32 = 9;
+pub const NUM_COLUMNS: u32 = 10;
 /// Meta column. The set of keys in the column is shared by full && light storages.
 pub const COLUMN_META: Option<u32> = Some(0);
 
@@ -50,6 +53,12 @@ pub mod meta_keys {
 	pub const GENESIS_HASH: &[u8; 3] = b"gen";
 	/// Leaves prefix list key.
 	pub const LEAF_PREFIX: &[u8; 4] = b"leaf";
+	/// Range of block numbers that are missing from the database.
+	pub const BLOCK_GAP: &[u8; 3] = b"gap";
+	/// Schema version of the database.
+	pub const DB_VERSION: &[u8; 7] = b"version";
+	/// Version and position of the migration that is being applied.
+	pub const MIGRATION_PROGRESS: &[u8; 9] = b"migration";
 }
 
 /// Database metadata.
@@ -133,14 +142,24 @@ pub fn db_err(err: io::Error) -> client::error::Error {
 	client::error::ErrorKind::Backend(err.description().into()).into()
 }
 
+/// File that every RocksDB database has, pointing to its current manifest.
+const ROCKSDB_CURRENT_FILE: &str = "CURRENT";
+/// Name of the file RocksDB locks the database with.
+const ROCKSDB_LOCK_FILE: &str = "LOCK";
+/// Extension of the immutable RocksDB table files.
+const ROCKSDB_TABLE_EXTENSION: &str = "sst";
+/// Number of attempts to make a consistent checkpoint of the database that is written to.
+const CHECKPOINT_ATTEMPTS: usize = 3;
+
 /// Open RocksDB database.
 pub fn open_database(config: &DatabaseSettings, col_meta: Option<u32>, db_type: &str) -> client::error::Result<Arc<KeyValueDB>> {
-	let mut db_config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
-	db_config.memory_budget = config.cache_size;
-	let path = config.path.to_str().ok_or_else(|| client::error::ErrorKind::Backend("Invalid database path".into()))?;
-	let db = Database::open(&db_config, &path).map_err(db_err)?;
+	if config.read_only {
+		return open_read_only(config, col_meta, db_type);
+	}
+	let db = open_rocksdb(config)?;
 
 	// check database type
+	let migrations = migration::migrations(db_type)?;
 	match db.get(col_meta, meta_keys::TYPE).map_err(db_err)? {
 		Some(stored_type) => {
 			if db_type.as_bytes() != &*stored_type {
@@ -149,15 +168,289 @@ pub fn open_database(config: &DatabaseSettings, col_meta: Option<u32>, db_type:
 			}
 		},
 		None => {
+			// new database has the latest schema
 			let mut transaction = DBTransaction::new();
 			transaction.put(col_meta, meta_keys::TYPE, db_type.as_bytes());
+			transaction.put(col_meta, meta_keys::DB_VERSION, &migration::latest_version(migrations).encode());
 			db.write(transaction).map_err(db_err)?;
 		},
 	}
 
+	migration::migrate(&db, col_meta, migrations, false)?;
+
 	Ok(Arc::new(db))
 }
 
+/// Open the checkpoint of the existing database, check that it has given type and the latest
+/// schema, which can't be migrated without writing to it, and wrap it into `ReadOnlyDb`.
+fn open_read_only(config: &DatabaseSettings, col_meta: Option<u32>, db_type: &str) -> client::error::Result<Arc<KeyValueDB>> {
+	let (checkpoint, db) = open_checkpoint(config)?;
+	match db.get(col_meta, meta_keys::TYPE).map_err(db_err)? {
+		Some(ref stored_type) if db_type.as_bytes() == &**stored_type => (),
+		Some(_) => return Err(client::error::ErrorKind::Backend(
+			format!("Unexpected database type. Expected: {}", db_type)).into()),
+		None => return Err(client::error::ErrorKind::Backend(
+			"Database doesn't exist and can't be created in read-only mode".into()).into()),
+	}
+
+	let version = migration::read_version(&db, col_meta)?;
+	let latest = migration::latest_version(migration::migrations(db_type)?);
+	if version != latest {
+		return Err(client::error::ErrorKind::Backend(format!(
+			"Database version {} differs from the supported version {} and can't be migrated in read-only mode", version, latest
+		)).into());
+	}
+
+	Ok(Arc::new(ReadOnlyDb { db: Arc::new(db), _checkpoint: Some(checkpoint) }))
+}
+
+/// Fail if the database is opened in read-only mode.
+pub fn ensure_writable(read_only: bool) -> client::error::Result<()> {
+	if read_only {
+		Err(client::error::ErrorKind::Backend("Database is opened in read-only mode".into()).into())
+	} else {
+		Ok(())
+	}
+}
+
+/// Database that rejects all writes. Buffered writes are dropped.
+pub struct ReadOnlyDb {
+	db: Arc<KeyValueDB>,
+	// declared after the database to be removed once the database is closed
+	_checkpoint: Option<Checkpoint>,
+}
+
+impl ReadOnlyDb {
+	/// Wrap the database, rejecting all writes to it.
+	pub fn new(db: Arc<KeyValueDB>) -> Self {
+		ReadOnlyDb { db, _checkpoint: None }
+	}
+}
+
+impl KeyValueDB for ReadOnlyDb {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<::kvdb::DBValue>> {
+		self.db.get(col, key)
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
+		self.db.get_by_prefix(col, prefix)
+	}
+
+	fn write_buffered(&self, _transaction: DBTransaction) {
+		warn!(target: "db", "Ignoring write to the database opened in read-only mode");
+	}
+
+	fn write(&self, _transaction: DBTransaction) -> io::Result<()> {
+		Err(read_only_err())
+	}
+
+	fn flush(&self) -> io::Result<()> {
+		Ok(())
+	}
+
+	fn iter<'a>(&'a self, col: Option<u32>) -> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
+		self.db.iter(col)
+	}
+
+	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8]) -> Box<Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
+		self.db.iter_from_prefix(col, prefix)
+	}
+
+	fn restore(&self, _new_db: &str) -> io::Result<()> {
+		Err(read_only_err())
+	}
+}
+
+fn read_only_err() -> io::Error {
+	io::Error::new(io::ErrorKind::PermissionDenied, "Database is opened in read-only mode")
+}
+
+fn open_rocksdb(config: &DatabaseSettings) -> client::error::Result<Database> {
+	open_rocksdb_at(config, &config.path).map_err(db_err)
+}
+
+fn open_rocksdb_at(config: &DatabaseSettings, path: &Path) -> io::Result<Database> {
+	let mut db_config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
+	db_config.memory_budget = config.cache_size;
+	let path = path.to_str().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid database path"))?;
+	Database::open(&db_config, &path)
+}
+
+/// Checkpoint of the database, removed once dropped.
+struct Checkpoint(PathBuf);
+
+impl Drop for Checkpoint {
+	fn drop(&mut self) {
+		if let Err(e) = fs::remove_dir_all(&self.0) {
+			warn!(target: "db", "Failed to remove database checkpoint at {}: {:?}", self.0.display(), e);
+		}
+	}
+}
+
+/// Make the checkpoint of the existing database and open it. The database may be used by the
+/// running node: RocksDB locks it, so it can't be opened directly. The immutable table files are
+/// hard linked into the checkpoint, the other files are copied. The checkpoint misses the writes
+/// made while it is being created. Creating it is retried if the files are replaced meanwhile.
+fn open_checkpoint(config: &DatabaseSettings) -> client::error::Result<(Checkpoint, Database)> {
+	// RocksDB creates the missing database on open
+	if !config.path.join(ROCKSDB_CURRENT_FILE).is_file() {
+		return Err(client::error::ErrorKind::Backend(
+			format!("Database doesn't exist at {} and can't be created in read-only mode", config.path.display())).into());
+	}
+
+	let checkpoint_path = config.path.with_extension(format!("read-only-{}", process::id()));
+	let mut last_error = None;
+	for _ in 0..CHECKPOINT_ATTEMPTS {
+		let checkpoint = Checkpoint(checkpoint_path.clone());
+		match make_checkpoint(&config.path, &checkpoint.0).and_then(|_| open_rocksdb_at(config, &checkpoint.0)) {
+			Ok(db) => return Ok((checkpoint, db)),
+			Err(e) => {
+				debug!(target: "db", "Failed to make database checkpoint at {}: {:?}", checkpoint_path.display(), e);
+				last_error = Some(e);
+			},
+		}
+	}
+
+	Err(client::error::ErrorKind::Backend(format!(
+		"Failed to make database checkpoint at {}: {:?}", checkpoint_path.display(), last_error
+	)).into())
+}
+
+/// Copy the files of the database at `from` to the empty directory `to`. The table files are
+/// linked after the other files are copied, so that the tables referenced by the copied manifest
+/// are present, unless they have been removed by compaction meanwhile.
+fn make_checkpoint(from: &Path, to: &Path) -> io::Result<()> {
+	if to.exists() {
+		fs::remove_dir_all(to)?;
+	}
+	fs::create_dir_all(to)?;
+
+	let is_table = |path: &Path| path.extension().map_or(false, |ext| ext == ROCKSDB_TABLE_EXTENSION);
+	for entry in fs::read_dir(from)? {
+		let path = entry?.path();
+		if !path.is_file() || is_table(&path) || path.file_name().map_or(true, |name| name == ROCKSDB_LOCK_FILE) {
+			continue;
+		}
+		fs::copy(&path, to.join(path.file_name().expect("checked above; qed")))?;
+	}
+	for entry in fs::read_dir(from)? {
+		let path = entry?.path();
+		if !is_table(&path) {
+			continue;
+		}
+		let target = to.join(path.file_name().expect("table files have extension; qed"));
+		if fs::hard_link(&path, &target).is_err() {
+			fs::copy(&path, &target)?;
+		}
+	}
+	Ok(())
+}
+
+/// Apply the schema migrations the existing database is missing, or only list them if `dry_run`
+/// is set. Returns the versions and descriptions of the migrations.
+pub fn migrate_database(config: &DatabaseSettings, col_meta: Option<u32>, dry_run: bool) -> client::error::Result<Vec<(u32, &'static str)>> {
+	if config.read_only && !dry_run {
+		return Err(client::error::ErrorKind::Backend("Database opened in read-only mode can't be migrated".into()).into());
+	}
+	let (_checkpoint, db) = match config.read_only {
+		true => open_checkpoint(config).map(|(checkpoint, db)| (Some(checkpoint), db))?,
+		false => (None, open_rocksdb(config)?),
+	};
+
+	let db_type = match db.get(col_meta, meta_keys::TYPE).map_err(db_err)? {
+		Some(db_type) => String::from_utf8_lossy(&db_type).into_owned(),
+		None => return Ok(Vec::new()),
+	};
+	let migrations = migration::migrate(&db, col_meta, migration::migrations(&db_type)?, dry_run)?;
+	Ok(migrations.into_iter().map(|migration| (migration.version, migration.description)).collect())
+}
+
+/// Copy all entries of all columns from one database to another, in batches of the profile size.
+/// Returns `false` if copying has been aborted by the progress observer.
+pub fn copy_columns(
+	from: &KeyValueDB,
+	to: &KeyValueDB,
+	profile: &CompactionProfile,
+	progress: &MaintenanceProgress,
+) -> client::error::Result<bool> {
+	let batch_size = ::std::cmp::max(profile.batch_size, 1);
+	for column in 0..NUM_COLUMNS {
+		let mut entries = 0u64;
+		let mut batch = DBTransaction::new();
+		let mut batch_len = 0;
+		let mut iter = from.iter(Some(column)).peekable();
+		while let Some((key, value)) = iter.next() {
+			batch.put(Some(column), &key, &value);
+			batch_len += 1;
+			entries += 1;
+
+			if batch_len == batch_size || iter.peek().is_none() {
+				to.write(batch).map_err(db_err)?;
+				batch = DBTransaction::new();
+				batch_len = 0;
+
+				if !progress.on_progress(column, NUM_COLUMNS, entries) {
+					return Ok(false);
+				}
+				if let Some(delay) = profile.batch_delay {
+					::std::thread::sleep(delay);
+				}
+			}
+		}
+	}
+
+	Ok(true)
+}
+
+/// Compact RocksDB database by copying its live entries to a fresh database, which then replaces
+/// the original one. Returns `false` if compaction has been aborted.
+pub fn compact_database(
+	config: &DatabaseSettings,
+	profile: &CompactionProfile,
+	progress: &MaintenanceProgress,
+) -> client::error::Result<bool> {
+	if config.read_only {
+		return Err(client::error::ErrorKind::Backend("Database opened in read-only mode can't be compacted".into()).into());
+	}
+	if !config.path.join(ROCKSDB_CURRENT_FILE).is_file() {
+		return Err(client::error::ErrorKind::Backend(
+			format!("Database doesn't exist at {}", config.path.display())).into());
+	}
+	let db = open_rocksdb(config)?;
+	compact_open_database(&db, &config.path, profile, progress)
+}
+
+/// Compact the open RocksDB database at given path, replacing it with the compacted copy. The
+/// database must not be written to until compaction completes. Returns `false` if compaction has
+/// been aborted.
+pub fn compact_open_database(
+	db: &KeyValueDB,
+	path: &Path,
+	profile: &CompactionProfile,
+	progress: &MaintenanceProgress,
+) -> client::error::Result<bool> {
+	let compacted_path = path.with_extension("compacted");
+	let compacted_path_str = compacted_path.to_str()
+		.ok_or_else(|| client::error::ErrorKind::Backend("Invalid database path".into()))?;
+	if compacted_path.exists() {
+		fs::remove_dir_all(&compacted_path).map_err(db_err)?;
+	}
+
+	let completed = {
+		let compacted = Database::open(&DatabaseConfig::with_columns(Some(NUM_COLUMNS)), compacted_path_str)
+			.map_err(db_err)?;
+		copy_columns(db, &compacted, profile, progress)?
+	};
+
+	if !completed {
+		fs::remove_dir_all(&compacted_path).map_err(db_err)?;
+		return Ok(false);
+	}
+
+	db.restore(compacted_path_str).map_err(db_err)?;
+	Ok(true)
+}
+
 /// Read database column entry for the given block.
 pub fn read_db<Block>(db: &KeyValueDB, col_index: Option<u32>, col: Option<u32>, id: BlockId<Block>) -> client::error::Result<Option<DBValue>>
 	where
@@ -187,6 +480,23 @@ pub fn read_header<Block: BlockT>(
 	}
 }
 
+/// Read the hash of the best chain block of given number from the canonical index column.
+pub fn read_canonical_hash<Block: BlockT>(
+	db: &KeyValueDB,
+	col_canonical: Option<u32>,
+	number: NumberFor<Block>,
+) -> client::error::Result<Option<Block::Hash>> {
+	match db.get(col_canonical, number_to_lookup_key(number).as_ref()).map_err(db_err)? {
+		Some(hash) => match Block::Hash::decode(&mut &hash[..]) {
+			Some(hash) => Ok(Some(hash)),
+			None => Err(
+				client::error::ErrorKind::Backend("Error decoding canonical hash".into()).into()
+			),
+		},
+		None => Ok(None),
+	}
+}
+
 /// Read meta from the database.
 pub fn read_meta<Block>(db: &KeyValueDB, col_meta: Option<u32>, col_header: Option<u32>) -> Result<
 	Meta<<<Block as BlockT>::Header as HeaderT>::Number, Block::Hash>,
@@ -217,7 +527,7 @@ pub fn read_meta<Block>(db: &KeyValueDB, col_meta: Option<u32>, col_header: Opti
 			}).map_err(db_err)?
 		{
 			let hash = header.hash();
-			debug!("DB Opened blockchain db, fetched {} = {:?} ({})", desc, hash, header.number());
+			debug!(target: "db", "DB Opened blockchain db, fetched {} = {:?} ({})", desc, hash, header.number());
 			Ok((hash, *header.number()))
 		} else {
 			Ok((genesis_hash.clone(), Zero::zero()))
@@ -235,3 +545,37 @@ pub fn read_meta<Block>(db: &KeyValueDB, col_meta: Option<u32>, col_header: Opti
 		genesis_hash,
 	})
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use PruningMode;
+
+	#[test]
+	fn database_used_by_node_is_opened_read_only() {
+		let path = env::temp_dir().join(format!("substrate-db-read-only-test-{}", process::id()));
+		let mut settings = DatabaseSettings {
+			cache_size: None,
+			path: path.clone(),
+			pruning: PruningMode::default(),
+			read_only: false,
+			memory_budget: None,
+		};
+
+		{
+			let db = open_database(&settings, COLUMN_META, "full").unwrap();
+			let mut transaction = DBTransaction::new();
+			transaction.put(Some(1), b"key", b"value");
+			db.write(transaction).unwrap();
+
+			settings.read_only = true;
+			let read_only = open_database(&settings, COLUMN_META, "full").unwrap();
+			assert_eq!(read_only.get(Some(1), b"key").unwrap().map(|value| value.to_vec()), Some(b"value".to_vec()));
+			assert!(read_only.write(DBTransaction::new()).is_err());
+		}
+
+		assert!(!path.with_extension(format!("read-only-{}", process::id())).exists());
+		fs::remove_dir_all(&path).unwrap();
+	}
+}
@@ -16,6 +16,7 @@
 
 //! Substrate Client data backend
 
+use std::time::Duration;
 use error;
 use primitives::AuthorityId;
 use runtime_primitives::{generic::BlockId, Justification, StorageMap, ChildrenStorageMap};
@@ -46,6 +47,32 @@ impl NewBlockState {
 	}
 }
 
+/// Parameters of the backend storage compaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactionProfile {
+	/// Number of entries rewritten in a single batch.
+	pub batch_size: usize,
+	/// Pause after every batch, leaving disk bandwidth to other processes.
+	pub batch_delay: Option<Duration>,
+}
+
+impl Default for CompactionProfile {
+	fn default() -> Self {
+		CompactionProfile {
+			batch_size: 10_000,
+			batch_delay: None,
+		}
+	}
+}
+
+/// Observer of the backend maintenance.
+pub trait MaintenanceProgress {
+	/// Called after every processed batch with the index of the column being compacted,
+	/// the total number of columns and the number of entries of the column processed so far.
+	/// Returning `false` aborts the maintenance, leaving the storage untouched.
+	fn on_progress(&self, column: u32, columns: u32, entries: u64) -> bool;
+}
+
 /// Block insertion operation. Keeps hold if the inserted block state and data.
 pub trait BlockImportOperation<Block, H>
 where
@@ -106,12 +133,22 @@ where
 	/// Commit block insertion.
 	fn commit_operation(&self, transaction: Self::BlockImportOperation) -> error::Result<()>;
 	/// Finalize block with given Id. This should only be called if the parent of the given
-	/// block has been finalized.
-	fn finalize_block(&self, block: BlockId<Block>) -> error::Result<()>;
+	/// block has been finalized. The justification, if given, is stored along with the block.
+	fn finalize_block(&self, block: BlockId<Block>, justification: Option<Justification>) -> error::Result<()>;
 	/// Returns reference to blockchain backend.
 	fn blockchain(&self) -> &Self::Blockchain;
 	/// Returns reference to changes trie storage.
 	fn changes_trie_storage(&self) -> Option<&Self::ChangesTrieStorage>;
+	/// Number of recent blocks whose changes tries are kept by the backend. `None` if changes
+	/// tries are never pruned.
+	fn changes_tries_blocks_to_keep(&self) -> Option<u64> {
+		None
+	}
+	/// Insert nodes of the changes trie of an already imported block, whose changes trie has
+	/// not been built at import.
+	fn insert_changes_trie(&self, _block: BlockId<Block>, _update: MemoryDB<H>) -> error::Result<()> {
+		Err(error::ErrorKind::ChangesTriesNotSupported.into())
+	}
 	/// Returns state backend with post-state of given block.
 	fn state_at(&self, block: BlockId<Block>) -> error::Result<Self::State>;
 	/// Attempts to revert the chain by `n` blocks. Returns the number of blocks that were
@@ -121,6 +158,22 @@ where
 	fn insert_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a(&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>(&self, insert: I, delete: D) -> error::Result<()>;
 	/// Query auxiliary data from key-value store.
 	fn get_aux(&self, key: &[u8]) -> error::Result<Option<Vec<u8>>>;
+	/// Prevent the state of the given block from being pruned. Pins are reference counted,
+	/// so every call must be matched by a call to `unpin_block`.
+	fn pin_block(&self, hash: &Block::Hash) -> error::Result<()>;
+	/// Release a single pin of the given block, allowing it to be pruned once unreferenced.
+	fn unpin_block(&self, hash: &Block::Hash);
+	/// Warm up the backend caches for reading the given keys from the state of the given block.
+	/// Backends without caches do nothing.
+	fn prefetch_state(&self, _block: BlockId<Block>, _keys: &[Vec<u8>]) -> error::Result<()> {
+		Ok(())
+	}
+	/// Compact the storage, reclaiming the space left by pruned and deleted data. Writes wait
+	/// until the maintenance completes, so it's meant to be run while the node is idle. Returns
+	/// `false` if maintenance has been aborted. Backends without persistent storage do nothing.
+	fn maintenance(&self, _profile: CompactionProfile, _progress: &MaintenanceProgress) -> error::Result<bool> {
+		Ok(true)
+	}
 }
 
 /// Mark for all Backend implementations, that are making use of state data, stored locally.
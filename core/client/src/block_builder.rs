@@ -16,16 +16,18 @@
 
 //! Utility struct to build a block.
 
+use std::collections::HashSet;
 use std::vec::Vec;
 use std::marker::PhantomData;
-use codec::Encode;
+use codec::{Decode, Encode};
 use state_machine;
-use runtime_primitives::traits::{Header as HeaderT, Hash, Block as BlockT, One, HashFor};
+use runtime_primitives::traits::{Header as HeaderT, Hash, Block as BlockT, One, HashFor, Digest, DigestItemFor};
 use runtime_primitives::generic::BlockId;
 use runtime_api::BlockBuilder as BlockBuilderAPI;
 use {backend, error, Client, CallExecutor};
-use runtime_primitives::ApplyOutcome;
+use runtime_primitives::{ApplyOutcome, ApplyResult};
 use primitives::{Blake2Hasher, H256};
+use primitives::storage::StorageKey;
 use hash_db::Hasher;
 
 /// Utility for building new (valid) blocks from a stream of extrinsics.
@@ -43,9 +45,37 @@ where
 	client: &'a Client<B, E, Block>,
 	block_id: BlockId<Block>,
 	changes: state_machine::OverlayedChanges,
+	proof: Option<ProofRecorder>,
 	_marker: PhantomData<H>,
 }
 
+/// Trie nodes read while building the block, gathered to estimate the size of the proof of the
+/// block execution.
+struct ProofRecorder {
+	nodes: HashSet<Vec<u8>>,
+	size: usize,
+	budget: Option<usize>,
+}
+
+impl ProofRecorder {
+	/// Nodes of the proof that haven't been recorded yet, along with the estimated size of the
+	/// proof that includes them.
+	fn new_nodes(&self, proof: Vec<Vec<u8>>) -> (Vec<Vec<u8>>, usize) {
+		let new_nodes: Vec<_> = proof.into_iter()
+			.filter(|node| !self.nodes.contains(node))
+			.collect();
+		let size = self.size + new_nodes.iter().map(|node| node.len()).sum::<usize>();
+		(new_nodes, size)
+	}
+
+	/// Record the nodes of the proof, regardless of the budget.
+	fn record(&mut self, proof: Vec<Vec<u8>>) {
+		let (new_nodes, size) = self.new_nodes(proof);
+		self.nodes.extend(new_nodes);
+		self.size = size;
+	}
+}
+
 impl<'a, B, E, Block> BlockBuilder<'a, B, E, Block, Blake2Hasher>
 where
 	B: backend::Backend<Block, Blake2Hasher> + 'a,
@@ -60,6 +90,42 @@ where
 	/// Create a new instance of builder from the given client using a particular block's ID to
 	/// build upon.
 	pub fn at_block(block_id: &BlockId<Block>, client: &'a Client<B, E, Block>) -> error::Result<Self> {
+		Self::at_block_with_pre_digest(block_id, client, Vec::new())
+	}
+
+	/// Create a new instance of builder from the given client using a particular block's ID to
+	/// build upon. The pre-runtime digest items are passed to the runtime on block initialisation.
+	pub fn at_block_with_pre_digest(
+		block_id: &BlockId<Block>,
+		client: &'a Client<B, E, Block>,
+		pre_digest: Vec<DigestItemFor<Block>>,
+	) -> error::Result<Self> {
+		Self::at_block_with_proof(block_id, client, pre_digest, None)
+	}
+
+	/// Create a new instance of builder from the given client using a particular block's ID to
+	/// build upon, recording the trie nodes read from the block initialisation through its
+	/// finalisation to estimate the size of the proof of the block execution. If the budget is
+	/// given, extrinsics that would make the estimated size exceed it are rejected.
+	pub fn at_block_recording_proof(
+		block_id: &BlockId<Block>,
+		client: &'a Client<B, E, Block>,
+		pre_digest: Vec<DigestItemFor<Block>>,
+		budget: Option<usize>,
+	) -> error::Result<Self> {
+		Self::at_block_with_proof(block_id, client, pre_digest, Some(ProofRecorder {
+			nodes: HashSet::new(),
+			size: 0,
+			budget,
+		}))
+	}
+
+	fn at_block_with_proof(
+		block_id: &BlockId<Block>,
+		client: &'a Client<B, E, Block>,
+		pre_digest: Vec<DigestItemFor<Block>>,
+		mut proof: Option<ProofRecorder>,
+	) -> error::Result<Self> {
 		let number = client.block_number_from_id(block_id)?
 			.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{}", block_id)))?
 			+ One::one();
@@ -67,16 +133,31 @@ where
 		let parent_hash = client.block_hash_from_id(block_id)?
 			.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{}", block_id)))?;
 
-		let mut changes = Default::default();
+		let mut digest = <<Block as BlockT>::Header as HeaderT>::Digest::default();
+		for item in pre_digest {
+			digest.push(item);
+		}
+
+		let mut changes = state_machine::OverlayedChanges::default();
+		changes.set_chain_entropy(client.chain_entropy(parent_hash)?);
+		changes.set_recent_block_hashes(client.recent_block_hashes(parent_hash)?);
 		let header = <<Block as BlockT>::Header as HeaderT>::new(
 			number,
 			Default::default(),
 			Default::default(),
 			parent_hash,
-			Default::default()
+			digest
 		);
 
-		client.initialise_block(block_id, &mut changes, &header)?;
+		match proof {
+			Some(ref mut recorder) => {
+				let state = client.state_at(block_id)?;
+				let (_, initialise_proof, _) = client.executor()
+					.prove_at_state(state, &mut changes, "initialise_block", &header.encode())?;
+				recorder.record(initialise_proof);
+			},
+			None => client.initialise_block(block_id, &mut changes, &header)?,
+		}
 		changes.commit_prospective();
 
 		Ok(BlockBuilder {
@@ -85,6 +166,7 @@ where
 			client,
 			block_id: *block_id,
 			changes,
+			proof,
 			_marker: Default::default(),
 		})
 	}
@@ -92,11 +174,30 @@ where
 	/// Push onto the block's list of extrinsics. This will ensure the extrinsic
 	/// can be validly executed (by executing it); if it is invalid, it'll be returned along with
 	/// the error. Otherwise, it will return a mutable reference to self (in order to chain).
+	///
+	/// If the proof is being recorded and the extrinsic would make it exceed the budget, the
+	/// extrinsic isn't included and `ProofBudgetExceeded` error is returned.
 	pub fn push(&mut self, xt: <Block as BlockT>::Extrinsic) -> error::Result<()> {
-		match self.client.apply_extrinsic(&self.block_id, &mut self.changes, &xt) {
-			Ok(result) => {
+		let applied = if self.proof.is_some() {
+			self.apply_with_proof(&xt)
+		} else {
+			self.client.apply_extrinsic(&self.block_id, &mut self.changes, &xt).map(|result| (result, Vec::new()))
+		};
+		match applied {
+			Ok((result, proof)) => {
 				match result {
 					Ok(ApplyOutcome::Success) | Ok(ApplyOutcome::Fail) => {
+						if let Some(ref mut recorder) = self.proof {
+							let (new_nodes, size) = recorder.new_nodes(proof);
+							if let Some(budget) = recorder.budget {
+								if size > budget {
+									self.changes.discard_prospective();
+									return Err(error::ErrorKind::ProofBudgetExceeded(size, budget).into());
+								}
+							}
+							recorder.nodes.extend(new_nodes);
+							recorder.size = size;
+						}
 						self.extrinsics.push(xt);
 						self.changes.commit_prospective();
 						Ok(())
@@ -114,15 +215,80 @@ where
 		}
 	}
 
+	/// Estimated size of the proof of the block initialisation and the pushed extrinsics, if the
+	/// proof is being recorded.
+	pub fn proof_size(&self) -> Option<usize> {
+		self.proof.as_ref().map(|recorder| recorder.size)
+	}
+
+	/// Apply the extrinsic on the proving backend, returning the result and the read trie nodes.
+	fn apply_with_proof(&mut self, xt: &<Block as BlockT>::Extrinsic) -> error::Result<(ApplyResult, Vec<Vec<u8>>)> {
+		let state = self.client.state_at(&self.block_id)?;
+		let (result, proof, _) = self.client.executor().prove_at_state(state, &mut self.changes, "apply_extrinsic", &xt.encode())?;
+		let result = ApplyResult::decode(&mut &result[..])
+			.ok_or_else(|| error::ErrorKind::CallResultDecode("apply_extrinsic"))?;
+		Ok((result, proof))
+	}
+
+	/// Finalise the block on the proving backend, recording the read trie nodes.
+	fn finalise_with_proof(&mut self) -> error::Result<<Block as BlockT>::Header> {
+		let state = self.client.state_at(&self.block_id)?;
+		let (result, proof, _) = self.client.executor().prove_at_state(state, &mut self.changes, "finalise_block", &[])?;
+		if let Some(ref mut recorder) = self.proof {
+			recorder.record(proof);
+		}
+		<Block as BlockT>::Header::decode(&mut &result[..])
+			.ok_or_else(|| error::ErrorKind::CallResultDecode("finalise_block").into())
+	}
+
+	/// Apply the extrinsic on top of the pushed ones without including it into the block, and read
+	/// the value of given storage key after the application. The builder is left unchanged.
+	pub fn dry_run(
+		&mut self,
+		xt: &<Block as BlockT>::Extrinsic,
+		key: Option<&[u8]>,
+	) -> error::Result<(ApplyResult, Option<Vec<u8>>)> {
+		let result = self.client.apply_extrinsic(&self.block_id, &mut self.changes, xt);
+		let result = match (result, key) {
+			(Ok(result), Some(key)) => self.storage(key).map(|value| (result, value)),
+			(Ok(result), None) => Ok((result, None)),
+			(Err(e), _) => Err(e),
+		};
+		self.changes.discard_prospective();
+		result
+	}
+
+	/// Read storage value of the block being built.
+	fn storage(&self, key: &[u8]) -> error::Result<Option<Vec<u8>>> {
+		match self.changes.storage(key) {
+			Some(value) => Ok(value.map(|value| value.to_vec())),
+			None => Ok(self.client.storage(&self.block_id, &StorageKey(key.to_vec()))?.map(|data| data.0)),
+		}
+	}
+
 	/// Consume the builder to return a valid `Block` containing all pushed extrinsics.
-	pub fn bake(mut self) -> error::Result<Block> {
-		self.header = self.client.finalise_block(&self.block_id, &mut self.changes)?;
+	pub fn bake(self) -> error::Result<Block> {
+		self.bake_with_proof_size().map(|(block, _)| block)
+	}
+
+	/// Consume the builder to return a valid `Block` containing all pushed extrinsics, along with
+	/// the estimated size of the proof of the whole block execution, if the proof is being recorded.
+	pub fn bake_with_proof_size(mut self) -> error::Result<(Block, Option<usize>)> {
+		self.header = if self.proof.is_some() {
+			self.finalise_with_proof()?
+		} else {
+			self.client.finalise_block(&self.block_id, &mut self.changes)?
+		};
+		let proof_size = self.proof_size();
 
 		debug_assert_eq!(
 			self.header.extrinsics_root().clone(),
 			HashFor::<Block>::ordered_trie_root(self.extrinsics.iter().map(Encode::encode)),
 		);
 
-		Ok(<Block as BlockT>::new(self.header, self.extrinsics))
+		// the block is likely to be imported by this node, so its changes are kept to skip the execution
+		self.client.note_own_block_changes(&self.header, self.changes);
+
+		Ok((<Block as BlockT>::new(self.header, self.extrinsics), proof_size))
 	}
 }
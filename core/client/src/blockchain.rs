@@ -16,12 +16,16 @@
 
 //! Substrate blockchain trait
 
+use futures::{future, Future};
 use primitives::AuthorityId;
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor};
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::Justification;
 
-use error::{ErrorKind, Result};
+use error::{Error, ErrorKind, Result};
+
+/// Future resolving to the blockchain data, which may be fetched from the remote nodes.
+pub type BlockchainFuture<'a, T> = Box<Future<Item=T, Error=Error> + 'a>;
 
 /// Blockchain database header backend. Does not perform any validation.
 pub trait HeaderBackend<Block: BlockT>: Send + Sync {
@@ -40,12 +44,35 @@ pub trait HeaderBackend<Block: BlockT>: Send + Sync {
 	fn expect_header(&self, id: BlockId<Block>) -> Result<Block::Header> {
 		self.header(id)?.ok_or_else(|| ErrorKind::UnknownBlock(format!("{}", id)).into())
 	}
+
+	/// Get block header stored locally. Unlike `header`, never fetches the header from the
+	/// remote nodes.
+	fn local_header(&self, id: BlockId<Block>) -> Result<Option<Block::Header>> {
+		self.header(id)
+	}
+
+	/// Get hash of the block stored locally. Unlike `hash`, never fetches the header from the
+	/// remote nodes.
+	fn local_hash(&self, number: NumberFor<Block>) -> Result<Option<Block::Hash>> {
+		self.hash(number)
+	}
+
+	/// Get block hash by number, retrieving it from the remote nodes if it isn't stored locally.
+	/// Must not be waited for on the network thread, which handles the remote responses.
+	fn hash_future<'a>(&'a self, number: NumberFor<Block>) -> BlockchainFuture<'a, Option<Block::Hash>> {
+		Box::new(future::result(self.hash(number)))
+	}
 }
 
 /// Blockchain database backend. Does not perform any validation.
 pub trait Backend<Block: BlockT>: HeaderBackend<Block> {
 	/// Get block body. Returns `None` if block is not found.
 	fn body(&self, id: BlockId<Block>) -> Result<Option<Vec<<Block as BlockT>::Extrinsic>>>;
+	/// Get block body, retrieving it from the remote nodes if it isn't stored locally. Must not
+	/// be waited for on the network thread, which handles the remote responses.
+	fn body_future<'a>(&'a self, id: BlockId<Block>) -> BlockchainFuture<'a, Option<Vec<<Block as BlockT>::Extrinsic>>> {
+		Box::new(future::result(self.body(id)))
+	}
 	/// Get block justification. Returns `None` if justification does not exist.
 	fn justification(&self, id: BlockId<Block>) -> Result<Option<Justification>>;
 	/// Get last finalized block hash.
@@ -57,6 +84,13 @@ pub trait Backend<Block: BlockT>: HeaderBackend<Block> {
 	/// in other words, that have no children, are chain heads.
 	/// Results must be ordered best (longest, heighest) chain first.
 	fn leaves(&self) -> Result<Vec<Block::Hash>>;
+
+	/// Returns the inclusive range of numbers of canonical blocks that are missing from the
+	/// database, because the chain has been imported starting from a later block. Blocks of
+	/// the gap are imported in reverse order, each being the parent of the block above the gap.
+	fn gap(&self) -> Result<Option<(NumberFor<Block>, NumberFor<Block>)>> {
+		Ok(None)
+	}
 }
 
 /// Blockchain optional data cache.
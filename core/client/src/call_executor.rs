@@ -16,20 +16,31 @@
 
 use std::sync::Arc;
 use std::cmp::Ord;
+use std::collections::{HashMap, VecDeque};
+use parking_lot::Mutex;
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::Block as BlockT;
 use state_machine::{self, OverlayedChanges, Ext,
-	CodeExecutor, ExecutionManager, native_when_possible};
+	CodeExecutor, ExecutionContext, ExecutionManager, StorageDiff, native_when_possible};
 use executor::{RuntimeVersion, RuntimeInfo, NativeVersion};
 use hash_db::Hasher;
 use trie::MemoryDB;
 use codec::Decode;
-use primitives::{H256, Blake2Hasher};
+use primitives::{H256, Blake2Hasher, LocalKeys};
+use primitives::offchain;
 use primitives::storage::well_known_keys;
 
 use backend;
+use blockchain::HeaderBackend;
 use error;
 
+/// Maximal number of blocks for which runtime call results are cached.
+const CALL_CACHE_BLOCKS: usize = 64;
+
+/// Runtime API methods that do not modify state and whose results only depend on
+/// the block they are executed at. Results of these calls are cached by `LocalCallExecutor`.
+pub const PURE_METHODS: &[&str] = &["authorities", "version", "metadata"];
+
 /// Information regarding the result of a call.
 #[derive(Debug, Clone)]
 pub struct CallResult {
@@ -37,6 +48,9 @@ pub struct CallResult {
 	pub return_data: Vec<u8>,
 	/// The changes made to the state by the call.
 	pub changes: OverlayedChanges,
+	/// Storage keys read by the call, in order of the first access. Only reported by remote
+	/// calls requesting a trace, and not covered by the execution proof.
+	pub accessed_keys: Vec<Vec<u8>>,
 }
 
 /// Method call executor.
@@ -64,21 +78,37 @@ where
 	/// No changes are made.
 	fn runtime_version(&self, id: &BlockId<B>) -> Result<RuntimeVersion, error::Error>;
 
-	/// Execute a call to a contract on top of given state.
+	/// Execute a call to a contract on top of given state in the given execution context. The
+	/// `local_keys` are ignored unless the code is executed in the `Offchain` context.
 	///
 	/// No changes are made.
 	fn call_at_state<
 		S: state_machine::Backend<H>,
-		F: FnOnce(Result<Vec<u8>, Self::Error>, Result<Vec<u8>, Self::Error>) -> Result<Vec<u8>, Self::Error>,
+		F: FnOnce(Result<Vec<u8>, Self::Error>, Result<Vec<u8>, Self::Error>, StorageDiff) -> Result<Vec<u8>, Self::Error>,
 	>(&self,
 		state: &S,
 		overlay: &mut OverlayedChanges,
 		method: &str,
 		call_data: &[u8],
+		context: ExecutionContext,
+		local_keys: Option<&LocalKeys>,
 		manager: ExecutionManager<F>
 	) -> Result<(Vec<u8>, S::Transaction, Option<MemoryDB<H>>), error::Error>;
 
-	/// Execute a call to a contract on top of given state, gathering execution proof.
+	/// Execute the offchain call on top of state in a block of given hash, with access to the
+	/// offchain externalities and the `local_keys`.
+	///
+	/// No changes are made.
+	fn call_offchain(&self,
+		id: &BlockId<B>,
+		method: &str,
+		call_data: &[u8],
+		offchain: &mut offchain::Externalities,
+		local_keys: Option<&LocalKeys>,
+	) -> Result<Vec<u8>, error::Error>;
+
+	/// Execute a call to a contract on top of given state, gathering execution proof and
+	/// the storage keys read by the call.
 	///
 	/// No changes are made.
 	fn prove_at_state<S: state_machine::Backend<H>>(&self,
@@ -86,23 +116,90 @@ where
 		overlay: &mut OverlayedChanges,
 		method: &str,
 		call_data: &[u8]
-	) -> Result<(Vec<u8>, Vec<Vec<u8>>), error::Error>;
+	) -> Result<(Vec<u8>, Vec<Vec<u8>>, Vec<Vec<u8>>), error::Error>;
 
 	/// Get runtime version if supported.
 	fn native_runtime_version(&self) -> Option<&NativeVersion>;
 }
 
+/// Bounded cache of pure runtime call results, keyed by (block, method, call data).
+/// When full, results of the least recently inserted block are evicted.
+#[derive(Default)]
+struct CallCache {
+	results: HashMap<H256, HashMap<(String, Vec<u8>), Vec<u8>>>,
+	blocks: VecDeque<H256>,
+}
+
+impl CallCache {
+	fn get(&self, block: &H256, method: &str, call_data: &[u8]) -> Option<Vec<u8>> {
+		self.results.get(block)
+			.and_then(|results| results.get(&(method.to_owned(), call_data.to_vec())))
+			.cloned()
+	}
+
+	fn insert(&mut self, block: H256, method: &str, call_data: &[u8], result: Vec<u8>) {
+		if !self.results.contains_key(&block) {
+			if self.blocks.len() >= CALL_CACHE_BLOCKS {
+				if let Some(evicted) = self.blocks.pop_front() {
+					self.results.remove(&evicted);
+				}
+			}
+			self.blocks.push_back(block);
+		}
+		self.results.entry(block).or_insert_with(Default::default)
+			.insert((method.to_owned(), call_data.to_vec()), result);
+	}
+
+	fn invalidate(&mut self, block: &H256) {
+		if self.results.remove(block).is_some() {
+			self.blocks.retain(|b| b != block);
+		}
+	}
+}
+
 /// Call executor that executes methods locally, querying all required
 /// data from local backend.
 pub struct LocalCallExecutor<B, E> {
 	backend: Arc<B>,
 	executor: E,
+	cache: Arc<Mutex<CallCache>>,
 }
 
 impl<B, E> LocalCallExecutor<B, E> {
 	/// Creates new instance of local call executor.
 	pub fn new(backend: Arc<B>, executor: E) -> Self {
-		LocalCallExecutor { backend, executor }
+		LocalCallExecutor {
+			backend,
+			executor,
+			cache: Default::default(),
+		}
+	}
+}
+
+impl<B, E> LocalCallExecutor<B, E> where E: CodeExecutor<Blake2Hasher> {
+	/// Execute the call `repeat` times against the frozen state of the given block. Writes
+	/// made by every run are discarded before the next one.
+	pub fn benchmark<Block>(
+		&self,
+		id: &BlockId<Block>,
+		method: &str,
+		call_data: &[u8],
+		strategy: state_machine::ExecutionStrategy,
+		repeat: u32,
+	) -> error::Result<state_machine::BenchmarkResult> where
+		B: backend::LocalBackend<Block, Blake2Hasher>,
+		Block: BlockT<Hash=H256>,
+	{
+		let state = self.backend.state_at(*id)?;
+		state_machine::benchmark(
+			&state,
+			&OverlayedChanges::default(),
+			&self.executor,
+			method,
+			call_data,
+			strategy,
+			repeat,
+		).map_err(Into::into)
 	}
 }
 
@@ -111,6 +208,7 @@ impl<B, E> Clone for LocalCallExecutor<B, E> where E: Clone {
 		LocalCallExecutor {
 			backend: self.backend.clone(),
 			executor: self.executor.clone(),
+			cache: self.cache.clone(),
 		}
 	}
 }
@@ -128,15 +226,47 @@ where
 		method: &str,
 		call_data: &[u8],
 	) -> error::Result<CallResult> {
+		let is_pure = PURE_METHODS.contains(&method);
+		let block = match *id {
+			BlockId::Hash(hash) => Some(hash),
+			BlockId::Number(number) => self.backend.blockchain().hash(number)?,
+		};
+
+		let state = match self.backend.state_at(*id) {
+			Ok(state) => state,
+			Err(err) => {
+				// the state may have been pruned, cached results are no longer valid
+				if let Some(block) = block {
+					self.cache.lock().invalidate(&block);
+				}
+				return Err(err);
+			},
+		};
+
+		if is_pure {
+			if let Some(return_data) = block.and_then(|block| self.cache.lock().get(&block, method, call_data)) {
+				return Ok(CallResult { return_data, changes: OverlayedChanges::default(), accessed_keys: Vec::new() });
+			}
+		}
+
 		let mut changes = OverlayedChanges::default();
 		let (return_data, _, _) = self.call_at_state(
-			&self.backend.state_at(*id)?,
+			&state,
 			&mut changes,
 			method,
 			call_data,
+			ExecutionContext::Other,
+			None,
 			native_when_possible(),
 		)?;
-		Ok(CallResult { return_data, changes })
+
+		if is_pure {
+			if let Some(block) = block {
+				self.cache.lock().insert(block, method, call_data, return_data.clone());
+			}
+		}
+
+		Ok(CallResult { return_data, changes, accessed_keys: Vec::new() })
 	}
 
 	fn runtime_version(&self, id: &BlockId<Block>) -> error::Result<RuntimeVersion> {
@@ -159,12 +289,14 @@ where
 
 	fn call_at_state<
 		S: state_machine::Backend<Blake2Hasher>,
-		F: FnOnce(Result<Vec<u8>, Self::Error>, Result<Vec<u8>, Self::Error>) -> Result<Vec<u8>, Self::Error>,
+		F: FnOnce(Result<Vec<u8>, Self::Error>, Result<Vec<u8>, Self::Error>, StorageDiff) -> Result<Vec<u8>, Self::Error>,
 	>(&self,
 		state: &S,
 		changes: &mut OverlayedChanges,
 		method: &str,
 		call_data: &[u8],
+		context: ExecutionContext,
+		local_keys: Option<&LocalKeys>,
 		manager: ExecutionManager<F>,
 	) -> error::Result<(Vec<u8>, S::Transaction, Option<MemoryDB<Blake2Hasher>>)> {
 		state_machine::execute_using_consensus_failure_handler(
@@ -174,17 +306,38 @@ where
 			&self.executor,
 			method,
 			call_data,
+			context,
+			local_keys,
 			manager,
 		).map_err(Into::into)
 	}
 
+	fn call_offchain(&self,
+		id: &BlockId<Block>,
+		method: &str,
+		call_data: &[u8],
+		offchain: &mut offchain::Externalities,
+		local_keys: Option<&LocalKeys>,
+	) -> error::Result<Vec<u8>> {
+		let state = self.backend.state_at(*id)?;
+		state_machine::execute_offchain(
+			&state,
+			self.backend.changes_trie_storage(),
+			&self.executor,
+			method,
+			call_data,
+			offchain,
+			local_keys,
+		).map_err(Into::into)
+	}
+
 	fn prove_at_state<S: state_machine::Backend<Blake2Hasher>>(&self,
 		state: S,
 		changes: &mut OverlayedChanges,
 		method: &str,
 		call_data: &[u8]
-	) -> Result<(Vec<u8>, Vec<Vec<u8>>), error::Error> {
-		state_machine::prove_execution(
+	) -> Result<(Vec<u8>, Vec<Vec<u8>>, Vec<Vec<u8>>), error::Error> {
+		state_machine::prove_execution_with_keys(
 			state,
 			changes,
 			&self.executor,
@@ -198,3 +351,50 @@ where
 		Some(self.executor.native_version())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn call_cache_is_bounded() {
+		let mut cache = CallCache::default();
+		for i in 0..CALL_CACHE_BLOCKS as u64 + 1 {
+			cache.insert(H256::from(i), "authorities", &[], vec![i as u8]);
+		}
+
+		assert_eq!(cache.blocks.len(), CALL_CACHE_BLOCKS);
+		assert_eq!(cache.get(&H256::from(0), "authorities", &[]), None);
+		assert_eq!(cache.get(&H256::from(1), "authorities", &[]), Some(vec![1]));
+		assert_eq!(cache.get(&H256::from(1), "authorities", &[1]), None);
+		assert_eq!(cache.get(&H256::from(1), "version", &[]), None);
+	}
+
+	#[test]
+	fn call_cache_invalidates_block() {
+		let mut cache = CallCache::default();
+		cache.insert(H256::from(1), "authorities", &[], vec![1]);
+		cache.insert(H256::from(1), "version", &[], vec![2]);
+		cache.insert(H256::from(2), "authorities", &[], vec![3]);
+
+		cache.invalidate(&H256::from(1));
+		assert_eq!(cache.get(&H256::from(1), "authorities", &[]), None);
+		assert_eq!(cache.get(&H256::from(1), "version", &[]), None);
+		assert_eq!(cache.get(&H256::from(2), "authorities", &[]), Some(vec![3]));
+		assert_eq!(cache.blocks, vec![H256::from(2)]);
+	}
+
+	#[test]
+	fn benchmark_repeats_call_against_block_state() {
+		let client = ::test_client::new();
+		let id = BlockId::Number(0);
+		let expected = client.executor().call(&id, "authorities", &[]).unwrap().return_data;
+
+		let result = client.executor()
+			.benchmark(&id, "authorities", &[], state_machine::ExecutionStrategy::NativeWhenPossible, 3)
+			.unwrap();
+		assert_eq!(result.output, expected);
+		assert_eq!(result.runs.len(), 3);
+		assert!(result.runs[0].db_access.storage_reads > 0);
+	}
+}
@@ -0,0 +1,132 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Entropy provided to the runtime through `ext_chain_randomness`.
+//!
+//! The entropy of a block only depends on the hashes of its ancestors, so every node executing
+//! the block derives the same value, whether it authors the block or imports it. The node
+//! that lacks any of these ancestors, e.g. because it has synced the chain starting from a later
+//! block, can't execute the block until it imports them: deriving the entropy from fewer hashes
+//! would make the node disagree with the other nodes.
+
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Hash as HashT, HashFor, Zero};
+
+use blockchain::HeaderBackend;
+use error;
+
+/// Number of the most recent blocks the entropy is derived from.
+pub const ENTROPY_BLOCKS: usize = 16;
+
+/// Entropy of the block built on top of the given parent: the hash of the hashes of the parent
+/// and its `ENTROPY_BLOCKS - 1` ancestors (or all the ancestors, if there are fewer). Fails with
+/// `MissingAncestor` if the header of any of them isn't in the database.
+pub fn chain_entropy<Block, B>(blockchain: &B, parent_hash: Block::Hash) -> error::Result<Vec<u8>> where
+	Block: BlockT,
+	B: HeaderBackend<Block>,
+{
+	let mut seed = Vec::new();
+	let mut hash = parent_hash;
+	for _ in 0..ENTROPY_BLOCKS {
+		seed.extend_from_slice(hash.as_ref());
+
+		let header = blockchain.header(BlockId::Hash(hash))?
+			.ok_or_else(|| error::ErrorKind::MissingAncestor(format!("{}", hash)))?;
+		if header.number().is_zero() {
+			break;
+		}
+		hash = *header.parent_hash();
+	}
+
+	Ok(HashFor::<Block>::hash(&seed).as_ref().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use backend::NewBlockState;
+	use consensus::BlockOrigin;
+	use in_mem;
+	use keyring::Keyring;
+	use test_client::{self, TestClient, BlockBuilderExt};
+	use test_client::runtime::{Block, Digest, Header, Transfer};
+
+	#[test]
+	fn entropy_is_same_on_all_nodes() {
+		let author = test_client::new();
+		let importer = test_client::new();
+		let mut entropies = Vec::new();
+		for _ in 0..ENTROPY_BLOCKS + 2 {
+			let block = author.new_block().unwrap().bake().unwrap();
+			author.justify_and_import(BlockOrigin::Own, block.clone()).unwrap();
+			importer.justify_and_import(BlockOrigin::NetworkBroadcast, block.clone()).unwrap();
+
+			let hash = block.hash();
+			let entropy = chain_entropy(author.backend().blockchain(), hash).unwrap();
+			assert_eq!(chain_entropy(importer.backend().blockchain(), hash).unwrap(), entropy);
+			entropies.push(entropy);
+		}
+
+		// every block gets its own entropy
+		entropies.sort();
+		entropies.dedup();
+		assert_eq!(entropies.len(), ENTROPY_BLOCKS + 2);
+	}
+
+	#[test]
+	fn entropy_differs_between_forks() {
+		let client = test_client::new();
+		let genesis_hash = client.info().unwrap().chain.genesis_hash;
+
+		let a1 = client.new_block().unwrap().bake().unwrap();
+		client.justify_and_import(BlockOrigin::Own, a1.clone()).unwrap();
+
+		let mut builder = client.new_block_at(&BlockId::Hash(genesis_hash)).unwrap();
+		builder.push_transfer(Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 41,
+			nonce: 0,
+		}).unwrap();
+		let b1 = builder.bake().unwrap();
+		client.justify_and_import(BlockOrigin::Own, b1.clone()).unwrap();
+
+		let blockchain = client.backend().blockchain();
+		assert!(
+			chain_entropy(blockchain, a1.hash()).unwrap() !=
+				chain_entropy(blockchain, b1.hash()).unwrap()
+		);
+	}
+
+	#[test]
+	fn entropy_is_not_derived_if_ancestor_is_missing() {
+		let blockchain = in_mem::Blockchain::<Block>::new();
+		let header = Header {
+			parent_hash: [1u8; 32].into(),
+			number: 5,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Digest { logs: vec![] },
+		};
+		let hash = header.hash();
+		blockchain.insert(hash, header, None, None, NewBlockState::Best).unwrap();
+
+		match chain_entropy(&blockchain, hash) {
+			Err(error::Error(error::ErrorKind::MissingAncestor(_), _)) => (),
+			result => panic!("Unexpected result: {:?}", result),
+		}
+	}
+}
@@ -0,0 +1,87 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bookkeeping of changes tries that have been skipped at import and are built later.
+
+use primitives::ChangesTrieConfiguration;
+
+/// Aux key under which the range `(first, last)` of block numbers with skipped changes tries is kept.
+pub const BACKFILL_RANGE_KEY: &[u8] = b"changes_tries_backfill_range";
+
+/// Aux key under which the ascending list of block numbers whose changes tries have failed to
+/// build is kept. These tries are retried before the rest of the range.
+pub const BACKFILL_FAILED_KEY: &[u8] = b"changes_tries_backfill_failed";
+
+/// Prefix of aux keys under which pre-runtime headers of blocks with skipped changes tries are kept.
+const PRE_HEADER_PREFIX: &[u8] = b"changes_tries_backfill_header";
+
+/// Aux key of the pre-runtime header of the block with given hash.
+pub fn pre_header_key<H: AsRef<[u8]>>(hash: &H) -> Vec<u8> {
+	let mut key = PRE_HEADER_PREFIX.to_vec();
+	key.extend_from_slice(hash.as_ref());
+	key
+}
+
+/// Range of blocks whose changes tries are required to build the digest trie at given block.
+/// Returns None if no digest is built at this block.
+pub fn digest_input_range(config: &ChangesTrieConfiguration, block: u64) -> Option<(u64, u64)> {
+	config.digest_level_at_block(block)
+		.map(|(_, digest_interval, _)| (block - digest_interval + 1, block - 1))
+}
+
+/// First block whose changes trie is worth building. Changes tries of older blocks are pruned
+/// by the backend anyway. The block starts the range of the top-level digest, because the tries
+/// of all blocks in this range are required to build the digest.
+pub fn first_kept_block(config: &ChangesTrieConfiguration, best: u64, blocks_to_keep: Option<u64>) -> u64 {
+	let oldest = match blocks_to_keep {
+		Some(blocks_to_keep) => best.saturating_sub(blocks_to_keep),
+		None => return 1,
+	};
+
+	let max_digest_interval = if config.is_digest_build_enabled() {
+		(0..config.digest_levels).fold(1u64, |interval, _| interval.saturating_mul(config.digest_interval))
+	} else {
+		1
+	};
+
+	oldest - oldest % max_digest_interval + 1
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config() -> ChangesTrieConfiguration {
+		ChangesTrieConfiguration { digest_interval: 4, digest_levels: 2 }
+	}
+
+	#[test]
+	fn digest_input_range_works() {
+		assert_eq!(digest_input_range(&config(), 3), None);
+		assert_eq!(digest_input_range(&config(), 4), Some((1, 3)));
+		assert_eq!(digest_input_range(&config(), 16), Some((1, 15)));
+		assert_eq!(digest_input_range(&config(), 20), Some((17, 19)));
+	}
+
+	#[test]
+	fn first_kept_block_starts_top_level_digest_range() {
+		assert_eq!(first_kept_block(&config(), 100, None), 1);
+		assert_eq!(first_kept_block(&config(), 100, Some(200)), 1);
+		assert_eq!(first_kept_block(&config(), 100, Some(60)), 33);
+		assert_eq!(first_kept_block(&config(), 100, Some(52)), 49);
+		assert_eq!(first_kept_block(&ChangesTrieConfiguration { digest_interval: 0, digest_levels: 0 }, 100, Some(60)), 41);
+	}
+}
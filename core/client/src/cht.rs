@@ -30,7 +30,7 @@ use trie;
 use primitives::H256;
 use runtime_primitives::traits::{As, Header as HeaderT, SimpleArithmetic, One};
 use state_machine::backend::InMemory as InMemoryState;
-use state_machine::{prove_read, read_proof_check};
+use state_machine::{prove_read_keys, read_proof_check};
 
 use error::{Error as ClientError, ErrorKind as ClientErrorKind, Result as ClientResult};
 
@@ -86,14 +86,33 @@ pub fn build_proof<Header, Hasher, I>(
 		Hasher: hash_db::Hasher,
 		Hasher::Out: Ord + HeapSizeOf,
 		I: IntoIterator<Item=Option<Header::Hash>>,
+{
+	build_multi_proof::<Header, Hasher, _, _>(cht_size, cht_num, ::std::iter::once(block_num), hashes)
+}
+
+/// Build single CHT-based proof of the headers of all given blocks. Each header is checked
+/// with `check_proof` against the same proof.
+pub fn build_multi_proof<Header, Hasher, BI, I>(
+	cht_size: u64,
+	cht_num: Header::Number,
+	blocks: BI,
+	hashes: I
+) -> Option<Vec<Vec<u8>>>
+	where
+		Header: HeaderT,
+		Hasher: hash_db::Hasher,
+		Hasher::Out: Ord + HeapSizeOf,
+		BI: IntoIterator<Item=Header::Number>,
+		I: IntoIterator<Item=Option<Header::Hash>>,
 {
 	let transaction = build_pairs::<Header, I>(cht_size, cht_num, hashes)?
 		.into_iter()
 		.map(|(k, v)| (None, k, Some(v)))
 		.collect::<Vec<_>>();
 	let storage = InMemoryState::<Hasher>::default().update(transaction);
-	let (value, proof) = prove_read(storage, &encode_cht_key(block_num)).ok()?;
-	if value.is_none() {
+	let keys = blocks.into_iter().map(encode_cht_key).collect::<Vec<_>>();
+	let (values, proof) = prove_read_keys(storage, keys).ok()?;
+	if values.is_empty() || values.iter().any(Option::is_none) {
 		None
 	} else {
 		Some(proof)
@@ -268,4 +287,16 @@ mod tests {
 		assert!(build_proof::<Header, Blake2Hasher, _>(
 			SIZE, 0, (SIZE / 2) as u64, vec![Some(1.into()); SIZE as usize]).is_some());
 	}
+
+	#[test]
+	fn multi_proof_is_checked_for_every_block() {
+		let hashes = (1..SIZE + 1).map(|number| Some(number.into())).collect::<Vec<_>>();
+		let root = compute_root::<Header, Blake2Hasher, _>(SIZE, 0, hashes.clone()).unwrap();
+		let proof = build_multi_proof::<Header, Blake2Hasher, _, _>(SIZE, 0, vec![3, 10], hashes.clone()).unwrap();
+		assert!(check_proof::<Header, Blake2Hasher>(root, 3, 3.into(), proof.clone()).is_ok());
+		assert!(check_proof::<Header, Blake2Hasher>(root, 10, 10.into(), proof.clone()).is_ok());
+		assert!(check_proof::<Header, Blake2Hasher>(root, 10, 3.into(), proof).is_err());
+
+		assert!(build_multi_proof::<Header, Blake2Hasher, _, _>(SIZE, 0, vec![3, SIZE * 1000], hashes).is_none());
+	}
 }
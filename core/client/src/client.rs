@@ -16,66 +16,146 @@
 
 //! Substrate Client
 
+use std::cmp;
+use std::collections::{BTreeSet, HashSet};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::time::Instant;
 use error::{Error, ErrorKind};
-use futures::sync::mpsc;
+use linked_hash_map::LinkedHashMap;
 use parking_lot::{Mutex, RwLock};
-use primitives::AuthorityId;
+use primitives::{AuthorityId, LocalKeys};
 use runtime_primitives::{
 	Justification,
 	generic::{BlockId, SignedBlock, Block as RuntimeBlock},
-	transaction_validity::{TransactionValidity, TransactionTag},
+	transaction_validity::{TransactionValidity, TransactionValidityV1, TransactionTag},
 };
 use consensus::{ImportBlock, ImportResult, BlockOrigin};
-use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Zero, As, NumberFor, CurrentHeight, BlockNumberToHash};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Zero, As, NumberFor, CurrentHeight, BlockNumberToHash,
+	Digest as DigestT, DigestItem as DigestItemT, DigestItemFor};
 use runtime_primitives::{ApplyResult, BuildStorage};
 use runtime_api as api;
 use primitives::{Blake2Hasher, H256, ChangesTrieConfiguration};
+use primitives::offchain;
 use primitives::storage::{StorageKey, StorageData};
 use primitives::storage::well_known_keys;
 use codec::{Encode, Decode};
 use state_machine::{
 	Backend as StateBackend, CodeExecutor,
-	ExecutionStrategy, ExecutionManager, prove_read,
-	key_changes, key_changes_proof, OverlayedChanges
+	ExecutionStrategy, ExecutionManager, ExecutionContext, prove_read_keys, prove_child_read, prove_child_roots, prove_read_prefix,
+	key_changes, key_changes_proof, block_changed_keys, OverlayedChanges, TrackingBackend, native_when_possible,
+	ChangesTrieRootsStorage, ChangesTrieStorage, overlay_transaction,
 };
 
 use backend::{self, BlockImportOperation};
-use blockchain::{self, Info as ChainInfo, Backend as ChainBackend, HeaderBackend as ChainHeaderBackend};
+use blockchain::{self, BlockchainFuture, Info as ChainInfo, Backend as ChainBackend, HeaderBackend as ChainHeaderBackend};
 use call_executor::{CallExecutor, LocalCallExecutor};
 use executor::{RuntimeVersion, RuntimeInfo};
 use notifications::{StorageNotifications, StorageEventStream};
-use {cht, error, in_mem, block_builder, genesis, consensus};
+use notification_sinks::{NotificationSinks, NotificationStream};
+use divergence::{DivergenceReport, DIVERGENCE_REPORTS_KEY, MAX_DIVERGENCE_REPORTS};
+use changes_trie_backfill::{self, BACKFILL_FAILED_KEY, BACKFILL_RANGE_KEY};
+use chain_entropy::chain_entropy;
+use recent_blocks::recent_block_hashes;
+use state_diff::StateDiff;
+use {cht, error, in_mem, block_builder, genesis, consensus, trie, hash_db, storage_query};
+use metadata::RuntimeMetadata;
 
 /// Type that implements `futures::Stream` of block import events.
-pub type ImportNotifications<Block> = mpsc::UnboundedReceiver<BlockImportNotification<Block>>;
+pub type ImportNotifications<Block> = NotificationStream<BlockImportNotification<Block>>;
 
 /// A stream of block finality notifications.
-pub type FinalityNotifications<Block> = mpsc::UnboundedReceiver<FinalityNotification<Block>>;
+pub type FinalityNotifications<Block> = NotificationStream<FinalityNotification<Block>>;
 
 /// Substrate Client
 pub struct Client<B, E, Block> where Block: BlockT {
 	backend: Arc<B>,
 	executor: E,
 	storage_notifications: Mutex<StorageNotifications<Block>>,
-	import_notification_sinks: Mutex<Vec<mpsc::UnboundedSender<BlockImportNotification<Block>>>>,
-	finality_notification_sinks: Mutex<Vec<mpsc::UnboundedSender<FinalityNotification<Block>>>>,
+	import_notification_sinks: Mutex<NotificationSinks<BlockImportNotification<Block>>>,
+	finality_notification_sinks: Mutex<NotificationSinks<FinalityNotification<Block>>>,
 	import_lock: Mutex<()>,
 	importing_block: RwLock<Option<Block::Hash>>, // holds the block hash currently being imported. TODO: replace this with block queue
 	block_execution_strategy: ExecutionStrategy,
 	api_execution_strategy: ExecutionStrategy,
 	changes_trie_config: Option<ChangesTrieConfiguration>,
+	// storage keys read by the last executed block, prefetched before the next one is executed.
+	access_trace: Mutex<Vec<Vec<u8>>>,
+	prefetch_hints: RwLock<Option<Box<PrefetchHints<Block>>>>,
+	justification_verifier: RwLock<Option<Box<JustificationVerifier<Block>>>>,
+	skip_changes_tries_on_sync: AtomicBool,
+	fork_choice_rule: RwLock<ForkChoiceRule>,
+	// keys of the node, available to the runtime in offchain calls.
+	local_keys: RwLock<Option<Arc<LocalKeys>>>,
+	// times when recently announced or received blocks were first seen.
+	block_arrivals: Mutex<LinkedHashMap<Block::Hash, Instant>>,
+	// guards updates of the range of blocks with skipped changes tries
+	changes_tries_backfill_lock: Mutex<()>,
+	// storage changes of the blocks recently built by this node, keyed by the hash of the built header.
+	own_block_changes: Mutex<LinkedHashMap<Block::Hash, OwnBlockChanges<Block>>>,
+}
+
+/// Storage changes of the block built by this node, reused when the block is imported instead
+/// of executing it once again.
+struct OwnBlockChanges<Block: BlockT> {
+	parent_hash: Block::Hash,
+	changes: OverlayedChanges,
+}
+
+/// Maximal number of storage keys kept in the access trace of the last executed block.
+const MAX_ACCESS_TRACE_KEYS: usize = 4096;
+
+/// Maximal number of block arrival times kept for the fork choice.
+const MAX_BLOCK_ARRIVALS: usize = 4096;
+
+/// Maximal number of recently built blocks whose storage changes are kept for the import.
+const MAX_OWN_BLOCK_CHANGES: usize = 8;
+
+/// Rule used to choose the best block among the blocks of the same height.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkChoiceRule {
+	/// Only a block with greater height replaces the best block. Among the blocks of
+	/// the same height the one that has been imported first wins.
+	LongestChain,
+	/// Same as `LongestChain`, but among the blocks of the same height the one that
+	/// has been seen first (announced or received from the network) wins.
+	FirstSeen,
+}
+
+impl Default for ForkChoiceRule {
+	fn default() -> Self {
+		ForkChoiceRule::LongestChain
+	}
+}
+
+/// Source of the storage keys that the block extrinsics are likely to read. These keys are
+/// prefetched before the block is executed, along with the keys read by the previous block.
+pub trait PrefetchHints<Block: BlockT>: Send + Sync {
+	/// Get the storage keys to prefetch before executing the given extrinsics.
+	fn storage_keys(&self, extrinsics: &[Block::Extrinsic]) -> Vec<Vec<u8>>;
+}
+
+/// Verifier of the block justifications. Used to check the headers that are imported without
+/// execution (i.e. by the light client) before they become the best block.
+pub trait JustificationVerifier<Block: BlockT>: Send + Sync {
+	/// Check that the justification of the header is signed by the given authorities.
+	fn verify_justification(
+		&self,
+		header: &Block::Header,
+		justification: &Justification,
+		authorities: &[AuthorityId],
+	) -> error::Result<()>;
 }
 
 /// A source of blockchain events.
 pub trait BlockchainEvents<Block: BlockT> {
 	/// Get block import event stream. Not guaranteed to be fired for every
-	/// imported block.
-	fn import_notification_stream(&self) -> ImportNotifications<Block>;
+	/// imported block. The label identifies the consumer when it is lagging.
+	fn import_notification_stream(&self, label: &'static str) -> ImportNotifications<Block>;
 
 	/// Get a stream of finality notifications. Not guaranteed to be fired for every
-	/// finalized block.
-	fn finality_notification_stream(&self) -> FinalityNotifications<Block>;
+	/// finalized block. The label identifies the consumer when it is lagging.
+	fn finality_notification_stream(&self, label: &'static str) -> FinalityNotifications<Block>;
 
 	/// Get storage changes event stream.
 	///
@@ -89,6 +169,18 @@ pub trait ChainHead<Block: BlockT> {
 	fn best_block_header(&self) -> Result<<Block as BlockT>::Header, error::Error>;
 }
 
+/// Finalization of blocks.
+pub trait Finalize<Block: BlockT> {
+	/// Finalize a block, along with all its ancestors, and fire finality notifications.
+	fn finalize(&self, id: BlockId<Block>) -> error::Result<()>;
+}
+
+/// Fetch block header by ID.
+pub trait BlockHeader<Block: BlockT> {
+	/// Get block header by ID. Returns `None` if the block is unknown.
+	fn block_header(&self, id: &BlockId<Block>) -> error::Result<Option<<Block as BlockT>::Header>>;
+}
+
 /// Fetch block body by ID.
 pub trait BlockBody<Block: BlockT> {
 	/// Get block body by ID. Returns `None` if the body is not stored.
@@ -144,6 +236,35 @@ pub struct FinalityNotification<Block: BlockT> {
 	pub header: Block::Header,
 }
 
+/// Guard keeping the state of a block from being pruned.
+/// The block is unpinned when the guard is dropped.
+pub struct PinnedBlock<B, Block> where
+	B: backend::Backend<Block, Blake2Hasher>,
+	Block: BlockT,
+{
+	backend: Arc<B>,
+	hash: Block::Hash,
+}
+
+impl<B, Block> PinnedBlock<B, Block> where
+	B: backend::Backend<Block, Blake2Hasher>,
+	Block: BlockT,
+{
+	/// Hash of the pinned block.
+	pub fn hash(&self) -> &Block::Hash {
+		&self.hash
+	}
+}
+
+impl<B, Block> Drop for PinnedBlock<B, Block> where
+	B: backend::Backend<Block, Blake2Hasher>,
+	Block: BlockT,
+{
+	fn drop(&mut self) {
+		self.backend.unpin_block(&self.hash);
+	}
+}
+
 // used in importing a block, where additional changes are made after the runtime
 // executed.
 enum PrePostHeader<H> {
@@ -179,6 +300,31 @@ impl<H> PrePostHeader<H> {
 	}
 }
 
+/// Check proof of extrinsic inclusion, generated by `Client::extrinsic_proof`, against
+/// the extrinsics root of given header. Returns the proven extrinsic.
+pub fn check_extrinsic_proof<Header, Hasher, Extrinsic>(
+	header: &Header,
+	index: u32,
+	proof: Vec<Vec<u8>>,
+) -> error::Result<Extrinsic>
+	where
+		Header: HeaderT,
+		Header::Hash: AsRef<[u8]>,
+		Hasher: hash_db::Hasher,
+		Extrinsic: Decode,
+{
+	let extrinsics_root = header.extrinsics_root().as_ref();
+	let mut root: Hasher::Out = Default::default();
+	if root.as_ref().len() != extrinsics_root.len() {
+		return Err(error::ErrorKind::InvalidExtrinsicProof.into());
+	}
+	root.as_mut().copy_from_slice(extrinsics_root);
+	let encoded = trie::check_ordered_trie_proof::<Hasher>(&root, index, proof)
+		.map_err(|_| error::ErrorKind::InvalidExtrinsicProof)?
+		.ok_or_else(|| error::ErrorKind::InvalidExtrinsicProof)?;
+	Decode::decode(&mut &encoded[..]).ok_or_else(|| error::ErrorKind::InvalidExtrinsicProof.into())
+}
+
 /// Create an instance of in-memory client.
 pub fn new_in_mem<E, Block, S>(
 	executor: E,
@@ -228,7 +374,7 @@ impl<B, E, Block> Client<B, E, Block> where
 			let state_root = op.reset_storage(genesis_storage, children_genesis_storage)?;
 
 			let genesis_block = genesis::construct_genesis_block::<Block>(state_root.into());
-			info!("Initialising Genesis block/state (state: {}, header-hash: {})", genesis_block.header().state_root(), genesis_block.header().hash());
+			info!(target: "client", "Initialising Genesis block/state (state: {}, header-hash: {})", genesis_block.header().state_root(), genesis_block.header().hash());
 			op.set_block_data(
 				genesis_block.deconstruct().0,
 				Some(vec![]),
@@ -255,9 +401,74 @@ impl<B, E, Block> Client<B, E, Block> where
 			block_execution_strategy,
 			api_execution_strategy,
 			changes_trie_config,
+			access_trace: Default::default(),
+			prefetch_hints: Default::default(),
+			justification_verifier: Default::default(),
+			skip_changes_tries_on_sync: Default::default(),
+			fork_choice_rule: Default::default(),
+			local_keys: Default::default(),
+			block_arrivals: Mutex::new(LinkedHashMap::new()),
+			changes_tries_backfill_lock: Default::default(),
+			own_block_changes: Mutex::new(LinkedHashMap::new()),
 		})
 	}
 
+	/// Set the source of storage keys to prefetch before executing imported blocks.
+	pub fn set_prefetch_hints(&self, hints: Box<PrefetchHints<Block>>) {
+		*self.prefetch_hints.write() = Some(hints);
+	}
+
+	/// Set the verifier of justifications of the headers imported without execution. Without
+	/// the verifier these headers are accepted as is.
+	pub fn set_justification_verifier(&self, verifier: Box<JustificationVerifier<Block>>) {
+		*self.justification_verifier.write() = Some(verifier);
+	}
+
+	/// Skip building changes tries of blocks imported during major sync. The roots from the
+	/// headers of these blocks are trusted until the tries are built by `backfill_changes_tries`.
+	pub fn set_skip_changes_tries_on_sync(&self, skip: bool) {
+		self.skip_changes_tries_on_sync.store(skip, AtomicOrdering::Relaxed);
+	}
+
+	/// Set the rule used to choose the best block among the blocks of the same height.
+	pub fn set_fork_choice_rule(&self, rule: ForkChoiceRule) {
+		*self.fork_choice_rule.write() = rule;
+	}
+
+	/// Set the keys held by the node. The runtime checks whether it is executed by one of the
+	/// authorities against these keys in offchain calls. Without the keys the node is never
+	/// considered an authority.
+	pub fn set_local_keys(&self, keys: Arc<LocalKeys>) {
+		*self.local_keys.write() = Some(keys);
+	}
+
+	/// Note that the block has been seen at the given time. Only the earliest
+	/// arrival of the block is remembered.
+	pub fn note_block_arrival(&self, hash: Block::Hash, at: Instant) {
+		let mut arrivals = self.block_arrivals.lock();
+		if arrivals.contains_key(&hash) {
+			return;
+		}
+
+		arrivals.insert(hash, at);
+		while arrivals.len() > MAX_BLOCK_ARRIVALS {
+			arrivals.pop_front();
+		}
+	}
+
+	/// Remember the storage changes of the block built by this node, so that the block isn't
+	/// executed once again when it is imported.
+	pub(crate) fn note_own_block_changes(&self, header: &Block::Header, changes: OverlayedChanges) {
+		let mut own_block_changes = self.own_block_changes.lock();
+		own_block_changes.insert(header.hash(), OwnBlockChanges {
+			parent_hash: header.parent_hash().clone(),
+			changes,
+		});
+		while own_block_changes.len() > MAX_OWN_BLOCK_CHANGES {
+			own_block_changes.pop_front();
+		}
+	}
+
 	/// Get a reference to the state at a given block.
 	pub fn state_at(&self, block: &BlockId<Block>) -> error::Result<B::State> {
 		self.backend.state_at(*block)
@@ -268,6 +479,18 @@ impl<B, E, Block> Client<B, E, Block> where
 		&self.backend
 	}
 
+	/// Prevent the state of the given block from being pruned while the returned guard is alive.
+	pub fn pin_block(&self, hash: Block::Hash) -> error::Result<PinnedBlock<B, Block>> {
+		// states are pruned on import and finalization, which are done under the import lock.
+		// holding it guarantees that the state isn't pruned between the check and the pin.
+		let _import_lock = self.import_lock.lock();
+		self.backend.pin_block(&hash)?;
+		Ok(PinnedBlock {
+			backend: self.backend.clone(),
+			hash,
+		})
+	}
+
 	/// Return single storage entry of contract under given address in state in a block of given hash.
 	pub fn storage(&self, id: &BlockId<Block>, key: &StorageKey) -> error::Result<Option<StorageData>> {
 		Ok(self.state_at(id)?
@@ -275,6 +498,59 @@ impl<B, E, Block> Client<B, E, Block> where
 			.map(StorageData))
 	}
 
+	/// Query a storage item by its module and item names, decoding the stored value into a
+	/// dynamically typed value. The item is looked up in the runtime metadata at the given block.
+	/// `map_key` is the encoded key for map items.
+	pub fn storage_named(
+		&self,
+		id: &BlockId<Block>,
+		module: &str,
+		item: &str,
+		map_key: Option<&[u8]>,
+		registry: &storage_query::TypeRegistry,
+	) -> error::Result<Option<storage_query::Value>> {
+		let metadata = self.executor.call(id, "metadata", &[])?.return_data;
+		let metadata = RuntimeMetadata::decode(&mut &metadata[..])
+			.ok_or_else(|| error::ErrorKind::InvalidStorageQuery("Runtime metadata can't be decoded".into()))?;
+		let item = storage_query::StorageItem::find(&metadata, module, item)?;
+		match self.storage(id, &item.key(map_key)?)? {
+			Some(data) => item.decode(registry, &data.0).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	/// Get storage keys that storage change listeners are interested in.
+	/// Listeners to all keys are not taken into account.
+	pub fn storage_listened_keys(&self) -> Vec<StorageKey> {
+		self.storage_notifications.lock().listened_keys()
+	}
+
+	/// Notify storage change listeners about changes at a block that have been learned
+	/// from elsewhere than block import (e.g. pushed to a light client by full nodes).
+	pub fn notify_storage_changes(&self, hash: &Block::Hash, changes: Vec<(Vec<u8>, Option<Vec<u8>>)>) {
+		self.storage_notifications.lock().trigger(hash, changes.into_iter());
+	}
+
+	/// Get all storage entries with given key prefix in state in a block of given hash.
+	pub fn storage_pairs(&self, id: &BlockId<Block>, key_prefix: &StorageKey) -> error::Result<Vec<(StorageKey, StorageData)>> {
+		self.storage_pairs_paged(id, key_prefix, None, ::std::usize::MAX)
+	}
+
+	/// Given a `BlockId`, a key prefix and a key, return at most `count` storage entries with the
+	/// prefix whose keys follow the given key, in the order of the keys.
+	pub fn storage_pairs_paged(
+		&self,
+		id: &BlockId<Block>,
+		key_prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		count: usize,
+	) -> error::Result<Vec<(StorageKey, StorageData)>> {
+		let pairs = self.state_at(id)?
+			.pairs_with_prefix_from(&key_prefix.0, start_key.map(|start_key| &start_key.0[..]), count)
+			.map_err(|e| error::Error::from_state(Box::new(e)))?;
+		Ok(pairs.into_iter().map(|(key, value)| (StorageKey(key), StorageData(value))).collect())
+	}
+
 	/// Get the code at a given block.
 	pub fn code_at(&self, id: &BlockId<Block>) -> error::Result<Vec<u8>> {
 		Ok(self.storage(id, &StorageKey(well_known_keys::CODE.to_vec()))?
@@ -291,30 +567,117 @@ impl<B, E, Block> Client<B, E, Block> where
 		}
 	}
 
+	/// Get the authorities disabled by the runtime at a given block. Empty if the runtime doesn't
+	/// provide the `DisabledValidators` API.
+	pub fn disabled_validators_at(&self, id: &BlockId<Block>) -> error::Result<Vec<AuthorityId>> {
+		match self.api_version::<api::id::DisabledValidatorsApi>(id)? {
+			Some(_) => self.call_api_at(id, "disabled_validators", &()),
+			None => Ok(Vec::new()),
+		}
+	}
+
+	/// Run the offchain worker of the runtime at a given block, with access to the offchain
+	/// externalities and the local keys. Nothing is executed if the runtime doesn't provide the
+	/// `OffchainWorker` API.
+	pub fn offchain_worker_at(&self, id: &BlockId<Block>, offchain: &mut offchain::Externalities) -> error::Result<()> {
+		if !self.has_api::<api::id::OffchainWorkerApi>(id)? {
+			return Ok(());
+		}
+
+		let number = self.block_number_from_id(id)?
+			.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", id)))?;
+		let local_keys = self.local_keys.read().clone();
+		self.executor.call_offchain(
+			id,
+			"offchain_worker",
+			&number.encode(),
+			offchain,
+			local_keys.as_ref().map(|keys| &**keys),
+		).map(|_| ())
+	}
+
 	/// Get the RuntimeVersion at a given block.
 	pub fn runtime_version_at(&self, id: &BlockId<Block>) -> error::Result<RuntimeVersion> {
 		// TODO: Post Poc-2 return an error if version is missing
 		self.executor.runtime_version(id)
 	}
 
+	/// Get the version of the given runtime API provided by the runtime at a given block.
+	pub fn api_version<A: api::RuntimeApiInfo>(&self, id: &BlockId<Block>) -> error::Result<Option<u32>> {
+		Ok(self.runtime_version_at(id)?.api_version(A::ID))
+	}
+
+	/// Check whether the runtime at a given block provides the version of the given runtime API
+	/// known to this node.
+	pub fn has_api<A: api::RuntimeApiInfo>(&self, id: &BlockId<Block>) -> error::Result<bool> {
+		Ok(self.api_version::<A>(id)? == Some(A::VERSION))
+	}
+
+	/// Validate transaction at a given block, calling `validate_transaction` with the signature
+	/// of the transaction queue API version provided by the runtime.
+	pub fn validate_transaction_at(&self, id: &BlockId<Block>, tx: &Block::Extrinsic) -> error::Result<TransactionValidity> {
+		match self.api_version::<api::id::TaggedTransactionQueueApi>(id)? {
+			Some(1) => api::TaggedTransactionQueue::validate_transaction::<TransactionValidityV1>(self, id, tx)
+				.map(Into::into),
+			_ => api::TaggedTransactionQueue::validate_transaction(self, id, tx),
+		}
+	}
+
 	/// Get call executor reference.
 	pub fn executor(&self) -> &E {
 		&self.executor
 	}
 
-	/// Reads storage value at a given block + key, returning read proof.
-	pub fn read_proof(&self, id: &BlockId<Block>, key: &[u8]) -> error::Result<Vec<Vec<u8>>> {
+	/// Reads storage values at a given block + keys, returning single read proof of all values.
+	pub fn read_proof(&self, id: &BlockId<Block>, keys: &[Vec<u8>]) -> error::Result<Vec<Vec<u8>>> {
 		self.state_at(id)
-			.and_then(|state| prove_read(state, key)
+			.and_then(|state| prove_read_keys(state, keys)
 				.map(|(_, proof)| proof)
 				.map_err(Into::into))
 	}
 
+	/// Reads child storage value at a given block + storage_key + key, returning read proof.
+	/// The proof also contains the nodes of the main trie proving the root of the child trie.
+	pub fn read_child_proof(&self, id: &BlockId<Block>, storage_key: &[u8], key: &[u8]) -> error::Result<Vec<Vec<u8>>> {
+		self.state_at(id)
+			.and_then(|state| prove_child_read(state, storage_key, key)
+				.map(|(_, proof)| proof)
+				.map_err(Into::into))
+	}
+
+	/// Reads storage keys and roots of all child tries at given block, returning proof
+	/// of their completeness.
+	pub fn child_roots_proof(&self, id: &BlockId<Block>) -> error::Result<Vec<Vec<u8>>> {
+		self.state_at(id)
+			.and_then(|state| prove_child_roots(state)
+				.map_err(Into::into))
+	}
+
+	/// Reads the page of storage entries with keys starting with the `prefix` at given block,
+	/// returning proof of the page. The page starts from the first such entry with the key that is
+	/// greater than or equal to the `start_key` and contains at most `limit` entries.
+	pub fn read_prefix_proof(&self, id: &BlockId<Block>, prefix: &[u8], start_key: &[u8], limit: usize) -> error::Result<Vec<Vec<u8>>> {
+		self.state_at(id)
+			.and_then(|state| prove_read_prefix(state, prefix, start_key, limit)
+				.map_err(Into::into))
+	}
+
 	/// Execute a call to a contract on top of state in a block of given hash
 	/// AND returning execution proof.
 	///
 	/// No changes are made.
 	pub fn execution_proof(&self, id: &BlockId<Block>, method: &str, call_data: &[u8]) -> error::Result<(Vec<u8>, Vec<Vec<u8>>)> {
+		self.execution_proof_with_keys(id, method, call_data).map(|(result, proof, _)| (result, proof))
+	}
+
+	/// Same as `execution_proof`, but also returns the storage keys read by the call, in order
+	/// of the first access.
+	pub fn execution_proof_with_keys(
+		&self,
+		id: &BlockId<Block>,
+		method: &str,
+		call_data: &[u8],
+	) -> error::Result<(Vec<u8>, Vec<Vec<u8>>, Vec<Vec<u8>>)> {
 		self.state_at(id).and_then(|state| self.executor.prove_at_state(state, &mut Default::default(), method, call_data))
 	}
 
@@ -326,16 +689,63 @@ impl<B, E, Block> Client<B, E, Block> where
 	/// Reads given header and generates CHT-based header proof for CHT of given size.
 	pub fn header_proof_with_cht_size(&self, id: &BlockId<Block>, cht_size: u64) -> error::Result<(Block::Header, Vec<Vec<u8>>)> {
 		let proof_error = || error::ErrorKind::Backend(format!("Failed to generate header proof for {:?}", id));
-		let header = self.header(id)?.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", id)))?;
+		// the proof is served to the remote nodes, so it is only built from the local headers
+		let blockchain = self.backend.blockchain();
+		let header = blockchain.local_header(*id)?.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", id)))?;
 		let block_num = *header.number();
 		let cht_num = cht::block_to_cht_number(cht_size, block_num).ok_or_else(proof_error)?;
 		let cht_start = cht::start_number(cht_size, cht_num);
-		let headers = (cht_start.as_()..).map(|num| self.block_hash(As::sa(num)).unwrap_or_default());
+		let headers = (cht_start.as_()..).map(|num| blockchain.local_hash(As::sa(num)).unwrap_or_default());
 		let proof = cht::build_proof::<Block::Header, Blake2Hasher, _>(cht_size, cht_num, block_num, headers)
 			.ok_or_else(proof_error)?;
 		Ok((header, proof))
 	}
 
+	/// Reads headers of all blocks of given range and generates CHT-based proof of these headers,
+	/// so that the requester may check that no block with the digest items it looks for is omitted.
+	pub fn digest_proof(&self, first: NumberFor<Block>, last: NumberFor<Block>) -> error::Result<(Vec<Block::Header>, Vec<Vec<u8>>)> {
+		self.digest_proof_with_cht_size(first, last, cht::SIZE)
+	}
+
+	/// Reads headers of all blocks of given range and generates CHT-based proof of these headers
+	/// for CHT of given size. The whole range should be included in the same CHT.
+	pub fn digest_proof_with_cht_size(
+		&self,
+		first: NumberFor<Block>,
+		last: NumberFor<Block>,
+		cht_size: u64,
+	) -> error::Result<(Vec<Block::Header>, Vec<Vec<u8>>)> {
+		let proof_error = || error::ErrorKind::Backend(format!("Failed to generate digest proof for {}..{}", first, last));
+		let cht_num = cht::block_to_cht_number(cht_size, first).ok_or_else(proof_error)?;
+		if last < first || cht::block_to_cht_number(cht_size, last) != Some(cht_num) {
+			return Err(proof_error().into());
+		}
+
+		let mut headers = Vec::new();
+		for number in first.as_()..last.as_() + 1 {
+			let id = BlockId::Number(As::sa(number));
+			let header = self.header(&id)?.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", id)))?;
+			headers.push(header);
+		}
+
+		let cht_start = cht::start_number(cht_size, cht_num);
+		let hashes = (cht_start.as_()..).map(|num| self.block_hash(As::sa(num)).unwrap_or_default());
+		let blocks = headers.iter().map(|header| *header.number());
+		let proof = cht::build_multi_proof::<Block::Header, Blake2Hasher, _, _>(cht_size, cht_num, blocks, hashes)
+			.ok_or_else(proof_error)?;
+		Ok((headers, proof))
+	}
+
+	/// Reads extrinsic with given index from the block body, returning it along with
+	/// the proof of its inclusion into the block extrinsics trie.
+	/// Returns `None` if there's no extrinsic with given index in the block.
+	pub fn extrinsic_proof(&self, id: &BlockId<Block>, index: u32) -> error::Result<Option<(Block::Extrinsic, Vec<Vec<u8>>)>> {
+		let mut extrinsics = self.body(id)?.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", id)))?;
+		let proof = trie::ordered_trie_proof::<Blake2Hasher, _, _>(extrinsics.iter().map(Encode::encode), index)
+			.map_err(|e| error::ErrorKind::Backend(format!("Failed to generate extrinsic proof for {:?}: {}", id, e)))?;
+		Ok(proof.map(|proof| (extrinsics.swap_remove(index as usize), proof)))
+	}
+
 	/// Get pairs of (block, extrinsic) where key has been changed at given blocks range.
 	/// Works only for runtimes that are supporting changes tries.
 	pub fn key_changes(
@@ -362,6 +772,497 @@ impl<B, E, Block> Client<B, E, Block> where
 		.map(|r| r.into_iter().map(|(b, e)| (As::sa(b), e)).collect())
 	}
 
+	/// Get the most recent reports of diverging native and wasm executions, oldest first.
+	pub fn divergence_reports(&self) -> error::Result<Vec<DivergenceReport<Block::Hash>>> {
+		match self.backend.get_aux(DIVERGENCE_REPORTS_KEY)? {
+			Some(encoded) => Decode::decode(&mut &encoded[..])
+				.ok_or_else(|| error::ErrorKind::Backend("Error decoding divergence reports".into()).into()),
+			None => Ok(Vec::new()),
+		}
+	}
+
+	/// Build changes tries of blocks that have been imported without them, in ascending order.
+	/// At most `max_blocks` blocks are processed at once, starting with the blocks whose tries
+	/// have failed to build before. Returns the number of built tries.
+	///
+	/// Changes tries of blocks beyond the pruning horizon of changes tries are not built at all.
+	/// Neither are tries of blocks whose parent state has been pruned, along with digest tries
+	/// depending on them, so the tries are only built in full by the archive nodes.
+	pub fn backfill_changes_tries(&self, max_blocks: u64) -> error::Result<u64> {
+		let config = match self.changes_trie_config {
+			Some(ref config) => config,
+			None => return Ok(0),
+		};
+		let range = self.changes_tries_backfill_range()?;
+		let mut failed = self.changes_tries_backfill_failed()?;
+		if range.is_none() && failed.is_empty() {
+			return Ok(0);
+		}
+
+		let best = self.backend.blockchain().info()?.best_number.as_();
+		let first_kept = changes_trie_backfill::first_kept_block(config, best, self.backend.changes_tries_blocks_to_keep());
+
+		let retried: Vec<u64> = failed.drain(..cmp::min(failed.len() as u64, max_blocks) as usize).collect();
+		let next = range.map(|(first, last)| {
+			let remaining = max_blocks - retried.len() as u64;
+			(first, cmp::min(last + 1, first.saturating_add(remaining)))
+		});
+		let blocks = retried.into_iter().chain(next.into_iter().flat_map(|(first, end)| first..end));
+
+		let mut built = 0;
+		let mut processed_keys = Vec::new();
+		let mut dropped = HashSet::new();
+		for block in blocks {
+			let (hash, pre_header, key) = match self.skipped_changes_trie(block)? {
+				Some(skipped) => skipped,
+				None => continue,
+			};
+
+			if block < first_kept || !self.can_build_changes_trie(config, block, &dropped)? {
+				debug!(target: "client", "Changes trie of block #{} ({}) is not built, its inputs have been pruned", block, hash);
+				dropped.insert(block);
+				processed_keys.push(key);
+				continue;
+			}
+
+			match self.build_changes_trie(config, hash, &pre_header) {
+				Ok(()) => {
+					built += 1;
+					processed_keys.push(key);
+				},
+				Err(e) => {
+					warn!(target: "client", "Unable to build changes trie of block #{} ({}): {}", block, hash, e);
+					failed.push(block);
+				},
+			}
+		}
+		failed.sort();
+		failed.dedup();
+
+		// blocks with skipped changes tries might have been imported in the meantime
+		let _lock = self.changes_tries_backfill_lock.lock();
+		let range = match (self.changes_tries_backfill_range()?, next) {
+			(Some((current_first, current_last)), Some((first, _))) if current_first < first => Some((current_first, current_last)),
+			(Some((_, current_last)), Some((_, end))) if end <= current_last => Some((end, current_last)),
+			(Some(_), Some(_)) => None,
+			(current, None) => current,
+			(None, Some(_)) => None,
+		};
+		let encoded_range = range.map(|range| range.encode());
+		let encoded_failed = if failed.is_empty() { None } else { Some(failed.encode()) };
+		let insert: Vec<(&[u8], &[u8])> = encoded_range.iter().map(|range| (BACKFILL_RANGE_KEY, &range[..]))
+			.chain(encoded_failed.iter().map(|failed| (BACKFILL_FAILED_KEY, &failed[..])))
+			.collect();
+		let mut delete: Vec<&[u8]> = processed_keys.iter().map(|key| &key[..]).collect();
+		if range.is_none() {
+			delete.push(BACKFILL_RANGE_KEY);
+		}
+		if encoded_failed.is_none() {
+			delete.push(BACKFILL_FAILED_KEY);
+		}
+		self.backend.insert_aux(&insert, &delete)?;
+
+		debug!(target: "client", "Built {} changes tries, {} failed to build", built, failed.len());
+		Ok(built)
+	}
+
+	/// Range of block numbers whose changes tries have been skipped at import and haven't been
+	/// built yet.
+	fn changes_tries_backfill_range(&self) -> error::Result<Option<(u64, u64)>> {
+		match self.backend.get_aux(BACKFILL_RANGE_KEY)? {
+			Some(encoded) => Decode::decode(&mut &encoded[..])
+				.map(Some)
+				.ok_or_else(|| error::ErrorKind::Backend("Error decoding changes tries backfill range".into()).into()),
+			None => Ok(None),
+		}
+	}
+
+	/// Block numbers whose changes tries have failed to build and are retried.
+	fn changes_tries_backfill_failed(&self) -> error::Result<Vec<u64>> {
+		match self.backend.get_aux(BACKFILL_FAILED_KEY)? {
+			Some(encoded) => Decode::decode(&mut &encoded[..])
+				.ok_or_else(|| error::ErrorKind::Backend("Error decoding failed changes tries".into()).into()),
+			None => Ok(Vec::new()),
+		}
+	}
+
+	/// Whether some changes tries skipped at import haven't been built yet.
+	fn has_skipped_changes_tries(&self) -> error::Result<bool> {
+		Ok(self.changes_tries_backfill_range()?.is_some() || !self.changes_tries_backfill_failed()?.is_empty())
+	}
+
+	/// Hash, encoded pre-runtime header and its aux key of the canonical block with given number,
+	/// if its changes trie has been skipped at import and hasn't been built yet.
+	fn skipped_changes_trie(&self, block: u64) -> error::Result<Option<(Block::Hash, Vec<u8>, Vec<u8>)>> {
+		let hash = match self.backend.blockchain().hash(As::sa(block))? {
+			Some(hash) => hash,
+			None => return Ok(None),
+		};
+		let key = changes_trie_backfill::pre_header_key(&hash);
+		Ok(self.backend.get_aux(&key)?.map(|pre_header| (hash, pre_header, key)))
+	}
+
+	/// Whether the inputs of the skipped changes trie of the block are still available: the
+	/// parent state hasn't been pruned and the tries the digest is built from haven't been
+	/// dropped, i.e. either exist or are still to be built.
+	fn can_build_changes_trie(&self, config: &ChangesTrieConfiguration, block: u64, dropped: &HashSet<u64>) -> error::Result<bool> {
+		let parent_hash = match self.backend.blockchain().hash(As::sa(block - 1))? {
+			Some(parent_hash) => parent_hash,
+			None => return Ok(false),
+		};
+		if self.backend.state_at(BlockId::Hash(parent_hash)).is_err() {
+			return Ok(false);
+		}
+
+		if let Some((first, last)) = changes_trie_backfill::digest_input_range(config, block) {
+			for input in first..last + 1 {
+				if dropped.contains(&input) {
+					return Ok(false);
+				}
+				if !self.has_changes_tries(input, input)? && self.skipped_changes_trie(input)?.is_none() {
+					return Ok(false);
+				}
+			}
+		}
+
+		Ok(true)
+	}
+
+	/// Whether all changes tries of the given (inclusive) range of blocks have been built.
+	fn has_changes_tries(&self, first: u64, last: u64) -> error::Result<bool> {
+		let storage = match self.backend.changes_trie_storage() {
+			Some(storage) => storage,
+			None => return Ok(false),
+		};
+
+		for block in first..last + 1 {
+			if let Some(root) = storage.root(block).map_err(error::ErrorKind::ChangesTrieAccessFailed)? {
+				if storage.get(&root).map_err(error::ErrorKind::ChangesTrieAccessFailed)?.is_none() {
+					return Ok(false);
+				}
+			}
+		}
+
+		Ok(true)
+	}
+
+	/// Returns the storage transactions of the block built by this node, produced from the changes
+	/// remembered when the block has been built. Returns None if the block has to be executed.
+	fn own_block_transaction<S: StateBackend<Blake2Hasher>>(
+		&self,
+		state: &S,
+		header: &Block::Header,
+	) -> Option<(S::Transaction, Option<trie::MemoryDB<Blake2Hasher>>, OverlayedChanges)> {
+		let hash = header.hash();
+		let OwnBlockChanges { parent_hash, mut changes } = self.own_block_changes.lock().remove(&hash)?;
+		if parent_hash != *header.parent_hash() {
+			return None;
+		}
+
+		let (root, changes_trie_root, storage_update, changes_update) = overlay_transaction(
+			state,
+			self.backend.changes_trie_storage(),
+			&mut changes,
+			header.number().as_(),
+		);
+		if root != *header.state_root() {
+			warn!(target: "client", "Changes of the built block {} don't match its state root. Executing the block", hash);
+			return None;
+		}
+		let changes_trie_root = changes_trie_root.map(|root| root.as_ref().to_vec());
+		let header_changes_trie_root = header.digest().log(DigestItemT::as_changes_trie_root)
+			.map(|root| root.as_ref().to_vec());
+		if changes_trie_root != header_changes_trie_root {
+			warn!(target: "client", "Changes of the built block {} don't match its changes trie root. Executing the block", hash);
+			return None;
+		}
+
+		// the block isn't executed, so the trace of the previously executed block is stale
+		self.access_trace.lock().clear();
+		trace!(target: "client", "Reusing changes of the built block {}", hash);
+		Some((storage_update, changes_update, changes))
+	}
+
+	/// Returns the changes trie root from the header of the imported block, if the changes trie
+	/// should not be built at import.
+	fn changes_trie_root_to_trust(&self, origin: BlockOrigin, header: &Block::Header) -> error::Result<Option<Vec<u8>>> {
+		let config = match (self.changes_trie_config.as_ref(), self.backend.changes_trie_storage()) {
+			(Some(config), Some(_)) => config,
+			_ => return Ok(None),
+		};
+		let root = match header.digest().log(DigestItemT::as_changes_trie_root) {
+			Some(root) => root.as_ref().to_vec(),
+			None => return Ok(None),
+		};
+
+		if origin == BlockOrigin::NetworkInitialSync && self.skip_changes_tries_on_sync.load(AtomicOrdering::Relaxed) {
+			return Ok(Some(root));
+		}
+
+		// digest tries are built from the tries of previous blocks, so they can't be built
+		// either until these tries are backfilled
+		match changes_trie_backfill::digest_input_range(config, header.number().as_()) {
+			Some((first, last)) if self.has_skipped_changes_tries()? && !self.has_changes_tries(first, last)? =>
+				Ok(Some(root)),
+			_ => Ok(None),
+		}
+	}
+
+	/// Remember the block whose changes trie has been skipped at import, so that it is built later.
+	fn note_skipped_changes_trie(&self, hash: Block::Hash, pre_header: &Block::Header) -> error::Result<()> {
+		let number = pre_header.number().as_();
+		let _lock = self.changes_tries_backfill_lock.lock();
+		let range = match self.changes_tries_backfill_range()? {
+			Some((first, last)) => (cmp::min(first, number), cmp::max(last, number)),
+			None => (number, number),
+		};
+		let encoded_range = range.encode();
+		let key = changes_trie_backfill::pre_header_key(&hash);
+		let encoded_header = pre_header.encode();
+		self.backend.insert_aux(&[(BACKFILL_RANGE_KEY, &encoded_range[..]), (&key[..], &encoded_header[..])], &[])
+	}
+
+	/// Build the changes trie of an already imported block by executing it once again.
+	fn build_changes_trie(&self, config: &ChangesTrieConfiguration, hash: Block::Hash, pre_header: &[u8]) -> error::Result<()> {
+		let pre_header = <Block as BlockT>::Header::decode(&mut &pre_header[..])
+			.ok_or_else(|| error::ErrorKind::Backend("Error decoding pre-runtime header".into()))?;
+		if let Some((first, last)) = changes_trie_backfill::digest_input_range(config, pre_header.number().as_()) {
+			if !self.has_changes_tries(first, last)? {
+				return Err(error::ErrorKind::ChangesTrieAccessFailed(
+					format!("changes tries of blocks #{}..#{} are missing", first, last)).into());
+			}
+		}
+
+		let body = self.body(&BlockId::Hash(hash))?
+			.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{}", hash)))?;
+		let state = self.state_at(&BlockId::Hash(*pre_header.parent_hash()))?;
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_chain_entropy(self.chain_entropy(*pre_header.parent_hash())?);
+		overlay.set_recent_block_hashes(self.recent_block_hashes(*pre_header.parent_hash())?);
+		// the block has been imported already, so the runtime checks the root of the built trie
+		// against the one in the header
+		let (_, _, changes_update) = self.executor.call_at_state(
+			&state,
+			&mut overlay,
+			"execute_block",
+			&<Block as BlockT>::new(pre_header, body).encode(),
+			ExecutionContext::Importing,
+			None,
+			native_when_possible(),
+		)?;
+
+		match changes_update {
+			Some(changes_update) => self.backend.insert_changes_trie(BlockId::Hash(hash), changes_update),
+			None => Err(error::ErrorKind::ChangesTriesNotSupported.into()),
+		}
+	}
+
+	/// Check whether the block should replace the current best block of the same height.
+	/// Blocks without known arrival time never win over the best block.
+	fn seen_before(&self, hash: &Block::Hash, best_hash: &Block::Hash) -> bool {
+		if *self.fork_choice_rule.read() != ForkChoiceRule::FirstSeen {
+			return false;
+		}
+
+		let arrivals = self.block_arrivals.lock();
+		match (arrivals.get(hash), arrivals.get(best_hash)) {
+			(Some(arrived), Some(best_arrived)) => arrived < best_arrived,
+			_ => false,
+		}
+	}
+
+	/// Warm up the backend caches for executing a block on top of the given parent.
+	fn prefetch_state(&self, parent_hash: Block::Hash, body: &Option<Vec<Block::Extrinsic>>) {
+		let mut keys = self.access_trace.lock().clone();
+		if let (Some(hints), Some(body)) = (self.prefetch_hints.read().as_ref(), body.as_ref()) {
+			keys.extend(hints.storage_keys(body));
+		}
+
+		if let Err(e) = self.backend.prefetch_state(BlockId::Hash(parent_hash), &keys) {
+			debug!(target: "client", "Error prefetching state of {}: {:?}", parent_hash, e);
+		}
+	}
+
+	/// Persist divergence report in aux storage and report it to telemetry.
+	fn note_divergence(&self, report: DivergenceReport<Block::Hash>) {
+		warn!(target: "client", "   Storage diff {:?}", report.storage_diff);
+		telemetry!("runtime.execute.divergence";
+			"block" => ?report.block,
+			"method" => %report.method,
+			"native_result" => ?report.native_result,
+			"wasm_result" => ?report.wasm_result,
+			"storage_diff" => ?report.storage_diff
+		);
+
+		let mut reports = match self.divergence_reports() {
+			Ok(reports) => reports,
+			Err(e) => {
+				warn!(target: "client", "Unable to read previous divergence reports: {}", e);
+				Vec::new()
+			},
+		};
+		reports.push(report);
+		if reports.len() > MAX_DIVERGENCE_REPORTS {
+			let excess = reports.len() - MAX_DIVERGENCE_REPORTS;
+			reports.drain(..excess);
+		}
+
+		let encoded = reports.encode();
+		if let Err(e) = self.backend.insert_aux(&[(DIVERGENCE_REPORTS_KEY, &encoded[..])], &[]) {
+			warn!(target: "client", "Unable to persist divergence report: {}", e);
+		}
+	}
+
+	/// Get the value of the storage entry at `key` over the range of blocks `from..=to`.
+	///
+	/// Returns the value at `from`, followed by every block in the range at which the value has
+	/// changed, along with the new value. Changes tries are used to find the candidate blocks when
+	/// they are supported and the range is on the canonical chain; otherwise every block of the
+	/// range is inspected.
+	pub fn storage_history(
+		&self,
+		key: &StorageKey,
+		from: Block::Hash,
+		to: Block::Hash,
+	) -> error::Result<Vec<(Block::Hash, Option<StorageData>)>> {
+		let from_number = self.require_block_number_from_id(&BlockId::Hash(from))?;
+		let to_number = self.require_block_number_from_id(&BlockId::Hash(to))?;
+		if from_number > to_number {
+			return Err(error::ErrorKind::InvalidBlockRange(format!("{}", from), format!("{}", to)).into());
+		}
+
+		let is_canonical = self.block_hash(from_number)? == Some(from) && self.block_hash(to_number)? == Some(to);
+		let changed_blocks = if is_canonical && from_number < to_number {
+			match self.changed_blocks(from_number, to, key) {
+				Ok(blocks) => Some(blocks),
+				Err(error::Error(error::ErrorKind::ChangesTriesNotSupported, _)) => None,
+				Err(err) => return Err(err),
+			}
+		} else {
+			None
+		};
+		let candidates = match changed_blocks {
+			Some(blocks) => blocks,
+			None => self.route_from(from, from_number, to, to_number)?,
+		};
+
+		let mut history = vec![(from, self.storage(&BlockId::Hash(from), key)?)];
+		for block in candidates {
+			let value = self.storage(&BlockId::Hash(block), key)?;
+			if history.last().map_or(true, |&(_, ref last)| *last != value) {
+				history.push((block, value));
+			}
+		}
+
+		Ok(history)
+	}
+
+	// hashes of canonical blocks in the range `(from_number, to]` where `key` has been changed,
+	// according to changes tries.
+	fn changed_blocks(
+		&self,
+		from_number: NumberFor<Block>,
+		to: Block::Hash,
+		key: &StorageKey,
+	) -> error::Result<Vec<Block::Hash>> {
+		let first = self.block_hash(from_number + As::sa(1))?
+			.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{}", from_number + As::sa(1))))?;
+		let mut numbers: Vec<_> = self.key_changes(first, to, &key.0)?
+			.into_iter()
+			.map(|(number, _)| number)
+			.collect();
+		numbers.sort();
+		numbers.dedup();
+		numbers.into_iter()
+			.map(|number| self.block_hash(number)?
+				.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{}", number)).into()))
+			.collect()
+	}
+
+	// hashes of all blocks in the range `(from, to]`, following parent links from `to`.
+	fn route_from(
+		&self,
+		from: Block::Hash,
+		from_number: NumberFor<Block>,
+		to: Block::Hash,
+		to_number: NumberFor<Block>,
+	) -> error::Result<Vec<Block::Hash>> {
+		let mut route = Vec::new();
+		let mut current = to;
+		let mut current_number = to_number;
+		while current_number > from_number {
+			route.push(current);
+			current = *self.header(&BlockId::Hash(current))?
+				.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{}", current)))?
+				.parent_hash();
+			current_number = current_number - As::sa(1);
+		}
+		if current != from {
+			return Err(error::ErrorKind::InvalidBlockRange(format!("{}", from), format!("{}", to)).into());
+		}
+
+		route.reverse();
+		Ok(route)
+	}
+
+	/// Get the storage keys that have been added, changed or removed between the states of the
+	/// blocks `from` and `to`.
+	///
+	/// Changes tries are used to find the candidate keys when they are supported and `from` is
+	/// the canonical ancestor of `to`; otherwise all pairs of both states are compared. Child
+	/// storages are not compared.
+	pub fn state_diff(&self, from: Block::Hash, to: Block::Hash) -> error::Result<StateDiff> {
+		let from_number = self.require_block_number_from_id(&BlockId::Hash(from))?;
+		let to_number = self.require_block_number_from_id(&BlockId::Hash(to))?;
+		let is_canonical = self.block_hash(from_number)? == Some(from) && self.block_hash(to_number)? == Some(to);
+		let changed_keys = if is_canonical && from_number <= to_number {
+			self.changed_keys(from_number, to_number)?
+		} else {
+			None
+		};
+
+		let from_state = self.state_at(&BlockId::Hash(from))?;
+		let to_state = self.state_at(&BlockId::Hash(to))?;
+		match changed_keys {
+			Some(keys) => {
+				let mut values = Vec::with_capacity(keys.len());
+				for key in keys {
+					let from_value = from_state.storage(&key).map_err(|e| error::Error::from_state(Box::new(e)))?;
+					let to_value = to_state.storage(&key).map_err(|e| error::Error::from_state(Box::new(e)))?;
+					values.push((key, from_value, to_value));
+				}
+				Ok(StateDiff::from_values(values))
+			},
+			None => Ok(StateDiff::from_values(from_state.storage_diff(&to_state)
+				.map_err(|e| error::Error::from_state(Box::new(e)))?)),
+		}
+	}
+
+	// keys changed at the canonical blocks `(from_number, to_number]`, according to changes tries.
+	// None if changes tries aren't supported or the trie of some block in the range is missing.
+	fn changed_keys(
+		&self,
+		from_number: NumberFor<Block>,
+		to_number: NumberFor<Block>,
+	) -> error::Result<Option<BTreeSet<Vec<u8>>>> {
+		let storage = match (self.changes_trie_config.as_ref(), self.backend.changes_trie_storage()) {
+			(Some(_), Some(storage)) => storage,
+			_ => return Ok(None),
+		};
+
+		let mut keys = BTreeSet::new();
+		for block in from_number.as_() + 1..to_number.as_() + 1 {
+			match block_changed_keys::<_, Blake2Hasher>(storage, block)
+				.map_err(|err| error::ErrorKind::ChangesTrieAccessFailed(err))?
+			{
+				Some(block_keys) => keys.extend(block_keys),
+				None => return Ok(None),
+			}
+		}
+
+		Ok(Some(keys))
+	}
+
 	/// Get proof for computation of (block, extrinsic) pairs where key has been changed at given blocks range.
 	/// `max` is the hash of the last block known to the requester - we can't use changes tries from descendants
 	/// of this block.
@@ -409,6 +1310,53 @@ impl<B, E, Block> Client<B, E, Block> where
 		block_builder::BlockBuilder::at_block(parent, &self)
 	}
 
+	/// Create a new block, built on top of `parent`, with the given pre-runtime digest items
+	/// made available to the runtime while it is being built.
+	pub fn new_block_at_with_pre_digest(
+		&self,
+		parent: &BlockId<Block>,
+		pre_digest: Vec<DigestItemFor<Block>>,
+	) -> error::Result<block_builder::BlockBuilder<B, E, Block, Blake2Hasher>>
+	where E: Clone
+	{
+		block_builder::BlockBuilder::at_block_with_pre_digest(parent, &self, pre_digest)
+	}
+
+	/// Create a new block, built on top of `parent`, recording the trie nodes read while it is
+	/// being built to estimate the size of the proof of its execution. If the budget is given,
+	/// extrinsics that would make the estimated size exceed it are rejected.
+	pub fn new_block_at_recording_proof(
+		&self,
+		parent: &BlockId<Block>,
+		budget: Option<usize>,
+	) -> error::Result<block_builder::BlockBuilder<B, E, Block, Blake2Hasher>>
+	where E: Clone
+	{
+		block_builder::BlockBuilder::at_block_recording_proof(parent, &self, Vec::new(), budget)
+	}
+
+	/// Apply the extrinsic on top of the state of the given block, as the first extrinsic of the
+	/// block built on it, without importing anything. Returns the result of the application and
+	/// the encoded events deposited in the block so far, if the runtime metadata defines the
+	/// `System Events` storage item.
+	pub fn dry_run_extrinsic(
+		&self,
+		at: &BlockId<Block>,
+		xt: &Block::Extrinsic,
+	) -> error::Result<(ApplyResult, Option<Vec<u8>>)>
+	where E: Clone
+	{
+		let events_item = self.executor.call(at, "metadata", &[]).ok()
+			.and_then(|metadata| RuntimeMetadata::decode(&mut &metadata.return_data[..]))
+			.and_then(|metadata| storage_query::StorageItem::find(&metadata, "System", "Events").ok());
+		let events_key = match events_item {
+			Some(item) => Some(item.key(None)?),
+			None => None,
+		};
+
+		self.new_block_at(at)?.dry_run(xt, events_key.as_ref().map(|key| &key.0[..]))
+	}
+
 	/// Set up the native execution environment to call into a native runtime code.
 	pub fn call_api<A, R>(&self, function: &'static str, args: &A) -> error::Result<R>
 		where A: Encode, R: Decode
@@ -421,19 +1369,22 @@ impl<B, E, Block> Client<B, E, Block> where
 		where A: Encode, R: Decode
 	{
 		let parent = at;
+		let parent_hash = self.block_hash_from_id(&parent)?
+			.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", parent)))?;
 		let header = <<Block as BlockT>::Header as HeaderT>::new(
 			self.block_number_from_id(&parent)?
 				.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", parent)))? + As::sa(1),
 			Default::default(),
 			Default::default(),
-			self.block_hash_from_id(&parent)?
-				.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{:?}", parent)))?,
+			parent_hash,
 			Default::default()
 		);
-		let mut overlay = Default::default();
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_chain_entropy(self.chain_entropy(parent_hash)?);
+		overlay.set_recent_block_hashes(self.recent_block_hashes(parent_hash)?);
 
-		self.call_at_state(at, "initialise_block", &header, &mut overlay)?;
-		self.call_at_state(at, function, args, &mut overlay)
+		self.call_at_state(at, "initialise_block", &header, &mut overlay, ExecutionContext::Other)?;
+		self.call_at_state(at, function, args, &mut overlay, ExecutionContext::Other)
 	}
 
 	fn call_at_state<A: Encode, R: Decode>(
@@ -441,27 +1392,36 @@ impl<B, E, Block> Client<B, E, Block> where
 		at: &BlockId<Block>,
 		function: &'static str,
 		args: &A,
-		changes: &mut OverlayedChanges
+		changes: &mut OverlayedChanges,
+		context: ExecutionContext,
 	) -> error::Result<R> {
 		let state = self.state_at(at)?;
 
 		let execution_manager = || match self.api_execution_strategy {
 			ExecutionStrategy::NativeWhenPossible => ExecutionManager::NativeWhenPossible,
 			ExecutionStrategy::AlwaysWasm => ExecutionManager::AlwaysWasm,
-			ExecutionStrategy::Both => ExecutionManager::Both(|wasm_result, native_result| {
-				warn!("Consensus error between wasm and native runtime execution at block {:?}", at);
-				warn!("   Function {:?}", function);
-				warn!("   Native result {:?}", native_result);
-				warn!("   Wasm result {:?}", wasm_result);
+			ExecutionStrategy::Both => ExecutionManager::Both(|wasm_result, native_result, storage_diff| {
+				warn!(target: "client", "Consensus error between wasm and native runtime execution at block {:?}", at);
+				warn!(target: "client", "   Function {:?}", function);
+				warn!(target: "client", "   Native result {:?}", native_result);
+				warn!(target: "client", "   Wasm result {:?}", wasm_result);
+				let block = self.block_hash_from_id(at).ok().and_then(|hash| hash).unwrap_or_default();
+				self.note_divergence(DivergenceReport::new(block, function, &native_result, &wasm_result, storage_diff));
 				wasm_result
 			}),
 		};
 
+		let local_keys = match context {
+			ExecutionContext::Offchain => self.local_keys.read().clone(),
+			_ => None,
+		};
 		self.executor.call_at_state(
 			&state,
 			changes,
 			function,
 			&args.encode(),
+			context,
+			local_keys.as_ref().map(|keys| &**keys),
 			execution_manager()
 		).and_then(|res|
 			R::decode(&mut &res.0[..])
@@ -477,7 +1437,7 @@ impl<B, E, Block> Client<B, E, Block> where
 			Some(ref extrinsics) => {
 				let mut tags = vec![];
 				for tx in extrinsics {
-					let tx = api::TaggedTransactionQueue::validate_transaction(self, &id, &tx)?;
+					let tx = self.validate_transaction_at(&id, &tx)?;
 					match tx {
 						TransactionValidity::Valid { mut provides, .. } => {
 							tags.append(&mut provides);
@@ -493,6 +1453,30 @@ impl<B, E, Block> Client<B, E, Block> where
 		})
 	}
 
+	fn verify_justification(
+		&self,
+		parent_hash: Block::Hash,
+		header: &Block::Header,
+		justification: &Justification,
+	) -> error::Result<()> {
+		// nothing proves the finality of the header without the verifier, so it is rejected
+		let verifier = self.justification_verifier.read();
+		let verifier = match verifier.as_ref() {
+			Some(verifier) => verifier,
+			None => {
+				warn!(target: "client", "Rejecting header {}: no justification verifier registered", header.hash());
+				return Err(ErrorKind::BadJustification(format!("{}: no justification verifier registered", header.hash())).into());
+			},
+		};
+
+		let authorities = self.authorities_at(&BlockId::Hash(parent_hash))?;
+		verifier.verify_justification(header, justification, &authorities)
+			.map_err(|e| {
+				warn!(target: "client", "Rejecting header {} with bad justification: {}", header.hash(), e);
+				ErrorKind::BadJustification(format!("{}: {}", header.hash(), e)).into()
+			})
+	}
+
 	fn execute_and_import_block(
 		&self,
 		origin: BlockOrigin,
@@ -525,47 +1509,83 @@ impl<B, E, Block> Client<B, E, Block> where
 		// ensure parent block is finalized to maintain invariant that
 		// finality is called sequentially.
 		if finalized {
-			self.apply_finality(parent_hash, last_best, make_notifications)?;
+			self.apply_finality(parent_hash, None, last_best, make_notifications)?;
 		}
 
 		let tags = self.transaction_tags(parent_hash, &body)?;
+		self.prefetch_state(parent_hash, &body);
+		let trusted_changes_trie_root = self.changes_trie_root_to_trust(origin, import_headers.pre())?;
 		let mut transaction = self.backend.begin_operation(BlockId::Hash(parent_hash))?;
 		let (storage_update, changes_update, storage_changes) = match transaction.state()? {
 			Some(transaction_state) => {
-				let mut overlay = Default::default();
-				let mut r = self.executor.call_at_state(
-					transaction_state,
-					&mut overlay,
-					"execute_block",
-					&<Block as BlockT>::new(import_headers.pre().clone(), body.clone().unwrap_or_default()).encode(),
-					match (origin, self.block_execution_strategy) {
-						(BlockOrigin::NetworkInitialSync, _) | (_, ExecutionStrategy::NativeWhenPossible) =>
-							ExecutionManager::NativeWhenPossible,
-						(_, ExecutionStrategy::AlwaysWasm) => ExecutionManager::AlwaysWasm,
-						_ => ExecutionManager::Both(|wasm_result, native_result| {
-							let header = import_headers.post();
-							warn!("Consensus error between wasm and native block execution at block {}", hash);
-							warn!("   Header {:?}", header);
-							warn!("   Native result {:?}", native_result);
-							warn!("   Wasm result {:?}", wasm_result);
-							telemetry!("block.execute.consensus_failure";
-								"hash" => ?hash,
-								"origin" => ?origin,
-								"header" => ?header
-							);
-							wasm_result
-						}),
+				let own_changes = match (origin, trusted_changes_trie_root.is_none()) {
+					(BlockOrigin::Own, true) => self.own_block_transaction(transaction_state, import_headers.pre()),
+					_ => None,
+				};
+				let (storage_update, changes_update, mut overlay) = match own_changes {
+					Some(own_changes) => own_changes,
+					None => {
+						let transaction_state = TrackingBackend::new(transaction_state);
+						let mut overlay = OverlayedChanges::default();
+						overlay.set_chain_entropy(self.chain_entropy(parent_hash)?);
+						overlay.set_recent_block_hashes(self.recent_block_hashes(parent_hash)?);
+						if let Some(ref root) = trusted_changes_trie_root {
+							overlay.trust_changes_trie_root(root.clone());
+						}
+						let mut r = self.executor.call_at_state(
+							&transaction_state,
+							&mut overlay,
+							"execute_block",
+							&<Block as BlockT>::new(import_headers.pre().clone(), body.clone().unwrap_or_default()).encode(),
+							match origin {
+								BlockOrigin::NetworkInitialSync => ExecutionContext::Syncing,
+								_ => ExecutionContext::Importing,
+							},
+							None,
+							match (origin, self.block_execution_strategy) {
+								(BlockOrigin::NetworkInitialSync, _) | (_, ExecutionStrategy::NativeWhenPossible) =>
+									ExecutionManager::NativeWhenPossible,
+								(_, ExecutionStrategy::AlwaysWasm) => ExecutionManager::AlwaysWasm,
+								_ => ExecutionManager::Both(|wasm_result, native_result, storage_diff| {
+									let header = import_headers.post();
+									warn!(target: "client", "Consensus error between wasm and native block execution at block {}", hash);
+									warn!(target: "client", "   Header {:?}", header);
+									warn!(target: "client", "   Native result {:?}", native_result);
+									warn!(target: "client", "   Wasm result {:?}", wasm_result);
+									telemetry!("block.execute.consensus_failure";
+										"hash" => ?hash,
+										"origin" => ?origin,
+										"header" => ?header
+									);
+									self.note_divergence(DivergenceReport::new(hash, "execute_block", &native_result, &wasm_result, storage_diff));
+									wasm_result
+								}),
+							},
+						);
+						let (_, storage_update, changes_update) = r?;
+						let mut access_trace = transaction_state.read_keys();
+						access_trace.truncate(MAX_ACCESS_TRACE_KEYS);
+						*self.access_trace.lock() = access_trace;
+						(storage_update, changes_update, overlay)
 					},
-				);
-				let (_, storage_update, changes_update) = r?;
+				};
 				overlay.commit_prospective();
 				(Some(storage_update), Some(changes_update), Some(overlay.into_committed()))
 			},
 			None => (None, None, None)
 		};
 
-		// TODO: non longest-chain rule.
-		let is_new_best = finalized || import_headers.post().number() > &last_best_number;
+		let is_new_best = finalized || match import_headers.post().number().cmp(&last_best_number) {
+			cmp::Ordering::Greater => true,
+			cmp::Ordering::Equal => self.seen_before(&hash, &last_best),
+			cmp::Ordering::Less => false,
+		};
+
+		// the state isn't available locally, so the header hasn't been checked by the execution
+		if storage_update.is_none() && is_new_best {
+			self.verify_justification(parent_hash, import_headers.post(), &justification)?;
+		}
+
 		let leaf_state = if finalized {
 			::backend::NewBlockState::Final
 		} else if is_new_best {
@@ -574,7 +1594,7 @@ impl<B, E, Block> Client<B, E, Block> where
 			::backend::NewBlockState::Normal
 		};
 
-		trace!("Imported {}, (#{}), best={}, origin={:?}", hash, import_headers.post().number(), is_new_best, origin);
+		trace!(target: "client", "Imported {}, (#{}), best={}, origin={:?}", hash, import_headers.post().number(), is_new_best, origin);
 
 		transaction.set_block_data(
 			import_headers.post().clone(),
@@ -594,6 +1614,10 @@ impl<B, E, Block> Client<B, E, Block> where
 		}
 		self.backend.commit_operation(transaction)?;
 
+		if trusted_changes_trie_root.is_some() {
+			self.note_skipped_changes_trie(hash, import_headers.pre())?;
+		}
+
 		if make_notifications {
 			if let Some(storage_changes) = storage_changes {
 				// TODO [ToDr] How to handle re-orgs? Should we re-emit all storage changes?
@@ -607,8 +1631,7 @@ impl<B, E, Block> Client<B, E, Block> where
 					header: import_headers.post().clone(),
 				};
 
-				self.finality_notification_sinks.lock()
-					.retain(|sink| sink.unbounded_send(notification.clone()).is_ok());
+				self.finality_notification_sinks.lock().notify(&notification);
 			}
 
 			let notification = BlockImportNotification::<Block> {
@@ -619,19 +1642,58 @@ impl<B, E, Block> Client<B, E, Block> where
 				tags,
 			};
 
-			self.import_notification_sinks.lock()
-				.retain(|sink| sink.unbounded_send(notification.clone()).is_ok());
+			self.import_notification_sinks.lock().notify(&notification);
+		}
+
+		Ok(ImportResult::Queued)
+	}
+
+	/// Import block of the block gap. The block is not executed, since the state of its parent
+	/// is missing, and it is known to be canonical, since the backend ensures that it is the parent
+	/// of the lowest block above the gap.
+	fn import_gap_block(
+		&self,
+		hash: Block::Hash,
+		import_headers: PrePostHeader<Block::Header>,
+		justification: Justification,
+		body: Option<Vec<Block::Extrinsic>>,
+	) -> error::Result<ImportResult> {
+		match self.backend.blockchain().status(BlockId::Hash(hash))? {
+			blockchain::BlockStatus::InChain => return Ok(ImportResult::AlreadyInChain),
+			blockchain::BlockStatus::Unknown => {},
 		}
 
+		trace!(target: "client", "Imported gap block {}, (#{})", hash, import_headers.post().number());
+
+		let mut transaction = self.backend.begin_operation(BlockId::Hash(Default::default()))?;
+		transaction.set_block_data(
+			import_headers.into_post(),
+			body,
+			Some(justification),
+			::backend::NewBlockState::Normal,
+		)?;
+		self.backend.commit_operation(transaction)?;
+
 		Ok(ImportResult::Queued)
 	}
 
-	/// Finalizes all blocks up to given.
-	fn apply_finality(&self, block: Block::Hash, best_block: Block::Hash, notify: bool) -> error::Result<()> {
+	/// Finalizes all blocks up to given. The justification, if any, is stored along with the given block.
+	fn apply_finality(
+		&self,
+		block: Block::Hash,
+		justification: Option<Justification>,
+		best_block: Block::Hash,
+		notify: bool,
+	) -> error::Result<()> {
 		// find tree route from last finalized to given block.
 		let last_finalized = self.backend.blockchain().last_finalized()?;
 
-		if block == last_finalized { return Ok(()) }
+		if block == last_finalized {
+			if justification.is_some() {
+				self.backend.finalize_block(BlockId::Hash(block), justification)?;
+			}
+			return Ok(())
+		}
 		let route_from_finalized = ::blockchain::tree_route(
 			self.backend.blockchain(),
 			BlockId::Hash(last_finalized),
@@ -639,7 +1701,7 @@ impl<B, E, Block> Client<B, E, Block> where
 		)?;
 
 		if let Some(retracted) = route_from_finalized.retracted().get(0) {
-			warn!("Safety violation: attempted to revert finalized block {:?} which is not in the \
+			warn!(target: "client", "Safety violation: attempted to revert finalized block {:?} which is not in the \
 				same chain as last finalized {:?}", retracted, last_finalized);
 
 			bail!(error::ErrorKind::NotInFinalizedChain);
@@ -658,8 +1720,10 @@ impl<B, E, Block> Client<B, E, Block> where
 			// `block`.
 		}
 
+		let mut justification = justification;
 		for finalize_new in route_from_finalized.enacted() {
-			self.backend.finalize_block(BlockId::Hash(finalize_new.hash))?;
+			let justification = if finalize_new.hash == block { justification.take() } else { None };
+			self.backend.finalize_block(BlockId::Hash(finalize_new.hash), justification)?;
 		}
 
 		if notify {
@@ -677,7 +1741,7 @@ impl<B, E, Block> Client<B, E, Block> where
 					hash: finalized.hash,
 				};
 
-				sinks.retain(|sink| sink.unbounded_send(notification.clone()).is_ok());
+				sinks.notify(&notification);
 			}
 		}
 
@@ -698,7 +1762,32 @@ impl<B, E, Block> Client<B, E, Block> where
 				.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("No block with number {:?}", n)))?,
 		};
 
-		self.apply_finality(to_finalize_hash, last_best, notify)
+		let _import_lock = self.import_lock.lock();
+		self.apply_finality(to_finalize_hash, None, last_best, notify)
+	}
+
+	/// Import the justification of the block that has been imported before, e.g. when the
+	/// justification arrives long after the block. The justification is checked by the
+	/// registered justification verifier, stored and the block (along with all its ancestors)
+	/// is finalized.
+	pub fn import_justification(&self, hash: Block::Hash, justification: Justification) -> error::Result<()> {
+		if justification.is_empty() {
+			return Err(error::ErrorKind::BadJustification(format!("{}: empty justification", hash)).into());
+		}
+
+		let header = self.backend.blockchain().header(BlockId::Hash(hash))?
+			.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{}", hash)))?;
+		self.verify_justification(*header.parent_hash(), &header, &justification)?;
+
+		let info = self.backend.blockchain().info()?;
+		if *header.number() < info.finalized_number {
+			// the descendant is finalized already, so the justification isn't required
+			trace!(target: "client", "Ignoring justification of {} below the last finalized block", hash);
+			return Ok(());
+		}
+
+		let _import_lock = self.import_lock.lock();
+		self.apply_finality(hash, Some(justification), info.best_hash, true)
 	}
 
 	/// Attempts to revert the chain by `n` blocks. Returns the number of blocks that were
@@ -731,11 +1820,29 @@ impl<B, E, Block> Client<B, E, Block> where
 		}
 	}
 
+	/// Get the inclusive range of numbers of canonical blocks that are missing from the database,
+	/// because the chain has been imported starting from a later block.
+	pub fn block_gap(&self) -> error::Result<Option<(NumberFor<Block>, NumberFor<Block>)>> {
+		self.backend.blockchain().gap()
+	}
+
 	/// Get block hash by number.
 	pub fn block_hash(&self, block_number: <<Block as BlockT>::Header as HeaderT>::Number) -> error::Result<Option<Block::Hash>> {
 		self.backend.blockchain().hash(block_number)
 	}
 
+	/// Get the entropy provided to the runtime when executing the block built on top of the
+	/// given parent.
+	pub fn chain_entropy(&self, parent_hash: Block::Hash) -> error::Result<Vec<u8>> {
+		chain_entropy(self.backend.blockchain(), parent_hash)
+	}
+
+	/// Get the hashes of the recent blocks provided to the runtime when executing the block
+	/// built on top of the given parent, starting from the parent.
+	pub fn recent_block_hashes(&self, parent_hash: Block::Hash) -> error::Result<Vec<Vec<u8>>> {
+		recent_block_hashes(self.backend.blockchain(), parent_hash)
+	}
+
 	/// Convert an arbitrary block ID into a block hash.
 	pub fn block_hash_from_id(&self, id: &BlockId<Block>) -> error::Result<Option<Block::Hash>> {
 		match *id {
@@ -768,6 +1875,18 @@ impl<B, E, Block> Client<B, E, Block> where
 		self.backend.blockchain().body(*id)
 	}
 
+	/// Get block body by id, retrieving it from the remote nodes if it isn't stored locally
+	/// (i.e. by the light client). Must not be waited for on the network thread.
+	pub fn body_future<'a>(&'a self, id: &BlockId<Block>) -> BlockchainFuture<'a, Option<Vec<<Block as BlockT>::Extrinsic>>> {
+		self.backend.blockchain().body_future(*id)
+	}
+
+	/// Get hash of the canonical block by number, retrieving it from the remote nodes if it isn't
+	/// stored locally (i.e. by the light client). Must not be waited for on the network thread.
+	pub fn block_hash_future<'a>(&'a self, number: NumberFor<Block>) -> BlockchainFuture<'a, Option<Block::Hash>> {
+		self.backend.blockchain().hash_future(number)
+	}
+
 	/// Get block justification set by id.
 	pub fn justification(&self, id: &BlockId<Block>) -> error::Result<Option<Justification>> {
 		self.backend.blockchain().justification(*id)
@@ -916,10 +2035,14 @@ impl<B, E, Block> consensus::BlockImport<Block> for Client<B, E, Block> where
 			..
 		} = import_block;
 		let parent_hash = header.parent_hash().clone();
+		let in_gap = self.backend.blockchain().gap()?
+			.map_or(false, |(start, end)| *header.number() >= start && *header.number() <= end);
 
-		match self.backend.blockchain().status(BlockId::Hash(parent_hash))? {
-			blockchain::BlockStatus::InChain => {},
-			blockchain::BlockStatus::Unknown => return Ok(ImportResult::UnknownParent),
+		if !in_gap {
+			match self.backend.blockchain().status(BlockId::Hash(parent_hash))? {
+				blockchain::BlockStatus::InChain => {},
+				blockchain::BlockStatus::Unknown => return Ok(ImportResult::UnknownParent),
+			}
 		}
 
 		let import_headers = if post_runtime_digests.is_empty() {
@@ -933,19 +2056,24 @@ impl<B, E, Block> consensus::BlockImport<Block> for Client<B, E, Block> where
 		};
 
 		let hash = import_headers.post().hash();
+		self.note_block_arrival(hash, Instant::now());
 		let _import_lock = self.import_lock.lock();
 		let height: u64 = import_headers.post().number().as_();
 		*self.importing_block.write() = Some(hash);
 
-		let result = self.execute_and_import_block(
-			origin,
-			hash,
-			import_headers,
-			external_justification,
-			body,
-			new_authorities,
-			finalized,
-		);
+		let result = if in_gap {
+			self.import_gap_block(hash, import_headers, external_justification, body)
+		} else {
+			self.execute_and_import_block(
+				origin,
+				hash,
+				import_headers,
+				external_justification,
+				body,
+				new_authorities,
+				finalized,
+			)
+		};
 
 		*self.importing_block.write() = None;
 		telemetry!("block.import";
@@ -966,6 +2094,10 @@ impl<B, E, Block> consensus::Authorities<Block> for Client<B, E, Block> where
 	fn authorities(&self, at: &BlockId<Block>) -> Result<Vec<AuthorityId>, Self::Error> {
 		self.authorities_at(at).map_err(|e| e.into())
 	}
+
+	fn disabled_authorities(&self, at: &BlockId<Block>) -> Result<Vec<AuthorityId>, Self::Error> {
+		self.disabled_validators_at(at)
+	}
 }
 
 impl<B, E, Block> CurrentHeight for Client<B, E, Block> where
@@ -998,16 +2130,12 @@ where
 	Block: BlockT<Hash=H256>,
 {
 	/// Get block import event stream.
-	fn import_notification_stream(&self) -> ImportNotifications<Block> {
-		let (sink, stream) = mpsc::unbounded();
-		self.import_notification_sinks.lock().push(sink);
-		stream
+	fn import_notification_stream(&self, label: &'static str) -> ImportNotifications<Block> {
+		self.import_notification_sinks.lock().subscribe(label)
 	}
 
-	fn finality_notification_stream(&self) -> FinalityNotifications<Block> {
-		let (sink, stream) = mpsc::unbounded();
-		self.finality_notification_sinks.lock().push(sink);
-		stream
+	fn finality_notification_stream(&self, label: &'static str) -> FinalityNotifications<Block> {
+		self.finality_notification_sinks.lock().subscribe(label)
 	}
 
 	/// Get storage changes event stream.
@@ -1027,6 +2155,26 @@ where
 	}
 }
 
+impl<B, E, Block> Finalize<Block> for Client<B, E, Block> where
+	B: backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	fn finalize(&self, id: BlockId<Block>) -> error::Result<()> {
+		self.finalize_block(id, true)
+	}
+}
+
+impl<B, E, Block> BlockHeader<Block> for Client<B, E, Block> where
+	B: backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	fn block_header(&self, id: &BlockId<Block>) -> error::Result<Option<<Block as BlockT>::Header>> {
+		self.header(id)
+	}
+}
+
 impl<B, E, Block> BlockBody<Block> for Client<B, E, Block> where
 	B: backend::Backend<Block, Blake2Hasher>,
 	E: CallExecutor<Block, Blake2Hasher>,
@@ -1057,6 +2205,18 @@ impl<B, E, Block> api::Core<Block, AuthorityId> for Client<B, E, Block> where
 	}
 }
 
+impl<B, E, Block> api::DisabledValidators<Block, AuthorityId> for Client<B, E, Block> where
+	B: backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	type Error = Error;
+
+	fn disabled_validators(&self, at: &BlockId<Block>) -> Result<Vec<AuthorityId>, Self::Error> {
+		self.disabled_validators_at(at)
+	}
+}
+
 impl<B, E, Block> api::Metadata<Block, Vec<u8>> for Client<B, E, Block> where
 	B: backend::Backend<Block, Blake2Hasher>,
 	E: CallExecutor<Block, Blake2Hasher>,
@@ -1083,7 +2243,7 @@ impl<B, E, Block> api::BlockBuilder<Block> for Client<B, E, Block> where
 		changes: &mut OverlayedChanges,
 		header: &<Block as BlockT>::Header
 	) -> Result<(), Self::Error> {
-		self.call_at_state(at, "initialise_block", header, changes)
+		self.call_at_state(at, "initialise_block", header, changes, ExecutionContext::BlockConstruction)
 	}
 
 	fn apply_extrinsic(
@@ -1092,7 +2252,7 @@ impl<B, E, Block> api::BlockBuilder<Block> for Client<B, E, Block> where
 		changes: &mut OverlayedChanges,
 		extrinsic: &<Block as BlockT>::Extrinsic
 	) -> Result<ApplyResult, Self::Error> {
-		self.call_at_state(at, "apply_extrinsic", extrinsic, changes)
+		self.call_at_state(at, "apply_extrinsic", extrinsic, changes, ExecutionContext::BlockConstruction)
 	}
 
 	fn finalise_block(
@@ -1100,7 +2260,7 @@ impl<B, E, Block> api::BlockBuilder<Block> for Client<B, E, Block> where
 		at: &BlockId<Block>,
 		changes: &mut OverlayedChanges
 	) -> Result<<Block as BlockT>::Header, Self::Error> {
-		self.call_at_state(at, "finalise_block", &(), changes)
+		self.call_at_state(at, "finalise_block", &(), changes, ExecutionContext::BlockConstruction)
 	}
 
 	fn inherent_extrinsics<InherentExtrinsic: Encode + Decode, UncheckedExtrinsic: Encode + Decode>(
@@ -1276,6 +2436,125 @@ pub(crate) mod tests {
 		assert_eq!(client.call_api::<_, u64>("balance_of", &Keyring::Ferdie.to_raw_public()).unwrap(), 42);
 	}
 
+	#[test]
+	fn own_block_changes_are_reused_at_import() {
+		let client = test_client::new();
+
+		let mut builder = client.new_block().unwrap();
+		builder.push_transfer(Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 42,
+			nonce: 0,
+		}).unwrap();
+		let block = builder.bake().unwrap();
+		assert!(client.own_block_changes.lock().contains_key(&block.hash()));
+		*client.access_trace.lock() = vec![b"stale".to_vec()];
+
+		client.justify_and_import(BlockOrigin::Own, block).unwrap();
+		assert!(client.own_block_changes.lock().is_empty());
+		assert!(client.access_trace.lock().is_empty());
+		assert_eq!(client.info().unwrap().chain.best_number, 1);
+		assert_eq!(client.call_api::<_, u64>("balance_of", &Keyring::Alice.to_raw_public()).unwrap(), 958);
+		assert_eq!(client.call_api::<_, u64>("balance_of", &Keyring::Ferdie.to_raw_public()).unwrap(), 42);
+
+		// blocks received from the network are always executed
+		let block = client.new_block().unwrap().bake().unwrap();
+		client.justify_and_import(BlockOrigin::NetworkBroadcast, block.clone()).unwrap();
+		assert!(client.own_block_changes.lock().contains_key(&block.hash()));
+		assert_eq!(client.info().unwrap().chain.best_number, 2);
+	}
+
+	#[test]
+	fn block_builder_respects_proof_budget() {
+		let client = test_client::new();
+		let transfer = || Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Bob.to_raw_public().into(),
+			amount: 10,
+			nonce: 0,
+		};
+
+		let genesis = BlockId::Number(0);
+		assert_eq!(client.new_block().unwrap().proof_size(), None);
+
+		// block initialisation is recorded
+		let mut builder = client.new_block_at_recording_proof(&genesis, None).unwrap();
+		let initialised_size = builder.proof_size().unwrap();
+		assert!(initialised_size > 0);
+		builder.push_transfer(transfer()).unwrap();
+		let size = builder.proof_size().unwrap();
+		assert!(size > initialised_size);
+
+		// block finalisation is recorded
+		let (_, baked_size) = builder.bake_with_proof_size().unwrap();
+		assert!(baked_size.unwrap() >= size);
+
+		// the transfer doesn't fit into the smaller budget
+		let mut builder = client.new_block_at_recording_proof(&genesis, Some(size - 1)).unwrap();
+		match builder.push_transfer(transfer()) {
+			Err(Error(ErrorKind::ProofBudgetExceeded(exceeding, budget), _)) => {
+				assert_eq!(exceeding, size);
+				assert_eq!(budget, size - 1);
+			},
+			_ => panic!("the proof budget is exceeded"),
+		}
+		assert_eq!(builder.proof_size(), Some(initialised_size));
+		assert!(builder.bake().unwrap().extrinsics.is_empty());
+
+		let mut builder = client.new_block_at_recording_proof(&genesis, Some(size)).unwrap();
+		builder.push_transfer(transfer()).unwrap();
+		assert_eq!(builder.bake().unwrap().extrinsics.len(), 1);
+	}
+
+	#[test]
+	fn dry_run_does_not_change_the_chain() {
+		let client = test_client::new();
+		let transfer = |nonce| {
+			let transfer = Transfer {
+				from: Keyring::Alice.to_raw_public().into(),
+				to: Keyring::Ferdie.to_raw_public().into(),
+				amount: 42,
+				nonce,
+			};
+			let signature = Keyring::Alice.sign(&transfer.encode()).into();
+			runtime::Extrinsic { transfer, signature }
+		};
+
+		let (result, _) = client.dry_run_extrinsic(&BlockId::Number(0), &transfer(0)).unwrap();
+		assert_eq!(result, Ok(::runtime_primitives::ApplyOutcome::Success));
+		let (result, _) = client.dry_run_extrinsic(&BlockId::Number(0), &transfer(1)).unwrap();
+		assert_eq!(result, Err(::runtime_primitives::ApplyError::Stale));
+
+		assert_eq!(client.info().unwrap().chain.best_number, 0);
+		assert_eq!(client.call_api::<_, u64>("balance_of", &Keyring::Alice.to_raw_public()).unwrap(), 1000);
+		assert_eq!(client.call_api::<_, u64>("balance_of", &Keyring::Ferdie.to_raw_public()).unwrap(), 0);
+	}
+
+	#[test]
+	fn extrinsic_proof_works() {
+		let client = test_client::new();
+
+		let mut builder = client.new_block().unwrap();
+		for (nonce, to) in vec![Keyring::Bob, Keyring::Charlie, Keyring::Dave].into_iter().enumerate() {
+			builder.push_transfer(Transfer {
+				from: Keyring::Alice.to_raw_public().into(),
+				to: to.to_raw_public().into(),
+				amount: 10,
+				nonce: nonce as u64,
+			}).unwrap();
+		}
+		client.justify_and_import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+
+		let header = client.header(&BlockId::Number(1)).unwrap().unwrap();
+		let body = client.body(&BlockId::Number(1)).unwrap().unwrap();
+		let (extrinsic, proof) = client.extrinsic_proof(&BlockId::Number(1), 1).unwrap().unwrap();
+		assert_eq!(extrinsic, body[1]);
+		assert_eq!(check_extrinsic_proof::<_, Blake2Hasher, runtime::Extrinsic>(&header, 1, proof.clone()).unwrap(), body[1]);
+		assert!(check_extrinsic_proof::<_, Blake2Hasher, runtime::Extrinsic>(&header, 2, proof).is_err());
+		assert!(client.extrinsic_proof(&BlockId::Number(1), 3).unwrap().is_none());
+	}
+
 	#[test]
 	fn client_uses_authorities_from_blockchain_cache() {
 		let client = test_client::new();
@@ -1575,6 +2854,98 @@ pub(crate) mod tests {
 		assert_eq!(None, client.best_containing(d2.hash().clone(), Some(0)).unwrap());
 	}
 
+	#[test]
+	fn divergence_reports_are_persisted() {
+		use state_machine::StorageDiff;
+
+		let client = test_client::new();
+		assert!(client.divergence_reports().unwrap().is_empty());
+
+		let genesis_hash = client.info().unwrap().chain.genesis_hash;
+		for i in 0..MAX_DIVERGENCE_REPORTS + 2 {
+			client.note_divergence(DivergenceReport::new(
+				genesis_hash,
+				"execute_block",
+				&Ok::<_, String>(vec![i as u8]),
+				&Err("wasm trap".to_string()),
+				StorageDiff::default(),
+			));
+		}
+
+		let reports = client.divergence_reports().unwrap();
+		assert_eq!(reports.len(), MAX_DIVERGENCE_REPORTS);
+		assert_eq!(reports[0].native_result, Ok(vec![2]));
+		assert_eq!(reports[0].wasm_result, Err("wasm trap".to_string()));
+		assert_eq!(reports[0].block, genesis_hash);
+	}
+
+	#[test]
+	fn storage_history_works() {
+		let client = test_client::new();
+		let alice = StorageKey(twox_128(&runtime::system::balance_of_key(Keyring::Alice.to_raw_public().into())).to_vec());
+
+		let mut hashes = vec![client.genesis_hash()];
+		for transfers in 0..3 {
+			let mut builder = client.new_block().unwrap();
+			if transfers != 1 {
+				builder.push_transfer(Transfer {
+					from: Keyring::Alice.to_raw_public().into(),
+					to: Keyring::Ferdie.to_raw_public().into(),
+					amount: 42,
+					nonce: if transfers == 0 { 0 } else { 1 },
+				}).unwrap();
+			}
+			let block = builder.bake().unwrap();
+			hashes.push(block.header.hash());
+			client.justify_and_import(BlockOrigin::Own, block).unwrap();
+		}
+
+		let history = client.storage_history(&alice, hashes[0], hashes[3]).unwrap();
+		let blocks: Vec<_> = history.iter().map(|&(hash, _)| hash).collect();
+		assert_eq!(blocks, vec![hashes[0], hashes[1], hashes[3]]);
+		assert_eq!(history[0].1, client.storage(&BlockId::Hash(hashes[0]), &alice).unwrap());
+		assert_eq!(history[2].1, client.storage(&BlockId::Hash(hashes[3]), &alice).unwrap());
+
+		let history = client.storage_history(&alice, hashes[1], hashes[2]).unwrap();
+		assert_eq!(history.len(), 1);
+		assert!(client.storage_history(&alice, hashes[2], hashes[1]).is_err());
+	}
+
+	#[test]
+	fn storage_history_uses_changes_tries() {
+		let (client, _, _) = prepare_client_with_key_changes();
+		let alice = StorageKey(twox_128(&runtime::system::balance_of_key(Keyring::Alice.to_raw_public().into())).to_vec());
+		let hashes: Vec<_> = (0..5).map(|n| client.block_hash(n).unwrap().unwrap()).collect();
+
+		let history = client.storage_history(&alice, hashes[0], hashes[4]).unwrap();
+		let blocks: Vec<_> = history.iter().map(|&(hash, _)| hash).collect();
+		assert_eq!(blocks, vec![hashes[0], hashes[1], hashes[4]]);
+	}
+
+	#[test]
+	fn state_diff_works() {
+		let (client, _, _) = prepare_client_with_key_changes();
+		let alice = StorageKey(twox_128(&runtime::system::balance_of_key(Keyring::Alice.to_raw_public().into())).to_vec());
+		let eve = StorageKey(twox_128(&runtime::system::balance_of_key(Keyring::Eve.to_raw_public().into())).to_vec());
+		let hashes: Vec<_> = (0..5).map(|n| client.block_hash(n).unwrap().unwrap()).collect();
+
+		// changes tries are used for the canonical range => same diff as the whole states comparison
+		let diff = client.state_diff(hashes[0], hashes[4]).unwrap();
+		let pairs_diff = StateDiff::from_pairs(
+			client.state_at(&BlockId::Hash(hashes[0])).unwrap().pairs(),
+			client.state_at(&BlockId::Hash(hashes[4])).unwrap().pairs(),
+		);
+		assert_eq!(diff, pairs_diff);
+		assert!(diff.changed.contains(&alice));
+		assert!(diff.added.contains(&eve));
+
+		// reversed range => whole states are compared
+		let reversed_diff = client.state_diff(hashes[4], hashes[0]).unwrap();
+		assert_eq!(reversed_diff.added, diff.removed);
+		assert_eq!(reversed_diff.removed, diff.added);
+		assert!(client.state_diff(hashes[2], hashes[2]).unwrap().is_empty());
+	}
+
 	#[test]
 	fn key_changes_works() {
 		let (client, _, test_cases) = prepare_client_with_key_changes();
@@ -1590,4 +2961,138 @@ pub(crate) mod tests {
 			}
 		}
 	}
+
+	#[test]
+	fn changes_tries_skipped_during_sync_are_backfilled() {
+		let (remote_client, _, test_cases) = prepare_client_with_key_changes();
+		let client = test_client::new_with_changes_trie();
+		client.set_skip_changes_tries_on_sync(true);
+		for number in 1..5 {
+			let block = remote_client.block(&BlockId::Number(number)).unwrap().unwrap().block;
+			client.justify_and_import(BlockOrigin::NetworkInitialSync, block).unwrap();
+		}
+		assert!(!client.has_changes_tries(1, 4).unwrap());
+		assert_eq!(client.changes_tries_backfill_range().unwrap(), Some((1, 4)));
+
+		assert_eq!(client.backfill_changes_tries(3).unwrap(), 3);
+		assert_eq!(client.changes_tries_backfill_range().unwrap(), Some((4, 4)));
+		assert_eq!(client.backfill_changes_tries(3).unwrap(), 1);
+		assert_eq!(client.changes_tries_backfill_range().unwrap(), None);
+		assert!(client.has_changes_tries(1, 4).unwrap());
+
+		for (begin, end, key, expected_result) in test_cases {
+			let begin = client.block_hash(begin).unwrap().unwrap();
+			let end = client.block_hash(end).unwrap().unwrap();
+			assert_eq!(client.key_changes(begin, end, &key).unwrap(), expected_result);
+		}
+	}
+
+	#[test]
+	fn first_seen_block_wins_among_blocks_of_same_height() {
+		use std::time::Duration;
+
+		let import_siblings = |rule| {
+			let client = test_client::new();
+			client.set_fork_choice_rule(rule);
+
+			let a1 = client.new_block().unwrap().bake().unwrap();
+			let mut builder = client.new_block().unwrap();
+			// this push is required as otherwise B1 has the same hash as A1
+			builder.push_transfer(Transfer {
+				from: Keyring::Alice.to_raw_public().into(),
+				to: Keyring::Ferdie.to_raw_public().into(),
+				amount: 41,
+				nonce: 0,
+			}).unwrap();
+			let b1 = builder.bake().unwrap();
+
+			// A1 is seen first, but B1 is imported first
+			let now = Instant::now();
+			client.note_block_arrival(a1.hash(), now);
+			client.note_block_arrival(b1.hash(), now + Duration::from_secs(1));
+			client.justify_and_import(BlockOrigin::NetworkBroadcast, b1.clone()).unwrap();
+			assert_eq!(client.info().unwrap().chain.best_hash, b1.hash());
+			client.justify_and_import(BlockOrigin::NetworkBroadcast, a1.clone()).unwrap();
+
+			(client.info().unwrap().chain.best_hash, a1.hash(), b1.hash())
+		};
+
+		let (best, _, b1) = import_siblings(ForkChoiceRule::LongestChain);
+		assert_eq!(best, b1);
+		let (best, a1, _) = import_siblings(ForkChoiceRule::FirstSeen);
+		assert_eq!(best, a1);
+	}
+
+	#[test]
+	fn justification_is_imported_after_block() {
+		struct Verifier;
+
+		impl JustificationVerifier<Block> for Verifier {
+			fn verify_justification(
+				&self,
+				_header: &runtime::Header,
+				justification: &Justification,
+				_authorities: &[AuthorityId],
+			) -> error::Result<()> {
+				match justification[..] {
+					[42] => Ok(()),
+					_ => Err("Bad justification".into()),
+				}
+			}
+		}
+
+		let client = test_client::new();
+		for _ in 0..2 {
+			let block = client.new_block().unwrap().bake().unwrap();
+			client.justify_and_import(BlockOrigin::Own, block).unwrap();
+		}
+		let hash = client.block_hash(2).unwrap().unwrap();
+
+		// justification can't be verified without the verifier
+		assert!(client.import_justification(hash, vec![42]).is_err());
+		assert_eq!(client.info().unwrap().chain.finalized_number, 0);
+
+		client.set_justification_verifier(Box::new(Verifier));
+
+		// bad and empty justifications are rejected
+		assert!(client.import_justification(hash, vec![]).is_err());
+		assert!(client.import_justification(hash, vec![1]).is_err());
+		assert_eq!(client.info().unwrap().chain.finalized_number, 0);
+
+		// good justification is stored and finalizes the block with its ancestors
+		client.import_justification(hash, vec![42]).unwrap();
+		assert_eq!(client.info().unwrap().chain.finalized_number, 2);
+		assert_eq!(client.justification(&BlockId::Hash(hash)).unwrap(), Some(vec![42]));
+	}
+
+	#[test]
+	fn api_version_is_read_from_runtime_version() {
+		let client = test_client::new();
+		let id = BlockId::Number(0);
+
+		assert_eq!(client.api_version::<api::id::TaggedTransactionQueueApi>(&id).unwrap(), Some(2));
+		assert!(client.has_api::<api::id::TaggedTransactionQueueApi>(&id).unwrap());
+
+		// the test runtime doesn't declare the block builder API
+		assert_eq!(client.api_version::<api::id::BlockBuilderApi>(&id).unwrap(), None);
+		assert!(!client.has_api::<api::id::BlockBuilderApi>(&id).unwrap());
+	}
+
+	#[test]
+	fn validate_transaction_at_uses_declared_api_version() {
+		let client = test_client::new();
+		let transfer = Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 42,
+			nonce: 0,
+		};
+		let signature = Keyring::Alice.sign(&transfer.encode()).into();
+		let tx = runtime::Extrinsic { transfer, signature };
+
+		match client.validate_transaction_at(&BlockId::Number(0), &tx).unwrap() {
+			TransactionValidity::Valid { propagate, .. } => assert!(propagate),
+			validity => panic!("Unexpected validity: {:?}", validity),
+		}
+	}
 }
@@ -0,0 +1,137 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reports of diverging native and wasm runtime executions.
+
+use std::fmt::Display;
+use codec::{Encode, Decode, Input, Output};
+use state_machine::StorageDiff;
+
+/// Aux key under which the most recent divergence reports are kept.
+pub const DIVERGENCE_REPORTS_KEY: &[u8] = b"native_wasm_divergence_reports";
+
+/// Maximal number of divergence reports kept in aux storage. Older reports are dropped.
+pub const MAX_DIVERGENCE_REPORTS: usize = 32;
+
+/// Report of a runtime call which has produced different results when executed
+/// natively and in wasm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DivergenceReport<Hash> {
+	/// Hash of the block at which the call has been executed.
+	pub block: Hash,
+	/// Name of the runtime method that has been called.
+	pub method: String,
+	/// Output of the native execution or its error description.
+	pub native_result: Result<Vec<u8>, String>,
+	/// Output of the wasm execution or its error description.
+	pub wasm_result: Result<Vec<u8>, String>,
+	/// Difference between storage changes of both executions.
+	pub storage_diff: StorageDiff,
+}
+
+impl<Hash> DivergenceReport<Hash> {
+	/// Create a report from the results of both executions.
+	pub fn new<E: Display>(
+		block: Hash,
+		method: &str,
+		native_result: &Result<Vec<u8>, E>,
+		wasm_result: &Result<Vec<u8>, E>,
+		storage_diff: StorageDiff,
+	) -> Self {
+		let describe = |result: &Result<Vec<u8>, E>| match *result {
+			Ok(ref output) => Ok(output.clone()),
+			Err(ref error) => Err(error.to_string()),
+		};
+
+		DivergenceReport {
+			block,
+			method: method.into(),
+			native_result: describe(native_result),
+			wasm_result: describe(wasm_result),
+			storage_diff,
+		}
+	}
+}
+
+fn encode_result<T: Output>(result: &Result<Vec<u8>, String>, dest: &mut T) {
+	match *result {
+		Ok(ref output) => {
+			dest.push_byte(0);
+			output.encode_to(dest);
+		},
+		Err(ref error) => {
+			dest.push_byte(1);
+			error.as_bytes().encode_to(dest);
+		},
+	}
+}
+
+fn decode_result<I: Input>(input: &mut I) -> Option<Result<Vec<u8>, String>> {
+	match input.read_byte()? {
+		0 => Some(Ok(Decode::decode(input)?)),
+		1 => String::from_utf8(Decode::decode(input)?).ok().map(Err),
+		_ => None,
+	}
+}
+
+impl<Hash: Encode> Encode for DivergenceReport<Hash> {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		self.block.encode_to(dest);
+		self.method.as_bytes().encode_to(dest);
+		encode_result(&self.native_result, dest);
+		encode_result(&self.wasm_result, dest);
+		self.storage_diff.top.encode_to(dest);
+		self.storage_diff.children.encode_to(dest);
+	}
+}
+
+impl<Hash: Decode> Decode for DivergenceReport<Hash> {
+	fn decode<I: Input>(input: &mut I) -> Option<Self> {
+		Some(DivergenceReport {
+			block: Decode::decode(input)?,
+			method: String::from_utf8(Decode::decode(input)?).ok()?,
+			native_result: decode_result(input)?,
+			wasm_result: decode_result(input)?,
+			storage_diff: StorageDiff {
+				top: Decode::decode(input)?,
+				children: Decode::decode(input)?,
+			},
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use primitives::H256;
+	use super::*;
+
+	#[test]
+	fn divergence_report_encoding_roundtrips() {
+		let report = DivergenceReport::new(
+			H256::from(1),
+			"execute_block",
+			&Ok::<_, String>(vec![1, 2, 3]),
+			&Err("wasm trap".to_string()),
+			StorageDiff {
+				top: vec![(vec![1], Some(Some(vec![2])), Some(None))],
+				children: vec![(vec![2], vec![3], None, Some(Some(vec![4])))],
+			},
+		);
+
+		let encoded = report.encode();
+		assert_eq!(DivergenceReport::<H256>::decode(&mut &encoded[..]), Some(report));
+	}
+}
@@ -40,12 +40,24 @@ error_chain! {
 			display("UnknownBlock: {}", &*h),
 		}
 
+		/// Header of the ancestor of the executed block is missing.
+		MissingAncestor(h: String) {
+			description("missing ancestor header"),
+			display("Header of the ancestor {} is missing", &*h),
+		}
+
 		/// Applying extrinsic error.
 		ApplyExtinsicFailed(e: ApplyError) {
 			description("Extrinsic error"),
 			display("Extrinsic error: {:?}", e),
 		}
 
+		/// Extrinsic doesn't fit into the proof size budget of the block.
+		ProofBudgetExceeded(size: usize, budget: usize) {
+			description("Proof budget exceeded"),
+			display("Proof size {} exceeds the budget of {} bytes", size, budget),
+		}
+
 		/// Execution error.
 		Execution(e: Box<state_machine::Error>) {
 			description("execution error"),
@@ -58,6 +70,12 @@ error_chain! {
 			display("Blockchain: {}", e),
 		}
 
+		/// Storage query by names can't be resolved or its result decoded.
+		InvalidStorageQuery(s: String) {
+			description("invalid storage query"),
+			display("Invalid storage query: {}", s),
+		}
+
 		/// Invalid authorities set received from the runtime.
 		InvalidAuthoritiesSet {
 			description("authorities set is invalid"),
@@ -94,6 +112,18 @@ error_chain! {
 			display("Remote node has responded with invalid header proof"),
 		}
 
+		/// Invalid extrinsic inclusion proof.
+		InvalidExtrinsicProof {
+			description("invalid extrinsic proof"),
+			display("Extrinsic inclusion proof is invalid"),
+		}
+
+		/// Remote node has responded with block body that doesn't match the header.
+		InvalidBodyProof {
+			description("invalid body proof"),
+			display("Remote node has responded with block body that doesn't match the header"),
+		}
+
 		/// Remote fetch has been cancelled.
 		RemoteFetchCancelled {
 			description("remote fetch cancelled"),
@@ -106,6 +136,18 @@ error_chain! {
 			display("Remote data fetch has been failed"),
 		}
 
+		/// Remote fetch has timed out on every attempt.
+		RemoteFetchTimedOut(attempts: usize) {
+			description("remote fetch timed out"),
+			display("Remote data fetch has timed out after {} attempts", attempts),
+		}
+
+		/// Remote peers have responded with invalid data on every attempt.
+		RemoteFetchInvalidResponse(attempts: usize, reason: String) {
+			description("invalid remote fetch response"),
+			display("Remote data fetch has failed after {} attempts: {}", attempts, reason),
+		}
+
 		/// Error decoding call result.
 		CallResultDecode(method: &'static str) {
 			description("Error decoding call result")
@@ -135,6 +177,12 @@ error_chain! {
 			description("Potential long-range attack: block not in finalized chain."),
 			display("Potential long-range attack: block not in finalized chain."),
 		}
+
+		/// Invalid range of blocks.
+		InvalidBlockRange(from: String, to: String) {
+			description("invalid block range"),
+			display("Invalid block range: {} .. {}", &*from, &*to),
+		}
 	}
 }
 
@@ -24,7 +24,7 @@ use backend::{self, NewBlockState};
 use light;
 use primitives::{AuthorityId, storage::well_known_keys};
 use runtime_primitives::generic::BlockId;
-use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Zero,
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Zero, One,
 	NumberFor, As, Digest, DigestItem};
 use runtime_primitives::{Justification, StorageMap, ChildrenStorageMap};
 use blockchain::{self, BlockStatus, HeaderBackend};
@@ -96,6 +96,7 @@ struct BlockchainStorage<Block: BlockT> {
 	genesis_hash: Block::Hash,
 	cht_roots: HashMap<NumberFor<Block>, Block::Hash>,
 	leaves: LeafSet<Block::Hash, NumberFor<Block>>,
+	aux: HashMap<Vec<u8>, Vec<u8>>,
 }
 
 /// In-memory blockchain. Supports concurrent reads.
@@ -144,6 +145,7 @@ impl<Block: BlockT> Blockchain<Block> {
 				genesis_hash: Default::default(),
 				cht_roots: HashMap::new(),
 				leaves: LeafSet::new(),
+				aux: HashMap::new(),
 			}));
 		Blockchain {
 			storage: storage.clone(),
@@ -239,12 +241,30 @@ impl<Block: BlockT> Blockchain<Block> {
 	}
 
 	fn finalize_header(&self, id: BlockId<Block>) -> error::Result<()> {
+		let (hash, number) = match self.header(id)? {
+			Some(h) => (h.hash(), *h.number()),
+			None => return Err(error::ErrorKind::UnknownBlock(format!("{}", id)).into()),
+		};
+
+		let mut storage = self.storage.write();
+		storage.finalized_hash = hash;
+		storage.finalized_number = number;
+		Ok(())
+	}
+
+	fn set_justification(&self, id: BlockId<Block>, justification: Justification) -> error::Result<()> {
 		let hash = match self.header(id)? {
 			Some(h) => h.hash(),
 			None => return Err(error::ErrorKind::UnknownBlock(format!("{}", id)).into()),
 		};
 
-		self.storage.write().finalized_hash = hash;
+		let mut storage = self.storage.write();
+		match storage.blocks.get_mut(&hash) {
+			Some(&mut StoredBlock::Header(_, ref mut j)) | Some(&mut StoredBlock::Full(_, ref mut j)) => {
+				*j = Some(justification);
+			},
+			None => return Err(error::ErrorKind::UnknownBlock(format!("{}", id)).into()),
+		}
 		Ok(())
 	}
 }
@@ -319,11 +339,12 @@ impl<Block: BlockT> light::blockchain::Storage<Block> for Blockchain<Block>
 		&self,
 		header: Block::Header,
 		authorities: Option<Vec<AuthorityId>>,
+		justification: Option<Justification>,
 		state: NewBlockState,
 	) -> error::Result<()> {
 		let hash = header.hash();
 		let parent_hash = *header.parent_hash();
-		self.insert(hash, header, None, None, state)?;
+		self.insert(hash, header, justification, None, state)?;
 		if state.is_best() {
 			self.cache.insert(parent_hash, authorities);
 		}
@@ -331,12 +352,61 @@ impl<Block: BlockT> light::blockchain::Storage<Block> for Blockchain<Block>
 		Ok(())
 	}
 
+	fn import_checkpoint(&self, header: Block::Header, authorities: Vec<AuthorityId>) -> error::Result<()> {
+		let hash = header.hash();
+		let number = *header.number();
+
+		{
+			let mut storage = self.storage.write();
+			if storage.blocks.len() != 1 || !storage.best_number.is_zero() || number.is_zero() {
+				return Err(error::ErrorKind::Backend("Checkpoint is only imported right after the genesis block".into()).into());
+			}
+
+			storage.leaves.import(hash, number, *header.parent_hash());
+			storage.blocks.insert(hash, StoredBlock::new(header, None, None));
+			storage.hashes.insert(number, hash);
+			storage.best_hash = hash;
+			storage.best_number = number;
+			storage.finalized_hash = hash;
+			storage.finalized_number = number;
+		}
+
+		self.cache.insert(hash, Some(authorities));
+		Ok(())
+	}
+
 	fn last_finalized(&self) -> error::Result<Block::Hash> {
 		Ok(self.storage.read().finalized_hash.clone())
 	}
 
-	fn finalize_header(&self, id: BlockId<Block>) -> error::Result<()> {
-		Blockchain::finalize_header(self, id)
+	fn justification(&self, id: BlockId<Block>) -> error::Result<Option<Justification>> {
+		blockchain::Backend::justification(self, id)
+	}
+
+	fn finalize_header(&self, id: BlockId<Block>, justification: Option<Justification>) -> error::Result<()> {
+		Blockchain::finalize_header(self, id)?;
+		match justification {
+			Some(justification) => self.set_justification(id, justification),
+			None => Ok(()),
+		}
+	}
+
+	fn revert_best(&self) -> error::Result<()> {
+		let mut storage = self.storage.write();
+		let (hash, number) = (storage.best_hash, storage.best_number);
+		if number.is_zero() || hash == storage.finalized_hash {
+			return Err(error::ErrorKind::Backend(format!("Unable to revert finalized block {:?}", hash)).into());
+		}
+
+		let parent_hash = match storage.blocks.remove(&hash) {
+			Some(block) => *block.header().parent_hash(),
+			None => return Err(error::ErrorKind::UnknownBlock(format!("{:?}", hash)).into()),
+		};
+		storage.hashes.remove(&number);
+		storage.leaves.revert(hash, number, parent_hash);
+		storage.best_hash = parent_hash;
+		storage.best_number = number - One::one();
+		Ok(())
 	}
 
 	fn cht_root(&self, _cht_size: u64, block: NumberFor<Block>) -> error::Result<Block::Hash> {
@@ -344,9 +414,28 @@ impl<Block: BlockT> light::blockchain::Storage<Block> for Blockchain<Block>
 			.ok_or_else(|| error::ErrorKind::Backend(format!("CHT for block {} not exists", block)).into())
 	}
 
+	fn set_headers_pruning(&self, _pruning: light::blockchain::HeadersPruning) {
+		// headers are never pruned from the in-memory storage
+	}
+
 	fn cache(&self) -> Option<&blockchain::Cache<Block>> {
 		Some(&self.cache)
 	}
+
+	fn insert_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a (&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>(&self, insert: I, delete: D) -> error::Result<()> {
+		let mut storage = self.storage.write();
+		for (k, v) in insert {
+			storage.aux.insert(k.to_vec(), v.to_vec());
+		}
+		for k in delete {
+			storage.aux.remove(*k);
+		}
+		Ok(())
+	}
+
+	fn get_aux(&self, key: &[u8]) -> error::Result<Option<Vec<u8>>> {
+		Ok(self.storage.read().aux.get(key).cloned())
+	}
 }
 
 /// In-memory operation.
@@ -496,10 +585,10 @@ where
 
 			let changes_trie_root = header.digest().log(DigestItem::as_changes_trie_root).cloned();
 			if let Some(changes_trie_root) = changes_trie_root {
-				if let Some(changes_trie_update) = operation.changes_trie_update {
-					let changes_trie_root: H::Out = changes_trie_root.into();
-					self.changes_trie_storage.insert(header.number().as_(), changes_trie_root, changes_trie_update);
-				}
+				// the root is kept even if the trie hasn't been built, so that it can be built later
+				let changes_trie_update = operation.changes_trie_update.unwrap_or_default();
+				let changes_trie_root: H::Out = changes_trie_root.into();
+				self.changes_trie_storage.insert(header.number().as_(), changes_trie_root, changes_trie_update);
 			}
 
 			self.blockchain.insert(hash, header, justification, body, pending_block.state)?;
@@ -511,8 +600,12 @@ where
 		Ok(())
 	}
 
-	fn finalize_block(&self, block: BlockId<Block>) -> error::Result<()> {
-		self.blockchain.finalize_header(block)
+	fn finalize_block(&self, block: BlockId<Block>, justification: Option<Justification>) -> error::Result<()> {
+		self.blockchain.finalize_header(block)?;
+		if let Some(justification) = justification {
+			self.blockchain.set_justification(block, justification)?;
+		}
+		Ok(())
 	}
 
 	fn blockchain(&self) -> &Self::Blockchain {
@@ -523,6 +616,15 @@ where
 		Some(&self.changes_trie_storage)
 	}
 
+	fn insert_changes_trie(&self, block: BlockId<Block>, update: MemoryDB<H>) -> error::Result<()> {
+		let header = self.blockchain.header(block)?
+			.ok_or_else(|| error::ErrorKind::UnknownBlock(format!("{}", block)))?;
+		let changes_trie_root = header.digest().log(DigestItem::as_changes_trie_root).cloned()
+			.ok_or_else(|| error::Error::from(format!("Block {} has no changes trie", block)))?;
+		self.changes_trie_storage.insert(header.number().as_(), changes_trie_root.into(), update);
+		Ok(())
+	}
+
 	fn state_at(&self, block: BlockId<Block>) -> error::Result<Self::State> {
 		match self.blockchain.id(block).and_then(|id| self.states.read().get(&id).cloned()) {
 			Some(state) => Ok(state),
@@ -548,6 +650,13 @@ where
 	fn get_aux(&self, key: &[u8]) -> error::Result<Option<Vec<u8>>> {
 		Ok(self.aux.read().get(key).cloned())
 	}
+
+	fn pin_block(&self, _hash: &Block::Hash) -> error::Result<()> {
+		// in-memory backend never prunes
+		Ok(())
+	}
+
+	fn unpin_block(&self, _hash: &Block::Hash) { }
 }
 
 impl<Block, H> backend::LocalBackend<Block, H> for Backend<Block, H>
@@ -604,4 +713,24 @@ mod tests {
 
 		test_client::trait_tests::test_blockchain_query_by_number_gets_canonical(backend);
 	}
+
+	#[test]
+	fn import_simulation_produces_expected_events() {
+		use test_client::import_simulation::{canned_blocks, simulate_import, ImportEvent, ImportMode, RejectReason};
+
+		let blocks = canned_blocks();
+		let hash = |index: usize| blocks[index].header.hash();
+		let client = test_client::new_with_backend(Arc::new(TestBackend::new()), false);
+		let events = simulate_import(&client, &blocks, ImportMode::Full).unwrap();
+		assert_eq!(events, vec![
+			ImportEvent::Accept { number: 1, hash: hash(0), best: true },
+			ImportEvent::Accept { number: 2, hash: hash(1), best: true },
+			ImportEvent::Accept { number: 2, hash: hash(2), best: false },
+			ImportEvent::Reorg { number: 3, hash: hash(3), common: 1, retracted: vec![hash(1)] },
+			ImportEvent::Reject { number: 2, hash: hash(4), reason: RejectReason::AlreadyInChain },
+			ImportEvent::Reject { number: 4, hash: hash(5), reason: RejectReason::Failed },
+			ImportEvent::Reject { number: 3, hash: hash(6), reason: RejectReason::UnknownParent },
+			ImportEvent::Accept { number: 4, hash: hash(7), best: true },
+		]);
+	}
 }
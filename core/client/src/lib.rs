@@ -38,7 +38,10 @@ extern crate parking_lot;
 extern crate hash_db;
 extern crate heapsize;
 extern crate kvdb;
+extern crate linked_hash_map;
 extern crate sr_api;
+extern crate substrate_metadata as metadata;
+extern crate serde;
 
 #[macro_use] extern crate error_chain;
 #[macro_use] extern crate log;
@@ -54,21 +57,33 @@ pub mod in_mem;
 pub mod genesis;
 pub mod block_builder;
 pub mod light;
+pub mod memory_budget;
+pub mod storage_query;
 mod leaves;
 mod call_executor;
+mod chain_entropy;
+mod changes_trie_backfill;
 mod client;
+mod divergence;
 mod notifications;
+mod notification_sinks;
+mod recent_blocks;
+mod state_diff;
 
 pub use blockchain::Info as ChainInfo;
 pub use call_executor::{CallResult, CallExecutor, LocalCallExecutor};
 pub use client::{
 	new_with_backend,
 	new_in_mem,
-	BlockBody, BlockStatus, ImportNotifications, FinalityNotifications, BlockchainEvents,
-	Client, ClientInfo, ChainHead,
+	check_extrinsic_proof,
+	BlockBody, BlockHeader, BlockStatus, ImportNotifications, FinalityNotifications, BlockchainEvents,
+	Client, ClientInfo, ChainHead, Finalize, ForkChoiceRule, JustificationVerifier, PinnedBlock, PrefetchHints,
 };
+pub use divergence::DivergenceReport;
 pub use notifications::{StorageEventStream, StorageChangeSet};
-pub use state_machine::ExecutionStrategy;
+pub use notification_sinks::{Notification, NotificationStream, NOTIFICATIONS_BUFFER_SIZE};
+pub use state_diff::StateDiff;
+pub use state_machine::{ExecutionStrategy, ExecutionContext, BenchmarkResult, BenchmarkRun, DbAccessCounts, TimingStats};
 pub use leaves::LeafSet;
 
 /// Traits for interfacing with the runtime from the client.
@@ -17,35 +17,115 @@
 //! Light client backend. Only stores headers and justifications of blocks.
 //! Everything else is requested from full nodes on demand.
 
+use std::collections::HashMap;
+use std::mem;
 use std::sync::{Arc, Weak};
-use futures::{Future, IntoFuture};
-use parking_lot::RwLock;
+use futures::{future, Async, Future, IntoFuture, Poll};
+use linked_hash_map::LinkedHashMap;
+use parking_lot::{Mutex, RwLock};
 
-use primitives::AuthorityId;
+use primitives::{AuthorityId, ChangesTrieConfiguration};
+use primitives::storage::well_known_keys;
 use runtime_primitives::{generic::BlockId, Justification, StorageMap, ChildrenStorageMap};
 use state_machine::{Backend as StateBackend, InMemoryChangesTrieStorage, TrieBackend};
-use runtime_primitives::traits::{Block as BlockT, NumberFor};
+use runtime_primitives::traits::{As, Block as BlockT, Header as HeaderT, NumberFor, Zero, One,
+	Digest, DigestItem};
 
 use in_mem;
 use backend::{Backend as ClientBackend, BlockImportOperation, RemoteBackend, NewBlockState};
 use blockchain::HeaderBackend as BlockchainHeaderBackend;
 use error::{Error as ClientError, ErrorKind as ClientErrorKind, Result as ClientResult};
 use light::blockchain::{Blockchain, Storage as BlockchainStorage};
-use light::fetcher::{Fetcher, RemoteReadRequest};
+use memory_budget::{MemoryAccount, MemoryBudget};
+use light::fetcher::{Fetcher, RemoteChangesRequest, RemoteReadRequest, RemoteReadChildRequest, RemoteReadPrefixRequest};
 use hash_db::Hasher;
 use trie::MemoryDB;
 use heapsize::HeapSizeOf;
+use codec::{Decode, Encode, KeyedVec};
+
+/// Aux key under which the latest justification of a finalized block is kept, along with the
+/// hash of this block.
+pub const LATEST_JUSTIFICATION_KEY: &[u8] = b"light_latest_justification";
+
+/// Aux key under which the genesis state root is kept, along with the well-known entries of the
+/// genesis storage.
+pub const GENESIS_STATE_KEY: &[u8] = b"light_genesis_state";
+
+/// Default size of the cached remote read results, in bytes.
+pub const DEFAULT_REMOTE_READ_CACHE_SIZE: usize = 8 * 1024 * 1024;
+
+/// Weight of the remote read cache in the shared memory budget.
+pub const REMOTE_READ_CACHE_WEIGHT: usize = 1;
+
+/// Number of storage entries requested at once when iterating over keys with given prefix.
+pub const READ_PREFIX_PAGE_SIZE: u32 = 256;
 
 /// Light client backend.
 pub struct Backend<S, F> {
 	blockchain: Arc<Blockchain<S, F>>,
+	read_cache: Arc<Mutex<RemoteReadCache>>,
+	pinned_keys: Arc<RwLock<PinnedKeys>>,
+	genesis_entries: Arc<RwLock<GenesisEntries>>,
+	/// Number of the trusted checkpoint. Blocks at or below the checkpoint are never imported.
+	checkpoint: Option<u64>,
+	/// Encoded header and authorities of the checkpoint, imported right after the genesis header.
+	pending_checkpoint: Mutex<Option<(Vec<u8>, Vec<AuthorityId>)>>,
+}
+
+/// Trusted checkpoint the light client is started from instead of the genesis block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint<Header> {
+	/// Header of the checkpoint block.
+	pub header: Header,
+	/// Authorities set at the checkpoint block.
+	pub authorities: Vec<AuthorityId>,
+}
+
+/// Bounded cache of the remote read results, keyed by block hash and storage key. When the size
+/// limit is reached, the least recently used entries are evicted first.
+pub struct RemoteReadCache {
+	entries: LinkedHashMap<(Vec<u8>, Vec<u8>), Option<Vec<u8>>>,
+	size: usize,
+	limit: usize,
+	stats: ReadCacheStats,
+	account: Option<MemoryAccount>,
+}
+
+/// Statistics of the remote read cache lookups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadCacheStats {
+	/// Number of lookups that have been answered from the cache.
+	pub hits: u64,
+	/// Number of lookups that have required a remote read.
+	pub misses: u64,
+}
+
+/// Storage keys that are read at almost every block (e.g. the runtime code or the authorities).
+/// The values are read from the remote node by the single request once the new best block is
+/// imported, and the storage of this block is read from the local copy.
+#[derive(Default)]
+pub struct PinnedKeys {
+	keys: Vec<Vec<u8>>,
+	/// Hash of the block the values have been read at, along with the proved values.
+	values: Option<(Vec<u8>, HashMap<Vec<u8>, Option<Vec<u8>>>)>,
+}
+
+/// Well-known entries of the genesis storage (e.g. the genesis authorities), which are known
+/// locally. The storage of the genesis block is read from them rather than from the remote node.
+#[derive(Default)]
+pub struct GenesisEntries {
+	/// Hash of the genesis block, along with the values of the entries.
+	values: Option<(Vec<u8>, HashMap<Vec<u8>, Option<Vec<u8>>>)>,
 }
 
 /// Light block (header and justification) import operation.
 pub struct ImportOperation<Block: BlockT, S, F> {
 	header: Option<Block::Header>,
 	authorities: Option<Vec<AuthorityId>>,
+	justification: Option<Justification>,
 	leaf_state: NewBlockState,
+	/// Genesis state root and the well-known entries of the genesis storage, set by `reset_storage`.
+	genesis_state: Option<(Block::Hash, Vec<(Vec<u8>, Option<Vec<u8>>)>)>,
 	_phantom: ::std::marker::PhantomData<(S, F)>,
 }
 
@@ -55,18 +135,242 @@ pub struct OnDemandState<Block: BlockT, S, F> {
 	blockchain: Weak<Blockchain<S, F>>,
 	block: Block::Hash,
 	cached_header: RwLock<Option<Block::Header>>,
+	read_cache: Arc<Mutex<RemoteReadCache>>,
+	pinned_keys: Arc<RwLock<PinnedKeys>>,
+	genesis_entries: Arc<RwLock<GenesisEntries>>,
+}
+
+/// Future resolving to the values of the storage entries, either cached or read from the remote
+/// node by the single request.
+pub type RemoteStorageMultiFuture<Block, F> = future::Either<
+	future::FutureResult<Vec<Option<Vec<u8>>>, ClientError>,
+	CachingRead<<<F as Fetcher<Block>>::RemoteReadResult as IntoFuture>::Future>,
+>;
+
+/// Future resolving to the value of the storage entry, either cached or read from the remote node.
+pub type RemoteStorageFuture<Block, F> = future::Map<
+	RemoteStorageMultiFuture<Block, F>,
+	fn(Vec<Option<Vec<u8>>>) -> Option<Vec<u8>>,
+>;
+
+/// Remote read of the storage entries that are missing from the cache, caching the values once
+/// they're received.
+pub struct CachingRead<R> {
+	read: Option<R>,
+	cache: Arc<Mutex<RemoteReadCache>>,
+	block: Vec<u8>,
+	/// Values of all requested entries, `None` for the entries that are read from the remote node.
+	values: Vec<Option<Option<Vec<u8>>>>,
+	/// Keys of the entries that are read from the remote node.
+	keys: Vec<Vec<u8>>,
 }
 
 impl<S, F> Backend<S, F> {
-	/// Create new light backend.
-	pub fn new(blockchain: Arc<Blockchain<S, F>>) -> Self {
-		Self { blockchain }
+	/// Create new light backend, started from the trusted checkpoint if given. The headers below
+	/// the checkpoint are never requested from the network and the forks below it are rejected.
+	pub fn new<Block>(
+		blockchain: Arc<Blockchain<S, F>>,
+		checkpoint: Option<Checkpoint<Block::Header>>,
+	) -> ClientResult<Self> where
+		Block: BlockT,
+		S: BlockchainStorage<Block>,
+	{
+		Self::with_read_cache_size(blockchain, DEFAULT_REMOTE_READ_CACHE_SIZE, checkpoint)
+	}
+
+	/// Create new light backend, caching at most `read_cache_size` bytes of the remote read results.
+	pub fn with_read_cache_size<Block>(
+		blockchain: Arc<Blockchain<S, F>>,
+		read_cache_size: usize,
+		checkpoint: Option<Checkpoint<Block::Header>>,
+	) -> ClientResult<Self> where
+		Block: BlockT,
+		S: BlockchainStorage<Block>,
+	{
+		let mut pending_checkpoint = None;
+		let checkpoint = match checkpoint {
+			Some(Checkpoint { header, authorities }) => {
+				let number = header.number().as_();
+				let info = blockchain.storage().info()?;
+				if info.best_hash == Default::default() {
+					pending_checkpoint = Some((header.encode(), authorities));
+				} else if info.best_number.is_zero() {
+					blockchain.storage().import_checkpoint(header, authorities)?;
+				}
+				Some(number)
+			},
+			None => None,
+		};
+
+		let backend = Self {
+			blockchain,
+			read_cache: Arc::new(Mutex::new(RemoteReadCache::new(read_cache_size))),
+			pinned_keys: Arc::new(RwLock::new(PinnedKeys::default())),
+			genesis_entries: Arc::new(RwLock::new(GenesisEntries::default())),
+			checkpoint,
+			pending_checkpoint: Mutex::new(pending_checkpoint),
+		};
+		if let Some((_, entries)) = backend.genesis_state::<Block>()? {
+			if let Some(genesis_hash) = backend.blockchain.storage().hash(Zero::zero())? {
+				backend.genesis_entries.write().set(genesis_hash, entries);
+			}
+		}
+		Ok(backend)
 	}
 
 	/// Get shared blockchain reference.
 	pub fn blockchain(&self) -> &Arc<Blockchain<S, F>> {
 		&self.blockchain
 	}
+
+	/// Get statistics of the remote read cache.
+	pub fn read_cache_stats(&self) -> ReadCacheStats {
+		self.read_cache.lock().stats()
+	}
+
+	/// Account the remote read cache in the shared memory budget.
+	pub fn set_memory_budget(&self, budget: &MemoryBudget) {
+		self.read_cache.lock().set_memory_budget(budget);
+	}
+
+	/// Pin the storage keys, so that they're read from the remote node at every new best block.
+	/// The values are available once the next best block is imported.
+	pub fn pin_storage_keys(&self, keys: Vec<Vec<u8>>) {
+		let mut pinned = self.pinned_keys.write();
+		for key in keys {
+			if !pinned.keys.contains(&key) {
+				pinned.keys.push(key);
+			}
+		}
+	}
+
+	/// Unpin the storage keys.
+	pub fn unpin_storage_keys(&self, keys: &[Vec<u8>]) {
+		let mut pinned = self.pinned_keys.write();
+		pinned.keys.retain(|key| !keys.contains(key));
+		if let Some((_, ref mut values)) = pinned.values {
+			for key in keys {
+				values.remove(key);
+			}
+		}
+	}
+
+	/// Read the pinned keys at the new best block. The storage of this block is read from the local
+	/// copy once the returned future resolves. If the read fails, the values of the previous best
+	/// block are discarded and the keys are read on demand.
+	///
+	/// Neither the import nor the sync wait for the remote node: the future is meant to be spawned
+	/// once the new best block is imported close to the head of the chain.
+	pub fn refresh_pinned_keys<Block>(&self, header: Block::Header) -> impl Future<Item=(), Error=()> where
+		Block: BlockT,
+		S: BlockchainStorage<Block>,
+		F: Fetcher<Block>,
+	{
+		let keys = self.pinned_keys.read().keys.clone();
+		if keys.is_empty() {
+			return future::Either::A(future::ok(()));
+		}
+
+		let hash = header.hash();
+		let values = match self.blockchain.fetcher().upgrade() {
+			Some(fetcher) => future::Either::A(fetcher.remote_read(RemoteReadRequest {
+				block: hash,
+				header,
+				keys: keys.clone(),
+				retry_count: None,
+			}).into_future()),
+			None => future::Either::B(future::err(ClientErrorKind::NotAvailableOnLightClient.into())),
+		};
+
+		let pinned_keys = self.pinned_keys.clone();
+		future::Either::B(values.then(move |values| {
+			let mut pinned_keys = pinned_keys.write();
+			pinned_keys.update(hash, keys, values);
+			pinned_keys.pin_authorities();
+			Ok(())
+		}))
+	}
+
+	/// Get the genesis state root along with the well-known entries of the genesis storage. `None`
+	/// if the genesis block hasn't been imported from the genesis storage.
+	pub fn genesis_state<Block>(&self) -> ClientResult<Option<(Block::Hash, Vec<(Vec<u8>, Option<Vec<u8>>)>)>> where
+		Block: BlockT,
+		S: BlockchainStorage<Block>,
+	{
+		Ok(self.blockchain.storage().get_aux(GENESIS_STATE_KEY)?
+			.and_then(|encoded| Decode::decode(&mut &encoded[..])))
+	}
+
+	/// Get pairs of (block, extrinsic) where the key has been changed at the given blocks range
+	/// of the best chain. The changes are read from the changes tries of the remote node and
+	/// checked against the changes tries roots of the local headers.
+	pub fn key_changes<Block>(
+		&self,
+		config: ChangesTrieConfiguration,
+		first: Block::Hash,
+		last: Block::Hash,
+		key: &[u8],
+	) -> ClientResult<Vec<(NumberFor<Block>, u32)>> where
+		Block: BlockT,
+		S: BlockchainStorage<Block>,
+		F: Fetcher<Block>,
+	{
+		let request = self.key_changes_request(config, first, last, key)?;
+		self.blockchain.fetcher().upgrade().ok_or(ClientErrorKind::NotAvailableOnLightClient)?
+			.remote_changes(request)
+			.into_future().wait()
+	}
+
+	/// Build the changes request for the given blocks range of the best chain, up to the best block.
+	fn key_changes_request<Block>(
+		&self,
+		config: ChangesTrieConfiguration,
+		first: Block::Hash,
+		last: Block::Hash,
+		key: &[u8],
+	) -> ClientResult<RemoteChangesRequest<Block::Header>> where
+		Block: BlockT,
+		S: BlockchainStorage<Block>,
+		F: Fetcher<Block>,
+	{
+		let canonical_number = |hash: Block::Hash| -> ClientResult<NumberFor<Block>> {
+			let number = self.blockchain.number(hash)?
+				.ok_or_else(|| ClientErrorKind::UnknownBlock(format!("{:?}", hash)))?;
+			if self.blockchain.hash(number)? != Some(hash) {
+				return Err(ClientErrorKind::ChangesTrieAccessFailed(
+					format!("Block {:?} is not a part of the best chain", hash)).into());
+			}
+			Ok(number)
+		};
+
+		let first_number = canonical_number(first)?;
+		let last_number = canonical_number(last)?;
+		if first_number > last_number {
+			return Err(ClientErrorKind::ChangesTrieAccessFailed(
+				format!("Invalid blocks range: {:?}..{:?}", first_number, last_number)).into());
+		}
+
+		// the roots of all tries up to the max block are required to check the proof
+		let info = self.blockchain.info()?;
+		let mut tries_roots = Vec::new();
+		let mut number = first_number;
+		while number <= info.best_number {
+			let header = self.blockchain.header(BlockId::Number(number))?
+				.ok_or_else(|| ClientErrorKind::UnknownBlock(format!("{:?}", number)))?;
+			tries_roots.push(header.digest().log(DigestItem::as_changes_trie_root).cloned().unwrap_or_default());
+			number = number + One::one();
+		}
+
+		Ok(RemoteChangesRequest {
+			changes_trie_config: config,
+			first_block: (first_number, first),
+			last_block: (last_number, last),
+			max_block: (info.best_number, info.best_hash),
+			tries_roots,
+			key: key.to_vec(),
+			retry_count: None,
+		})
+	}
 }
 
 impl<S, F, Block, H> ClientBackend<Block, H> for Backend<S, F> where
@@ -85,22 +389,78 @@ impl<S, F, Block, H> ClientBackend<Block, H> for Backend<S, F> where
 		Ok(ImportOperation {
 			header: None,
 			authorities: None,
+			justification: None,
 			leaf_state: NewBlockState::Normal,
+			genesis_state: None,
 			_phantom: Default::default(),
 		})
 	}
 
 	fn commit_operation(&self, operation: Self::BlockImportOperation) -> ClientResult<()> {
 		let header = operation.header.expect("commit is called after set_block_data; set_block_data sets header; qed");
+		let hash = header.hash();
+		let number = header.number().as_();
+		if self.checkpoint.map_or(false, |checkpoint| number != 0 && number <= checkpoint) {
+			warn!(target: "client", "Rejecting block {:?} at or below the trusted checkpoint", hash);
+			return Err(ClientErrorKind::NotInFinalizedChain.into());
+		}
+		let justification = operation.justification.filter(|justification| !justification.is_empty());
+		let latest_justification = match operation.leaf_state {
+			NewBlockState::Final => justification.clone(),
+			_ => None,
+		};
+
+		if let Some((ref state_root, _)) = operation.genesis_state {
+			if number != 0 || header.state_root() != state_root {
+				return Err(ClientErrorKind::GenesisInvalid.into());
+			}
+		}
+
+		// the authorities coming from the remote node have been checked by the import queue
+		// verifier, and the genesis authorities are read from the local genesis storage
 		self.blockchain.storage().import_header(
 			header,
 			operation.authorities,
+			justification,
 			operation.leaf_state,
-		)
+		)?;
+
+		if number == 0 {
+			if let Some((checkpoint, authorities)) = self.pending_checkpoint.lock().take() {
+				let checkpoint = Decode::decode(&mut &checkpoint[..])
+					.expect("checkpoint header is encoded by the backend constructor; qed");
+				self.blockchain.storage().import_checkpoint(checkpoint, authorities)?;
+			}
+		}
+
+		if let Some(genesis_state) = operation.genesis_state {
+			let encoded = genesis_state.encode();
+			self.blockchain.storage().insert_aux(&[(GENESIS_STATE_KEY, &encoded[..])], &[])?;
+			self.genesis_entries.write().set(hash, genesis_state.1);
+		}
+
+		if let Some(justification) = latest_justification {
+			let encoded = (hash, justification).encode();
+			self.blockchain.storage().insert_aux(&[(LATEST_JUSTIFICATION_KEY, &encoded[..])], &[])?;
+		}
+
+		Ok(())
 	}
 
-	fn finalize_block(&self, block: BlockId<Block>) -> ClientResult<()> {
-		self.blockchain.storage().finalize_header(block)
+	fn finalize_block(&self, block: BlockId<Block>, justification: Option<Justification>) -> ClientResult<()> {
+		let justification = justification.filter(|justification| !justification.is_empty());
+		self.blockchain.storage().finalize_header(block, justification.clone())?;
+		if let Some(justification) = justification {
+			// the header has just been finalized => it is in the local storage
+			let hash = match block {
+				BlockId::Hash(h) => h,
+				BlockId::Number(n) => self.blockchain.storage().hash(n)?
+					.ok_or_else(|| ClientErrorKind::UnknownBlock(format!("{}", block)))?,
+			};
+			let encoded = (hash, justification).encode();
+			self.blockchain.storage().insert_aux(&[(LATEST_JUSTIFICATION_KEY, &encoded[..])], &[])?;
+		}
+		Ok(())
 	}
 
 	fn blockchain(&self) -> &Blockchain<S, F> {
@@ -122,20 +482,41 @@ impl<S, F, Block, H> ClientBackend<Block, H> for Backend<S, F> where
 			blockchain: Arc::downgrade(&self.blockchain),
 			block: block_hash.ok_or_else(|| ClientErrorKind::UnknownBlock(format!("{}", block)))?,
 			cached_header: RwLock::new(None),
+			read_cache: self.read_cache.clone(),
+			pinned_keys: self.pinned_keys.clone(),
+			genesis_entries: self.genesis_entries.clone(),
 		})
 	}
 
-	fn revert(&self, _n: NumberFor<Block>) -> ClientResult<NumberFor<Block>> {
-		Err(ClientErrorKind::NotAvailableOnLightClient.into())
+	fn revert(&self, n: NumberFor<Block>) -> ClientResult<NumberFor<Block>> {
+		let mut reverted = Zero::zero();
+		while reverted < n {
+			let info = self.blockchain.info()?;
+			if info.best_number.is_zero() || info.best_number <= info.finalized_number {
+				break;
+			}
+
+			self.blockchain.storage().revert_best()?;
+			self.read_cache.lock().invalidate(info.best_hash.as_ref());
+			reverted += One::one();
+		}
+		Ok(reverted)
+	}
+
+	fn insert_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a (&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>(&self, insert: I, delete: D) -> ClientResult<()> {
+		self.blockchain.storage().insert_aux(insert, delete)
 	}
 
-	fn insert_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a (&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>(&self, _insert: I, _delete: D) -> ClientResult<()> {
-		Err(ClientErrorKind::NotAvailableOnLightClient.into())
+	fn get_aux(&self, key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		self.blockchain.storage().get_aux(key)
 	}
 
-	fn get_aux(&self, _key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
-		Err(ClientErrorKind::NotAvailableOnLightClient.into())
+	fn pin_block(&self, _hash: &Block::Hash) -> ClientResult<()> {
+		// light client has no local state to prune
+		Ok(())
 	}
+
+	fn unpin_block(&self, _hash: &Block::Hash) { }
 }
 
 impl<S, F, Block, H> RemoteBackend<Block, H> for Backend<S, F>
@@ -166,11 +547,12 @@ where
 		&mut self,
 		header: Block::Header,
 		_body: Option<Vec<Block::Extrinsic>>,
-		_justification: Option<Justification>,
+		justification: Option<Justification>,
 		state: NewBlockState,
 	) -> ClientResult<()> {
 		self.leaf_state = state;
 		self.header = Some(header);
+		self.justification = justification;
 		Ok(())
 	}
 
@@ -189,9 +571,374 @@ where
 	}
 
 	fn reset_storage(&mut self, top: StorageMap, children: ChildrenStorageMap) -> ClientResult<H::Out> {
+		// only the well-known entries are kept, the rest of the genesis state is read from the
+		// remote node along with the proof, checked against the genesis state root
+		let authorities_count = top.get(well_known_keys::AUTHORITY_COUNT)
+			.and_then(|value| u32::decode(&mut &value[..]));
+		let mut keys = vec![
+			well_known_keys::AUTHORITY_COUNT.to_vec(),
+			well_known_keys::CHANGES_TRIE_CONFIG.to_vec(),
+			well_known_keys::HEAP_PAGES.to_vec(),
+		];
+		keys.extend((0..authorities_count.unwrap_or(0)).map(|i| i.to_keyed_vec(well_known_keys::AUTHORITY_PREFIX)));
+		let entries: Vec<_> = keys.into_iter()
+			.map(|key| {
+				let value = top.get(&key).cloned();
+				(key, value)
+			})
+			.collect();
+		let authorities = authorities_count.and_then(|_| entries[3..].iter()
+			.map(|&(_, ref value)| value.as_ref().and_then(|value| AuthorityId::decode(&mut &value[..])))
+			.collect::<Option<Vec<_>>>());
+
 		let in_mem = in_mem::Backend::<Block, H>::new();
 		let mut op = in_mem.begin_operation(BlockId::Hash(Default::default()))?;
-		op.reset_storage(top, children)
+		let state_root = op.reset_storage(top, children)?;
+
+		if authorities.is_some() {
+			self.authorities = authorities;
+		}
+		self.genesis_state = Some((state_root, entries));
+		Ok(state_root)
+	}
+}
+
+impl<Block, S, F> OnDemandState<Block, S, F>
+where
+	Block: BlockT,
+	S: BlockchainStorage<Block>,
+	F: Fetcher<Block>,
+{
+	/// Read the storage entry from the remote node without blocking the calling thread. The
+	/// returned future resolves once the response is received and checked, so multiple reads may
+	/// be in flight concurrently. The header of the block is read synchronously, it's only
+	/// fetched from the network if the block is ancient and its header has been pruned.
+	pub fn storage_future(&self, key: &[u8]) -> RemoteStorageFuture<Block, F> {
+		fn single_value(values: Vec<Option<Vec<u8>>>) -> Option<Vec<u8>> {
+			values.into_iter().next().unwrap_or(None)
+		}
+
+		self.storage_multi_future(&[key.to_vec()]).map(single_value as fn(_) -> _)
+	}
+
+	/// Read multiple storage entries without blocking the calling thread. The entries that are
+	/// missing from the cache are read from the remote node by the single request, so they're
+	/// proved by the single proof. The values are returned in the order of the keys.
+	pub fn storage_multi_future(&self, keys: &[Vec<u8>]) -> RemoteStorageMultiFuture<Block, F> {
+		let values: Vec<_> = {
+			let genesis = self.genesis_entries.read();
+			let pinned = self.pinned_keys.read();
+			let mut cache = self.read_cache.lock();
+			let block = self.block.as_ref();
+			keys.iter()
+				.map(|key| genesis.get(block, key)
+					.or_else(|| pinned.get(block, key))
+					.or_else(|| cache.get(block, key)))
+				.collect()
+		};
+		let missing_keys: Vec<_> = keys.iter().zip(values.iter())
+			.filter(|&(_, value)| value.is_none())
+			.map(|(key, _)| key.clone())
+			.collect();
+		if missing_keys.is_empty() {
+			return future::Either::A(future::ok(values.into_iter().map(|value| value.unwrap_or(None)).collect()));
+		}
+
+		let request = self.header().and_then(|header| {
+			let fetcher = self.fetcher.upgrade().ok_or(ClientErrorKind::NotAvailableOnLightClient)?;
+			Ok(fetcher.remote_read(RemoteReadRequest {
+				block: self.block,
+				header,
+				keys: missing_keys.clone(),
+				retry_count: None,
+			}))
+		});
+
+		match request {
+			Ok(request) => future::Either::B(CachingRead {
+				read: Some(request.into_future()),
+				cache: self.read_cache.clone(),
+				block: self.block.as_ref().to_vec(),
+				values,
+				keys: missing_keys,
+			}),
+			Err(e) => future::Either::A(future::err(e)),
+		}
+	}
+
+	/// Read multiple storage entries, blocking the calling thread until all of them are read.
+	/// See `storage_multi_future`.
+	pub fn storage_multi(&self, keys: &[Vec<u8>]) -> ClientResult<Vec<Option<Vec<u8>>>> {
+		self.storage_multi_future(keys).wait()
+	}
+
+	/// Read the page of storage entries with keys starting with the `prefix` from the remote node.
+	/// The page starts from the first such entry with the key that is greater than or equal to the
+	/// `start_key` and contains at most `limit` entries, though the remote node may return less.
+	/// Returns the entries and the key the next page starts from, or `None` if there are no more
+	/// entries with the `prefix`. The values are cached, so that following reads of these keys
+	/// don't hit the network.
+	pub fn storage_prefix_page(
+		&self,
+		prefix: &[u8],
+		start_key: &[u8],
+		limit: u32,
+	) -> ClientResult<(Vec<(Vec<u8>, Vec<u8>)>, Option<Vec<u8>>)> {
+		let header = self.header()?;
+		let fetcher = self.fetcher.upgrade().ok_or(ClientErrorKind::NotAvailableOnLightClient)?;
+		let (mut entries, complete) = fetcher.remote_read_prefix(RemoteReadPrefixRequest {
+			block: self.block,
+			header,
+			prefix: prefix.to_vec(),
+			start_key: start_key.to_vec(),
+			limit,
+			retry_count: None,
+		}).into_future().wait()?;
+
+		// the entry following the page is proved too
+		let next_key = if entries.len() > limit as usize {
+			entries.drain(limit as usize..).next().map(|(key, _)| key)
+		} else if complete {
+			None
+		} else {
+			entries.last().map(|&(ref key, _)| {
+				let mut next_key = key.clone();
+				next_key.push(0);
+				next_key
+			})
+		};
+
+		let mut cache = self.read_cache.lock();
+		for &(ref key, ref value) in &entries {
+			cache.insert(self.block.as_ref().to_vec(), key.clone(), Some(value.clone()));
+		}
+
+		Ok((entries, next_key))
+	}
+
+	/// Header of the state's block, cached after the first read.
+	fn header(&self) -> ClientResult<Block::Header> {
+		if let Some(header) = self.cached_header.read().clone() {
+			return Ok(header);
+		}
+
+		let header = self.blockchain.upgrade()
+			.ok_or_else(|| ClientErrorKind::UnknownBlock(format!("{}", self.block)).into())
+			.and_then(|blockchain| blockchain.expect_header(BlockId::Hash(self.block)))?;
+		*self.cached_header.write() = Some(header.clone());
+		Ok(header)
+	}
+}
+
+impl GenesisEntries {
+	/// Get the value of the well-known entry of the genesis storage. `None` if the block isn't
+	/// the genesis block or the entry isn't well-known.
+	pub fn get(&self, block: &[u8], key: &[u8]) -> Option<Option<Vec<u8>>> {
+		match self.values {
+			Some((ref genesis_hash, ref values)) if &genesis_hash[..] == block => values.get(key).cloned(),
+			_ => None,
+		}
+	}
+
+	fn set<H: AsRef<[u8]>>(&mut self, genesis_hash: H, entries: Vec<(Vec<u8>, Option<Vec<u8>>)>) {
+		self.values = Some((genesis_hash.as_ref().to_vec(), entries.into_iter().collect()));
+	}
+}
+
+impl PinnedKeys {
+	/// Get the value of the pinned key at given block. `None` if the key isn't pinned or the
+	/// values haven't been read at this block.
+	pub fn get(&self, block: &[u8], key: &[u8]) -> Option<Option<Vec<u8>>> {
+		match self.values {
+			Some((ref values_block, ref values)) if &values_block[..] == block => values.get(key).cloned(),
+			_ => None,
+		}
+	}
+
+	/// Replace the values with the ones read at given block. The keys that have been unpinned
+	/// while the values were read are left out.
+	fn update<H: ::std::fmt::Display + AsRef<[u8]>>(
+		&mut self,
+		block: H,
+		keys: Vec<Vec<u8>>,
+		values: ClientResult<Vec<Option<Vec<u8>>>>,
+	) {
+		self.values = match values {
+			Ok(ref values) if values.len() == keys.len() => {
+				let pinned = &self.keys;
+				let values = keys.into_iter()
+					.zip(values.iter().cloned())
+					.filter(|&(ref key, _)| pinned.contains(key))
+					.collect();
+				Some((block.as_ref().to_vec(), values))
+			},
+			Ok(_) => {
+				debug!(target: "client", "Failed to read pinned keys at {}: invalid response", block);
+				None
+			},
+			Err(error) => {
+				debug!(target: "client", "Failed to read pinned keys at {}: {}", block, error);
+				None
+			},
+		};
+	}
+
+	/// Pin the keys of the authorities once their number has been read along with the other
+	/// pinned keys, unpinning the keys of the removed authorities. The keys of the added
+	/// authorities are read from the next best block.
+	fn pin_authorities(&mut self) {
+		let count = match self.values {
+			Some((_, ref values)) => match values.get(well_known_keys::AUTHORITY_COUNT) {
+				Some(&Some(ref count)) => match u32::decode(&mut &count[..]) {
+					Some(count) => count,
+					None => return,
+				},
+				_ => return,
+			},
+			None => return,
+		};
+
+		// `:auth:len` shares the prefix of the authority keys, which are one byte longer
+		let prefix = well_known_keys::AUTHORITY_PREFIX;
+		let authority_index = |key: &[u8]| match key.len() == prefix.len() + 4 && key.starts_with(prefix) {
+			true => u32::decode(&mut &key[prefix.len()..]),
+			false => None,
+		};
+		self.keys.retain(|key| authority_index(key).map_or(true, |index| index < count));
+		for index in 0..count {
+			let key = index.to_keyed_vec(prefix);
+			if !self.keys.contains(&key) {
+				self.keys.push(key);
+			}
+		}
+	}
+}
+
+impl RemoteReadCache {
+	/// Create new cache, keeping at most `limit` bytes of the keys and values.
+	pub fn new(limit: usize) -> Self {
+		RemoteReadCache {
+			entries: LinkedHashMap::new(),
+			size: 0,
+			limit,
+			stats: Default::default(),
+			account: None,
+		}
+	}
+
+	/// Account the cached entries in the shared memory budget. The cache is shrunk below its own
+	/// limit when the budget is exceeded.
+	pub fn set_memory_budget(&mut self, budget: &MemoryBudget) {
+		self.account = Some(budget.register("remote_read_cache", REMOTE_READ_CACHE_WEIGHT));
+	}
+
+	/// Get cached value of the storage entry at given block, marking it as recently used. The
+	/// cache is shrunk first if the memory budget has asked it to.
+	pub fn get(&mut self, block: &[u8], key: &[u8]) -> Option<Option<Vec<u8>>> {
+		self.apply_memory_request();
+		let value = self.entries.get_refresh(&(block.to_vec(), key.to_vec())).map(|value| value.clone());
+		match value {
+			Some(_) => self.stats.hits += 1,
+			None => self.stats.misses += 1,
+		}
+		value
+	}
+
+	/// Get statistics of the cache lookups.
+	pub fn stats(&self) -> ReadCacheStats {
+		self.stats
+	}
+
+	/// Insert value of the storage entry at given block into the cache.
+	pub fn insert(&mut self, block: Vec<u8>, key: Vec<u8>, value: Option<Vec<u8>>) {
+		let size = entry_size(&block, &key, &value);
+		if size > self.limit {
+			return;
+		}
+
+		self.apply_memory_request();
+
+		self.size += size;
+		if let Some(old_value) = self.entries.insert((block.clone(), key.clone()), value) {
+			self.size -= entry_size(&block, &key, &old_value);
+		}
+		let limit = self.limit;
+		self.shrink(limit);
+		self.report_memory_usage();
+	}
+
+	/// Shrink the cache to the size it has been asked to shrink to by the memory budget, if any.
+	fn apply_memory_request(&mut self) {
+		let requested = self.account.as_ref().and_then(|account| account.requested_size());
+		if let Some(requested) = requested {
+			self.shrink(requested);
+			self.report_memory_usage();
+		}
+	}
+
+	/// Report the size of the cache to the memory budget, shrinking the cache to its share if
+	/// the budget is exceeded.
+	fn report_memory_usage(&mut self) {
+		let share = self.account.as_ref().and_then(|account| account.report(self.size));
+		if let Some(share) = share {
+			self.shrink(share);
+			if let Some(ref account) = self.account {
+				account.report(self.size);
+			}
+		}
+	}
+
+	fn shrink(&mut self, limit: usize) {
+		while self.size > limit {
+			let ((block, key), value) = self.entries.pop_front()
+				.expect("size is only non-zero when there are cached entries; qed");
+			self.size -= entry_size(&block, &key, &value);
+		}
+	}
+
+	/// Remove all cached entries of given block.
+	pub fn invalidate(&mut self, block: &[u8]) {
+		let keys: Vec<_> = self.entries.keys().filter(|&&(ref b, _)| &b[..] == block).cloned().collect();
+		for key in keys {
+			if let Some(value) = self.entries.remove(&key) {
+				self.size -= entry_size(&key.0, &key.1, &value);
+			}
+		}
+	}
+}
+
+fn entry_size(block: &[u8], key: &[u8], value: &Option<Vec<u8>>) -> usize {
+	block.len() + key.len() + value.as_ref().map_or(0, |value| value.len())
+}
+
+impl<R: Future<Item=Vec<Option<Vec<u8>>>, Error=ClientError>> Future for CachingRead<R> {
+	type Item = Vec<Option<Vec<u8>>>;
+	type Error = ClientError;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let read_values = match self.read.as_mut().map(|read| read.poll()) {
+			Some(Ok(Async::Ready(read_values))) => read_values,
+			Some(Ok(Async::NotReady)) => return Ok(Async::NotReady),
+			Some(Err(error)) => return Err(error),
+			None => Vec::new(),
+		};
+		if read_values.len() != self.keys.len() {
+			return Err(ClientErrorKind::RemoteFetchFailed.into());
+		}
+
+		{
+			let mut cache = self.cache.lock();
+			for (key, value) in mem::replace(&mut self.keys, Vec::new()).into_iter().zip(read_values.iter()) {
+				cache.insert(self.block.clone(), key, value.clone());
+			}
+		}
+
+		let mut read_values = read_values.into_iter();
+		let values = mem::replace(&mut self.values, Vec::new()).into_iter()
+			.map(|value| value.unwrap_or_else(|| read_values.next()
+				.expect("there's a read value for every missing value; qed")))
+			.collect();
+		Ok(Async::Ready(values))
 	}
 }
 
@@ -207,31 +954,38 @@ where
 	type TrieBackendStorage = MemoryDB<H>;
 
 	fn storage(&self, key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
-		let mut header = self.cached_header.read().clone();
-		if header.is_none() {
-			let cached_header = self.blockchain.upgrade()
-				.ok_or_else(|| ClientErrorKind::UnknownBlock(format!("{}", self.block)).into())
-				.and_then(|blockchain| blockchain.expect_header(BlockId::Hash(self.block)))?;
-			header = Some(cached_header.clone());
-			*self.cached_header.write() = Some(cached_header);
-		}
-
-		self.fetcher.upgrade().ok_or(ClientErrorKind::NotAvailableOnLightClient)?
-			.remote_read(RemoteReadRequest {
-				block: self.block,
-				header: header.expect("if block above guarantees that header is_some(); qed"),
-				key: key.to_vec(),
-				retry_count: None,
-			})
-			.into_future().wait()
+		self.storage_future(key).wait()
 	}
 
-	fn child_storage(&self, _storage_key: &[u8], _key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
-		Err(ClientErrorKind::NotAvailableOnLightClient.into())
+	fn child_storage(&self, storage_key: &[u8], key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		let header = self.header()?;
+		let fetcher = self.fetcher.upgrade().ok_or(ClientErrorKind::NotAvailableOnLightClient)?;
+		fetcher.remote_read_child(RemoteReadChildRequest {
+			block: self.block,
+			header,
+			storage_key: storage_key.to_vec(),
+			key: key.to_vec(),
+			retry_count: None,
+		}).into_future().wait()
 	}
 
-	fn for_keys_with_prefix<A: FnMut(&[u8])>(&self, _prefix: &[u8], _action: A) {
-		// whole state is not available on light node
+	fn for_keys_with_prefix<A: FnMut(&[u8])>(&self, prefix: &[u8], mut action: A) {
+		let mut start_key = prefix.to_vec();
+		loop {
+			match self.storage_prefix_page(prefix, &start_key, READ_PREFIX_PAGE_SIZE) {
+				Ok((entries, next_key)) => {
+					entries.iter().for_each(|&(ref key, _)| action(key));
+					match next_key {
+						Some(next_key) => start_key = next_key,
+						None => return,
+					}
+				},
+				Err(error) => {
+					warn!(target: "client", "Unable to read keys with prefix {:?} at {}: {}", prefix, self.block, error);
+					return;
+				},
+			}
+		}
 	}
 
 	fn for_keys_in_child_storage<A: FnMut(&[u8])>(&self, _storage_key: &[u8], _action: A) {
@@ -261,3 +1015,432 @@ where
 		None
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use call_executor::CallResult;
+	use light::fetcher::tests::OkCallFetcher;
+	use state_machine::OverlayedChanges;
+	use test_client::import_simulation::{canned_blocks, simulate_import, ImportEvent, ImportMode};
+	use test_client::runtime::{Block, Header};
+	use primitives::Blake2Hasher;
+
+	fn test_header(number: u64, parent_hash: ::primitives::H256) -> Header {
+		Header {
+			number,
+			parent_hash,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		}
+	}
+
+	/// Light backend with the genesis header imported, reading the remote data with given fetcher.
+	fn backend_at_genesis<F: Fetcher<Block>>(fetcher: &Arc<F>) -> (Header, Backend<in_mem::Blockchain<Block>, F>) {
+		use light::blockchain::Storage;
+
+		let storage = in_mem::Blockchain::new();
+		let genesis = test_header(0, Default::default());
+		storage.import_header(genesis.clone(), None, None, NewBlockState::Final).unwrap();
+		let blockchain = Arc::new(Blockchain::new(storage));
+		blockchain.set_fetcher(Arc::downgrade(fetcher));
+		(genesis, Backend::new(blockchain, None).unwrap())
+	}
+
+	/// Fetcher responding to the remote calls with empty result.
+	fn empty_call_fetcher() -> Arc<OkCallFetcher> {
+		Arc::new(Mutex::new(CallResult {
+			return_data: Vec::new(),
+			changes: OverlayedChanges::default(),
+			accessed_keys: Vec::new(),
+		}))
+	}
+
+	fn import_header<F: Fetcher<Block>>(
+		backend: &Backend<in_mem::Blockchain<Block>, F>,
+		header: Header,
+		justification: Option<Justification>,
+		state: NewBlockState,
+	) -> ClientResult<()> {
+		let mut op = ClientBackend::<Block, Blake2Hasher>::begin_operation(backend, BlockId::Number(0))?;
+		BlockImportOperation::<Block, Blake2Hasher>::set_block_data(&mut op, header, None, justification, state)?;
+		ClientBackend::<Block, Blake2Hasher>::commit_operation(backend, op)
+	}
+
+	#[test]
+	fn remote_read_cache_is_bounded() {
+		let mut cache = RemoteReadCache::new(10);
+		cache.insert(vec![1], vec![1], Some(vec![1; 2]));
+		cache.insert(vec![1], vec![2], None);
+		assert_eq!(cache.get(&[1], &[1]), Some(Some(vec![1; 2])));
+
+		// least recently used entry is evicted
+		cache.insert(vec![2], vec![1], Some(vec![2; 4]));
+		assert_eq!(cache.get(&[1], &[2]), None);
+		assert_eq!(cache.get(&[1], &[1]), Some(Some(vec![1; 2])));
+		assert_eq!(cache.size, 10);
+
+		// entries larger than the limit are never cached
+		cache.insert(vec![3], vec![1], Some(vec![3; 9]));
+		assert_eq!(cache.get(&[3], &[1]), None);
+		assert_eq!(cache.stats(), ReadCacheStats { hits: 2, misses: 2 });
+	}
+
+	#[test]
+	fn remote_read_cache_is_shrunk_to_memory_budget() {
+		let budget = MemoryBudget::new(10);
+		let mut cache = RemoteReadCache::new(100);
+		cache.set_memory_budget(&budget);
+		cache.insert(vec![1], vec![1], Some(vec![1; 4]));
+		cache.insert(vec![1], vec![2], Some(vec![2; 4]));
+		assert_eq!(cache.get(&[1], &[1]), None);
+		assert_eq!(cache.get(&[1], &[2]), Some(Some(vec![2; 4])));
+		assert_eq!(cache.size, 6);
+	}
+
+	#[test]
+	fn remote_read_cache_is_invalidated() {
+		let mut cache = RemoteReadCache::new(100);
+		cache.insert(vec![1], vec![1], Some(vec![1]));
+		cache.insert(vec![1], vec![2], Some(vec![2]));
+		cache.insert(vec![2], vec![1], Some(vec![3]));
+		cache.invalidate(&[1]);
+		assert_eq!(cache.get(&[1], &[1]), None);
+		assert_eq!(cache.get(&[1], &[2]), None);
+		assert_eq!(cache.get(&[2], &[1]), Some(Some(vec![3])));
+		assert_eq!(cache.size, 3);
+	}
+
+	#[test]
+	fn caching_read_merges_cached_and_read_values() {
+		let cache = Arc::new(Mutex::new(RemoteReadCache::new(100)));
+		let read = CachingRead {
+			read: Some(future::ok::<_, ClientError>(vec![Some(vec![2]), None])),
+			cache: cache.clone(),
+			block: vec![1],
+			values: vec![None, Some(Some(vec![1])), None],
+			keys: vec![vec![2], vec![3]],
+		};
+		assert_eq!(read.wait().unwrap(), vec![Some(vec![2]), Some(vec![1]), None]);
+		assert_eq!(cache.lock().get(&[1], &[2]), Some(Some(vec![2])));
+		assert_eq!(cache.lock().get(&[1], &[3]), Some(None));
+	}
+
+	#[test]
+	fn light_backend_is_started_from_checkpoint() {
+		let genesis = test_header(0, Default::default());
+		let checkpoint = test_header(10, [1u8; 32].into());
+		let blockchain = Arc::new(Blockchain::new(in_mem::Blockchain::<Block>::new()));
+		let backend = Backend::<_, OkCallFetcher>::new(blockchain, Some(Checkpoint {
+			header: checkpoint.clone(),
+			authorities: vec![[1u8; 32].into()],
+		})).unwrap();
+
+		// the checkpoint is imported right after the genesis block
+		import_header(&backend, genesis.clone(), None, NewBlockState::Final).unwrap();
+		let info = backend.blockchain().info().unwrap();
+		assert_eq!((info.best_hash, info.best_number), (checkpoint.hash(), 10));
+		assert_eq!((info.finalized_hash, info.finalized_number), (checkpoint.hash(), 10));
+
+		// forks below the checkpoint are rejected
+		assert!(import_header(&backend, test_header(1, genesis.hash()), None, NewBlockState::Best).is_err());
+		assert!(import_header(&backend, test_header(10, Default::default()), None, NewBlockState::Normal).is_err());
+		assert!(import_header(&backend, test_header(11, checkpoint.hash()), None, NewBlockState::Best).is_ok());
+		assert_eq!(backend.blockchain().info().unwrap().best_number, 11);
+	}
+
+	#[test]
+	fn justification_is_persisted_on_import() {
+		use blockchain::Backend as BlockchainBackend;
+
+		let fetcher = empty_call_fetcher();
+		let (genesis, backend) = backend_at_genesis(&fetcher);
+		let header1 = test_header(1, genesis.hash());
+		let header2 = test_header(2, header1.hash());
+		import_header(&backend, header1.clone(), Some(vec![42]), NewBlockState::Final).unwrap();
+		import_header(&backend, header2.clone(), Some(Vec::new()), NewBlockState::Best).unwrap();
+
+		let blockchain = backend.blockchain();
+		assert_eq!(blockchain.justification(BlockId::Hash(header1.hash())).unwrap(), Some(vec![42]));
+		assert_eq!(blockchain.justification(BlockId::Number(1)).unwrap(), Some(vec![42]));
+		assert_eq!(blockchain.justification(BlockId::Hash(header2.hash())).unwrap(), None);
+
+		// justification that is received after the import is persisted on finalization
+		ClientBackend::<Block, Blake2Hasher>::finalize_block(&backend, BlockId::Number(2), Some(vec![43])).unwrap();
+		assert_eq!(blockchain.justification(BlockId::Hash(header2.hash())).unwrap(), Some(vec![43]));
+	}
+
+	#[test]
+	fn authorities_change_is_only_accepted_if_proved() {
+		use light::blockchain::Storage;
+		use light::fetcher::tests::OkReadFetcher;
+
+		let genesis = test_header(0, Default::default());
+		let header1 = test_header(1, genesis.hash());
+		let authorities: Vec<AuthorityId> = vec![[1u8; 32].into()];
+
+		// the remote node proves the set
+		let storage = in_mem::Blockchain::<Block>::new();
+		storage.import_header(genesis.clone(), None, None, NewBlockState::Final).unwrap();
+		let blockchain = Blockchain::new(storage);
+		let fetcher: Arc<OkReadFetcher> = Arc::new(Mutex::new(vec![Some(1u32.encode()), Some(authorities[0].encode())]));
+		blockchain.set_fetcher(Arc::downgrade(&fetcher));
+		assert_eq!(blockchain.check_authorities(&header1, authorities.clone()).unwrap(), Some(authorities.clone()));
+
+		// the remote node proves different set => header is rejected
+		*fetcher.lock() = vec![Some(1u32.encode()), Some(AuthorityId::from([2u8; 32]).encode())];
+		assert!(blockchain.check_authorities(&header1, authorities.clone()).is_err());
+
+		// the remote node can't read the set, e.g. because it has pruned the state => header is
+		// imported without the set
+		let fetcher = empty_call_fetcher();
+		let (_, backend) = backend_at_genesis(&fetcher);
+		let blockchain = backend.blockchain().clone();
+		assert_eq!(blockchain.check_authorities(&header1, authorities.clone()).unwrap(), None);
+
+		// the checked set is stored in the storage cache when the header is committed, without
+		// reading it again
+		let mut op = ClientBackend::<Block, Blake2Hasher>::begin_operation(&backend, BlockId::Number(0)).unwrap();
+		BlockImportOperation::<Block, Blake2Hasher>::set_block_data(&mut op, header1.clone(), None, None, NewBlockState::Best).unwrap();
+		BlockImportOperation::<Block, Blake2Hasher>::update_authorities(&mut op, authorities.clone());
+		ClientBackend::<Block, Blake2Hasher>::commit_operation(&backend, op).unwrap();
+
+		// fork with the same set is accepted without the proof
+		let mut fork1 = test_header(1, genesis.hash());
+		fork1.state_root = [1u8; 32].into();
+		assert_eq!(blockchain.check_authorities(&fork1, authorities.clone()).unwrap(), Some(authorities));
+	}
+
+	#[test]
+	fn key_changes_request_is_checked_against_local_headers() {
+		use client::tests::prepare_client_with_key_changes;
+		use light::blockchain::Storage;
+		use light::fetcher::{FetchChecker, LightDataChecker};
+		use test_client::{self, runtime};
+
+		let (remote_client, _, test_cases) = prepare_client_with_key_changes();
+		let storage = in_mem::Blockchain::<Block>::new();
+		let best_number = remote_client.info().unwrap().chain.best_number;
+		for number in 0..best_number + 1 {
+			let header = remote_client.header(&BlockId::Number(number)).unwrap().unwrap();
+			storage.import_header(header, None, None, NewBlockState::Best).unwrap();
+		}
+		let backend = Backend::<_, OkCallFetcher>::new(Arc::new(Blockchain::new(storage)), None).unwrap();
+		let checker = LightDataChecker::<_, Blake2Hasher>::new(test_client::LocalExecutor::new());
+
+		for (begin, end, key, expected_result) in test_cases {
+			let begin = remote_client.block_hash(begin).unwrap().unwrap();
+			let end = remote_client.block_hash(end).unwrap().unwrap();
+			let request = backend.key_changes_request(runtime::changes_trie_config(), begin, end, &key).unwrap();
+			let (remote_max, remote_proof) = remote_client.key_changes_proof(
+				request.first_block.1, request.last_block.1, request.max_block.1, &key).unwrap();
+			let result = (&checker as &FetchChecker<Block>)
+				.check_changes_proof(&request, remote_max, remote_proof).unwrap();
+			assert_eq!(result, expected_result);
+		}
+
+		// the range is checked before anything is requested
+		let first = remote_client.block_hash(3).unwrap().unwrap();
+		let last = remote_client.block_hash(1).unwrap().unwrap();
+		assert!(backend.key_changes_request(runtime::changes_trie_config(), first, last, &[]).is_err());
+	}
+
+	#[test]
+	fn light_import_only_differs_from_full_import_in_executed_checks() {
+		let blocks = canned_blocks();
+		let full_events = simulate_import(&test_client::new(), &blocks, ImportMode::Full).unwrap();
+
+		let fetcher = empty_call_fetcher();
+		let light_client = test_client::new_light(fetcher);
+		let light_events = simulate_import(&light_client, &blocks, ImportMode::HeadersOnly).unwrap();
+
+		// the invalid state root of C4 is only detected by the execution of the block, so C4
+		// becomes the best block of the light client instead of B4
+		assert_eq!(light_events[..5], full_events[..5]);
+		assert_eq!(light_events[5], ImportEvent::Accept { number: 4, hash: blocks[5].header.hash(), best: true });
+		assert_eq!(light_events[6], full_events[6]);
+		assert_eq!(light_events[7], ImportEvent::Accept { number: 4, hash: blocks[7].header.hash(), best: false });
+	}
+
+	#[test]
+	fn light_import_rejects_head_with_bad_justification() {
+		use client::JustificationVerifier;
+		use consensus::{BlockImport, BlockOrigin, ImportBlock};
+
+		struct TestVerifier;
+
+		impl JustificationVerifier<Block> for TestVerifier {
+			fn verify_justification(
+				&self,
+				_header: &Header,
+				justification: &Justification,
+				authorities: &[AuthorityId],
+			) -> ClientResult<()> {
+				// the test justification is the number of signing authorities
+				match justification.get(0) {
+					Some(signers) if *signers as usize * 3 > authorities.len() * 2 => Ok(()),
+					_ => Err(ClientErrorKind::BadJustification("not enough signatures".into()).into()),
+				}
+			}
+		}
+
+		let fetcher = empty_call_fetcher();
+		let client = test_client::new_light(fetcher);
+		client.set_justification_verifier(Box::new(TestVerifier));
+		let genesis_hash = client.info().unwrap().chain.best_hash;
+
+		let block = canned_blocks().remove(0);
+		let import = |justification: Justification| client.import_block(ImportBlock {
+			origin: BlockOrigin::NetworkBroadcast,
+			header: block.header.clone(),
+			external_justification: justification,
+			post_runtime_digests: vec![],
+			body: None,
+			finalized: false,
+			auxiliary: Vec::new(),
+		}, None);
+
+		// the justification signed by two of three genesis authorities isn't enough
+		match import(vec![2]) {
+			Err(ClientError(ClientErrorKind::BadJustification(_), _)) => (),
+			result => panic!("Unexpected import result: {:?}", result),
+		}
+		assert_eq!(client.info().unwrap().chain.best_hash, genesis_hash);
+
+		assert!(import(vec![3]).is_ok());
+		assert_eq!(client.info().unwrap().chain.best_hash, block.header.hash());
+	}
+
+	#[test]
+	fn genesis_state_is_recorded_on_reset_storage() {
+		use blockchain::Cache as BlockchainCache;
+
+		let fetcher = empty_call_fetcher();
+		let client = test_client::new_light(fetcher);
+		let genesis_header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+
+		let (state_root, entries) = client.backend().genesis_state::<Block>().unwrap().unwrap();
+		assert_eq!(state_root, *genesis_header.state_root());
+		assert!(entries.iter().any(|&(ref key, ref value)| &key[..] == well_known_keys::AUTHORITY_COUNT && value.is_some()));
+
+		// the genesis authorities are known without checking them against the remote node
+		let authorities = client.backend().blockchain().authorities_at(BlockId::Number(0)).unwrap();
+		assert_eq!(authorities.len(), 3);
+
+		// the well-known entries are read locally => the fetcher isn't used
+		let state = ClientBackend::<Block, Blake2Hasher>::state_at(&**client.backend(), BlockId::Number(0)).unwrap();
+		assert_eq!(state.storage(well_known_keys::AUTHORITY_COUNT).unwrap(), Some(3u32.encode()));
+	}
+
+	#[test]
+	fn pinned_keys_are_read_from_local_copy() {
+		let fetcher = empty_call_fetcher();
+		let (genesis, backend) = backend_at_genesis(&fetcher);
+
+		let code = well_known_keys::CODE.to_vec();
+		let count = well_known_keys::AUTHORITY_COUNT.to_vec();
+		backend.pin_storage_keys(vec![code.clone(), count.clone(), code.clone()]);
+		assert_eq!(backend.pinned_keys.read().keys, vec![code.clone(), count.clone()]);
+		backend.pinned_keys.write().values = Some((
+			genesis.hash().as_ref().to_vec(),
+			vec![(code.clone(), Some(vec![42])), (count.clone(), None)].into_iter().collect(),
+		));
+
+		// the test fetcher fails to read anything => the values are read from the local copy
+		let state = ClientBackend::<Block, Blake2Hasher>::state_at(&backend, BlockId::Hash(genesis.hash())).unwrap();
+		assert_eq!(state.storage_future(&code).wait().unwrap(), Some(vec![42]));
+		assert_eq!(state.storage_future(&count).wait().unwrap(), None);
+		assert!(state.storage_future(b":other").wait().is_err());
+
+		backend.unpin_storage_keys(&[code.clone()]);
+		assert!(state.storage_future(&code).wait().is_err());
+
+		// the import of the new best block doesn't wait for the remote node
+		let best = test_header(1, genesis.hash());
+		import_header(&backend, best.clone(), None, NewBlockState::Best).unwrap();
+		assert!(backend.pinned_keys.read().values.is_some());
+
+		// the keys can't be read at the new best block => the values are discarded
+		backend.refresh_pinned_keys::<Block>(best).wait().unwrap();
+		assert!(backend.pinned_keys.read().values.is_none());
+	}
+
+	#[test]
+	fn pinned_keys_are_refreshed_at_new_best_block() {
+		use light::fetcher::tests::OkReadFetcher;
+
+		let fetcher: Arc<OkReadFetcher> = Arc::new(Mutex::new(vec![Some(vec![42]), None]));
+		let (genesis, backend) = backend_at_genesis(&fetcher);
+
+		let code = well_known_keys::CODE.to_vec();
+		let count = well_known_keys::AUTHORITY_COUNT.to_vec();
+		backend.pin_storage_keys(vec![code.clone(), count.clone()]);
+
+		let best = test_header(1, genesis.hash());
+		import_header(&backend, best.clone(), None, NewBlockState::Best).unwrap();
+		backend.refresh_pinned_keys::<Block>(best.clone()).wait().unwrap();
+
+		// the values are read from the local copy, even when the fetcher responds with others
+		*fetcher.lock() = vec![Some(vec![43]), Some(vec![44])];
+		let state = ClientBackend::<Block, Blake2Hasher>::state_at(&backend, BlockId::Hash(best.hash())).unwrap();
+		assert_eq!(state.storage_multi_future(&[code.clone(), count.clone()]).wait().unwrap(), vec![Some(vec![42]), None]);
+
+		// the values aren't used at other blocks
+		*fetcher.lock() = vec![Some(vec![43])];
+		let state = ClientBackend::<Block, Blake2Hasher>::state_at(&backend, BlockId::Hash(genesis.hash())).unwrap();
+		assert_eq!(state.storage_future(&code).wait().unwrap(), Some(vec![43]));
+	}
+
+	#[test]
+	fn authority_keys_are_pinned_along_with_their_count() {
+		let code = well_known_keys::CODE.to_vec();
+		let count = well_known_keys::AUTHORITY_COUNT.to_vec();
+		let authority = |index: u32| index.to_keyed_vec(well_known_keys::AUTHORITY_PREFIX);
+		let mut pinned = PinnedKeys::default();
+		pinned.keys = vec![code.clone(), count.clone()];
+
+		// the number of authorities isn't known yet
+		pinned.pin_authorities();
+		assert_eq!(pinned.keys, vec![code.clone(), count.clone()]);
+
+		let block = |byte: u8| ::primitives::H256::from([byte; 32]);
+		pinned.update(block(1), pinned.keys.clone(), Ok(vec![None, Some(2u32.encode())]));
+		pinned.pin_authorities();
+		assert_eq!(pinned.keys, vec![code.clone(), count.clone(), authority(0), authority(1)]);
+
+		// the keys of the removed authorities are unpinned
+		pinned.update(block(2), pinned.keys.clone(), Ok(vec![None, Some(1u32.encode()), None, None]));
+		pinned.pin_authorities();
+		assert_eq!(pinned.keys, vec![code, count, authority(0)]);
+	}
+
+	#[test]
+	fn storage_futures_read_values_from_remote_node_and_cache() {
+		use light::fetcher::tests::OkReadFetcher;
+
+		let fetcher: Arc<OkReadFetcher> = Arc::new(Mutex::new(vec![Some(vec![1]), None]));
+		let (genesis, backend) = backend_at_genesis(&fetcher);
+
+		let best = test_header(1, genesis.hash());
+		import_header(&backend, best.clone(), None, NewBlockState::Best).unwrap();
+		let state = ClientBackend::<Block, Blake2Hasher>::state_at(&backend, BlockId::Hash(best.hash())).unwrap();
+
+		// the values are returned in the order of the keys
+		let (key1, key2, key3) = (vec![1], vec![2], vec![3]);
+		assert_eq!(state.storage_multi_future(&[key1.clone(), key2.clone()]).wait().unwrap(), vec![Some(vec![1]), None]);
+
+		// the values read are cached, so only the missing one is read from the remote node
+		*fetcher.lock() = vec![Some(vec![3])];
+		assert_eq!(state.storage_future(&key1).wait().unwrap(), Some(vec![1]));
+		assert_eq!(
+			state.storage_multi_future(&[key2.clone(), key3.clone(), key1.clone()]).wait().unwrap(),
+			vec![None, Some(vec![3]), Some(vec![1])],
+		);
+
+		// the read fails once the fetcher is gone, unless the value is cached
+		drop(fetcher);
+		assert_eq!(state.storage_future(&key3).wait().unwrap(), Some(vec![3]));
+		assert!(state.storage_future(&[4]).wait().is_err());
+	}
+}
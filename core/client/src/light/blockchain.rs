@@ -18,41 +18,83 @@
 //! blocks. CHT roots are stored for headers of ancient blocks.
 
 use std::sync::Weak;
-use futures::{Future, IntoFuture};
+use futures::{future, Future, IntoFuture};
 use parking_lot::Mutex;
 
+use codec::{Decode, KeyedVec};
 use primitives::AuthorityId;
+use primitives::storage::well_known_keys;
 use runtime_primitives::{Justification, generic::BlockId};
-use runtime_primitives::traits::{Block as BlockT, Header as HeaderT,NumberFor, Zero};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor, Zero};
 
 use backend::NewBlockState;
-use blockchain::{Backend as BlockchainBackend, BlockStatus, Cache as BlockchainCache,
+use blockchain::{Backend as BlockchainBackend, BlockStatus, BlockchainFuture, Cache as BlockchainCache,
 	HeaderBackend as BlockchainHeaderBackend, Info as BlockchainInfo};
 use cht;
 use error::{ErrorKind as ClientErrorKind, Result as ClientResult};
-use light::fetcher::{Fetcher, RemoteHeaderRequest};
+use light::fetcher::{Fetcher, RemoteBodyRequest, RemoteHeaderRequest, RemoteReadRequest};
+
+/// Pruning of the headers which are covered by CHTs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadersPruning {
+	/// Keep all headers.
+	KeepAll,
+	/// Discard the headers covered by the built CHT once the last of them is at least given
+	/// number of blocks behind the last finalized block.
+	KeepRecent(u64),
+}
+
+impl Default for HeadersPruning {
+	fn default() -> Self {
+		HeadersPruning::KeepRecent(0)
+	}
+}
 
 /// Light client blockchain storage.
 pub trait Storage<Block: BlockT>: BlockchainHeaderBackend<Block> {
-	/// Store new header. Should refuse to revert any finalized blocks.
+	/// Store new header along with its justification, if any. Should refuse to revert any
+	/// finalized blocks.
 	fn import_header(
 		&self,
 		header: Block::Header,
 		authorities: Option<Vec<AuthorityId>>,
+		justification: Option<Justification>,
 		state: NewBlockState,
 	) -> ClientResult<()>;
 
-	/// Mark historic header as finalized.
-	fn finalize_header(&self, block: BlockId<Block>) -> ClientResult<()>;
+	/// Import the header of the trusted checkpoint as the best and finalized header, without its
+	/// ancestors. Fails unless the genesis header is the only header in the storage.
+	fn import_checkpoint(&self, header: Block::Header, authorities: Vec<AuthorityId>) -> ClientResult<()>;
+
+	/// Mark historic header as finalized, storing the justification of the finalization, if any,
+	/// along with the header.
+	fn finalize_header(&self, block: BlockId<Block>, justification: Option<Justification>) -> ClientResult<()>;
+
+	/// Remove the best header, making its parent the best one. Should refuse to revert
+	/// finalized header.
+	fn revert_best(&self) -> ClientResult<()>;
 
 	/// Get last finalized header.
 	fn last_finalized(&self) -> ClientResult<Block::Hash>;
 
+	/// Get justification of the block, if it has been imported along with the header. The
+	/// justification is discarded when the header is pruned.
+	fn justification(&self, id: BlockId<Block>) -> ClientResult<Option<Justification>>;
+
 	/// Get CHT root for given block. Fails if the block is not pruned (not a part of any CHT).
 	fn cht_root(&self, cht_size: u64, block: NumberFor<Block>) -> ClientResult<Block::Hash>;
 
+	/// Set pruning of the headers covered by CHTs. Applies to the blocks finalized afterwards.
+	fn set_headers_pruning(&self, pruning: HeadersPruning);
+
 	/// Get storage cache.
 	fn cache(&self) -> Option<&BlockchainCache<Block>>;
+
+	/// Insert auxiliary data into key-value store.
+	fn insert_aux<'a, 'b: 'a, 'c: 'a, I: IntoIterator<Item=&'a(&'c [u8], &'c [u8])>, D: IntoIterator<Item=&'a &'b [u8]>>(&self, insert: I, delete: D) -> ClientResult<()>;
+
+	/// Query auxiliary data from key-value store.
+	fn get_aux(&self, key: &[u8]) -> ClientResult<Option<Vec<u8>>>;
 }
 
 /// Light client blockchain.
@@ -86,35 +128,120 @@ impl<S, F> Blockchain<S, F> {
 	}
 }
 
-impl<S, F, Block> BlockchainHeaderBackend<Block> for Blockchain<S, F> where Block: BlockT, S: Storage<Block>, F: Fetcher<Block> {
-	fn header(&self, id: BlockId<Block>) -> ClientResult<Option<Block::Header>> {
-		match self.storage.header(id)? {
-			Some(header) => Ok(Some(header)),
-			None => {
-				let number = match id {
-					BlockId::Hash(hash) => match self.storage.number(hash)? {
-						Some(number) => number,
-						None => return Ok(None),
-					},
-					BlockId::Number(number) => number,
-				};
-
-				// if the header is from future or genesis (we never prune genesis) => return
-				if number.is_zero() || self.storage.status(BlockId::Number(number))? != BlockStatus::InChain {
-					return Ok(None);
-				}
-
-				self.fetcher().upgrade().ok_or(ClientErrorKind::NotAvailableOnLightClient)?
-					.remote_header(RemoteHeaderRequest {
-						cht_root: self.storage.cht_root(cht::SIZE, number)?,
-						block: number,
-						retry_count: None,
-					})
-					.into_future().wait()
-					.map(Some)
+impl<S, F> Blockchain<S, F> {
+	/// Set pruning of the headers covered by CHTs. The pruned headers are fetched from the
+	/// remote node when requested.
+	pub fn set_headers_pruning<Block>(&self, pruning: HeadersPruning) where Block: BlockT, S: Storage<Block> {
+		self.storage.set_headers_pruning(pruning)
+	}
+
+	/// Retrieve the canonical header of the block, which has been pruned from the storage,
+	/// from the remote node. The header is checked against the root of the CHT it belongs to.
+	fn remote_header<'a, Block>(&'a self, number: NumberFor<Block>) -> BlockchainFuture<'a, Option<Block::Header>> where
+		Block: BlockT,
+		S: Storage<Block>,
+		F: Fetcher<Block>,
+	{
+		let request = || -> ClientResult<Option<F::RemoteHeaderResult>> {
+			// if the header is from future or genesis (we never prune genesis) => return
+			if number.is_zero() || self.storage.status(BlockId::Number(number))? != BlockStatus::InChain {
+				return Ok(None);
 			}
+
+			let fetcher = self.fetcher().upgrade().ok_or(ClientErrorKind::NotAvailableOnLightClient)?;
+			Ok(Some(fetcher.remote_header(RemoteHeaderRequest {
+				cht_root: self.storage.cht_root(cht::SIZE, number)?,
+				block: number,
+				retry_count: None,
+			})))
+		};
+
+		match request() {
+			Ok(Some(request)) => Box::new(request.into_future().map(Some)),
+			Ok(None) => Box::new(future::ok(None)),
+			Err(e) => Box::new(future::err(e)),
+		}
+	}
+
+	/// Get the header from the storage, or retrieve it from the remote node if it has been
+	/// pruned.
+	fn header_future<'a, Block>(&'a self, id: BlockId<Block>) -> BlockchainFuture<'a, Option<Block::Header>> where
+		Block: BlockT,
+		S: Storage<Block>,
+		F: Fetcher<Block>,
+	{
+		let number = match self.storage.header(id) {
+			Ok(Some(header)) => return Box::new(future::ok(Some(header))),
+			Ok(None) => match id {
+				BlockId::Hash(hash) => match self.storage.number(hash) {
+					Ok(Some(number)) => number,
+					Ok(None) => return Box::new(future::ok(None)),
+					Err(e) => return Box::new(future::err(e)),
+				},
+				BlockId::Number(number) => number,
+			},
+			Err(e) => return Box::new(future::err(e)),
+		};
+
+		self.remote_header(number)
+	}
+
+	/// Check the authority set of the imported header. If the set differs from the set of the
+	/// parent block, it's read from the state of the header by the remote node, along with the
+	/// storage proof. Returns the set if it's unchanged or proved, or `None` if the remote node
+	/// fails to prove it, e.g. because it has pruned the state of the block: the header is then
+	/// imported without the set. Fails if the proved set differs from the given one.
+	///
+	/// Blocks until the remote node responds, so it's called by the import queue verifier rather
+	/// than when the header is committed.
+	pub fn check_authorities<Block>(&self, header: &Block::Header, authorities: Vec<AuthorityId>) -> ClientResult<Option<Vec<AuthorityId>>> where
+		Block: BlockT,
+		S: Storage<Block>,
+		F: Fetcher<Block>,
+	{
+		let parent_authorities = self.storage.cache()
+			.and_then(|cache| cache.authorities_at(BlockId::Hash(*header.parent_hash())));
+		if parent_authorities.as_ref() == Some(&authorities) {
+			return Ok(Some(authorities));
+		}
+
+		let mut keys = vec![well_known_keys::AUTHORITY_COUNT.to_vec()];
+		keys.extend((0..authorities.len() as u32).map(|i| i.to_keyed_vec(well_known_keys::AUTHORITY_PREFIX)));
+		let values = self.fetcher().upgrade().ok_or(ClientErrorKind::NotAvailableOnLightClient)?
+			.remote_read(RemoteReadRequest {
+				block: header.hash(),
+				header: header.clone(),
+				keys,
+				retry_count: None,
+			})
+			.into_future().wait();
+		let values = match values {
+			Ok(values) => values,
+			Err(e) => {
+				debug!(target: "client", "Importing {} without authorities: failed to read them from the remote node: {}", header.hash(), e);
+				return Ok(None);
+			},
+		};
+
+		let mut values = values.into_iter();
+		let count = values.next().and_then(|value| value).and_then(|value| u32::decode(&mut &value[..]));
+		let proved_authorities = values
+			.map(|value| value.and_then(|value| AuthorityId::decode(&mut &value[..])))
+			.collect::<Option<Vec<_>>>();
+		match (count, proved_authorities) {
+			(Some(count), Some(ref proved)) if count as usize == authorities.len() && *proved == authorities => Ok(Some(authorities)),
+			_ => {
+				warn!(target: "client", "Rejecting authorities change at {}: not proved by the remote node", header.hash());
+				Err(ClientErrorKind::InvalidAuthoritiesSet.into())
+			},
 		}
 	}
+}
+
+impl<S, F, Block> BlockchainHeaderBackend<Block> for Blockchain<S, F> where Block: BlockT, S: Storage<Block>, F: Fetcher<Block> {
+	fn header(&self, id: BlockId<Block>) -> ClientResult<Option<Block::Header>> {
+		self.header_future(id).wait()
+	}
 
 	fn info(&self) -> ClientResult<BlockchainInfo<Block>> {
 		self.storage.info()
@@ -131,16 +258,51 @@ impl<S, F, Block> BlockchainHeaderBackend<Block> for Blockchain<S, F> where Bloc
 	fn hash(&self, number: <<Block as BlockT>::Header as HeaderT>::Number) -> ClientResult<Option<Block::Hash>> {
 		self.storage.hash(number)
 	}
+
+	fn local_header(&self, id: BlockId<Block>) -> ClientResult<Option<Block::Header>> {
+		self.storage.header(id)
+	}
+
+	fn local_hash(&self, number: NumberFor<Block>) -> ClientResult<Option<Block::Hash>> {
+		self.storage.hash(number)
+	}
+
+	fn hash_future<'a>(&'a self, number: NumberFor<Block>) -> BlockchainFuture<'a, Option<Block::Hash>> {
+		match self.storage.hash(number) {
+			Ok(Some(hash)) => Box::new(future::ok(Some(hash))),
+			Ok(None) => Box::new(self.remote_header(number).map(|header| header.map(|header| header.hash()))),
+			Err(e) => Box::new(future::err(e)),
+		}
+	}
 }
 
 impl<S, F, Block> BlockchainBackend<Block> for Blockchain<S, F> where Block: BlockT, S: Storage<Block>, F: Fetcher<Block> {
 	fn body(&self, _id: BlockId<Block>) -> ClientResult<Option<Vec<Block::Extrinsic>>> {
-		// TODO [light]: fetch from remote node
+		// the light client doesn't store the bodies, see `body_future`
 		Ok(None)
 	}
 
-	fn justification(&self, _id: BlockId<Block>) -> ClientResult<Option<Justification>> {
-		Ok(None)
+	fn body_future<'a>(&'a self, id: BlockId<Block>) -> BlockchainFuture<'a, Option<Vec<Block::Extrinsic>>> {
+		// the body is checked against the extrinsics root of the known header
+		Box::new(self.header_future(id).and_then(move |header| -> BlockchainFuture<'a, _> {
+			let header = match header {
+				Some(header) => header,
+				None => return Box::new(future::ok(None)),
+			};
+			let fetcher = match self.fetcher().upgrade() {
+				Some(fetcher) => fetcher,
+				None => return Box::new(future::err(ClientErrorKind::NotAvailableOnLightClient.into())),
+			};
+
+			Box::new(fetcher.remote_body(RemoteBodyRequest {
+				header,
+				retry_count: None,
+			}).into_future().map(Some))
+		}))
+	}
+
+	fn justification(&self, id: BlockId<Block>) -> ClientResult<Option<Justification>> {
+		self.storage.justification(id)
 	}
 
 	fn last_finalized(&self) -> ClientResult<Block::Hash> {
@@ -24,8 +24,10 @@ use futures::{IntoFuture, Future};
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT};
 use state_machine::{Backend as StateBackend, CodeExecutor, OverlayedChanges,
-	execution_proof_check, ExecutionManager};
+	execution_proof_check, ExecutionContext, ExecutionManager, StorageDiff};
 use hash_db::Hasher;
+use primitives::LocalKeys;
+use primitives::offchain;
 
 use blockchain::Backend as ChainBackend;
 use call_executor::{CallExecutor, CallResult};
@@ -59,19 +61,24 @@ impl<B, F, H> RemoteCallExecutor<B, F, H> {
 	pub fn new(blockchain: Arc<B>, fetcher: Arc<F>) -> Self {
 		RemoteCallExecutor { blockchain, fetcher, _hasher: PhantomData }
 	}
-}
 
-impl<B, F, Block, H> CallExecutor<Block, H> for RemoteCallExecutor<B, F, H>
-where
-	Block: BlockT,
-	B: ChainBackend<Block>,
-	F: Fetcher<Block>,
-	H: Hasher<Out=Block::Hash>,
-	Block::Hash: Ord,
-{
-	type Error = ClientError;
+	/// Execute a call on the remote node, also asking it for the storage keys read by the call.
+	/// The keys are reported in `CallResult::accessed_keys`; they are not covered by the proof.
+	pub fn call_with_keys<Block>(&self, id: &BlockId<Block>, method: &str, call_data: &[u8]) -> ClientResult<CallResult>
+		where
+			Block: BlockT,
+			B: ChainBackend<Block>,
+			F: Fetcher<Block>,
+	{
+		self.remote_call(id, method, call_data, true)
+	}
 
-	fn call(&self, id: &BlockId<Block>, method: &str, call_data: &[u8]) -> ClientResult<CallResult> {
+	fn remote_call<Block>(&self, id: &BlockId<Block>, method: &str, call_data: &[u8], trace_keys: bool) -> ClientResult<CallResult>
+		where
+			Block: BlockT,
+			B: ChainBackend<Block>,
+			F: Fetcher<Block>,
+	{
 		let block_hash = match *id {
 			BlockId::Hash(hash) => hash,
 			BlockId::Number(number) => self.blockchain.hash(number)?
@@ -84,9 +91,25 @@ where
 			header: block_header,
 			method: method.into(),
 			call_data: call_data.to_vec(),
+			trace_keys,
 			retry_count: None,
 		}).into_future().wait()
 	}
+}
+
+impl<B, F, Block, H> CallExecutor<Block, H> for RemoteCallExecutor<B, F, H>
+where
+	Block: BlockT,
+	B: ChainBackend<Block>,
+	F: Fetcher<Block>,
+	H: Hasher<Out=Block::Hash>,
+	Block::Hash: Ord,
+{
+	type Error = ClientError;
+
+	fn call(&self, id: &BlockId<Block>, method: &str, call_data: &[u8]) -> ClientResult<CallResult> {
+		self.remote_call(id, method, call_data, false)
+	}
 
 	fn runtime_version(&self, id: &BlockId<Block>) -> ClientResult<RuntimeVersion> {
 		let call_result = self.call(id, "version", &[])?;
@@ -96,24 +119,37 @@ where
 
 	fn call_at_state<
 		S: StateBackend<H>,
-		FF: FnOnce(Result<Vec<u8>, Self::Error>, Result<Vec<u8>, Self::Error>) -> Result<Vec<u8>, Self::Error>
+		FF: FnOnce(Result<Vec<u8>, Self::Error>, Result<Vec<u8>, Self::Error>, StorageDiff) -> Result<Vec<u8>, Self::Error>
 	>(&self,
 		_state: &S,
 		_changes: &mut OverlayedChanges,
 		_method: &str,
 		_call_data: &[u8],
+		_context: ExecutionContext,
+		_local_keys: Option<&LocalKeys>,
 		_m: ExecutionManager<FF>
 	) -> ClientResult<(Vec<u8>, S::Transaction, Option<MemoryDB<H>>)> {
 		Err(ClientErrorKind::NotAvailableOnLightClient.into())
 	}
 
+	fn call_offchain(
+		&self,
+		_id: &BlockId<Block>,
+		_method: &str,
+		_call_data: &[u8],
+		_offchain: &mut offchain::Externalities,
+		_local_keys: Option<&LocalKeys>,
+	) -> ClientResult<Vec<u8>> {
+		Err(ClientErrorKind::NotAvailableOnLightClient.into())
+	}
+
 	fn prove_at_state<S: StateBackend<H>>(
 		&self,
 		_state: S,
 		_changes: &mut OverlayedChanges,
 		_method: &str,
 		_call_data: &[u8]
-	) -> ClientResult<(Vec<u8>, Vec<Vec<u8>>)> {
+	) -> ClientResult<(Vec<u8>, Vec<Vec<u8>>, Vec<Vec<u8>>)> {
 		Err(ClientErrorKind::NotAvailableOnLightClient.into())
 	}
 
@@ -148,7 +184,7 @@ pub fn check_execution_proof<Header, E, H>(
 		&request.method,
 		&request.call_data)?;
 
-	Ok(CallResult { return_data: local_result, changes })
+	Ok(CallResult { return_data: local_result, changes, accessed_keys: Vec::new() })
 }
 
 #[cfg(test)]
@@ -181,7 +217,30 @@ mod tests {
 			},
 			method: "authorities".into(),
 			call_data: vec![],
+			trace_keys: false,
 			retry_count: None,
 		}, remote_execution_proof).unwrap();
 	}
+
+	#[test]
+	fn remote_call_executor_resolves_block_number_and_decodes_result() {
+		use codec::Encode;
+		use parking_lot::Mutex;
+		use primitives::Blake2Hasher;
+		use backend::Backend;
+
+		// the fetcher returns the version of the remote runtime
+		let remote_client = test_client::new();
+		let version = remote_client.runtime_version_at(&BlockId::Number(0)).unwrap();
+		let fetcher = Arc::new(Mutex::new(CallResult {
+			return_data: version.encode(),
+			changes: OverlayedChanges::default(),
+			accessed_keys: Vec::new(),
+		}));
+
+		let blockchain = Arc::new(remote_client.backend().blockchain().clone());
+		let executor = RemoteCallExecutor::<_, _, Blake2Hasher>::new(blockchain, fetcher);
+		assert!(executor.runtime_version(&BlockId::Number(0)).unwrap() == version);
+		assert!(executor.runtime_version(&BlockId::Number(1)).is_err());
+	}
 }
@@ -0,0 +1,417 @@
+// Copyright 2017-2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Remote proofs checker. Verifies the read, call, CHT and changes proofs of the remote nodes
+//! against the locally known roots. Shared by the light client fetcher, the archive state
+//! fallback of the full node and any other consumer of the remote proofs.
+//!
+//! Consecutive reads at the same block are proved by the proofs sharing the upper trie nodes.
+//! The hashes of the nodes of the successfully checked proofs are cached, so that only the new
+//! nodes of the following proofs against the same state root are hashed.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use codec::Encode;
+use hash_db::{Hasher, HashDB};
+use heapsize::HeapSizeOf;
+use parking_lot::Mutex;
+use runtime_primitives::traits::{As, Block as BlockT, Header as HeaderT, NumberFor,
+	Digest, DigestItemFor, Hash as HashT, One};
+use state_machine::{CodeExecutor, ChangesTrieRootsStorage, DBValue, MemoryDB, read_keys_proof_nodes_check,
+	key_changes_proof_check, child_roots_proof_check, read_prefix_proof_check, child_read_proof_nodes_check};
+
+use call_executor::CallResult;
+use cht;
+use error::{ErrorKind as ClientErrorKind, Error as ClientError, Result as ClientResult};
+use light::call_executor::check_execution_proof;
+use light::fetcher::{RemoteHeaderRequest, RemoteReadRequest, RemoteReadChildRequest, RemoteReadPrefixRequest,
+	RemoteCallRequest, RemoteChangesRequest, RemoteChildRootsRequest, RemoteDigestRequest, RemoteBodyRequest};
+
+/// Light client remote data checker.
+///
+/// Implementations of this trait should not use any blockchain data except that is
+/// passed to its methods.
+pub trait FetchChecker<Block: BlockT>: Send + Sync {
+	/// Check remote header proof.
+	fn check_header_proof(
+		&self,
+		request: &RemoteHeaderRequest<Block::Header>,
+		header: Option<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Block::Header>;
+	/// Check remote storage read proof.
+	fn check_read_proof(
+		&self,
+		request: &RemoteReadRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<Option<Vec<u8>>>>;
+	/// Check remote child storage read proof. The root of the child trie is proved by the main
+	/// trie part of the proof.
+	fn check_read_child_proof(
+		&self,
+		request: &RemoteReadChildRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Option<Vec<u8>>>;
+	/// Check remote storage prefix read proof.
+	fn check_read_prefix_proof(
+		&self,
+		request: &RemoteReadPrefixRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<(Vec<(Vec<u8>, Vec<u8>)>, bool)>;
+	/// Check remote method execution proof.
+	fn check_execution_proof(
+		&self,
+		request: &RemoteCallRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<CallResult>;
+	/// Check remote changes query proof.
+	fn check_changes_proof(
+		&self,
+		request: &RemoteChangesRequest<Block::Header>,
+		remote_max: NumberFor<Block>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<(NumberFor<Block>, u32)>>;
+	/// Check remote child tries roots proof.
+	fn check_child_roots_proof(
+		&self,
+		request: &RemoteChildRootsRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<(Vec<u8>, Vec<u8>)>>;
+	/// Check remote digest items proof. Headers of every block of the requested range should be
+	/// proved. Returns the requested items of these headers.
+	fn check_digest_proof(
+		&self,
+		request: &RemoteDigestRequest<Block::Header>,
+		remote_headers: Vec<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<(NumberFor<Block>, Vec<DigestItemFor<Block>>)>>;
+	/// Check remote block body against the extrinsics root of the header.
+	fn check_body_proof(
+		&self,
+		request: &RemoteBodyRequest<Block::Header>,
+		body: Vec<Block::Extrinsic>
+	) -> ClientResult<Vec<Block::Extrinsic>>;
+}
+
+/// Digest items of the header that have one of given types. The type of the item is the first
+/// byte of its encoding.
+pub fn matching_digest_items<Header: HeaderT>(
+	header: &Header,
+	item_types: &[u8],
+) -> Vec<<Header::Digest as Digest>::Item> {
+	header.digest().logs().iter()
+		.filter(|item| item.encode().first().map_or(false, |item_type| item_types.contains(item_type)))
+		.cloned()
+		.collect()
+}
+
+/// Maximal number of the trie nodes cached by the checker.
+const MAX_VERIFIED_NODES: usize = 4096;
+
+/// Nodes of the proofs that have been successfully checked against the state root, along with
+/// their hashes.
+struct VerifiedNodes<Hash> {
+	root: Hash,
+	nodes: HashMap<Vec<u8>, Hash>,
+}
+
+/// Remote data checker.
+pub struct LightDataChecker<E, H: Hasher> {
+	executor: E,
+	verified_nodes: Mutex<Option<VerifiedNodes<H::Out>>>,
+	_hasher: PhantomData<H>,
+}
+
+impl<E, H: Hasher> LightDataChecker<E, H> {
+	/// Create new light data checker.
+	pub fn new(executor: E) -> Self {
+		Self {
+			executor,
+			verified_nodes: Mutex::new(None),
+			_hasher: PhantomData,
+		}
+	}
+}
+
+impl<E, H> LightDataChecker<E, H>
+	where
+		H: Hasher,
+		H::Out: HeapSizeOf,
+{
+	/// Insert the proof nodes into the database. Nodes that have already been verified against
+	/// the same state root are inserted under the cached hashes. Returns the database and the
+	/// nodes that have been hashed.
+	fn proof_nodes(&self, root: &H::Out, proof: Vec<Vec<u8>>) -> (MemoryDB<H>, Vec<(Vec<u8>, H::Out)>) {
+		let verified_nodes = self.verified_nodes.lock();
+		let cached_nodes = verified_nodes.as_ref()
+			.filter(|verified| verified.root == *root)
+			.map(|verified| &verified.nodes);
+
+		let mut db = MemoryDB::default();
+		let mut hashed = Vec::new();
+		for node in proof {
+			match cached_nodes.and_then(|nodes| nodes.get(&node)) {
+				Some(hash) => db.emplace(*hash, DBValue::from_slice(&node)),
+				None => {
+					let hash = db.insert(&node);
+					hashed.push((node, hash));
+				},
+			}
+		}
+		(db, hashed)
+	}
+
+	/// Cache the nodes of the proof that has been successfully checked against the state root.
+	/// The cache is reset when the proof is checked against the other state root.
+	fn note_verified(&self, root: H::Out, hashed: Vec<(Vec<u8>, H::Out)>) {
+		let mut verified_nodes = self.verified_nodes.lock();
+		if verified_nodes.as_ref().map_or(true, |verified| verified.root != root) {
+			*verified_nodes = Some(VerifiedNodes { root, nodes: HashMap::new() });
+		}
+
+		let nodes = &mut verified_nodes.as_mut()
+			.expect("verified nodes of the root are created above; qed")
+			.nodes;
+		for (node, hash) in hashed {
+			if nodes.len() >= MAX_VERIFIED_NODES {
+				break;
+			}
+			nodes.insert(node, hash);
+		}
+	}
+}
+
+impl<E, Block, H> FetchChecker<Block> for LightDataChecker<E, H>
+	where
+		Block: BlockT,
+		E: CodeExecutor<H>,
+		H: Hasher,
+		H::Out: Ord + HeapSizeOf,
+{
+	fn check_header_proof(
+		&self,
+		request: &RemoteHeaderRequest<Block::Header>,
+		remote_header: Option<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Block::Header> {
+		let remote_header = remote_header.ok_or_else(||
+			ClientError::from(ClientErrorKind::InvalidHeaderProof))?;
+		let remote_header_hash = remote_header.hash();
+		cht::check_proof::<Block::Header, H>(
+			request.cht_root,
+			request.block,
+			remote_header_hash,
+			remote_proof)
+			.map(|_| remote_header)
+	}
+
+	fn check_read_proof(
+		&self,
+		request: &RemoteReadRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<Option<Vec<u8>>>> {
+		let mut root: H::Out = Default::default();
+		root.as_mut().copy_from_slice(request.header.state_root().as_ref());
+		let (nodes, hashed) = self.proof_nodes(&root, remote_proof);
+		let values = read_keys_proof_nodes_check::<H, _>(root, nodes, &request.keys)?;
+		self.note_verified(root, hashed);
+		Ok(values)
+	}
+
+	fn check_read_child_proof(
+		&self,
+		request: &RemoteReadChildRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Option<Vec<u8>>> {
+		let mut root: H::Out = Default::default();
+		root.as_mut().copy_from_slice(request.header.state_root().as_ref());
+		let (nodes, hashed) = self.proof_nodes(&root, remote_proof);
+		let value = child_read_proof_nodes_check::<H>(root, nodes, &request.storage_key, &request.key)?;
+		self.note_verified(root, hashed);
+		Ok(value)
+	}
+
+	fn check_read_prefix_proof(
+		&self,
+		request: &RemoteReadPrefixRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<(Vec<(Vec<u8>, Vec<u8>)>, bool)> {
+		let mut root: H::Out = Default::default();
+		root.as_mut().copy_from_slice(request.header.state_root().as_ref());
+		read_prefix_proof_check::<H>(root, remote_proof, &request.prefix, &request.start_key).map_err(Into::into)
+	}
+
+	fn check_execution_proof(
+		&self,
+		request: &RemoteCallRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<CallResult> {
+		check_execution_proof::<_, _, H>(&self.executor, request, remote_proof)
+	}
+
+	fn check_changes_proof(
+		&self,
+		request: &RemoteChangesRequest<Block::Header>,
+		remote_max: NumberFor<Block>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<(NumberFor<Block>, u32)>> {
+		// since we need roots of all changes tries for the range begin..max
+		// => remote node can't use max block greater that one that we have passed
+		if remote_max > request.max_block.0 || remote_max < request.last_block.0 {
+			return Err(ClientErrorKind::ChangesTrieAccessFailed(format!(
+				"Invalid max_block used by the remote node: {}. Local: {}..{}..{}",
+				remote_max, request.first_block.0, request.last_block.0, request.max_block.0,
+			)).into());
+		}
+
+		let first_number = request.first_block.0.as_();
+		key_changes_proof_check::<_, H>(
+			&request.changes_trie_config,
+			&RootsStorage {
+				first: first_number,
+				roots: &request.tries_roots,
+			},
+			remote_proof,
+			first_number,
+			request.last_block.0.as_(),
+			remote_max.as_(),
+			&request.key)
+		.map(|pairs| pairs.into_iter().map(|(b, x)| (As::sa(b), x)).collect())
+		.map_err(|err| ClientErrorKind::ChangesTrieAccessFailed(err).into())
+	}
+
+	fn check_child_roots_proof(
+		&self,
+		request: &RemoteChildRootsRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<(Vec<u8>, Vec<u8>)>> {
+		let mut root: H::Out = Default::default();
+		root.as_mut().copy_from_slice(request.header.state_root().as_ref());
+		child_roots_proof_check::<H>(root, remote_proof).map_err(Into::into)
+	}
+
+	fn check_digest_proof(
+		&self,
+		request: &RemoteDigestRequest<Block::Header>,
+		remote_headers: Vec<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<(NumberFor<Block>, Vec<DigestItemFor<Block>>)>> {
+		// headers of all blocks of the range are required, so that none of the blocks with
+		// requested items may be omitted
+		if request.last_block < request.first_block
+			|| remote_headers.len() as u64 != (request.last_block - request.first_block).as_() + 1 {
+			return Err(ClientErrorKind::InvalidHeaderProof.into());
+		}
+
+		let mut result = Vec::new();
+		let mut expected_number = request.first_block;
+		for header in remote_headers {
+			let number = *header.number();
+			if number != expected_number {
+				return Err(ClientErrorKind::InvalidHeaderProof.into());
+			}
+			expected_number = expected_number + One::one();
+
+			cht::check_proof::<Block::Header, H>(request.cht_root, number, header.hash(), remote_proof.clone())?;
+			let items = matching_digest_items(&header, &request.item_types);
+			if !items.is_empty() {
+				result.push((number, items));
+			}
+		}
+		Ok(result)
+	}
+
+	fn check_body_proof(
+		&self,
+		request: &RemoteBodyRequest<Block::Header>,
+		body: Vec<Block::Extrinsic>
+	) -> ClientResult<Vec<Block::Extrinsic>> {
+		let extrinsics_root = <<Block::Header as HeaderT>::Hashing as HashT>::ordered_trie_root(
+			body.iter().map(Encode::encode));
+		if *request.header.extrinsics_root() != extrinsics_root {
+			return Err(ClientErrorKind::InvalidBodyProof.into());
+		}
+		Ok(body)
+	}
+}
+
+/// A view of HashMap<Number, Hash> as a changes trie roots storage.
+struct RootsStorage<'a, Hash: 'a> {
+	first: u64,
+	roots: &'a [Hash],
+}
+
+impl<'a, H, Hash> ChangesTrieRootsStorage<H> for RootsStorage<'a, Hash>
+	where
+		H: Hasher,
+		Hash: 'a + Send + Sync + Clone + AsRef<[u8]>,
+{
+	fn root(&self, block: u64) -> Result<Option<H::Out>, String> {
+		Ok(block.checked_sub(self.first)
+			.and_then(|index| self.roots.get(index as usize))
+			.cloned()
+			.map(|root| {
+				let mut hasher_root: H::Out = Default::default();
+				hasher_root.as_mut().copy_from_slice(root.as_ref());
+				hasher_root
+			}))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use primitives::Blake2Hasher;
+	use primitives::storage::well_known_keys;
+	use runtime_primitives::traits::Header as HeaderT;
+	use test_client::runtime::{Block, Header};
+	use light::fetcher::tests::prepare_for_read_proof_check;
+	use super::*;
+
+	#[test]
+	fn nodes_of_checked_proofs_are_cached() {
+		let (local_checker, remote_block_header, remote_read_proof, authorities_len) = prepare_for_read_proof_check();
+		let mut request = RemoteReadRequest::<Header> {
+			block: remote_block_header.hash(),
+			header: remote_block_header,
+			keys: vec![well_known_keys::AUTHORITY_COUNT.to_vec(), b":missing".to_vec()],
+			retry_count: None,
+		};
+		let check = |request: &RemoteReadRequest<Header>| (&local_checker as &FetchChecker<Block>)
+			.check_read_proof(request, remote_read_proof.clone());
+		let root = |header: &Header| {
+			let mut root: <Blake2Hasher as Hasher>::Out = Default::default();
+			root.as_mut().copy_from_slice(header.state_root().as_ref());
+			root
+		};
+
+		// nothing is cached until the proof is checked
+		let (_, hashed) = local_checker.proof_nodes(&root(&request.header), remote_read_proof.clone());
+		assert_eq!(hashed.len(), remote_read_proof.len());
+
+		let values = check(&request).unwrap();
+		assert_eq!(values[0].as_ref().unwrap()[0], authorities_len as u8);
+		let (_, hashed) = local_checker.proof_nodes(&root(&request.header), remote_read_proof.clone());
+		assert!(hashed.is_empty());
+
+		// cached nodes are only used for the proofs against the same state root
+		assert_eq!(check(&request).unwrap(), values);
+		let verified_root = root(&request.header);
+		request.header.state_root = Default::default();
+		assert!(check(&request).is_err());
+		assert_eq!(local_checker.verified_nodes.lock().as_ref().map(|verified| verified.root), Some(verified_root));
+	}
+}
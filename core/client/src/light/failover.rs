@@ -0,0 +1,233 @@
+// Copyright 2017-2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Light client fetcher with failover. Requests are sent to the primary fetcher (e.g. the
+//! on-demand network fetcher) and are only resent to the secondary fetcher (e.g. the RPC bridge)
+//! if the primary fetcher has failed to answer or is gone. Fetchers are nested to get more
+//! than two priority levels.
+
+use std::sync::Weak;
+use futures::{Async, Future, IntoFuture, Poll};
+
+use runtime_primitives::traits::Block as BlockT;
+
+use error::{Error as ClientError, ErrorKind as ClientErrorKind};
+use light::fetcher::{Fetcher, RemoteHeaderRequest, RemoteReadRequest, RemoteReadChildRequest,
+	RemoteCallRequest, RemoteChangesRequest, RemoteReadPrefixRequest, RemoteChildRootsRequest,
+	RemoteDigestRequest, RemoteBodyRequest};
+
+/// Fetcher that sends the request to the secondary fetcher when the primary fetcher fails.
+pub struct FailoverFetcher<P, S> {
+	primary: Weak<P>,
+	secondary: Weak<S>,
+}
+
+/// Future of the remote request, resent to the secondary fetcher if the primary fetcher fails.
+pub struct FailoverFuture<P: IntoFuture, S: IntoFuture, F, R> {
+	primary: Option<P::Future>,
+	secondary: Option<(Weak<F>, R, fn(&F, R) -> S)>,
+	secondary_future: Option<S::Future>,
+	error: Option<ClientError>,
+}
+
+impl<P, S> FailoverFetcher<P, S> {
+	/// Create new fetcher with given fetchers, ordered by priority.
+	pub fn new(primary: Weak<P>, secondary: Weak<S>) -> Self {
+		FailoverFetcher { primary, secondary }
+	}
+
+	fn failover<R: Clone, PR: IntoFuture, SR: IntoFuture>(
+		&self,
+		request: R,
+		primary: fn(&P, R) -> PR,
+		secondary: fn(&S, R) -> SR,
+	) -> FailoverFuture<PR, SR, S, R> {
+		FailoverFuture {
+			primary: self.primary.upgrade().map(|fetcher| primary(&fetcher, request.clone()).into_future()),
+			secondary: Some((self.secondary.clone(), request, secondary)),
+			secondary_future: None,
+			error: None,
+		}
+	}
+}
+
+impl<P, S, F, R> Future for FailoverFuture<P, S, F, R> where
+	P: IntoFuture<Error=ClientError>,
+	S: IntoFuture<Item=P::Item, Error=ClientError>,
+{
+	type Item = P::Item;
+	type Error = ClientError;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		if let Some(mut primary) = self.primary.take() {
+			match primary.poll() {
+				Ok(Async::NotReady) => {
+					self.primary = Some(primary);
+					return Ok(Async::NotReady);
+				},
+				Ok(Async::Ready(result)) => return Ok(Async::Ready(result)),
+				Err(error) => {
+					debug!(target: "client", "Primary fetcher has failed: {}. Trying secondary fetcher", error);
+					self.error = Some(error);
+				},
+			}
+		}
+
+		if let Some((fetcher, request, fetch)) = self.secondary.take() {
+			match fetcher.upgrade() {
+				Some(fetcher) => self.secondary_future = Some(fetch(&fetcher, request).into_future()),
+				None => return Err(self.error.take()
+					.unwrap_or_else(|| ClientErrorKind::NotAvailableOnLightClient.into())),
+			}
+		}
+
+		self.secondary_future.as_mut()
+			.expect("secondary future is created above if the secondary fetcher is alive; qed")
+			.poll()
+	}
+}
+
+impl<Block, P, S> Fetcher<Block> for FailoverFetcher<P, S> where
+	Block: BlockT,
+	P: Fetcher<Block>,
+	S: Fetcher<Block>,
+{
+	type RemoteHeaderResult = FailoverFuture<P::RemoteHeaderResult, S::RemoteHeaderResult,
+		S, RemoteHeaderRequest<Block::Header>>;
+	type RemoteReadResult = FailoverFuture<P::RemoteReadResult, S::RemoteReadResult,
+		S, RemoteReadRequest<Block::Header>>;
+	type RemoteReadChildResult = FailoverFuture<P::RemoteReadChildResult, S::RemoteReadChildResult,
+		S, RemoteReadChildRequest<Block::Header>>;
+	type RemoteCallResult = FailoverFuture<P::RemoteCallResult, S::RemoteCallResult,
+		S, RemoteCallRequest<Block::Header>>;
+	type RemoteChangesResult = FailoverFuture<P::RemoteChangesResult, S::RemoteChangesResult,
+		S, RemoteChangesRequest<Block::Header>>;
+	type RemoteReadPrefixResult = FailoverFuture<P::RemoteReadPrefixResult, S::RemoteReadPrefixResult,
+		S, RemoteReadPrefixRequest<Block::Header>>;
+	type RemoteChildRootsResult = FailoverFuture<P::RemoteChildRootsResult, S::RemoteChildRootsResult,
+		S, RemoteChildRootsRequest<Block::Header>>;
+	type RemoteDigestResult = FailoverFuture<P::RemoteDigestResult, S::RemoteDigestResult,
+		S, RemoteDigestRequest<Block::Header>>;
+	type RemoteBodyResult = FailoverFuture<P::RemoteBodyResult, S::RemoteBodyResult,
+		S, RemoteBodyRequest<Block::Header>>;
+
+	fn remote_header(&self, request: RemoteHeaderRequest<Block::Header>) -> Self::RemoteHeaderResult {
+		self.failover(request, <P as Fetcher<Block>>::remote_header, <S as Fetcher<Block>>::remote_header)
+	}
+
+	fn remote_read(&self, request: RemoteReadRequest<Block::Header>) -> Self::RemoteReadResult {
+		self.failover(request, <P as Fetcher<Block>>::remote_read, <S as Fetcher<Block>>::remote_read)
+	}
+
+	fn remote_read_child(&self, request: RemoteReadChildRequest<Block::Header>) -> Self::RemoteReadChildResult {
+		self.failover(request, <P as Fetcher<Block>>::remote_read_child, <S as Fetcher<Block>>::remote_read_child)
+	}
+
+	fn remote_call(&self, request: RemoteCallRequest<Block::Header>) -> Self::RemoteCallResult {
+		self.failover(request, <P as Fetcher<Block>>::remote_call, <S as Fetcher<Block>>::remote_call)
+	}
+
+	fn remote_changes(&self, request: RemoteChangesRequest<Block::Header>) -> Self::RemoteChangesResult {
+		self.failover(request, <P as Fetcher<Block>>::remote_changes, <S as Fetcher<Block>>::remote_changes)
+	}
+
+	fn remote_read_prefix(&self, request: RemoteReadPrefixRequest<Block::Header>) -> Self::RemoteReadPrefixResult {
+		self.failover(request, <P as Fetcher<Block>>::remote_read_prefix, <S as Fetcher<Block>>::remote_read_prefix)
+	}
+
+	fn remote_child_roots(&self, request: RemoteChildRootsRequest<Block::Header>) -> Self::RemoteChildRootsResult {
+		self.failover(request, <P as Fetcher<Block>>::remote_child_roots, <S as Fetcher<Block>>::remote_child_roots)
+	}
+
+	fn remote_digest(&self, request: RemoteDigestRequest<Block::Header>) -> Self::RemoteDigestResult {
+		self.failover(request, <P as Fetcher<Block>>::remote_digest, <S as Fetcher<Block>>::remote_digest)
+	}
+
+	fn remote_body(&self, request: RemoteBodyRequest<Block::Header>) -> Self::RemoteBodyResult {
+		self.failover(request, <P as Fetcher<Block>>::remote_body, <S as Fetcher<Block>>::remote_body)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use parking_lot::Mutex;
+	use call_executor::CallResult;
+	use light::fetcher::tests::OkCallFetcher;
+	use state_machine::OverlayedChanges;
+	use test_client::runtime::{Block, Header};
+	use super::*;
+
+	fn call_fetcher(return_data: Vec<u8>) -> Arc<OkCallFetcher> {
+		Arc::new(Mutex::new(CallResult {
+			return_data,
+			changes: OverlayedChanges::default(),
+			accessed_keys: Vec::new(),
+		}))
+	}
+
+	fn call_request() -> RemoteCallRequest<Header> {
+		RemoteCallRequest {
+			block: Default::default(),
+			header: Header {
+				number: 0,
+				parent_hash: Default::default(),
+				state_root: Default::default(),
+				extrinsics_root: Default::default(),
+				digest: Default::default(),
+			},
+			method: "test".into(),
+			call_data: Vec::new(),
+			trace_keys: false,
+			retry_count: None,
+		}
+	}
+
+	#[test]
+	fn request_is_answered_by_primary_fetcher() {
+		let primary = call_fetcher(vec![1]);
+		let secondary = call_fetcher(vec![2]);
+		let fetcher = FailoverFetcher::new(Arc::downgrade(&primary), Arc::downgrade(&secondary));
+		let result = Fetcher::<Block>::remote_call(&fetcher, call_request()).wait().unwrap();
+		assert_eq!(result.return_data, vec![1]);
+	}
+
+	#[test]
+	fn failed_request_is_resent_to_secondary_fetcher() {
+		let primary = call_fetcher(vec![1]);
+		let secondary = call_fetcher(vec![2]);
+
+		// the nested fetcher fails because all of its fetchers are gone
+		let gone = call_fetcher(vec![3]);
+		let failing = Arc::new(FailoverFetcher::new(Arc::downgrade(&gone), Arc::downgrade(&gone)));
+		drop(gone);
+		let fetcher = FailoverFetcher::new(Arc::downgrade(&failing), Arc::downgrade(&secondary));
+		let result = Fetcher::<Block>::remote_call(&fetcher, call_request()).wait().unwrap();
+		assert_eq!(result.return_data, vec![2]);
+
+		// the request is sent to the secondary fetcher if the primary one is gone
+		let fetcher = FailoverFetcher::new(Arc::downgrade(&primary), Arc::downgrade(&secondary));
+		drop(primary);
+		let result = Fetcher::<Block>::remote_call(&fetcher, call_request()).wait().unwrap();
+		assert_eq!(result.return_data, vec![2]);
+
+		drop(secondary);
+		match Fetcher::<Block>::remote_call(&fetcher, call_request()).wait() {
+			Err(ClientError(ClientErrorKind::NotAvailableOnLightClient, _)) => (),
+			result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+		}
+	}
+}
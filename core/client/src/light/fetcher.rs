@@ -16,20 +16,16 @@
 
 //! Light client data fetcher. Fetches requested data from remote full nodes.
 
-use std::marker::PhantomData;
+use std::time::Duration;
 use futures::IntoFuture;
 
-use hash_db::Hasher;
-use heapsize::HeapSizeOf;
 use primitives::ChangesTrieConfiguration;
-use runtime_primitives::traits::{As, Block as BlockT, Header as HeaderT, NumberFor};
-use state_machine::{CodeExecutor, ChangesTrieRootsStorage, read_proof_check,
-	key_changes_proof_check};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor, DigestItemFor};
 
 use call_executor::CallResult;
-use cht;
-use error::{Error as ClientError, ErrorKind as ClientErrorKind, Result as ClientResult};
-use light::call_executor::check_execution_proof;
+use error::Error as ClientError;
+
+pub use light::checker::{FetchChecker, LightDataChecker, matching_digest_items};
 
 /// Remote call request.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -42,7 +38,9 @@ pub struct RemoteCallRequest<Header: HeaderT> {
 	pub method: String,
 	/// Call data.
 	pub call_data: Vec<u8>,
-	/// Number of times to retry request. None means that default RETRY_COUNT is used.
+	/// Whether to ask the remote node for the storage keys read by the call.
+	pub trace_keys: bool,
+	/// Number of times to retry request. None means that the default of the fetcher is used.
 	pub retry_count: Option<usize>,
 }
 
@@ -53,7 +51,7 @@ pub struct RemoteHeaderRequest<Header: HeaderT> {
 	pub cht_root: Header::Hash,
 	/// Number of the header to query.
 	pub block: Header::Number,
-	/// Number of times to retry request. None means that default RETRY_COUNT is used.
+	/// Number of times to retry request. None means that the default of the fetcher is used.
 	pub retry_count: Option<usize>,
 }
 
@@ -64,9 +62,77 @@ pub struct RemoteReadRequest<Header: HeaderT> {
 	pub block: Header::Hash,
 	/// Header of block at which read is performed.
 	pub header: Header,
-	/// Storage key to read.
+	/// Storage keys to read. All values are proved by the single proof.
+	pub keys: Vec<Vec<u8>>,
+	/// Number of times to retry request. None means that the default of the fetcher is used.
+	pub retry_count: Option<usize>,
+}
+
+/// Remote child storage read request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RemoteReadChildRequest<Header: HeaderT> {
+	/// Read at state of given block.
+	pub block: Header::Hash,
+	/// Header of block at which read is performed.
+	pub header: Header,
+	/// Storage key of the child trie.
+	pub storage_key: Vec<u8>,
+	/// Child storage key to read.
 	pub key: Vec<u8>,
-	/// Number of times to retry request. None means that default RETRY_COUNT is used.
+	/// Number of times to retry request. None means that the default of the fetcher is used.
+	pub retry_count: Option<usize>,
+}
+
+/// Remote storage prefix read request. Reads the page of the storage entries with keys starting
+/// with the `prefix`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RemoteReadPrefixRequest<Header: HeaderT> {
+	/// Read at state of given block.
+	pub block: Header::Hash,
+	/// Header of block at which read is performed.
+	pub header: Header,
+	/// Prefix of the storage keys to read.
+	pub prefix: Vec<u8>,
+	/// The page starts from the first entry with the key that is greater than or equal to this key.
+	pub start_key: Vec<u8>,
+	/// Maximal number of entries in the page. The remote node may return less entries.
+	pub limit: u32,
+	/// Number of times to retry request. None means that the default of the fetcher is used.
+	pub retry_count: Option<usize>,
+}
+
+/// Remote child tries roots request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RemoteChildRootsRequest<Header: HeaderT> {
+	/// Read at state of given block.
+	pub block: Header::Hash,
+	/// Header of block at which read is performed.
+	pub header: Header,
+	/// Number of times to retry request. None means that the default of the fetcher is used.
+	pub retry_count: Option<usize>,
+}
+
+/// Remote block body request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RemoteBodyRequest<Header: HeaderT> {
+	/// Header of the block which body is requested.
+	pub header: Header,
+	/// Number of times to retry request. None means that the default of the fetcher is used.
+	pub retry_count: Option<usize>,
+}
+
+/// Remote digest items request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RemoteDigestRequest<Header: HeaderT> {
+	/// The root of CHT the requested blocks are included in.
+	pub cht_root: Header::Hash,
+	/// Number of the first block of the range.
+	pub first_block: Header::Number,
+	/// Number of the last block of the range (inclusive). Should be included in the same CHT.
+	pub last_block: Header::Number,
+	/// Types of the requested digest items. The type of the item is the first byte of its encoding.
+	pub item_types: Vec<u8>,
+	/// Number of times to retry request. None means that the default of the fetcher is used.
 	pub retry_count: Option<usize>,
 }
 
@@ -88,176 +154,134 @@ pub struct RemoteChangesRequest<Header: HeaderT> {
 	pub tries_roots: Vec<Header::Hash>,
 	/// Storage key to read.
 	pub key: Vec<u8>,
-	/// Number of times to retry request. None means that default RETRY_COUNT is used.
+	/// Number of times to retry request. None means that the default of the fetcher is used.
 	pub retry_count: Option<usize>,
 }
 
+/// Kind of the remote request. Fetcher metrics are collected per request kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RequestKind {
+	/// Header request.
+	Header,
+	/// Storage read request.
+	Read,
+	/// Child storage read request.
+	ReadChild,
+	/// Call request.
+	Call,
+	/// Key changes request.
+	Changes,
+	/// Child storage roots request.
+	ChildRoots,
+	/// Digest items request.
+	Digest,
+	/// Storage read by key prefix request.
+	ReadPrefix,
+	/// Block body request.
+	Body,
+}
+
+impl RequestKind {
+	/// Name of the request kind, used when the metrics are reported.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			RequestKind::Header => "header",
+			RequestKind::Read => "read",
+			RequestKind::ReadChild => "read_child",
+			RequestKind::Call => "call",
+			RequestKind::Changes => "changes",
+			RequestKind::ChildRoots => "child_roots",
+			RequestKind::Digest => "digest",
+			RequestKind::ReadPrefix => "read_prefix",
+			RequestKind::Body => "body",
+		}
+	}
+}
+
+/// Upper bounds of the latency histogram buckets, in milliseconds. Latencies above the last
+/// bound are counted by the additional bucket.
+pub const LATENCY_BUCKETS_MS: [u64; 8] = [10, 25, 50, 100, 250, 500, 1000, 5000];
+
+/// Histogram of the response latencies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+	/// Number of responses per bucket. The bucket `i` counts latencies up to
+	/// `LATENCY_BUCKETS_MS[i]`, the last bucket counts the rest.
+	pub buckets: [u64; 9],
+}
+
+impl LatencyHistogram {
+	/// Note the response received after given time.
+	pub fn note(&mut self, latency: Duration) {
+		let latency_ms = latency.as_secs() * 1000 + latency.subsec_millis() as u64;
+		let bucket = LATENCY_BUCKETS_MS.iter()
+			.position(|bound| latency_ms <= *bound)
+			.unwrap_or(LATENCY_BUCKETS_MS.len());
+		self.buckets[bucket] += 1;
+	}
+}
+
+/// Metrics of the remote requests of one kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestMetrics {
+	/// Number of requests made. Retries are not counted.
+	pub requests: u64,
+	/// Number of responses that have passed verification.
+	pub responses: u64,
+	/// Number of responses that have failed verification or were unexpected.
+	pub invalid_responses: u64,
+	/// Number of attempts that have timed out.
+	pub timeouts: u64,
+	/// Number of requests that have failed after all retries.
+	pub failures: u64,
+	/// Latencies of the responses that have passed verification.
+	pub latency: LatencyHistogram,
+}
+
 /// Light client data fetcher. Implementations of this trait must check if remote data
 /// is correct (see FetchedDataChecker) and return already checked data.
 pub trait Fetcher<Block: BlockT>: Send + Sync {
 	/// Remote header future.
 	type RemoteHeaderResult: IntoFuture<Item=Block::Header, Error=ClientError>;
 	/// Remote storage read future.
-	type RemoteReadResult: IntoFuture<Item=Option<Vec<u8>>, Error=ClientError>;
+	type RemoteReadResult: IntoFuture<Item=Vec<Option<Vec<u8>>>, Error=ClientError>;
+	/// Remote child storage read future.
+	type RemoteReadChildResult: IntoFuture<Item=Option<Vec<u8>>, Error=ClientError>;
 	/// Remote call result future.
 	type RemoteCallResult: IntoFuture<Item=CallResult, Error=ClientError>;
 	/// Remote changes result future.
 	type RemoteChangesResult: IntoFuture<Item=Vec<(NumberFor<Block>, u32)>, Error=ClientError>;
+	/// Remote storage prefix read result future.
+	type RemoteReadPrefixResult: IntoFuture<Item=(Vec<(Vec<u8>, Vec<u8>)>, bool), Error=ClientError>;
+	/// Remote child tries roots result future.
+	type RemoteChildRootsResult: IntoFuture<Item=Vec<(Vec<u8>, Vec<u8>)>, Error=ClientError>;
+	/// Remote digest items result future.
+	type RemoteDigestResult: IntoFuture<Item=Vec<(NumberFor<Block>, Vec<DigestItemFor<Block>>)>, Error=ClientError>;
+	/// Remote block body result future.
+	type RemoteBodyResult: IntoFuture<Item=Vec<Block::Extrinsic>, Error=ClientError>;
 
 	/// Fetch remote header.
 	fn remote_header(&self, request: RemoteHeaderRequest<Block::Header>) -> Self::RemoteHeaderResult;
-	/// Fetch remote storage value.
+	/// Fetch remote storage values, in the order of the requested keys.
 	fn remote_read(&self, request: RemoteReadRequest<Block::Header>) -> Self::RemoteReadResult;
+	/// Fetch remote child storage value.
+	fn remote_read_child(&self, request: RemoteReadChildRequest<Block::Header>) -> Self::RemoteReadChildResult;
 	/// Fetch remote call result.
 	fn remote_call(&self, request: RemoteCallRequest<Block::Header>) -> Self::RemoteCallResult;
 	/// Fetch remote changes ((block number, extrinsic index)) where given key has been changed
 	/// at a given blocks range.
 	fn remote_changes(&self, request: RemoteChangesRequest<Block::Header>) -> Self::RemoteChangesResult;
-}
-
-/// Light client remote data checker.
-///
-/// Implementations of this trait should not use any blockchain data except that is
-/// passed to its methods.
-pub trait FetchChecker<Block: BlockT>: Send + Sync {
-	/// Check remote header proof.
-	fn check_header_proof(
-		&self,
-		request: &RemoteHeaderRequest<Block::Header>,
-		header: Option<Block::Header>,
-		remote_proof: Vec<Vec<u8>>
-	) -> ClientResult<Block::Header>;
-	/// Check remote storage read proof.
-	fn check_read_proof(
-		&self,
-		request: &RemoteReadRequest<Block::Header>,
-		remote_proof: Vec<Vec<u8>>
-	) -> ClientResult<Option<Vec<u8>>>;
-	/// Check remote method execution proof.
-	fn check_execution_proof(
-		&self,
-		request: &RemoteCallRequest<Block::Header>,
-		remote_proof: Vec<Vec<u8>>
-	) -> ClientResult<CallResult>;
-	/// Check remote changes query proof.
-	fn check_changes_proof(
-		&self,
-		request: &RemoteChangesRequest<Block::Header>,
-		remote_max: NumberFor<Block>,
-		remote_proof: Vec<Vec<u8>>
-	) -> ClientResult<Vec<(NumberFor<Block>, u32)>>;
-}
-
-/// Remote data checker.
-pub struct LightDataChecker<E, H> {
-	executor: E,
-	_hasher: PhantomData<H>,
-}
-
-impl<E, H> LightDataChecker<E, H> {
-	/// Create new light data checker.
-	pub fn new(executor: E) -> Self {
-		Self {
-			executor, _hasher: PhantomData
-		}
-	}
-}
-
-impl<E, Block, H> FetchChecker<Block> for LightDataChecker<E, H>
-	where
-		Block: BlockT,
-		E: CodeExecutor<H>,
-		H: Hasher,
-		H::Out: Ord + HeapSizeOf,
-{
-	fn check_header_proof(
-		&self,
-		request: &RemoteHeaderRequest<Block::Header>,
-		remote_header: Option<Block::Header>,
-		remote_proof: Vec<Vec<u8>>
-	) -> ClientResult<Block::Header> {
-		let remote_header = remote_header.ok_or_else(||
-			ClientError::from(ClientErrorKind::InvalidHeaderProof))?;
-		let remote_header_hash = remote_header.hash();
-		cht::check_proof::<Block::Header, H>(
-			request.cht_root,
-			request.block,
-			remote_header_hash,
-			remote_proof)
-			.map(|_| remote_header)
-	}
-
-	fn check_read_proof(
-		&self,
-		request: &RemoteReadRequest<Block::Header>,
-		remote_proof: Vec<Vec<u8>>
-	) -> ClientResult<Option<Vec<u8>>> {
-		let mut root: H::Out = Default::default();
-		root.as_mut().copy_from_slice(request.header.state_root().as_ref());
-		read_proof_check::<H>(root, remote_proof, &request.key).map_err(Into::into)
-	}
-
-	fn check_execution_proof(
-		&self,
-		request: &RemoteCallRequest<Block::Header>,
-		remote_proof: Vec<Vec<u8>>
-	) -> ClientResult<CallResult> {
-		check_execution_proof::<_, _, H>(&self.executor, request, remote_proof)
-	}
-
-	fn check_changes_proof(
-		&self,
-		request: &RemoteChangesRequest<Block::Header>,
-		remote_max: NumberFor<Block>,
-		remote_proof: Vec<Vec<u8>>
-	) -> ClientResult<Vec<(NumberFor<Block>, u32)>> {
-		// since we need roots of all changes tries for the range begin..max
-		// => remote node can't use max block greater that one that we have passed
-		if remote_max > request.max_block.0 || remote_max < request.last_block.0 {
-			return Err(ClientErrorKind::ChangesTrieAccessFailed(format!(
-				"Invalid max_block used by the remote node: {}. Local: {}..{}..{}",
-				remote_max, request.first_block.0, request.last_block.0, request.max_block.0,
-			)).into());
-		}
-
-		let first_number = request.first_block.0.as_();
-		key_changes_proof_check::<_, H>(
-			&request.changes_trie_config,
-			&RootsStorage {
-				first: first_number,
-				roots: &request.tries_roots,
-			},
-			remote_proof,
-			first_number,
-			request.last_block.0.as_(),
-			remote_max.as_(),
-			&request.key)
-		.map(|pairs| pairs.into_iter().map(|(b, x)| (As::sa(b), x)).collect())
-		.map_err(|err| ClientErrorKind::ChangesTrieAccessFailed(err).into())
-	}
-}
-
-/// A view of HashMap<Number, Hash> as a changes trie roots storage.
-struct RootsStorage<'a, Hash: 'a> {
-	first: u64,
-	roots: &'a [Hash],
-}
-
-impl<'a, H, Hash> ChangesTrieRootsStorage<H> for RootsStorage<'a, Hash>
-	where
-		H: Hasher,
-		Hash: 'a + Send + Sync + Clone + AsRef<[u8]>,
-{
-	fn root(&self, block: u64) -> Result<Option<H::Out>, String> {
-		Ok(block.checked_sub(self.first)
-			.and_then(|index| self.roots.get(index as usize))
-			.cloned()
-			.map(|root| {
-				let mut hasher_root: H::Out = Default::default();
-				hasher_root.as_mut().copy_from_slice(root.as_ref());
-				hasher_root
-			}))
-	}
+	/// Fetch the page of storage entries with keys starting with given prefix. The flag is true
+	/// if there are no more entries with the prefix after the page.
+	fn remote_read_prefix(&self, request: RemoteReadPrefixRequest<Block::Header>) -> Self::RemoteReadPrefixResult;
+	/// Fetch storage keys and roots of all child tries ((child storage key, child trie root)).
+	fn remote_child_roots(&self, request: RemoteChildRootsRequest<Block::Header>) -> Self::RemoteChildRootsResult;
+	/// Fetch digest items of requested types ((block number, digest items)) from the headers of
+	/// a given blocks range. Blocks without such items are omitted from the result. Headers of
+	/// the whole range are fetched and proved, so none of the matching blocks may be left out.
+	fn remote_digest(&self, request: RemoteDigestRequest<Block::Header>) -> Self::RemoteDigestResult;
+	/// Fetch the body of the block.
+	fn remote_body(&self, request: RemoteBodyRequest<Block::Header>) -> Self::RemoteBodyResult;
 }
 
 #[cfg(test)]
@@ -272,6 +296,7 @@ pub mod tests {
 	use test_client::runtime::{self, Hash, Block, Header};
 	use consensus::BlockOrigin;
 
+	use cht;
 	use in_mem::{Blockchain as InMemoryBlockchain};
 	use light::fetcher::{Fetcher, FetchChecker, LightDataChecker,
 		RemoteCallRequest, RemoteHeaderRequest};
@@ -283,11 +308,19 @@ pub mod tests {
 
 	pub type OkCallFetcher = Mutex<CallResult>;
 
+	/// Test fetcher that responds to every remote read with the same values.
+	pub type OkReadFetcher = Mutex<Vec<Option<Vec<u8>>>>;
+
 	impl Fetcher<Block> for OkCallFetcher {
 		type RemoteHeaderResult = FutureResult<Header, ClientError>;
-		type RemoteReadResult = FutureResult<Option<Vec<u8>>, ClientError>;
+		type RemoteReadResult = FutureResult<Vec<Option<Vec<u8>>>, ClientError>;
+		type RemoteReadChildResult = FutureResult<Option<Vec<u8>>, ClientError>;
 		type RemoteCallResult = FutureResult<CallResult, ClientError>;
 		type RemoteChangesResult = FutureResult<Vec<(NumberFor<Block>, u32)>, ClientError>;
+		type RemoteReadPrefixResult = FutureResult<(Vec<(Vec<u8>, Vec<u8>)>, bool), ClientError>;
+		type RemoteChildRootsResult = FutureResult<Vec<(Vec<u8>, Vec<u8>)>, ClientError>;
+		type RemoteDigestResult = FutureResult<Vec<(NumberFor<Block>, Vec<DigestItemFor<Block>>)>, ClientError>;
+		type RemoteBodyResult = FutureResult<Vec<runtime::Extrinsic>, ClientError>;
 
 		fn remote_header(&self, _request: RemoteHeaderRequest<Header>) -> Self::RemoteHeaderResult {
 			err("Not implemented on test node".into())
@@ -297,6 +330,10 @@ pub mod tests {
 			err("Not implemented on test node".into())
 		}
 
+		fn remote_read_child(&self, _request: RemoteReadChildRequest<Header>) -> Self::RemoteReadChildResult {
+			err("Not implemented on test node".into())
+		}
+
 		fn remote_call(&self, _request: RemoteCallRequest<Header>) -> Self::RemoteCallResult {
 			ok((*self.lock()).clone())
 		}
@@ -304,9 +341,73 @@ pub mod tests {
 		fn remote_changes(&self, _request: RemoteChangesRequest<Header>) -> Self::RemoteChangesResult {
 			err("Not implemented on test node".into())
 		}
+
+		fn remote_read_prefix(&self, _request: RemoteReadPrefixRequest<Header>) -> Self::RemoteReadPrefixResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_child_roots(&self, _request: RemoteChildRootsRequest<Header>) -> Self::RemoteChildRootsResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_digest(&self, _request: RemoteDigestRequest<Header>) -> Self::RemoteDigestResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_body(&self, _request: RemoteBodyRequest<Header>) -> Self::RemoteBodyResult {
+			err("Not implemented on test node".into())
+		}
+	}
+
+	impl Fetcher<Block> for OkReadFetcher {
+		type RemoteHeaderResult = FutureResult<Header, ClientError>;
+		type RemoteReadResult = FutureResult<Vec<Option<Vec<u8>>>, ClientError>;
+		type RemoteReadChildResult = FutureResult<Option<Vec<u8>>, ClientError>;
+		type RemoteCallResult = FutureResult<CallResult, ClientError>;
+		type RemoteChangesResult = FutureResult<Vec<(NumberFor<Block>, u32)>, ClientError>;
+		type RemoteReadPrefixResult = FutureResult<(Vec<(Vec<u8>, Vec<u8>)>, bool), ClientError>;
+		type RemoteChildRootsResult = FutureResult<Vec<(Vec<u8>, Vec<u8>)>, ClientError>;
+		type RemoteDigestResult = FutureResult<Vec<(NumberFor<Block>, Vec<DigestItemFor<Block>>)>, ClientError>;
+		type RemoteBodyResult = FutureResult<Vec<runtime::Extrinsic>, ClientError>;
+
+		fn remote_header(&self, _request: RemoteHeaderRequest<Header>) -> Self::RemoteHeaderResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_read(&self, _request: RemoteReadRequest<Header>) -> Self::RemoteReadResult {
+			ok((*self.lock()).clone())
+		}
+
+		fn remote_read_child(&self, _request: RemoteReadChildRequest<Header>) -> Self::RemoteReadChildResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_call(&self, _request: RemoteCallRequest<Header>) -> Self::RemoteCallResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_changes(&self, _request: RemoteChangesRequest<Header>) -> Self::RemoteChangesResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_read_prefix(&self, _request: RemoteReadPrefixRequest<Header>) -> Self::RemoteReadPrefixResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_child_roots(&self, _request: RemoteChildRootsRequest<Header>) -> Self::RemoteChildRootsResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_digest(&self, _request: RemoteDigestRequest<Header>) -> Self::RemoteDigestResult {
+			err("Not implemented on test node".into())
+		}
+
+		fn remote_body(&self, _request: RemoteBodyRequest<Header>) -> Self::RemoteBodyResult {
+			err("Not implemented on test node".into())
+		}
 	}
 
-	fn prepare_for_read_proof_check() -> (
+	pub fn prepare_for_read_proof_check() -> (
 		LightDataChecker<executor::NativeExecutor<test_client::LocalExecutor>, Blake2Hasher>,
 		Header, Vec<Vec<u8>>, usize)
 	{
@@ -319,7 +420,10 @@ pub mod tests {
 
 		// 'fetch' read proof from remote node
 		let authorities_len = remote_client.authorities_at(&remote_block_id).unwrap().len();
-		let remote_read_proof = remote_client.read_proof(&remote_block_id, well_known_keys::AUTHORITY_COUNT).unwrap();
+		let remote_read_proof = remote_client.read_proof(&remote_block_id, &[
+			well_known_keys::AUTHORITY_COUNT.to_vec(),
+			b":missing".to_vec(),
+		]).unwrap();
 
 		// check remote read proof locally
 		let local_storage = InMemoryBlockchain::<Block>::new();
@@ -366,12 +470,124 @@ pub mod tests {
 	#[test]
 	fn storage_read_proof_is_generated_and_checked() {
 		let (local_checker, remote_block_header, remote_read_proof, authorities_len) = prepare_for_read_proof_check();
-		assert_eq!((&local_checker as &FetchChecker<Block>).check_read_proof(&RemoteReadRequest::<Header> {
+		let values = (&local_checker as &FetchChecker<Block>).check_read_proof(&RemoteReadRequest::<Header> {
+			block: remote_block_header.hash(),
+			header: remote_block_header,
+			keys: vec![well_known_keys::AUTHORITY_COUNT.to_vec(), b":missing".to_vec()],
+			retry_count: None,
+		}, remote_read_proof).unwrap();
+		assert_eq!(values.len(), 2);
+		assert_eq!(values[0].as_ref().unwrap()[0], authorities_len as u8);
+		assert_eq!(values[1], None);
+	}
+
+	#[test]
+	fn child_storage_read_proof_is_generated_and_checked() {
+		let (local_checker, remote_block_header, _, _) = prepare_for_read_proof_check();
+		let remote_client = test_client::new();
+		let storage_key = [well_known_keys::CHILD_STORAGE_KEY_PREFIX, &b"default:missing"[..]].concat();
+		let remote_proof = remote_client.read_child_proof(&BlockId::Number(0), &storage_key, b"key").unwrap();
+
+		let mut request = RemoteReadChildRequest::<Header> {
+			block: remote_block_header.hash(),
+			header: remote_block_header,
+			storage_key,
+			key: b"key".to_vec(),
+			retry_count: None,
+		};
+		// there are no child tries in the genesis state
+		assert_eq!((&local_checker as &FetchChecker<Block>)
+			.check_read_child_proof(&request, remote_proof.clone()).unwrap(), None);
+
+		request.header.state_root = Default::default();
+		assert!((&local_checker as &FetchChecker<Block>)
+			.check_read_child_proof(&request, remote_proof).is_err());
+	}
+
+	#[test]
+	fn read_prefix_proof_is_generated_and_checked() {
+		let (local_checker, remote_block_header, _, authorities_len) = prepare_for_read_proof_check();
+		let remote_client = test_client::new();
+		let remote_proof = remote_client.read_prefix_proof(&BlockId::Number(0), well_known_keys::AUTHORITY_PREFIX, &[], 100).unwrap();
+
+		let mut request = RemoteReadPrefixRequest::<Header> {
+			block: remote_block_header.hash(),
+			header: remote_block_header,
+			prefix: well_known_keys::AUTHORITY_PREFIX.to_vec(),
+			start_key: Vec::new(),
+			limit: 100,
+			retry_count: None,
+		};
+		let (entries, complete) = (&local_checker as &FetchChecker<Block>)
+			.check_read_prefix_proof(&request, remote_proof.clone()).unwrap();
+		assert!(complete);
+		// authorities and their count are stored under the prefix
+		assert_eq!(entries.len(), authorities_len + 1);
+		assert!(entries.iter().all(|&(ref key, _)| key.starts_with(well_known_keys::AUTHORITY_PREFIX)));
+
+		request.header.state_root = Default::default();
+		assert!((&local_checker as &FetchChecker<Block>)
+			.check_read_prefix_proof(&request, remote_proof).is_err());
+	}
+
+	#[test]
+	fn child_roots_proof_is_generated_and_checked() {
+		let remote_client = test_client::new();
+		let remote_block_id = BlockId::Number(0);
+		let mut remote_block_header = remote_client.header(&remote_block_id).unwrap().unwrap();
+		remote_block_header.state_root = remote_client.state_at(&remote_block_id).unwrap().storage_root(::std::iter::empty()).0.into();
+		let remote_proof = remote_client.child_roots_proof(&remote_block_id).unwrap();
+
+		let local_checker = LightDataChecker::<_, Blake2Hasher>::new(test_client::LocalExecutor::new());
+		let mut request = RemoteChildRootsRequest::<Header> {
 			block: remote_block_header.hash(),
 			header: remote_block_header,
-			key: well_known_keys::AUTHORITY_COUNT.to_vec(),
 			retry_count: None,
-		}, remote_read_proof).unwrap().unwrap()[0], authorities_len as u8);
+		};
+		assert_eq!((&local_checker as &FetchChecker<Block>)
+			.check_child_roots_proof(&request, remote_proof.clone()).unwrap(), vec![]);
+
+		request.header.state_root = Default::default();
+		assert!((&local_checker as &FetchChecker<Block>)
+			.check_child_roots_proof(&request, remote_proof).is_err());
+	}
+
+	#[test]
+	fn digest_proof_is_generated_and_checked() {
+		// every block of the remote chain has changes trie root digest item
+		let (remote_client, _, _) = prepare_client_with_key_changes();
+		let changes_trie_root_type = 2;
+		let (remote_headers, remote_proof) = remote_client.digest_proof_with_cht_size(2, 3, 4).unwrap();
+		assert_eq!(remote_headers.len(), 2);
+
+		let local_headers_hashes = (1..5).map(|number| remote_client.block_hash(number).unwrap());
+		let local_cht_root = cht::compute_root::<Header, Blake2Hasher, _>(4, 0, local_headers_hashes).unwrap();
+		let local_checker = LightDataChecker::<_, Blake2Hasher>::new(test_client::LocalExecutor::new());
+		let local_checker = &local_checker as &FetchChecker<Block>;
+		let request = RemoteDigestRequest::<Header> {
+			cht_root: local_cht_root,
+			first_block: 2,
+			last_block: 3,
+			item_types: vec![changes_trie_root_type],
+			retry_count: None,
+		};
+		let items = local_checker.check_digest_proof(&request, remote_headers.clone(), remote_proof.clone()).unwrap();
+		assert_eq!(
+			items.iter().map(|&(number, ref items)| (number, items.len())).collect::<Vec<_>>(),
+			vec![(2, 1), (3, 1)],
+		);
+
+		// headers without requested items are proved, but not returned
+		let other_request = RemoteDigestRequest::<Header> { item_types: vec![42], ..request.clone() };
+		assert!(local_checker.check_digest_proof(&other_request, remote_headers.clone(), remote_proof.clone()).unwrap().is_empty());
+
+		// headers out of the requested range are rejected
+		let other_request = RemoteDigestRequest::<Header> { first_block: 3, ..request.clone() };
+		assert!(local_checker.check_digest_proof(&other_request, remote_headers.clone(), remote_proof.clone()).is_err());
+
+		// omitted headers of the requested range are rejected
+		assert!(local_checker.check_digest_proof(&request, remote_headers[1..].to_vec(), remote_proof.clone()).is_err());
+		assert!(local_checker.check_digest_proof(&request, vec![], remote_proof).is_err());
 	}
 
 	#[test]
@@ -481,4 +697,42 @@ pub mod tests {
 		remote_proof = local_roots_range.into_iter().map(|v| v.to_vec()).collect();
 		assert!(local_checker.check_changes_proof(&request, remote_max, remote_proof).is_err());
 	}
+
+	#[test]
+	fn body_proof_is_checked_against_extrinsics_root() {
+		use keyring::Keyring;
+		use test_client::BlockBuilderExt;
+
+		let remote_client = test_client::new();
+		let mut builder = remote_client.new_block().unwrap();
+		builder.push_transfer(runtime::Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Ferdie.to_raw_public().into(),
+			amount: 42,
+			nonce: 0,
+		}).unwrap();
+		let block = builder.bake().unwrap();
+
+		let local_checker = LightDataChecker::<_, Blake2Hasher>::new(test_client::LocalExecutor::new());
+		let local_checker = &local_checker as &FetchChecker<Block>;
+		let request = RemoteBodyRequest::<Header> {
+			header: block.header.clone(),
+			retry_count: None,
+		};
+		assert_eq!(local_checker.check_body_proof(&request, block.extrinsics.clone()).unwrap(), block.extrinsics);
+		assert!(local_checker.check_body_proof(&request, vec![]).is_err());
+	}
+
+	#[test]
+	fn latency_histogram_buckets_latencies() {
+		use std::time::Duration;
+
+		let mut histogram = LatencyHistogram::default();
+		histogram.note(Duration::from_millis(0));
+		histogram.note(Duration::from_millis(10));
+		histogram.note(Duration::from_millis(11));
+		histogram.note(Duration::from_millis(700));
+		histogram.note(Duration::from_secs(60));
+		assert_eq!(histogram.buckets, [2, 1, 0, 0, 0, 0, 1, 0, 1]);
+	}
 }
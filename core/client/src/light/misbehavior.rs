@@ -0,0 +1,160 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Records of remote peers misbehavior, detected by the light client.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+use codec::{Encode, Decode, Input, Output};
+use hash_db::Hasher;
+use runtime_primitives::traits::Block as BlockT;
+
+use backend::Backend;
+use error::{ErrorKind as ClientErrorKind, Result as ClientResult};
+
+/// Aux key under which misbehavior records are kept.
+pub const MISBEHAVIOR_RECORDS_KEY: &[u8] = b"light_misbehavior_records";
+
+/// Maximal number of misbehavior records kept. Older records are dropped.
+pub const MAX_MISBEHAVIOR_RECORDS: usize = 256;
+
+/// Record of a remote response that has failed verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MisbehaviorRecord {
+	/// Description of the peer that has provided the response.
+	pub peer: String,
+	/// Unix timestamp (in seconds) of the moment when the response has been received.
+	pub timestamp: u64,
+	/// Encoded request message.
+	pub request: Vec<u8>,
+	/// Encoded response message.
+	pub response: Vec<u8>,
+	/// Description of the verification failure.
+	pub error: String,
+}
+
+impl Encode for MisbehaviorRecord {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		self.peer.as_bytes().encode_to(dest);
+		self.timestamp.encode_to(dest);
+		self.request.encode_to(dest);
+		self.response.encode_to(dest);
+		self.error.as_bytes().encode_to(dest);
+	}
+}
+
+impl Decode for MisbehaviorRecord {
+	fn decode<I: Input>(input: &mut I) -> Option<Self> {
+		Some(MisbehaviorRecord {
+			peer: String::from_utf8(Decode::decode(input)?).ok()?,
+			timestamp: Decode::decode(input)?,
+			request: Decode::decode(input)?,
+			response: Decode::decode(input)?,
+			error: String::from_utf8(Decode::decode(input)?).ok()?,
+		})
+	}
+}
+
+/// Log of peers misbehavior.
+pub trait MisbehaviorLog: Send + Sync {
+	/// Note new misbehavior record.
+	fn note(&self, record: MisbehaviorRecord) -> ClientResult<()>;
+
+	/// Get all known misbehavior records, oldest first.
+	fn records(&self) -> ClientResult<Vec<MisbehaviorRecord>>;
+}
+
+/// Misbehavior log that is persisted in the backend aux storage.
+pub struct AuxMisbehaviorLog<B, Block, H> {
+	backend: Arc<B>,
+	_phantom: PhantomData<(Block, H)>,
+}
+
+impl<B, Block, H> AuxMisbehaviorLog<B, Block, H> {
+	/// Create new log, backed by given backend.
+	pub fn new(backend: Arc<B>) -> Self {
+		AuxMisbehaviorLog {
+			backend,
+			_phantom: PhantomData,
+		}
+	}
+}
+
+impl<B, Block, H> MisbehaviorLog for AuxMisbehaviorLog<B, Block, H> where
+	Block: BlockT,
+	H: Hasher<Out=Block::Hash>,
+	B: Backend<Block, H>,
+{
+	fn note(&self, record: MisbehaviorRecord) -> ClientResult<()> {
+		let mut records = self.records()?;
+		records.push(record);
+		if records.len() > MAX_MISBEHAVIOR_RECORDS {
+			let excess = records.len() - MAX_MISBEHAVIOR_RECORDS;
+			records.drain(..excess);
+		}
+
+		let encoded = records.encode();
+		self.backend.insert_aux(&[(MISBEHAVIOR_RECORDS_KEY, &encoded[..])], &[])
+	}
+
+	fn records(&self) -> ClientResult<Vec<MisbehaviorRecord>> {
+		match self.backend.get_aux(MISBEHAVIOR_RECORDS_KEY)? {
+			Some(encoded) => Decode::decode(&mut &encoded[..])
+				.ok_or_else(|| ClientErrorKind::Backend("Error decoding misbehavior records".into()).into()),
+			None => Ok(Vec::new()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use in_mem::Backend as InMemoryBackend;
+	use primitives::Blake2Hasher;
+	use test_client::runtime::Block;
+	use super::*;
+
+	fn record(index: u64) -> MisbehaviorRecord {
+		MisbehaviorRecord {
+			peer: "peer".into(),
+			timestamp: index,
+			request: vec![1],
+			response: vec![2],
+			error: "invalid proof".into(),
+		}
+	}
+
+	#[test]
+	fn misbehavior_records_are_persisted() {
+		let backend = Arc::new(InMemoryBackend::<Block, Blake2Hasher>::new());
+		let log = AuxMisbehaviorLog::<_, Block, Blake2Hasher>::new(backend.clone());
+		assert!(log.records().unwrap().is_empty());
+
+		log.note(record(1)).unwrap();
+		assert_eq!(AuxMisbehaviorLog::<_, Block, Blake2Hasher>::new(backend).records().unwrap(), vec![record(1)]);
+	}
+
+	#[test]
+	fn old_misbehavior_records_are_dropped() {
+		let log = AuxMisbehaviorLog::<_, Block, Blake2Hasher>::new(Arc::new(InMemoryBackend::<Block, Blake2Hasher>::new()));
+		for index in 0..MAX_MISBEHAVIOR_RECORDS as u64 + 1 {
+			log.note(record(index)).unwrap();
+		}
+
+		let records = log.records().unwrap();
+		assert_eq!(records.len(), MAX_MISBEHAVIOR_RECORDS);
+		assert_eq!(records[0], record(1));
+	}
+}
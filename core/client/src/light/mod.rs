@@ -19,7 +19,11 @@
 pub mod backend;
 pub mod blockchain;
 pub mod call_executor;
+pub mod checker;
+pub mod failover;
 pub mod fetcher;
+pub mod misbehavior;
+pub mod storage_watch;
 
 use std::sync::Arc;
 
@@ -30,10 +34,11 @@ use state_machine::{CodeExecutor, ExecutionStrategy};
 
 use client::Client;
 use error::Result as ClientResult;
-use light::backend::Backend;
+use light::backend::{Backend, Checkpoint};
 use light::blockchain::{Blockchain, Storage as BlockchainStorage};
 use light::call_executor::RemoteCallExecutor;
-use light::fetcher::{Fetcher, LightDataChecker};
+use light::checker::LightDataChecker;
+use light::fetcher::Fetcher;
 use hash_db::Hasher;
 
 /// Create an instance of light client blockchain backend.
@@ -41,10 +46,16 @@ pub fn new_light_blockchain<B: BlockT, S: BlockchainStorage<B>, F>(storage: S) -
 	Arc::new(Blockchain::new(storage))
 }
 
-/// Create an instance of light client backend.
-pub fn new_light_backend<B: BlockT, S: BlockchainStorage<B>, F: Fetcher<B>>(blockchain: Arc<Blockchain<S, F>>, fetcher: Arc<F>) -> Arc<Backend<S, F>> {
+/// Create an instance of light client backend, caching at most `read_cache_size` bytes of the
+/// remote read results. The light client is started from the trusted checkpoint, if given.
+pub fn new_light_backend<B: BlockT, S: BlockchainStorage<B>, F: Fetcher<B>>(
+	blockchain: Arc<Blockchain<S, F>>,
+	fetcher: Arc<F>,
+	read_cache_size: usize,
+	checkpoint: Option<Checkpoint<B::Header>>,
+) -> ClientResult<Arc<Backend<S, F>>> {
 	blockchain.set_fetcher(Arc::downgrade(&fetcher));
-	Arc::new(Backend::new(blockchain))
+	Ok(Arc::new(Backend::with_read_cache_size(blockchain, read_cache_size, checkpoint)?))
 }
 
 /// Create an instance of light client.
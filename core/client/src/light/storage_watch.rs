@@ -0,0 +1,148 @@
+// Copyright 2017-2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Light client storage watch. Watched storage keys are read from the remote nodes at every
+//! new best block and only the changed values are reported.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::{Future, IntoFuture, future::{self, Either}};
+use parking_lot::Mutex;
+
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT};
+
+use error::Error as ClientError;
+use light::fetcher::{Fetcher, RemoteReadRequest};
+
+/// Storage changes of the watched keys: the keys along with their new values.
+pub type StorageChanges = Vec<(Vec<u8>, Option<Vec<u8>>)>;
+
+/// Storage watch, reading the watched keys from the remote nodes.
+pub struct StorageWatch<F> {
+	fetcher: Arc<F>,
+	values: Arc<Mutex<WatchedValues>>,
+}
+
+/// Values of the watched keys, read at the most recent best block.
+#[derive(Default)]
+struct WatchedValues {
+	/// Id of the next read.
+	next_read: u64,
+	/// Id of the most recent read which values have been applied.
+	last_applied: Option<u64>,
+	values: HashMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<F> StorageWatch<F> {
+	/// Create new storage watch, reading values through the given fetcher.
+	pub fn new(fetcher: Arc<F>) -> Self {
+		StorageWatch {
+			fetcher,
+			values: Default::default(),
+		}
+	}
+
+	/// Read the watched keys at the new best block. The returned future resolves to the keys
+	/// which values differ from the values read at the previous best block.
+	///
+	/// Keys that have not been watched before are only remembered, not reported. Values of
+	/// the keys that are no longer watched are forgotten.
+	pub fn on_new_best<Block>(
+		&self,
+		header: Block::Header,
+		mut keys: Vec<Vec<u8>>,
+	) -> impl Future<Item=StorageChanges, Error=ClientError> where
+		Block: BlockT,
+		F: Fetcher<Block>,
+	{
+		keys.sort();
+		keys.dedup();
+
+		let read_id = {
+			let mut values = self.values.lock();
+			values.values.retain(|key, _| keys.binary_search(key).is_ok());
+			let read_id = values.next_read;
+			values.next_read += 1;
+			read_id
+		};
+
+		if keys.is_empty() {
+			return Either::A(future::ok(Vec::new()));
+		}
+
+		let values = self.values.clone();
+		let read = self.fetcher.remote_read(RemoteReadRequest {
+			block: header.hash(),
+			header,
+			keys: keys.clone(),
+			retry_count: None,
+		});
+		Either::B(read.into_future().map(move |read_values| values.lock().apply(read_id, keys, read_values)))
+	}
+}
+
+impl WatchedValues {
+	/// Remember the values read by the given read, returning the changed values. Reads that
+	/// complete after a more recent read has been applied are ignored.
+	fn apply(&mut self, read_id: u64, keys: Vec<Vec<u8>>, read_values: Vec<Option<Vec<u8>>>) -> StorageChanges {
+		if self.last_applied.map(|last_applied| last_applied > read_id).unwrap_or(false) {
+			return Vec::new();
+		}
+		self.last_applied = Some(read_id);
+
+		let values = &mut self.values;
+		keys.into_iter()
+			.zip(read_values)
+			.filter_map(|(key, value)| match values.insert(key.clone(), value.clone()) {
+				Some(ref old_value) if *old_value != value => Some((key, value)),
+				Some(_) | None => None,
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reports_only_changed_values() {
+		let mut values = WatchedValues::default();
+		let keys = vec![vec![1], vec![2]];
+
+		assert!(values.apply(0, keys.clone(), vec![Some(vec![10]), None]).is_empty());
+		assert!(values.apply(1, keys.clone(), vec![Some(vec![10]), None]).is_empty());
+		assert_eq!(
+			values.apply(2, keys.clone(), vec![Some(vec![11]), Some(vec![20])]),
+			vec![(vec![1], Some(vec![11])), (vec![2], Some(vec![20]))],
+		);
+		assert_eq!(
+			values.apply(3, keys.clone(), vec![Some(vec![11]), None]),
+			vec![(vec![2], None)],
+		);
+	}
+
+	#[test]
+	fn ignores_outdated_reads() {
+		let mut values = WatchedValues::default();
+		let keys = vec![vec![1]];
+
+		assert!(values.apply(0, keys.clone(), vec![Some(vec![10])]).is_empty());
+		assert_eq!(values.apply(2, keys.clone(), vec![Some(vec![12])]), vec![(vec![1], Some(vec![12]))]);
+		assert!(values.apply(1, keys.clone(), vec![Some(vec![11])]).is_empty());
+		assert_eq!(values.values.get(&vec![1]), Some(&Some(vec![12])));
+	}
+}
@@ -0,0 +1,286 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Memory budget shared by the caches of the node.
+//!
+//! Every cache keeps its own size limit and registers with the budget under some weight. The
+//! caches report their size whenever it grows. While the total size is within the budget, nothing
+//! changes. Once the budget is exceeded, it's split between the caches in proportion to their
+//! weights, where the part of the share that a cache doesn't use goes to the other caches, and
+//! the caches above their share are asked to shrink to it. The cache that has exceeded the budget
+//! learns its share from the report, while the other caches find the size they're asked to shrink
+//! to with `MemoryAccount::requested_size`, which they check whenever they're accessed. Only the
+//! sizes reported by the caches are accounted, so the caches report their size once they've
+//! shrunk.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use parking_lot::Mutex;
+
+/// Value of the shrink request meaning that the component isn't asked to shrink.
+const NO_REQUEST: usize = ::std::usize::MAX;
+
+/// Memory budget shared by the caches.
+#[derive(Clone)]
+pub struct MemoryBudget {
+	state: Arc<Mutex<BudgetState>>,
+}
+
+/// Memory used by the single component of the budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentUsage {
+	/// Name of the component.
+	pub name: &'static str,
+	/// Weight of the component.
+	pub weight: usize,
+	/// Memory used by the component, in bytes.
+	pub used: usize,
+}
+
+/// Account of the single component in the memory budget. The component is unregistered when the
+/// account is dropped.
+pub struct MemoryAccount {
+	state: Arc<Mutex<BudgetState>>,
+	index: usize,
+	request: Arc<AtomicUsize>,
+}
+
+struct BudgetState {
+	limit: usize,
+	components: Vec<Option<ComponentUsage>>,
+	/// Sizes the components are asked to shrink to, `NO_REQUEST` if they aren't.
+	requests: Vec<Arc<AtomicUsize>>,
+}
+
+impl MemoryBudget {
+	/// Create new budget of `limit` bytes.
+	pub fn new(limit: usize) -> Self {
+		MemoryBudget {
+			state: Arc::new(Mutex::new(BudgetState {
+				limit,
+				components: Vec::new(),
+				requests: Vec::new(),
+			})),
+		}
+	}
+
+	/// Total budget, in bytes.
+	pub fn limit(&self) -> usize {
+		self.state.lock().limit
+	}
+
+	/// Register the component with given weight.
+	pub fn register(&self, name: &'static str, weight: usize) -> MemoryAccount {
+		let mut state = self.state.lock();
+		let index = state.components.len();
+		let request = Arc::new(AtomicUsize::new(NO_REQUEST));
+		state.components.push(Some(ComponentUsage { name, weight, used: 0 }));
+		state.requests.push(request.clone());
+		MemoryAccount {
+			state: self.state.clone(),
+			index,
+			request,
+		}
+	}
+
+	/// Memory used by all registered components, in bytes.
+	pub fn used(&self) -> usize {
+		self.state.lock().components.iter().filter_map(|c| c.as_ref()).map(|c| c.used).sum()
+	}
+
+	/// Memory used by every registered component.
+	pub fn usage(&self) -> Vec<ComponentUsage> {
+		self.state.lock().components.iter().filter_map(|c| c.clone()).collect()
+	}
+}
+
+impl MemoryAccount {
+	/// Report the memory used by the component. Returns the size the component must shrink to
+	/// if the budget is exceeded and the component uses more than its share, in which case it
+	/// reports its size again once it has shrunk. The other components above their share are
+	/// asked to shrink to it as well.
+	pub fn report(&self, used: usize) -> Option<usize> {
+		let mut state = self.state.lock();
+		self.request.store(NO_REQUEST, Ordering::Relaxed);
+		if let Some(component) = state.components[self.index].as_mut() {
+			component.used = used;
+		}
+
+		let total: usize = state.components.iter().filter_map(|c| c.as_ref()).map(|c| c.used).sum();
+		if total <= state.limit {
+			return None;
+		}
+
+		let shares = shares(state.limit, &state.components);
+		for (index, (component, share)) in state.components.iter().zip(shares.iter()).enumerate() {
+			if let Some(ref component) = *component {
+				if component.used > *share && index != self.index {
+					state.requests[index].store(*share, Ordering::Relaxed);
+				}
+			}
+		}
+
+		let share = shares[self.index];
+		if used <= share {
+			return None;
+		}
+		Some(share)
+	}
+
+	/// Take the size the component has been asked to shrink to, when the budget has been exceeded
+	/// by the other components.
+	pub fn requested_size(&self) -> Option<usize> {
+		match self.request.swap(NO_REQUEST, Ordering::Relaxed) {
+			NO_REQUEST => None,
+			size => Some(size),
+		}
+	}
+}
+
+impl Drop for MemoryAccount {
+	fn drop(&mut self) {
+		self.state.lock().components[self.index] = None;
+	}
+}
+
+/// Split the budget between the components in proportion to their weights. Components that use
+/// less than their share keep what they use, and the rest is split between the other components.
+fn shares(limit: usize, components: &[Option<ComponentUsage>]) -> Vec<usize> {
+	let mut shares: Vec<Option<usize>> = components.iter()
+		.map(|c| match *c {
+			Some(_) => None,
+			None => Some(0),
+		})
+		.collect();
+	let mut remaining = limit;
+	loop {
+		let weights: usize = components.iter().zip(shares.iter())
+			.filter_map(|(c, share)| match (c, share) {
+				(&Some(ref c), &None) => Some(c.weight),
+				_ => None,
+			})
+			.sum();
+		let satisfied: Vec<_> = components.iter().zip(shares.iter()).enumerate()
+			.filter_map(|(index, (c, share))| match (c, share) {
+				(&Some(ref c), &None) if c.used <= weighted_share(remaining, c.weight, weights) =>
+					Some((index, c.used)),
+				_ => None,
+			})
+			.collect();
+		if satisfied.is_empty() {
+			for (c, share) in components.iter().zip(shares.iter_mut()) {
+				if let Some(ref c) = *c {
+					if share.is_none() {
+						*share = Some(weighted_share(remaining, c.weight, weights));
+					}
+				}
+			}
+			break;
+		}
+
+		for (index, used) in satisfied {
+			shares[index] = Some(used);
+			remaining -= used;
+		}
+	}
+
+	shares.into_iter().map(|share| share.unwrap_or(0)).collect()
+}
+
+fn weighted_share(remaining: usize, weight: usize, weights: usize) -> usize {
+	if weights == 0 {
+		return 0;
+	}
+	(remaining as u128 * weight as u128 / weights as u128) as usize
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn nothing_is_shrunk_within_budget() {
+		let budget = MemoryBudget::new(100);
+		let a = budget.register("a", 1);
+		let b = budget.register("b", 3);
+		assert_eq!(a.report(60), None);
+		assert_eq!(b.report(40), None);
+		assert_eq!(budget.used(), 100);
+	}
+
+	#[test]
+	fn components_are_shrunk_to_weighted_share() {
+		let budget = MemoryBudget::new(100);
+		let a = budget.register("a", 1);
+		let b = budget.register("b", 3);
+		assert_eq!(a.report(60), None);
+
+		// b is within its share of 75 => a shrinks to the rest of the budget
+		assert_eq!(b.report(70), None);
+		assert_eq!(a.report(61), Some(30));
+
+		// the usage is recorded once a has shrunk
+		assert_eq!(budget.used(), 131);
+		assert_eq!(a.report(30), None);
+		assert_eq!(budget.used(), 100);
+
+		// both are above their share => both are shrunk
+		assert_eq!(b.report(90), Some(75));
+		assert_eq!(a.requested_size(), Some(25));
+		assert_eq!(b.report(75), None);
+		assert_eq!(a.report(25), None);
+		assert_eq!(budget.usage(), vec![
+			ComponentUsage { name: "a", weight: 1, used: 25 },
+			ComponentUsage { name: "b", weight: 3, used: 75 },
+		]);
+	}
+
+	#[test]
+	fn other_components_are_asked_to_shrink() {
+		let budget = MemoryBudget::new(100);
+		let a = budget.register("a", 1);
+		let b = budget.register("b", 1);
+		assert_eq!(a.report(70), None);
+		assert_eq!(a.requested_size(), None);
+
+		// b exceeds the budget while being within its share => a is asked to shrink
+		assert_eq!(b.report(40), None);
+		assert_eq!(b.requested_size(), None);
+		assert_eq!(a.requested_size(), Some(60));
+		assert_eq!(a.requested_size(), None);
+		assert_eq!(budget.used(), 110);
+		assert_eq!(a.report(60), None);
+		assert_eq!(budget.used(), 100);
+
+		// the request is dropped once the component reports its usage
+		assert_eq!(b.report(60), Some(50));
+		assert_eq!(a.report(50), None);
+		assert_eq!(a.requested_size(), None);
+	}
+
+	#[test]
+	fn dropped_component_releases_its_share() {
+		let budget = MemoryBudget::new(100);
+		let a = budget.register("a", 1);
+		let b = budget.register("b", 1);
+		assert_eq!(a.report(50), None);
+		assert_eq!(b.report(60), Some(50));
+
+		drop(a);
+		assert_eq!(b.report(100), None);
+		assert_eq!(budget.usage().len(), 1);
+	}
+}
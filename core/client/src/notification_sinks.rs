@@ -0,0 +1,182 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bounded channels of the block import and finality notifications.
+
+use futures::{Poll, Stream};
+use futures::sync::mpsc;
+
+/// Maximal number of notifications buffered for a single consumer. Notifications sent while
+/// the buffer of the consumer is full are dropped.
+pub const NOTIFICATIONS_BUFFER_SIZE: usize = 1024;
+
+/// Item of the notification stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification<T> {
+	/// The notification.
+	Item(T),
+	/// The given number of notifications have been dropped, because the consumer has been
+	/// lagging. The consumer should resync with the current state of the chain, since the
+	/// following notifications don't cover the dropped ones.
+	Lagged(usize),
+}
+
+impl<T> Notification<T> {
+	/// Get the notification, `None` if the notifications have been dropped.
+	pub fn item(self) -> Option<T> {
+		match self {
+			Notification::Item(item) => Some(item),
+			Notification::Lagged(_) => None,
+		}
+	}
+}
+
+/// Stream of notifications. Notifications that haven't fit into the buffer of the lagging
+/// consumer are dropped, and the consumer is informed with the `Lagged` item once the buffer
+/// has room again.
+pub struct NotificationStream<T> {
+	receiver: mpsc::Receiver<Notification<T>>,
+}
+
+impl<T> Stream for NotificationStream<T> {
+	type Item = Notification<T>;
+	type Error = ();
+
+	fn poll(&mut self) -> Poll<Option<Notification<T>>, ()> {
+		self.receiver.poll()
+	}
+}
+
+struct Sink<T> {
+	label: &'static str,
+	sender: mpsc::Sender<Notification<T>>,
+	/// Number of notifications dropped since the consumer has started lagging.
+	missed: usize,
+}
+
+/// Senders of the notifications to all registered consumers.
+pub(crate) struct NotificationSinks<T> {
+	buffer_size: usize,
+	sinks: Vec<Sink<T>>,
+}
+
+impl<T: Clone> Default for NotificationSinks<T> {
+	fn default() -> Self {
+		NotificationSinks::with_buffer_size(NOTIFICATIONS_BUFFER_SIZE)
+	}
+}
+
+impl<T: Clone> NotificationSinks<T> {
+	/// Create sinks buffering at most `buffer_size` notifications for every consumer.
+	pub fn with_buffer_size(buffer_size: usize) -> Self {
+		NotificationSinks {
+			buffer_size,
+			sinks: Vec::new(),
+		}
+	}
+
+	/// Register new consumer. The label identifies the consumer in logs and telemetry.
+	pub fn subscribe(&mut self, label: &'static str) -> NotificationStream<T> {
+		let (sender, receiver) = mpsc::channel(self.buffer_size);
+		self.sinks.push(Sink {
+			label,
+			sender,
+			missed: 0,
+		});
+
+		NotificationStream {
+			receiver,
+		}
+	}
+
+	/// Send the notification to all consumers. Consumers that have dropped their streams
+	/// are unregistered. The lagging consumer is sent the `Lagged` item instead of the first
+	/// notification that fits into its buffer, so the notification is counted as dropped.
+	pub fn notify(&mut self, notification: &T) {
+		let sinks = ::std::mem::replace(&mut self.sinks, Vec::new());
+		self.sinks = sinks.into_iter().filter_map(|mut sink| {
+			let item = match sink.missed {
+				0 => Notification::Item(notification.clone()),
+				missed => Notification::Lagged(missed + 1),
+			};
+			match sink.sender.try_send(item) {
+				Ok(()) => {
+					if sink.missed != 0 {
+						debug!(target: "client", "Notifications consumer {} has caught up after missing {} notifications",
+							sink.label, sink.missed + 1);
+						telemetry!("client.notifications.recovered"; "sink" => sink.label, "missed" => sink.missed + 1);
+						sink.missed = 0;
+					}
+					Some(sink)
+				},
+				Err(ref e) if e.is_full() => {
+					if sink.missed == 0 {
+						warn!(target: "client", "Notifications consumer {} is lagging, dropping notifications", sink.label);
+						telemetry!("client.notifications.lagging"; "sink" => sink.label);
+					}
+					sink.missed += 1;
+					Some(sink)
+				},
+				Err(_) => None,
+			}
+		}).collect();
+	}
+
+	/// Get the number of registered consumers.
+	#[cfg(test)]
+	pub fn len(&self) -> usize {
+		self.sinks.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::Async;
+	use super::*;
+
+	#[test]
+	fn lagging_consumer_is_informed_of_dropped_notifications() {
+		let mut sinks = NotificationSinks::with_buffer_size(1);
+		let mut stream = sinks.subscribe("test");
+
+		// the capacity of the channel is the buffer size + one slot for the sender
+		for notification in 1..5 {
+			sinks.notify(&notification);
+		}
+		assert_eq!(stream.poll(), Ok(Async::Ready(Some(Notification::Item(1)))));
+		assert_eq!(stream.poll(), Ok(Async::Ready(Some(Notification::Item(2)))));
+
+		// the notification that fits into the buffer is replaced with the number of dropped ones
+		sinks.notify(&5);
+		sinks.notify(&6);
+		drop(sinks);
+		assert_eq!(stream.wait().collect::<Result<Vec<_>, _>>(), Ok(vec![
+			Notification::Lagged(3),
+			Notification::Item(6),
+		]));
+	}
+
+	#[test]
+	fn closed_consumers_are_unregistered() {
+		let mut sinks = NotificationSinks::default();
+		let stream1 = sinks.subscribe("test1");
+		let _stream2 = sinks.subscribe("test2");
+		drop(stream1);
+
+		sinks.notify(&1);
+		assert_eq!(sinks.len(), 1);
+	}
+}
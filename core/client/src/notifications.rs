@@ -151,6 +151,11 @@ impl<Block: BlockT> StorageNotifications<Block> {
 		}
 	}
 
+	/// Get keys that have at least one listener. Wildcard listeners are not taken into account.
+	pub fn listened_keys(&self) -> Vec<StorageKey> {
+		self.listeners.keys().cloned().collect()
+	}
+
 	/// Start listening for particular storage keys.
 	pub fn listen(&mut self, filter_keys: Option<&[StorageKey]>) -> StorageEventStream<Block::Hash> {
 		self.next_id += 1;
@@ -245,6 +250,14 @@ mod tests {
 		].into())));
 	}
 
+	#[test]
+	fn listened_keys_exclude_wildcard_listeners() {
+		let mut notifications = StorageNotifications::<Block>::default();
+		let _recv1 = notifications.listen(Some(&[StorageKey(vec![1])])).wait();
+		let _recv2 = notifications.listen(None).wait();
+		assert_eq!(notifications.listened_keys(), vec![StorageKey(vec![1])]);
+	}
+
 	#[test]
 	fn should_cleanup_subscribers_if_dropped() {
 		// given
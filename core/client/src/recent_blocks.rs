@@ -0,0 +1,103 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Hashes of the recent blocks provided to the runtime through `ext_recent_block_hash`.
+//!
+//! The hashes only depend on the ancestors of the executed block, so every node executing
+//! the block provides the same values to the runtime, whether it authors the block or imports it.
+//! The node that lacks any of these ancestors, e.g. because it has synced the chain starting from
+//! a later block, can't execute the block until it imports them, instead of providing fewer
+//! hashes than the other nodes.
+
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Zero};
+
+use blockchain::HeaderBackend;
+use error;
+
+/// Number of the most recent ancestors whose hashes are provided to the runtime.
+pub const RECENT_BLOCKS: usize = 64;
+
+/// Hashes of the given parent and its `RECENT_BLOCKS - 1` ancestors (or all the ancestors, if
+/// there are fewer), starting from the parent. Fails with `MissingAncestor` if the header of any
+/// of them isn't in the database.
+pub fn recent_block_hashes<Block, B>(blockchain: &B, parent_hash: Block::Hash) -> error::Result<Vec<Vec<u8>>> where
+	Block: BlockT,
+	B: HeaderBackend<Block>,
+{
+	let mut hashes = Vec::with_capacity(RECENT_BLOCKS);
+	let mut hash = parent_hash;
+	while hashes.len() < RECENT_BLOCKS {
+		hashes.push(hash.as_ref().to_vec());
+
+		let header = blockchain.header(BlockId::Hash(hash))?
+			.ok_or_else(|| error::ErrorKind::MissingAncestor(format!("{}", hash)))?;
+		if header.number().is_zero() {
+			break;
+		}
+		hash = *header.parent_hash();
+	}
+
+	Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use backend::NewBlockState;
+	use consensus::BlockOrigin;
+	use in_mem;
+	use test_client::{self, TestClient};
+	use test_client::runtime::{Block, Digest, Header};
+
+	#[test]
+	fn recent_block_hashes_start_from_parent() {
+		let client = test_client::new();
+		let genesis_hash = client.info().unwrap().chain.genesis_hash;
+		let mut hashes = vec![genesis_hash.as_ref().to_vec()];
+		for _ in 0..RECENT_BLOCKS + 2 {
+			let block = client.new_block().unwrap().bake().unwrap();
+			client.justify_and_import(BlockOrigin::Own, block.clone()).unwrap();
+			hashes.insert(0, block.hash().as_ref().to_vec());
+		}
+
+		let blockchain = client.backend().blockchain();
+		assert_eq!(recent_block_hashes(blockchain, genesis_hash).unwrap(), vec![genesis_hash.as_ref().to_vec()]);
+
+		let best_hash = client.info().unwrap().chain.best_hash;
+		hashes.truncate(RECENT_BLOCKS);
+		assert_eq!(recent_block_hashes(blockchain, best_hash).unwrap(), hashes);
+	}
+
+	#[test]
+	fn recent_block_hashes_are_not_provided_if_ancestor_is_missing() {
+		let blockchain = in_mem::Blockchain::<Block>::new();
+		let header = Header {
+			parent_hash: [1u8; 32].into(),
+			number: 5,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Digest { logs: vec![] },
+		};
+		let hash = header.hash();
+		blockchain.insert(hash, header, None, None, NewBlockState::Best).unwrap();
+
+		match recent_block_hashes(&blockchain, hash) {
+			Err(error::Error(error::ErrorKind::MissingAncestor(_), _)) => (),
+			result => panic!("Unexpected result: {:?}", result),
+		}
+	}
+}
@@ -0,0 +1,93 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Difference between the storage states of two blocks.
+
+use std::collections::BTreeMap;
+use primitives::storage::StorageKey;
+
+/// Storage keys that differ between the states of two blocks. Keys are sorted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StateDiff {
+	/// Keys that have no value at the first block and have a value at the second block.
+	pub added: Vec<StorageKey>,
+	/// Keys that have different values at both blocks.
+	pub changed: Vec<StorageKey>,
+	/// Keys that have a value at the first block and have no value at the second block.
+	pub removed: Vec<StorageKey>,
+}
+
+impl StateDiff {
+	/// Build the diff from the values of the keys at both blocks. Keys with equal values are
+	/// skipped.
+	pub fn from_values<I>(values: I) -> Self where
+		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>)>,
+	{
+		let mut diff = StateDiff::default();
+		for (key, from_value, to_value) in values {
+			match (from_value, to_value) {
+				(None, Some(_)) => diff.added.push(StorageKey(key)),
+				(Some(_), None) => diff.removed.push(StorageKey(key)),
+				(Some(from_value), Some(to_value)) => if from_value != to_value {
+					diff.changed.push(StorageKey(key));
+				},
+				(None, None) => (),
+			}
+		}
+
+		diff.added.sort();
+		diff.changed.sort();
+		diff.removed.sort();
+		diff
+	}
+
+	/// Build the diff from all key-value pairs of both states.
+	pub fn from_pairs(from: Vec<(Vec<u8>, Vec<u8>)>, to: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+		let mut values: BTreeMap<Vec<u8>, (Option<Vec<u8>>, Option<Vec<u8>>)> = BTreeMap::new();
+		for (key, value) in from {
+			values.entry(key).or_default().0 = Some(value);
+		}
+		for (key, value) in to {
+			values.entry(key).or_default().1 = Some(value);
+		}
+
+		Self::from_values(values.into_iter().map(|(key, (from_value, to_value))| (key, from_value, to_value)))
+	}
+
+	/// Returns true if the states are equal.
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn state_diff_from_pairs_works() {
+		let diff = StateDiff::from_pairs(
+			vec![(vec![1], vec![1]), (vec![2], vec![2]), (vec![3], vec![3])],
+			vec![(vec![4], vec![4]), (vec![2], vec![20]), (vec![3], vec![3])],
+		);
+		assert_eq!(diff, StateDiff {
+			added: vec![StorageKey(vec![4])],
+			changed: vec![StorageKey(vec![2])],
+			removed: vec![StorageKey(vec![1])],
+		});
+		assert!(StateDiff::from_pairs(vec![(vec![1], vec![1])], vec![(vec![1], vec![1])]).is_empty());
+	}
+}
@@ -0,0 +1,419 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage queries by module and item names.
+//!
+//! Storage keys are built the same way `decl_storage!` does, from the module prefix and
+//! the item name found in the runtime metadata. Values are decoded into dynamically typed
+//! `Value`s, using the type names of the metadata resolved by a `TypeRegistry`.
+
+use std::collections::HashMap;
+
+use codec::{Compact, Decode, Input};
+use metadata::{DecodeDifferent, RuntimeMetadata, StorageFunctionModifier, StorageFunctionType};
+use primitives::hashing::twox_128;
+use primitives::hexdisplay::HexDisplay;
+use primitives::storage::StorageKey;
+use serde::{Serialize, Serializer};
+use error;
+
+/// Dynamically typed storage value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+	/// A boolean.
+	Bool(bool),
+	/// An unsigned integer of any width.
+	Unsigned(u128),
+	/// A signed integer of any width.
+	Signed(i128),
+	/// A byte array or a vector of bytes.
+	Bytes(Vec<u8>),
+	/// A vector or an array of values.
+	Sequence(Vec<Value>),
+	/// A tuple of values.
+	Tuple(Vec<Value>),
+	/// An optional value.
+	Option(Option<Box<Value>>),
+	/// Encoded value of a type which is not known to the registry.
+	Raw(Vec<u8>),
+}
+
+impl Serialize for Value {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match *self {
+			Value::Bool(v) => serializer.serialize_bool(v),
+			Value::Unsigned(v) if v <= u64::max_value() as u128 => serializer.serialize_u64(v as u64),
+			Value::Unsigned(v) => serializer.serialize_str(&v.to_string()),
+			Value::Signed(v) if v >= i64::min_value() as i128 && v <= i64::max_value() as i128 =>
+				serializer.serialize_i64(v as i64),
+			Value::Signed(v) => serializer.serialize_str(&v.to_string()),
+			Value::Bytes(ref v) | Value::Raw(ref v) =>
+				serializer.serialize_str(&format!("0x{}", HexDisplay::from(v))),
+			Value::Sequence(ref v) | Value::Tuple(ref v) => v.serialize(serializer),
+			Value::Option(ref v) => v.serialize(serializer),
+		}
+	}
+}
+
+/// Parsed type name of the metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Type {
+	Named(String),
+	Vec(Box<Type>),
+	Option(Box<Type>),
+	Compact(Box<Type>),
+	Tuple(Vec<Type>),
+	Array(Box<Type>, usize),
+}
+
+impl Type {
+	fn parse(name: &str) -> error::Result<Type> {
+		let name: String = name.chars().filter(|c| !c.is_whitespace()).collect();
+		Self::parse_normalized(&name)
+	}
+
+	fn parse_normalized(name: &str) -> error::Result<Type> {
+		let invalid = || error::Error::from(error::ErrorKind::InvalidStorageQuery(format!("Unsupported type {}", name)));
+		if name.starts_with('(') && name.ends_with(')') {
+			let inner = &name[1..name.len() - 1];
+			return Ok(Type::Tuple(split_top_level(inner, ',').into_iter()
+				.filter(|part| !part.is_empty())
+				.map(Self::parse_normalized)
+				.collect::<error::Result<_>>()?));
+		}
+		if name.starts_with('[') && name.ends_with(']') {
+			let parts = split_top_level(&name[1..name.len() - 1], ';');
+			if parts.len() != 2 {
+				return Err(invalid());
+			}
+			let len = parts[1].parse().map_err(|_| invalid())?;
+			return Ok(Type::Array(Box::new(Self::parse_normalized(parts[0])?), len));
+		}
+		if let Some(start) = name.find('<') {
+			if !name.ends_with('>') {
+				return Err(invalid());
+			}
+			let args = split_top_level(&name[start + 1..name.len() - 1], ',');
+			let wrapper: fn(Box<Type>) -> Type = match &name[..start] {
+				"Vec" => Type::Vec,
+				"Option" => Type::Option,
+				"Compact" => Type::Compact,
+				_ => return Ok(Type::Named(strip_path(name).into())),
+			};
+			if args.len() != 1 {
+				return Err(invalid());
+			}
+			return Ok(wrapper(Box::new(Self::parse_normalized(args[0])?)));
+		}
+		Ok(Type::Named(strip_path(name).into()))
+	}
+}
+
+/// Split at the separator, ignoring separators nested in brackets.
+fn split_top_level(s: &str, separator: char) -> Vec<&str> {
+	let mut parts = Vec::new();
+	let mut depth = 0;
+	let mut start = 0;
+	for (i, c) in s.char_indices() {
+		match c {
+			'<' | '(' | '[' => depth += 1,
+			'>' | ')' | ']' => depth -= 1,
+			c if c == separator && depth == 0 => {
+				parts.push(&s[start..i]);
+				start = i + 1;
+			},
+			_ => (),
+		}
+	}
+	parts.push(&s[start..]);
+	parts
+}
+
+/// Strip the path (e.g. `T::`) of a type name without generic arguments.
+fn strip_path(name: &str) -> &str {
+	if name.contains('<') {
+		return name;
+	}
+	name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Resolves type names of the runtime metadata into types the values may be decoded as.
+///
+/// Type names that are not builtin are looked up among the aliases, e.g. `AccountId` may be
+/// aliased to `[u8; 32]`. Values of types that can't be resolved are returned encoded.
+#[derive(Debug, Clone)]
+pub struct TypeRegistry {
+	aliases: HashMap<String, String>,
+}
+
+impl Default for TypeRegistry {
+	/// Registry with aliases of the types common to substrate runtimes.
+	fn default() -> Self {
+		TypeRegistry::new()
+			.with_alias("Hash", "[u8; 32]")
+			.with_alias("H256", "[u8; 32]")
+			.with_alias("AccountId", "[u8; 32]")
+			.with_alias("AuthorityId", "[u8; 32]")
+			.with_alias("SessionKey", "[u8; 32]")
+			.with_alias("BlockNumber", "u64")
+			.with_alias("Index", "u64")
+			.with_alias("Moment", "u64")
+			.with_alias("Balance", "u128")
+	}
+}
+
+impl TypeRegistry {
+	/// Create a registry knowing builtin types only.
+	pub fn new() -> Self {
+		TypeRegistry { aliases: HashMap::new() }
+	}
+
+	/// Register `name` as an alias of type `ty`.
+	pub fn with_alias(mut self, name: &str, ty: &str) -> Self {
+		self.aliases.insert(name.into(), ty.into());
+		self
+	}
+
+	/// Decode the value of the given type. Fails if the data does not match the type.
+	pub fn decode(&self, ty: &str, data: &[u8]) -> error::Result<Value> {
+		let ty = Type::parse(ty)?;
+		let mut input = data;
+		let value = match self.decode_type(&ty, &mut input, 0)? {
+			Some(value) => value,
+			None => return Ok(Value::Raw(data.to_vec())),
+		};
+		if !input.is_empty() {
+			return Err(error::ErrorKind::InvalidStorageQuery("Trailing data after the decoded value".into()).into());
+		}
+		Ok(value)
+	}
+
+	/// Decode the value of the given type from input. Returns `None` if the type is unknown.
+	fn decode_type(&self, ty: &Type, input: &mut &[u8], depth: usize) -> error::Result<Option<Value>> {
+		// aliases may refer to each other
+		if depth > 16 {
+			return Ok(None);
+		}
+
+		let invalid = || error::Error::from(error::ErrorKind::InvalidStorageQuery(format!("Value does not match type {:?}", ty)));
+		let value = match *ty {
+			Type::Named(ref name) => match name.as_str() {
+				"bool" => Value::Bool(bool::decode(input).ok_or_else(invalid)?),
+				"u8" => Value::Unsigned(u8::decode(input).ok_or_else(invalid)? as u128),
+				"u16" => Value::Unsigned(u16::decode(input).ok_or_else(invalid)? as u128),
+				"u32" => Value::Unsigned(u32::decode(input).ok_or_else(invalid)? as u128),
+				"u64" => Value::Unsigned(u64::decode(input).ok_or_else(invalid)? as u128),
+				"u128" => Value::Unsigned(u128::decode(input).ok_or_else(invalid)?),
+				"i8" => Value::Signed(u8::decode(input).ok_or_else(invalid)? as i8 as i128),
+				"i16" => Value::Signed(u16::decode(input).ok_or_else(invalid)? as i16 as i128),
+				"i32" => Value::Signed(u32::decode(input).ok_or_else(invalid)? as i32 as i128),
+				"i64" => Value::Signed(u64::decode(input).ok_or_else(invalid)? as i64 as i128),
+				"i128" => Value::Signed(u128::decode(input).ok_or_else(invalid)? as i128),
+				name => match self.aliases.get(name) {
+					Some(alias) => return self.decode_type(&Type::parse(alias)?, input, depth + 1),
+					None => return Ok(None),
+				},
+			},
+			Type::Compact(_) => Value::Unsigned(<Compact<u128>>::decode(input).ok_or_else(invalid)?.0),
+			Type::Vec(ref item) if **item == Type::Named("u8".into()) =>
+				Value::Bytes(<Vec<u8>>::decode(input).ok_or_else(invalid)?),
+			Type::Vec(ref item) => {
+				// the length prefix is encoded the same way for all vectors
+				let len = <Vec<()>>::decode(input).ok_or_else(invalid)?.len();
+				match self.decode_sequence(item, len, input, depth)? {
+					Some(items) => Value::Sequence(items),
+					None => return Ok(None),
+				}
+			},
+			Type::Array(ref item, len) if **item == Type::Named("u8".into()) => {
+				let mut bytes = vec![0u8; len];
+				if input.read(&mut bytes) != len {
+					return Err(invalid());
+				}
+				Value::Bytes(bytes)
+			},
+			Type::Array(ref item, len) => match self.decode_sequence(item, len, input, depth)? {
+				Some(items) => Value::Sequence(items),
+				None => return Ok(None),
+			},
+			Type::Option(ref item) => match input.read_byte().ok_or_else(invalid)? {
+				0 => Value::Option(None),
+				1 => match self.decode_type(item, input, depth)? {
+					Some(value) => Value::Option(Some(Box::new(value))),
+					None => return Ok(None),
+				},
+				_ => return Err(invalid()),
+			},
+			Type::Tuple(ref items) => {
+				let mut values = Vec::with_capacity(items.len());
+				for item in items {
+					match self.decode_type(item, input, depth)? {
+						Some(value) => values.push(value),
+						None => return Ok(None),
+					}
+				}
+				Value::Tuple(values)
+			},
+		};
+		Ok(Some(value))
+	}
+
+	fn decode_sequence(&self, item: &Type, len: usize, input: &mut &[u8], depth: usize) -> error::Result<Option<Vec<Value>>> {
+		let mut items = Vec::with_capacity(::std::cmp::min(len, input.len()));
+		for _ in 0..len {
+			match self.decode_type(item, input, depth)? {
+				Some(value) => items.push(value),
+				None => return Ok(None),
+			}
+		}
+		Ok(Some(items))
+	}
+}
+
+/// Storage item of the runtime, as described by the metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageItem {
+	/// Storage prefix of the module.
+	pub prefix: String,
+	/// Name of the item.
+	pub name: String,
+	/// Type name of the map key, if the item is a map.
+	pub key_type: Option<String>,
+	/// Type name of the value.
+	pub value_type: String,
+	/// Whether a default value is returned when nothing is stored.
+	pub has_default: bool,
+}
+
+impl StorageItem {
+	/// Find the item by the module and item names in the metadata. The module is matched
+	/// against both the module name and its storage prefix.
+	pub fn find(metadata: &RuntimeMetadata, module: &str, item: &str) -> error::Result<Self> {
+		let not_found = || error::Error::from(error::ErrorKind::InvalidStorageQuery(format!("Unknown storage item {}::{}", module, item)));
+		for module_metadata in decoded_slice(&metadata.modules) {
+			let storage = match module_metadata.storage {
+				Some(DecodeDifferent::Decoded(ref storage)) => storage,
+				_ => continue,
+			};
+			let prefix = decoded_str(&storage.prefix);
+			if decoded_str(&module_metadata.prefix) != module && prefix != module {
+				continue;
+			}
+
+			if let Some(function) = decoded_slice(&storage.functions).iter().find(|f| decoded_str(&f.name) == item) {
+				let (key_type, value_type) = match function.ty {
+					StorageFunctionType::Plain(ref value) => (None, decoded_str(value).to_owned()),
+					StorageFunctionType::Map { ref key, ref value } =>
+						(Some(decoded_str(key).to_owned()), decoded_str(value).to_owned()),
+				};
+				return Ok(StorageItem {
+					prefix: prefix.to_owned(),
+					name: item.to_owned(),
+					key_type,
+					value_type,
+					has_default: function.modifier == StorageFunctionModifier::Default,
+				});
+			}
+		}
+
+		Err(not_found())
+	}
+
+	/// Storage key of the item. `map_key` is the encoded key of a map item and must be given
+	/// iff the item is a map.
+	pub fn key(&self, map_key: Option<&[u8]>) -> error::Result<StorageKey> {
+		let mut key = format!("{} {}", self.prefix, self.name).into_bytes();
+		match (self.key_type.is_some(), map_key) {
+			(true, Some(map_key)) => key.extend_from_slice(map_key),
+			(false, None) => (),
+			(true, None) => return Err(error::ErrorKind::InvalidStorageQuery(
+				format!("Storage item {} is a map and requires a key", self.name)).into()),
+			(false, Some(_)) => return Err(error::ErrorKind::InvalidStorageQuery(
+				format!("Storage item {} is not a map", self.name)).into()),
+		}
+		Ok(StorageKey(twox_128(&key).to_vec()))
+	}
+
+	/// Decode the stored value of the item.
+	pub fn decode(&self, registry: &TypeRegistry, data: &[u8]) -> error::Result<Value> {
+		registry.decode(&self.value_type, data)
+	}
+}
+
+fn decoded_str(s: &DecodeDifferent<&'static str, String>) -> &str {
+	match *s {
+		DecodeDifferent::Encode(s) => s,
+		DecodeDifferent::Decoded(ref s) => s,
+	}
+}
+
+fn decoded_slice<T>(s: &DecodeDifferent<&'static [T], Vec<T>>) -> &[T] {
+	match *s {
+		DecodeDifferent::Encode(s) => s,
+		DecodeDifferent::Decoded(ref s) => s,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+
+	#[test]
+	fn parses_type_names() {
+		assert_eq!(Type::parse("T::AccountId").unwrap(), Type::Named("AccountId".into()));
+		assert_eq!(Type::parse("Vec < ( T :: BlockNumber , [ u8 ; 4 ] ) >").unwrap(), Type::Vec(Box::new(Type::Tuple(vec![
+			Type::Named("BlockNumber".into()),
+			Type::Array(Box::new(Type::Named("u8".into())), 4),
+		]))));
+		assert_eq!(Type::parse("Option<EventRecord<T::Event>>").unwrap(),
+			Type::Option(Box::new(Type::Named("EventRecord<T::Event>".into()))));
+	}
+
+	#[test]
+	fn decodes_values_of_known_types() {
+		let registry = TypeRegistry::default();
+		let data = vec![(5u64, [1u8; 32]), (6u64, [2u8; 32])].encode();
+		assert_eq!(registry.decode("Vec<(T::BlockNumber, T::AccountId)>", &data).unwrap(), Value::Sequence(vec![
+			Value::Tuple(vec![Value::Unsigned(5), Value::Bytes(vec![1u8; 32])]),
+			Value::Tuple(vec![Value::Unsigned(6), Value::Bytes(vec![2u8; 32])]),
+		]));
+		assert_eq!(registry.decode("Option<T::Balance>", &Some(7u128).encode()).unwrap(),
+			Value::Option(Some(Box::new(Value::Unsigned(7)))));
+		assert!(registry.decode("u32", &5u64.encode()).is_err());
+	}
+
+	#[test]
+	fn returns_values_of_unknown_types_encoded() {
+		let registry = TypeRegistry::default();
+		assert_eq!(registry.decode("Vec<T::Proposal>", &[1, 2, 3]).unwrap(), Value::Raw(vec![1, 2, 3]));
+	}
+
+	#[test]
+	fn builds_storage_keys() {
+		let item = StorageItem {
+			prefix: "Balances".into(),
+			name: "FreeBalance".into(),
+			key_type: Some("T::AccountId".into()),
+			value_type: "T::Balance".into(),
+			has_default: true,
+		};
+		let mut expected = b"Balances FreeBalance".to_vec();
+		expected.extend_from_slice(&[1u8; 32]);
+		assert_eq!(item.key(Some(&[1u8; 32])).unwrap(), StorageKey(twox_128(&expected).to_vec()));
+		assert!(item.key(None).is_err());
+	}
+}
@@ -53,6 +53,8 @@ extern crate log;
 
 extern crate futures;
 
+mod slot_clock;
+
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -62,13 +64,14 @@ use client::ChainHead;
 use consensus_common::{ImportBlock, BlockOrigin};
 use runtime_primitives::{generic, generic::BlockId};
 use runtime_primitives::traits::{Block, Header, Digest, DigestItemFor};
-use network::import_queue::{Verifier, BasicQueue};
+use network::import_queue::{Verifier, VerificationError, BasicQueue};
 use primitives::{AuthorityId, ed25519};
 
 use futures::{Stream, Future, IntoFuture, future::{self, Either}};
 use tokio::timer::Interval;
 
-pub use consensus_common::SyncOracle;
+pub use consensus_common::{SyncOracle, TimeOracle};
+pub use slot_clock::{SlotClock, SystemSlotClock, DriftCheck, check_drift};
 
 /// A handle to the network. This is generally implemented by providing some
 /// handle to a gossip service or similar.
@@ -88,7 +91,10 @@ pub struct Config {
 	/// The local authority keypair. Can be none if this is just an observer.
 	pub local_key: Option<Arc<ed25519::Pair>>,
 	/// The slot duration in seconds.
-	pub slot_duration: u64
+	pub slot_duration: u64,
+	/// Number of seconds the slot of an imported block may start after the local time. Blocks
+	/// further in the future are held by the import queue until their slot comes.
+	pub max_future_drift: u64,
 }
 
 /// Get slot author for given block along with authorities.
@@ -111,15 +117,10 @@ fn duration_now() -> Option<Duration> {
 
 	let now = SystemTime::now();
 	now.duration_since(SystemTime::UNIX_EPOCH).map_err(|e| {
-			warn!("Current time {:?} is before unix epoch. Something is wrong: {:?}", now, e);
+			warn!(target: "aura", "Current time {:?} is before unix epoch. Something is wrong: {:?}", now, e);
 	}).ok()
 }
 
-/// Get the slot for now.
-fn slot_now(slot_duration: u64) -> Option<u64> {
-	duration_now().map(|s| s.as_secs() / slot_duration)
-}
-
 /// A digest item which is usable with aura consensus.
 pub trait CompatibleDigestItem: Sized {
 	/// Construct a digest item which is a slot number and a signature on the
@@ -161,11 +162,12 @@ impl CompatibleDigestItem for generic::DigestItem<primitives::H256, primitives::
 }
 
 /// Start the aura worker. This should be run in a tokio runtime.
-pub fn start_aura<B, C, E, SO, Error>(
+pub fn start_aura<B, C, E, SO, SC, Error>(
 	config: Config,
 	client: Arc<C>,
 	env: Arc<E>,
 	sync_oracle: SO,
+	slot_clock: SC,
 )
 	-> impl Future<Item=(),Error=()> where
 	B: Block,
@@ -173,6 +175,7 @@ pub fn start_aura<B, C, E, SO, Error>(
 	E: Environment<B, Error=Error>,
 	E::Proposer: Proposer<B, Error=Error>,
 	SO: SyncOracle + Send + Clone,
+	SC: SlotClock + Send + Clone,
 	DigestItemFor<B>: CompatibleDigestItem,
 	Error: ::std::error::Error + Send + 'static + From<::consensus_common::Error>,
 {
@@ -181,15 +184,14 @@ pub fn start_aura<B, C, E, SO, Error>(
 		let client = client.clone();
 		let env = env.clone();
 		let sync_oracle = sync_oracle.clone();
+		let slot_clock = slot_clock.clone();
 
 		let local_keys = config.local_key.map(|pair| (pair.public(), pair));
 		let slot_duration = config.slot_duration;
 		let mut last_authored_slot = 0;
-		let next_slot_start = duration_now().map(|now| {
-			let remaining_full_secs = slot_duration - (now.as_secs() % slot_duration) - 1;
-			let remaining_nanos = 1_000_000_000 - now.subsec_nanos();
-			Instant::now() + Duration::new(remaining_full_secs, remaining_nanos)
-		}).unwrap_or_else(|| Instant::now());
+		let next_slot_start = slot_clock.time_until_next_slot(slot_duration)
+			.map(|remaining| Instant::now() + remaining)
+			.unwrap_or_else(|| Instant::now());
 
 		Interval::new(next_slot_start, Duration::from_secs(slot_duration))
 			.filter(move |_| !sync_oracle.is_major_syncing()) // only propose when we are not syncing.
@@ -198,7 +200,7 @@ pub fn start_aura<B, C, E, SO, Error>(
 			.for_each(move |(public_key, key)| {
 				use futures::future;
 
-				let slot_num = match slot_now(slot_duration) {
+				let slot_num = match slot_clock.slot_now(slot_duration) {
 					Some(n) => n,
 					None => return Either::B(future::err(())),
 				};
@@ -206,6 +208,17 @@ pub fn start_aura<B, C, E, SO, Error>(
 				if last_authored_slot >= slot_num { return Either::B(future::ok(())) }
 				last_authored_slot = slot_num;
 
+				match check_drift(&slot_clock, slot_duration) {
+					DriftCheck::Ok => {},
+					DriftCheck::Warning(drift) => {
+						warn!(target: "aura", "Local clock differs from the network time by {}ms", drift);
+					},
+					DriftCheck::TooFar(drift) => {
+						warn!(target: "aura", "Local clock differs from the network time by {}ms. Refusing to author block in slot {}", drift, slot_num);
+						return Either::B(future::ok(()))
+					},
+				}
+
 				let chain_head = match client.best_block_header() {
 					Ok(x) => x,
 					Err(e) => {
@@ -217,7 +230,7 @@ pub fn start_aura<B, C, E, SO, Error>(
 				let authorities = match client.authorities(&BlockId::Hash(chain_head.hash())){
 					Ok(authorities) => authorities,
 					Err(e) => {
-						warn!("Unable to fetch authorities at block {:?}: {:?}", chain_head.hash(), e);
+						warn!(target: "aura", "Unable to fetch authorities at block {:?}: {:?}", chain_head.hash(), e);
 						return Either::B(future::ok(()));
 					}
 				};
@@ -229,7 +242,7 @@ pub fn start_aura<B, C, E, SO, Error>(
 						let proposer = match env.init(&chain_head, &authorities, key.clone()) {
 							Ok(p) => p,
 							Err(e) => {
-								warn!("Unable to author block in slot {:?}: {:?}", slot_num, e);
+								warn!(target: "aura", "Unable to author block in slot {:?}: {:?}", slot_num, e);
 								return Either::B(future::ok(()))
 							}
 						};
@@ -266,7 +279,7 @@ pub fn start_aura<B, C, E, SO, Error>(
 							warn!(target: "aura", "Error with block built on {:?}: {:?}", parent_hash, e);
 						}
 					})
-					.map_err(|e| warn!("Failed to construct block: {:?}", e))
+					.map_err(|e| warn!(target: "aura", "Failed to construct block: {:?}", e))
 				)
 			})
 	};
@@ -276,13 +289,13 @@ pub fn start_aura<B, C, E, SO, Error>(
 		authorship_task.catch_unwind().then(|res| {
 			match res {
 				Ok(Ok(())) => (),
-				Ok(Err(())) => warn!("Aura authorship task terminated unexpectedly. Restarting"),
+				Ok(Err(())) => warn!(target: "aura", "Aura authorship task terminated unexpectedly. Restarting"),
 				Err(e) => {
 					if let Some(s) = e.downcast_ref::<&'static str>() {
-						warn!("Aura authorship task panicked at {:?}", s);
+						warn!(target: "aura", "Aura authorship task panicked at {:?}", s);
 					}
 
-					warn!("Restarting Aura authorship task");
+					warn!(target: "aura", "Restarting Aura authorship task");
 				}
 			}
 
@@ -349,6 +362,13 @@ pub struct AuraVerifier<C> {
 	client: Arc<C>,
 }
 
+impl<C> AuraVerifier<C> {
+	/// Create new verifier.
+	pub fn new(config: Config, client: Arc<C>) -> Self {
+		AuraVerifier { config, client }
+	}
+}
+
 impl<B: Block, C> Verifier<B> for AuraVerifier<C> where
 	C: Authorities<B> + BlockImport<B> + Send + Sync,
 	DigestItemFor<B>: CompatibleDigestItem,
@@ -359,18 +379,18 @@ impl<B: Block, C> Verifier<B> for AuraVerifier<C> where
 		header: B::Header,
 		_justification: Vec<u8>,
 		body: Option<Vec<B::Extrinsic>>
-	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), String> {
-		let slot_now = slot_now(self.config.slot_duration)
+	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), VerificationError> {
+		let max_slot = duration_now()
+			.map(|now| (now.as_secs() + self.config.max_future_drift) / self.config.slot_duration)
 			.ok_or("System time is before UnixTime?".to_owned())?;
 		let hash = header.hash();
 		let parent_hash = *header.parent_hash();
+		// the state of the parent may be missing until its ancestors are imported
 		let authorities = self.client.authorities(&BlockId::Hash(parent_hash))
-			.map_err(|e| format!("Could not fetch authorities at {:?}: {:?}", parent_hash, e))?;
+			.map_err(|e| VerificationError::Unavailable(format!("Could not fetch authorities at {:?}: {:?}", parent_hash, e)))?;
 
-		// we add one to allow for some small drift.
-		// FIXME: in the future, alter this queue to allow deferring of headers
-		// https://github.com/paritytech/substrate/issues/1019
-		let checked_header = check_header::<B>(slot_now + 1, header, hash, &authorities[..])?;
+		// headers that are too far in the future are normally held by the queue (see `defer`)
+		let checked_header = check_header::<B>(max_slot, header, hash, &authorities[..])?;
 		match checked_header {
 			CheckedHeader::Checked(pre_header, slot_num, sig) => {
 				let item = <DigestItemFor<B>>::aura_seal(slot_num, sig);
@@ -392,10 +412,55 @@ impl<B: Block, C> Verifier<B> for AuraVerifier<C> where
 			}
 			CheckedHeader::Deferred(a, b) => {
 				debug!(target: "aura", "Checking {:?} failed; {:?}, {:?}.", hash, a, b);
-				Err(format!("Header {:?} rejected: too far in the future", hash))
+				Err(format!("Header {:?} rejected: too far in the future", hash).into())
 			}
 		}
 	}
+
+	fn defer(&self, header: &B::Header) -> Option<Duration> {
+		let slot_num = header.digest().logs().last()
+			.and_then(|item| item.as_aura_seal())
+			.map(|(slot_num, _)| slot_num)?;
+		let slot_start = Duration::from_secs(slot_num.saturating_mul(self.config.slot_duration));
+		let allowed = duration_now()? + Duration::from_secs(self.config.max_future_drift);
+		if slot_start > allowed {
+			Some(slot_start - allowed)
+		} else {
+			None
+		}
+	}
+}
+
+/// Verifier of the headers imported by the light client. Aura doesn't produce justifications,
+/// so the header is only accepted with an empty one and a valid seal of the slot author.
+pub struct AuraJustificationVerifier<B>(::std::marker::PhantomData<B>);
+
+impl<B> Default for AuraJustificationVerifier<B> {
+	fn default() -> Self {
+		AuraJustificationVerifier(::std::marker::PhantomData)
+	}
+}
+
+impl<B: Block> client::JustificationVerifier<B> for AuraJustificationVerifier<B> where
+	DigestItemFor<B>: CompatibleDigestItem,
+{
+	fn verify_justification(
+		&self,
+		header: &B::Header,
+		justification: &runtime_primitives::Justification,
+		authorities: &[AuthorityId],
+	) -> client::error::Result<()> {
+		if !justification.is_empty() {
+			return Err(client::error::ErrorKind::BadJustification("Aura blocks carry no justifications".into()).into());
+		}
+
+		// the slot of the header has been checked against the local time by the import queue
+		match check_header::<B>(u64::max_value(), header.clone(), header.hash(), authorities) {
+			Ok(CheckedHeader::Checked(..)) => Ok(()),
+			Ok(CheckedHeader::Deferred(..)) => unreachable!("no slot is after the maximal slot; qed"),
+			Err(e) => Err(client::error::ErrorKind::BadJustification(e).into()),
+		}
+	}
 }
 
 /// The Aura import queue type.
@@ -407,7 +472,7 @@ pub fn import_queue<B, C>(config: Config, client: Arc<C>) -> AuraImportQueue<B,
 	C: Authorities<B> + BlockImport<B> + Send + Sync,
 	DigestItemFor<B>: CompatibleDigestItem,
 {
-	let verifier = Arc::new(AuraVerifier { config, client });
+	let verifier = Arc::new(AuraVerifier::new(config, client));
 	BasicQueue::new(verifier)
 }
 
@@ -476,7 +541,7 @@ mod tests {
 		fn make_verifier(&self, client: Arc<PeersClient>, _cfg: &ProtocolConfig)
 			-> Arc<Self::Verifier>
 		{
-			let config = Config { local_key: None, slot_duration: SLOT_DURATION };
+			let config = Config { local_key: None, slot_duration: SLOT_DURATION, max_future_drift: SLOT_DURATION };
 			Arc::new(AuraVerifier { client, config })
 		}
 
@@ -522,7 +587,8 @@ mod tests {
 			let mut client = net.lock().peer(*peer_id).client().clone();
 			let environ = Arc::new(DummyFactory(client.clone()));
 			import_notifications.push(
-				client.import_notification_stream()
+				client.import_notification_stream("test")
+					.filter_map(|n| n.item())
 					.take_while(|n| {
 						Ok(!(n.origin != BlockOrigin::Own && n.header.number() < &5))
 					})
@@ -531,11 +597,13 @@ mod tests {
 			let aura = start_aura(
 				Config {
 					local_key: Some(Arc::new(key.clone().into())),
-					slot_duration: SLOT_DURATION
+					slot_duration: SLOT_DURATION,
+					max_future_drift: SLOT_DURATION,
 				},
 				client,
 				environ.clone(),
 				DummyOracle,
+				SystemSlotClock::new(DummyOracle),
 			);
 
 			runtime.spawn(aura);
@@ -557,4 +625,35 @@ mod tests {
 
 		runtime.block_on(wait_for.select(drive_to_completion).map_err(|_| ())).unwrap();
 	}
+
+	#[test]
+	fn justification_verifier_checks_seal_of_slot_author() {
+		use client::JustificationVerifier;
+
+		let authorities: Vec<AuthorityId> = vec![Keyring::Alice.to_raw_public().into(), Keyring::Bob.to_raw_public().into()];
+		let sealed = |slot_num: u64, key: Keyring| {
+			let mut header = <<TestBlock as BlockT>::Header as Header>::new(
+				1,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Default::default(),
+			);
+			let signature = key.sign(&(slot_num, header.hash()).encode());
+			header.digest_mut().push(<DigestItemFor<TestBlock> as CompatibleDigestItem>::aura_seal(slot_num, signature));
+			header
+		};
+		let verifier = AuraJustificationVerifier::<TestBlock>::default();
+		let verify = |header, justification: Vec<u8>| verifier.verify_justification(header, &justification, &authorities);
+
+		// Bob is the author of the slot 3
+		assert!(verify(&sealed(3, Keyring::Bob), Vec::new()).is_ok());
+		assert!(verify(&sealed(3, Keyring::Alice), Vec::new()).is_err());
+		assert!(verify(&sealed(3, Keyring::Bob), vec![42]).is_err());
+
+		// unsealed header is rejected
+		let mut unsealed = sealed(3, Keyring::Bob);
+		unsealed.digest_mut().pop();
+		assert!(verify(&unsealed, Vec::new()).is_err());
+	}
 }
@@ -0,0 +1,135 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Clocks for slot-based authoring.
+//!
+//! Slot authors are expected to roughly agree on the current time. The local clock is
+//! compared against the median time reported by connected peers, and authoring is
+//! refused when the local clock is too far off.
+
+use std::time::Duration;
+
+use consensus_common::TimeOracle;
+
+/// Local clock drift (in milliseconds) above which a warning is issued.
+pub const DRIFT_WARNING_MILLIS: u64 = 500;
+
+/// A clock for slot-based consensus.
+pub trait SlotClock {
+	/// Current time as a duration since the unix epoch. Returns `None` if it is unknown.
+	fn now(&self) -> Option<Duration>;
+
+	/// Estimated difference between the network time and the local time, in milliseconds.
+	/// Positive if the local clock is behind. Returns `None` if the network time is unknown.
+	fn drift(&self) -> Option<i64>;
+
+	/// Get the slot for now.
+	fn slot_now(&self, slot_duration: u64) -> Option<u64> {
+		self.now().map(|now| now.as_secs() / slot_duration)
+	}
+
+	/// Get the time remaining until the next slot starts.
+	fn time_until_next_slot(&self, slot_duration: u64) -> Option<Duration> {
+		self.now().map(|now| {
+			let remaining_full_secs = slot_duration - (now.as_secs() % slot_duration) - 1;
+			let remaining_nanos = 1_000_000_000 - now.subsec_nanos();
+			Duration::new(remaining_full_secs, remaining_nanos)
+		})
+	}
+}
+
+/// Result of the local clock drift check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftCheck {
+	/// Drift is unknown or negligible.
+	Ok,
+	/// Drift (in milliseconds) is noticeable, but the local clock is still usable.
+	Warning(i64),
+	/// Local clock is too far off (drift is in milliseconds) to author blocks.
+	TooFar(i64),
+}
+
+/// Check the drift of the clock. The local clock is too far off when it differs from the
+/// network time by at least half of the slot: our blocks would likely be considered to be
+/// authored in a wrong slot.
+pub fn check_drift<C: SlotClock + ?Sized>(clock: &C, slot_duration: u64) -> DriftCheck {
+	let drift = match clock.drift() {
+		Some(drift) => drift,
+		None => return DriftCheck::Ok,
+	};
+
+	let abs_drift = drift.checked_abs().unwrap_or(i64::max_value()) as u64;
+	if abs_drift.saturating_mul(2) >= slot_duration.saturating_mul(1_000) {
+		DriftCheck::TooFar(drift)
+	} else if abs_drift >= DRIFT_WARNING_MILLIS {
+		DriftCheck::Warning(drift)
+	} else {
+		DriftCheck::Ok
+	}
+}
+
+/// Slot clock based on the local system time, checked against the network time.
+#[derive(Clone)]
+pub struct SystemSlotClock<T> {
+	time_oracle: T,
+}
+
+impl<T> SystemSlotClock<T> {
+	/// Create new clock, checked against the time provided by given oracle.
+	pub fn new(time_oracle: T) -> Self {
+		SystemSlotClock { time_oracle }
+	}
+}
+
+impl<T: TimeOracle> SlotClock for SystemSlotClock<T> {
+	fn now(&self) -> Option<Duration> {
+		::duration_now()
+	}
+
+	fn drift(&self) -> Option<i64> {
+		self.time_oracle.median_time_offset()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct FixedClock(Duration, Option<i64>);
+
+	impl SlotClock for FixedClock {
+		fn now(&self) -> Option<Duration> { Some(self.0) }
+		fn drift(&self) -> Option<i64> { self.1 }
+	}
+
+	#[test]
+	fn slots_are_computed_from_clock_time() {
+		let clock = FixedClock(Duration::new(10, 250_000_000), None);
+		assert_eq!(clock.slot_now(3), Some(3));
+		assert_eq!(clock.time_until_next_slot(3), Some(Duration::new(1, 750_000_000)));
+	}
+
+	#[test]
+	fn drift_is_checked_against_slot_duration() {
+		let check = |drift| check_drift(&FixedClock(Default::default(), drift), 4);
+		assert_eq!(check(None), DriftCheck::Ok);
+		assert_eq!(check(Some(-100)), DriftCheck::Ok);
+		assert_eq!(check(Some(700)), DriftCheck::Warning(700));
+		assert_eq!(check(Some(-1_999)), DriftCheck::Warning(-1_999));
+		assert_eq!(check(Some(2_000)), DriftCheck::TooFar(2_000));
+		assert_eq!(check(Some(i64::min_value())), DriftCheck::TooFar(i64::min_value()));
+	}
+}
@@ -59,6 +59,18 @@ pub use block_import::{BlockImport, ImportBlock, BlockOrigin, ImportResult};
 pub trait Authorities<B: Block> {
 	type Error: ::std::error::Error + Send + 'static;	/// Get the authorities at the given block.
 	fn authorities(&self, at: &BlockId<B>) -> Result<Vec<AuthorityId>, Self::Error>;
+
+	/// Get the authorities disabled by the runtime at the given block, whose votes must not be
+	/// counted. None by default.
+	fn disabled_authorities(&self, _at: &BlockId<B>) -> Result<Vec<AuthorityId>, Self::Error> {
+		Ok(Vec::new())
+	}
+}
+
+/// Authorities whose votes are counted: the given authorities excluding the disabled ones, in the
+/// original order. Agreement thresholds must be computed over this set.
+pub fn active_authorities(authorities: &[AuthorityId], disabled: &[AuthorityId]) -> Vec<AuthorityId> {
+	authorities.iter().filter(|authority| !disabled.contains(authority)).cloned().collect()
 }
 
 /// Environment producer for a Consensus instance. Creates proposer instance and communication streams.
@@ -130,3 +142,22 @@ impl<T: SyncOracle> SyncOracle for Arc<T> {
 		T::is_major_syncing(&*self)
 	}
 }
+
+/// An oracle for the time reported by the network.
+///
+/// Used to detect local clock drift, which is fatal for time-based consensus.
+pub trait TimeOracle {
+	/// Median difference between the time reported by connected peers and the local time,
+	/// in milliseconds. Returns `None` if the network time is unknown.
+	fn median_time_offset(&self) -> Option<i64>;
+}
+
+impl TimeOracle for NoNetwork {
+	fn median_time_offset(&self) -> Option<i64> { None }
+}
+
+impl<T: TimeOracle> TimeOracle for Arc<T> {
+	fn median_time_offset(&self) -> Option<i64> {
+		T::median_time_offset(&*self)
+	}
+}
@@ -0,0 +1,58 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Manual seal RPC errors.
+
+use rpc;
+
+error_chain! {
+	errors {
+		/// Manual seal engine is not running.
+		EngineStopped {
+			description("manual seal engine stopped"),
+			display("Manual seal engine is not running"),
+		}
+		/// Manual seal engine has failed to execute the command.
+		Engine(e: String) {
+			description("manual seal engine error"),
+			display("Manual seal engine error: {}", e),
+		}
+	}
+}
+
+const ERROR: i64 = 3000;
+
+impl From<Error> for rpc::Error {
+	fn from(e: Error) -> Self {
+		match e {
+			Error(ErrorKind::EngineStopped, _) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(ERROR + 1),
+				message: "Manual seal engine is not running".into(),
+				data: None,
+			},
+			Error(ErrorKind::Engine(e), _) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(ERROR + 2),
+				message: e,
+				data: None,
+			},
+			e => rpc::Error {
+				code: rpc::ErrorCode::InternalError,
+				message: "Unknown error occured".into(),
+				data: Some(format!("{:?}", e).into()),
+			},
+		}
+	}
+}
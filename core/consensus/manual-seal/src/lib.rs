@@ -0,0 +1,353 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Manual sealing consensus for development nodes.
+//!
+//! Blocks are authored only when requested (via the `engine_createBlock` RPC, or the
+//! `engine_createBlockAt` RPC on top of an arbitrary parent) and
+//! finalized only when requested (via the `engine_finalizeBlock` RPC). There are
+//! no slots and no validator checks: the local node is the only author of the chain.
+//!
+//! In the instant seal mode a block is also authored and finalized for every transaction
+//! imported to the transaction pool.
+
+#![warn(missing_docs)]
+
+extern crate futures;
+extern crate jsonrpc_core as rpc;
+extern crate serde;
+extern crate substrate_client as client;
+extern crate substrate_consensus_common as consensus_common;
+extern crate substrate_primitives as primitives;
+extern crate sr_primitives as runtime_primitives;
+
+#[macro_use]
+extern crate error_chain;
+#[macro_use]
+extern crate jsonrpc_macros;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(test)]
+extern crate substrate_keyring as keyring;
+#[cfg(test)]
+extern crate substrate_test_client as test_client;
+#[cfg(test)]
+extern crate tokio;
+
+pub mod error;
+pub mod rpc_api;
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use client::{BlockHeader, ChainHead, Finalize};
+use consensus_common::{Authorities, BlockImport, BlockOrigin, Environment, ImportBlock, ImportResult, Proposer};
+use futures::{Future, IntoFuture, Stream, future::{self, Either}};
+use futures::sync::{mpsc, oneshot};
+use primitives::ed25519;
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::{Block, Header};
+
+pub use rpc_api::{ManualSeal, ManualSealApi};
+
+/// Sending end of the manual seal commands channel.
+pub type CommandSender<Hash> = mpsc::UnboundedSender<EngineCommand<Hash>>;
+
+/// Receiving end of the manual seal commands channel.
+pub type CommandReceiver<Hash> = mpsc::UnboundedReceiver<EngineCommand<Hash>>;
+
+/// Command sent to the manual seal engine.
+pub enum EngineCommand<Hash> {
+	/// Author a new block on top of the given parent, or the best block.
+	CreateBlock {
+		/// Hash of the parent block. `None` for the best block.
+		parent: Option<Hash>,
+		/// Finalize the block right after import.
+		finalize: bool,
+		/// Channel to report the result to.
+		sender: oneshot::Sender<Result<CreatedBlock<Hash>, String>>,
+	},
+	/// Finalize the block, along with all its ancestors.
+	FinalizeBlock {
+		/// Hash of the block.
+		hash: Hash,
+		/// Channel to report the result to.
+		sender: oneshot::Sender<Result<(), String>>,
+	},
+}
+
+/// Block that has been created by the manual seal engine.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedBlock<Hash> {
+	/// Hash of the block.
+	pub hash: Hash,
+	/// Whether the block has been finalized.
+	pub finalized: bool,
+}
+
+/// Create a new channel of manual seal commands.
+pub fn channel<Hash>() -> (CommandSender<Hash>, CommandReceiver<Hash>) {
+	mpsc::unbounded()
+}
+
+/// Convert a stream of transaction pool import notifications into commands that create
+/// and finalize a block for every imported transaction.
+pub fn instant_seal_commands<Hash, S>(imports: S) -> impl Stream<Item=EngineCommand<Hash>, Error=()> where
+	S: Stream<Item=(), Error=()>,
+{
+	imports.map(|()| {
+		let (sender, _) = oneshot::channel();
+		EngineCommand::CreateBlock { parent: None, finalize: true, sender }
+	})
+}
+
+/// Run the manual seal engine, serving the commands from given stream until it ends.
+/// This should be run in a tokio runtime.
+pub fn run_manual_seal<B, C, E, S>(
+	client: Arc<C>,
+	env: Arc<E>,
+	local_key: Arc<ed25519::Pair>,
+	commands: S,
+) -> impl Future<Item=(), Error=()> where
+	B: Block,
+	C: Authorities<B> + BlockImport<B> + BlockHeader<B> + ChainHead<B> + Finalize<B>,
+	E: Environment<B>,
+	E::Error: Debug,
+	<E::Proposer as Proposer<B>>::Error: Debug,
+	S: Stream<Item=EngineCommand<B::Hash>, Error=()>,
+{
+	commands.for_each(move |command| match command {
+		EngineCommand::CreateBlock { parent, finalize, sender } => {
+			let client = client.clone();
+			let proposer = init_proposer(&*client, &*env, local_key.clone(), parent);
+			Either::A(future::result(proposer)
+				.and_then(|proposer| proposer.propose().into_future()
+					.map_err(|e| format!("Failed to construct block: {:?}", e)))
+				.and_then(move |block| import_block(&*client, block, finalize))
+				.then(move |result| {
+					if let Err(ref e) = result {
+						warn!(target: "manual-seal", "Unable to create block: {}", e);
+					}
+					let _ = sender.send(result);
+					Ok(())
+				}))
+		},
+		EngineCommand::FinalizeBlock { hash, sender } => {
+			let result = client.finalize(BlockId::Hash(hash))
+				.map_err(|e| format!("Failed to finalize block {:?}: {:?}", hash, e));
+			if let Err(ref e) = result {
+				warn!(target: "manual-seal", "{}", e);
+			}
+			let _ = sender.send(result);
+			Either::B(future::ok(()))
+		},
+	})
+}
+
+fn init_proposer<B, C, E>(
+	client: &C,
+	env: &E,
+	local_key: Arc<ed25519::Pair>,
+	parent: Option<B::Hash>,
+) -> Result<E::Proposer, String> where
+	B: Block,
+	C: Authorities<B> + BlockHeader<B> + ChainHead<B>,
+	E: Environment<B>,
+	E::Error: Debug,
+{
+	let parent = match parent {
+		Some(hash) => client.block_header(&BlockId::Hash(hash))
+			.map_err(|e| format!("Unable to fetch header of {:?}: {:?}", hash, e))?
+			.ok_or_else(|| format!("Unknown parent block {:?}", hash))?,
+		None => client.best_block_header()
+			.map_err(|e| format!("No best block header: {:?}", e))?,
+	};
+	let parent_hash = parent.hash();
+	let authorities = client.authorities(&BlockId::Hash(parent_hash))
+		.map_err(|e| format!("Unable to fetch authorities at block {:?}: {:?}", parent_hash, e))?;
+	env.init(&parent, &authorities, local_key)
+		.map_err(|e| format!("Unable to author block on {:?}: {:?}", parent_hash, e))
+}
+
+fn import_block<B, C>(client: &C, block: B, finalize: bool) -> Result<CreatedBlock<B::Hash>, String> where
+	B: Block,
+	C: BlockImport<B>,
+{
+	let (header, body) = block.deconstruct();
+	let hash = header.hash();
+	let import_block = ImportBlock {
+		origin: BlockOrigin::Own,
+		header,
+		external_justification: Vec::new(),
+		post_runtime_digests: Vec::new(),
+		body: Some(body),
+		finalized: finalize,
+		auxiliary: Vec::new(),
+	};
+
+	match client.import_block(import_block, None) {
+		Ok(ImportResult::Queued) => {
+			info!(target: "manual-seal", "Created block {:?}{}", hash, if finalize { " (finalized)" } else { "" });
+			Ok(CreatedBlock { hash, finalized: finalize })
+		},
+		Ok(result) => Err(format!("Block {:?} has not been imported: {:?}", hash, result)),
+		Err(e) => Err(format!("Error importing block {:?}: {:?}", hash, e)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use client::BlockchainEvents;
+	use keyring::Keyring;
+	use primitives::AuthorityId;
+	use test_client::{self, runtime::Block as TestBlock};
+	use tokio::runtime::current_thread;
+
+	type Error = client::error::Error;
+
+	type TestClient = client::Client<test_client::Backend, test_client::Executor, TestBlock>;
+
+	struct DummyFactory(Arc<TestClient>);
+	struct DummyProposer(Arc<TestClient>, <TestBlock as Block>::Hash);
+
+	impl Environment<TestBlock> for DummyFactory {
+		type Proposer = DummyProposer;
+		type Error = Error;
+
+		fn init(&self, parent_header: &<TestBlock as Block>::Header, _authorities: &[AuthorityId], _sign_with: Arc<ed25519::Pair>)
+			-> Result<DummyProposer, Error>
+		{
+			Ok(DummyProposer(self.0.clone(), parent_header.hash()))
+		}
+	}
+
+	impl Proposer<TestBlock> for DummyProposer {
+		type Error = Error;
+		type Create = Result<TestBlock, Error>;
+
+		fn propose(&self) -> Result<TestBlock, Error> {
+			self.0.new_block_at(&BlockId::Hash(self.1)).unwrap().bake().map_err(|e| e.into())
+		}
+	}
+
+	#[test]
+	fn creates_and_finalizes_blocks_on_demand() {
+		let client = Arc::new(test_client::new());
+		let (sender, commands) = channel();
+		let mut runtime = current_thread::Runtime::new().unwrap();
+		runtime.spawn(run_manual_seal(
+			client.clone(),
+			Arc::new(DummyFactory(client.clone())),
+			Arc::new(Keyring::Alice.into()),
+			commands,
+		));
+
+		let mut create_block = |finalize| {
+			let (tx, rx) = oneshot::channel();
+			sender.unbounded_send(EngineCommand::CreateBlock { parent: None, finalize, sender: tx }).unwrap();
+			runtime.block_on(rx).unwrap().unwrap()
+		};
+		let first = create_block(false);
+		let second = create_block(true);
+		assert_eq!(first.finalized, false);
+		assert_eq!(second.finalized, true);
+
+		let info = client.info().unwrap().chain;
+		assert_eq!(info.best_number, 2);
+		assert_eq!(info.best_hash, second.hash);
+		assert_eq!(info.finalized_hash, second.hash);
+	}
+
+	#[test]
+	fn instant_seal_creates_block_per_transaction() {
+		let client = Arc::new(test_client::new());
+		let import_notifications = client.import_notification_stream("test");
+		let (imports_sink, imports) = mpsc::unbounded();
+		let mut runtime = current_thread::Runtime::new().unwrap();
+		runtime.spawn(run_manual_seal(
+			client.clone(),
+			Arc::new(DummyFactory(client.clone())),
+			Arc::new(Keyring::Alice.into()),
+			instant_seal_commands(imports),
+		));
+
+		imports_sink.unbounded_send(()).unwrap();
+		let (notification, _) = runtime.block_on(import_notifications.into_future()).map_err(|_| ()).unwrap();
+		assert_eq!(notification.and_then(|n| n.item()).unwrap().header.number, 1);
+		assert_eq!(client.info().unwrap().chain.finalized_number, 1);
+	}
+
+	#[test]
+	fn finalizes_blocks_on_demand() {
+		let client = Arc::new(test_client::new());
+		let (sender, commands) = channel();
+		let mut runtime = current_thread::Runtime::new().unwrap();
+		runtime.spawn(run_manual_seal(
+			client.clone(),
+			Arc::new(DummyFactory(client.clone())),
+			Arc::new(Keyring::Alice.into()),
+			commands,
+		));
+
+		let (tx, rx) = oneshot::channel();
+		sender.unbounded_send(EngineCommand::CreateBlock { parent: None, finalize: false, sender: tx }).unwrap();
+		let created = runtime.block_on(rx).unwrap().unwrap();
+		assert_eq!(client.info().unwrap().chain.finalized_number, 0);
+
+		let (tx, rx) = oneshot::channel();
+		sender.unbounded_send(EngineCommand::FinalizeBlock { hash: created.hash, sender: tx }).unwrap();
+		runtime.block_on(rx).unwrap().unwrap();
+		assert_eq!(client.info().unwrap().chain.finalized_hash, created.hash);
+
+		let (tx, rx) = oneshot::channel();
+		sender.unbounded_send(EngineCommand::FinalizeBlock { hash: Default::default(), sender: tx }).unwrap();
+		assert!(runtime.block_on(rx).unwrap().is_err());
+	}
+
+	#[test]
+	fn creates_blocks_on_top_of_given_parent() {
+		let client = Arc::new(test_client::new());
+		let (sender, commands) = channel();
+		let mut runtime = current_thread::Runtime::new().unwrap();
+		runtime.spawn(run_manual_seal(
+			client.clone(),
+			Arc::new(DummyFactory(client.clone())),
+			Arc::new(Keyring::Alice.into()),
+			commands,
+		));
+
+		let mut create_block = |parent| {
+			let (tx, rx) = oneshot::channel();
+			sender.unbounded_send(EngineCommand::CreateBlock { parent, finalize: false, sender: tx }).unwrap();
+			runtime.block_on(rx).unwrap()
+		};
+		let genesis = client.info().unwrap().chain.genesis_hash;
+		let first = create_block(None).unwrap();
+		let second = create_block(Some(first.hash)).unwrap();
+
+		// sibling of the first block
+		let fork = create_block(Some(genesis)).unwrap();
+		assert_eq!(client.header(&BlockId::Hash(fork.hash)).unwrap().unwrap().parent_hash, genesis);
+		assert_eq!(client.info().unwrap().chain.best_hash, second.hash);
+
+		assert!(create_block(Some(Default::default())).is_err());
+	}
+}
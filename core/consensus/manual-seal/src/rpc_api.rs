@@ -0,0 +1,121 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Manual seal RPC API.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use futures::Future;
+use futures::sync::oneshot;
+use jsonrpc_macros::Trailing;
+use serde::{Serialize, de::DeserializeOwned};
+
+use error::{ErrorKind, Result};
+use {CommandSender, CreatedBlock, EngineCommand};
+
+build_rpc_trait! {
+	/// Manual seal RPC API.
+	pub trait ManualSealApi<Hash> {
+		/// Author a new block on top of the best block. The block is finalized if requested.
+		#[rpc(name = "engine_createBlock")]
+		fn create_block(&self, Trailing<bool>) -> Result<CreatedBlock<Hash>>;
+
+		/// Author a new block on top of the given parent, which doesn't have to be the best block.
+		/// The block is finalized if requested.
+		#[rpc(name = "engine_createBlockAt")]
+		fn create_block_at(&self, Hash, Trailing<bool>) -> Result<CreatedBlock<Hash>>;
+
+		/// Finalize the block, along with all its ancestors.
+		#[rpc(name = "engine_finalizeBlock")]
+		fn finalize_block(&self, Hash) -> Result<bool>;
+	}
+}
+
+/// Manual seal API, forwarding the calls to the manual seal engine.
+pub struct ManualSeal<Hash> {
+	sender: CommandSender<Hash>,
+	engine_started: Arc<AtomicBool>,
+}
+
+impl<Hash> ManualSeal<Hash> {
+	/// Create new instance of manual seal API, sending commands to the given channel.
+	/// The calls fail until `engine_started` is set, since nothing would answer the commands.
+	pub fn new(sender: CommandSender<Hash>, engine_started: Arc<AtomicBool>) -> Self {
+		ManualSeal {
+			sender,
+			engine_started,
+		}
+	}
+
+	fn send<T>(&self, command: EngineCommand<Hash>, receiver: oneshot::Receiver<::std::result::Result<T, String>>) -> Result<T> {
+		if !self.engine_started.load(Ordering::Acquire) {
+			return Err(ErrorKind::EngineStopped.into());
+		}
+		self.sender.unbounded_send(command).map_err(|_| ErrorKind::EngineStopped)?;
+		receiver.wait()
+			.map_err(|_| ErrorKind::EngineStopped)?
+			.map_err(|e| ErrorKind::Engine(e).into())
+	}
+}
+
+impl<Hash> ManualSealApi<Hash> for ManualSeal<Hash> where
+	Hash: Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+	fn create_block(&self, finalize: Trailing<bool>) -> Result<CreatedBlock<Hash>> {
+		let (sender, receiver) = oneshot::channel();
+		let finalize = Into::<Option<bool>>::into(finalize).unwrap_or(false);
+		self.send(EngineCommand::CreateBlock { parent: None, finalize, sender }, receiver)
+	}
+
+	fn create_block_at(&self, parent: Hash, finalize: Trailing<bool>) -> Result<CreatedBlock<Hash>> {
+		let (sender, receiver) = oneshot::channel();
+		let finalize = Into::<Option<bool>>::into(finalize).unwrap_or(false);
+		self.send(EngineCommand::CreateBlock { parent: Some(parent), finalize, sender }, receiver)
+	}
+
+	fn finalize_block(&self, hash: Hash) -> Result<bool> {
+		let (sender, receiver) = oneshot::channel();
+		self.send(EngineCommand::FinalizeBlock { hash, sender }, receiver)
+			.map(|_| true)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use channel;
+
+	#[test]
+	fn fails_immediately_until_engine_is_started() {
+		let (sender, receiver) = channel::<u64>();
+		let engine_started = Arc::new(AtomicBool::new(false));
+		let api = ManualSeal::new(sender, engine_started.clone());
+
+		// the commands are never answered, yet the call doesn't block
+		match api.finalize_block(1) {
+			Err(::error::Error(ErrorKind::EngineStopped, _)) => (),
+			_ => panic!("Expected engine stopped error"),
+		}
+
+		// the engine has stopped once the receiver is dropped
+		engine_started.store(true, Ordering::Release);
+		drop(receiver);
+		match api.finalize_block(1) {
+			Err(::error::Error(ErrorKind::EngineStopped, _)) => (),
+			_ => panic!("Expected engine stopped error"),
+		}
+	}
+}
@@ -73,7 +73,7 @@ use consensus::offline_tracker::OfflineTracker;
 use consensus::error::{ErrorKind as CommonErrorKind};
 use consensus::{Authorities, BlockImport, Environment, Proposer as BaseProposer};
 use client::{Client as SubstrateClient, CallExecutor};
-use client::runtime_api::{Core, BlockBuilder as BlockBuilderAPI, OldTxQueue, BlockBuilderError};
+use client::runtime_api::{Core, BlockBuilder as BlockBuilderAPI, OldTxQueue, BlockBuilderError, id::BlockBuilderApi};
 use runtime_primitives::generic::{BlockId, Era, ImportResult, ImportBlock, BlockOrigin};
 use runtime_primitives::traits::{Block, Header};
 use runtime_primitives::traits::{Block as BlockT, Hash as HashT, Header as HeaderT, As, BlockNumberToHash};
@@ -566,6 +566,12 @@ impl<B, P, I> BftService<B, P, I>
 
 		let authorities = self.client.authorities(&BlockId::Hash(hash.clone()))
 			.map_err(|e| CommonErrorKind::Other(Box::new(e)).into())?;
+		let disabled = self.client.disabled_authorities(&BlockId::Hash(hash.clone()))
+			.map_err(|e| CommonErrorKind::Other(Box::new(e)).into())?;
+		if !disabled.is_empty() {
+			debug!(target: "rhd", "Votes of disabled authorities are not counted: {:?}", disabled);
+		}
+		let authorities = consensus::active_authorities(&authorities, &disabled);
 
 		let n = authorities.len();
 		let max_faulty = max_faulty_of(n);
@@ -831,6 +837,37 @@ pub fn check_prepare_justification<B: Block>(authorities: &[AuthorityId], parent
 	check_justification_signed_message(authorities, &message[..], just).map(|e| PrepareJustification(e.0))
 }
 
+/// Verifier of the BFT justifications of the headers imported by the light client.
+pub struct JustificationVerifier<B>(::std::marker::PhantomData<B>);
+
+impl<B> Default for JustificationVerifier<B> {
+	fn default() -> Self {
+		JustificationVerifier(::std::marker::PhantomData)
+	}
+}
+
+impl<B: Block> client::JustificationVerifier<B> for JustificationVerifier<B> {
+	fn verify_justification(
+		&self,
+		header: &B::Header,
+		justification: &Justification,
+		authorities: &[AuthorityId],
+	) -> client::error::Result<()> {
+		let bad_justification = |reason: &str|
+			client::error::ErrorKind::BadJustification(reason.into()).into();
+
+		let just = UncheckedJustification::<B::Hash>::decode_justification(justification.clone())
+			.ok_or_else(|| bad_justification("failed to decode"))?;
+		if just.0.digest != header.hash() {
+			return Err(bad_justification("justification is for another block"));
+		}
+
+		check_justification::<B>(authorities, *header.parent_hash(), just)
+			.map(|_| ())
+			.map_err(|_| bad_justification("not enough valid signatures"))
+	}
+}
+
 /// Check proposal message signatures and authority.
 /// Provide all valid authorities.
 pub fn check_proposal<B: Block + Clone>(
@@ -951,10 +988,8 @@ impl<'a, B, E, Block> AuthoringApi for SubstrateClient<B, E, Block> where
 		inherent_data: InherentData,
 		mut build_ctx: F,
 	) -> Result<Self::Block, Error> {
-		let runtime_version = self.runtime_version_at(at)?;
-
 		let mut block_builder = self.new_block_at(at)?;
-		if runtime_version.has_api(*b"blkbuild", 1) {
+		if self.has_api::<BlockBuilderApi>(at)? {
 			for inherent in self.inherent_extrinsics(at, &inherent_data)? {
 				block_builder.push(inherent)?;
 			}
@@ -1013,7 +1048,7 @@ impl<N, C, A> consensus::Environment<<C as AuthoringApi>::Block> for ProposerFac
 		let validators = self.client.validators(&id)?;
 		self.offline.write().note_new_block(&validators[..]);
 
-		info!("Starting consensus session on top of parent {:?}", parent_hash);
+		info!(target: "rhd", "Starting consensus session on top of parent {:?}", parent_hash);
 
 		let local_id = sign_with.public().0.into();
 		let (input, output) = self.network.communication_for(
@@ -1096,6 +1131,7 @@ impl<C, A> BaseProposer<<C as AuthoringApi>::Block> for Proposer<C, A> where
 
 		if !offline_indices.is_empty() {
 			info!(
+				target: "rhd",
 				"Submitting offline validators {:?} for slash-vote",
 				offline_indices.iter().map(|&i| self.validators[i as usize]).collect::<Vec<_>>(),
 				)
@@ -1133,7 +1169,7 @@ impl<C, A> BaseProposer<<C as AuthoringApi>::Block> for Proposer<C, A> where
 				self.transaction_pool.remove_invalid(&unqueue_invalid);
 			})?;
 
-		info!("Proposing block [number: {}; hash: {}; parent_hash: {}; extrinsics: [{}]]",
+		info!(target: "rhd", "Proposing block [number: {}; hash: {}; parent_hash: {}; extrinsics: [{}]]",
 			block.header().number(),
 			<<C as AuthoringApi>::Block as BlockT>::Hash::from(block.header().hash()),
 			block.header().parent_hash(),
@@ -1309,7 +1345,7 @@ impl<C, A> LocalProposer<<C as AuthoringApi>::Block> for Proposer<C, A> where
 				&mut extrinsic.encode().as_slice()).expect("Encoded extrinsic is valid");
 			let hash = BlockId::<<C as AuthoringApi>::Block>::hash(self.parent_hash);
 			if let Err(e) = self.transaction_pool.submit_one(&hash, uxt) {
-				warn!("Error importing misbehavior report: {:?}", e);
+				warn!(target: "rhd", "Error importing misbehavior report: {:?}", e);
 			}
 		}
 	}
@@ -1324,6 +1360,7 @@ impl<C, A> LocalProposer<<C as AuthoringApi>::Block> for Proposer<C, A> where
 		if !was_proposed {
 			let public = ed25519::Public::from_raw(primary_validator.0);
 			info!(
+				target: "rhd",
 				"Potential Offline Validator: {} failed to propose during assigned slot: {}",
 				public,
 				round_number,
@@ -1523,6 +1560,16 @@ mod tests {
 		assert_eq!(max_faulty_of(99), 32);
 	}
 
+	#[test]
+	fn threshold_excludes_disabled_authorities() {
+		let authorities: Vec<AuthorityId> = (0..5u8).map(|i| [i; 32].into()).collect();
+		assert_eq!(bft_threshold(authorities.len()), 4);
+
+		let active = ::consensus::active_authorities(&authorities, &authorities[3..]);
+		assert_eq!(active, authorities[..3].to_vec());
+		assert_eq!(bft_threshold(active.len()), 3);
+	}
+
 	#[test]
 	fn justification_check_works() {
 		let parent_hash = Default::default();
@@ -1585,6 +1632,52 @@ mod tests {
 		assert!(check_justification::<TestBlock>(&authorities, parent_hash, unchecked).is_err());
 	}
 
+	#[test]
+	fn justification_verifier_checks_header_hash_and_signatures() {
+		let header = from_block_number(1);
+		let parent_hash = *header.parent_hash();
+		let hash = header.hash();
+
+		let authorities: Vec<AuthorityId> = vec![
+			Keyring::One.to_raw_public().into(),
+			Keyring::Two.to_raw_public().into(),
+			Keyring::Alice.to_raw_public().into(),
+			Keyring::Eve.to_raw_public().into(),
+		];
+
+		let authorities_keys: Vec<ed25519::Pair> = vec![
+			Keyring::One.into(),
+			Keyring::Two.into(),
+			Keyring::Alice.into(),
+			Keyring::Eve.into(),
+		];
+
+		let justification = |digest: H256, signers: usize| -> Justification {
+			UncheckedJustification(rhododendron::UncheckedJustification {
+				digest,
+				round_number: 1,
+				signatures: authorities_keys.iter().take(signers).map(|key| {
+					sign_vote(rhododendron::Vote::Commit(1, hash).into(), key, parent_hash)
+				}).collect(),
+			}).into()
+		};
+		let verify = |justification: Justification|
+			client::JustificationVerifier::<TestBlock>::verify_justification(
+				&JustificationVerifier::<TestBlock>::default(),
+				&header,
+				&justification,
+				&authorities,
+			);
+
+		assert!(verify(justification(hash, 3)).is_ok());
+		// not enough signatures.
+		assert!(verify(justification(hash, 2)).is_err());
+		// justification of another block.
+		assert!(verify(justification([0xfe; 32].into(), 3)).is_err());
+		// garbage.
+		assert!(verify(vec![42]).is_err());
+	}
+
 	#[test]
 	fn propose_check_works() {
 		let parent_hash = Default::default();
@@ -22,7 +22,7 @@ use std::thread;
 use std::time::{Duration, Instant};
 use std::sync::Arc;
 
-use client::{BlockchainEvents, ChainHead, BlockBody};
+use client::{BlockchainEvents, ChainHead, BlockBody, Notification};
 use futures::prelude::*;
 use transaction_pool::txpool::{Pool as TransactionPool, ChainApi as PoolChainApi};
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, BlockNumberToHash};
@@ -115,9 +115,16 @@ impl Service {
 				let client = client.clone();
 				let bft_service = bft_service.clone();
 
-				client.import_notification_stream().for_each(move |notification| {
-					if notification.is_new_best {
-						start_bft(notification.header, bft_service.clone());
+				let best_client = client.clone();
+				client.import_notification_stream("rhd").for_each(move |notification| {
+					match notification {
+						Notification::Item(notification) => if notification.is_new_best {
+							start_bft(notification.header, bft_service.clone());
+						},
+						// notifications have been dropped => restart at the current best block
+						Notification::Lagged(_) => if let Ok(header) = best_client.best_block_header() {
+							start_bft(header, bft_service.clone());
+						},
 					}
 					Ok(())
 				})
@@ -131,7 +138,7 @@ impl Service {
 			let mut prev_best = match client.best_block_header() {
 				Ok(header) => header.hash(),
 				Err(e) => {
-					warn!("Cant's start consensus service. Error reading best block header: {:?}", e);
+					warn!(target: "rhd", "Cant's start consensus service. Error reading best block header: {:?}", e);
 					return;
 				}
 			};
@@ -158,7 +165,7 @@ impl Service {
 			runtime.spawn(timed);
 
 			if let Err(e) = runtime.block_on(exit) {
-				debug!("BFT event loop error {:?}", e);
+				debug!(target: "rhd", "BFT event loop error {:?}", e);
 			}
 		});
 		Service {
@@ -17,7 +17,9 @@
 //! Rust implementation of Substrate contracts.
 
 use std::collections::HashMap;
+use std::str;
 
+use codec::Encode;
 use wasmi::{
 	Module, ModuleInstance, MemoryInstance, MemoryRef, TableRef, ImportsBuilder
 };
@@ -26,8 +28,9 @@ use wasmi::memory_units::{Pages, Bytes};
 use state_machine::Externalities;
 use error::{Error, ErrorKind, Result};
 use wasm_utils::UserError;
-use primitives::{blake2_256, twox_128, twox_256, ed25519};
+use primitives::{blake2_256, twox_128, twox_256, ed25519, AuthorityId};
 use primitives::hexdisplay::HexDisplay;
+use primitives::offchain::{HttpError, HttpRequestId, Timestamp};
 use primitives::sandbox as sandbox_primitives;
 use primitives::Blake2Hasher;
 use trie::ordered_trie_root;
@@ -140,6 +143,24 @@ impl ReadPrimitive<u32> for MemoryInstance {
 	}
 }
 
+/// Maximal number of bytes of the response body read by the single `ext_http_response_read_body`
+/// call. The runtime reads the rest of the body with the following calls.
+const MAX_HTTP_READ_SIZE: u32 = 64 * 1024;
+
+/// Decode the id of the HTTP request passed to the host functions.
+fn http_request_id(value: u32) -> ::std::result::Result<HttpRequestId, HttpError> {
+	if value > u16::max_value() as u32 {
+		Err(HttpError::Invalid)
+	} else {
+		Ok(HttpRequestId(value as u16))
+	}
+}
+
+/// Encode the result of the HTTP request operation as the value returned from the host functions.
+fn http_result(result: ::std::result::Result<(), HttpError>) -> u32 {
+	result.err().map_or(0, |error| error.as_u32())
+}
+
 // TODO: this macro does not support `where` clauses and that seems somewhat tricky to add
 impl_function_executor!(this: FunctionExecutor<'e, E>,
 	ext_print_utf8(utf8_data: *const u8, utf8_len: u32) => {
@@ -437,6 +458,144 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 	ext_chain_id() -> u64 => {
 		Ok(this.ext.chain_id())
 	},
+	ext_chain_randomness(subject_data: *const u8, subject_len: u32, result: *mut u8) -> u32 => {
+		let subject = this.memory.get(subject_data, subject_len as usize)
+			.map_err(|_| UserError("Invalid attempt to determine subject in ext_chain_randomness"))?;
+		let r = this.ext.chain_randomness(&subject);
+		if let Some(ref r) = r {
+			this.memory.set(result, &r[..]).map_err(|_| UserError("Invalid attempt to set memory in ext_chain_randomness"))?;
+		}
+		Ok(if r.is_some() { 1u32 } else { 0u32 })
+	},
+	ext_recent_block_hash(depth: u32, result: *mut u8) -> u32 => {
+		let r = this.ext.recent_block_hash(depth);
+		if let Some(ref r) = r {
+			this.memory.set(result, &r[..]).map_err(|_| UserError("Invalid attempt to set memory in ext_recent_block_hash"))?;
+		}
+		Ok(if r.is_some() { 1u32 } else { 0u32 })
+	},
+	ext_execution_context() -> u32 => {
+		Ok(this.ext.execution_context().as_u32())
+	},
+	ext_has_local_key(authority_data: *const u8) -> u32 => {
+		let mut authority = [0u8; 32];
+		this.memory.get_into(authority_data, &mut authority[..]).map_err(|_| UserError("Invalid attempt to get authority in ext_has_local_key"))?;
+		Ok(if this.ext.has_local_key(&AuthorityId(authority)) { 1 } else { 0 })
+	},
+	ext_offchain_timestamp() -> u64 => {
+		let offchain = this.ext.offchain()
+			.ok_or_else(|| UserError("Calling unavailable offchain API in ext_offchain_timestamp"))?;
+		Ok(offchain.timestamp().0)
+	},
+	// return u32::max_value() if the request can't be started.
+	ext_http_request_start(
+		method_data: *const u8,
+		method_len: u32,
+		uri_data: *const u8,
+		uri_len: u32,
+		meta_data: *const u8,
+		meta_len: u32
+	) -> u32 => {
+		let method = this.memory.get(method_data, method_len as usize).map_err(|_| UserError("Invalid attempt to get method in ext_http_request_start"))?;
+		let uri = this.memory.get(uri_data, uri_len as usize).map_err(|_| UserError("Invalid attempt to get uri in ext_http_request_start"))?;
+		let meta = this.memory.get(meta_data, meta_len as usize).map_err(|_| UserError("Invalid attempt to get meta in ext_http_request_start"))?;
+		let offchain = this.ext.offchain()
+			.ok_or_else(|| UserError("Calling unavailable offchain API in ext_http_request_start"))?;
+
+		let id = match (str::from_utf8(&method), str::from_utf8(&uri)) {
+			(Ok(method), Ok(uri)) => offchain.http_request_start(method, uri, &meta).ok(),
+			_ => None,
+		};
+		Ok(id.map_or(u32::max_value(), |id| id.0 as u32))
+	},
+	ext_http_request_add_header(
+		request_id: u32,
+		name_data: *const u8,
+		name_len: u32,
+		value_data: *const u8,
+		value_len: u32
+	) -> u32 => {
+		let name = this.memory.get(name_data, name_len as usize).map_err(|_| UserError("Invalid attempt to get name in ext_http_request_add_header"))?;
+		let value = this.memory.get(value_data, value_len as usize).map_err(|_| UserError("Invalid attempt to get value in ext_http_request_add_header"))?;
+		let offchain = this.ext.offchain()
+			.ok_or_else(|| UserError("Calling unavailable offchain API in ext_http_request_add_header"))?;
+
+		Ok(http_result(match (http_request_id(request_id), str::from_utf8(&name), str::from_utf8(&value)) {
+			(Ok(request_id), Ok(name), Ok(value)) => offchain.http_request_add_header(request_id, name, value),
+			_ => Err(HttpError::Invalid),
+		}))
+	},
+	ext_http_request_write_body(request_id: u32, chunk_data: *const u8, chunk_len: u32, deadline: u64) -> u32 => {
+		let chunk = this.memory.get(chunk_data, chunk_len as usize).map_err(|_| UserError("Invalid attempt to get chunk in ext_http_request_write_body"))?;
+		let offchain = this.ext.offchain()
+			.ok_or_else(|| UserError("Calling unavailable offchain API in ext_http_request_write_body"))?;
+
+		Ok(http_result(http_request_id(request_id).and_then(|request_id| offchain.http_request_write_body(
+			request_id,
+			&chunk,
+			Timestamp::deadline_from_u64(deadline),
+		))))
+	},
+	ext_http_response_wait(ids_data: *const u32, ids_len: u32, statuses_data: *mut u32, deadline: u64) => {
+		let ids = (0..ids_len)
+			.map(|i| this.memory.read_primitive(ids_data + i * 4))
+			.collect::<::std::result::Result<Vec<u32>, UserError>>()?;
+		let offchain = this.ext.offchain()
+			.ok_or_else(|| UserError("Calling unavailable offchain API in ext_http_response_wait"))?;
+
+		// ids that can't be decoded are reported as invalid requests
+		let statuses = {
+			let valid_ids = ids.iter().filter_map(|id| http_request_id(*id).ok()).collect::<Vec<_>>();
+			let mut valid_statuses = offchain.http_response_wait(&valid_ids, Timestamp::deadline_from_u64(deadline)).into_iter();
+			ids.iter()
+				.map(|id| match http_request_id(*id) {
+					Ok(_) => valid_statuses.next().map_or(0, |status| status.as_u32()),
+					Err(_) => 0,
+				})
+				.collect::<Vec<_>>()
+		};
+		for (i, status) in statuses.into_iter().enumerate() {
+			this.memory.write_primitive(statuses_data + i as u32 * 4, status)
+				.map_err(|_| UserError("Invalid attempt to write status in ext_http_response_wait"))?;
+		}
+		Ok(())
+	},
+	ext_http_response_headers(request_id: u32, written_out: *mut u32) -> *mut u8 => {
+		let offchain = this.ext.offchain()
+			.ok_or_else(|| UserError("Calling unavailable offchain API in ext_http_response_headers"))?;
+		let headers = http_request_id(request_id)
+			.map(|request_id| offchain.http_response_headers(request_id))
+			.unwrap_or_default()
+			.encode();
+
+		let offset = this.heap.allocate(headers.len() as u32) as u32;
+		this.memory.set(offset, &headers).map_err(|_| UserError("Invalid attempt to set memory in ext_http_response_headers"))?;
+		this.memory.write_primitive(written_out, headers.len() as u32)
+			.map_err(|_| UserError("Invalid attempt to write written_out in ext_http_response_headers"))?;
+		Ok(offset)
+	},
+	ext_http_response_read_body(
+		request_id: u32,
+		buffer_data: *mut u8,
+		buffer_len: u32,
+		deadline: u64,
+		written_out: *mut u32
+	) -> u32 => {
+		let mut buffer = vec![0u8; ::std::cmp::min(buffer_len, MAX_HTTP_READ_SIZE) as usize];
+		let offchain = this.ext.offchain()
+			.ok_or_else(|| UserError("Calling unavailable offchain API in ext_http_response_read_body"))?;
+
+		let result = http_request_id(request_id).and_then(|request_id| offchain.http_response_read_body(
+			request_id,
+			&mut buffer,
+			Timestamp::deadline_from_u64(deadline),
+		));
+		let written = *result.as_ref().unwrap_or(&0);
+		this.memory.set(buffer_data, &buffer[..written]).map_err(|_| UserError("Invalid attempt to set buffer in ext_http_response_read_body"))?;
+		this.memory.write_primitive(written_out, written as u32)
+			.map_err(|_| UserError("Invalid attempt to write written_out in ext_http_response_read_body"))?;
+		Ok(http_result(result.map(|_| ())))
+	},
 	ext_twox_128(data: *const u8, len: u32, out: *mut u8) => {
 		let result = if len == 0 {
 			let hashed = twox_128(&[0u8; 0]);
@@ -492,6 +651,27 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 			5
 		})
 	},
+	ext_ed25519_verify_preverified(
+		extrinsic_hash_data: *const u8,
+		msg_data: *const u8,
+		msg_len: u32,
+		sig_data: *const u8,
+		pubkey_data: *const u8
+	) -> u32 => {
+		let mut extrinsic_hash = [0u8; 32];
+		this.memory.get_into(extrinsic_hash_data, &mut extrinsic_hash[..]).map_err(|_| UserError("Invalid attempt to get extrinsic hash in ext_ed25519_verify_preverified"))?;
+		let mut sig = [0u8; 64];
+		this.memory.get_into(sig_data, &mut sig[..]).map_err(|_| UserError("Invalid attempt to get signature in ext_ed25519_verify_preverified"))?;
+		let mut pubkey = [0u8; 32];
+		this.memory.get_into(pubkey_data, &mut pubkey[..]).map_err(|_| UserError("Invalid attempt to get pubkey in ext_ed25519_verify_preverified"))?;
+		let msg = this.memory.get(msg_data, msg_len as usize).map_err(|_| UserError("Invalid attempt to get message in ext_ed25519_verify_preverified"))?;
+
+		Ok(if ed25519::verify_preverified(&extrinsic_hash, &sig, &msg, &pubkey) {
+			0
+		} else {
+			5
+		})
+	},
 	ext_sandbox_instantiate(
 		dispatch_thunk_idx: usize,
 		wasm_ptr: *const u8,
@@ -18,34 +18,42 @@
 //! Integration of the GRANDPA finality gadget into substrate.
 //!
 //! This is a long-running future that produces finality notifications.
+//! The voter's view of the current round is exposed via the `grandpa_roundState` RPC.
 // end::description[]
 
 extern crate finality_grandpa as grandpa;
 extern crate futures;
+extern crate jsonrpc_core as rpc;
+extern crate parking_lot;
+extern crate serde;
 extern crate substrate_client as client;
 extern crate sr_primitives as runtime_primitives;
 extern crate substrate_primitives;
 extern crate tokio;
 extern crate parity_codec as codec;
 
+#[macro_use]
+extern crate jsonrpc_macros;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 
 #[cfg(test)]
 extern crate substrate_network as network;
 
-#[cfg(test)]
-extern crate parking_lot;
-
 #[cfg(test)]
 extern crate substrate_keyring as keyring;
 
 use futures::prelude::*;
+use futures::future::{self, Loop};
 use futures::stream::Fuse;
 use futures::sync::mpsc;
-use client::{Client, ImportNotifications, backend::Backend, CallExecutor};
-use codec::{Encode, Decode};
-use runtime_primitives::traits::{As, NumberFor, Block as BlockT, Header as HeaderT};
+use client::{Client, ImportNotifications, Notification, backend::Backend, CallExecutor};
+use codec::{Encode, Decode, Input, Output};
+use runtime_primitives::traits::{
+	As, NumberFor, Block as BlockT, Header as HeaderT, Digest as DigestT, DigestItem as DigestItemT,
+};
 use runtime_primitives::generic::BlockId;
 use substrate_primitives::{ed25519, H256, AuthorityId, Blake2Hasher};
 use tokio::timer::Interval;
@@ -57,7 +65,14 @@ use std::collections::{VecDeque, HashMap};
 use std::sync::Arc;
 use std::time::{Instant, Duration};
 
+pub mod round_state;
+pub mod rpc_api;
+
+pub use round_state::{SharedRoundState, Step};
+pub use rpc_api::{Grandpa, GrandpaApi};
+
 const LAST_COMPLETED_KEY: &[u8] = b"grandpa_completed_round";
+const VOTER_SET_KEY: &[u8] = b"grandpa_voters";
 
 /// A GRANDPA message for a substrate chain.
 pub type Message<Block> = grandpa::Message<<Block as BlockT>::Hash>;
@@ -65,16 +80,89 @@ pub type Message<Block> = grandpa::Message<<Block as BlockT>::Hash>;
 pub type SignedMessage<Block> = grandpa::SignedMessage<<Block as BlockT>::Hash, ed25519::Signature, AuthorityId>;
 
 /// Configuration for the GRANDPA service.
+#[derive(Clone)]
 pub struct Config {
 	/// The expected duration for a message to be gossiped across the network.
 	pub gossip_duration: Duration,
-	/// The voters.
-	// TODO: make dynamic
-	pub voters: Vec<AuthorityId>,
 	/// The local signing key.
 	pub local_key: Option<Arc<ed25519::Pair>>,
 }
 
+/// The set of GRANDPA voters.
+///
+/// The set only changes at a block that signals a change of the authorities in its digest, once
+/// that block is finalized. The new set consists of the authorities at that block, excluding the
+/// ones disabled by the runtime at that block, and gets the next set id. Since all voters enact the
+/// change at the same block, they agree on the voters of every round.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoterSet {
+	/// The id of the set, incremented on every change.
+	pub set_id: u64,
+	/// The voters and their weights.
+	pub voters: HashMap<AuthorityId, usize>,
+}
+
+impl VoterSet {
+	/// The set that follows this one, given the authorities and the disabled authorities at the
+	/// block that enacts the change.
+	pub fn next(&self, authorities: &[AuthorityId], disabled: &[AuthorityId]) -> VoterSet {
+		VoterSet {
+			set_id: self.set_id + 1,
+			voters: authorities.iter()
+				.filter(|id| !disabled.contains(id))
+				.map(|id| (id.clone(), 1))
+				.collect(),
+		}
+	}
+}
+
+impl Encode for VoterSet {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		let mut voters: Vec<(AuthorityId, u64)> = self.voters.iter()
+			.map(|(id, weight)| (id.clone(), *weight as u64))
+			.collect();
+		voters.sort_by(|a, b| (a.0).0.cmp(&(b.0).0));
+		self.set_id.encode_to(dest);
+		voters.encode_to(dest);
+	}
+}
+
+impl Decode for VoterSet {
+	fn decode<I: Input>(input: &mut I) -> Option<Self> {
+		let set_id = u64::decode(input)?;
+		let voters: Vec<(AuthorityId, u64)> = Decode::decode(input)?;
+		Some(VoterSet {
+			set_id,
+			voters: voters.into_iter().map(|(id, weight)| (id, weight as usize)).collect(),
+		})
+	}
+}
+
+/// The voter set along with the progress of applying the changes of the set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoterSetState {
+	/// The current voter set.
+	pub voter_set: VoterSet,
+	/// The number of the last finalized block whose digest has been checked for the changes.
+	pub applied_up_to: u32,
+}
+
+impl Encode for VoterSetState {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		self.voter_set.encode_to(dest);
+		self.applied_up_to.encode_to(dest);
+	}
+}
+
+impl Decode for VoterSetState {
+	fn decode<I: Input>(input: &mut I) -> Option<Self> {
+		Some(VoterSetState {
+			voter_set: Decode::decode(input)?,
+			applied_up_to: Decode::decode(input)?,
+		})
+	}
+}
+
 /// Errors that can occur while voting in GRANDPA.
 #[derive(Debug)]
 pub enum Error {
@@ -88,6 +176,9 @@ pub enum Error {
 	CouldNotCompleteRound(::client::error::Error),
 	/// A timer failed to fire.
 	Timer(::tokio::timer::Error),
+	/// A block enacting a change of the voter set has been finalized. The voter must be restarted
+	/// with the new set.
+	VoterSetChanged(VoterSetState),
 }
 
 impl From<GrandpaError> for Error {
@@ -104,15 +195,15 @@ pub trait Network: Clone {
 	/// A stream of input messages for a topic.
 	type In: Stream<Item=Vec<u8>,Error=()>;
 
-	/// Get a stream of messages for a specific round. This stream should
+	/// Get a stream of messages for a specific round of a voter set. This stream should
 	/// never logically conclude.
-	fn messages_for(&self, round: u64) -> Self::In;
+	fn messages_for(&self, round: u64, set_id: u64) -> Self::In;
 
-	/// Send a message at a specific round out.
-	fn send_message(&self, round: u64, message: Vec<u8>);
+	/// Send a message at a specific round of a voter set out.
+	fn send_message(&self, round: u64, set_id: u64, message: Vec<u8>);
 
-	/// Clean up messages for a round.
-	fn drop_messages(&self, round: u64);
+	/// Clean up messages for a round of a voter set.
+	fn drop_messages(&self, round: u64, set_id: u64);
 }
 
 /// Something which can determine if a block is known.
@@ -154,8 +245,8 @@ impl<Block: BlockT, Status: BlockStatus<Block>, I: Stream> UntilImported<Block,
 		// how often to check if pending messages that are waiting for blocks to be
 		// imported can be checked.
 		//
-		// the import notifications interval takes care of most of this; this is
-		// used in the event of missed import notifications
+		// the import notifications take care of most of this; this is used in the
+		// event of dropped import notifications that haven't been reported yet
 		const CHECK_PENDING_INTERVAL: Duration = Duration::from_secs(5);
 		let now = Instant::now();
 
@@ -208,26 +299,31 @@ impl<Block: BlockT, Status: BlockStatus<Block>, I> Stream for UntilImported<Bloc
 			}
 		}
 
+		let mut check_pending = false;
 		loop {
 			match self.import_notifications.poll() {
 				Err(_) => return Err(Error::Network(format!("Failed to get new message"))),
 				Ok(Async::Ready(None)) => return Ok(Async::Ready(None)),
-				Ok(Async::Ready(Some(notification))) => {
+				Ok(Async::Ready(Some(Notification::Item(notification)))) => {
 					// new block imported. queue up all messages tied to that hash.
 					if let Some(messages) = self.pending.remove(&notification.hash) {
 						self.ready.extend(messages);
 				 	}
 				}
+				Ok(Async::Ready(Some(Notification::Lagged(_)))) => {
+					// import notifications have been dropped. check all pending messages
+					// right away instead of waiting for the interval.
+					check_pending = true;
+				}
 				Ok(Async::NotReady) => break,
 			}
 		}
 
-		let mut update_interval = false;
 		while let Async::Ready(Some(_)) = self.check_pending.poll().map_err(Error::Timer)? {
-			update_interval = true;
+			check_pending = true;
 		}
 
-		if update_interval {
+		if check_pending {
 			let mut known_keys = Vec::new();
 			for &block_hash in self.pending.keys() {
 				if let Some(number) = self.status_check.block_number(block_hash)? {
@@ -271,6 +367,7 @@ impl<Block: BlockT, Status: BlockStatus<Block>, I> Stream for UntilImported<Bloc
 // clears the network messages for inner round on drop.
 struct ClearOnDrop<I, N: Network> {
 	round: u64,
+	set_id: u64,
 	inner: I,
 	network: N,
 }
@@ -294,7 +391,7 @@ impl<I: Sink, N: Network> Sink for ClearOnDrop<I, N> {
 
 impl<I, N: Network> Drop for ClearOnDrop<I, N> {
 	fn drop(&mut self) {
-		self.network.drop_messages(self.round);
+		self.network.drop_messages(self.round, self.set_id);
 	}
 }
 
@@ -336,6 +433,7 @@ fn outgoing_messages<Block: BlockT, N: Network>(
 	local_key: Option<Arc<ed25519::Pair>>,
 	voters: Vec<AuthorityId>,
 	round: u64,
+	set_id: u64,
 	network: N,
 ) -> (
 	impl Stream<Item=SignedMessage<Block>,Error=Error>,
@@ -360,7 +458,7 @@ fn outgoing_messages<Block: BlockT, N: Network>(
 				};
 
 				// forward to network.
-				network.send_message(round, signed.encode());
+				network.send_message(round, set_id, signed.encode());
 				Some(signed)
 			} else {
 				None
@@ -380,9 +478,48 @@ fn outgoing_messages<Block: BlockT, N: Network>(
 /// The environment we run GRANDPA in.
 pub struct Environment<B, E, Block: BlockT, N: Network> {
 	inner: Arc<Client<B, E, Block>>,
-	voters: HashMap<AuthorityId, usize>,
+	voter_set: VoterSet,
 	config: Config,
 	network: N,
+	round_state: SharedRoundState<Block::Hash>,
+}
+
+impl<B, E, Block: BlockT<Hash=H256>, N: Network> Environment<B, E, Block, N> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	// the earliest block on the chain ending at `head` and not yet finalized, down to `base`, that
+	// signals a change of the authorities in its digest. the change is enacted once the block is
+	// finalized, so the current set must not finalize any of its descendants.
+	fn earliest_change(&self, base: Block::Hash, head: Block::Header)
+		-> Result<Option<(Block::Hash, u32)>, client::error::Error> where NumberFor<Block>: As<u32>
+	{
+		let finalized_number: u32 = self.inner.info()?.chain.finalized_number.as_();
+		let mut earliest = None;
+		let mut header = head;
+		loop {
+			let number: u32 = header.number().as_();
+			if number <= finalized_number {
+				break;
+			}
+
+			let hash = header.hash();
+			if header.digest().logs().iter().any(|item| item.as_authorities_change().is_some()) {
+				earliest = Some((hash, number));
+			}
+			if hash == base {
+				break;
+			}
+
+			let parent_hash = header.parent_hash().clone();
+			header = match self.inner.header(&BlockId::Hash(parent_hash))? {
+				Some(header) => header,
+				None => break,
+			};
+		}
+
+		Ok(earliest)
+	}
 }
 
 impl<Block: BlockT<Hash=H256>, B, E, N> grandpa::Chain<Block::Hash> for Environment<B, E, Block, N> where
@@ -424,8 +561,17 @@ impl<Block: BlockT<Hash=H256>, B, E, N> grandpa::Chain<Block::Hash> for Environm
 			Ok(Some(hash)) => {
 				let header = self.inner.header(&BlockId::Hash(hash)).ok()?
 					.expect("Header known to exist after `best_containing` call; qed");
-
-				Some((hash, header.number().as_()))
+				let number = header.number().as_();
+
+				// votes of this set must not go beyond a block that changes the set.
+				match self.earliest_change(block, header) {
+					Ok(Some(change)) => Some(change),
+					Ok(None) => Some((hash, number)),
+					Err(e) => {
+						debug!(target: "afg", "Encountered error looking for voter set changes up to {:?}: {:?}", hash, e);
+						None
+					}
+				}
 			}
 			Ok(None) => None,
 			Err(e) => {
@@ -459,26 +605,42 @@ impl<B, E, Block: BlockT<Hash=H256>, N> voter::Environment<Block::Hash> for Envi
 		use tokio::timer::Delay;
 
 		let now = Instant::now();
-		let prevote_timer = Delay::new(now + self.config.gossip_duration * 2);
-		let precommit_timer = Delay::new(now + self.config.gossip_duration * 4);
+		let prevote_at = now + self.config.gossip_duration * 2;
+		let precommit_at = now + self.config.gossip_duration * 4;
+		self.round_state.start_round(round, prevote_at, precommit_at);
+
+		let round_state = self.round_state.clone();
+		let prevote_timer = Delay::new(prevote_at)
+			.inspect(move |_| round_state.timer_fired(round, Step::Precommit));
+		let round_state = self.round_state.clone();
+		let precommit_timer = Delay::new(precommit_at)
+			.inspect(move |_| round_state.timer_fired(round, Step::Completing));
+
+		let set_id = self.voter_set.set_id;
+		let voters = self.voter_set.voters.clone();
+		let active: Vec<_> = voters.keys().cloned().collect();
 
 		// TODO: dispatch this with `mpsc::spawn`.
+		let round_state = self.round_state.clone();
 		let incoming = checked_message_stream::<Block, _>(
-			self.network.messages_for(round),
-			self.config.voters.clone(),
-		);
+			self.network.messages_for(round, set_id),
+			active.clone(),
+		).inspect(move |msg| round_state.note_vote(round, &msg.id, &msg.message));
 
 		let (out_rx, outgoing) = outgoing_messages::<Block, _>(
 			self.config.local_key.clone(),
-			self.config.voters.clone(),
+			active,
 			round,
+			set_id,
 			self.network.clone(),
 		);
+		let round_state = self.round_state.clone();
+		let out_rx = out_rx.inspect(move |msg| round_state.note_vote(round, &msg.id, &msg.message));
 
 		// schedule incoming messages from the network to be held until
 		// corresponding blocks are imported.
 		let incoming = UntilImported::new(
-			self.inner.import_notification_stream(),
+			self.inner.import_notification_stream("grandpa"),
 			self.inner.clone(),
 			incoming,
 		);
@@ -489,6 +651,7 @@ impl<B, E, Block: BlockT<Hash=H256>, N> voter::Environment<Block::Hash> for Envi
 		// schedule network message cleanup when sink drops.
 		let outgoing = Box::new(ClearOnDrop {
 			round,
+			set_id,
 			network: self.network.clone(),
 			inner: outgoing,
 		});
@@ -496,13 +659,14 @@ impl<B, E, Block: BlockT<Hash=H256>, N> voter::Environment<Block::Hash> for Envi
 		voter::RoundData {
 			prevote_timer: Box::new(prevote_timer.map_err(Error::Timer)),
 			precommit_timer: Box::new(precommit_timer.map_err(Error::Timer)),
-			voters: self.voters.clone(),
+			voters,
 			incoming,
 			outgoing,
 		}
 	}
 
 	fn completed(&self, round: u64, state: RoundState<Block::Hash>) -> Result<(), Self::Error> {
+		self.round_state.complete_round(round, state.finalized.clone(), state.estimate.clone());
 		let encoded_state = (round, state).encode();
 		if let Err(e) = self.inner.backend()
 			.insert_aux(&[(LAST_COMPLETED_KEY, &encoded_state[..])], &[])
@@ -521,35 +685,44 @@ impl<B, E, Block: BlockT<Hash=H256>, N> voter::Environment<Block::Hash> for Envi
 		}
 
 		// we return without error in all cases because not being able to finalize is
-		// non-fatal.
+		// non-fatal. changes of the voter set are enacted by `watch_voter_set_changes` once
+		// the notification of the finalized block is received.
 		Ok(())
 	}
 
 	fn prevote_equivocation(
 		&self,
-		_round: u64,
+		round: u64,
 		equivocation: ::grandpa::Equivocation<Self::Id, Prevote<Block::Hash>, Self::Signature>
 	) {
 		warn!(target: "afg", "Detected prevote equivocation in the finality worker: {:?}", equivocation);
+		self.round_state.note_equivocation(round, &equivocation.identity);
 		// nothing yet; this could craft misbehavior reports of some kind.
 	}
 
 	fn precommit_equivocation(
 		&self,
-		_round: u64,
+		round: u64,
 		equivocation: Equivocation<Self::Id, Precommit<Block::Hash>, Self::Signature>
 	) {
 		warn!(target: "afg", "Detected precommit equivocation in the finality worker: {:?}", equivocation);
+		self.round_state.note_equivocation(round, &equivocation.identity);
 		// nothing yet
 	}
 }
 
 /// Run a GRANDPA voter as a task. The returned future should be executed in a tokio runtime.
+///
+/// The voter keeps the `round_state` up to date, so that it can be served by the `Grandpa` RPC API.
+/// The service serves the round state it exposes via `Service::grandpa_round_state`.
+/// The given `voters` form the genesis voter set, used until the first change of the set is
+/// enacted. The voter is restarted with the new set on every change.
 pub fn run_grandpa<B, E, Block: BlockT<Hash=H256>, N>(
 	config: Config,
 	client: Arc<Client<B, E, Block>>,
 	voters: HashMap<AuthorityId, usize>,
 	network: N,
+	round_state: SharedRoundState<Block::Hash>,
 ) -> Result<impl Future<Item=(),Error=()>,client::error::Error> where
 	Block::Hash: Ord,
 	B: Backend<Block, Blake2Hasher> + 'static,
@@ -558,17 +731,140 @@ pub fn run_grandpa<B, E, Block: BlockT<Hash=H256>, N>(
 	N::In: 'static,
 	NumberFor<Block>: As<u32>,
 {
-	let chain_info = client.info()?;
+	let voter_set_state = match client.backend().get_aux(VOTER_SET_KEY)? {
+		None => VoterSetState { voter_set: VoterSet { set_id: 0, voters }, applied_up_to: 0 },
+		Some(raw) => VoterSetState::decode(&mut &raw[..])
+			.ok_or_else(|| ::client::error::ErrorKind::Backend(
+				format!("GRANDPA voter set kept in invalid format")
+			))?
+	};
+
+	let voters_work = future::loop_fn(voter_set_state, move |mut voter_set_state| {
+		// changes of the set finalized while the voter hasn't been running are enacted first
+		let voter = apply_finalized_changes(&*client, &mut voter_set_state)
+			.map_err(|e| Error::Blockchain(format!("{:?}", e)))
+			.and_then(|_| start_voter(
+				config.clone(),
+				client.clone(),
+				voter_set_state.voter_set.clone(),
+				network.clone(),
+				round_state.clone(),
+			));
+		let voter = match voter {
+			Ok(voter) => voter,
+			Err(e) => return future::Either::A(future::err(e)),
+		};
+
+		let set_changes = watch_voter_set_changes(client.clone(), voter_set_state);
+		future::Either::B(voter.select(set_changes).then(|result| match result {
+			Ok(_) => Ok(Loop::Break(())),
+			Err((Error::VoterSetChanged(voter_set_state), _)) => Ok(Loop::Continue(voter_set_state)),
+			Err((e, _)) => Err(e),
+		}))
+	});
+
+	Ok(voters_work.map_err(|e| warn!("GRANDPA Voter failed: {:?}", e)))
+}
+
+// resolves with `Error::VoterSetChanged` once a block that changes the voter set is finalized,
+// no matter if it has been finalized by the local voter or imported with a justification.
+fn watch_voter_set_changes<B, E, Block: BlockT<Hash=H256>>(
+	client: Arc<Client<B, E, Block>>,
+	mut voter_set_state: VoterSetState,
+) -> impl Future<Item=(), Error=Error> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	NumberFor<Block>: As<u32>,
+{
+	use client::BlockchainEvents;
+
+	client.finality_notification_stream("grandpa-voter-set")
+		.map_err(|()| Error::Blockchain(format!("Finality notifications stream has failed")))
+		.for_each(move |_| match apply_finalized_changes(&*client, &mut voter_set_state) {
+			Ok(false) => Ok(()),
+			Ok(true) => Err(Error::VoterSetChanged(voter_set_state.clone())),
+			Err(e) => Err(Error::Blockchain(format!("{:?}", e))),
+		})
+}
+
+// apply the changes of the voter set signalled by the blocks finalized since the last call.
+// returns true if the set has changed, in which case the new set starts voting from round 0 on
+// top of the block that has enacted the last change.
+fn apply_finalized_changes<B, E, Block: BlockT<Hash=H256>>(
+	client: &Client<B, E, Block>,
+	voter_set_state: &mut VoterSetState,
+) -> Result<bool, client::error::Error> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	NumberFor<Block>: As<u32>,
+{
+	let finalized_number: u32 = client.info()?.chain.finalized_number.as_();
+	if voter_set_state.applied_up_to >= finalized_number {
+		return Ok(false);
+	}
+
+	let mut enacted_at = None;
+	while voter_set_state.applied_up_to < finalized_number {
+		let number = voter_set_state.applied_up_to + 1;
+		let id = BlockId::<Block>::Number(<NumberFor<Block> as As<u32>>::sa(number));
+		let header = client.header(&id)?
+			.ok_or_else(|| client::error::ErrorKind::UnknownBlock(format!("{}", id)))?;
+		if header.digest().logs().iter().any(|item| item.as_authorities_change().is_some()) {
+			let authorities = client.authorities_at(&id)?;
+			let disabled = client.disabled_validators_at(&id)?;
+			voter_set_state.voter_set = voter_set_state.voter_set.next(&authorities, &disabled);
+			enacted_at = Some((header.hash(), number));
+		}
+		voter_set_state.applied_up_to = number;
+	}
+
+	let encoded_set_state = voter_set_state.encode();
+	match enacted_at {
+		Some(enacted_at) => {
+			info!(target: "afg", "Enacting GRANDPA voter set {} at block {:?}",
+				voter_set_state.voter_set.set_id, enacted_at);
+			let encoded_state = (0u64, RoundState::genesis(enacted_at)).encode();
+			client.backend().insert_aux(&[
+				(VOTER_SET_KEY, &encoded_set_state[..]),
+				(LAST_COMPLETED_KEY, &encoded_state[..]),
+			], &[])?;
+			Ok(true)
+		},
+		None => {
+			client.backend().insert_aux(&[(VOTER_SET_KEY, &encoded_set_state[..])], &[])?;
+			Ok(false)
+		},
+	}
+}
+
+// start a voter of the given set from the last completed round.
+fn start_voter<B, E, Block: BlockT<Hash=H256>, N>(
+	config: Config,
+	client: Arc<Client<B, E, Block>>,
+	voter_set: VoterSet,
+	network: N,
+	round_state: SharedRoundState<Block::Hash>,
+) -> Result<voter::Voter<Block::Hash, Environment<B, E, Block, N>>, Error> where
+	Block::Hash: Ord,
+	B: Backend<Block, Blake2Hasher> + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + 'static,
+	N: Network + 'static,
+	N::In: 'static,
+	NumberFor<Block>: As<u32>,
+{
+	let blockchain_error = |e: client::error::Error| Error::Blockchain(format!("{:?}", e));
+
+	let chain_info = client.info().map_err(&blockchain_error)?;
 	let genesis_hash = chain_info.chain.genesis_hash;
 	let last_finalized = (
 		chain_info.chain.finalized_hash,
 		chain_info.chain.finalized_number.as_()
 	);
 
-	let (last_round_number, last_state) = match client.backend().get_aux(LAST_COMPLETED_KEY)? {
+	let (last_round_number, last_state) = match client.backend().get_aux(LAST_COMPLETED_KEY).map_err(&blockchain_error)? {
 		None => (0, RoundState::genesis((genesis_hash, 0))),
 		Some(raw) => <(u64, RoundState<Block::Hash>)>::decode(&mut &raw[..])
-			.ok_or_else(|| ::client::error::ErrorKind::Backend(
+			.ok_or_else(|| Error::Blockchain(
 				format!("Last GRANDPA round state kept in invalid format")
 			))?
 	};
@@ -576,18 +872,17 @@ pub fn run_grandpa<B, E, Block: BlockT<Hash=H256>, N>(
 	let environment = Arc::new(Environment {
 		inner: client,
 		config,
-		voters,
+		voter_set,
 		network,
+		round_state,
 	});
 
-	let voter = voter::Voter::new(
+	Ok(voter::Voter::new(
 		environment,
 		last_round_number,
 		last_state,
 		last_finalized,
-	);
-
-	Ok(voter.map_err(|e| warn!("GRANDPA Voter failed: {:?}", e)))
+	))
 }
 
 #[cfg(test)]
@@ -614,11 +909,11 @@ mod tests {
 		}
 	}
 
-	fn round_to_topic(round: u64) -> Hash {
+	fn round_to_topic(round: u64, set_id: u64) -> Hash {
 		let mut hash = Hash::default();
-		round.using_encoded(|s| {
+		(round, set_id).using_encoded(|s| {
 			let raw = hash.as_mut();
-			raw[..8].copy_from_slice(s);
+			raw[..16].copy_from_slice(s);
 		});
 		hash
 	}
@@ -626,9 +921,9 @@ mod tests {
 	impl Network for TestGrandpaNetwork {
 		type In = Box<Stream<Item=Vec<u8>,Error=()>>;
 
-		fn messages_for(&self, round: u64) -> Self::In {
+		fn messages_for(&self, round: u64, set_id: u64) -> Self::In {
 			let messages = self.inner.lock().peer(self.peer_id)
-				.with_spec(|spec, _| spec.gossip.messages_for(round_to_topic(round)));
+				.with_spec(|spec, _| spec.gossip.messages_for(round_to_topic(round, set_id)));
 
 			let messages = messages.map_err(
 				move |_| panic!("Messages for round {} dropped too early", round)
@@ -637,14 +932,14 @@ mod tests {
 			Box::new(messages)
 		}
 
-		fn send_message(&self, round: u64, message: Vec<u8>) {
+		fn send_message(&self, round: u64, set_id: u64, message: Vec<u8>) {
 			let mut inner = self.inner.lock();
-			inner.peer(self.peer_id).gossip_message(round_to_topic(round), message);
+			inner.peer(self.peer_id).gossip_message(round_to_topic(round, set_id), message);
 			inner.route();
 		}
 
-		fn drop_messages(&self, round: u64) {
-			let topic = round_to_topic(round);
+		fn drop_messages(&self, round: u64, set_id: u64) {
+			let topic = round_to_topic(round, set_id);
 			self.inner.lock().peer(self.peer_id)
 				.with_spec(|spec, _| spec.gossip.collect_garbage(|t| t == &topic));
 		}
@@ -653,6 +948,59 @@ mod tests {
 	const TEST_GOSSIP_DURATION: Duration = Duration::from_millis(500);
 	const TEST_ROUTING_INTERVAL: Duration = Duration::from_millis(50);
 
+	#[test]
+	fn next_voter_set_excludes_disabled_voters() {
+		let authorities: Vec<_> = [Keyring::Alice, Keyring::Bob, Keyring::Charlie, Keyring::Dave].iter()
+			.map(|key| AuthorityId(key.to_raw_public()))
+			.collect();
+		let genesis = VoterSet { set_id: 0, voters: authorities.iter().map(|id| (id.clone(), 1)).collect() };
+
+		let next = genesis.next(&authorities, &authorities[1..2]);
+		assert_eq!(next.set_id, 1);
+		assert_eq!(next.voters.len(), 3);
+		assert!(!next.voters.contains_key(&authorities[1]));
+		assert_eq!(next.voters.values().sum::<usize>(), 3);
+
+		let next = next.next(&authorities, &[]);
+		assert_eq!(next, VoterSet { set_id: 2, voters: genesis.voters.clone() });
+	}
+
+	#[test]
+	fn voter_set_encoding_roundtrips() {
+		let voter_set = VoterSet {
+			set_id: 7,
+			voters: [(Keyring::Alice, 1), (Keyring::Bob, 3)].iter()
+				.map(|&(ref key, weight)| (AuthorityId(key.to_raw_public()), weight))
+				.collect(),
+		};
+
+		let encoded = voter_set.encode();
+		assert_eq!(VoterSet::decode(&mut &encoded[..]), Some(voter_set.clone()));
+
+		let voter_set_state = VoterSetState { voter_set, applied_up_to: 42 };
+		let encoded = voter_set_state.encode();
+		assert_eq!(VoterSetState::decode(&mut &encoded[..]), Some(voter_set_state));
+	}
+
+	#[test]
+	fn finalized_blocks_are_checked_for_voter_set_changes() {
+		let mut net = TestNet::new(1);
+		net.peer(0).push_blocks(10, false);
+		let client = net.peer(0).client().clone();
+		let voters: HashMap<_, _> = [(AuthorityId(Keyring::Alice.to_raw_public()), 1)].iter().cloned().collect();
+		let mut voter_set_state = VoterSetState { voter_set: VoterSet { set_id: 0, voters }, applied_up_to: 0 };
+
+		// blocks finalized without the local voter are checked too
+		assert_eq!(apply_finalized_changes(&*client, &mut voter_set_state).unwrap(), false);
+		client.finalize_block(BlockId::Number(5), true).unwrap();
+		assert_eq!(apply_finalized_changes(&*client, &mut voter_set_state).unwrap(), false);
+		assert_eq!(voter_set_state.applied_up_to, 5);
+		assert_eq!(voter_set_state.voter_set.set_id, 0);
+
+		let stored = client.backend().get_aux(VOTER_SET_KEY).unwrap().unwrap();
+		assert_eq!(VoterSetState::decode(&mut &stored[..]), Some(voter_set_state));
+	}
+
 	#[test]
 	fn finalize_20_unanimous_3_peers() {
 		let mut net = TestNet::new(3);
@@ -676,19 +1024,20 @@ mod tests {
 		for (peer_id, key) in peers {
 			let client = net.lock().peer(*peer_id).client().clone();
 			finality_notifications.push(
-				client.finality_notification_stream()
+				client.finality_notification_stream("test")
+					.filter_map(|n| n.item())
 					.take_while(|n| Ok(n.header.number() < &20))
 					.for_each(move |_| Ok(()))
 			);
 			let voter = run_grandpa(
 				Config {
 					gossip_duration: TEST_GOSSIP_DURATION,
-					voters: voters.clone(),
 					local_key: Some(Arc::new(key.clone().into())),
 				},
 				client,
 				voters.iter().map(|&id| (id, 1)).collect(),
 				TestGrandpaNetwork::new(net.clone(), *peer_id),
+				Default::default(),
 			).expect("all in order with client and network");
 
 			runtime.spawn(voter);
@@ -735,19 +1084,20 @@ mod tests {
 		for (peer_id, local_key) in all_peers {
 			let client = net.lock().peer(peer_id).client().clone();
 			finality_notifications.push(
-				client.finality_notification_stream()
+				client.finality_notification_stream("test")
+					.filter_map(|n| n.item())
 					.take_while(|n| Ok(n.header.number() < &20))
 					.for_each(move |_| Ok(()))
 			);
 			let voter = run_grandpa(
 				Config {
 					gossip_duration: TEST_GOSSIP_DURATION,
-					voters: voters.keys().cloned().collect(),
 					local_key,
 				},
 				client,
 				voters.clone(),
 				TestGrandpaNetwork::new(net.clone(), peer_id),
+				Default::default(),
 			).expect("all in order with client and network");
 
 			runtime.spawn(voter);
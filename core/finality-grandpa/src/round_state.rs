@@ -0,0 +1,265 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Voter's view of the current round, shared with the RPC.
+//!
+//! The voter builds the new snapshot on every change aside and then only swaps the pointer to
+//! the latest snapshot, while readers only clone this pointer. The pointer is the only thing
+//! guarded by the lock shared by the voter and the readers, so that inspecting the round state
+//! never holds up the voter.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use grandpa::{Message, Prevote, Precommit};
+use parking_lot::{Mutex, RwLock};
+use substrate_primitives::AuthorityId;
+
+/// Step of the round, as driven by the round timers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Step {
+	/// Collecting prevotes until the prevote timer fires.
+	Prevote,
+	/// Collecting precommits until the precommit timer fires.
+	Precommit,
+	/// Both timers have fired, waiting for the round to become completable.
+	Completing,
+}
+
+/// Votes of a single authority in the round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorityVotes<Hash> {
+	/// Block hash and number the authority has prevoted for.
+	pub prevote: Option<(Hash, u32)>,
+	/// Block hash and number the authority has precommitted to.
+	pub precommit: Option<(Hash, u32)>,
+	/// Number of detected equivocations of the authority.
+	pub equivocations: u32,
+}
+
+impl<Hash> Default for AuthorityVotes<Hash> {
+	fn default() -> Self {
+		AuthorityVotes {
+			prevote: None,
+			precommit: None,
+			equivocations: 0,
+		}
+	}
+}
+
+/// State of the last round that has been completed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletedRound<Hash> {
+	/// Number of the round.
+	pub round: u64,
+	/// Block finalized in the round.
+	pub finalized: Option<(Hash, u32)>,
+	/// Estimate of the finalized block at the end of the round.
+	pub estimate: Option<(Hash, u32)>,
+}
+
+/// Snapshot of the voter's view of the current round.
+#[derive(Debug, Clone)]
+pub struct RoundSnapshot<Hash> {
+	/// Number of the round.
+	pub round: u64,
+	/// Current step of the round.
+	pub step: Step,
+	/// Time the round has been started at.
+	pub started: Instant,
+	/// Time the prevote timer fires at.
+	pub prevote_timer: Instant,
+	/// Time the precommit timer fires at.
+	pub precommit_timer: Instant,
+	/// Votes collected in the round, by authority.
+	pub votes: HashMap<AuthorityId, AuthorityVotes<Hash>>,
+	/// The last completed round.
+	pub last_completed: Option<CompletedRound<Hash>>,
+}
+
+/// Round state shared by the voter and the RPC.
+pub struct SharedRoundState<Hash> {
+	latest: Arc<RwLock<Option<Arc<RoundSnapshot<Hash>>>>>,
+	// serializes the updates, which are made by the voter only
+	updates: Arc<Mutex<()>>,
+}
+
+impl<Hash> Clone for SharedRoundState<Hash> {
+	fn clone(&self) -> Self {
+		SharedRoundState {
+			latest: self.latest.clone(),
+			updates: self.updates.clone(),
+		}
+	}
+}
+
+impl<Hash> Default for SharedRoundState<Hash> {
+	fn default() -> Self {
+		SharedRoundState {
+			latest: Default::default(),
+			updates: Default::default(),
+		}
+	}
+}
+
+impl<Hash: Clone> SharedRoundState<Hash> {
+	/// Get the latest snapshot. `None` if the voter hasn't started any round yet.
+	pub fn snapshot(&self) -> Option<Arc<RoundSnapshot<Hash>>> {
+		self.latest.read().clone()
+	}
+
+	/// Note that the round has been started.
+	pub(crate) fn start_round(&self, round: u64, prevote_timer: Instant, precommit_timer: Instant) {
+		self.replace(|latest| {
+			if latest.map_or(false, |snapshot| snapshot.round > round) {
+				return None;
+			}
+
+			Some(RoundSnapshot {
+				round,
+				step: Step::Prevote,
+				started: Instant::now(),
+				prevote_timer,
+				precommit_timer,
+				votes: HashMap::new(),
+				last_completed: latest.and_then(|snapshot| snapshot.last_completed.clone()),
+			})
+		});
+	}
+
+	/// Note that the timer of the round has fired.
+	pub(crate) fn timer_fired(&self, round: u64, step: Step) {
+		self.update(round, |snapshot| if snapshot.step != Step::Completing {
+			snapshot.step = step;
+		});
+	}
+
+	/// Note the vote, received or cast in the round.
+	pub(crate) fn note_vote(&self, round: u64, id: &AuthorityId, message: &Message<Hash>) {
+		self.update(round, |snapshot| {
+			let votes = snapshot.votes.entry(*id).or_insert_with(Default::default);
+			match *message {
+				Message::Prevote(Prevote { ref target_hash, target_number }) =>
+					votes.prevote = Some((target_hash.clone(), target_number)),
+				Message::Precommit(Precommit { ref target_hash, target_number }) =>
+					votes.precommit = Some((target_hash.clone(), target_number)),
+			}
+		});
+	}
+
+	/// Note the equivocation of the authority in the round.
+	pub(crate) fn note_equivocation(&self, round: u64, id: &AuthorityId) {
+		self.update(round, |snapshot| {
+			snapshot.votes.entry(*id).or_insert_with(Default::default).equivocations += 1;
+		});
+	}
+
+	/// Note that the round has been completed.
+	pub(crate) fn complete_round(&self, round: u64, finalized: Option<(Hash, u32)>, estimate: Option<(Hash, u32)>) {
+		self.replace(|latest| {
+			let latest = latest?;
+			if latest.last_completed.as_ref().map_or(false, |completed| completed.round >= round) {
+				return None;
+			}
+
+			let mut snapshot = latest.clone();
+			snapshot.last_completed = Some(CompletedRound { round, finalized, estimate });
+			Some(snapshot)
+		});
+	}
+
+	/// Update the snapshot of the round, if it is still the current one.
+	fn update<F: FnOnce(&mut RoundSnapshot<Hash>)>(&self, round: u64, f: F) {
+		self.replace(|latest| {
+			let mut snapshot = latest.filter(|snapshot| snapshot.round == round)?.clone();
+			f(&mut snapshot);
+			Some(snapshot)
+		});
+	}
+
+	/// Build the new snapshot from the latest one and make it the latest. The snapshot is built
+	/// without holding the lock shared with the readers. `None` leaves the latest snapshot as is.
+	fn replace<F: FnOnce(Option<&RoundSnapshot<Hash>>) -> Option<RoundSnapshot<Hash>>>(&self, f: F) {
+		let _update = self.updates.lock();
+		let latest = self.snapshot();
+		if let Some(snapshot) = f(latest.as_ref().map(|snapshot| &**snapshot)) {
+			*self.latest.write() = Some(Arc::new(snapshot));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_primitives::H256;
+
+	fn vote(number: u32) -> Message<H256> {
+		Message::Prevote(Prevote { target_hash: H256::from(number as u64), target_number: number })
+	}
+
+	#[test]
+	fn votes_of_current_round_are_recorded() {
+		let state = SharedRoundState::<H256>::default();
+		let now = Instant::now();
+		let alice = AuthorityId([1; 32]);
+		assert!(state.snapshot().is_none());
+
+		state.start_round(1, now, now);
+		state.note_vote(1, &alice, &vote(10));
+		let first = state.snapshot().unwrap();
+
+		state.note_vote(0, &alice, &vote(5));
+		state.note_equivocation(1, &alice);
+		state.timer_fired(1, Step::Precommit);
+
+		// previously taken snapshot isn't affected by updates
+		assert_eq!(first.votes[&alice].equivocations, 0);
+		assert_eq!(first.step, Step::Prevote);
+
+		let second = state.snapshot().unwrap();
+		assert_eq!(second.step, Step::Precommit);
+		assert_eq!(second.votes[&alice], AuthorityVotes {
+			prevote: Some((H256::from(10), 10)),
+			precommit: None,
+			equivocations: 1,
+		});
+	}
+
+	#[test]
+	fn completed_round_is_kept_across_rounds() {
+		let state = SharedRoundState::<H256>::default();
+		let now = Instant::now();
+		let alice = AuthorityId([1; 32]);
+
+		state.start_round(1, now, now);
+		state.note_vote(1, &alice, &vote(10));
+		state.complete_round(1, Some((H256::from(10), 10)), None);
+		state.start_round(2, now, now);
+		// late notifications of the previous round are ignored
+		state.start_round(1, now, now);
+		state.timer_fired(1, Step::Completing);
+
+		let snapshot = state.snapshot().unwrap();
+		assert_eq!(snapshot.round, 2);
+		assert_eq!(snapshot.step, Step::Prevote);
+		assert!(snapshot.votes.is_empty());
+		assert_eq!(snapshot.last_completed.as_ref().unwrap().round, 1);
+	}
+}
@@ -0,0 +1,191 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! GRANDPA RPC API, exposing the voter's view of the current round.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use rpc::Result as RpcResult;
+use serde::Serialize;
+use substrate_primitives::AuthorityId;
+
+use round_state::{AuthorityVotes, CompletedRound, SharedRoundState, Step};
+
+/// Block voted for in the round, with the number of votes for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Proposal<Hash> {
+	/// Hash of the block.
+	pub hash: Hash,
+	/// Number of the block.
+	pub number: u32,
+	/// Number of the authorities that have prevoted for the block.
+	pub prevotes: u32,
+	/// Number of the authorities that have precommitted to the block.
+	pub precommits: u32,
+}
+
+/// Votes of the authority in the round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Votes<Hash> {
+	/// The authority.
+	pub authority: AuthorityId,
+	/// Votes of the authority.
+	#[serde(flatten)]
+	pub votes: AuthorityVotes<Hash>,
+}
+
+/// The voter's view of the current round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundInfo<Hash> {
+	/// Number of the round.
+	pub round: u64,
+	/// Current step of the round.
+	pub step: Step,
+	/// Milliseconds elapsed since the round has been started.
+	pub elapsed_ms: u64,
+	/// Milliseconds until the prevote timer fires, 0 if it has already fired.
+	pub prevote_timeout_ms: u64,
+	/// Milliseconds until the precommit timer fires, 0 if it has already fired.
+	pub precommit_timeout_ms: u64,
+	/// Blocks voted for in the round, ordered by the block number.
+	pub proposals: Vec<Proposal<Hash>>,
+	/// Votes collected in the round, by authority.
+	pub votes: Vec<Votes<Hash>>,
+	/// The last completed round.
+	pub last_completed: Option<CompletedRound<Hash>>,
+}
+
+build_rpc_trait! {
+	/// GRANDPA RPC API.
+	pub trait GrandpaApi<Hash> {
+		/// Get the voter's view of the current round. `None` if the voter hasn't started yet.
+		#[rpc(name = "grandpa_roundState")]
+		fn round_state(&self) -> RpcResult<Option<RoundInfo<Hash>>>;
+	}
+}
+
+/// GRANDPA API, reading the round state shared by the voter.
+pub struct Grandpa<Hash> {
+	round_state: SharedRoundState<Hash>,
+}
+
+impl<Hash> Grandpa<Hash> {
+	/// Create new instance of GRANDPA API, reading the given round state.
+	pub fn new(round_state: SharedRoundState<Hash>) -> Self {
+		Grandpa {
+			round_state,
+		}
+	}
+}
+
+impl<Hash> GrandpaApi<Hash> for Grandpa<Hash> where
+	Hash: ::std::hash::Hash + Eq + Clone + Send + Sync + Serialize + 'static,
+{
+	fn round_state(&self) -> RpcResult<Option<RoundInfo<Hash>>> {
+		let snapshot = match self.round_state.snapshot() {
+			Some(snapshot) => snapshot,
+			None => return Ok(None),
+		};
+
+		let now = Instant::now();
+		let millis_until = |at: Instant| if at > now { duration_millis(at - now) } else { 0 };
+
+		let mut proposals = HashMap::new();
+		for votes in snapshot.votes.values() {
+			if let Some((ref hash, number)) = votes.prevote {
+				proposals.entry(hash.clone()).or_insert_with(|| proposal(hash, number)).prevotes += 1;
+			}
+			if let Some((ref hash, number)) = votes.precommit {
+				proposals.entry(hash.clone()).or_insert_with(|| proposal(hash, number)).precommits += 1;
+			}
+		}
+		let mut proposals: Vec<_> = proposals.into_iter().map(|(_, proposal)| proposal).collect();
+		proposals.sort_by(|a, b| a.number.cmp(&b.number).then_with(|| b.prevotes.cmp(&a.prevotes)));
+
+		let mut votes: Vec<_> = snapshot.votes.iter()
+			.map(|(authority, votes)| Votes { authority: *authority, votes: votes.clone() })
+			.collect();
+		votes.sort_by(|a, b| a.authority.0.cmp(&b.authority.0));
+
+		Ok(Some(RoundInfo {
+			round: snapshot.round,
+			step: snapshot.step,
+			elapsed_ms: if now > snapshot.started { duration_millis(now - snapshot.started) } else { 0 },
+			prevote_timeout_ms: millis_until(snapshot.prevote_timer),
+			precommit_timeout_ms: millis_until(snapshot.precommit_timer),
+			proposals,
+			votes,
+			last_completed: snapshot.last_completed.clone(),
+		}))
+	}
+}
+
+fn proposal<Hash: Clone>(hash: &Hash, number: u32) -> Proposal<Hash> {
+	Proposal {
+		hash: hash.clone(),
+		number,
+		prevotes: 0,
+		precommits: 0,
+	}
+}
+
+fn duration_millis(duration: ::std::time::Duration) -> u64 {
+	duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use grandpa::{Message, Prevote, Precommit};
+	use std::time::Duration;
+	use substrate_primitives::H256;
+
+	#[test]
+	fn round_state_is_reported() {
+		let state = SharedRoundState::<H256>::default();
+		let api = Grandpa::new(state.clone());
+		assert_eq!(api.round_state().unwrap(), None);
+
+		let now = Instant::now();
+		state.start_round(3, now, now + Duration::from_secs(60));
+		state.timer_fired(3, Step::Precommit);
+		for (id, number) in &[(1u8, 10u32), (2, 10), (3, 11)] {
+			let prevote = Prevote { target_hash: H256::from(*number as u64), target_number: *number };
+			state.note_vote(3, &AuthorityId([*id; 32]), &Message::Prevote(prevote));
+		}
+		let precommit = Precommit { target_hash: H256::from(10), target_number: 10 };
+		state.note_vote(3, &AuthorityId([2; 32]), &Message::Precommit(precommit));
+
+		let round_state = api.round_state().unwrap().unwrap();
+		assert_eq!(round_state.round, 3);
+		assert_eq!(round_state.step, Step::Precommit);
+		assert_eq!(round_state.prevote_timeout_ms, 0);
+		assert!(round_state.precommit_timeout_ms > 0);
+		assert_eq!(round_state.proposals, vec![
+			Proposal { hash: H256::from(10), number: 10, prevotes: 2, precommits: 1 },
+			Proposal { hash: H256::from(11), number: 11, prevotes: 1, precommits: 0 },
+		]);
+		assert_eq!(
+			round_state.votes.iter().map(|votes| votes.authority).collect::<Vec<_>>(),
+			vec![AuthorityId([1; 32]), AuthorityId([2; 32]), AuthorityId([3; 32])],
+		);
+		assert_eq!(round_state.votes[1].votes.precommit, Some((H256::from(10), 10)));
+	}
+}
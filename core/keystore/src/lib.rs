@@ -155,20 +155,7 @@ impl Store {
 	/// Only the first 32 bytes of the sead are used. This is meant to be used for testing only.
 	// TODO: Remove this
 	pub fn generate_from_seed(&mut self, seed: &str) -> Result<Pair> {
-		let mut s: [u8; 32] = [' ' as u8; 32];
-
-		let was_hex = if seed.len() == 66 && &seed[0..2] == "0x" {
-			if let Ok(d) = hex::decode(&seed[2..]) {
-				s.copy_from_slice(&d);
-				true
-			} else { false }
-		} else { false };
-
-		if !was_hex {
-			let len = ::std::cmp::min(32, seed.len());
-			&mut s[..len].copy_from_slice(&seed.as_bytes()[..len]);
-		}
-
+		let s = seed_from_str(seed);
 		let pair = Pair::from_seed(&s);
 		self.additional.insert(pair.public(), s);
 		Ok(pair)
@@ -222,6 +209,30 @@ impl Store {
 	}
 }
 
+/// Create a key from seed, the same way `Store::generate_from_seed` does.
+/// This is meant to be used for testing only.
+pub fn pair_from_seed(seed: &str) -> Pair {
+	Pair::from_seed(&seed_from_str(seed))
+}
+
+fn seed_from_str(seed: &str) -> [u8; 32] {
+	let mut s: [u8; 32] = [' ' as u8; 32];
+
+	let was_hex = if seed.len() == 66 && &seed[0..2] == "0x" {
+		if let Ok(d) = hex::decode(&seed[2..]) {
+			s.copy_from_slice(&d);
+			true
+		} else { false }
+	} else { false };
+
+	if !was_hex {
+		let len = ::std::cmp::min(32, seed.len());
+		&mut s[..len].copy_from_slice(&seed.as_bytes()[..len]);
+	}
+
+	s
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -0,0 +1,357 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Embeddable Substrate light client.
+//!
+//! Wraps the light client backend, the on-demand fetcher and the network service into a
+//! single `LightClient` with a small asynchronous API, suitable for embedding in wallets
+//! without pulling in the full node service.
+
+#![warn(missing_docs)]
+
+extern crate futures;
+extern crate parking_lot;
+extern crate sr_primitives as runtime_primitives;
+extern crate substrate_primitives as primitives;
+extern crate substrate_client as client;
+extern crate substrate_executor;
+extern crate substrate_network as network;
+
+#[macro_use]
+extern crate error_chain;
+
+#[cfg(test)]
+extern crate substrate_test_client as test_client;
+
+pub mod error;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::{Future, Stream, future};
+use parking_lot::Mutex;
+use client::{BlockchainEvents, ChainHead, JustificationVerifier, Notification};
+use client::light::blockchain::{Blockchain, Storage as BlockchainStorage};
+use client::light::call_executor::RemoteCallExecutor;
+use client::light::fetcher::{Fetcher, RemoteCallRequest, RemoteReadRequest};
+use network::{OnDemand, OnDemandService, ProtocolConfig, Roles, SyncProvider};
+use network::import_queue::ImportQueue;
+use network::specialization::Specialization;
+use primitives::{Blake2Hasher, H256};
+use primitives::storage::{StorageKey, StorageData};
+use runtime_primitives::BuildStorage;
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, Hash as HashT};
+use substrate_executor::{NativeExecutor, NativeExecutionDispatch};
+
+pub use error::{Error, ErrorKind};
+pub use client::light::backend::Checkpoint;
+pub use network::{FetcherConfig, NetworkConfiguration, ProtocolId};
+
+/// Network service of the light client.
+pub type NetworkService<Block, S> = network::Service<Block, S, <Block as BlockT>::Hash>;
+
+/// Fetcher of the light client.
+pub type LightFetcher<Block, S> = OnDemand<Block, NetworkService<Block, S>>;
+
+/// Backend of the light client.
+pub type Backend<Block, St, S> = client::light::backend::Backend<St, LightFetcher<Block, S>>;
+
+/// Call executor of the light client.
+pub type Executor<Block, St, S> = RemoteCallExecutor<
+	Blockchain<St, LightFetcher<Block, S>>,
+	LightFetcher<Block, S>,
+	Blake2Hasher,
+>;
+
+/// Client type of the light client.
+pub type Client<Block, St, S> = client::Client<Backend<Block, St, S>, Executor<Block, St, S>, Block>;
+
+/// Future resolved with the response of the remote node.
+pub type LightFuture<T> = Box<Future<Item=T, Error=Error> + Send>;
+
+/// Stream of the best block headers.
+pub type HeadStream<Header> = Box<Stream<Item=Header, Error=()> + Send>;
+
+/// Light client configuration.
+pub struct Configuration<G, H> {
+	/// Network configuration.
+	pub network: NetworkConfiguration,
+	/// Protocol id of the chain.
+	pub protocol_id: ProtocolId,
+	/// Genesis storage of the chain.
+	pub genesis_storage: G,
+	/// Size of the cached remote read results, in bytes.
+	pub read_cache_size: usize,
+	/// Number of distinct peers whose announced heads are cross-checked. Disabled if zero.
+	pub head_providers: usize,
+	/// Retry policy of the requests sent to the full nodes.
+	pub fetcher: FetcherConfig,
+	/// Trusted checkpoint to start syncing from instead of the genesis block.
+	pub checkpoint: Option<Checkpoint<H>>,
+}
+
+/// Light client, syncing headers from the network and fetching everything else on demand.
+pub struct LightClient<Block: BlockT, St, S: Specialization<Block>> {
+	client: Arc<Client<Block, St, S>>,
+	fetcher: Arc<LightFetcher<Block, S>>,
+	network: Arc<NetworkService<Block, S>>,
+	extrinsics: Arc<PendingExtrinsics<Block>>,
+}
+
+impl<Block, St, S> LightClient<Block, St, S> where
+	Block: BlockT<Hash=H256> + 'static,
+	St: BlockchainStorage<Block> + 'static,
+	S: Specialization<Block>,
+{
+	/// Create the light client on top of the given headers storage and start the network.
+	/// The import queue, verifying the headers received from the network, is built by the
+	/// `import_queue` from the client. The headers are finalized and become the best only
+	/// once their justifications are checked by the `justification_verifier` of the consensus
+	/// engine.
+	pub fn new<G, D, Q, I>(
+		config: Configuration<G, Block::Header>,
+		storage: St,
+		executor: NativeExecutor<D>,
+		specialization: S,
+		import_queue: Q,
+		justification_verifier: Box<JustificationVerifier<Block>>,
+	) -> error::Result<Self> where
+		G: BuildStorage,
+		D: NativeExecutionDispatch,
+		Q: FnOnce(Arc<Client<Block, St, S>>) -> error::Result<I>,
+		I: ImportQueue<Block> + 'static,
+	{
+		let blockchain = client::light::new_light_blockchain(storage);
+		let fetch_checker = Arc::new(client::light::new_fetch_checker::<_, Blake2Hasher>(executor));
+		let fetcher = Arc::new(OnDemand::with_config(fetch_checker, config.fetcher));
+		let backend = client::light::new_light_backend(blockchain, fetcher.clone(), config.read_cache_size, config.checkpoint)?;
+		let client = Arc::new(client::light::new_light(backend, fetcher.clone(), config.genesis_storage)?);
+		client.set_justification_verifier(justification_verifier);
+		let import_queue = import_queue(client.clone())?;
+		let extrinsics = Arc::new(PendingExtrinsics::default());
+
+		let network_params = network::Params {
+			config: ProtocolConfig {
+				roles: Roles::LIGHT,
+				light_head_providers: config.head_providers,
+				..Default::default()
+			},
+			network_config: config.network,
+			chain: client.clone(),
+			on_demand: Some(fetcher.clone() as Arc<OnDemandService<Block>>),
+			fallback_on_demand: None,
+			transaction_pool: extrinsics.clone(),
+			specialization,
+		};
+		let network = network::Service::new(network_params, config.protocol_id, import_queue)?;
+		fetcher.set_service_link(Arc::downgrade(&network));
+		fetcher.set_storage_changes_sink(client.clone());
+
+		Ok(LightClient {
+			client,
+			fetcher,
+			network,
+			extrinsics,
+		})
+	}
+
+	/// Get shared client instance.
+	pub fn client(&self) -> &Arc<Client<Block, St, S>> {
+		&self.client
+	}
+
+	/// Get shared network instance.
+	pub fn network(&self) -> &Arc<NetworkService<Block, S>> {
+		&self.network
+	}
+
+	/// Get the future that informs the network about the imported headers. It must be spawned
+	/// by the embedder to keep the light client in sync. Completes once the network is stopped.
+	pub fn sync_task(&self) -> Box<Future<Item=(), Error=()> + Send> {
+		let network = Arc::downgrade(&self.network);
+		Box::new(self.imported_headers("light-client-sync")
+			.for_each(move |(header, _)| match network.upgrade() {
+				Some(network) => {
+					network.on_block_imported(header.hash(), &header);
+					Ok(())
+				},
+				None => Err(()),
+			}))
+	}
+
+	/// Pin the storage keys that are read at almost every block, so that they're read by the
+	/// single request at every new best block. See `pinned_keys_task`.
+	pub fn pin_storage_keys(&self, keys: Vec<StorageKey>) {
+		self.client.backend().pin_storage_keys(keys.into_iter().map(|key| key.0).collect());
+	}
+
+	/// Get the future that reads the pinned keys at the new best blocks. It must be spawned by
+	/// the embedder for the pinned keys to be read from the local copy. The keys aren't read
+	/// during the major sync, where the best block changes faster than the values are read.
+	/// Completes once the network is stopped.
+	pub fn pinned_keys_task(&self) -> Box<Future<Item=(), Error=()> + Send> {
+		let backend = self.client.backend().clone();
+		let network = Arc::downgrade(&self.network);
+		Box::new(self.imported_headers("light-client-pinned-keys")
+			.filter_map(|(header, is_new_best)| if is_new_best { Some(header) } else { None })
+			.for_each(move |header| match network.upgrade() {
+				Some(ref network) if network.status().sync.is_major_syncing() => future::Either::A(future::ok(())),
+				Some(_) => future::Either::B(backend.refresh_pinned_keys::<Block>(header)),
+				None => future::Either::A(future::err(())),
+			}))
+	}
+
+	/// Get the header of the best block.
+	pub fn best_header(&self) -> error::Result<Block::Header> {
+		self.client.best_block_header().map_err(Into::into)
+	}
+
+	/// Submit the extrinsic. The extrinsic is propagated to the connected peers until at least
+	/// one of them receives it.
+	pub fn submit_extrinsic(&self, extrinsic: Block::Extrinsic) -> Block::Hash {
+		let hash = <<Block::Header as HeaderT>::Hashing as HashT>::hash_of(&extrinsic);
+		self.extrinsics.extrinsics.lock().insert(hash, extrinsic);
+		self.network.trigger_repropagate();
+		hash
+	}
+
+	/// Read the storage value at the best block.
+	pub fn storage(&self, key: &StorageKey) -> LightFuture<Option<StorageData>> {
+		let header = match self.best_header() {
+			Ok(header) => header,
+			Err(error) => return Box::new(future::err(error)),
+		};
+
+		Box::new(self.fetcher.remote_read(RemoteReadRequest {
+			block: header.hash(),
+			header,
+			keys: vec![key.0.clone()],
+			retry_count: None,
+		})
+			.map(|mut values| values.pop().and_then(|value| value).map(StorageData))
+			.map_err(Into::into))
+	}
+
+	/// Call the runtime method at the best block and return the encoded result.
+	pub fn call(&self, method: &str, call_data: &[u8]) -> LightFuture<Vec<u8>> {
+		let header = match self.best_header() {
+			Ok(header) => header,
+			Err(error) => return Box::new(future::err(error)),
+		};
+
+		Box::new(self.fetcher.remote_call(RemoteCallRequest {
+			block: header.hash(),
+			header,
+			method: method.into(),
+			call_data: call_data.to_vec(),
+			trace_keys: false,
+			retry_count: None,
+		})
+			.map(|result| result.return_data)
+			.map_err(Into::into))
+	}
+
+	/// Subscribe to the headers of the new best blocks.
+	pub fn subscribe_heads(&self) -> HeadStream<Block::Header> {
+		Box::new(self.imported_headers("light-client-heads")
+			.filter_map(|(header, is_new_best)| if is_new_best { Some(header) } else { None }))
+	}
+
+	/// Get the stream of the imported headers, along with the flag whether the header is the new
+	/// best. When the import notifications have been dropped, the stream yields the current best
+	/// header instead, so that the consumers resync with the chain.
+	fn imported_headers(&self, label: &'static str) -> Box<Stream<Item=(Block::Header, bool), Error=()> + Send> {
+		let client = self.client.clone();
+		Box::new(self.client.import_notification_stream(label)
+			.filter_map(move |notification| match notification {
+				Notification::Item(notification) => Some((notification.header, notification.is_new_best)),
+				Notification::Lagged(_) => client.best_block_header().ok().map(|header| (header, true)),
+			}))
+	}
+}
+
+/// Extrinsics submitted to the light client that are not yet propagated to the network.
+struct PendingExtrinsics<Block: BlockT> {
+	extrinsics: Mutex<HashMap<Block::Hash, Block::Extrinsic>>,
+}
+
+impl<Block: BlockT> Default for PendingExtrinsics<Block> {
+	fn default() -> Self {
+		PendingExtrinsics {
+			extrinsics: Mutex::new(HashMap::new()),
+		}
+	}
+}
+
+impl<Block: BlockT<Hash=H256>> network::TransactionPool<Block::Hash, Block> for PendingExtrinsics<Block> {
+	fn transactions(&self) -> Vec<(Block::Hash, Block::Extrinsic)> {
+		self.extrinsics.lock().iter()
+			.map(|(hash, extrinsic)| (hash.clone(), extrinsic.clone()))
+			.collect()
+	}
+
+	fn import(&self, _transaction: &Block::Extrinsic) -> Option<Block::Hash> {
+		// light client doesn't accept extrinsics from the network
+		None
+	}
+
+	fn on_broadcasted(&self, propagations: HashMap<Block::Hash, Vec<String>>) {
+		let mut extrinsics = self.extrinsics.lock();
+		for (hash, peers) in propagations {
+			if !peers.is_empty() {
+				extrinsics.remove(&hash);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use network::TransactionPool;
+	use test_client::keyring::Keyring;
+	use test_client::runtime::{Block, Extrinsic, Transfer};
+
+	fn extrinsic(nonce: u64) -> Extrinsic {
+		let transfer = Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Bob.to_raw_public().into(),
+			amount: 1,
+			nonce,
+		};
+		let signature = Keyring::Alice.sign(&runtime_primitives::codec::Encode::encode(&transfer)).into();
+		Extrinsic { transfer, signature }
+	}
+
+	#[test]
+	fn pending_extrinsics_are_propagated_until_received() {
+		let pending = PendingExtrinsics::<Block>::default();
+		let (first, second) = (extrinsic(0), extrinsic(1));
+		let (first_hash, second_hash) = (H256::from(1), H256::from(2));
+		pending.extrinsics.lock().insert(first_hash, first.clone());
+		pending.extrinsics.lock().insert(second_hash, second.clone());
+
+		// extrinsics from the network are rejected
+		assert_eq!(pending.import(&extrinsic(2)), None);
+		assert_eq!(pending.transactions().len(), 2);
+
+		// extrinsic that no peer has received is kept
+		let mut propagations = HashMap::new();
+		propagations.insert(first_hash, vec!["peer".to_owned()]);
+		propagations.insert(second_hash, vec![]);
+		pending.on_broadcasted(propagations);
+		assert_eq!(pending.transactions(), vec![(second_hash, second)]);
+	}
+}
@@ -0,0 +1,285 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+// tag::description[]
+//! Logger with the filter that may be changed at runtime.
+//!
+//! The filter is given by `env_logger` directives, like `sync=trace,db=debug,info`. Directives
+//! added at runtime override the directives the logger has been initialized with, until the
+//! filter is reset. An added directive replaces the directive added earlier for the same target,
+//! and at most `MAX_ADDED_DIRECTIVES` directives may be added.
+// end::description[]
+
+#![warn(missing_docs)]
+
+extern crate env_logger;
+#[macro_use]
+extern crate lazy_static;
+#[cfg_attr(test, macro_use)]
+extern crate log;
+extern crate parking_lot;
+
+use std::{fmt, io};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use env_logger::fmt::Formatter;
+use log::{LevelFilter, Log, Metadata, Record};
+use parking_lot::RwLock;
+
+/// Maximal number of the directives added at runtime, so that the filter doesn't grow unbounded.
+pub const MAX_ADDED_DIRECTIVES: usize = 64;
+
+/// Formatter of the log records.
+pub type Format = Fn(&mut Formatter, &Record) -> io::Result<()> + Sync + Send;
+
+/// Error of changing the log filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// The logger hasn't been initialized with `init`.
+	NotInitialized,
+	/// Directive can't be parsed.
+	InvalidDirective(String),
+	/// Too many directives have been added since the filter has been reset.
+	TooManyDirectives,
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::NotInitialized => write!(f, "Logger is not initialized"),
+			Error::InvalidDirective(ref directive) => write!(f, "Invalid log filter directive: {}", directive),
+			Error::TooManyDirectives => write!(f, "More than {} log filter directives added", MAX_ADDED_DIRECTIVES),
+		}
+	}
+}
+
+struct State {
+	/// Directives the logger has been initialized with, followed by the added directives, one per
+	/// target.
+	directives: Vec<String>,
+	initial: usize,
+	format: Arc<Format>,
+	logger: env_logger::Logger,
+}
+
+lazy_static! {
+	static ref STATE: RwLock<Option<State>> = RwLock::new(None);
+}
+
+static LOGGER: ReloadableLogger = ReloadableLogger;
+
+/// Logger, delegating to the `env_logger` built from the current directives.
+struct ReloadableLogger;
+
+impl Log for ReloadableLogger {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		STATE.read().as_ref().map_or(false, |state| state.logger.enabled(metadata))
+	}
+
+	fn log(&self, record: &Record) {
+		if let Some(ref state) = *STATE.read() {
+			state.logger.log(record);
+		}
+	}
+
+	fn flush(&self) {}
+}
+
+/// Initialize the global logger with given directives and format. Later directives override
+/// the earlier ones with the same target.
+pub fn init<F>(directives: &[&str], format: F) -> Result<(), log::SetLoggerError> where
+	F: Fn(&mut Formatter, &Record) -> io::Result<()> + Sync + Send + 'static,
+{
+	log::set_logger(&LOGGER)?;
+
+	let format: Arc<Format> = Arc::new(format);
+	let directives: Vec<_> = directives.iter().map(|directive| directive.to_string()).collect();
+	let logger = build(&directives, &format);
+	log::set_max_level(logger.filter());
+	*STATE.write() = Some(State {
+		initial: directives.len(),
+		directives,
+		format,
+		logger,
+	});
+	Ok(())
+}
+
+/// Add directives to the filter of the global logger, e.g. `sync=trace,txpool=debug`.
+pub fn add_directives(directives: &str) -> Result<(), Error> {
+	check_directives(directives)?;
+
+	let mut state = STATE.write();
+	let state = state.as_mut().ok_or(Error::NotInitialized)?;
+	let initial = state.initial;
+	let added = merge_directives(&state.directives[initial..], directives)?;
+	state.directives.truncate(initial);
+	state.directives.extend(added);
+	state.logger = build(&state.directives, &state.format);
+	log::set_max_level(state.logger.filter());
+	Ok(())
+}
+
+/// Reset the filter of the global logger to the directives it has been initialized with.
+pub fn reset_directives() -> Result<(), Error> {
+	let mut state = STATE.write();
+	let state = state.as_mut().ok_or(Error::NotInitialized)?;
+	let initial = state.initial;
+	state.directives.truncate(initial);
+	state.logger = build(&state.directives, &state.format);
+	log::set_max_level(state.logger.filter());
+	Ok(())
+}
+
+fn build(directives: &[String], format: &Arc<Format>) -> env_logger::Logger {
+	let mut builder = env_logger::Builder::new();
+	// directives of the later `parse` calls override the directives with the same target
+	for directives in directives {
+		builder.parse(directives);
+	}
+
+	let format = format.clone();
+	builder.format(move |buf, record| format(buf, record));
+	builder.build()
+}
+
+/// Add the directives to the previously added ones, replacing the directives with the same target.
+fn merge_directives(added: &[String], directives: &str) -> Result<Vec<String>, Error> {
+	let mut added = added.to_vec();
+	let mut parts = directives.splitn(2, '/');
+	let spec = parts.next().unwrap_or("");
+	let new = spec.split(',')
+		.map(str::trim)
+		.filter(|directive| !directive.is_empty())
+		.map(str::to_owned)
+		.chain(parts.next().map(|filter| format!("/{}", filter)));
+	for directive in new {
+		added.retain(|existing| directive_target(existing) != directive_target(&directive));
+		added.push(directive);
+	}
+
+	if added.len() > MAX_ADDED_DIRECTIVES {
+		return Err(Error::TooManyDirectives);
+	}
+	Ok(added)
+}
+
+/// Target the directive applies to, where the bare level applies to the empty target and the
+/// message filter to `/`.
+fn directive_target(directive: &str) -> &str {
+	if directive.starts_with('/') {
+		return "/";
+	}
+	let mut parts = directive.splitn(2, '=');
+	let target = parts.next().unwrap_or("").trim();
+	match parts.next() {
+		None if LevelFilter::from_str(target).is_ok() => "",
+		_ => target,
+	}
+}
+
+/// `env_logger` ignores the invalid directives, so they're checked before being added.
+fn check_directives(directives: &str) -> Result<(), Error> {
+	let spec = directives.splitn(2, '/').next().unwrap_or("");
+	let mut directives = spec.split(',').map(str::trim).filter(|directive| !directive.is_empty()).peekable();
+	if directives.peek().is_none() {
+		return Err(Error::InvalidDirective(spec.to_owned()));
+	}
+
+	for directive in directives {
+		let mut parts = directive.splitn(2, '=');
+		let target = parts.next().unwrap_or("").trim();
+		match parts.next() {
+			Some(level) if target.is_empty() || LevelFilter::from_str(level.trim()).is_err() =>
+				return Err(Error::InvalidDirective(directive.to_owned())),
+			_ => (),
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use log::Level;
+
+	#[test]
+	fn directives_are_checked() {
+		assert_eq!(check_directives("sync=trace,db=debug,info"), Ok(()));
+		assert_eq!(check_directives("sync"), Ok(()));
+		assert_eq!(check_directives("sync=debug/import"), Ok(()));
+		assert_eq!(check_directives(""), Err(Error::InvalidDirective("".into())));
+		assert_eq!(check_directives("sync=loud"), Err(Error::InvalidDirective("sync=loud".into())));
+		assert_eq!(check_directives("info,=debug"), Err(Error::InvalidDirective("=debug".into())));
+	}
+
+	#[test]
+	fn added_directives_replace_the_same_target() {
+		let added = merge_directives(&[], "sync=debug,db=trace").unwrap();
+		assert_eq!(added, vec!["sync=debug".to_owned(), "db=trace".to_owned()]);
+
+		let added = merge_directives(&added, "info, sync=trace/import").unwrap();
+		assert_eq!(added, vec![
+			"db=trace".to_owned(),
+			"info".to_owned(),
+			"sync=trace".to_owned(),
+			"/import".to_owned(),
+		]);
+
+		let added = merge_directives(&added, "warn,/block").unwrap();
+		assert_eq!(added, vec![
+			"db=trace".to_owned(),
+			"sync=trace".to_owned(),
+			"warn".to_owned(),
+			"/block".to_owned(),
+		]);
+	}
+
+	#[test]
+	fn number_of_added_directives_is_bounded() {
+		let mut added = Vec::new();
+		for i in 0..MAX_ADDED_DIRECTIVES {
+			added = merge_directives(&added, &format!("target{}=debug", i)).unwrap();
+		}
+		// replacing the directive doesn't add a new one
+		added = merge_directives(&added, "target0=trace").unwrap();
+		assert_eq!(added.len(), MAX_ADDED_DIRECTIVES);
+		assert_eq!(merge_directives(&added, "sync=debug"), Err(Error::TooManyDirectives));
+	}
+
+	#[test]
+	fn filter_is_changed_at_runtime() {
+		assert_eq!(add_directives("sync=debug"), Err(Error::NotInitialized));
+
+		init(&["info"], |_, _| Ok(())).unwrap();
+		assert!(!log_enabled!(target: "sync", Level::Debug));
+
+		add_directives("sync=debug").unwrap();
+		assert!(log_enabled!(target: "sync", Level::Debug));
+		assert!(!log_enabled!(target: "sync", Level::Trace));
+		assert!(!log_enabled!(target: "db", Level::Debug));
+
+		add_directives("sync=trace,db=debug").unwrap();
+		assert!(log_enabled!(target: "sync", Level::Trace));
+		assert!(log_enabled!(target: "db", Level::Debug));
+
+		reset_directives().unwrap();
+		assert!(!log_enabled!(target: "sync", Level::Debug));
+		assert!(log_enabled!(target: "sync", Level::Info));
+	}
+}
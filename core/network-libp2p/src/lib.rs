@@ -43,6 +43,8 @@ extern crate error_chain;
 extern crate log;
 #[cfg(test)] #[macro_use]
 extern crate assert_matches;
+#[cfg(test)]
+extern crate tempdir;
 
 mod custom_proto;
 mod error;
@@ -56,6 +58,7 @@ mod transport;
 
 pub use custom_proto::RegisteredProtocol;
 pub use error::{Error, ErrorKind, DisconnectReason};
+pub use secret::{generate_node_key, node_key_peer_id, write_node_key_file};
 pub use libp2p::{Multiaddr, multiaddr::Protocol, PeerId};
 pub use service_task::{start_service, Service, ServiceEvent};
 pub use traits::*;	// TODO: expand to actual items
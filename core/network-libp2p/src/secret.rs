@@ -14,12 +14,12 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use libp2p::secio;
+use libp2p::{secio, PeerId};
 use rand::{self, Rng};
 use std::fs;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind, Read, Write};
 use std::path::Path;
-use NetworkConfiguration;
+use {NetworkConfiguration, Secret};
 
 // File where the private key is stored.
 const SECRET_FILE: &str = "secret";
@@ -41,28 +41,49 @@ pub(crate) fn obtain_private_key(
 			let secret_path = Path::new(path).join(SECRET_FILE);
 			match load_private_key_from_file(&secret_path) {
 				Ok(s) => Ok(s),
-				Err(err) => {
-					// Failed to fetch existing file ; generate a new key
-					trace!(target: "sub-libp2p",
-						"Failed to load existing secret key file {:?}, generating new key ; err = {:?}",
+				Err(ref err) if err.kind() == IoErrorKind::NotFound => {
+					// There's no existing file ; generate a new key
+					info!(target: "sub-libp2p",
+						"Generating new node key, stored in file {:?}",
 						secret_path,
-						err
 					);
 					Ok(gen_key_and_try_write_to_file(&secret_path))
-				}
+				},
+				// Don't replace the existing key, so that the node identity doesn't change
+				Err(err) => Err(IoError::new(err.kind(), format!(
+					"Failed to load existing secret key file {:?}: {}",
+					secret_path,
+					err,
+				))),
 			}
 
 		} else {
 			// No path in the configuration, nothing we can do except generate
 			// a new key.
-			let mut key: [u8; 32] = [0; 32];
-			rand::rngs::EntropyRng::new().fill(&mut key);
-			Ok(secio::SecioKeyPair::secp256k1_raw_key(&key)
+			Ok(secio::SecioKeyPair::secp256k1_raw_key(&generate_node_key())
 				.expect("randomly-generated key with correct len should always be valid"))
 		}
 	}
 }
 
+/// Generates a new random node key.
+pub fn generate_node_key() -> Secret {
+	rand::rngs::EntropyRng::new().gen()
+}
+
+/// Returns the id of the peer with given node key.
+pub fn node_key_peer_id(secret: &Secret) -> Result<PeerId, IoError> {
+	secio::SecioKeyPair::secp256k1_raw_key(&secret[..])
+		.map(|key| key.to_public_key().into_peer_id())
+		.map_err(|err| IoError::new(IoErrorKind::InvalidData, err))
+}
+
+/// Writes the node key to a new file at the given path, which is only accessible by the owner.
+/// Fails if the file already exists.
+pub fn write_node_key_file<P: AsRef<Path>>(path: P, content: &[u8]) -> Result<(), IoError> {
+	open_priv_key_file(path)?.write_all(content)
+}
+
 /// Tries to load a private key from a file located at the given path.
 fn load_private_key_from_file<P>(path: P)
 	-> Result<secio::SecioKeyPair, IoError>
@@ -84,7 +105,7 @@ fn load_private_key_from_file<P>(path: P)
 /// Doesn't error if we couldn't open or write to the file.
 fn gen_key_and_try_write_to_file<P>(path: P) -> secio::SecioKeyPair
 	where P: AsRef<Path> {
-	let raw_key: [u8; 32] = rand::rngs::EntropyRng::new().gen();
+	let raw_key = generate_node_key();
 	let secio_key = secio::SecioKeyPair::secp256k1_raw_key(&raw_key)
 		.expect("randomly-generated key with correct len should always be valid");
 
@@ -130,3 +151,33 @@ fn open_priv_key_file<P>(path: P) -> Result<fs::File, IoError>
 		.create_new(true)
 		.open(path)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempdir::TempDir;
+
+	#[test]
+	fn node_key_file_is_not_overwritten() {
+		let dir = TempDir::new("node-key").unwrap();
+		let path = dir.path().join("key");
+		write_node_key_file(&path, b"first").unwrap();
+
+		let err = write_node_key_file(&path, b"second").unwrap_err();
+		assert_eq!(err.kind(), IoErrorKind::AlreadyExists);
+		assert_eq!(fs::read(&path).unwrap(), b"first".to_vec());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn node_key_file_is_only_accessible_by_owner() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let dir = TempDir::new("node-key").unwrap();
+		let path = dir.path().join("key");
+		write_node_key_file(&path, &generate_node_key()).unwrap();
+
+		let mode = fs::metadata(&path).unwrap().permissions().mode();
+		assert_eq!(mode & 0o777, 0o600);
+	}
+}
@@ -402,7 +402,19 @@ impl Service {
 		self.drop_node_inner(node_index, DisconnectReason::Useless, None);
 	}
 
-	/// Common implementation of `drop_node` and `ban_node`.
+	/// Disconnects a peer to make room for other peers, without lowering the score of its address.
+	///
+	/// This is asynchronous and will not immediately close the peer.
+	/// Corresponding closing events will be generated once the closing actually happens.
+	pub fn rotate_node(&mut self, node_index: NodeIndex) {
+		if let Some(peer_id) = self.swarm.peer_id_of_node(node_index) {
+			info!(target: "sub-libp2p", "Rotated {:?}", peer_id);
+		}
+
+		self.drop_node_inner(node_index, DisconnectReason::Rotated, None);
+	}
+
+	/// Common implementation of `drop_node`, `rotate_node` and `ban_node`.
 	fn drop_node_inner(
 		&mut self,
 		node_index: NodeIndex,
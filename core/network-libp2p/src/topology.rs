@@ -386,6 +386,7 @@ impl NetTopology {
 			DisconnectReason::RemoteClosed => -5,
 			DisconnectReason::Useless => -5,
 			DisconnectReason::Banned => -5,
+			DisconnectReason::Rotated => 0,
 		};
 
 		for info in self.store.values_mut() {
@@ -450,6 +451,8 @@ pub enum DisconnectReason {
 	Useless,
 	/// The peer has been banned.
 	Banned,
+	/// We have disconnected to connect to other peers. The peer isn't at fault.
+	Rotated,
 }
 
 fn peer_access<'a>(store: &'a mut FnvHashMap<PeerId, PeerInfo>, peer: &PeerId) -> &'a mut PeerInfo {
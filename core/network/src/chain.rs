@@ -16,7 +16,9 @@
 
 //! Blockchain access trait
 
+use std::time::Instant;
 use client::{self, Client as SubstrateClient, ClientInfo, BlockStatus, CallExecutor};
+use client::backend::Backend;
 use client::error::Error;
 use consensus::BlockImport;
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor};
@@ -24,6 +26,8 @@ use runtime_primitives::generic::{BlockId};
 use consensus::{ImportBlock, ImportResult};
 use runtime_primitives::Justification;
 use primitives::{H256, Blake2Hasher, AuthorityId};
+use primitives::storage::StorageKey;
+use on_demand::StorageChangesSink;
 
 /// Local client abstraction for the network.
 pub trait Client<Block: BlockT>: Send + Sync {
@@ -40,6 +44,9 @@ pub trait Client<Block: BlockT>: Send + Sync {
 	/// Get block hash by number.
 	fn block_hash(&self, block_number: <Block::Header as HeaderT>::Number) -> Result<Option<Block::Hash>, Error>;
 
+	/// Get the inclusive range of numbers of blocks that are missing from the database.
+	fn block_gap(&self) -> Result<Option<(NumberFor<Block>, NumberFor<Block>)>, Error>;
+
 	/// Get block header.
 	fn header(&self, id: &BlockId<Block>) -> Result<Option<Block::Header>, Error>;
 
@@ -49,14 +56,32 @@ pub trait Client<Block: BlockT>: Send + Sync {
 	/// Get block justification.
 	fn justification(&self, id: &BlockId<Block>) -> Result<Option<Justification>, Error>;
 
+	/// Import the justification of the block that has been imported before, finalizing the block.
+	fn import_justification(&self, hash: Block::Hash, justification: Justification) -> Result<(), Error>;
+
 	/// Get block header proof.
 	fn header_proof(&self, block_number: <Block::Header as HeaderT>::Number) -> Result<(Block::Header, Vec<Vec<u8>>), Error>;
 
-	/// Get storage read execution proof.
-	fn read_proof(&self, block: &Block::Hash, key: &[u8]) -> Result<Vec<Vec<u8>>, Error>;
+	/// Get storage value.
+	fn storage(&self, block: &Block::Hash, key: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+
+	/// Get single storage read proof of the values of all given keys.
+	fn read_proof(&self, block: &Block::Hash, keys: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error>;
+
+	/// Get child storage read proof.
+	fn read_child_proof(&self, block: &Block::Hash, storage_key: &[u8], key: &[u8]) -> Result<Vec<Vec<u8>>, Error>;
+
+	/// Get proof of the page of storage entries with keys starting with given prefix.
+	fn read_prefix_proof(&self, block: &Block::Hash, prefix: &[u8], start_key: &[u8], limit: usize) -> Result<Vec<Vec<u8>>, Error>;
 
-	/// Get method execution proof.
-	fn execution_proof(&self, block: &Block::Hash, method: &str, data: &[u8]) -> Result<(Vec<u8>, Vec<Vec<u8>>), Error>;
+	/// Get proof of the storage keys and roots of all child tries.
+	fn child_roots_proof(&self, block: &Block::Hash) -> Result<Vec<Vec<u8>>, Error>;
+
+	/// Get headers of all blocks of given range, with CHT-based proof of these headers.
+	fn digest_proof(&self, first: NumberFor<Block>, last: NumberFor<Block>) -> Result<(Vec<Block::Header>, Vec<Vec<u8>>), Error>;
+
+	/// Get method execution proof and storage keys read by the call.
+	fn execution_proof(&self, block: &Block::Hash, method: &str, data: &[u8]) -> Result<(Vec<u8>, Vec<Vec<u8>>, Vec<Vec<u8>>), Error>;
 
 	/// Get key changes proof.
 	fn key_changes_proof(
@@ -66,6 +91,15 @@ pub trait Client<Block: BlockT>: Send + Sync {
 		max: Block::Hash,
 		key: &[u8]
 	) -> Result<(NumberFor<Block>, Vec<Vec<u8>>), Error>;
+
+	/// Insert and delete auxiliary data.
+	fn insert_aux(&self, insert: &[(&[u8], &[u8])], delete: &[&[u8]]) -> Result<(), Error>;
+
+	/// Get auxiliary data.
+	fn get_aux(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+
+	/// Note that the block has been announced or received from the network.
+	fn note_block_arrival(&self, hash: &Block::Hash);
 }
 
 impl<B, E, Block> Client<Block> for SubstrateClient<B, E, Block> where
@@ -92,6 +126,10 @@ impl<B, E, Block> Client<Block> for SubstrateClient<B, E, Block> where
 		(self as &SubstrateClient<B, E, Block>).block_hash(block_number)
 	}
 
+	fn block_gap(&self) -> Result<Option<(NumberFor<Block>, NumberFor<Block>)>, Error> {
+		(self as &SubstrateClient<B, E, Block>).block_gap()
+	}
+
 	fn header(&self, id: &BlockId<Block>) -> Result<Option<Block::Header>, Error> {
 		(self as &SubstrateClient<B, E, Block>).header(id)
 	}
@@ -104,16 +142,41 @@ impl<B, E, Block> Client<Block> for SubstrateClient<B, E, Block> where
 		(self as &SubstrateClient<B, E, Block>).justification(id)
 	}
 
+	fn import_justification(&self, hash: Block::Hash, justification: Justification) -> Result<(), Error> {
+		(self as &SubstrateClient<B, E, Block>).import_justification(hash, justification)
+	}
+
 	fn header_proof(&self, block_number: <Block::Header as HeaderT>::Number) -> Result<(Block::Header, Vec<Vec<u8>>), Error> {
 		(self as &SubstrateClient<B, E, Block>).header_proof(&BlockId::Number(block_number))
 	}
 
-	fn read_proof(&self, block: &Block::Hash, key: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
-		(self as &SubstrateClient<B, E, Block>).read_proof(&BlockId::Hash(block.clone()), key)
+	fn storage(&self, block: &Block::Hash, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		(self as &SubstrateClient<B, E, Block>).storage(&BlockId::Hash(block.clone()), &StorageKey(key.to_vec()))
+			.map(|value| value.map(|value| value.0))
 	}
 
-	fn execution_proof(&self, block: &Block::Hash, method: &str, data: &[u8]) -> Result<(Vec<u8>, Vec<Vec<u8>>), Error> {
-		(self as &SubstrateClient<B, E, Block>).execution_proof(&BlockId::Hash(block.clone()), method, data)
+	fn read_proof(&self, block: &Block::Hash, keys: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+		(self as &SubstrateClient<B, E, Block>).read_proof(&BlockId::Hash(block.clone()), keys)
+	}
+
+	fn read_child_proof(&self, block: &Block::Hash, storage_key: &[u8], key: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+		(self as &SubstrateClient<B, E, Block>).read_child_proof(&BlockId::Hash(block.clone()), storage_key, key)
+	}
+
+	fn read_prefix_proof(&self, block: &Block::Hash, prefix: &[u8], start_key: &[u8], limit: usize) -> Result<Vec<Vec<u8>>, Error> {
+		(self as &SubstrateClient<B, E, Block>).read_prefix_proof(&BlockId::Hash(block.clone()), prefix, start_key, limit)
+	}
+
+	fn child_roots_proof(&self, block: &Block::Hash) -> Result<Vec<Vec<u8>>, Error> {
+		(self as &SubstrateClient<B, E, Block>).child_roots_proof(&BlockId::Hash(block.clone()))
+	}
+
+	fn digest_proof(&self, first: NumberFor<Block>, last: NumberFor<Block>) -> Result<(Vec<Block::Header>, Vec<Vec<u8>>), Error> {
+		(self as &SubstrateClient<B, E, Block>).digest_proof(first, last)
+	}
+
+	fn execution_proof(&self, block: &Block::Hash, method: &str, data: &[u8]) -> Result<(Vec<u8>, Vec<Vec<u8>>, Vec<Vec<u8>>), Error> {
+		(self as &SubstrateClient<B, E, Block>).execution_proof_with_keys(&BlockId::Hash(block.clone()), method, data)
 	}
 
 	fn key_changes_proof(
@@ -125,4 +188,26 @@ impl<B, E, Block> Client<Block> for SubstrateClient<B, E, Block> where
 	) -> Result<(NumberFor<Block>, Vec<Vec<u8>>), Error> {
 		(self as &SubstrateClient<B, E, Block>).key_changes_proof(first, last, max, key)
 	}
+
+	fn insert_aux(&self, insert: &[(&[u8], &[u8])], delete: &[&[u8]]) -> Result<(), Error> {
+		self.backend().insert_aux(insert, delete)
+	}
+
+	fn get_aux(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		self.backend().get_aux(key)
+	}
+
+	fn note_block_arrival(&self, hash: &Block::Hash) {
+		(self as &SubstrateClient<B, E, Block>).note_block_arrival(*hash, Instant::now())
+	}
+}
+
+impl<B, E, Block> StorageChangesSink<Block> for SubstrateClient<B, E, Block> where
+	B: client::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	Block: BlockT<Hash=H256>,
+{
+	fn on_storage_changes(&self, block: Block::Hash, changes: Vec<(Vec<u8>, Option<Vec<u8>>)>) {
+		self.notify_storage_changes(&block, changes)
+	}
 }
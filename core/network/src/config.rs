@@ -15,18 +15,42 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 pub use service::Roles;
+pub use rebroadcast::RebroadcastConfig;
+pub use watchdog::WatchdogConfig;
+
+use network_libp2p::PeerId;
 
 /// Protocol configuration
 #[derive(Clone)]
 pub struct ProtocolConfig {
 	/// Assigned roles.
 	pub roles: Roles,
+	/// Transactions rebroadcast schedule.
+	pub rebroadcast: RebroadcastConfig,
+	/// Number of distinct peers whose announced heads are cross-checked against the finalized
+	/// chain by the light client. Disabled if zero.
+	pub light_head_providers: usize,
+	/// Peers the on-demand requests are sent to. All connected full nodes are used if empty.
+	pub on_demand_peers: Vec<PeerId>,
+	/// Peers serving the fallback on-demand service, if any. They don't serve the main one.
+	pub fallback_on_demand_peers: Vec<PeerId>,
+	/// Id of the fork of the chain, announced in the status. Peers announcing a different fork id
+	/// are rejected.
+	pub fork_id: Option<Vec<u8>>,
+	/// Detection of the stalled chain. Disabled if `None`.
+	pub watchdog: Option<WatchdogConfig>,
 }
 
 impl Default for ProtocolConfig {
 	fn default() -> ProtocolConfig {
 		ProtocolConfig {
 			roles: Roles::FULL,
+			rebroadcast: Default::default(),
+			light_head_providers: 0,
+			on_demand_peers: Vec::new(),
+			fallback_on_demand_peers: Vec::new(),
+			fork_id: None,
+			watchdog: None,
 		}
 	}
 }
@@ -0,0 +1,223 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cross-checking of the heads announced to the light client.
+//!
+//! The light client follows the heads announced by a limited number of distinct peers, the
+//! providers. Announced heads are checked against the verified (finalized) chain: the provider
+//! that has announced a block conflicting with the finalized block of the same number is
+//! flagged and replaced by another connected peer. Only the blocks finalized when the head is
+//! announced are checked: the heads above the finalized block may be legitimate forks that are
+//! abandoned later, so they're only kept to log the providers announcing different heads of the
+//! same number, until the chain is finalized up to them.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+use network_libp2p::NodeIndex;
+
+/// Maximal number of block numbers the unchecked announcements are kept for.
+const MAX_PENDING_NUMBERS: usize = 256;
+
+/// Cross-check of the heads announced by the providers.
+pub struct HeadCrossCheck<H, N> {
+	max_providers: usize,
+	providers: HashSet<NodeIndex>,
+	/// Announcements above the finalized block, by block number.
+	pending: BTreeMap<N, HashMap<H, HashSet<NodeIndex>>>,
+	flagged: HashSet<NodeIndex>,
+}
+
+impl<H: Hash + Eq + Copy + Debug, N: Ord + Copy + Debug> HeadCrossCheck<H, N> {
+	/// Create new cross-check, following heads of at most `max_providers` peers.
+	pub fn new(max_providers: usize) -> Self {
+		HeadCrossCheck {
+			max_providers,
+			providers: HashSet::new(),
+			pending: BTreeMap::new(),
+			flagged: HashSet::new(),
+		}
+	}
+
+	/// Peers whose heads are followed.
+	pub fn providers(&self) -> &HashSet<NodeIndex> {
+		&self.providers
+	}
+
+	/// Peers that have announced heads conflicting with the finalized chain.
+	pub fn flagged(&self) -> &HashSet<NodeIndex> {
+		&self.flagged
+	}
+
+	/// Follow heads of the newly connected peer, if there are less than `max_providers` providers.
+	pub fn on_connect(&mut self, who: NodeIndex) {
+		if self.providers.len() < self.max_providers && !self.flagged.contains(&who) {
+			trace!(target: "sync", "Following heads of {}", who);
+			self.providers.insert(who);
+		}
+	}
+
+	/// Stop following heads of the disconnected peer, replacing it with one of the `connected`
+	/// peers.
+	pub fn on_disconnect<I: IntoIterator<Item=NodeIndex>>(&mut self, who: NodeIndex, connected: I) {
+		if self.providers.remove(&who) {
+			self.forget(who);
+			self.refill(connected);
+		}
+	}
+
+	/// Check the head announced by the peer. `finalized` is the number of the last finalized block
+	/// and `canonical` returns the hash of the finalized block of given number. Returns `false` if
+	/// the announcement conflicts with the finalized chain, in which case the peer is flagged.
+	pub fn on_announce<F>(&mut self, who: NodeIndex, number: N, hash: H, finalized: N, canonical: F) -> bool where
+		F: Fn(N) -> Option<H>,
+	{
+		if !self.providers.contains(&who) {
+			return true;
+		}
+
+		if number <= finalized {
+			if canonical(number).map_or(false, |canonical| canonical != hash) {
+				self.flag(who);
+				return false;
+			}
+			return true;
+		}
+
+		let announced = self.pending.entry(number).or_insert_with(HashMap::new);
+		announced.entry(hash).or_insert_with(HashSet::new).insert(who);
+		if announced.len() > 1 {
+			debug!(target: "sync", "Providers announce different heads #{:?}: {:?}", number, announced);
+		}
+		while self.pending.len() > MAX_PENDING_NUMBERS {
+			let oldest = *self.pending.keys().next().expect("pending is not empty; qed");
+			self.pending.remove(&oldest);
+		}
+		true
+	}
+
+	/// Forget the announcements of the blocks finalized up to `finalized`. The providers that
+	/// have announced the abandoned forks aren't flagged, since the heads weren't finalized when
+	/// announced.
+	pub fn on_finalized<F>(&mut self, finalized: N, canonical: F) where
+		F: Fn(N) -> Option<H>,
+	{
+		let finalized_numbers: Vec<_> = self.pending.keys().cloned().take_while(|number| *number <= finalized).collect();
+		for number in finalized_numbers {
+			let announced = self.pending.remove(&number).expect("collected from pending keys; qed");
+			if let Some(canonical) = canonical(number) {
+				for (hash, peers) in announced.into_iter().filter(|&(hash, _)| hash != canonical) {
+					trace!(target: "sync", "Providers {:?} have announced abandoned fork #{:?} ({:?})", peers, number, hash);
+				}
+			}
+		}
+	}
+
+	fn flag(&mut self, who: NodeIndex) {
+		debug!(target: "sync", "Provider {} has announced head conflicting with the finalized chain", who);
+		self.flagged.insert(who);
+		self.providers.remove(&who);
+		self.forget(who);
+	}
+
+	fn forget(&mut self, who: NodeIndex) {
+		for announced in self.pending.values_mut() {
+			for peers in announced.values_mut() {
+				peers.remove(&who);
+			}
+			announced.retain(|_, peers| !peers.is_empty());
+		}
+		self.pending.retain(|_, announced| !announced.is_empty());
+	}
+
+	fn refill<I: IntoIterator<Item=NodeIndex>>(&mut self, connected: I) {
+		for who in connected {
+			if self.providers.len() >= self.max_providers {
+				break;
+			}
+			if !self.flagged.contains(&who) && !self.providers.contains(&who) {
+				trace!(target: "sync", "Following heads of {}", who);
+				self.providers.insert(who);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn canonical(number: u64) -> Option<u64> {
+		Some(number * 10)
+	}
+
+	#[test]
+	fn only_limited_number_of_providers_is_followed() {
+		let mut check = HeadCrossCheck::<u64, u64>::new(2);
+		check.on_connect(1);
+		check.on_connect(2);
+		check.on_connect(3);
+		assert_eq!(check.providers(), &vec![1, 2].into_iter().collect());
+
+		// announcements of non-providers aren't checked
+		assert!(check.on_announce(3, 5, 51, 10, canonical));
+
+		check.on_disconnect(1, vec![2, 3]);
+		assert_eq!(check.providers(), &vec![2, 3].into_iter().collect());
+	}
+
+	#[test]
+	fn conflict_with_finalized_chain_is_flagged() {
+		let mut check = HeadCrossCheck::<u64, u64>::new(2);
+		check.on_connect(1);
+		check.on_connect(2);
+
+		assert!(check.on_announce(1, 5, 50, 10, canonical));
+		assert!(!check.on_announce(2, 5, 51, 10, canonical));
+		assert_eq!(check.flagged(), &vec![2].into_iter().collect());
+		assert_eq!(check.providers(), &vec![1].into_iter().collect());
+
+		// flagged peer is never followed again
+		check.on_connect(2);
+		check.on_disconnect(3, vec![2]);
+		assert_eq!(check.providers(), &vec![1].into_iter().collect());
+	}
+
+	#[test]
+	fn abandoned_forks_announced_before_finalization_are_not_flagged() {
+		let mut check = HeadCrossCheck::<u64, u64>::new(3);
+		check.on_connect(1);
+		check.on_connect(2);
+		check.on_connect(3);
+
+		assert!(check.on_announce(1, 12, 120, 10, canonical));
+		assert!(check.on_announce(2, 12, 121, 10, canonical));
+		assert!(check.on_announce(3, 14, 141, 10, canonical));
+
+		check.on_finalized(12, canonical);
+		assert!(check.flagged().is_empty());
+		assert_eq!(check.providers(), &vec![1, 2, 3].into_iter().collect());
+		assert_eq!(check.pending.len(), 1);
+
+		check.on_finalized(14, canonical);
+		assert!(check.flagged().is_empty());
+		assert!(check.pending.is_empty());
+
+		// the same fork announced after the finalization conflicts with the finalized chain
+		assert!(!check.on_announce(2, 12, 121, 14, canonical));
+		assert_eq!(check.flagged(), &vec![2].into_iter().collect());
+	}
+}
@@ -24,17 +24,21 @@
 //! The `BasicQueue` and `BasicVerifier` traits allow serial queues to be
 //! instantiated simply.
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Weak};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use parking_lot::{Condvar, Mutex, RwLock};
 use network_libp2p::{NodeIndex, Severity};
-use primitives::AuthorityId;
+use primitives::{AuthorityId, blake2_256, ed25519};
+use codec::Encode;
 
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor, Zero};
 
 pub use blocks::BlockData;
 use chain::Client;
+use client::light::blockchain::{Blockchain as LightBlockchain, Storage as LightStorage};
+use client::light::fetcher::Fetcher;
 use error::{ErrorKind, Error};
 use protocol::Context;
 use service::ExecuteInContext;
@@ -46,18 +50,88 @@ pub use consensus::{ImportBlock, ImportResult, BlockOrigin};
 #[cfg(any(test, feature = "test-helpers"))]
 use std::cell::RefCell;
 
+/// Error of the block verification, with the message presented to the user in the logs.
+#[derive(Debug, PartialEq)]
+pub enum VerificationError {
+	/// The block is invalid.
+	Invalid(String),
+	/// The block can't be verified now, e.g. because the state of its parent is missing or can't
+	/// be read. The block may still be valid, so the peer that has sent it isn't penalized.
+	Unavailable(String),
+}
+
+impl From<String> for VerificationError {
+	fn from(message: String) -> Self {
+		VerificationError::Invalid(message)
+	}
+}
+
 /// Verify a justification of a block
 pub trait Verifier<B: BlockT>: Send + Sync + Sized {
 	/// Verify the given data and return the ImportBlock and an optional
-	/// new set of validators to import. If not, err with an Error-Message
-	/// presented to the User in the logs.
+	/// new set of validators to import. If not, err with the reason.
 	fn verify(
 		&self,
 		origin: BlockOrigin,
 		header: B::Header,
 		justification: Vec<u8>,
 		body: Option<Vec<B::Extrinsic>>
-	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), String>;
+	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), VerificationError>;
+
+	/// Returns the time to wait before the block may be verified, if its timestamp is too far
+	/// in the future to verify it now. Such blocks (and their descendants) are held by the queue
+	/// and retried once the delay passes, instead of being rejected.
+	fn defer(&self, _header: &B::Header) -> Option<Duration> {
+		None
+	}
+}
+
+/// Verifier of the light client, wrapping the verifier of the consensus engine. The authority set
+/// changes returned by the wrapped verifier are checked against the state of the block, read by
+/// the remote node along with the storage proof.
+pub struct LightVerifier<V, S, F> {
+	inner: V,
+	blockchain: Arc<LightBlockchain<S, F>>,
+}
+
+impl<V, S, F> LightVerifier<V, S, F> {
+	/// Wrap the verifier of the consensus engine.
+	pub fn new(inner: V, blockchain: Arc<LightBlockchain<S, F>>) -> Self {
+		LightVerifier {
+			inner,
+			blockchain,
+		}
+	}
+}
+
+impl<B, V, S, F> Verifier<B> for LightVerifier<V, S, F> where
+	B: BlockT,
+	V: Verifier<B>,
+	S: LightStorage<B> + Send + Sync,
+	F: Fetcher<B> + Send + Sync,
+{
+	fn verify(
+		&self,
+		origin: BlockOrigin,
+		header: B::Header,
+		justification: Vec<u8>,
+		body: Option<Vec<B::Extrinsic>>
+	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), VerificationError> {
+		// the verifier may strip the seal from the header, while the remote node reads the state
+		// of the sealed block
+		let sealed_header = header.clone();
+		let (import_block, authorities) = self.inner.verify(origin, header, justification, body)?;
+		let authorities = match authorities {
+			Some(authorities) => self.blockchain.check_authorities(&sealed_header, authorities)
+				.map_err(|e| format!("Error checking authorities of {:?}: {}", sealed_header.hash(), e))?,
+			None => None,
+		};
+		Ok((import_block, authorities))
+	}
+
+	fn defer(&self, header: &B::Header) -> Option<Duration> {
+		self.inner.defer(header)
+	}
 }
 
 /// Blocks import queue API.
@@ -87,6 +161,151 @@ pub trait ImportQueue<B: BlockT>: Send + Sync {
 	fn is_importing(&self, hash: &B::Hash) -> bool;
 	/// Import bunch of blocks.
 	fn import_blocks(&self, origin: BlockOrigin, blocks: Vec<BlockData<B>>);
+	/// Replace the set of rules that imported blocks are checked against.
+	fn set_block_rules(&self, _rules: BlockRules<B>) {}
+	/// Set the source of extrinsic signatures to check ahead of block execution.
+	fn set_extrinsic_signatures(&self, _signatures: Arc<ExtrinsicSignatures<B>>) {}
+}
+
+/// Number of threads checking extrinsic signatures of queued blocks.
+const PREVERIFY_THREADS: usize = 4;
+
+/// Maximal number of blocks held by the queue until their timestamp becomes valid.
+const MAX_DEFERRED_BLOCKS: usize = 256;
+/// Blocks that would have to be held longer than this (in seconds) are rejected at once.
+const MAX_DEFER_SEC: u64 = 600;
+
+/// Ed25519-signed payload of an extrinsic.
+pub struct SignedPayload {
+	/// The signature.
+	pub signature: [u8; 64],
+	/// The message that was signed.
+	pub message: Vec<u8>,
+	/// Public key of the signer.
+	pub signer: [u8; 32],
+}
+
+/// Extracts signatures of extrinsics, so that the import queue can check them
+/// on a worker pool before the blocks are executed.
+///
+/// Results are cached by extrinsic hash and reused by the runtime when it
+/// verifies the same signature during execution.
+pub trait ExtrinsicSignatures<B: BlockT>: Send + Sync {
+	/// Get the signed payload of an extrinsic included in the block with given number,
+	/// if it is signed and the payload can be determined outside of the runtime.
+	fn signed_payload(&self, number: NumberFor<B>, extrinsic: &B::Extrinsic) -> Option<SignedPayload>;
+}
+
+/// Operator-supplied rules that blocks must satisfy before being imported.
+///
+/// Allows known-bad blocks to be rejected and forks contradicting required
+/// canonical hashes to be refused, regardless of what the verifier says.
+/// Oversized blocks are rejected before they are verified and executed.
+#[derive(Debug, Clone)]
+pub struct BlockRules<B: BlockT> {
+	bad_blocks: HashSet<B::Hash>,
+	fork_blocks: HashMap<NumberFor<B>, B::Hash>,
+	limits: BlockLimits,
+}
+
+/// Resource limits of a single block.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockLimits {
+	/// Maximal size of the encoded block (header and body), in bytes.
+	pub max_block_size: Option<usize>,
+	/// Maximal number of extrinsics in the block body.
+	pub max_extrinsics: Option<usize>,
+}
+
+/// Outcome of checking a block against `BlockRules`.
+#[derive(Debug, PartialEq)]
+pub enum BlockRulesCheck<H> {
+	/// No rule applies to the block.
+	Allowed,
+	/// The block is explicitly marked as bad.
+	KnownBad,
+	/// The block has a different hash than the one required at its height.
+	Mismatch {
+		/// Hash that is required at this height.
+		expected: H,
+	},
+	/// The encoded block is larger than allowed.
+	TooLarge {
+		/// Size of the encoded block.
+		size: usize,
+		/// Maximal allowed size.
+		limit: usize,
+	},
+	/// The block contains more extrinsics than allowed.
+	TooManyExtrinsics {
+		/// Number of extrinsics in the block.
+		count: usize,
+		/// Maximal allowed number of extrinsics.
+		limit: usize,
+	},
+}
+
+impl<B: BlockT> Default for BlockRules<B> {
+	fn default() -> Self {
+		BlockRules {
+			bad_blocks: HashSet::new(),
+			fork_blocks: HashMap::new(),
+			limits: Default::default(),
+		}
+	}
+}
+
+impl<B: BlockT> BlockRules<B> {
+	/// Mark block with given hash as bad.
+	pub fn mark_bad(&mut self, hash: B::Hash) {
+		self.bad_blocks.insert(hash);
+	}
+
+	/// Require block at given height to have given hash.
+	pub fn require_hash(&mut self, number: NumberFor<B>, hash: B::Hash) {
+		self.fork_blocks.insert(number, hash);
+	}
+
+	/// Limit resources a single block may use.
+	pub fn set_limits(&mut self, limits: BlockLimits) {
+		self.limits = limits;
+	}
+
+	/// Are there no rules at all?
+	pub fn is_empty(&self) -> bool {
+		self.bad_blocks.is_empty() && self.fork_blocks.is_empty() && self.limits == Default::default()
+	}
+
+	/// Check block with given number and hash against the rules.
+	pub fn check(&self, number: NumberFor<B>, hash: &B::Hash) -> BlockRulesCheck<B::Hash> {
+		if self.bad_blocks.contains(hash) {
+			return BlockRulesCheck::KnownBad;
+		}
+
+		match self.fork_blocks.get(&number) {
+			Some(expected) if expected != hash => BlockRulesCheck::Mismatch { expected: expected.clone() },
+			_ => BlockRulesCheck::Allowed,
+		}
+	}
+
+	/// Check block header and body against the resource limits.
+	pub fn check_limits(&self, header: &B::Header, body: Option<&Vec<B::Extrinsic>>) -> BlockRulesCheck<B::Hash> {
+		let count = body.map(|body| body.len()).unwrap_or(0);
+		if let Some(limit) = self.limits.max_extrinsics {
+			if count > limit {
+				return BlockRulesCheck::TooManyExtrinsics { count, limit };
+			}
+		}
+
+		if let Some(limit) = self.limits.max_block_size {
+			let size = header.encode().len() + body.map(|body| body.encode().len()).unwrap_or(0);
+			if size > limit {
+				return BlockRulesCheck::TooLarge { size, limit };
+			}
+		}
+
+		BlockRulesCheck::Allowed
+	}
 }
 
 /// Import queue status. It isn't completely accurate.
@@ -105,13 +324,16 @@ pub struct BasicQueue<B: BlockT, V: 'static + Verifier<B>> {
 	verifier: Arc<V>,
 }
 
-/// Locks order: queue, queue_blocks, best_importing_number
+/// Locks order: queue, deferred, queue_blocks, best_importing_number
 struct AsyncImportQueueData<B: BlockT> {
 	signal: Condvar,
 	queue: Mutex<VecDeque<(BlockOrigin, Vec<BlockData<B>>)>>,
+	deferred: Mutex<Vec<(Instant, BlockOrigin, Vec<BlockData<B>>)>>,
 	queue_blocks: RwLock<HashSet<B::Hash>>,
 	best_importing_number: RwLock<<<B as BlockT>::Header as HeaderT>::Number>,
 	is_stopping: AtomicBool,
+	rules: RwLock<BlockRules<B>>,
+	signatures: RwLock<Option<Arc<ExtrinsicSignatures<B>>>>,
 }
 
 impl<B: BlockT, V: Verifier<B>> BasicQueue<B, V> {
@@ -130,9 +352,12 @@ impl<B: BlockT> AsyncImportQueueData<B> {
 		Self {
 			signal: Default::default(),
 			queue: Mutex::new(VecDeque::new()),
+			deferred: Mutex::new(Vec::new()),
 			queue_blocks: RwLock::new(HashSet::new()),
 			best_importing_number: RwLock::new(Zero::zero()),
 			is_stopping: Default::default(),
+			rules: RwLock::new(Default::default()),
+			signatures: RwLock::new(None),
 		}
 	}
 }
@@ -156,9 +381,11 @@ impl<B: BlockT, V: 'static + Verifier<B>> ImportQueue<B> for BasicQueue<B, V> {
 
 	fn clear(&self) {
 		let mut queue = self.data.queue.lock();
+		let mut deferred = self.data.deferred.lock();
 		let mut queue_blocks = self.data.queue_blocks.write();
 		let mut best_importing_number = self.data.best_importing_number.write();
 		queue_blocks.clear();
+		deferred.clear();
 		queue.clear();
 		*best_importing_number = Zero::zero();
 	}
@@ -202,6 +429,14 @@ impl<B: BlockT, V: 'static + Verifier<B>> ImportQueue<B> for BasicQueue<B, V> {
 		queue.push_back((origin, blocks));
 		self.data.signal.notify_one();
 	}
+
+	fn set_block_rules(&self, rules: BlockRules<B>) {
+		*self.data.rules.write() = rules;
+	}
+
+	fn set_extrinsic_signatures(&self, signatures: Arc<ExtrinsicSignatures<B>>) {
+		*self.data.signatures.write() = Some(signatures);
+	}
 }
 
 impl<B: BlockT, V: 'static + Verifier<B>> Drop for BasicQueue<B, V> {
@@ -224,26 +459,56 @@ fn import_thread<B: BlockT, E: ExecuteInContext<B>, V: Verifier<B>>(
 			break;
 		}
 
-		let new_blocks = {
+		let (origin, mut blocks) = {
 			let mut queue_lock = qdata.queue.lock();
+			requeue_deferred(&qdata, &mut queue_lock);
 			if queue_lock.is_empty() {
-				qdata.signal.wait(&mut queue_lock);
+				let retry_at = qdata.deferred.lock().iter().map(|d| d.0).min();
+				match retry_at {
+					Some(retry_at) => { qdata.signal.wait_until(&mut queue_lock, retry_at); },
+					None => { qdata.signal.wait(&mut queue_lock); },
+				}
+				requeue_deferred(&qdata, &mut queue_lock);
 			}
 
 			match queue_lock.pop_front() {
 				Some(new_blocks) => new_blocks,
-				None => break,
+				// woken up to stop or to retry blocks that are not due yet
+				None => continue,
 			}
 		};
 
+		// blocks are ordered, so all blocks after the first one that is too far in the future
+		// are held, too.
+		let deferral = blocks.iter().enumerate()
+			.filter_map(|(index, b)| b.block.header.as_ref()
+				.and_then(|header| verifier.defer(header))
+				.map(|delay| (index, delay)))
+			.next();
+		if let Some((index, delay)) = deferral {
+			let deferred = blocks.split_off(index);
+			if let Some(rejected) = defer_blocks(&qdata, origin.clone(), deferred, delay) {
+				blocks.extend(rejected);
+			}
+		}
+		if blocks.is_empty() {
+			continue;
+		}
+		let new_blocks = (origin, blocks);
+
 		match (sync.upgrade(), service.upgrade(), chain.upgrade()) {
 			(Some(sync), Some(service), Some(chain)) => {
 				let blocks_hashes: Vec<B::Hash> = new_blocks.1.iter().map(|b| b.block.hash.clone()).collect();
+				let rules = qdata.rules.read().clone();
+				if let Some(signatures) = qdata.signatures.read().clone() {
+					preverify_signatures(&*signatures, &new_blocks.1);
+				}
 				if !import_many_blocks(
 					&mut SyncLink{chain: &sync, client: &*chain, context: &*service},
 					Some(&*qdata),
 					new_blocks,
 					verifier.clone(),
+					&rules,
 				) {
 					break;
 				}
@@ -259,18 +524,85 @@ fn import_thread<B: BlockT, E: ExecuteInContext<B>, V: Verifier<B>>(
 
 	trace!(target: "sync", "Stopping import thread");
 }
+
+/// Move deferred blocks that are due back to the queue.
+fn requeue_deferred<B: BlockT>(qdata: &AsyncImportQueueData<B>, queue: &mut VecDeque<(BlockOrigin, Vec<BlockData<B>>)>) {
+	let now = Instant::now();
+	let mut deferred = qdata.deferred.lock();
+	let (due, pending): (Vec<_>, Vec<_>) = deferred.drain(..).partition(|d| d.0 <= now);
+	*deferred = pending;
+	for (_, origin, blocks) in due {
+		trace!(target: "sync", "Retrying import of {} deferred blocks", blocks.len());
+		queue.push_back((origin, blocks));
+	}
+}
+
+/// Hold blocks until given delay passes. Blocks that can't be held are returned back.
+fn defer_blocks<B: BlockT>(
+	qdata: &AsyncImportQueueData<B>,
+	origin: BlockOrigin,
+	blocks: Vec<BlockData<B>>,
+	delay: Duration,
+) -> Option<Vec<BlockData<B>>> {
+	let mut deferred = qdata.deferred.lock();
+	let count: usize = deferred.iter().map(|d| d.2.len()).sum();
+	if delay > Duration::from_secs(MAX_DEFER_SEC) || count + blocks.len() > MAX_DEFERRED_BLOCKS {
+		debug!(target: "sync", "Unable to defer {} blocks for {:?}", blocks.len(), delay);
+		return Some(blocks);
+	}
+
+	debug!(target: "sync", "Deferring import of {} blocks for {:?}", blocks.len(), delay);
+	deferred.push((Instant::now() + delay, origin, blocks));
+	None
+}
+
+/// Check signatures of all extrinsics of given blocks in parallel, so that block
+/// execution can reuse the results instead of serializing on crypto.
+fn preverify_signatures<B: BlockT>(signatures: &ExtrinsicSignatures<B>, blocks: &[BlockData<B>]) {
+	let mut chunks: Vec<Vec<([u8; 32], SignedPayload)>> = (0..PREVERIFY_THREADS).map(|_| Vec::new()).collect();
+	let mut count = 0;
+	for block in blocks {
+		let (header, body) = match (block.block.header.as_ref(), block.block.body.as_ref()) {
+			(Some(header), Some(body)) => (header, body),
+			_ => continue,
+		};
+		for extrinsic in body {
+			if let Some(payload) = signatures.signed_payload(*header.number(), extrinsic) {
+				chunks[count % PREVERIFY_THREADS].push((blake2_256(&extrinsic.encode()), payload));
+				count += 1;
+			}
+		}
+	}
+
+	trace!(target: "sync", "Pre-verifying {} extrinsic signatures", count);
+	let workers: Vec<_> = chunks.into_iter()
+		.filter(|chunk| !chunk.is_empty())
+		.filter_map(|chunk| ::std::thread::Builder::new().name("ImportQueuePreverify".into()).spawn(move || {
+			for (hash, payload) in chunk {
+				ed25519::preverify(hash, &payload.signature[..], &payload.message, &payload.signer[..]);
+			}
+		}).ok())
+		.collect();
+	for worker in workers {
+		// signatures left unchecked are verified during execution anyway.
+		let _ = worker.join();
+	}
+}
+
 /// ChainSync link trait.
 trait SyncLinkApi<B: BlockT> {
 	/// Get chain reference.
 	fn chain(&self) -> &Client<B>;
 	/// Block imported.
 	fn block_imported(&mut self, hash: &B::Hash, number: NumberFor<B>);
+	/// Block is invalid, it and its descendants must not be requested.
+	fn block_invalid(&mut self, hash: &B::Hash);
 	/// Maintain sync.
 	fn maintain_sync(&mut self);
 	/// Disconnect from peer.
 	fn useless_peer(&mut self, who: NodeIndex, reason: &str);
-	/// Disconnect from peer and restart sync.
-	fn note_useless_and_restart_sync(&mut self, who: NodeIndex, reason: &str);
+	/// Disconnect from misbehaving peer and restart sync.
+	fn note_bad_and_restart_sync(&mut self, who: NodeIndex, reason: &str);
 	/// Restart sync.
 	fn restart(&mut self);
 }
@@ -305,6 +637,10 @@ impl<'a, B: 'static + BlockT, E: 'a + ExecuteInContext<B>> SyncLinkApi<B> for Sy
 		self.with_sync(|sync, _| sync.block_imported(&hash, number))
 	}
 
+	fn block_invalid(&mut self, hash: &B::Hash) {
+		self.with_sync(|sync, _| sync.block_invalid(hash))
+	}
+
 	fn maintain_sync(&mut self) {
 		self.with_sync(|sync, protocol| sync.maintain_sync(protocol))
 	}
@@ -313,9 +649,9 @@ impl<'a, B: 'static + BlockT, E: 'a + ExecuteInContext<B>> SyncLinkApi<B> for Sy
 		self.with_sync(|_, protocol| protocol.report_peer(who, Severity::Useless(reason)))
 	}
 
-	fn note_useless_and_restart_sync(&mut self, who: NodeIndex, reason: &str) {
+	fn note_bad_and_restart_sync(&mut self, who: NodeIndex, reason: &str) {
 		self.with_sync(|sync, protocol| {
-			protocol.report_peer(who, Severity::Useless(reason));	// is this actually malign or just useless?
+			protocol.report_peer(who, Severity::Bad(reason));
 			sync.restart(protocol);
 		})
 	}
@@ -336,15 +672,17 @@ enum BlockImportResult<H: ::std::fmt::Debug + PartialEq, N: ::std::fmt::Debug +
 
 /// Block import error.
 #[derive(Debug, PartialEq)]
-enum BlockImportError {
+enum BlockImportError<H: ::std::fmt::Debug + PartialEq> {
 	/// Block missed header, can't be imported
 	IncompleteHeader(Option<NodeIndex>),
 	/// Block missed justification, can't be imported
 	IncompleteJustification(Option<NodeIndex>),
 	/// Block verification failed, can't be imported
-	VerificationFailed(Option<NodeIndex>, String),
-	/// Block is known to be Bad
-	BadBlock(Option<NodeIndex>),
+	VerificationFailed(Option<NodeIndex>, H, String),
+	/// Block can't be verified now, but may be valid
+	VerificationUnavailable(H, String),
+	/// Block is invalid: known to be bad or violating the block rules
+	BadBlock(Option<NodeIndex>, H, String),
 	/// Block has an unknown parent
 	UnknownParent,
 	/// Other Error.
@@ -356,7 +694,8 @@ fn import_many_blocks<'a, B: BlockT, V: Verifier<B>>(
 	link: &mut SyncLinkApi<B>,
 	qdata: Option<&AsyncImportQueueData<B>>,
 	blocks: (BlockOrigin, Vec<BlockData<B>>),
-	verifier: Arc<V>
+	verifier: Arc<V>,
+	rules: &BlockRules<B>,
 ) -> bool
 {
 	let (blocks_origin, blocks) = blocks;
@@ -380,6 +719,7 @@ fn import_many_blocks<'a, B: BlockT, V: Verifier<B>>(
 			blocks_origin.clone(),
 			block,
 			verifier.clone(),
+			rules,
 		);
 		let is_import_failed = import_result.is_err();
 		imported += process_import_result(link, import_result);
@@ -403,8 +743,9 @@ fn import_single_block<B: BlockT, V: Verifier<B>>(
 	chain: &Client<B>,
 	block_origin: BlockOrigin,
 	block: BlockData<B>,
-	verifier: Arc<V>
-) -> Result<BlockImportResult<B::Hash, <<B as BlockT>::Header as HeaderT>::Number>, BlockImportError>
+	verifier: Arc<V>,
+	rules: &BlockRules<B>,
+) -> Result<BlockImportResult<B::Hash, <<B as BlockT>::Header as HeaderT>::Number>, BlockImportError<B::Hash>>
 {
 	let peer = block.origin;
 	let block = block.block;
@@ -432,14 +773,44 @@ fn import_single_block<B: BlockT, V: Verifier<B>>(
 	let number = header.number().clone();
 	let hash = header.hash();
 	let parent = header.parent_hash().clone();
+	let check = match rules.check(number, &hash) {
+		BlockRulesCheck::Allowed => rules.check_limits(&header, block.body.as_ref()),
+		check => check,
+	};
+	match check {
+		BlockRulesCheck::Allowed => (),
+		BlockRulesCheck::KnownBad => {
+			debug!(target: "sync", "Rejecting known bad block {}: {:?}", number, hash);
+			return Err(BlockImportError::BadBlock(peer, hash, "Sent us a known bad block".into())) //TODO: use persistent ID
+		},
+		BlockRulesCheck::Mismatch { expected } => {
+			debug!(target: "sync", "Rejecting block {}: {:?}, required fork block is {:?}", number, hash, expected);
+			return Err(BlockImportError::BadBlock(peer, hash, "Sent us a block of the wrong fork".into())) //TODO: use persistent ID
+		},
+		BlockRulesCheck::TooLarge { size, limit } => {
+			debug!(target: "sync", "Rejecting block {}: {:?}, encoded size {} exceeds limit {}", number, hash, size, limit);
+			return Err(BlockImportError::BadBlock(peer, hash, "Sent us a block exceeding the size limit".into())) //TODO: use persistent ID
+		},
+		BlockRulesCheck::TooManyExtrinsics { count, limit } => {
+			debug!(target: "sync", "Rejecting block {}: {:?}, {} extrinsics exceed limit {}", number, hash, count, limit);
+			return Err(BlockImportError::BadBlock(peer, hash, "Sent us a block exceeding the extrinsics limit".into())) //TODO: use persistent ID
+		},
+	}
+
 	let (import_block, new_authorities) = verifier.verify(block_origin, header, justification, block.body)
-		.map_err(|msg| {
-			if let Some(peer) = peer {
-				trace!(target: "sync", "Verifying {}({}) from {} failed: {}", number, hash, peer, msg);
-			} else {
-				trace!(target: "sync", "Verifying {}({}) failed: {}", number, hash, msg);
-			}
-			BlockImportError::VerificationFailed(peer, msg)
+		.map_err(|e| match e {
+			VerificationError::Invalid(msg) => {
+				if let Some(peer) = peer {
+					trace!(target: "sync", "Verifying {}({}) from {} failed: {}", number, hash, peer, msg);
+				} else {
+					trace!(target: "sync", "Verifying {}({}) failed: {}", number, hash, msg);
+				}
+				BlockImportError::VerificationFailed(peer, hash, msg)
+			},
+			VerificationError::Unavailable(msg) => {
+				debug!(target: "sync", "Verification of {}({}) is unavailable: {}", number, hash, msg);
+				BlockImportError::VerificationUnavailable(hash, msg)
+			},
 		})?;
 
 	match chain.import(import_block, new_authorities) {
@@ -461,7 +832,7 @@ fn import_single_block<B: BlockT, V: Verifier<B>>(
 		},
 		Ok(ImportResult::KnownBad) => {
 			debug!(target: "sync", "Peer gave us a bad block {}: {:?}", number, hash);
-			Err(BlockImportError::BadBlock(peer)) //TODO: use persistent ID
+			Err(BlockImportError::BadBlock(peer, hash, "Sent us a bad block".into())) //TODO: use persistent ID
 		}
 		Err(e) => {
 			debug!(target: "sync", "Error importing block {}: {:?}: {:?}", number, hash, e);
@@ -473,7 +844,7 @@ fn import_single_block<B: BlockT, V: Verifier<B>>(
 /// Process single block import result.
 fn process_import_result<'a, B: BlockT>(
 	link: &mut SyncLinkApi<B>,
-	result: Result<BlockImportResult<B::Hash, <<B as BlockT>::Header as HeaderT>::Number>, BlockImportError>
+	result: Result<BlockImportResult<B::Hash, <<B as BlockT>::Header as HeaderT>::Number>, BlockImportError<B::Hash>>
 ) -> usize
 {
 	match result {
@@ -497,19 +868,23 @@ fn process_import_result<'a, B: BlockT>(
 			}
 			0
 		},
-		Err(BlockImportError::VerificationFailed(who, e)) => {
+		Err(BlockImportError::VerificationFailed(who, hash, e)) => {
+			link.block_invalid(&hash);
 			if let Some(peer) = who {
 				link.useless_peer(peer, &format!("Verification failed: {}", e));
 			}
 			0
 		},
-		Err(BlockImportError::BadBlock(who)) => {
+		Err(BlockImportError::BadBlock(who, hash, reason)) => {
+			link.block_invalid(&hash);
 			if let Some(peer) = who {
-				link.note_useless_and_restart_sync(peer, "Sent us a bad block");
+				link.note_bad_and_restart_sync(peer, &reason);
 			}
 			0
 		},
-		Err(BlockImportError::UnknownParent) | Err(BlockImportError::Error) => {
+		Err(BlockImportError::VerificationUnavailable(..)) |
+		Err(BlockImportError::UnknownParent) |
+		Err(BlockImportError::Error) => {
 			link.restart();
 			0
 		},
@@ -556,7 +931,7 @@ impl<B: BlockT> Verifier<B> for PassThroughVerifier {
 		header: B::Header,
 		justification: Vec<u8>,
 		body: Option<Vec<B::Extrinsic>>
-	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), String> {
+	) -> Result<(ImportBlock<B>, Option<Vec<AuthorityId>>), VerificationError> {
 		Ok((ImportBlock {
 			origin,
 			header,
@@ -572,12 +947,12 @@ impl<B: BlockT> Verifier<B> for PassThroughVerifier {
 #[cfg(any(test, feature = "test-helpers"))]
 /// Blocks import queue that is importing blocks in the same thread.
 /// The boolean value indicates whether blocks should be imported without instant finality.
-pub struct SyncImportQueue<B: BlockT, V: Verifier<B>>(Arc<V>, ImportCB<B>);
+pub struct SyncImportQueue<B: BlockT, V: Verifier<B>>(Arc<V>, ImportCB<B>, Arc<RwLock<BlockRules<B>>>);
 #[cfg(any(test, feature = "test-helpers"))]
 impl<B: BlockT, V: Verifier<B>> SyncImportQueue<B, V> {
 	/// Create a new SyncImportQueue wrapping the given Verifier
 	pub fn new(verifier: Arc<V>) -> Self {
-		SyncImportQueue(verifier, ImportCB::new())
+		SyncImportQueue(verifier, ImportCB::new(), Arc::new(RwLock::new(Default::default())))
 	}
 }
 
@@ -591,8 +966,10 @@ impl<B: 'static + BlockT, V: 'static + Verifier<B>> ImportQueue<B> for SyncImpor
 		chain: Weak<Client<B>>
 	) -> Result<(), Error> {
 		let v = self.0.clone();
+		let r = self.2.clone();
 		self.1.set(Box::new(move | origin, new_blocks | {
 			let verifier = v.clone();
+			let rules = r.read().clone();
 			match (sync.upgrade(), service.upgrade(), chain.upgrade()) {
 				(Some(sync), Some(service), Some(chain)) =>
 					import_many_blocks(
@@ -600,6 +977,7 @@ impl<B: 'static + BlockT, V: 'static + Verifier<B>> ImportQueue<B> for SyncImpor
 						None,
 						(origin, new_blocks),
 						verifier,
+						&rules,
 					),
 				_ => false
 			}
@@ -624,6 +1002,10 @@ impl<B: 'static + BlockT, V: 'static + Verifier<B>> ImportQueue<B> for SyncImpor
 	fn import_blocks(&self, origin: BlockOrigin, blocks: Vec<BlockData<B>>) {
 		self.1.call(origin, blocks);
 	}
+
+	fn set_block_rules(&self, rules: BlockRules<B>) {
+		*self.2.write() = rules;
+	}
 }
 
 #[cfg(test)]
@@ -640,6 +1022,7 @@ pub mod tests {
 	struct TestLink {
 		chain: Arc<Client<Block>>,
 		imported: usize,
+		invalid: usize,
 		maintains: usize,
 		disconnects: usize,
 		restarts: usize,
@@ -650,6 +1033,7 @@ pub mod tests {
 			TestLink {
 				chain: Arc::new(test_client::new()),
 				imported: 0,
+				invalid: 0,
 				maintains: 0,
 				disconnects: 0,
 				restarts: 0,
@@ -657,16 +1041,17 @@ pub mod tests {
 		}
 
 		fn total(&self) -> usize {
-			self.imported + self.maintains + self.disconnects + self.restarts
+			self.imported + self.invalid + self.maintains + self.disconnects + self.restarts
 		}
 	}
 
 	impl SyncLinkApi<Block> for TestLink {
 		fn chain(&self) -> &Client<Block> { &*self.chain }
 		fn block_imported(&mut self, _hash: &Hash, _number: NumberFor<Block>) { self.imported += 1; }
+		fn block_invalid(&mut self, _hash: &Hash) { self.invalid += 1; }
 		fn maintain_sync(&mut self) { self.maintains += 1; }
 		fn useless_peer(&mut self, _: NodeIndex, _: &str) { self.disconnects += 1; }
-		fn note_useless_and_restart_sync(&mut self, _: NodeIndex, _: &str) { self.disconnects += 1; self.restarts += 1; }
+		fn note_bad_and_restart_sync(&mut self, _: NodeIndex, _: &str) { self.disconnects += 1; self.restarts += 1; }
 		fn restart(&mut self) { self.restarts += 1; }
 	}
 
@@ -688,11 +1073,53 @@ pub mod tests {
 		(client, hash, number, BlockData { block, origin: Some(0) })
 	}
 
+	struct TestSignatures(Mutex<usize>);
+
+	impl ExtrinsicSignatures<Block> for TestSignatures {
+		fn signed_payload(&self, _number: u64, extrinsic: &test_client::runtime::Extrinsic) -> Option<SignedPayload> {
+			*self.0.lock() += 1;
+			Some(SignedPayload {
+				signature: (extrinsic.signature.0).0,
+				message: extrinsic.transfer.encode(),
+				signer: extrinsic.transfer.from.0,
+			})
+		}
+	}
+
+	#[test]
+	fn preverify_signatures_checks_all_extrinsics() {
+		use keyring::Keyring;
+		use test_client::runtime::{Extrinsic, Transfer};
+
+		let (_, _, _, mut block) = prepare_good_block();
+		let transfer = Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Bob.to_raw_public().into(),
+			amount: 42,
+			nonce: 0,
+		};
+		let signature = Keyring::Alice.sign(&transfer.encode()).into();
+		let extrinsic = Extrinsic { transfer, signature };
+		block.block.body = Some(vec![extrinsic.clone(), extrinsic.clone()]);
+
+		let signatures = TestSignatures(Mutex::new(0));
+		preverify_signatures(&signatures, &[block]);
+		assert_eq!(*signatures.0.lock(), 2);
+
+		let payload = signatures.signed_payload(1, &extrinsic).unwrap();
+		assert!(ed25519::verify_preverified(
+			&blake2_256(&extrinsic.encode()),
+			&payload.signature[..],
+			&payload.message,
+			&payload.signer[..],
+		));
+	}
+
 	#[test]
 	fn import_single_good_block_works() {
 		let (_, hash, number, block) = prepare_good_block();
 		assert_eq!(
-			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true))),
+			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true)), &BlockRules::default()),
 			Ok(BlockImportResult::ImportedUnknown(hash, number))
 		);
 	}
@@ -701,7 +1128,7 @@ pub mod tests {
 	fn import_single_good_known_block_is_ignored() {
 		let (client, hash, number, block) = prepare_good_block();
 		assert_eq!(
-			import_single_block(&client, BlockOrigin::File, block, Arc::new(PassThroughVerifier(true))),
+			import_single_block(&client, BlockOrigin::File, block, Arc::new(PassThroughVerifier(true)), &BlockRules::default()),
 			Ok(BlockImportResult::ImportedKnown(hash, number))
 		);
 	}
@@ -711,7 +1138,7 @@ pub mod tests {
 		let (_, _, _, mut block) = prepare_good_block();
 		block.block.header = None;
 		assert_eq!(
-			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true))),
+			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true)), &BlockRules::default()),
 			Err(BlockImportError::IncompleteHeader(Some(0)))
 		);
 	}
@@ -721,7 +1148,7 @@ pub mod tests {
 		let (_, _, _, mut block) = prepare_good_block();
 		block.block.justification = None;
 		assert_eq!(
-			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true))),
+			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true)), &BlockRules::default()),
 			Err(BlockImportError::IncompleteJustification(Some(0)))
 		);
 	}
@@ -752,6 +1179,19 @@ pub mod tests {
 		assert_eq!(link.total(), 1);
 		assert_eq!(link.disconnects, 1);
 
+		let mut link = TestLink::new();
+		assert_eq!(process_import_result::<Block>(&mut link, Err(BlockImportError::VerificationFailed(Some(0), Default::default(), String::new()))), 0);
+		assert_eq!(link.total(), 2);
+		assert_eq!(link.invalid, 1);
+		assert_eq!(link.disconnects, 1);
+
+		let mut link = TestLink::new();
+		assert_eq!(process_import_result::<Block>(&mut link, Err(BlockImportError::BadBlock(Some(0), Default::default(), String::new()))), 0);
+		assert_eq!(link.total(), 3);
+		assert_eq!(link.invalid, 1);
+		assert_eq!(link.disconnects, 1);
+		assert_eq!(link.restarts, 1);
+
 		let mut link = TestLink::new();
 		assert_eq!(process_import_result::<Block>(&mut link, Err(BlockImportError::UnknownParent)), 0);
 		assert_eq!(link.total(), 1);
@@ -761,6 +1201,34 @@ pub mod tests {
 		assert_eq!(process_import_result::<Block>(&mut link, Err(BlockImportError::Error)), 0);
 		assert_eq!(link.total(), 1);
 		assert_eq!(link.restarts, 1);
+
+		let mut link = TestLink::new();
+		assert_eq!(process_import_result::<Block>(&mut link, Err(BlockImportError::VerificationUnavailable(Default::default(), String::new()))), 0);
+		assert_eq!(link.total(), 1);
+		assert_eq!(link.restarts, 1);
+	}
+
+	#[test]
+	fn import_single_block_failing_unavailable_verification_is_not_invalid() {
+		struct UnavailableVerifier;
+
+		impl Verifier<Block> for UnavailableVerifier {
+			fn verify(
+				&self,
+				_origin: BlockOrigin,
+				_header: <Block as BlockT>::Header,
+				_justification: Vec<u8>,
+				_body: Option<Vec<<Block as BlockT>::Extrinsic>>
+			) -> Result<(ImportBlock<Block>, Option<Vec<AuthorityId>>), VerificationError> {
+				Err(VerificationError::Unavailable("State of the parent is missing".into()))
+			}
+		}
+
+		let (_, hash, _, block) = prepare_good_block();
+		assert_eq!(
+			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(UnavailableVerifier), &BlockRules::default()),
+			Err(BlockImportError::VerificationUnavailable(hash, "State of the parent is missing".into()))
+		);
 	}
 
 	#[test]
@@ -773,10 +1241,97 @@ pub mod tests {
 			&mut TestLink::new(),
 			Some(&qdata),
 			(BlockOrigin::File, vec![block.clone(), block]),
-			verifier
+			verifier,
+			&BlockRules::default(),
 		));
 	}
 
+	#[test]
+	fn deferred_blocks_are_requeued_when_due() {
+		let (_, _, _, block) = prepare_good_block();
+		let qdata = AsyncImportQueueData::new();
+		let mut queue = VecDeque::new();
+
+		assert!(defer_blocks(&qdata, BlockOrigin::NetworkBroadcast, vec![block.clone()], Duration::from_secs(0)).is_none());
+		assert!(defer_blocks(&qdata, BlockOrigin::NetworkBroadcast, vec![block.clone()], Duration::from_secs(60)).is_none());
+		assert!(defer_blocks(&qdata, BlockOrigin::NetworkBroadcast, vec![block], Duration::from_secs(MAX_DEFER_SEC + 1)).is_some());
+
+		requeue_deferred(&qdata, &mut queue);
+		assert_eq!(queue.len(), 1);
+		assert_eq!(qdata.deferred.lock().len(), 1);
+	}
+
+	#[test]
+	fn import_single_known_bad_block_fails() {
+		let (_, hash, _, block) = prepare_good_block();
+		let mut rules = BlockRules::default();
+		rules.mark_bad(hash);
+		assert_eq!(
+			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true)), &rules),
+			Err(BlockImportError::BadBlock(Some(0), hash, "Sent us a known bad block".into()))
+		);
+	}
+
+	#[test]
+	fn import_single_block_contradicting_required_hash_fails() {
+		let (_, hash, number, block) = prepare_good_block();
+		let mut rules = BlockRules::default();
+		rules.require_hash(number, Default::default());
+		assert_eq!(
+			import_single_block(&test_client::new(), BlockOrigin::File, block.clone(), Arc::new(PassThroughVerifier(true)), &rules),
+			Err(BlockImportError::BadBlock(Some(0), hash, "Sent us a block of the wrong fork".into()))
+		);
+
+		let mut rules = BlockRules::default();
+		rules.require_hash(number, hash);
+		assert_eq!(
+			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true)), &rules),
+			Ok(BlockImportResult::ImportedUnknown(hash, number))
+		);
+	}
+
+	#[test]
+	fn import_single_block_exceeding_limits_fails() {
+		let (_, hash, number, mut block) = prepare_good_block();
+		block.block.body = Some(Vec::new());
+		let mut rules = BlockRules::default();
+		rules.set_limits(BlockLimits { max_block_size: Some(16), max_extrinsics: None });
+		assert_eq!(
+			import_single_block(&test_client::new(), BlockOrigin::File, block.clone(), Arc::new(PassThroughVerifier(true)), &rules),
+			Err(BlockImportError::BadBlock(Some(0), hash, "Sent us a block exceeding the size limit".into()))
+		);
+
+		let mut rules = BlockRules::default();
+		rules.set_limits(BlockLimits { max_block_size: Some(1024 * 1024), max_extrinsics: Some(0) });
+		assert_eq!(
+			import_single_block(&test_client::new(), BlockOrigin::File, block, Arc::new(PassThroughVerifier(true)), &rules),
+			Ok(BlockImportResult::ImportedUnknown(hash, number))
+		);
+	}
+
+	#[test]
+	fn block_limits_check_extrinsics_count() {
+		use keyring::Keyring;
+		use test_client::runtime::{Extrinsic, Transfer};
+
+		let (_, _, _, block) = prepare_good_block();
+		let header = block.block.header.unwrap();
+		let transfer = Transfer {
+			from: Keyring::Alice.to_raw_public().into(),
+			to: Keyring::Bob.to_raw_public().into(),
+			amount: 42,
+			nonce: 0,
+		};
+		let signature = Keyring::Alice.sign(&transfer.encode()).into();
+		let mut rules = BlockRules::<Block>::default();
+		rules.set_limits(BlockLimits { max_block_size: None, max_extrinsics: Some(0) });
+		assert_eq!(rules.check_limits(&header, None), BlockRulesCheck::Allowed);
+		assert_eq!(
+			rules.check_limits(&header, Some(&vec![Extrinsic { transfer, signature }])),
+			BlockRulesCheck::TooManyExtrinsics { count: 1, limit: 0 },
+		);
+	}
+
 	#[test]
 	fn async_import_queue_drops() {
 		let verifier = Arc::new(PassThroughVerifier(true));
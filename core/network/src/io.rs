@@ -23,6 +23,8 @@ use std::sync::Arc;
 pub trait SyncIo {
 	/// Report a peer for misbehaviour.
 	fn report_peer(&mut self, who: NodeIndex, reason: Severity);
+	/// Disconnect a peer that hasn't misbehaved, to connect to other peers instead.
+	fn rotate_peer(&mut self, who: NodeIndex);
 	/// Send a packet to a peer.
 	fn send(&mut self, who: NodeIndex, data: Vec<u8>);
 	/// Returns peer identifier string
@@ -51,7 +53,7 @@ impl<'s> NetSyncIo<'s> {
 
 impl<'s> SyncIo for NetSyncIo<'s> {
 	fn report_peer(&mut self, who: NodeIndex, reason: Severity) {
-		info!("Purposefully dropping {} ; reason: {:?}", who, reason);
+		info!(target: "sync", "Purposefully dropping {} ; reason: {:?}", who, reason);
 		match reason {
 			Severity::Bad(_) => self.network.lock().ban_node(who),
 			Severity::Useless(_) => self.network.lock().drop_node(who),
@@ -59,6 +61,11 @@ impl<'s> SyncIo for NetSyncIo<'s> {
 		}
 	}
 
+	fn rotate_peer(&mut self, who: NodeIndex) {
+		info!(target: "sync", "Rotating {}", who);
+		self.network.lock().rotate_node(who)
+	}
+
 	fn send(&mut self, who: NodeIndex, data: Vec<u8>) {
 		self.network.lock().send_custom_message(who, self.protocol, data)
 	}
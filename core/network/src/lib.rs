@@ -57,6 +57,9 @@ mod config;
 mod chain;
 mod blocks;
 mod on_demand;
+mod rebroadcast;
+mod head_check;
+mod watchdog;
 pub mod import_queue;
 pub mod consensus_gossip;
 pub mod error;
@@ -68,12 +71,12 @@ pub mod test;
 
 pub use chain::Client as ClientHandle;
 pub use service::{Service, FetchFuture, TransactionPool, Params, ManageNetwork, SyncProvider};
-pub use protocol::{ProtocolStatus, PeerInfo, Context};
+pub use protocol::{ProtocolStatus, PeerInfo, Context, LightServingStats};
 pub use sync::{Status as SyncStatus, SyncState};
-pub use network_libp2p::{NonReservedPeerMode, NetworkConfiguration, NodeIndex, ProtocolId, Severity, Protocol};
+pub use network_libp2p::{NonReservedPeerMode, NetworkConfiguration, NodeIndex, PeerId, ProtocolId, Severity, Protocol, parse_str_addr};
 pub use message::{generic as generic_message, RequestId, Status as StatusMessage};
 pub use error::Error;
-pub use config::{Roles, ProtocolConfig};
-pub use on_demand::{OnDemand, OnDemandService, RemoteResponse};
+pub use config::{Roles, ProtocolConfig, RebroadcastConfig, WatchdogConfig};
+pub use on_demand::{FetcherConfig, OnDemand, OnDemandService, PeerStats, RemoteResponse, StorageChangesSink};
 #[doc(hidden)]
 pub use runtime_primitives::traits::Block as BlockT;
@@ -22,12 +22,51 @@ pub use self::generic::{
 	BlockAnnounce, RemoteCallRequest, RemoteReadRequest,
 	RemoteHeaderRequest, RemoteHeaderResponse,
 	RemoteChangesRequest, RemoteChangesResponse,
-	FromBlock
+	RemoteStorageChanges, RemoteChildRootsRequest,
+	RemoteDigestRequest, RemoteDigestResponse, RemoteReadPrefixRequest,
+	RemoteBodyRequest, RemoteBodyResponse, RemoteReadChildRequest, RemoteReadMultiRequest, FromBlock
 };
 
 /// A unique ID of a request.
 pub type RequestId = u64;
 
+/// Version of the message envelope format produced by this node.
+///
+/// Should be bumped whenever a message type is added or an existing message is extended.
+pub const MESSAGE_VERSION: u8 = 12;
+
+/// Indices of the message types known to this version. Messages with other indices
+/// were introduced by newer versions and are ignored.
+const KNOWN_MESSAGE_TYPES: &[u8] = &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 255];
+
+/// Versioned wrapper for every message sent over the wire.
+///
+/// The payload is length-prefixed so that the receiver is able to skip message types it
+/// doesn't know about and to ignore trailing fields appended by newer versions.
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub struct Envelope {
+	/// Message format version of the sender.
+	pub version: u8,
+	/// Encoded message.
+	pub payload: Vec<u8>,
+}
+
+/// Result of decoding an incoming packet.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DecodedMessage<M> {
+	/// Message of a known type.
+	Known(M),
+	/// Message introduced by a newer version of the protocol. Should be ignored.
+	Unknown {
+		/// Message format version of the sender.
+		version: u8,
+		/// Message type index.
+		index: u8,
+	},
+	/// Packet is malformed.
+	Invalid,
+}
+
 /// Type alias for using the message type using block type parameters.
 pub type Message<B> = generic::Message<
 	<B as BlockT>::Header,
@@ -105,12 +144,35 @@ pub enum Direction {
 }
 
 /// Remote call response.
-#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct RemoteCallResponse {
 	/// Id of a request this response was made for.
 	pub id: RequestId,
 	/// Execution proof.
 	pub proof: Vec<Vec<u8>>,
+	/// Storage keys read by the call, in order of the first access. Empty unless requested or
+	/// in responses of older versions.
+	pub accessed_keys: Vec<Vec<u8>>,
+}
+
+// The accessed keys are appended at the end so that older versions are able to ignore them and
+// responses of older versions are still decodable.
+impl Encode for RemoteCallResponse {
+	fn encode_to<T: Output>(&self, dest: &mut T) {
+		self.id.encode_to(dest);
+		self.proof.encode_to(dest);
+		self.accessed_keys.encode_to(dest);
+	}
+}
+
+impl Decode for RemoteCallResponse {
+	fn decode<I: Input>(input: &mut I) -> Option<Self> {
+		Some(RemoteCallResponse {
+			id: Decode::decode(input)?,
+			proof: Decode::decode(input)?,
+			accessed_keys: Decode::decode(input).unwrap_or_default(),
+		})
+	}
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
@@ -122,12 +184,48 @@ pub struct RemoteReadResponse {
 	pub proof: Vec<Vec<u8>>,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Remote storage subscription request. Replaces previous subscription of the peer.
+pub struct RemoteStorageSubscribe {
+	/// Unique subscription id.
+	pub id: RequestId,
+	/// Storage keys to watch.
+	pub keys: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Remote storage subscription cancellation.
+pub struct RemoteStorageUnsubscribe {
+	/// Id of the subscription to cancel.
+	pub id: RequestId,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Remote child tries roots response.
+pub struct RemoteChildRootsResponse {
+	/// Id of a request this response was made for.
+	pub id: RequestId,
+	/// Proof of the child tries roots.
+	pub proof: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Remote storage prefix read response.
+pub struct RemoteReadPrefixResponse {
+	/// Id of a request this response was made for.
+	pub id: RequestId,
+	/// Proof of the page of storage entries.
+	pub proof: Vec<Vec<u8>>,
+}
+
 /// Generic types.
 pub mod generic {
+	use codec::{Encode, Decode, Input, Output};
 	use runtime_primitives::Justification;
 	use service::Roles;
 	use super::{
 		BlockAttributes, RemoteCallResponse, RemoteReadResponse,
+		RemoteStorageSubscribe, RemoteStorageUnsubscribe, RemoteChildRootsResponse, RemoteReadPrefixResponse,
 		RequestId, Transactions, Direction
 	};
 	/// Consensus is opaque to us
@@ -190,13 +288,39 @@ pub mod generic {
 		RemoteChangesRequest(RemoteChangesRequest<Hash>),
 		/// Remote changes reponse.
 		RemoteChangesResponse(RemoteChangesResponse<Number>),
+		/// Remote storage subscription request.
+		RemoteStorageSubscribe(RemoteStorageSubscribe),
+		/// Remote storage subscription cancellation.
+		RemoteStorageUnsubscribe(RemoteStorageUnsubscribe),
+		/// Storage changes pushed to the subscriber.
+		RemoteStorageChanges(RemoteStorageChanges<Hash>),
+		/// Remote child tries roots request.
+		RemoteChildRootsRequest(RemoteChildRootsRequest<Hash>),
+		/// Remote child tries roots response.
+		RemoteChildRootsResponse(RemoteChildRootsResponse),
+		/// Remote digest items request.
+		RemoteDigestRequest(RemoteDigestRequest<Number>),
+		/// Remote digest items response.
+		RemoteDigestResponse(RemoteDigestResponse<Header>),
+		/// Remote storage prefix read request.
+		RemoteReadPrefixRequest(RemoteReadPrefixRequest<Hash>),
+		/// Remote storage prefix read response.
+		RemoteReadPrefixResponse(RemoteReadPrefixResponse),
+		/// Remote block body request.
+		RemoteBodyRequest(RemoteBodyRequest<Hash>),
+		/// Remote block body response.
+		RemoteBodyResponse(RemoteBodyResponse<Extrinsic>),
+		/// Remote child storage read request. Answered with `RemoteReadResponse`.
+		RemoteReadChildRequest(RemoteReadChildRequest<Hash>),
+		/// Remote storage read request of multiple keys. Answered with `RemoteReadResponse`.
+		RemoteReadMultiRequest(RemoteReadMultiRequest<Hash>),
 		/// Chain-specific message
 		#[codec(index = "255")]
 		ChainSpecific(Vec<u8>),
 	}
 
 	/// Status sent on connection.
-	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	#[derive(Debug, PartialEq, Eq, Clone)]
 	pub struct Status<Hash, Number> {
 		/// Protocol version.
 		pub version: u32,
@@ -210,6 +334,45 @@ pub mod generic {
 		pub genesis_hash: Hash,
 		/// Chain-specific status.
 		pub chain_status: Vec<u8>,
+		/// Unix time (in milliseconds) of the moment the status has been sent. Absent in
+		/// statuses of older versions.
+		pub timestamp: Option<u64>,
+		/// Id of the fork of the chain the peer is on. Absent if the chain hasn't been forked
+		/// or in statuses of older versions.
+		pub fork_id: Option<Vec<u8>>,
+	}
+
+	// The timestamp and the fork id are appended at the end so that older versions are able to
+	// ignore them and statuses of older versions are still decodable. The timestamp slot is always
+	// sent, zero if the time is unknown, so that the fork id that follows it is never lost.
+	impl<Hash: Encode, Number: Encode> Encode for Status<Hash, Number> {
+		fn encode_to<T: Output>(&self, dest: &mut T) {
+			self.version.encode_to(dest);
+			self.roles.encode_to(dest);
+			self.best_number.encode_to(dest);
+			self.best_hash.encode_to(dest);
+			self.genesis_hash.encode_to(dest);
+			self.chain_status.encode_to(dest);
+			self.timestamp.unwrap_or(0).encode_to(dest);
+			if let Some(ref fork_id) = self.fork_id {
+				fork_id.encode_to(dest);
+			}
+		}
+	}
+
+	impl<Hash: Decode, Number: Decode> Decode for Status<Hash, Number> {
+		fn decode<I: Input>(input: &mut I) -> Option<Self> {
+			Some(Status {
+				version: Decode::decode(input)?,
+				roles: Decode::decode(input)?,
+				best_number: Decode::decode(input)?,
+				best_hash: Decode::decode(input)?,
+				genesis_hash: Decode::decode(input)?,
+				chain_status: Decode::decode(input)?,
+				timestamp: u64::decode(input).and_then(|timestamp| if timestamp == 0 { None } else { Some(timestamp) }),
+				fork_id: Decode::decode(input),
+			})
+		}
 	}
 
 	/// Request block data from a peer.
@@ -245,7 +408,7 @@ pub mod generic {
 		pub header: H,
 	}
 
-	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	#[derive(Debug, PartialEq, Eq, Clone)]
 	/// Remote call request.
 	pub struct RemoteCallRequest<H> {
 		/// Unique request id.
@@ -256,6 +419,33 @@ pub mod generic {
 		pub method: String,
 		/// Call data.
 		pub data: Vec<u8>,
+		/// Whether to include storage keys read by the call in the response. False in requests
+		/// of older versions.
+		pub trace_keys: bool,
+	}
+
+	// The flag is appended at the end so that older versions are able to ignore it and requests
+	// of older versions are still decodable.
+	impl<H: Encode> Encode for RemoteCallRequest<H> {
+		fn encode_to<T: Output>(&self, dest: &mut T) {
+			self.id.encode_to(dest);
+			self.block.encode_to(dest);
+			self.method.encode_to(dest);
+			self.data.encode_to(dest);
+			self.trace_keys.encode_to(dest);
+		}
+	}
+
+	impl<H: Decode> Decode for RemoteCallRequest<H> {
+		fn decode<I: Input>(input: &mut I) -> Option<Self> {
+			Some(RemoteCallRequest {
+				id: Decode::decode(input)?,
+				block: Decode::decode(input)?,
+				method: Decode::decode(input)?,
+				data: Decode::decode(input)?,
+				trace_keys: Decode::decode(input).unwrap_or(false),
+			})
+		}
 	}
 
 	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
@@ -269,6 +459,17 @@ pub mod generic {
 		pub key: Vec<u8>,
 	}
 
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Remote storage read request of multiple keys, proved by a single proof.
+	pub struct RemoteReadMultiRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Block at which to perform call.
+		pub block: H,
+		/// Storage keys.
+		pub keys: Vec<Vec<u8>>,
+	}
+
 	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
 	/// Remote header request.
 	pub struct RemoteHeaderRequest<N> {
@@ -315,4 +516,301 @@ pub mod generic {
 		/// Changes proof.
 		pub proof: Vec<Vec<u8>>,
 	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Remote child tries roots request.
+	pub struct RemoteChildRootsRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Block at which to read the roots.
+		pub block: H,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Remote digest items request.
+	pub struct RemoteDigestRequest<N> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Number of the first block of the range (including first).
+		pub first: N,
+		/// Number of the last block of the range (including last).
+		pub last: N,
+		/// Types of the requested digest items. The items are picked by the requester itself,
+		/// the responder proves the headers of the whole range.
+		pub item_types: Vec<u8>,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Remote digest items response.
+	pub struct RemoteDigestResponse<Header> {
+		/// Id of a request this response was made for.
+		pub id: RequestId,
+		/// Headers of all blocks of the range. Whole headers are sent, because the items can only
+		/// be checked against the CHT through the hash of the header, and the requester has to
+		/// make sure that no block with the requested items is omitted.
+		pub headers: Vec<Header>,
+		/// CHT-based proof of the headers.
+		pub proof: Vec<Vec<u8>>,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Remote storage prefix read request.
+	pub struct RemoteReadPrefixRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Block at which to perform read.
+		pub block: H,
+		/// Prefix of the storage keys.
+		pub prefix: Vec<u8>,
+		/// First key of the page (or the key preceding it, if it's missing).
+		pub start_key: Vec<u8>,
+		/// Maximal number of entries in the page.
+		pub limit: u32,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Changes of subscribed storage keys at a block.
+	pub struct RemoteStorageChanges<H> {
+		/// Id of the subscription these changes were made for.
+		pub id: RequestId,
+		/// Block at which the values were read.
+		pub block: H,
+		/// Changed keys with their new values.
+		pub changes: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+		/// Read proof of the new values.
+		pub proof: Vec<Vec<u8>>,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Remote block body request.
+	pub struct RemoteBodyRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Hash of the block which body is requested.
+		pub block: H,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Remote block body response.
+	pub struct RemoteBodyResponse<Extrinsic> {
+		/// Id of a request this response was made for.
+		pub id: RequestId,
+		/// Block body. None if the block or its body is unknown to the remote node.
+		pub body: Option<Vec<Extrinsic>>,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Remote child storage read request.
+	pub struct RemoteReadChildRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Block at which to perform read.
+		pub block: H,
+		/// Storage key of the child trie.
+		pub storage_key: Vec<u8>,
+		/// Child storage key.
+		pub key: Vec<u8>,
+	}
+}
+
+impl<Header, Hash, Number, Extrinsic> generic::Message<Header, Hash, Number, Extrinsic> where
+	Header: Encode + Decode,
+	Hash: Encode + Decode,
+	Number: Encode + Decode,
+	Extrinsic: Encode + Decode,
+{
+	/// Wrap the message into an envelope and encode it.
+	pub fn encode_envelope(&self) -> Vec<u8> {
+		Envelope {
+			version: MESSAGE_VERSION,
+			payload: self.encode(),
+		}.encode()
+	}
+
+	/// Decode the message from an envelope.
+	///
+	/// Unknown message types are reported as `DecodedMessage::Unknown`. Messages of known
+	/// types sent by newer versions may carry additional trailing fields that are ignored. If
+	/// a message from a newer version still fails to decode, it is considered unknown rather
+	/// than malformed.
+	pub fn decode_envelope(mut data: &[u8]) -> DecodedMessage<Self> {
+		let envelope = match Envelope::decode(&mut data) {
+			Some(envelope) => envelope,
+			None => return DecodedMessage::Invalid,
+		};
+
+		let index = match envelope.payload.first() {
+			Some(index) => *index,
+			None => return DecodedMessage::Invalid,
+		};
+		if !KNOWN_MESSAGE_TYPES.contains(&index) {
+			return DecodedMessage::Unknown { version: envelope.version, index };
+		}
+
+		match Self::decode(&mut &envelope.payload[..]) {
+			Some(message) => DecodedMessage::Known(message),
+			None if envelope.version > MESSAGE_VERSION =>
+				DecodedMessage::Unknown { version: envelope.version, index },
+			None => DecodedMessage::Invalid,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use codec::Encode;
+	use service::Roles;
+	use super::{Envelope, DecodedMessage, MESSAGE_VERSION, RemoteCallResponse, RemoteReadResponse, RemoteStorageSubscribe};
+	use super::generic::{self, Status};
+
+	type Message = generic::Message<u64, u64, u64, u64>;
+
+	fn status() -> Message {
+		generic::Message::Status(Status {
+			version: 1,
+			roles: Roles::FULL,
+			best_number: 10,
+			best_hash: 20,
+			genesis_hash: 30,
+			chain_status: vec![1, 2, 3],
+			timestamp: Some(1_000),
+			fork_id: None,
+		})
+	}
+
+	#[test]
+	fn envelope_round_trip() {
+		let messages = vec![
+			status(),
+			generic::Message::Transactions(vec![1, 2, 3]),
+			generic::Message::RemoteReadResponse(RemoteReadResponse { id: 5, proof: vec![vec![4]] }),
+			generic::Message::RemoteStorageSubscribe(RemoteStorageSubscribe { id: 6, keys: vec![vec![1], vec![2]] }),
+			generic::Message::RemoteStorageChanges(generic::RemoteStorageChanges {
+				id: 6,
+				block: 7,
+				changes: vec![(vec![1], Some(vec![8])), (vec![2], None)],
+				proof: vec![vec![9]],
+			}),
+			generic::Message::RemoteDigestRequest(generic::RemoteDigestRequest { id: 8, first: 1, last: 4, item_types: vec![2] }),
+			generic::Message::RemoteReadPrefixRequest(generic::RemoteReadPrefixRequest {
+				id: 9,
+				block: 10,
+				prefix: vec![1],
+				start_key: vec![1, 2],
+				limit: 16,
+			}),
+			generic::Message::RemoteBodyRequest(generic::RemoteBodyRequest { id: 11, block: 12 }),
+			generic::Message::RemoteBodyResponse(generic::RemoteBodyResponse { id: 11, body: Some(vec![13]) }),
+			generic::Message::RemoteReadChildRequest(generic::RemoteReadChildRequest {
+				id: 14,
+				block: 15,
+				storage_key: vec![1],
+				key: vec![2],
+			}),
+			generic::Message::RemoteReadMultiRequest(generic::RemoteReadMultiRequest {
+				id: 16,
+				block: 17,
+				keys: vec![vec![1], vec![2]],
+			}),
+			generic::Message::ChainSpecific(vec![42]),
+		];
+		for message in messages {
+			assert_eq!(Message::decode_envelope(&message.encode_envelope()), DecodedMessage::Known(message));
+		}
+	}
+
+	#[test]
+	fn trailing_fields_from_newer_version_are_ignored() {
+		let mut payload = status().encode();
+		payload.extend(&[0xde, 0xad]);
+		let data = Envelope { version: MESSAGE_VERSION + 1, payload }.encode();
+		assert_eq!(Message::decode_envelope(&data), DecodedMessage::Known(status()));
+	}
+
+	#[test]
+	fn status_without_timestamp_is_decodable() {
+		let mut old_status = status();
+		if let generic::Message::Status(ref mut status) = old_status {
+			status.timestamp = None;
+		}
+		let mut payload = old_status.encode();
+		assert_eq!(Message::decode_envelope(&Envelope { version: 1, payload: payload.clone() }.encode()), DecodedMessage::Known(old_status.clone()));
+
+		// statuses of older versions end before the timestamp slot
+		let slot_len = 0u64.encode().len();
+		let len = payload.len() - slot_len;
+		payload.truncate(len);
+		assert_eq!(Message::decode_envelope(&Envelope { version: 1, payload: payload.clone() }.encode()), DecodedMessage::Known(old_status));
+
+		payload.extend(&1_000u64.encode());
+		assert_eq!(Message::decode_envelope(&Envelope { version: 1, payload }.encode()), DecodedMessage::Known(status()));
+	}
+
+	#[test]
+	fn status_with_fork_id_is_decodable() {
+		let mut forked_status = status();
+		if let generic::Message::Status(ref mut status) = forked_status {
+			status.fork_id = Some(b"fork".to_vec());
+		}
+		let payload = forked_status.encode();
+		assert_eq!(Message::decode_envelope(&Envelope { version: 1, payload }.encode()), DecodedMessage::Known(forked_status.clone()));
+
+		// the fork id is kept when the time is unknown
+		if let generic::Message::Status(ref mut status) = forked_status {
+			status.timestamp = None;
+		}
+		let payload = forked_status.encode();
+		assert_eq!(Message::decode_envelope(&Envelope { version: 1, payload }.encode()), DecodedMessage::Known(forked_status));
+	}
+
+	#[test]
+	fn remote_call_of_older_version_is_decodable() {
+		let request = generic::Message::RemoteCallRequest(generic::RemoteCallRequest {
+			id: 1,
+			block: 2,
+			method: "method".into(),
+			data: vec![3],
+			trace_keys: false,
+		});
+		let mut payload = request.encode();
+		assert_eq!(payload.pop(), Some(0));
+		assert_eq!(Message::decode_envelope(&Envelope { version: 1, payload }.encode()), DecodedMessage::Known(request));
+
+		let response = generic::Message::RemoteCallResponse(RemoteCallResponse {
+			id: 1,
+			proof: vec![vec![4]],
+			accessed_keys: Vec::new(),
+		});
+		let mut payload = response.encode();
+		assert_eq!(payload.pop(), Some(0));
+		assert_eq!(Message::decode_envelope(&Envelope { version: 1, payload }.encode()), DecodedMessage::Known(response));
+	}
+
+	#[test]
+	fn unknown_message_type_is_ignored() {
+		let data = Envelope { version: MESSAGE_VERSION + 1, payload: vec![100, 1, 2, 3] }.encode();
+		assert_eq!(
+			Message::decode_envelope(&data),
+			DecodedMessage::Unknown { version: MESSAGE_VERSION + 1, index: 100 },
+		);
+	}
+
+	#[test]
+	fn changed_known_message_from_newer_version_is_ignored() {
+		let data = Envelope { version: MESSAGE_VERSION + 1, payload: vec![0, 1] }.encode();
+		assert_eq!(
+			Message::decode_envelope(&data),
+			DecodedMessage::Unknown { version: MESSAGE_VERSION + 1, index: 0 },
+		);
+	}
+
+	#[test]
+	fn malformed_packets_are_invalid() {
+		assert_eq!(Message::decode_envelope(&[]), DecodedMessage::Invalid);
+		assert_eq!(Message::decode_envelope(&status().encode()), DecodedMessage::Invalid);
+		let data = Envelope { version: MESSAGE_VERSION, payload: vec![0, 1] }.encode();
+		assert_eq!(Message::decode_envelope(&data), DecodedMessage::Invalid);
+		let data = Envelope { version: MESSAGE_VERSION, payload: vec![] }.encode();
+		assert_eq!(Message::decode_envelope(&data), DecodedMessage::Invalid);
+	}
 }
@@ -16,27 +16,96 @@
 
 //! On-demand requests service.
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Weak};
-use std::time::{Instant, Duration};
+use std::time::{Instant, Duration, SystemTime, UNIX_EPOCH};
 use futures::{Async, Future, Poll};
 use futures::sync::oneshot::{channel, Receiver, Sender};
 use linked_hash_map::LinkedHashMap;
 use linked_hash_map::Entry;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
+use codec::Encode;
 use client::{self, error::{Error as ClientError, ErrorKind as ClientErrorKind}};
 use client::light::fetcher::{Fetcher, FetchChecker, RemoteHeaderRequest,
-	RemoteCallRequest, RemoteReadRequest, RemoteChangesRequest};
+	RemoteCallRequest, RemoteReadRequest, RemoteChangesRequest, RemoteChildRootsRequest, RemoteDigestRequest,
+	RemoteReadPrefixRequest, RemoteBodyRequest, RemoteReadChildRequest, RequestKind, RequestMetrics};
+use client::light::misbehavior::{MisbehaviorLog, MisbehaviorRecord};
 use io::SyncIo;
 use message;
 use network_libp2p::{Severity, NodeIndex};
 use service;
-use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor, DigestItemFor};
 
-/// Remote request timeout.
+/// Default remote request timeout.
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
 /// Default request retry count.
 const RETRY_COUNT: usize = 1;
+/// Default delay before the first retry of the failed request.
+const RETRY_BACKOFF_BASE: Duration = Duration::from_millis(500);
+/// Maximal number of times the retry delay is doubled.
+const MAX_BACKOFF_DOUBLINGS: usize = 8;
+/// Maximal number of pushed storage changes waiting for their block to be imported.
+const MAX_PENDING_STORAGE_CHANGES: usize = 32;
+/// Weight of the older responses in the average latency of the peer, as in `(n - 1) / n`.
+const LATENCY_SMOOTHING: u32 = 4;
+
+/// Retry policy of the on-demand requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetcherConfig {
+	/// Number of times the request is retried after it has failed or timed out, unless the
+	/// request specifies its own `retry_count`.
+	pub max_retries: usize,
+	/// Delay before the first retry. The delay is doubled after every following failure.
+	pub backoff_base: Duration,
+	/// Time given to the peer to respond to the request.
+	pub request_timeout: Duration,
+	/// Time the request waits for a peer able to serve it before it fails. The request waits
+	/// indefinitely if `None`.
+	pub unserved_timeout: Option<Duration>,
+}
+
+impl Default for FetcherConfig {
+	fn default() -> Self {
+		FetcherConfig {
+			max_retries: RETRY_COUNT,
+			backoff_base: RETRY_BACKOFF_BASE,
+			request_timeout: REQUEST_TIMEOUT,
+			unserved_timeout: None,
+		}
+	}
+}
+
+/// Statistics of the responses of the peer to the on-demand requests. Kept while the peer is
+/// connected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerStats {
+	/// Number of responses that have passed verification.
+	pub valid_responses: u64,
+	/// Number of responses that have failed verification or were unexpected.
+	pub invalid_responses: u64,
+	/// Number of requests that have timed out.
+	pub timeouts: u64,
+	/// Moving average of the response time. `None` if the peer hasn't responded yet.
+	pub average_latency: Option<Duration>,
+}
+
+impl PeerStats {
+	/// Rank of the peer; requests are sent to the idle peer with the lowest rank. Peers that have
+	/// misbehaved are demoted, the others are ordered by their latency. Peers that haven't
+	/// responded yet come first, so that their latency gets known.
+	fn rank(&self) -> (u64, Option<Duration>) {
+		(self.invalid_responses + self.timeouts, self.average_latency)
+	}
+
+	/// Note the valid response received after given time.
+	fn note_valid_response(&mut self, latency: Duration) {
+		self.valid_responses += 1;
+		self.average_latency = Some(match self.average_latency {
+			Some(average) => average * (LATENCY_SMOOTHING - 1) / LATENCY_SMOOTHING + latency / LATENCY_SMOOTHING,
+			None => latency,
+		});
+	}
+}
 
 /// On-demand service API.
 pub trait OnDemandService<Block: BlockT>: Send + Sync {
@@ -73,12 +142,61 @@ pub trait OnDemandService<Block: BlockT>: Send + Sync {
 		peer: NodeIndex,
 		response: message::RemoteChangesResponse<NumberFor<Block>>
 	);
+
+	/// When child tries roots response is received from remote node.
+	fn on_remote_child_roots_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteChildRootsResponse);
+
+	/// When digest items response is received from remote node.
+	fn on_remote_digest_response(
+		&self,
+		io: &mut SyncIo,
+		peer: NodeIndex,
+		response: message::RemoteDigestResponse<Block::Header>
+	);
+
+	/// When storage prefix read response is received from remote node.
+	fn on_remote_read_prefix_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteReadPrefixResponse);
+
+	/// When block body response is received from remote node.
+	fn on_remote_body_response(
+		&self,
+		io: &mut SyncIo,
+		peer: NodeIndex,
+		response: message::RemoteBodyResponse<Block::Extrinsic>
+	);
+
+	/// When storage changes are pushed by remote node. The header is known if the
+	/// block the changes were made at is already imported.
+	fn on_remote_storage_changes(
+		&self,
+		io: &mut SyncIo,
+		peer: NodeIndex,
+		header: Option<Block::Header>,
+		changes: message::RemoteStorageChanges<Block::Hash>
+	);
+
+	/// When new block is imported.
+	fn on_block_imported(&self, io: &mut SyncIo, header: &Block::Header);
+
+	/// Get the response statistics of the connected peers.
+	fn peer_stats(&self) -> Vec<(NodeIndex, PeerStats)>;
+
+	/// Get the metrics of the requests made since the start, per request kind.
+	fn metrics(&self) -> Vec<(RequestKind, RequestMetrics)>;
+}
+
+/// Receiver of checked storage changes pushed by full nodes.
+pub trait StorageChangesSink<Block: BlockT>: Send + Sync {
+	/// Storage keys have changed at given block.
+	fn on_storage_changes(&self, block: Block::Hash, changes: Vec<(Vec<u8>, Option<Vec<u8>>)>);
 }
 
 /// On-demand requests service. Dispatches requests to appropriate peers.
 pub struct OnDemand<B: BlockT, E: service::ExecuteInContext<B>> {
 	core: Mutex<OnDemandCore<B, E>>,
 	checker: Arc<FetchChecker<B>>,
+	misbehavior_log: RwLock<Option<Arc<MisbehaviorLog>>>,
+	storage_changes_sink: RwLock<Option<Arc<StorageChangesSink<B>>>>,
 }
 
 /// On-demand remote call response.
@@ -89,31 +207,56 @@ pub struct RemoteResponse<T> {
 #[derive(Default)]
 struct OnDemandCore<B: BlockT, E: service::ExecuteInContext<B>> {
 	service: Weak<E>,
+	config: FetcherConfig,
 	next_request_id: u64,
 	pending_requests: VecDeque<Request<B>>,
 	active_peers: LinkedHashMap<NodeIndex, Request<B>>,
 	idle_peers: VecDeque<NodeIndex>,
 	best_blocks: HashMap<NodeIndex, NumberFor<B>>,
+	/// Light client peers that only serve header requests.
+	light_servers: HashSet<NodeIndex>,
+	/// Highest block that light server peers have reported as not available. Light servers prune
+	/// old headers, so header requests for blocks at or below it aren't sent to these peers.
+	light_servers_pruned: HashMap<NodeIndex, NumberFor<B>>,
+	peer_stats: HashMap<NodeIndex, PeerStats>,
+	metrics: HashMap<RequestKind, RequestMetrics>,
+	storage_keys: Vec<Vec<u8>>,
+	storage_subscription: Option<(NodeIndex, u64)>,
+	pending_storage_changes: VecDeque<(NodeIndex, message::RemoteStorageChanges<B::Hash>)>,
 }
 
 struct Request<Block: BlockT> {
 	id: u64,
 	timestamp: Instant,
 	retry_count: usize,
+	/// Number of failed attempts.
+	failures: usize,
+	/// The request isn't dispatched before this time.
+	next_attempt: Instant,
 	data: RequestData<Block>,
 }
 
 enum RequestData<Block: BlockT> {
 	RemoteHeader(RemoteHeaderRequest<Block::Header>, Sender<Result<Block::Header, ClientError>>),
-	RemoteRead(RemoteReadRequest<Block::Header>, Sender<Result<Option<Vec<u8>>, ClientError>>),
+	RemoteRead(RemoteReadRequest<Block::Header>, Sender<Result<Vec<Option<Vec<u8>>>, ClientError>>),
+	RemoteReadChild(RemoteReadChildRequest<Block::Header>, Sender<Result<Option<Vec<u8>>, ClientError>>),
 	RemoteCall(RemoteCallRequest<Block::Header>, Sender<Result<client::CallResult, ClientError>>),
 	RemoteChanges(RemoteChangesRequest<Block::Header>, Sender<Result<Vec<(NumberFor<Block>, u32)>, ClientError>>),
+	RemoteChildRoots(RemoteChildRootsRequest<Block::Header>, Sender<Result<Vec<(Vec<u8>, Vec<u8>)>, ClientError>>),
+	RemoteDigest(
+		RemoteDigestRequest<Block::Header>,
+		Sender<Result<Vec<(NumberFor<Block>, Vec<DigestItemFor<Block>>)>, ClientError>>
+	),
+	RemoteReadPrefix(RemoteReadPrefixRequest<Block::Header>, Sender<Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), ClientError>>),
+	RemoteBody(RemoteBodyRequest<Block::Header>, Sender<Result<Vec<Block::Extrinsic>, ClientError>>),
 }
 
 enum Accept<Block: BlockT> {
 	Ok,
 	CheckFailed(ClientError, RequestData<Block>),
 	Unexpected(RequestData<Block>),
+	/// The peer doesn't have the requested data (it isn't misbehaving).
+	NotAvailable(RequestData<Block>),
 }
 
 impl<T> Future for RemoteResponse<T> {
@@ -137,15 +280,30 @@ impl<B: BlockT, E> OnDemand<B, E> where
 {
 	/// Creates new on-demand service.
 	pub fn new(checker: Arc<FetchChecker<B>>) -> Self {
+		Self::with_config(checker, Default::default())
+	}
+
+	/// Creates new on-demand service with given retry policy.
+	pub fn with_config(checker: Arc<FetchChecker<B>>, config: FetcherConfig) -> Self {
 		OnDemand {
 			checker,
+			misbehavior_log: RwLock::new(None),
+			storage_changes_sink: RwLock::new(None),
 			core: Mutex::new(OnDemandCore {
 				service: Weak::new(),
+				config,
 				next_request_id: 0,
 				pending_requests: VecDeque::new(),
 				active_peers: LinkedHashMap::new(),
 				idle_peers: VecDeque::new(),
 				best_blocks: HashMap::new(),
+				light_servers: HashSet::new(),
+				light_servers_pruned: HashMap::new(),
+				peer_stats: HashMap::new(),
+				metrics: HashMap::new(),
+				storage_keys: Vec::new(),
+				storage_subscription: None,
+				pending_storage_changes: VecDeque::new(),
 			})
 		}
 	}
@@ -155,16 +313,110 @@ impl<B: BlockT, E> OnDemand<B, E> where
 		self.core.lock().service = service;
 	}
 
+	/// Sets the log where responses that have failed verification are recorded.
+	pub fn set_misbehavior_log(&self, log: Arc<MisbehaviorLog>) {
+		*self.misbehavior_log.write() = Some(log);
+	}
+
+	/// Get all recorded responses that have failed verification, oldest first.
+	pub fn misbehavior_records(&self) -> Result<Vec<MisbehaviorRecord>, ClientError> {
+		match *self.misbehavior_log.read() {
+			Some(ref log) => log.records(),
+			None => Ok(Vec::new()),
+		}
+	}
+
+	/// Get the checker of the remote proofs, so that the other consumers of the remote proofs
+	/// could share it.
+	pub fn checker(&self) -> Arc<FetchChecker<B>> {
+		self.checker.clone()
+	}
+
+	/// Sets the receiver of storage changes pushed by full nodes.
+	pub fn set_storage_changes_sink(&self, sink: Arc<StorageChangesSink<B>>) {
+		*self.storage_changes_sink.write() = Some(sink);
+	}
+
+	/// Watch given storage keys at one of the connected full nodes, replacing previously
+	/// watched keys. Changes pushed by the node are checked and passed to the storage changes sink.
+	pub fn subscribe_storage(&self, mut keys: Vec<Vec<u8>>) {
+		keys.sort();
+		keys.dedup();
+
+		let mut core = self.core.lock();
+		if core.storage_keys == keys {
+			return;
+		}
+
+		core.storage_keys = keys;
+		core.unsubscribe_storage();
+		core.subscribe_storage();
+	}
+
+	/// Check pushed storage changes against the header of the block they were made at.
+	fn check_storage_changes(
+		&self,
+		io: &mut SyncIo,
+		peer: NodeIndex,
+		header: B::Header,
+		changes: message::RemoteStorageChanges<B::Hash>,
+	) {
+		let (keys, values): (Vec<_>, Vec<_>) = changes.changes.iter().cloned().unzip();
+		let request = RemoteReadRequest {
+			block: changes.block,
+			header,
+			keys,
+			retry_count: None,
+		};
+		match self.checker.check_read_proof(&request, changes.proof) {
+			Ok(ref proved) if *proved == values => (),
+			Ok(_) | Err(_) => {
+				io.report_peer(peer, Severity::Bad("Failed to check remote storage changes from peer"));
+				let mut core = self.core.lock();
+				core.remove_peer(peer);
+				core.dispatch();
+				return;
+			},
+		}
+
+		if let Some(ref sink) = *self.storage_changes_sink.read() {
+			sink.on_storage_changes(changes.block, changes.changes);
+		}
+	}
+
+	/// Encode response if it could be required by the misbehavior log.
+	fn encode_for_log<T: Encode>(&self, response: &T) -> Option<Vec<u8>> {
+		self.misbehavior_log.read().as_ref().map(|_| response.encode())
+	}
+
+	/// Record response that has failed verification.
+	fn note_misbehavior(&self, record: MisbehaviorRecord) {
+		if let Some(ref log) = *self.misbehavior_log.read() {
+			if let Err(error) = log.note(record) {
+				warn!(target: "sync", "Failed to record remote peer misbehavior: {}", error);
+			}
+		}
+	}
+
 	/// Schedule && dispatch all scheduled requests.
 	fn schedule_request<R>(&self, retry_count: Option<usize>, data: RequestData<B>, result: R) -> R {
 		let mut core = self.core.lock();
-		core.insert(retry_count.unwrap_or(RETRY_COUNT), data);
+		let retry_count = retry_count.unwrap_or(core.config.max_retries);
+		core.insert(retry_count, data);
 		core.dispatch();
 		result
 	}
 
 	/// Try to accept response from given peer.
-	fn accept_response<F: FnOnce(Request<B>) -> Accept<B>>(&self, rtype: &str, io: &mut SyncIo, peer: NodeIndex, request_id: u64, try_accept: F) {
+	fn accept_response<F: FnOnce(Request<B>) -> Accept<B>>(
+		&self,
+		rtype: &str,
+		io: &mut SyncIo,
+		peer: NodeIndex,
+		request_id: u64,
+		encoded_response: Option<Vec<u8>>,
+		try_accept: F,
+	) {
 		let mut core = self.core.lock();
 		let request = match core.remove(peer, request_id) {
 			Some(request) => request,
@@ -175,31 +427,68 @@ impl<B: BlockT, E> OnDemand<B, E> where
 			},
 		};
 
-		let retry_count = request.retry_count;
-		let (retry_count, retry_request_data) = match try_accept(request) {
-			Accept::Ok => (retry_count, None),
+		let (retry_count, failures) = (request.retry_count, request.failures);
+		let latency = request.timestamp.elapsed();
+		let kind = request.data.kind();
+		let required_block = request.required_block();
+		let encoded_request = encoded_response.as_ref().map(|_| request.message().encode());
+		let retry = match try_accept(request) {
+			Accept::Ok => {
+				core.peer_stats.entry(peer).or_default().note_valid_response(latency);
+				let metrics = core.metrics.entry(kind).or_default();
+				metrics.responses += 1;
+				metrics.latency.note(latency);
+				None
+			},
 			Accept::CheckFailed(error, retry_request_data) => {
+				core.peer_stats.entry(peer).or_default().invalid_responses += 1;
+				core.metrics.entry(kind).or_default().invalid_responses += 1;
+
+				let error_description = error.to_string();
+				if let (Some(request), Some(response)) = (encoded_request, encoded_response) {
+					self.note_misbehavior(MisbehaviorRecord {
+						peer: io.peer_debug_info(peer),
+						timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default(),
+						request,
+						response,
+						error: error_description.clone(),
+					});
+				}
+
 				io.report_peer(peer, Severity::Bad(&format!("Failed to check remote {} response from peer: {}", rtype, error)));
 				core.remove_peer(peer);
 
 				if retry_count > 0 {
-					(retry_count - 1, Some(retry_request_data))
+					Some((retry_count - 1, failures + 1, retry_request_data))
 				} else {
 					trace!(target: "sync", "Failed to get remote {} response for given number of retries", rtype);
-					retry_request_data.fail(ClientErrorKind::RemoteFetchFailed.into());
-					(0, None)
+					core.metrics.entry(kind).or_default().failures += 1;
+					retry_request_data.fail(ClientErrorKind::RemoteFetchInvalidResponse(failures + 1, error_description).into());
+					None
 				}
 			},
 			Accept::Unexpected(retry_request_data) => {
+				core.peer_stats.entry(peer).or_default().invalid_responses += 1;
+				core.metrics.entry(kind).or_default().invalid_responses += 1;
 				io.report_peer(peer, Severity::Bad(&format!("Unexpected response to remote {} from peer", rtype)));
 				core.remove_peer(peer);
 
-				(retry_count, Some(retry_request_data))
+				Some((retry_count, failures, retry_request_data))
+			},
+			Accept::NotAvailable(retry_request_data) => {
+				trace!(target: "sync", "Remote {} response from peer {}: block {} is not available",
+					rtype, peer, required_block);
+				let pruned = core.light_servers_pruned.entry(peer).or_insert(required_block);
+				if *pruned < required_block {
+					*pruned = required_block;
+				}
+
+				Some((retry_count, failures, retry_request_data))
 			},
 		};
 
-		if let Some(request_data) = retry_request_data {
-			core.insert(retry_count, request_data);
+		if let Some((retry_count, failures, request_data)) = retry {
+			core.insert_retry(retry_count, failures, request_data);
 		}
 
 		core.dispatch();
@@ -212,12 +501,20 @@ impl<B, E> OnDemandService<B> for OnDemand<B, E> where
 	B::Header: HeaderT,
 {
 	fn on_connect(&self, peer: NodeIndex, role: service::Roles, best_number: NumberFor<B>) {
-		if !role.intersects(service::Roles::FULL | service::Roles::AUTHORITY) { // TODO: correct?
+		let is_full = role.intersects(service::Roles::FULL | service::Roles::AUTHORITY);
+		let is_light_server = role.contains(service::Roles::LIGHT | service::Roles::LIGHT_SERVER);
+		if !is_full && !is_light_server { // TODO: correct?
 			return;
 		}
 
 		let mut core = self.core.lock();
 		core.add_peer(peer, best_number);
+		if !is_full {
+			core.light_servers.insert(peer);
+		}
+		if core.storage_subscription.is_none() {
+			core.subscribe_storage();
+		}
 		core.dispatch();
 	}
 
@@ -230,6 +527,7 @@ impl<B, E> OnDemandService<B> for OnDemand<B, E> where
 	fn on_disconnect(&self, peer: NodeIndex) {
 		let mut core = self.core.lock();
 		core.remove_peer(peer);
+		core.peer_stats.remove(&peer);
 		core.dispatch();
 	}
 
@@ -242,21 +540,28 @@ impl<B, E> OnDemandService<B> for OnDemand<B, E> where
 	}
 
 	fn on_remote_header_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteHeaderResponse<B::Header>) {
-		self.accept_response("header", io, peer, response.id, |request| match request.data {
-			RequestData::RemoteHeader(request, sender) => match self.checker.check_header_proof(&request, response.header, response.proof) {
-				Ok(response) => {
-					// we do not bother if receiver has been dropped already
-					let _ = sender.send(Ok(response));
-					Accept::Ok
+		let encoded_response = self.encode_for_log(&response);
+		// light servers answer with no header when it has been pruned
+		let is_light_server = self.core.lock().light_servers.contains(&peer);
+		self.accept_response("header", io, peer, response.id, encoded_response, |request| match request.data {
+			RequestData::RemoteHeader(request, sender) => match response.header {
+				None if is_light_server => Accept::NotAvailable(RequestData::RemoteHeader(request, sender)),
+				header => match self.checker.check_header_proof(&request, header, response.proof) {
+					Ok(response) => {
+						// we do not bother if receiver has been dropped already
+						let _ = sender.send(Ok(response));
+						Accept::Ok
+					},
+					Err(error) => Accept::CheckFailed(error, RequestData::RemoteHeader(request, sender)),
 				},
-				Err(error) => Accept::CheckFailed(error, RequestData::RemoteHeader(request, sender)),
 			},
 			data @ _ => Accept::Unexpected(data),
 		})
 	}
 
 	fn on_remote_read_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteReadResponse) {
-		self.accept_response("read", io, peer, response.id, |request| match request.data {
+		let encoded_response = self.encode_for_log(&response);
+		self.accept_response("read", io, peer, response.id, encoded_response, |request| match request.data {
 			RequestData::RemoteRead(request, sender) => match self.checker.check_read_proof(&request, response.proof) {
 				Ok(response) => {
 					// we do not bother if receiver has been dropped already
@@ -265,16 +570,29 @@ impl<B, E> OnDemandService<B> for OnDemand<B, E> where
 				},
 				Err(error) => Accept::CheckFailed(error, RequestData::RemoteRead(request, sender)),
 			},
+			RequestData::RemoteReadChild(request, sender) => match self.checker.check_read_child_proof(&request, response.proof) {
+				Ok(response) => {
+					// we do not bother if receiver has been dropped already
+					let _ = sender.send(Ok(response));
+					Accept::Ok
+				},
+				Err(error) => Accept::CheckFailed(error, RequestData::RemoteReadChild(request, sender)),
+			},
 			data @ _ => Accept::Unexpected(data),
 		})
 	}
 
 	fn on_remote_call_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteCallResponse) {
-		self.accept_response("call", io, peer, response.id, |request| match request.data {
+		let encoded_response = self.encode_for_log(&response);
+		self.accept_response("call", io, peer, response.id, encoded_response, |request| match request.data {
 			RequestData::RemoteCall(request, sender) => match self.checker.check_execution_proof(&request, response.proof) {
-				Ok(response) => {
+				Ok(mut result) => {
+					if request.trace_keys {
+						result.accessed_keys = response.accessed_keys;
+					}
+
 					// we do not bother if receiver has been dropped already
-					let _ = sender.send(Ok(response));
+					let _ = sender.send(Ok(result));
 					Accept::Ok
 				},
 				Err(error) => Accept::CheckFailed(error, RequestData::RemoteCall(request, sender)),
@@ -284,7 +602,8 @@ impl<B, E> OnDemandService<B> for OnDemand<B, E> where
 	}
 
 	fn on_remote_changes_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteChangesResponse<NumberFor<B>>) {
-		self.accept_response("changes", io, peer, response.id, |request| match request.data {
+		let encoded_response = self.encode_for_log(&response);
+		self.accept_response("changes", io, peer, response.id, encoded_response, |request| match request.data {
 			RequestData::RemoteChanges(request, sender) => match self.checker.check_changes_proof(
 				&request, response.max, response.proof
 			) {
@@ -298,6 +617,129 @@ impl<B, E> OnDemandService<B> for OnDemand<B, E> where
 			data @ _ => Accept::Unexpected(data),
 		})
 	}
+
+	fn on_remote_child_roots_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteChildRootsResponse) {
+		let encoded_response = self.encode_for_log(&response);
+		self.accept_response("child roots", io, peer, response.id, encoded_response, |request| match request.data {
+			RequestData::RemoteChildRoots(request, sender) => match self.checker.check_child_roots_proof(&request, response.proof) {
+				Ok(response) => {
+					// we do not bother if receiver has been dropped already
+					let _ = sender.send(Ok(response));
+					Accept::Ok
+				},
+				Err(error) => Accept::CheckFailed(error, RequestData::RemoteChildRoots(request, sender)),
+			},
+			data @ _ => Accept::Unexpected(data),
+		})
+	}
+
+	fn on_remote_digest_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteDigestResponse<B::Header>) {
+		let encoded_response = self.encode_for_log(&response);
+		self.accept_response("digest", io, peer, response.id, encoded_response, |request| match request.data {
+			RequestData::RemoteDigest(request, sender) => match self.checker.check_digest_proof(&request, response.headers, response.proof) {
+				Ok(response) => {
+					// we do not bother if receiver has been dropped already
+					let _ = sender.send(Ok(response));
+					Accept::Ok
+				},
+				Err(error) => Accept::CheckFailed(error, RequestData::RemoteDigest(request, sender)),
+			},
+			data @ _ => Accept::Unexpected(data),
+		})
+	}
+
+	fn on_remote_read_prefix_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteReadPrefixResponse) {
+		let encoded_response = self.encode_for_log(&response);
+		self.accept_response("prefix read", io, peer, response.id, encoded_response, |request| match request.data {
+			RequestData::RemoteReadPrefix(request, sender) => match self.checker.check_read_prefix_proof(&request, response.proof) {
+				Ok(response) => {
+					// we do not bother if receiver has been dropped already
+					let _ = sender.send(Ok(response));
+					Accept::Ok
+				},
+				Err(error) => Accept::CheckFailed(error, RequestData::RemoteReadPrefix(request, sender)),
+			},
+			data @ _ => Accept::Unexpected(data),
+		})
+	}
+
+	fn on_remote_body_response(&self, io: &mut SyncIo, peer: NodeIndex, response: message::RemoteBodyResponse<B::Extrinsic>) {
+		let encoded_response = self.encode_for_log(&response);
+		self.accept_response("body", io, peer, response.id, encoded_response, |request| match request.data {
+			RequestData::RemoteBody(request, sender) => {
+				// the full node that has announced the block is expected to know its body
+				let checked_body = response.body
+					.ok_or_else(|| ClientErrorKind::InvalidBodyProof.into())
+					.and_then(|body| self.checker.check_body_proof(&request, body));
+				match checked_body {
+					Ok(body) => {
+						// we do not bother if receiver has been dropped already
+						let _ = sender.send(Ok(body));
+						Accept::Ok
+					},
+					Err(error) => Accept::CheckFailed(error, RequestData::RemoteBody(request, sender)),
+				}
+			},
+			data @ _ => Accept::Unexpected(data),
+		})
+	}
+
+	fn on_remote_storage_changes(
+		&self,
+		io: &mut SyncIo,
+		peer: NodeIndex,
+		header: Option<B::Header>,
+		changes: message::RemoteStorageChanges<B::Hash>
+	) {
+		let header = {
+			let mut core = self.core.lock();
+			if core.storage_subscription != Some((peer, changes.id)) {
+				trace!(target: "sync", "Ignoring storage changes {} from {} for unknown subscription", changes.id, peer);
+				return;
+			}
+
+			match header {
+				Some(header) => header,
+				None => {
+					// changes are checked once the block is imported
+					core.pending_storage_changes.push_back((peer, changes));
+					while core.pending_storage_changes.len() > MAX_PENDING_STORAGE_CHANGES {
+						core.pending_storage_changes.pop_front();
+					}
+					return;
+				},
+			}
+		};
+
+		self.check_storage_changes(io, peer, header, changes);
+	}
+
+	fn on_block_imported(&self, io: &mut SyncIo, header: &B::Header) {
+		let hash = header.hash();
+		let ready: Vec<_> = {
+			let mut core = self.core.lock();
+			let (ready, pending) = core.pending_storage_changes.drain(..)
+				.partition::<VecDeque<_>, _>(|&(_, ref changes)| changes.block == hash);
+			core.pending_storage_changes = pending;
+			ready.into_iter().collect()
+		};
+
+		for (peer, changes) in ready {
+			self.check_storage_changes(io, peer, header.clone(), changes);
+		}
+	}
+
+	fn peer_stats(&self) -> Vec<(NodeIndex, PeerStats)> {
+		self.core.lock().peer_stats.iter().map(|(peer, stats)| (*peer, stats.clone())).collect()
+	}
+
+	fn metrics(&self) -> Vec<(RequestKind, RequestMetrics)> {
+		let mut metrics: Vec<_> = self.core.lock().metrics.iter()
+			.map(|(kind, metrics)| (*kind, metrics.clone()))
+			.collect();
+		metrics.sort_by_key(|&(kind, _)| kind);
+		metrics
+	}
 }
 
 impl<B, E> Fetcher<B> for OnDemand<B, E> where
@@ -306,9 +748,14 @@ impl<B, E> Fetcher<B> for OnDemand<B, E> where
 	B::Header: HeaderT,
 {
 	type RemoteHeaderResult = RemoteResponse<B::Header>;
-	type RemoteReadResult = RemoteResponse<Option<Vec<u8>>>;
+	type RemoteReadResult = RemoteResponse<Vec<Option<Vec<u8>>>>;
+	type RemoteReadChildResult = RemoteResponse<Option<Vec<u8>>>;
 	type RemoteCallResult = RemoteResponse<client::CallResult>;
 	type RemoteChangesResult = RemoteResponse<Vec<(NumberFor<B>, u32)>>;
+	type RemoteChildRootsResult = RemoteResponse<Vec<(Vec<u8>, Vec<u8>)>>;
+	type RemoteDigestResult = RemoteResponse<Vec<(NumberFor<B>, Vec<DigestItemFor<B>>)>>;
+	type RemoteReadPrefixResult = RemoteResponse<(Vec<(Vec<u8>, Vec<u8>)>, bool)>;
+	type RemoteBodyResult = RemoteResponse<Vec<B::Extrinsic>>;
 
 	fn remote_header(&self, request: RemoteHeaderRequest<B::Header>) -> Self::RemoteHeaderResult {
 		let (sender, receiver) = channel();
@@ -318,10 +765,22 @@ impl<B, E> Fetcher<B> for OnDemand<B, E> where
 
 	fn remote_read(&self, request: RemoteReadRequest<B::Header>) -> Self::RemoteReadResult {
 		let (sender, receiver) = channel();
+		// full nodes refuse to read too many keys at once
+		if request.keys.len() > ::protocol::MAX_READ_KEYS {
+			let _ = sender.send(Err(ClientErrorKind::RemoteFetchFailed.into()));
+			return RemoteResponse { receiver };
+		}
+
 		self.schedule_request(request.retry_count.clone(), RequestData::RemoteRead(request, sender),
 			RemoteResponse { receiver })
 	}
 
+	fn remote_read_child(&self, request: RemoteReadChildRequest<B::Header>) -> Self::RemoteReadChildResult {
+		let (sender, receiver) = channel();
+		self.schedule_request(request.retry_count.clone(), RequestData::RemoteReadChild(request, sender),
+			RemoteResponse { receiver })
+	}
+
 	fn remote_call(&self, request: RemoteCallRequest<B::Header>) -> Self::RemoteCallResult {
 		let (sender, receiver) = channel();
 		self.schedule_request(request.retry_count.clone(), RequestData::RemoteCall(request, sender),
@@ -333,6 +792,30 @@ impl<B, E> Fetcher<B> for OnDemand<B, E> where
 		self.schedule_request(request.retry_count.clone(), RequestData::RemoteChanges(request, sender),
 			RemoteResponse { receiver })
 	}
+
+	fn remote_child_roots(&self, request: RemoteChildRootsRequest<B::Header>) -> Self::RemoteChildRootsResult {
+		let (sender, receiver) = channel();
+		self.schedule_request(request.retry_count.clone(), RequestData::RemoteChildRoots(request, sender),
+			RemoteResponse { receiver })
+	}
+
+	fn remote_digest(&self, request: RemoteDigestRequest<B::Header>) -> Self::RemoteDigestResult {
+		let (sender, receiver) = channel();
+		self.schedule_request(request.retry_count.clone(), RequestData::RemoteDigest(request, sender),
+			RemoteResponse { receiver })
+	}
+
+	fn remote_read_prefix(&self, request: RemoteReadPrefixRequest<B::Header>) -> Self::RemoteReadPrefixResult {
+		let (sender, receiver) = channel();
+		self.schedule_request(request.retry_count.clone(), RequestData::RemoteReadPrefix(request, sender),
+			RemoteResponse { receiver })
+	}
+
+	fn remote_body(&self, request: RemoteBodyRequest<B::Header>) -> Self::RemoteBodyResult {
+		let (sender, receiver) = channel();
+		self.schedule_request(request.retry_count.clone(), RequestData::RemoteBody(request, sender),
+			RemoteResponse { receiver })
+	}
 }
 
 impl<B, E> OnDemandCore<B, E> where
@@ -343,14 +826,26 @@ impl<B, E> OnDemandCore<B, E> where
 	pub fn add_peer(&mut self, peer: NodeIndex, best_number: NumberFor<B>) {
 		self.idle_peers.push_back(peer);
 		self.best_blocks.insert(peer, best_number);
+		self.peer_stats.insert(peer, PeerStats::default());
 	}
 
 	pub fn update_peer(&mut self, peer: NodeIndex, best_number: NumberFor<B>) {
-		self.best_blocks.insert(peer, best_number);
+		// announces of the peers that are not serving requests are ignored
+		if let Some(peer_best_number) = self.best_blocks.get_mut(&peer) {
+			*peer_best_number = best_number;
+		}
 	}
 
 	pub fn remove_peer(&mut self, peer: NodeIndex) {
 		self.best_blocks.remove(&peer);
+		self.light_servers.remove(&peer);
+		self.light_servers_pruned.remove(&peer);
+
+		if self.storage_subscription.map(|(subscribed_peer, _)| subscribed_peer == peer).unwrap_or(false) {
+			self.storage_subscription = None;
+			self.pending_storage_changes.retain(|&(pending_peer, _)| pending_peer != peer);
+			self.subscribe_storage();
+		}
 
 		if let Some(request) = self.active_peers.remove(&peer) {
 			self.pending_requests.push_front(request);
@@ -362,33 +857,123 @@ impl<B, E> OnDemandCore<B, E> where
 		}
 	}
 
+	/// Subscribe to watched storage keys at one of the connected peers.
+	pub fn subscribe_storage(&mut self) {
+		if self.storage_keys.is_empty() {
+			return;
+		}
+
+		// light servers have no state to watch
+		let light_servers = &self.light_servers;
+		let peer = self.best_blocks.keys().find(|peer| !light_servers.contains(peer)).cloned();
+		let (service, peer) = match (self.service.upgrade(), peer) {
+			(Some(service), Some(peer)) => (service, peer),
+			_ => return,
+		};
+
+		let id = self.next_request_id;
+		self.next_request_id += 1;
+		self.storage_subscription = Some((peer, id));
+
+		trace!(target: "sync", "Subscribing to {} storage keys at peer {}", self.storage_keys.len(), peer);
+		let message = message::generic::Message::RemoteStorageSubscribe(message::RemoteStorageSubscribe {
+			id,
+			keys: self.storage_keys.clone(),
+		});
+		service.execute_in_context(|ctx| ctx.send_message(peer, message.clone()));
+	}
+
+	/// Cancel current storage subscription.
+	pub fn unsubscribe_storage(&mut self) {
+		let (peer, id) = match self.storage_subscription.take() {
+			Some(subscription) => subscription,
+			None => return,
+		};
+		self.pending_storage_changes.clear();
+
+		if let Some(service) = self.service.upgrade() {
+			let message = message::generic::Message::RemoteStorageUnsubscribe(message::RemoteStorageUnsubscribe { id });
+			service.execute_in_context(|ctx| ctx.send_message(peer, message.clone()));
+		}
+	}
+
 	pub fn maintain_peers(&mut self) -> Vec<NodeIndex> {
 		let now = Instant::now();
+		if let Some(unserved_timeout) = self.config.unserved_timeout {
+			self.fail_unserved_requests(now, unserved_timeout);
+		}
+
 		let mut bad_peers = Vec::new();
 		loop {
 			match self.active_peers.front() {
-				Some((_, request)) if now - request.timestamp >= REQUEST_TIMEOUT => (),
+				Some((_, request)) if now - request.timestamp >= self.config.request_timeout => (),
 				_ => return bad_peers,
 			}
 
-			let (bad_peer, request) = self.active_peers.pop_front().expect("front() is Some as checked above");
-			self.pending_requests.push_front(request);
+			let (bad_peer, mut request) = self.active_peers.pop_front().expect("front() is Some as checked above");
+			self.peer_stats.entry(bad_peer).or_default().timeouts += 1;
 			bad_peers.push(bad_peer);
+
+			self.metrics.entry(request.data.kind()).or_default().timeouts += 1;
+			request.failures += 1;
+			if request.retry_count == 0 {
+				trace!(target: "sync", "Remote request {} has timed out for given number of retries", request.id);
+				self.metrics.entry(request.data.kind()).or_default().failures += 1;
+				request.data.fail(ClientErrorKind::RemoteFetchTimedOut(request.failures).into());
+				continue;
+			}
+
+			request.retry_count -= 1;
+			request.next_attempt = now + self.retry_backoff(request.failures);
+			self.pending_requests.push_front(request);
+		}
+	}
+
+	/// Fail the requests that no peer has been able to serve for given time since their
+	/// (re)submission.
+	fn fail_unserved_requests(&mut self, now: Instant, unserved_timeout: Duration) {
+		let (unserved, pending) = ::std::mem::replace(&mut self.pending_requests, VecDeque::new())
+			.into_iter()
+			.partition::<VecDeque<_>, _>(|request| request.next_attempt + unserved_timeout <= now);
+		self.pending_requests = pending;
+		for request in unserved {
+			trace!(target: "sync", "Remote request {} hasn't been served by any peer", request.id);
+			self.metrics.entry(request.data.kind()).or_default().failures += 1;
+			request.data.fail(ClientErrorKind::RemoteFetchFailed.into());
 		}
 	}
 
 	pub fn insert(&mut self, retry_count: usize, data: RequestData<B>) {
+		self.metrics.entry(data.kind()).or_default().requests += 1;
+		self.insert_retry(retry_count, 0, data);
+	}
+
+	/// Insert request that has already failed given number of times. It is dispatched once the
+	/// backoff delay has passed.
+	pub fn insert_retry(&mut self, retry_count: usize, failures: usize, data: RequestData<B>) {
 		let request_id = self.next_request_id;
 		self.next_request_id += 1;
 
+		let now = Instant::now();
+		let next_attempt = now + self.retry_backoff(failures);
 		self.pending_requests.push_back(Request {
 			id: request_id,
-			timestamp: Instant::now(),
+			timestamp: now,
 			retry_count,
+			failures,
+			next_attempt,
 			data,
 		});
 	}
 
+	/// Delay before the next attempt of the request that has failed given number of times.
+	fn retry_backoff(&self, failures: usize) -> Duration {
+		match failures {
+			0 => Duration::from_secs(0),
+			_ => self.config.backoff_base * (1u32 << ::std::cmp::min(failures - 1, MAX_BACKOFF_DOUBLINGS)),
+		}
+	}
+
 	pub fn remove(&mut self, peer: NodeIndex, id: u64) -> Option<Request<B>> {
 		match self.active_peers.entry(peer) {
 			Entry::Occupied(entry) => match entry.get().id == id {
@@ -408,37 +993,46 @@ impl<B, E> OnDemandCore<B, E> where
 			None => return,
 		};
 
-		let last_peer = self.idle_peers.back().cloned();
-		while !self.pending_requests.is_empty() {
-			let peer = match self.idle_peers.pop_front() {
-				Some(peer) => peer,
+		let now = Instant::now();
+		loop {
+			// requests that are waiting for the retry backoff are skipped
+			let index = match self.pending_requests.iter().position(|request| request.next_attempt <= now) {
+				Some(index) => index,
 				None => return,
 			};
 
-			// check if request can (optimistically) be processed by the peer
-			let can_be_processed_by_peer = {
-				let request = self.pending_requests.front().expect("checked in loop condition; qed");
-				let peer_best_block = self.best_blocks.get(&peer)
-					.expect("entries are inserted into best_blocks when peer is connected;
-						entries are removed from best_blocks when peer is disconnected;
-						peer is in idle_peers and thus connected; qed");
-				request.required_block() <= *peer_best_block
+			// pick the best ranked of the peers that can (optimistically) process the request;
+			// equally ranked peers are picked in the order they've become idle
+			let peer_index = {
+				let required_block = self.pending_requests[index].required_block();
+				let header_only = self.pending_requests[index].is_header_request();
+				let best_blocks = &self.best_blocks;
+				let light_servers = &self.light_servers;
+				let light_servers_pruned = &self.light_servers_pruned;
+				let peer_stats = &self.peer_stats;
+				self.idle_peers.iter()
+					.enumerate()
+					.filter(|&(_, peer)| !light_servers.contains(peer) || (header_only
+						&& light_servers_pruned.get(peer).map_or(true, |pruned| required_block > *pruned)))
+					.filter(|&(_, peer)| {
+						let peer_best_block = best_blocks.get(peer)
+							.expect("entries are inserted into best_blocks when peer is connected;
+								entries are removed from best_blocks when peer is disconnected;
+								peer is in idle_peers and thus connected; qed");
+						required_block <= *peer_best_block
+					})
+					.min_by_key(|&(_, peer)| peer_stats.get(peer).map(PeerStats::rank).unwrap_or_default())
+					.map(|(peer_index, _)| peer_index)
 			};
 
-			if !can_be_processed_by_peer {
-				// return peer to the back of the queue
-				self.idle_peers.push_back(peer);
-
-				// we have enumerated all peers and noone can handle request
-				if Some(peer) == last_peer {
-					break;
-				}
-
-				continue;
-			}
+			// noone can handle request
+			let peer = match peer_index {
+				Some(peer_index) => self.idle_peers.remove(peer_index).expect("index is found by enumerate() above; qed"),
+				None => return,
+			};
 
-			let mut request = self.pending_requests.pop_front().expect("checked in loop condition; qed");
-			request.timestamp = Instant::now();
+			let mut request = self.pending_requests.remove(index).expect("index is found by position() above; qed");
+			request.timestamp = now;
 			trace!(target: "sync", "Dispatching remote request {} to peer {}", request.id, peer);
 
 			service.execute_in_context(|ctx| ctx.send_message(peer, request.message()));
@@ -448,12 +1042,25 @@ impl<B, E> OnDemandCore<B, E> where
 }
 
 impl<Block: BlockT> Request<Block> {
+	/// Returns true if the request may be served by light server peers.
+	pub fn is_header_request(&self) -> bool {
+		match self.data {
+			RequestData::RemoteHeader(_, _) => true,
+			_ => false,
+		}
+	}
+
 	pub fn required_block(&self) -> NumberFor<Block> {
 		match self.data {
 			RequestData::RemoteHeader(ref data, _) => data.block,
 			RequestData::RemoteRead(ref data, _) => *data.header.number(),
+			RequestData::RemoteReadChild(ref data, _) => *data.header.number(),
 			RequestData::RemoteCall(ref data, _) => *data.header.number(),
 			RequestData::RemoteChanges(ref data, _) => data.max_block.0,
+			RequestData::RemoteChildRoots(ref data, _) => *data.header.number(),
+			RequestData::RemoteDigest(ref data, _) => data.last_block,
+			RequestData::RemoteReadPrefix(ref data, _) => *data.header.number(),
+			RequestData::RemoteBody(ref data, _) => *data.header.number(),
 		}
 	}
 
@@ -464,10 +1071,24 @@ impl<Block: BlockT> Request<Block> {
 					id: self.id,
 					block: data.block,
 				}),
-			RequestData::RemoteRead(ref data, _) =>
+			// single keys are requested with the message known to the older versions
+			RequestData::RemoteRead(ref data, _) if data.keys.len() == 1 =>
 				message::generic::Message::RemoteReadRequest(message::RemoteReadRequest {
 					id: self.id,
 					block: data.block,
+					key: data.keys[0].clone(),
+				}),
+			RequestData::RemoteRead(ref data, _) =>
+				message::generic::Message::RemoteReadMultiRequest(message::RemoteReadMultiRequest {
+					id: self.id,
+					block: data.block,
+					keys: data.keys.clone(),
+				}),
+			RequestData::RemoteReadChild(ref data, _) =>
+				message::generic::Message::RemoteReadChildRequest(message::RemoteReadChildRequest {
+					id: self.id,
+					block: data.block,
+					storage_key: data.storage_key.clone(),
 					key: data.key.clone(),
 				}),
 			RequestData::RemoteCall(ref data, _) =>
@@ -476,6 +1097,7 @@ impl<Block: BlockT> Request<Block> {
 					block: data.block,
 					method: data.method.clone(),
 					data: data.call_data.clone(),
+					trace_keys: data.trace_keys,
 				}),
 			RequestData::RemoteChanges(ref data, _) =>
 				message::generic::Message::RemoteChangesRequest(message::RemoteChangesRequest {
@@ -485,18 +1107,62 @@ impl<Block: BlockT> Request<Block> {
 					max: data.max_block.1.clone(),
 					key: data.key.clone(),
 				}),
+			RequestData::RemoteChildRoots(ref data, _) =>
+				message::generic::Message::RemoteChildRootsRequest(message::RemoteChildRootsRequest {
+					id: self.id,
+					block: data.block,
+				}),
+			RequestData::RemoteDigest(ref data, _) =>
+				message::generic::Message::RemoteDigestRequest(message::RemoteDigestRequest {
+					id: self.id,
+					first: data.first_block,
+					last: data.last_block,
+					item_types: data.item_types.clone(),
+				}),
+			RequestData::RemoteReadPrefix(ref data, _) =>
+				message::generic::Message::RemoteReadPrefixRequest(message::RemoteReadPrefixRequest {
+					id: self.id,
+					block: data.block,
+					prefix: data.prefix.clone(),
+					start_key: data.start_key.clone(),
+					limit: data.limit,
+				}),
+			RequestData::RemoteBody(ref data, _) =>
+				message::generic::Message::RemoteBodyRequest(message::RemoteBodyRequest {
+					id: self.id,
+					block: data.header.hash(),
+				}),
 		}
 	}
 }
 
 impl<Block: BlockT> RequestData<Block> {
+	pub fn kind(&self) -> RequestKind {
+		match *self {
+			RequestData::RemoteHeader(_, _) => RequestKind::Header,
+			RequestData::RemoteRead(_, _) => RequestKind::Read,
+			RequestData::RemoteReadChild(_, _) => RequestKind::ReadChild,
+			RequestData::RemoteCall(_, _) => RequestKind::Call,
+			RequestData::RemoteChanges(_, _) => RequestKind::Changes,
+			RequestData::RemoteChildRoots(_, _) => RequestKind::ChildRoots,
+			RequestData::RemoteDigest(_, _) => RequestKind::Digest,
+			RequestData::RemoteReadPrefix(_, _) => RequestKind::ReadPrefix,
+			RequestData::RemoteBody(_, _) => RequestKind::Body,
+		}
+	}
+
 	pub fn fail(self, error: ClientError) {
 		// don't care if anyone is listening
 		match self {
 			RequestData::RemoteHeader(_, sender) => { let _ = sender.send(Err(error)); },
 			RequestData::RemoteCall(_, sender) => { let _ = sender.send(Err(error)); },
 			RequestData::RemoteRead(_, sender) => { let _ = sender.send(Err(error)); },
+			RequestData::RemoteReadChild(_, sender) => { let _ = sender.send(Err(error)); },
 			RequestData::RemoteChanges(_, sender) => { let _ = sender.send(Err(error)); },
+			RequestData::RemoteChildRoots(_, sender) => { let _ = sender.send(Err(error)); },
+			RequestData::RemoteDigest(_, sender) => { let _ = sender.send(Err(error)); },
+			RequestData::RemoteReadPrefix(_, sender) => { let _ = sender.send(Err(error)); },
+			RequestData::RemoteBody(_, sender) => { let _ = sender.send(Err(error)); },
 		}
 	}
 }
@@ -505,21 +1171,39 @@ impl<Block: BlockT> RequestData<Block> {
 pub mod tests {
 	use std::collections::VecDeque;
 	use std::sync::Arc;
-	use std::time::Instant;
+	use std::time::{Duration, Instant};
 	use futures::Future;
-	use parking_lot::RwLock;
-	use client::{self, error::{ErrorKind as ClientErrorKind, Result as ClientResult}};
+	use parking_lot::{Mutex, RwLock};
+	use codec::Encode;
+	use client::{self, error::{Error as ClientError, ErrorKind as ClientErrorKind, Result as ClientResult}};
 	use client::light::fetcher::{Fetcher, FetchChecker, RemoteHeaderRequest,
-		RemoteCallRequest, RemoteReadRequest, RemoteChangesRequest};
+		RemoteCallRequest, RemoteReadRequest, RemoteChangesRequest, RemoteChildRootsRequest, RemoteDigestRequest,
+		RemoteReadPrefixRequest, RemoteBodyRequest, RemoteReadChildRequest, RequestKind};
+	use client::light::misbehavior::{MisbehaviorLog, MisbehaviorRecord};
 	use message;
 	use network_libp2p::NodeIndex;
+	use runtime_primitives::generic;
+	use runtime_primitives::traits::{Block as BlockT, Header as HeaderT};
 	use service::{Roles, ExecuteInContext};
 	use test::TestIo;
-	use super::{REQUEST_TIMEOUT, OnDemand, OnDemandService};
-	use test_client::runtime::{changes_trie_config, Block, Header};
+	use super::{REQUEST_TIMEOUT, FetcherConfig, OnDemand, OnDemandService, StorageChangesSink};
+	use test_client::runtime::{changes_trie_config, Block, DigestItem, Extrinsic, Header};
 
 	pub struct DummyExecutor;
 	struct DummyFetchChecker { ok: bool }
+	#[derive(Default)]
+	struct DummyMisbehaviorLog(Mutex<Vec<MisbehaviorRecord>>);
+
+	impl MisbehaviorLog for DummyMisbehaviorLog {
+		fn note(&self, record: MisbehaviorRecord) -> ClientResult<()> {
+			self.0.lock().push(record);
+			Ok(())
+		}
+
+		fn records(&self) -> ClientResult<Vec<MisbehaviorRecord>> {
+			Ok(self.0.lock().clone())
+		}
+	}
 
 	impl ExecuteInContext<Block> for DummyExecutor {
 		fn execute_in_context<F: Fn(&mut ::protocol::Context<Block>)>(&self, _closure: F) {}
@@ -538,7 +1222,14 @@ pub mod tests {
 			}
 		}
 
-		fn check_read_proof(&self, _: &RemoteReadRequest<Header>, _: Vec<Vec<u8>>) -> ClientResult<Option<Vec<u8>>> {
+		fn check_read_proof(&self, request: &RemoteReadRequest<Header>, _: Vec<Vec<u8>>) -> ClientResult<Vec<Option<Vec<u8>>>> {
+			match self.ok {
+				true => Ok(request.keys.iter().map(|_| Some(vec![42])).collect()),
+				false => Err(ClientErrorKind::Backend("Test error".into()).into()),
+			}
+		}
+
+		fn check_read_child_proof(&self, _: &RemoteReadChildRequest<Header>, _: Vec<Vec<u8>>) -> ClientResult<Option<Vec<u8>>> {
 			match self.ok {
 				true => Ok(Some(vec![42])),
 				false => Err(ClientErrorKind::Backend("Test error".into()).into()),
@@ -550,6 +1241,7 @@ pub mod tests {
 				true => Ok(client::CallResult {
 					return_data: vec![42],
 					changes: Default::default(),
+					accessed_keys: Vec::new(),
 				}),
 				false => Err(ClientErrorKind::Backend("Test error".into()).into()),
 			}
@@ -561,11 +1253,57 @@ pub mod tests {
 				false => Err(ClientErrorKind::Backend("Test error".into()).into()),
 			}
 		}
+
+		fn check_child_roots_proof(&self, _: &RemoteChildRootsRequest<Header>, _: Vec<Vec<u8>>) -> ClientResult<Vec<(Vec<u8>, Vec<u8>)>> {
+			match self.ok {
+				true => Ok(vec![(vec![1], vec![2])]),
+				false => Err(ClientErrorKind::Backend("Test error".into()).into()),
+			}
+		}
+
+		fn check_digest_proof(
+			&self,
+			_: &RemoteDigestRequest<Header>,
+			_: Vec<Header>,
+			_: Vec<Vec<u8>>
+		) -> ClientResult<Vec<(u64, Vec<DigestItem>)>> {
+			match self.ok {
+				true => Ok(vec![(3, vec![generic::DigestItem::Other(vec![42])])]),
+				false => Err(ClientErrorKind::Backend("Test error".into()).into()),
+			}
+		}
+
+		fn check_read_prefix_proof(
+			&self,
+			_: &RemoteReadPrefixRequest<Header>,
+			_: Vec<Vec<u8>>
+		) -> ClientResult<(Vec<(Vec<u8>, Vec<u8>)>, bool)> {
+			match self.ok {
+				true => Ok((vec![(vec![1, 2], vec![3])], true)),
+				false => Err(ClientErrorKind::Backend("Test error".into()).into()),
+			}
+		}
+
+		fn check_body_proof(
+			&self,
+			_: &RemoteBodyRequest<Header>,
+			body: Vec<Extrinsic>
+		) -> ClientResult<Vec<Extrinsic>> {
+			match self.ok {
+				true => Ok(body),
+				false => Err(ClientErrorKind::Backend("Test error".into()).into()),
+			}
+		}
 	}
 
 	fn dummy(ok: bool) -> (Arc<DummyExecutor>, Arc<OnDemand<Block, DummyExecutor>>) {
+		dummy_with_backoff(ok, Duration::from_secs(0))
+	}
+
+	fn dummy_with_backoff(ok: bool, backoff_base: Duration) -> (Arc<DummyExecutor>, Arc<OnDemand<Block, DummyExecutor>>) {
 		let executor = Arc::new(DummyExecutor);
-		let service = Arc::new(OnDemand::new(Arc::new(DummyFetchChecker { ok })));
+		let config = FetcherConfig { backoff_base, ..Default::default() };
+		let service = Arc::new(OnDemand::with_config(Arc::new(DummyFetchChecker { ok }), config));
 		service.set_service_link(Arc::downgrade(&executor));
 		(executor, service)
 	}
@@ -579,9 +1317,19 @@ pub mod tests {
 		on_demand.on_remote_call_response(network, peer, message::RemoteCallResponse {
 			id: id,
 			proof: vec![vec![2]],
+			accessed_keys: vec![],
 		});
 	}
 
+	#[derive(Default)]
+	struct DummyStorageChangesSink(Mutex<Vec<(Vec<u8>, Option<Vec<u8>>)>>);
+
+	impl StorageChangesSink<Block> for DummyStorageChangesSink {
+		fn on_storage_changes(&self, _block: <Block as BlockT>::Hash, changes: Vec<(Vec<u8>, Option<Vec<u8>>)>) {
+			self.0.lock().extend(changes);
+		}
+	}
+
 	fn dummy_header() -> Header {
 		Header {
 			parent_hash: Default::default(),
@@ -603,6 +1351,95 @@ pub mod tests {
 		assert_eq!(on_demand.core.lock().best_blocks.get(&2), Some(&3000));
 	}
 
+	#[test]
+	fn sends_only_header_requests_to_light_servers() {
+		let (_x, on_demand) = dummy(true);
+		on_demand.on_connect(0, Roles::LIGHT | Roles::LIGHT_SERVER, 1000);
+		assert_eq!(vec![0], on_demand.core.lock().idle_peers.iter().cloned().collect::<Vec<_>>());
+
+		on_demand.remote_call(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			trace_keys: false,
+			retry_count: None,
+		});
+		assert!(on_demand.core.lock().active_peers.is_empty());
+		assert_eq!(1, on_demand.core.lock().pending_requests.len());
+
+		on_demand.core.lock().pending_requests.clear();
+		on_demand.remote_header(RemoteHeaderRequest {
+			cht_root: Default::default(),
+			block: 1,
+			retry_count: None,
+		});
+		assert_eq!(vec![0], on_demand.core.lock().active_peers.keys().cloned().collect::<Vec<_>>());
+		assert!(on_demand.core.lock().pending_requests.is_empty());
+	}
+
+	#[test]
+	fn pruned_headers_are_not_requested_from_light_servers_again() {
+		let (_x, on_demand) = dummy(false);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::LIGHT | Roles::LIGHT_SERVER, 1000);
+
+		on_demand.remote_header(RemoteHeaderRequest {
+			cht_root: Default::default(),
+			block: 10,
+			retry_count: None,
+		});
+		on_demand.on_remote_header_response(&mut network, 0, message::RemoteHeaderResponse {
+			id: 0,
+			header: None,
+			proof: vec![],
+		});
+
+		// the peer isn't penalized and the request waits for the peer that has the block
+		assert!(network.to_disconnect.is_empty());
+		assert_eq!(vec![0], on_demand.core.lock().idle_peers.iter().cloned().collect::<Vec<_>>());
+		assert_eq!(1, on_demand.core.lock().pending_requests.len());
+
+		// blocks above the pruned one are still requested from the light server
+		on_demand.remote_header(RemoteHeaderRequest {
+			cht_root: Default::default(),
+			block: 11,
+			retry_count: None,
+		});
+		assert_eq!(vec![0], on_demand.core.lock().active_peers.keys().cloned().collect::<Vec<_>>());
+
+		on_demand.on_connect(1, Roles::FULL, 1000);
+		assert_eq!(vec![0, 1], on_demand.core.lock().active_peers.keys().cloned().collect::<Vec<_>>());
+		assert!(on_demand.core.lock().pending_requests.is_empty());
+	}
+
+	#[test]
+	fn too_many_keys_are_not_requested() {
+		let (_x, on_demand) = dummy(true);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+
+		let response = on_demand.remote_read(RemoteReadRequest {
+			header: dummy_header(),
+			block: Default::default(),
+			keys: (0..::protocol::MAX_READ_KEYS + 1).map(|i| (i as u32).encode()).collect(),
+			retry_count: None,
+		});
+		assert!(response.wait().is_err());
+		assert!(on_demand.core.lock().active_peers.is_empty());
+		assert!(on_demand.core.lock().pending_requests.is_empty());
+	}
+
+	#[test]
+	fn ignores_announces_of_peers_not_serving_requests() {
+		let (_, on_demand) = dummy(true);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+		on_demand.on_block_announce(0, 1100);
+		on_demand.on_block_announce(1, 2000);
+		assert_eq!(on_demand.core.lock().best_blocks.get(&0), Some(&1100));
+		assert_eq!(on_demand.core.lock().best_blocks.get(&1), None);
+	}
+
 	#[test]
 	fn disconnects_from_idle_peer() {
 		let (_, on_demand) = dummy(true);
@@ -631,6 +1468,7 @@ pub mod tests {
 			header: dummy_header(),
 			method: "test".into(),
 			call_data: vec![],
+			trace_keys: false,
 			retry_count: None,
 		});
 		assert_eq!(vec![1], on_demand.core.lock().idle_peers.iter().cloned().collect::<Vec<_>>());
@@ -655,6 +1493,7 @@ pub mod tests {
 			header: dummy_header(),
 			method: "test".into(),
 			call_data: vec![],
+			trace_keys: false,
 			retry_count: None,
 		});
 		receive_call_response(&*on_demand, &mut network, 0, 1);
@@ -672,6 +1511,7 @@ pub mod tests {
 			header: dummy_header(),
 			method: "test".into(),
 			call_data: vec![],
+			trace_keys: false,
 			retry_count: Some(1),
 		});
 
@@ -681,6 +1521,32 @@ pub mod tests {
 		assert_eq!(on_demand.core.lock().pending_requests.len(), 1);
 	}
 
+	#[test]
+	fn records_misbehavior_on_incorrect_response() {
+		let (_x, on_demand) = dummy(false);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		assert!(on_demand.misbehavior_records().unwrap().is_empty());
+		on_demand.set_misbehavior_log(Arc::new(DummyMisbehaviorLog::default()));
+		on_demand.remote_call(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			trace_keys: false,
+			retry_count: Some(1),
+		});
+
+		on_demand.on_connect(0, Roles::FULL, 1000);
+		receive_call_response(&*on_demand, &mut network, 0, 0);
+		assert!(network.to_disconnect.contains(&0));
+
+		let records = on_demand.misbehavior_records().unwrap();
+		assert_eq!(records.len(), 1);
+		assert_eq!(records[0].response, message::RemoteCallResponse { id: 0, proof: vec![vec![2]], accessed_keys: vec![] }.encode());
+		assert!(records[0].error.contains("Test error"));
+	}
+
 	#[test]
 	fn disconnects_from_peer_on_unexpected_response() {
 		let (_x, on_demand) = dummy(true);
@@ -704,6 +1570,7 @@ pub mod tests {
 			header: dummy_header(),
 			method: "test".into(),
 			call_data: vec![],
+			trace_keys: false,
 			retry_count: Some(1),
 		});
 
@@ -735,6 +1602,7 @@ pub mod tests {
 			header: dummy_header(),
 			method: "test".into(),
 			call_data: vec![],
+			trace_keys: false,
 			retry_count: Some(retry_count)
 		});
 		let thread = ::std::thread::spawn(move || {
@@ -758,6 +1626,137 @@ pub mod tests {
 		thread.join().unwrap();
 	}
 
+	#[test]
+	fn retries_failed_request_after_backoff() {
+		let (_x, on_demand) = dummy_with_backoff(false, Duration::from_secs(60));
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+		on_demand.on_connect(1, Roles::FULL, 1000);
+
+		on_demand.remote_call(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			trace_keys: false,
+			retry_count: Some(1),
+		});
+		receive_call_response(&*on_demand, &mut network, 0, 0);
+
+		// the request waits for the backoff delay
+		on_demand.maintain_peers(&mut network);
+		assert!(on_demand.core.lock().active_peers.is_empty());
+		assert_eq!(on_demand.core.lock().pending_requests[0].failures, 1);
+
+		on_demand.core.lock().pending_requests[0].next_attempt = Instant::now();
+		on_demand.maintain_peers(&mut network);
+		assert_eq!(vec![1], on_demand.core.lock().active_peers.keys().cloned().collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn receives_remote_failure_after_timeouts() {
+		let (_x, on_demand) = dummy(true);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+		on_demand.on_connect(1, Roles::FULL, 1000);
+
+		let response = on_demand.remote_call(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			trace_keys: false,
+			retry_count: Some(1),
+		});
+		for _ in 0..2 {
+			let peer = *on_demand.core.lock().active_peers.keys().next().unwrap();
+			on_demand.core.lock().active_peers[&peer].timestamp = Instant::now() - REQUEST_TIMEOUT - REQUEST_TIMEOUT;
+			on_demand.maintain_peers(&mut network);
+		}
+
+		match response.wait() {
+			Err(ClientError(ClientErrorKind::RemoteFetchTimedOut(2), _)) => (),
+			result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+		}
+
+		let metrics = on_demand.metrics();
+		assert_eq!(metrics.len(), 1);
+		assert_eq!(metrics[0].0, RequestKind::Call);
+		assert_eq!((metrics[0].1.requests, metrics[0].1.timeouts, metrics[0].1.failures), (1, 2, 1));
+	}
+
+	#[test]
+	fn fails_requests_unserved_for_too_long() {
+		let executor = Arc::new(DummyExecutor);
+		let config = FetcherConfig { unserved_timeout: Some(REQUEST_TIMEOUT), ..Default::default() };
+		let on_demand = OnDemand::with_config(Arc::new(DummyFetchChecker { ok: true }), config);
+		on_demand.set_service_link(Arc::downgrade(&executor));
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+
+		// no peer is connected, so the request stays pending
+		let response = on_demand.remote_call(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			trace_keys: false,
+			retry_count: None,
+		});
+		on_demand.maintain_peers(&mut network);
+		assert_eq!(on_demand.core.lock().pending_requests.len(), 1);
+
+		on_demand.core.lock().pending_requests[0].next_attempt = Instant::now() - REQUEST_TIMEOUT;
+		on_demand.maintain_peers(&mut network);
+		assert!(on_demand.core.lock().pending_requests.is_empty());
+		match response.wait() {
+			Err(ClientError(ClientErrorKind::RemoteFetchFailed, _)) => (),
+			result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+		}
+	}
+
+	#[test]
+	fn sends_requests_to_best_ranked_peer() {
+		let (_x, on_demand) = dummy(true);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+		on_demand.on_connect(1, Roles::FULL, 1000);
+		let call = || on_demand.remote_call(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			trace_keys: false,
+			retry_count: None,
+		});
+
+		// peer 0 responds slowly
+		call();
+		on_demand.core.lock().active_peers[&0].timestamp = Instant::now() - Duration::from_secs(5);
+		receive_call_response(&*on_demand, &mut network, 0, 0);
+
+		// peer 1 hasn't responded yet, so it's tried before peer 0
+		call();
+		assert_eq!(vec![1], on_demand.core.lock().active_peers.keys().cloned().collect::<Vec<_>>());
+		receive_call_response(&*on_demand, &mut network, 1, 1);
+
+		// peer 1 is faster than peer 0
+		call();
+		assert_eq!(vec![1], on_demand.core.lock().active_peers.keys().cloned().collect::<Vec<_>>());
+
+		let mut stats = on_demand.peer_stats();
+		stats.sort_by_key(|&(peer, _)| peer);
+		assert_eq!(stats.iter().map(|&(_, ref stats)| stats.valid_responses).collect::<Vec<_>>(), vec![1, 1]);
+		let metrics = on_demand.metrics();
+		assert_eq!((metrics[0].1.requests, metrics[0].1.responses), (3, 2));
+		assert_eq!(metrics[0].1.latency.buckets.iter().sum::<u64>(), 2);
+		assert!(stats[0].1.average_latency.unwrap() >= Duration::from_secs(5));
+		assert!(stats[1].1.average_latency.unwrap() < Duration::from_secs(5));
+	}
+
 	#[test]
 	fn receives_remote_call_response() {
 		let (_x, on_demand) = dummy(true);
@@ -770,6 +1769,7 @@ pub mod tests {
 			header: dummy_header(),
 			method: "test".into(),
 			call_data: vec![],
+			trace_keys: false,
 			retry_count: None,
 		});
 		let thread = ::std::thread::spawn(move || {
@@ -781,6 +1781,34 @@ pub mod tests {
 		thread.join().unwrap();
 	}
 
+	#[test]
+	fn receives_accessed_keys_when_requested() {
+		let (_x, on_demand) = dummy(true);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+
+		let response = on_demand.remote_call(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			trace_keys: true,
+			retry_count: None,
+		});
+		let thread = ::std::thread::spawn(move || {
+			let result = response.wait().unwrap();
+			assert_eq!(result.accessed_keys, vec![vec![1], vec![2]]);
+		});
+
+		on_demand.on_remote_call_response(&mut network, 0, message::RemoteCallResponse {
+			id: 0,
+			proof: vec![vec![2]],
+			accessed_keys: vec![vec![1], vec![2]],
+		});
+		thread.join().unwrap();
+	}
+
 	#[test]
 	fn receives_remote_read_response() {
 		let (_x, on_demand) = dummy(true);
@@ -791,6 +1819,59 @@ pub mod tests {
 		let response = on_demand.remote_read(RemoteReadRequest {
 			header: dummy_header(),
 			block: Default::default(),
+			keys: vec![b":key1".to_vec(), b":key2".to_vec()],
+			retry_count: None,
+		});
+		let thread = ::std::thread::spawn(move || {
+			let result = response.wait().unwrap();
+			assert_eq!(result, vec![Some(vec![42]), Some(vec![42])]);
+		});
+
+		on_demand.on_remote_read_response(&mut network, 0, message::RemoteReadResponse {
+			id: 0,
+			proof: vec![vec![2]],
+		});
+		thread.join().unwrap();
+	}
+
+	#[test]
+	fn single_key_is_read_with_message_known_to_older_versions() {
+		let request = |keys: Vec<Vec<u8>>| super::Request::<Block> {
+			id: 1,
+			timestamp: ::std::time::Instant::now(),
+			retry_count: 0,
+			failures: 0,
+			next_attempt: ::std::time::Instant::now(),
+			data: super::RequestData::RemoteRead(RemoteReadRequest {
+				header: dummy_header(),
+				block: Default::default(),
+				keys,
+				retry_count: None,
+			}, ::futures::sync::oneshot::channel().0),
+		};
+
+		match request(vec![b":key1".to_vec()]).message() {
+			message::generic::Message::RemoteReadRequest(request) => assert_eq!(request.key, b":key1".to_vec()),
+			_ => panic!("single key is read with RemoteReadRequest"),
+		}
+		match request(vec![b":key1".to_vec(), b":key2".to_vec()]).message() {
+			message::generic::Message::RemoteReadMultiRequest(request) =>
+				assert_eq!(request.keys, vec![b":key1".to_vec(), b":key2".to_vec()]),
+			_ => panic!("multiple keys are read with RemoteReadMultiRequest"),
+		}
+	}
+
+	#[test]
+	fn receives_remote_read_child_response() {
+		let (_x, on_demand) = dummy(true);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+
+		let response = on_demand.remote_read_child(RemoteReadChildRequest {
+			header: dummy_header(),
+			block: Default::default(),
+			storage_key: b":child_storage:default:sub".to_vec(),
 			key: b":key".to_vec(),
 			retry_count: None,
 		});
@@ -866,6 +1947,125 @@ pub mod tests {
 		thread.join().unwrap();
 	}
 
+	#[test]
+	fn receives_remote_child_roots_response() {
+		let (_x, on_demand) = dummy(true);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+
+		let response = on_demand.remote_child_roots(RemoteChildRootsRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			retry_count: None,
+		});
+		let thread = ::std::thread::spawn(move || {
+			let result = response.wait().unwrap();
+			assert_eq!(result, vec![(vec![1], vec![2])]);
+		});
+
+		on_demand.on_remote_child_roots_response(&mut network, 0, message::RemoteChildRootsResponse {
+			id: 0,
+			proof: vec![vec![2]],
+		});
+		thread.join().unwrap();
+	}
+
+	#[test]
+	fn receives_remote_digest_response() {
+		let (_x, on_demand) = dummy(true);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+
+		let response = on_demand.remote_digest(RemoteDigestRequest {
+			cht_root: Default::default(),
+			first_block: 1,
+			last_block: 10,
+			item_types: vec![0],
+			retry_count: None,
+		});
+		let thread = ::std::thread::spawn(move || {
+			let result = response.wait().unwrap();
+			assert_eq!(result, vec![(3, vec![generic::DigestItem::Other(vec![42])])]);
+		});
+
+		on_demand.on_remote_digest_response(&mut network, 0, message::RemoteDigestResponse {
+			id: 0,
+			headers: vec![dummy_header()],
+			proof: vec![vec![2]],
+		});
+		thread.join().unwrap();
+	}
+
+	#[test]
+	fn receives_remote_read_prefix_response() {
+		let (_x, on_demand) = dummy(true);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+
+		let response = on_demand.remote_read_prefix(RemoteReadPrefixRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			prefix: vec![1],
+			start_key: vec![],
+			limit: 16,
+			retry_count: None,
+		});
+		let thread = ::std::thread::spawn(move || {
+			let result = response.wait().unwrap();
+			assert_eq!(result, (vec![(vec![1, 2], vec![3])], true));
+		});
+
+		on_demand.on_remote_read_prefix_response(&mut network, 0, message::RemoteReadPrefixResponse {
+			id: 0,
+			proof: vec![vec![2]],
+		});
+		thread.join().unwrap();
+	}
+
+	#[test]
+	fn receives_remote_body_response() {
+		let (_x, on_demand) = dummy(true);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+
+		let response = on_demand.remote_body(RemoteBodyRequest {
+			header: dummy_header(),
+			retry_count: None,
+		});
+		let thread = ::std::thread::spawn(move || {
+			let result = response.wait().unwrap();
+			assert!(result.is_empty());
+		});
+
+		on_demand.on_remote_body_response(&mut network, 0, message::RemoteBodyResponse {
+			id: 0,
+			body: Some(vec![]),
+		});
+		thread.join().unwrap();
+	}
+
+	#[test]
+	fn missing_remote_body_is_invalid_response() {
+		let (_x, on_demand) = dummy(true);
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+
+		on_demand.remote_body(RemoteBodyRequest {
+			header: dummy_header(),
+			retry_count: Some(0),
+		});
+		on_demand.on_remote_body_response(&mut network, 0, message::RemoteBodyResponse {
+			id: 0,
+			body: None,
+		});
+		assert!(network.to_disconnect.contains(&0));
+	}
+
 	#[test]
 	fn does_not_sends_request_to_peer_who_has_no_required_block() {
 		let (_x, on_demand) = dummy(true);
@@ -914,4 +2114,38 @@ pub mod tests {
 		assert!(!on_demand.core.lock().idle_peers.iter().any(|_| true));
 		assert_eq!(on_demand.core.lock().pending_requests.len(), 0);
 	}
+
+	#[test]
+	fn storage_changes_are_checked_when_block_is_imported() {
+		let (_x, on_demand) = dummy(true);
+		let sink = Arc::new(DummyStorageChangesSink::default());
+		on_demand.set_storage_changes_sink(sink.clone());
+		let queue = RwLock::new(VecDeque::new());
+		let mut network = TestIo::new(&queue, None);
+
+		on_demand.subscribe_storage(vec![vec![1], vec![1]]);
+		on_demand.on_connect(0, Roles::FULL, 1000);
+		assert_eq!(on_demand.core.lock().storage_keys, vec![vec![1]]);
+		let (peer, id) = on_demand.core.lock().storage_subscription.unwrap();
+		assert_eq!(peer, 0);
+
+		let header = dummy_header();
+		let changes = |id| message::RemoteStorageChanges {
+			id,
+			block: header.hash(),
+			changes: vec![(vec![1], Some(vec![42]))],
+			proof: vec![vec![2]],
+		};
+
+		// changes for unknown subscription are ignored
+		on_demand.on_remote_storage_changes(&mut network, 0, None, changes(id + 1));
+		// changes at unknown block are buffered until it is imported
+		on_demand.on_remote_storage_changes(&mut network, 0, None, changes(id));
+		assert_eq!(on_demand.core.lock().pending_storage_changes.len(), 1);
+		assert!(sink.0.lock().is_empty());
+
+		on_demand.on_block_imported(&mut network, &header);
+		assert!(on_demand.core.lock().pending_storage_changes.is_empty());
+		assert_eq!(*sink.0.lock(), vec![(vec![1], Some(vec![42]))]);
+	}
 }
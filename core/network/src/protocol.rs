@@ -17,30 +17,40 @@
 use std::collections::{HashMap, HashSet};
 use std::{mem, cmp};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time;
 use parking_lot::RwLock;
 use rustc_hex::ToHex;
+use codec::Encode;
 use runtime_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor, As, Zero};
 use runtime_primitives::generic::BlockId;
 use network_libp2p::{NodeIndex, Severity};
-use codec::{Encode, Decode};
+use client::light::fetcher::{RequestKind, RequestMetrics};
 
-use message::{self, Message};
+use message::{self, Message, DecodedMessage};
 use message::generic::Message as GenericMessage;
 use specialization::Specialization;
-use sync::{ChainSync, Status as SyncStatus, SyncState};
+use sync::{ChainSync, Status as SyncStatus};
 use service::{Roles, TransactionPool, ExHashT};
 use import_queue::ImportQueue;
 use config::ProtocolConfig;
 use chain::Client;
-use on_demand::OnDemandService;
+use on_demand::{OnDemandService, PeerStats};
+use rebroadcast::RebroadcastScheduler;
+use head_check::HeadCrossCheck;
+use watchdog::StallWatchdog;
 use io::SyncIo;
 use error;
 
 const REQUEST_TIMEOUT_SEC: u64 = 40;
+/// Minimal number of peers that should report their time to estimate local clock drift.
+const MIN_TIME_OFFSET_SAMPLES: usize = 3;
 
 /// Current protocol version.
-pub (crate) const CURRENT_VERSION: u32 = 1;
+pub (crate) const CURRENT_VERSION: u32 = 2;
+/// Lowest protocol version we can talk to. Peers with newer versions are accepted,
+/// messages they send that we don't understand are ignored.
+const MIN_VERSION: u32 = 2;
 
 // Maximum allowed entries in `BlockResponse`
 const MAX_BLOCK_DATA_RESPONSE: u32 = 128;
@@ -48,11 +58,21 @@ const MAX_BLOCK_DATA_RESPONSE: u32 = 128;
 /// for at least `LIGHT_MAXIMAL_BLOCKS_DIFFERENCE` blocks, we consider it unuseful
 /// and disconnect to free connection slot.
 const LIGHT_MAXIMAL_BLOCKS_DIFFERENCE: u64 = 8192;
+/// Maximal number of storage keys a light client peer may subscribe to.
+const MAX_SUBSCRIBED_STORAGE_KEYS: usize = 256;
+/// Maximal number of storage entries proved in response to the prefix read request.
+const MAX_READ_PREFIX_PAGE_SIZE: u32 = 1024;
+/// Maximal number of storage keys read by a single remote read request.
+pub(crate) const MAX_READ_KEYS: usize = 1024;
 
 // Lock must always be taken in order declared here.
 pub struct Protocol<B: BlockT, S: Specialization<B>, H: ExHashT> {
 	config: ProtocolConfig,
 	on_demand: Option<Arc<OnDemandService<B>>>,
+	// On-demand service of the fallback peers, used once the `on_demand` service fails.
+	fallback_on_demand: Option<Arc<OnDemandService<B>>>,
+	// Connected peers serving the `fallback_on_demand` service.
+	fallback_peers: RwLock<HashSet<NodeIndex>>,
 	genesis_hash: B::Hash,
 	sync: Arc<RwLock<ChainSync<B>>>,
 	specialization: RwLock<S>,
@@ -60,6 +80,18 @@ pub struct Protocol<B: BlockT, S: Specialization<B>, H: ExHashT> {
 	// Connected peers pending Status message.
 	handshaking_peers: RwLock<HashMap<NodeIndex, time::Instant>>,
 	transaction_pool: Arc<TransactionPool<H, B>>,
+	// Propagation schedule of the pooled transactions.
+	rebroadcast: RwLock<RebroadcastScheduler<H>>,
+	/// Cross-check of the announced heads, if enabled on the light client.
+	head_check: Option<RwLock<HeadCrossCheck<B::Hash, NumberFor<B>>>>,
+	// Cost of serving light client requests, per connected peer.
+	light_serving: RwLock<HashMap<NodeIndex, LightServingStats>>,
+	// Storage subscriptions of light client peers.
+	storage_subscriptions: RwLock<HashMap<NodeIndex, StorageSubscription>>,
+	// Number of peers rejected because they are on a different chain.
+	chain_mismatches: AtomicUsize,
+	// Detection of the stalled chain, if enabled.
+	watchdog: Option<RwLock<StallWatchdog<NumberFor<B>>>>,
 }
 /// Syncing status and statistics
 #[derive(Clone)]
@@ -70,6 +102,37 @@ pub struct ProtocolStatus<B: BlockT> {
 	pub num_peers: usize,
 	/// Total number of active peers.
 	pub num_active_peers: usize,
+	/// Cost of serving light client requests, per connected peer.
+	pub light_serving: HashMap<NodeIndex, LightServingStats>,
+	/// Total number of peers rejected because their genesis hash or fork id differs from ours.
+	pub chain_mismatches: usize,
+	/// Total number of the chain stalls detected by the watchdog.
+	pub stalls: usize,
+	/// Time since the best block has changed, if the chain is stalled.
+	pub stalled_for: Option<time::Duration>,
+	/// Metrics of the on-demand requests, per request kind. Empty if on-demand is disabled.
+	pub on_demand_metrics: Vec<(RequestKind, RequestMetrics)>,
+	/// Response statistics of the peers serving on-demand requests.
+	pub on_demand_peers: Vec<(NodeIndex, PeerStats)>,
+}
+
+/// Cost of serving light client requests to a peer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LightServingStats {
+	/// Number of proofs served.
+	pub proofs: u64,
+	/// Total size of served proofs, in bytes.
+	pub bytes: u64,
+	/// Time spent generating proofs.
+	pub cpu_time: time::Duration,
+}
+
+/// Storage subscription of a light client peer.
+struct StorageSubscription {
+	/// Subscription id chosen by the peer.
+	id: message::RequestId,
+	/// Values of subscribed keys that have been pushed to the peer.
+	values: HashMap<Vec<u8>, Option<Vec<u8>>>,
 }
 
 /// Peer information
@@ -92,6 +155,9 @@ struct Peer<B: BlockT, H: ExHashT> {
 	known_blocks: HashSet<B::Hash>,
 	/// Request counter,
 	next_request_id: message::RequestId,
+	/// Difference between the time reported by the peer in its status and the local
+	/// time, in milliseconds.
+	time_offset: Option<i64>,
 }
 
 /// Info about a peer's known state.
@@ -195,7 +261,16 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 		specialization: S,
 	) -> error::Result<Self> {
 		let info = chain.info()?;
-		let sync = ChainSync::new(config.roles, &info, import_queue);
+		let mut sync = ChainSync::new(config.roles, &info, import_queue);
+		sync.restore_checkpoint(&*chain);
+		let rebroadcast = RebroadcastScheduler::new(config.rebroadcast.clone());
+		let head_check = if config.roles & Roles::LIGHT == Roles::LIGHT && config.light_head_providers > 0 {
+			Some(RwLock::new(HeadCrossCheck::new(config.light_head_providers)))
+		} else {
+			None
+		};
+		let watchdog = config.watchdog.clone().map(|watchdog_config|
+			RwLock::new(StallWatchdog::new(watchdog_config, info.chain.best_number, time::Instant::now())));
 		let protocol = Protocol {
 			config: config,
 			context_data: ContextData {
@@ -203,15 +278,43 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 				chain,
 			},
 			on_demand,
+			fallback_on_demand: None,
+			fallback_peers: RwLock::new(HashSet::new()),
 			genesis_hash: info.chain.genesis_hash,
 			sync: Arc::new(RwLock::new(sync)),
 			specialization: RwLock::new(specialization),
 			handshaking_peers: RwLock::new(HashMap::new()),
+			light_serving: RwLock::new(HashMap::new()),
+			storage_subscriptions: RwLock::new(HashMap::new()),
+			chain_mismatches: AtomicUsize::new(0),
 			transaction_pool: transaction_pool,
+			rebroadcast: RwLock::new(rebroadcast),
+			head_check,
+			watchdog,
 		};
 		Ok(protocol)
 	}
 
+	/// Send the on-demand requests to the fallback peers through the given service. The fallback
+	/// peers only serve this service, while the other peers serve the main on-demand service.
+	pub fn with_fallback_on_demand(mut self, fallback_on_demand: Arc<OnDemandService<B>>) -> Self {
+		self.fallback_on_demand = Some(fallback_on_demand);
+		self
+	}
+
+	/// On-demand service the peer is serving.
+	fn on_demand_of(&self, peer: NodeIndex) -> Option<&Arc<OnDemandService<B>>> {
+		match self.fallback_peers.read().contains(&peer) {
+			true => self.fallback_on_demand.as_ref(),
+			false => self.on_demand.as_ref(),
+		}
+	}
+
+	/// All the on-demand services.
+	fn on_demand_services<'a>(&'a self) -> impl Iterator<Item=&'a Arc<OnDemandService<B>>> + 'a {
+		self.on_demand.iter().chain(self.fallback_on_demand.iter())
+	}
+
 	pub(crate) fn context_data(&self) -> &ContextData<B, H> {
 		&self.context_data
 	}
@@ -228,13 +331,37 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 			sync: sync.status(),
 			num_peers: peers.values().count(),
 			num_active_peers: peers.values().filter(|p| p.block_request.is_some()).count(),
+			light_serving: self.light_serving.read().clone(),
+			chain_mismatches: self.chain_mismatches.load(Ordering::Relaxed),
+			stalls: self.watchdog.as_ref().map_or(0, |watchdog| watchdog.read().stalls()),
+			stalled_for: self.watchdog.as_ref().and_then(|watchdog| watchdog.read().stalled_for(time::Instant::now())),
+			on_demand_metrics: self.on_demand_services().flat_map(|s| s.metrics()).collect(),
+			on_demand_peers: self.on_demand_services().flat_map(|s| s.peer_stats()).collect(),
 		}
 	}
 
-	pub fn handle_packet(&self, io: &mut SyncIo, who: NodeIndex, mut data: &[u8]) {
-		let message: Message<B> = match Decode::decode(&mut data) {
-			Some(m) => m,
-			None => {
+	/// Returns median difference between the time reported by connected peers and the
+	/// local time, in milliseconds. Returns `None` if too few peers have reported their time.
+	pub fn median_time_offset(&self) -> Option<i64> {
+		let mut offsets: Vec<_> = self.context_data.peers.read().values()
+			.filter_map(|peer| peer.time_offset)
+			.collect();
+		if offsets.len() < MIN_TIME_OFFSET_SAMPLES {
+			return None;
+		}
+
+		offsets.sort_unstable();
+		Some(offsets[offsets.len() / 2])
+	}
+
+	pub fn handle_packet(&self, io: &mut SyncIo, who: NodeIndex, data: &[u8]) {
+		let message: Message<B> = match Message::<B>::decode_envelope(data) {
+			DecodedMessage::Known(m) => m,
+			DecodedMessage::Unknown { version, index } => {
+				trace!(target: "sync", "Ignoring unknown message {} (version {}) from {}", index, version, who);
+				return;
+			},
+			DecodedMessage::Invalid => {
 				trace!(target: "sync", "Invalid packet from {}", who);
 				io.report_peer(who, Severity::Bad("Peer sent us a packet with invalid format"));
 				return;
@@ -277,6 +404,19 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 			GenericMessage::RemoteHeaderResponse(response) => self.on_remote_header_response(io, who, response),
 			GenericMessage::RemoteChangesRequest(request) => self.on_remote_changes_request(io, who, request),
 			GenericMessage::RemoteChangesResponse(response) => self.on_remote_changes_response(io, who, response),
+			GenericMessage::RemoteStorageSubscribe(request) => self.on_remote_storage_subscribe(io, who, request),
+			GenericMessage::RemoteStorageUnsubscribe(request) => self.on_remote_storage_unsubscribe(who, request),
+			GenericMessage::RemoteStorageChanges(changes) => self.on_remote_storage_changes(io, who, changes),
+			GenericMessage::RemoteChildRootsRequest(request) => self.on_remote_child_roots_request(io, who, request),
+			GenericMessage::RemoteChildRootsResponse(response) => self.on_remote_child_roots_response(io, who, response),
+			GenericMessage::RemoteDigestRequest(request) => self.on_remote_digest_request(io, who, request),
+			GenericMessage::RemoteDigestResponse(response) => self.on_remote_digest_response(io, who, response),
+			GenericMessage::RemoteReadPrefixRequest(request) => self.on_remote_read_prefix_request(io, who, request),
+			GenericMessage::RemoteReadPrefixResponse(response) => self.on_remote_read_prefix_response(io, who, response),
+			GenericMessage::RemoteBodyRequest(request) => self.on_remote_body_request(io, who, request),
+			GenericMessage::RemoteBodyResponse(response) => self.on_remote_body_response(io, who, response),
+			GenericMessage::RemoteReadChildRequest(request) => self.on_remote_read_child_request(io, who, request),
+			GenericMessage::RemoteReadMultiRequest(request) => self.on_remote_read_multi_request(io, who, request),
 			other => self.specialization.write().on_message(&mut ProtocolContext::new(&self.context_data, io), who, &mut Some(other)),
 		}
 	}
@@ -304,13 +444,20 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 			let mut peers = self.context_data.peers.write();
 			let mut handshaking_peers = self.handshaking_peers.write();
 			handshaking_peers.remove(&peer);
-			peers.remove(&peer).is_some()
+			self.light_serving.write().remove(&peer);
+			self.storage_subscriptions.write().remove(&peer);
+			let removed = peers.remove(&peer).is_some();
+			if let Some(ref head_check) = self.head_check {
+				head_check.write().on_disconnect(peer, peers.keys().cloned());
+			}
+			removed
 		};
 		if removed {
 			let mut context = ProtocolContext::new(&self.context_data, io);
 			sync.peer_disconnected(&mut context, peer);
 			spec.on_disconnect(&mut context, peer);
-			self.on_demand.as_ref().map(|s| s.on_disconnect(peer));
+			self.on_demand_of(peer).map(|s| s.on_disconnect(peer));
+			self.fallback_peers.write().remove(&peer);
 		}
 	}
 
@@ -381,6 +528,9 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 		};
 
 		if let Some((origin, new_blocks)) = new_blocks {
+			for block in &new_blocks {
+				self.context_data.chain.note_block_arrival(&block.block.hash);
+			}
 			let import_queue = self.sync.read().import_queue();
 			import_queue.import_blocks(origin, new_blocks);
 		}
@@ -391,7 +541,11 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 	/// Perform time based maintenance.
 	pub fn tick(&self, io: &mut SyncIo) {
 		self.maintain_peers(io);
-		self.on_demand.as_ref().map(|s| s.maintain_peers(io));
+		self.check_stall(io);
+		self.sync.write().maybe_checkpoint(&*self.context_data.chain);
+		for on_demand in self.on_demand_services() {
+			on_demand.maintain_peers(io);
+		}
 	}
 
 	fn maintain_peers(&self, io: &mut SyncIo) {
@@ -416,6 +570,35 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 		}
 	}
 
+	/// Check if the chain is stalled, disconnecting a portion of the peers if it is.
+	fn check_stall(&self, io: &mut SyncIo) {
+		let watchdog = match self.watchdog {
+			Some(ref watchdog) => watchdog,
+			None => return,
+		};
+		let best_number = match self.context_data.chain.info() {
+			Ok(info) => info.chain.best_number,
+			Err(_) => return,
+		};
+		let peers: Vec<_> = self.context_data.peers.read().iter()
+			.map(|(who, peer)| (*who, peer.best_number))
+			.collect();
+		let stall = match watchdog.write().check(best_number, peers.clone(), time::Instant::now()) {
+			Some(stall) => stall,
+			None => return,
+		};
+
+		let sync = self.sync.read().status();
+		warn!(target: "sync", "Best block #{} hasn't changed for {} secs despite {} peers (sync state: {:?}, best seen block: {:?}). Rotating {} peers",
+			stall.best_number, stall.duration.as_secs(), peers.len(), sync.state, sync.best_seen_block, stall.rotated.len());
+		for (who, number) in peers {
+			debug!(target: "sync", "Peer {} at #{}: {}", who, number, io.peer_debug_info(who));
+		}
+		for who in stall.rotated {
+			io.rotate_peer(who);
+		}
+	}
+
 	#[allow(dead_code)]
 	pub fn peer_info(&self, peer: NodeIndex) -> Option<PeerInfo<B>> {
 		self.context_data.peers.read().get(&peer).map(|p| {
@@ -440,10 +623,18 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 				return;
 			}
 			if status.genesis_hash != self.genesis_hash {
+				self.chain_mismatches.fetch_add(1, Ordering::Relaxed);
 				io.report_peer(who, Severity::Bad(&format!("Peer is on different chain (our genesis: {} theirs: {})", self.genesis_hash, status.genesis_hash)));
 				return;
 			}
-			if status.version != CURRENT_VERSION {
+			if status.fork_id != self.config.fork_id {
+				self.chain_mismatches.fetch_add(1, Ordering::Relaxed);
+				let fork_id = |fork_id: &Option<Vec<u8>>| fork_id.as_ref().map(|id| id.to_hex()).unwrap_or_else(|| "none".into());
+				io.report_peer(who, Severity::Bad(&format!("Peer is on different fork (our fork id: {} theirs: {})",
+					fork_id(&self.config.fork_id), fork_id(&status.fork_id))));
+				return;
+			}
+			if status.version < MIN_VERSION {
 				io.report_peer(who, Severity::Bad(&format!("Peer using unsupported protocol version {}", status.version)));
 				return;
 			}
@@ -468,22 +659,38 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 				known_extrinsics: HashSet::new(),
 				known_blocks: HashSet::new(),
 				next_request_id: 0,
+				time_offset: status.timestamp.and_then(|timestamp|
+					unix_time_millis().map(|now| timestamp as i64 - now as i64)),
 			};
 			peers.insert(who.clone(), peer);
 			handshaking_peers.remove(&who);
+			if let Some(ref head_check) = self.head_check {
+				head_check.write().on_connect(who);
+			}
 			debug!(target: "sync", "Connected {} {}", who, io.peer_debug_info(who));
 		}
 
+		let peer_id = io.peer_id(who);
+		let serves_fallback = self.fallback_on_demand.is_some()
+			&& peer_id.as_ref().map_or(false, |peer_id| self.config.fallback_on_demand_peers.contains(peer_id));
+		let serves_on_demand = self.config.on_demand_peers.is_empty()
+			|| peer_id.as_ref().map_or(false, |peer_id| self.config.on_demand_peers.contains(peer_id));
+		if serves_fallback {
+			self.fallback_peers.write().insert(who);
+			self.fallback_on_demand.as_ref().map(|s| s.on_connect(who, status.roles, status.best_number));
+		} else if serves_on_demand {
+			self.on_demand.as_ref().map(|s| s.on_connect(who, status.roles, status.best_number));
+		}
+
 		let mut context = ProtocolContext::new(&self.context_data, io);
-		self.on_demand.as_ref().map(|s| s.on_connect(who, status.roles, status.best_number));
 		self.sync.write().new_peer(&mut context, who);
 		self.specialization.write().on_connect(&mut context, who, status);
 	}
 
 	/// Called when peer sends us new extrinsics
 	fn on_extrinsics(&self, _io: &mut SyncIo, who: NodeIndex, extrinsics: message::Transactions<B::Extrinsic>) {
-		// Accept extrinsics only when fully synced
-		if self.sync.read().status().state != SyncState::Idle {
+		// Accept extrinsics only when fully synced, they can't be validated against the ancient state
+		if self.sync.read().status().is_major_syncing() {
 			trace!(target: "sync", "{} Ignoring extrinsics while syncing", who);
 			return;
 		}
@@ -504,12 +711,27 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 	pub fn propagate_extrinsics(&self, io: &mut SyncIo) {
 		debug!(target: "sync", "Propagating extrinsics");
 
-		// Accept transactions only when fully synced
-		if self.sync.read().status().state != SyncState::Idle {
+		// Propagate transactions only when fully synced
+		if self.sync.read().status().is_major_syncing() {
 			return;
 		}
 
-		let extrinsics = self.transaction_pool.transactions();
+		let best_number: u64 = match self.context_data.chain.info() {
+			Ok(info) => info.chain.best_number.as_(),
+			Err(e) => {
+				debug!(target: "sync", "Error reading blockchain: {:?}", e);
+				return;
+			}
+		};
+
+		// Transactions that are not due are not sent to the newly connected peers until they're
+		// rebroadcast.
+		let mut extrinsics = self.transaction_pool.transactions();
+		let due = self.rebroadcast.write().due(best_number, extrinsics.iter().map(|&(ref hash, _)| hash));
+		extrinsics.retain(|&(ref hash, _)| due.contains(hash));
+		if extrinsics.is_empty() {
+			return;
+		}
 
 		let mut propagated_to = HashMap::new();
 		let mut peers = self.context_data.peers.write();
@@ -544,6 +766,8 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 				best_number: info.chain.best_number,
 				best_hash: info.chain.best_hash,
 				chain_status: self.specialization.read().status(),
+				timestamp: unix_time_millis(),
+				fork_id: self.config.fork_id.clone(),
 			};
 			self.send_message(io, who, GenericMessage::Status(status))
 		}
@@ -572,16 +796,30 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 	pub fn on_block_announce(&self, io: &mut SyncIo, who: NodeIndex, announce: message::BlockAnnounce<B::Header>) {
 		let header = announce.header;
 		let hash = header.hash();
+		if let Some(ref head_check) = self.head_check {
+			let chain = &self.context_data.chain;
+			let finalized = chain.info().map(|info| info.chain.finalized_number).unwrap_or_else(|_| Zero::zero());
+			let canonical = |number| chain.block_hash(number).ok().and_then(|hash| hash);
+			if !head_check.write().on_announce(who, *header.number(), hash, finalized, canonical) {
+				io.report_peer(who, Severity::Bad("Announced head conflicts with the finalized chain"));
+				return;
+			}
+		}
 		{
 			let mut peers = self.context_data.peers.write();
 			if let Some(ref mut peer) = peers.get_mut(&who) {
 				peer.known_blocks.insert(hash.clone());
 			}
 		}
-		self.on_demand.as_ref().map(|s| s.on_block_announce(who, *header.number()));
+		self.context_data.chain.note_block_arrival(&hash);
+		self.on_demand_of(who).map(|s| s.on_block_announce(who, *header.number()));
 		self.sync.write().on_block_announce(&mut ProtocolContext::new(&self.context_data, io), who, hash, &header);
 	}
 
+	pub fn request_justification(&self, io: &mut SyncIo, hash: B::Hash, number: NumberFor<B>) {
+		self.sync.write().request_justification(&mut ProtocolContext::new(&self.context_data, io), hash, number);
+	}
+
 	pub fn on_block_imported(&self, io: &mut SyncIo, hash: B::Hash, header: &B::Header) {
 		self.sync.write().update_chain_info(&header);
 		self.specialization.write().on_block_imported(
@@ -592,9 +830,15 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 
 		// blocks are not announced by light clients
 		if self.config.roles & Roles::LIGHT == Roles::LIGHT {
+			for on_demand in self.on_demand_services() {
+				on_demand.on_block_imported(io, header);
+			}
+			self.prune_announced_heads();
 			return;
 		}
 
+		self.push_storage_changes(io, &hash);
+
 		// send out block announcements
 		let mut peers = self.context_data.peers.write();
 
@@ -610,29 +854,32 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 
 	fn on_remote_call_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteCallRequest<B::Hash>) {
 		trace!(target: "sync", "Remote call request {} from {} ({} at {})", request.id, who, request.method, request.block);
-		let proof = match self.context_data.chain.execution_proof(&request.block, &request.method, &request.data) {
-			Ok((_, proof)) => proof,
+		let started = time::Instant::now();
+		let (proof, accessed_keys) = match self.context_data.chain.execution_proof(&request.block, &request.method, &request.data) {
+			Ok((_, proof, keys)) => (proof, if request.trace_keys { keys } else { Vec::new() }),
 			Err(error) => {
 				trace!(target: "sync", "Remote call request {} from {} ({} at {}) failed with: {}",
 					request.id, who, request.method, request.block, error);
 				Default::default()
 			},
 		};
+		self.note_light_serving(who, started, &proof);
 
 		self.send_message(io, who, GenericMessage::RemoteCallResponse(message::RemoteCallResponse {
-			id: request.id, proof,
+			id: request.id, proof, accessed_keys,
 		}));
 	}
 
 	fn on_remote_call_response(&self, io: &mut SyncIo, who: NodeIndex, response: message::RemoteCallResponse) {
 		trace!(target: "sync", "Remote call response {} from {}", response.id, who);
-		self.on_demand.as_ref().map(|s| s.on_remote_call_response(io, who, response));
+		self.on_demand_of(who).map(|s| s.on_remote_call_response(io, who, response));
 	}
 
 	fn on_remote_read_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteReadRequest<B::Hash>) {
 		trace!(target: "sync", "Remote read request {} from {} ({} at {})",
 			request.id, who, request.key.to_hex(), request.block);
-		let proof = match self.context_data.chain.read_proof(&request.block, &request.key) {
+		let started = time::Instant::now();
+		let proof = match self.context_data.chain.read_proof(&request.block, &[request.key.clone()]) {
 			Ok(proof) => proof,
 			Err(error) => {
 				trace!(target: "sync", "Remote read request {} from {} ({} at {}) failed with: {}",
@@ -640,18 +887,72 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 				Default::default()
 			},
 		};
+		self.note_light_serving(who, started, &proof);
+		self.send_message(io, who, GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
+			id: request.id, proof,
+		}));
+	}
+
+	fn on_remote_read_multi_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteReadMultiRequest<B::Hash>) {
+		trace!(target: "sync", "Remote read multi request {} from {} ({} keys at {})",
+			request.id, who, request.keys.len(), request.block);
+		if request.keys.len() > MAX_READ_KEYS {
+			io.report_peer(who, Severity::Useless("Peer requested too many storage keys"));
+			return;
+		}
+
+		let started = time::Instant::now();
+		let proof = match self.context_data.chain.read_proof(&request.block, &request.keys) {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "Remote read multi request {} from {} ({} keys at {}) failed with: {}",
+					request.id, who, request.keys.len(), request.block, error);
+				Default::default()
+			},
+		};
+		self.note_light_serving(who, started, &proof);
 		self.send_message(io, who, GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
 			id: request.id, proof,
 		}));
 	}
+	fn on_remote_read_child_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteReadChildRequest<B::Hash>) {
+		trace!(target: "sync", "Remote read child request {} from {} ({} {} at {})",
+			request.id, who, request.storage_key.to_hex(), request.key.to_hex(), request.block);
+		let started = time::Instant::now();
+		let proof = match self.context_data.chain.read_child_proof(&request.block, &request.storage_key, &request.key) {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "Remote read child request {} from {} ({} {} at {}) failed with: {}",
+					request.id, who, request.storage_key.to_hex(), request.key.to_hex(), request.block, error);
+				Default::default()
+			},
+		};
+		self.note_light_serving(who, started, &proof);
+		self.send_message(io, who, GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
+			id: request.id, proof,
+		}));
+	}
+
 	fn on_remote_read_response(&self, io: &mut SyncIo, who: NodeIndex, response: message::RemoteReadResponse) {
 		trace!(target: "sync", "Remote read response {} from {}", response.id, who);
-		self.on_demand.as_ref().map(|s| s.on_remote_read_response(io, who, response));
+		self.on_demand_of(who).map(|s| s.on_remote_read_response(io, who, response));
 	}
 
 	fn on_remote_header_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteHeaderRequest<NumberFor<B>>) {
 		trace!(target: "sync", "Remote header proof request {} from {} ({})",
 			request.id, who, request.block);
+		// light clients only answer header requests when they have opted into serving them
+		let is_light = self.config.roles & Roles::LIGHT == Roles::LIGHT;
+		if is_light && self.config.roles & Roles::LIGHT_SERVER != Roles::LIGHT_SERVER {
+			trace!(target: "sync", "Ignoring remote header proof request {} from {}: not serving light clients",
+				request.id, who);
+			self.send_message(io, who, GenericMessage::RemoteHeaderResponse(message::RemoteHeaderResponse {
+				id: request.id, header: None, proof: Default::default(),
+			}));
+			return;
+		}
+
+		let started = time::Instant::now();
 		let (header, proof) = match self.context_data.chain.header_proof(request.block) {
 			Ok((header, proof)) => (Some(header), proof),
 			Err(error) => {
@@ -660,6 +961,7 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 				(Default::default(), Default::default())
 			},
 		};
+		self.note_light_serving(who, started, &proof);
  		self.send_message(io, who, GenericMessage::RemoteHeaderResponse(message::RemoteHeaderResponse {
 			id: request.id, header, proof,
 		}));
@@ -667,12 +969,13 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 
  	fn on_remote_header_response(&self, io: &mut SyncIo, who: NodeIndex, response: message::RemoteHeaderResponse<B::Header>) {
 		trace!(target: "sync", "Remote header proof response {} from {}", response.id, who);
-		self.on_demand.as_ref().map(|s| s.on_remote_header_response(io, who, response));
+		self.on_demand_of(who).map(|s| s.on_remote_header_response(io, who, response));
 	}
 
 	fn on_remote_changes_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteChangesRequest<B::Hash>) {
 		trace!(target: "sync", "Remote changes proof request {} from {} for key {} ({}..{})",
 			request.id, who, request.key.to_hex(), request.first, request.last);
+		let started = time::Instant::now();
 		let (max, proof) = match self.context_data.chain.key_changes_proof(request.first, request.last, request.max, &request.key) {
 			Ok((max, proof)) => (max, proof),
 			Err(error) => {
@@ -681,6 +984,7 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
 				(Zero::zero(), Default::default())
 			},
 		};
+		self.note_light_serving(who, started, &proof);
  		self.send_message(io, who, GenericMessage::RemoteChangesResponse(message::RemoteChangesResponse {
 			id: request.id, max, proof,
 		}));
@@ -689,9 +993,237 @@ impl<B: BlockT, S: Specialization<B>, H: ExHashT> Protocol<B, S, H> {
  	fn on_remote_changes_response(&self, io: &mut SyncIo, who: NodeIndex, response: message::RemoteChangesResponse<NumberFor<B>>) {
 		trace!(target: "sync", "Remote changes proof response {} from {} (max={})",
 			response.id, who, response.max);
-		self.on_demand.as_ref().map(|s| s.on_remote_changes_response(io, who, response));
+		self.on_demand_of(who).map(|s| s.on_remote_changes_response(io, who, response));
+	}
+
+	fn on_remote_child_roots_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteChildRootsRequest<B::Hash>) {
+		trace!(target: "sync", "Remote child roots request {} from {} (at {})", request.id, who, request.block);
+		let started = time::Instant::now();
+		let proof = match self.context_data.chain.child_roots_proof(&request.block) {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "Remote child roots request {} from {} (at {}) failed with: {}",
+					request.id, who, request.block, error);
+				Default::default()
+			},
+		};
+		self.note_light_serving(who, started, &proof);
+		self.send_message(io, who, GenericMessage::RemoteChildRootsResponse(message::RemoteChildRootsResponse {
+			id: request.id, proof,
+		}));
 	}
 
+	fn on_remote_child_roots_response(&self, io: &mut SyncIo, who: NodeIndex, response: message::RemoteChildRootsResponse) {
+		trace!(target: "sync", "Remote child roots response {} from {}", response.id, who);
+		self.on_demand_of(who).map(|s| s.on_remote_child_roots_response(io, who, response));
+	}
+
+	fn on_remote_digest_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteDigestRequest<NumberFor<B>>) {
+		trace!(target: "sync", "Remote digest proof request {} from {} ({}..{})",
+			request.id, who, request.first, request.last);
+		let started = time::Instant::now();
+		let (headers, proof) = match self.context_data.chain.digest_proof(request.first, request.last) {
+			Ok((headers, proof)) => (headers, proof),
+			Err(error) => {
+				trace!(target: "sync", "Remote digest proof request {} from {} ({}..{}) failed with: {}",
+					request.id, who, request.first, request.last, error);
+				(Default::default(), Default::default())
+			},
+		};
+		self.note_light_serving(who, started, &proof);
+		self.send_message(io, who, GenericMessage::RemoteDigestResponse(message::RemoteDigestResponse {
+			id: request.id, headers, proof,
+		}));
+	}
+
+	fn on_remote_digest_response(&self, io: &mut SyncIo, who: NodeIndex, response: message::RemoteDigestResponse<B::Header>) {
+		trace!(target: "sync", "Remote digest proof response {} from {} ({} headers)",
+			response.id, who, response.headers.len());
+		self.on_demand_of(who).map(|s| s.on_remote_digest_response(io, who, response));
+	}
+
+	fn on_remote_read_prefix_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteReadPrefixRequest<B::Hash>) {
+		trace!(target: "sync", "Remote prefix read request {} from {} ({} from {} at {})",
+			request.id, who, request.prefix.to_hex(), request.start_key.to_hex(), request.block);
+		let started = time::Instant::now();
+		// the light client accepts shorter pages, so the page size is limited here
+		let limit = ::std::cmp::min(request.limit, MAX_READ_PREFIX_PAGE_SIZE) as usize;
+		let proof = match self.context_data.chain.read_prefix_proof(&request.block, &request.prefix, &request.start_key, limit) {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "Remote prefix read request {} from {} ({} from {} at {}) failed with: {}",
+					request.id, who, request.prefix.to_hex(), request.start_key.to_hex(), request.block, error);
+				Default::default()
+			},
+		};
+		self.note_light_serving(who, started, &proof);
+		self.send_message(io, who, GenericMessage::RemoteReadPrefixResponse(message::RemoteReadPrefixResponse {
+			id: request.id, proof,
+		}));
+	}
+
+	fn on_remote_read_prefix_response(&self, io: &mut SyncIo, who: NodeIndex, response: message::RemoteReadPrefixResponse) {
+		trace!(target: "sync", "Remote prefix read response {} from {}", response.id, who);
+		self.on_demand_of(who).map(|s| s.on_remote_read_prefix_response(io, who, response));
+	}
+
+	fn on_remote_body_request(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteBodyRequest<B::Hash>) {
+		trace!(target: "sync", "Remote body request {} from {} ({})", request.id, who, request.block);
+		let started = time::Instant::now();
+		let body = match self.context_data.chain.body(&BlockId::Hash(request.block)) {
+			Ok(body) => body,
+			Err(error) => {
+				trace!(target: "sync", "Remote body request {} from {} ({}) failed with: {}",
+					request.id, who, request.block, error);
+				None
+			},
+		};
+		let encoded_body = body.as_ref().map(|body| vec![body.encode()]).unwrap_or_default();
+		self.note_light_serving(who, started, &encoded_body);
+		self.send_message(io, who, GenericMessage::RemoteBodyResponse(message::RemoteBodyResponse {
+			id: request.id, body,
+		}));
+	}
+
+	fn on_remote_body_response(&self, io: &mut SyncIo, who: NodeIndex, response: message::RemoteBodyResponse<B::Extrinsic>) {
+		trace!(target: "sync", "Remote body response {} from {}", response.id, who);
+		self.on_demand_of(who).map(|s| s.on_remote_body_response(io, who, response));
+	}
+
+
+	fn on_remote_storage_subscribe(&self, io: &mut SyncIo, who: NodeIndex, request: message::RemoteStorageSubscribe) {
+		trace!(target: "sync", "Remote storage subscription {} from {} ({} keys)", request.id, who, request.keys.len());
+		if self.config.roles & Roles::LIGHT == Roles::LIGHT {
+			return;
+		}
+		if request.keys.len() > MAX_SUBSCRIBED_STORAGE_KEYS {
+			io.report_peer(who, Severity::Useless("Peer subscribed to too many storage keys"));
+			return;
+		}
+
+		let best_hash = match self.context_data.chain.info() {
+			Ok(info) => info.chain.best_hash,
+			Err(error) => {
+				trace!(target: "sync", "Remote storage subscription {} from {} failed with: {}", request.id, who, error);
+				return;
+			},
+		};
+
+		// current values of all keys are pushed right away
+		let started = time::Instant::now();
+		let mut subscription = StorageSubscription {
+			id: request.id,
+			values: request.keys.into_iter().map(|key| (key, None)).collect(),
+		};
+		if let Some(changes) = self.storage_changes(&best_hash, &mut subscription, true) {
+			self.note_light_serving(who, started, &changes.proof);
+			self.send_message(io, who, GenericMessage::RemoteStorageChanges(changes));
+		}
+		self.storage_subscriptions.write().insert(who, subscription);
+	}
+
+	fn on_remote_storage_unsubscribe(&self, who: NodeIndex, request: message::RemoteStorageUnsubscribe) {
+		trace!(target: "sync", "Remote storage subscription {} from {} cancelled", request.id, who);
+		let mut subscriptions = self.storage_subscriptions.write();
+		if subscriptions.get(&who).map(|subscription| subscription.id == request.id).unwrap_or(false) {
+			subscriptions.remove(&who);
+		}
+	}
+
+	fn on_remote_storage_changes(&self, io: &mut SyncIo, who: NodeIndex, changes: message::RemoteStorageChanges<B::Hash>) {
+		trace!(target: "sync", "Remote storage changes {} from {} at {} ({} keys)",
+			changes.id, who, changes.block, changes.changes.len());
+		let header = match self.context_data.chain.header(&BlockId::Hash(changes.block)) {
+			Ok(header) => header,
+			Err(_) => None,
+		};
+		self.on_demand_of(who).map(|s| s.on_remote_storage_changes(io, who, header, changes));
+	}
+
+	/// Push changes of subscribed storage keys at given block to the subscribers.
+	fn push_storage_changes(&self, io: &mut SyncIo, hash: &B::Hash) {
+		let changes: Vec<_> = self.storage_subscriptions.write().iter_mut()
+			.filter_map(|(who, subscription)| {
+				let started = time::Instant::now();
+				self.storage_changes(hash, subscription, false).map(|changes| (*who, started, changes))
+			})
+			.collect();
+		for (who, started, changes) in changes {
+			self.note_light_serving(who, started, &changes.proof);
+			self.send_message(io, who, GenericMessage::RemoteStorageChanges(changes));
+		}
+	}
+
+	/// Collect values of subscribed storage keys at given block that differ from the values pushed
+	/// before (or all values if `all` is true), together with their read proof.
+	fn storage_changes(
+		&self,
+		block: &B::Hash,
+		subscription: &mut StorageSubscription,
+		all: bool,
+	) -> Option<message::RemoteStorageChanges<B::Hash>> {
+		let mut changes = Vec::new();
+		for (key, value) in subscription.values.iter() {
+			match self.context_data.chain.storage(block, key) {
+				Ok(new_value) => if all || new_value != *value {
+					changes.push((key.clone(), new_value));
+				},
+				Err(error) => {
+					trace!(target: "sync", "Remote storage subscription {} failed at {} with: {}",
+						subscription.id, block, error);
+					return None;
+				},
+			}
+		}
+
+		if changes.is_empty() {
+			return None;
+		}
+
+		let changed_keys: Vec<_> = changes.iter().map(|&(ref key, _)| key.clone()).collect();
+		let proof = match self.context_data.chain.read_proof(block, &changed_keys) {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "Remote storage subscription {} failed at {} with: {}",
+					subscription.id, block, error);
+				return None;
+			},
+		};
+
+		for &(ref key, ref new_value) in &changes {
+			subscription.values.insert(key.clone(), new_value.clone());
+		}
+		Some(message::RemoteStorageChanges {
+			id: subscription.id,
+			block: block.clone(),
+			changes,
+			proof,
+		})
+	}
+
+	/// Forget the heads announced by the providers that are finalized by now.
+	fn prune_announced_heads(&self) {
+		let head_check = match self.head_check {
+			Some(ref head_check) => head_check,
+			None => return,
+		};
+		let chain = &self.context_data.chain;
+		let finalized = match chain.info() {
+			Ok(info) => info.chain.finalized_number,
+			Err(_) => return,
+		};
+		let canonical = |number| chain.block_hash(number).ok().and_then(|hash| hash);
+		head_check.write().on_finalized(finalized, canonical);
+	}
+
+	/// Account for a proof served to a light client peer.
+	fn note_light_serving(&self, who: NodeIndex, started: time::Instant, proof: &[Vec<u8>]) {
+		let mut light_serving = self.light_serving.write();
+		let stats = light_serving.entry(who).or_insert_with(Default::default);
+		stats.proofs += 1;
+		stats.bytes += proof.iter().map(|node| node.len() as u64).sum::<u64>();
+		stats.cpu_time += started.elapsed();
+	}
 
 	/// Execute a closure with access to a network context and specialization.
 	pub fn with_spec<F, U>(&self, io: &mut SyncIo, f: F) -> U
@@ -714,7 +1246,13 @@ fn send_message<B: BlockT, H: ExHashT>(peers: &RwLock<HashMap<NodeIndex, Peer<B,
 		},
 		_ => (),
 	}
-	io.send(who, message.encode());
+	io.send(who, message.encode_envelope());
+}
+
+/// Current unix time in milliseconds.
+fn unix_time_millis() -> Option<u64> {
+	time::SystemTime::now().duration_since(time::UNIX_EPOCH).ok()
+		.map(|d| d.as_secs() * 1_000 + d.subsec_millis() as u64)
 }
 
 /// Construct a simple protocol that is composed of several sub protocols.
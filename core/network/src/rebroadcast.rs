@@ -0,0 +1,132 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Scheduling of the pooled transactions propagation.
+//!
+//! A transaction is propagated to all connected peers as soon as it enters the pool. Peers that
+//! connect later only receive it when it's rebroadcast: after it has been waiting in the pool for
+//! `delay` blocks, then after `2 * delay`, `4 * delay` blocks and so on, at most `max_rebroadcasts`
+//! times. This way the new peers are not flooded with the whole pool on connection.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// Configuration of the transactions rebroadcasting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebroadcastConfig {
+	/// Number of blocks after which a transaction that hasn't been included is rebroadcast first.
+	pub delay: u64,
+	/// Maximal number of rebroadcasts of a single transaction.
+	pub max_rebroadcasts: u32,
+}
+
+impl Default for RebroadcastConfig {
+	fn default() -> Self {
+		RebroadcastConfig {
+			delay: 4,
+			max_rebroadcasts: 5,
+		}
+	}
+}
+
+/// Propagation state of a single transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Schedule {
+	/// Number of times the transaction has been broadcast, including the initial propagation.
+	broadcasts: u32,
+	/// Block number at which the transaction is broadcast next. `None` if it's never broadcast again.
+	next: Option<u64>,
+}
+
+/// Rebroadcast scheduler of the pooled transactions.
+pub struct RebroadcastScheduler<H: Hash + Eq> {
+	config: RebroadcastConfig,
+	transactions: HashMap<H, Schedule>,
+}
+
+impl<H: Hash + Eq + Clone> RebroadcastScheduler<H> {
+	/// Create new scheduler.
+	pub fn new(config: RebroadcastConfig) -> Self {
+		RebroadcastScheduler {
+			config,
+			transactions: HashMap::new(),
+		}
+	}
+
+	/// Select the transactions that are due for propagation at given best block. `pool` are the
+	/// hashes of all transactions that are currently in the pool, the transactions that have left
+	/// the pool are forgotten.
+	pub fn due<'a, I>(&mut self, best_number: u64, pool: I) -> HashSet<H> where
+		H: 'a,
+		I: IntoIterator<Item=&'a H>,
+	{
+		let pool: HashSet<_> = pool.into_iter().collect();
+		self.transactions.retain(|hash, _| pool.contains(hash));
+
+		let config = &self.config;
+		let mut due = HashSet::new();
+		for hash in pool {
+			let schedule = self.transactions.entry(hash.clone())
+				.or_insert_with(|| Schedule { broadcasts: 0, next: Some(best_number) });
+			if schedule.next.map_or(true, |next| next > best_number) {
+				continue;
+			}
+
+			// the initial propagation is followed by `max_rebroadcasts` rebroadcasts
+			let rebroadcasts = schedule.broadcasts;
+			schedule.broadcasts += 1;
+			schedule.next = if rebroadcasts < config.max_rebroadcasts {
+				let interval = config.delay.max(1).saturating_mul(1u64 << rebroadcasts.min(63));
+				Some(best_number.saturating_add(interval))
+			} else {
+				None
+			};
+			due.insert(hash.clone());
+		}
+		due
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn scheduler() -> RebroadcastScheduler<u32> {
+		RebroadcastScheduler::new(RebroadcastConfig { delay: 2, max_rebroadcasts: 2 })
+	}
+
+	#[test]
+	fn transactions_are_rebroadcast_with_exponential_intervals() {
+		let mut scheduler = scheduler();
+		let pool = vec![1];
+		let due_at: Vec<_> = (10..30)
+			.filter(|best_number| !scheduler.due(*best_number, &pool).is_empty())
+			.collect();
+		// initial propagation, then after 2 and 4 more blocks
+		assert_eq!(due_at, vec![10, 12, 16]);
+	}
+
+	#[test]
+	fn transactions_left_the_pool_are_forgotten() {
+		let mut scheduler = scheduler();
+		assert_eq!(scheduler.due(10, &[1, 2]), vec![1, 2].into_iter().collect());
+		assert_eq!(scheduler.due(11, &[2, 3]), vec![3].into_iter().collect());
+		assert_eq!(scheduler.transactions.len(), 2);
+
+		// the transaction that has re-entered the pool is propagated immediately
+		assert_eq!(scheduler.due(11, &[1, 2, 3]), vec![1].into_iter().collect());
+	}
+}
@@ -20,18 +20,18 @@ use std::{io, thread};
 use std::time::Duration;
 use futures::{self, Future, Stream, stream, sync::oneshot};
 use parking_lot::Mutex;
-use network_libp2p::{ProtocolId, PeerId, NetworkConfiguration, ErrorKind};
+use network_libp2p::{ProtocolId, PeerId, NodeIndex, NetworkConfiguration, ErrorKind};
 use network_libp2p::{start_service, Service as NetworkService, ServiceEvent as NetworkServiceEvent};
 use network_libp2p::{RegisteredProtocol, parse_str_addr, Protocol as Libp2pProtocol};
 use io::NetSyncIo;
-use protocol::{self, Protocol, ProtocolContext, Context, ProtocolStatus};
+use protocol::{self, Protocol, ProtocolContext, Context, PeerInfo, ProtocolStatus};
 use config::{ProtocolConfig};
 use error::Error;
 use chain::Client;
 use specialization::Specialization;
 use on_demand::OnDemandService;
 use import_queue::ImportQueue;
-use runtime_primitives::traits::{Block as BlockT};
+use runtime_primitives::traits::{Block as BlockT, NumberFor};
 use tokio::{runtime::Runtime, timer::Interval};
 
 /// Type that represents fetch completion future.
@@ -51,6 +51,8 @@ bitflags! {
 		const LIGHT = 0b00000010;
 		/// Act as an authority
 		const AUTHORITY = 0b00000100;
+		/// Light client node, serving headers and CHT proofs to other light clients.
+		const LIGHT_SERVER = 0b00001000;
 	}
 }
 
@@ -70,6 +72,8 @@ impl ::codec::Decode for Roles {
 pub trait SyncProvider<B: BlockT>: Send + Sync {
 	/// Get sync status
 	fn status(&self) -> ProtocolStatus<B>;
+	/// Get the connected peers that have completed the handshake, along with their peer ids.
+	fn peers(&self) -> Vec<(NodeIndex, Option<PeerId>, PeerInfo<B>)>;
 	/// Get this node id if available.
 	fn node_id(&self) -> Option<String>;
 }
@@ -103,6 +107,8 @@ pub struct Params<B: BlockT, S, H: ExHashT> {
 	pub chain: Arc<Client<B>>,
 	/// On-demand service reference.
 	pub on_demand: Option<Arc<OnDemandService<B>>>,
+	/// On-demand service of the fallback peers, see `ProtocolConfig::fallback_on_demand_peers`.
+	pub fallback_on_demand: Option<Arc<OnDemandService<B>>>,
 	/// Transaction pool.
 	pub transaction_pool: Arc<TransactionPool<H, B>>,
 	/// Protocol specialization.
@@ -132,14 +138,18 @@ impl<B: BlockT + 'static, S: Specialization<B>, H: ExHashT> Service<B, S, H> {
 	) -> Result<Arc<Service<B, S, H>>, Error> {
 		let chain = params.chain.clone();
 		let import_queue = Arc::new(import_queue);
-		let handler = Arc::new(Protocol::new(
+		let mut protocol = Protocol::new(
 			params.config,
 			params.chain,
 			import_queue.clone(),
 			params.on_demand,
 			params.transaction_pool,
 			params.specialization,
-		)?);
+		)?;
+		if let Some(fallback_on_demand) = params.fallback_on_demand {
+			protocol = protocol.with_fallback_on_demand(fallback_on_demand);
+		}
+		let handler = Arc::new(protocol);
 		let versions = [(protocol::CURRENT_VERSION as u8)];
 		let registered = RegisteredProtocol::new(protocol_id, &versions[..]);
 		let (thread, network) = start_thread(params.network_config, handler.clone(), registered)?;
@@ -165,6 +175,12 @@ impl<B: BlockT + 'static, S: Specialization<B>, H: ExHashT> Service<B, S, H> {
 		self.handler.on_block_imported(&mut NetSyncIo::new(&self.network, self.protocol_id), hash, header)
 	}
 
+	/// Request the justification of the imported block from the connected peers. The justification
+	/// is imported by the client once it is received.
+	pub fn request_justification(&self, hash: B::Hash, number: NumberFor<B>) {
+		self.handler.request_justification(&mut NetSyncIo::new(&self.network, self.protocol_id), hash, number)
+	}
+
 	/// Called when new transactons are imported by the client.
 	pub fn trigger_repropagate(&self) {
 		self.handler.propagate_extrinsics(&mut NetSyncIo::new(&self.network, self.protocol_id));
@@ -184,13 +200,19 @@ impl<B: BlockT + 'static, S: Specialization<B>, H: ExHashT> ::consensus::SyncOra
 	}
 }
 
+impl<B: BlockT + 'static, S: Specialization<B>, H: ExHashT> ::consensus::TimeOracle for Service<B, S, H> {
+	fn median_time_offset(&self) -> Option<i64> {
+		self.handler.median_time_offset()
+	}
+}
+
 impl<B: BlockT + 'static, S: Specialization<B>, H:ExHashT> Drop for Service<B, S, H> {
 	fn drop(&mut self) {
 		self.handler.stop();
 		if let Some((sender, join)) = self.bg_thread.take() {
 			let _ = sender.send(());
 			if let Err(e) = join.join() {
-				error!("Error while waiting on background thread: {:?}", e);
+				error!(target: "sync", "Error while waiting on background thread: {:?}", e);
 			}
 		}
 	}
@@ -208,6 +230,18 @@ impl<B: BlockT + 'static, S: Specialization<B>, H: ExHashT> SyncProvider<B> for
 		self.handler.status()
 	}
 
+	fn peers(&self) -> Vec<(NodeIndex, Option<PeerId>, PeerInfo<B>)> {
+		let connected: Vec<_> = {
+			let network = self.network.lock();
+			network.connected_peers()
+				.map(|node_index| (node_index, network.peer_id_of_node(node_index).cloned()))
+				.collect()
+		};
+		connected.into_iter()
+			.filter_map(|(node_index, peer_id)| self.handler.peer_info(node_index).map(|info| (node_index, peer_id, info)))
+			.collect()
+	}
+
 	fn node_id(&self) -> Option<String> {
 		let network = self.network.lock();
 		let ret = network
@@ -280,8 +314,8 @@ fn start_thread<B: BlockT + 'static, S: Specialization<B>, H: ExHashT>(
 		Err(err) => {
 			match err.kind() {
 				ErrorKind::Io(ref e) if e.kind() == io::ErrorKind::AddrInUse =>
-					warn!("Network port is already in use, make sure that another instance of Substrate client is not running or change the port using the --port option."),
-				_ => warn!("Error starting network: {}", err),
+					warn!(target: "sync", "Network port is already in use, make sure that another instance of Substrate client is not running or change the port using the --port option."),
+				_ => warn!(target: "sync", "Error starting network: {}", err),
 			};
 			return Err(err.into())
 		},
@@ -326,7 +360,7 @@ fn run_thread<B: BlockT + 'static, S: Specialization<B>, H: ExHashT>(
 		.then(|res| {
 			match res {
 				Ok(()) => (),
-				Err(err) => error!("Error in the propagation timer: {:?}", err),
+				Err(err) => error!(target: "sync", "Error in the propagation timer: {:?}", err),
 			};
 			Ok(())
 		});
@@ -344,7 +378,7 @@ fn run_thread<B: BlockT + 'static, S: Specialization<B>, H: ExHashT>(
 		.then(|res| {
 			match res {
 				Ok(()) => (),
-				Err(err) => error!("Error in the propagation timer: {:?}", err),
+				Err(err) => error!(target: "sync", "Error in the propagation timer: {:?}", err),
 			};
 			Ok(())
 		});
@@ -391,7 +425,7 @@ fn run_thread<B: BlockT + 'static, S: Specialization<B>, H: ExHashT>(
 
 	futures::select_all(futures)
 		.and_then(move |_| {
-			debug!("Networking ended");
+			debug!(target: "sync", "Networking ended");
 			Ok(())
 		})
 		.map_err(|(r, _, _)| r)
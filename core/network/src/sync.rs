@@ -14,8 +14,9 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use std::time::Instant;
 use protocol::Context;
 use network_libp2p::{Severity, NodeIndex};
 use client::{BlockStatus, ClientInfo};
@@ -27,11 +28,18 @@ use runtime_primitives::generic::BlockId;
 use message::{self, generic::Message as GenericMessage};
 use service::Roles;
 use import_queue::ImportQueue;
+use codec::{Decode, Encode};
 
 // Maximum blocks to request in a single packet.
 const MAX_BLOCKS_TO_REQUEST: usize = 128;
 // Maximum blocks to store in the import queue.
 const MAX_IMPORTING_BLOCKS: usize = 2048;
+// Interval between persisting sync checkpoints.
+const CHECKPOINT_INTERVAL_SEC: u64 = 30;
+// Aux storage key of the sync checkpoint.
+const SYNC_CHECKPOINT_KEY: &[u8] = b"sync_checkpoint";
+// Maximum number of invalid blocks to remember.
+const MAX_INVALID_BLOCKS: usize = 1024;
 
 struct PeerSync<B: BlockT> {
 	pub common_hash: B::Hash,
@@ -47,6 +55,28 @@ enum PeerSyncState<B: BlockT> {
 	Available,
 	DownloadingNew(NumberFor<B>),
 	DownloadingStale(B::Hash),
+	DownloadingGap(NumberFor<B>),
+	DownloadingJustification(B::Hash),
+}
+
+/// Sync state of a single peer, as persisted in the checkpoint.
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+struct PeerCheckpoint<Hash, Number> {
+	best_hash: Hash,
+	best_number: Number,
+	common_hash: Hash,
+	common_number: Number,
+}
+
+/// Sync state, periodically persisted during major sync so it can be resumed after restart.
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+struct SyncCheckpoint<Hash, Number> {
+	/// Best block seen on the network.
+	best_seen: Number,
+	/// Best and common blocks of connected peers. Downloaded blocks that have not been imported are
+	/// lost on restart, so the pending block ranges aren't kept: they are requested again from the
+	/// best imported block.
+	peers: Vec<PeerCheckpoint<Hash, Number>>,
 }
 
 /// Relay chain sync strategy.
@@ -58,6 +88,20 @@ pub struct ChainSync<B: BlockT> {
 	best_queued_hash: B::Hash,
 	required_block_attributes: message::BlockAttributes,
 	import_queue: Arc<ImportQueue<B>>,
+	restored: Option<SyncCheckpoint<B::Hash, NumberFor<B>>>,
+	last_checkpoint: Instant,
+	has_checkpoint: bool,
+	/// Block gap of the client that is being backfilled.
+	gap: Option<(NumberFor<B>, NumberFor<B>)>,
+	/// Lowest block of the gap that has been requested.
+	gap_requested_to: Option<NumberFor<B>>,
+	/// Blocks that have failed to import, along with their known descendants.
+	invalid_blocks: HashSet<B::Hash>,
+	/// Invalid blocks in the order they have been noted, oldest first.
+	invalid_blocks_order: VecDeque<B::Hash>,
+	/// Blocks whose justifications are requested from the network, along with the peers that
+	/// have been asked for them without success.
+	pending_justifications: HashMap<B::Hash, (NumberFor<B>, HashSet<NodeIndex>)>,
 }
 
 /// Reported sync state.
@@ -105,6 +149,14 @@ impl<B: BlockT> ChainSync<B> {
 			best_queued_number: info.best_queued_number.unwrap_or(info.chain.best_number),
 			required_block_attributes,
 			import_queue,
+			restored: None,
+			last_checkpoint: Instant::now(),
+			has_checkpoint: false,
+			gap: None,
+			gap_requested_to: None,
+			invalid_blocks: HashSet::new(),
+			invalid_blocks_order: VecDeque::new(),
+			pending_justifications: HashMap::new(),
 		}
 	}
 
@@ -112,6 +164,92 @@ impl<B: BlockT> ChainSync<B> {
 		self.peers.values().max_by_key(|p| p.best_number).map(|p| p.best_number)
 	}
 
+	/// Restore sync state from the checkpoint persisted before restart, if sync has not been completed then.
+	pub(crate) fn restore_checkpoint(&mut self, client: &::chain::Client<B>) {
+		let checkpoint = match client.get_aux(SYNC_CHECKPOINT_KEY) {
+			Ok(Some(encoded)) => match SyncCheckpoint::decode(&mut &encoded[..]) {
+				Some(checkpoint) => checkpoint,
+				None => {
+					debug!(target: "sync", "Ignoring invalid sync checkpoint");
+					return;
+				},
+			},
+			Ok(None) => return,
+			Err(e) => {
+				debug!(target: "sync", "Error reading sync checkpoint: {:?}", e);
+				return;
+			},
+		};
+
+		self.has_checkpoint = true;
+		if checkpoint.best_seen <= self.best_queued_number {
+			return;
+		}
+
+		debug!(target: "sync", "Resuming sync to #{} with {} known peer forks",
+			checkpoint.best_seen, checkpoint.peers.len());
+		self.restored = Some(checkpoint);
+	}
+
+	/// Persist sync state if the checkpoint interval has passed. The checkpoint is kept only while major syncing.
+	pub(crate) fn maybe_checkpoint(&mut self, client: &::chain::Client<B>) {
+		if self.last_checkpoint.elapsed().as_secs() < CHECKPOINT_INTERVAL_SEC {
+			return;
+		}
+		self.last_checkpoint = Instant::now();
+		self.checkpoint(client);
+	}
+
+	/// Persist sync state.
+	pub(crate) fn checkpoint(&mut self, client: &::chain::Client<B>) {
+		let result = match self.checkpoint_data() {
+			Some(checkpoint) => {
+				self.has_checkpoint = true;
+				client.insert_aux(&[(SYNC_CHECKPOINT_KEY, &checkpoint.encode()[..])], &[])
+			},
+			// keep the restored checkpoint until peers are connected again
+			None if self.has_checkpoint && self.restored.is_none() => {
+				self.has_checkpoint = false;
+				client.insert_aux(&[], &[SYNC_CHECKPOINT_KEY])
+			},
+			None => Ok(()),
+		};
+
+		if let Err(e) = result {
+			debug!(target: "sync", "Error writing sync checkpoint: {:?}", e);
+		}
+	}
+
+	fn checkpoint_data(&self) -> Option<SyncCheckpoint<B::Hash, NumberFor<B>>> {
+		if !self.status().is_major_syncing() {
+			return None;
+		}
+
+		Some(SyncCheckpoint {
+			best_seen: self.best_seen_block()?,
+			peers: self.peers.values().map(|peer| PeerCheckpoint {
+				best_hash: peer.best_hash,
+				best_number: peer.best_number,
+				common_hash: peer.common_hash,
+				common_number: peer.common_number,
+			}).collect(),
+		})
+	}
+
+	/// Common block with the peer fork known before restart, if it is still in our chain.
+	fn restored_common_block(&self, client: &::chain::Client<B>, best_hash: &B::Hash) -> Option<NumberFor<B>> {
+		let checkpoint = self.restored.as_ref()?;
+		let peer = checkpoint.peers.iter().find(|peer| peer.best_hash == *best_hash)?;
+		if peer.common_number > self.best_queued_number {
+			return None;
+		}
+
+		match client.block_hash(peer.common_number) {
+			Ok(Some(hash)) if hash == peer.common_hash => Some(peer.common_number),
+			_ => None,
+		}
+	}
+
 	/// Returns import queue reference.
 	pub(crate) fn import_queue(&self) -> Arc<ImportQueue<B>> {
 		self.import_queue.clone()
@@ -148,14 +286,16 @@ impl<B: BlockT> ChainSync<B> {
 					let our_best = self.best_queued_number;
 					if our_best > As::sa(0) {
 						debug!(target:"sync", "New peer with unknown best hash {} ({}), searching for common ancestor.", info.best_hash, info.best_number);
+						// resume from the common block of this fork if it is known from before restart
+						let search_from = self.restored_common_block(&*protocol.client(), &info.best_hash).unwrap_or(our_best);
 						self.peers.insert(who, PeerSync {
 							common_hash: self.genesis_hash,
 							common_number: As::sa(0),
 							best_hash: info.best_hash,
 							best_number: info.best_number,
-							state: PeerSyncState::AncestorSearch(our_best),
+							state: PeerSyncState::AncestorSearch(search_from),
 						});
-						Self::request_ancestry(protocol, who, our_best)
+						Self::request_ancestry(protocol, who, search_from)
 					} else {
 						// We are at genesis, just start downloading
 						debug!(target:"sync", "New peer with best hash {} ({}).", info.best_hash, info.best_number);
@@ -206,10 +346,60 @@ impl<B: BlockT> ChainSync<B> {
 						block: b
 					}).collect()
 				},
+				PeerSyncState::DownloadingGap(top) => {
+					peer.state = PeerSyncState::Available;
+					// blocks are imported top-down, each being the parent of the previous one
+					let is_valid_response = response.blocks.iter().enumerate().all(|(index, block)|
+						block.header.as_ref().map_or(false, |h| *h.number() + As::sa(index as u64) == top));
+					if !is_valid_response {
+						trace!(target: "sync", "Invalid response when backfilling block gap from {}", who);
+						protocol.report_peer(who, Severity::Bad("Invalid response when backfilling block gap"));
+						return None;
+					}
+
+					if let Some(lowest) = response.blocks.last().and_then(|b| b.header.as_ref()).map(|h| *h.number()) {
+						self.gap_requested_to = Some(lowest);
+					}
+
+					response.blocks.into_iter().map(|b| blocks::BlockData {
+						origin: Some(who),
+						block: b
+					}).collect()
+				},
+				PeerSyncState::DownloadingJustification(hash) => {
+					peer.state = PeerSyncState::Available;
+					let justification = response.blocks.into_iter().next()
+						.filter(|block| block.hash == hash)
+						.and_then(|block| block.justification)
+						.filter(|justification| !justification.is_empty());
+					match justification {
+						Some(justification) => match protocol.client().import_justification(hash, justification) {
+							Ok(()) => {
+								trace!(target: "sync", "Imported justification of {} from {}", hash, who);
+								self.pending_justifications.remove(&hash);
+							},
+							Err(e) => {
+								debug!(target: "sync", "Error importing justification of {} from {}: {:?}", hash, who, e);
+								protocol.report_peer(who, Severity::Bad("Sent us an invalid justification"));
+								return None;
+							},
+						},
+						None => {
+							trace!(target: "sync", "Peer {} has no justification of {}", who, hash);
+							if let Some(&mut (_, ref mut asked)) = self.pending_justifications.get_mut(&hash) {
+								asked.insert(who);
+							}
+						},
+					}
+					vec![]
+				},
 				PeerSyncState::AncestorSearch(n) => {
 					match response.blocks.get(0) {
 						Some(ref block) => {
 							trace!(target: "sync", "Got ancestry block #{} ({}) from peer {}", n, block.hash, who);
+							let finalized_number = protocol.client().info()
+								.map(|info| info.chain.finalized_number)
+								.unwrap_or_else(|_| As::sa(0));
 							match protocol.client().block_hash(n) {
 								Ok(Some(block_hash)) if block_hash == block.hash => {
 									if peer.common_number < n {
@@ -220,6 +410,13 @@ impl<B: BlockT> ChainSync<B> {
 									trace!(target:"sync", "Found common ancestor for peer {}: {} ({})", who, block.hash, n);
 									vec![]
 								},
+								Ok(_) if n > As::sa(0) && n <= finalized_number => {
+									// blocks below the finalized block are never imported (and the light client
+									// started from the trusted checkpoint doesn't even know them)
+									trace!(target:"sync", "Ancestry search: peer {} is on the fork below the finalized block #{}", who, finalized_number);
+									protocol.report_peer(who, Severity::Useless("Ancestry search: fork below the finalized block"));
+									return None;
+								},
 								Ok(our_best) if n > As::sa(0) => {
 									trace!(target:"sync", "Ancestry block mismatch for peer {}: theirs: {} ({}), ours: {:?}", who, block.hash, n, our_best);
 									let n = n - As::sa(1);
@@ -251,6 +448,7 @@ impl<B: BlockT> ChainSync<B> {
 			vec![]
 		};
 
+		let new_blocks = self.drop_invalid_descendants(new_blocks);
 		let best_seen = self.best_seen_block();
 		let is_best = new_blocks.first().and_then(|b| b.block.header.as_ref()).map(|h| best_seen.as_ref().map_or(false, |n| h.number() >= n));
 		let origin = if is_best.unwrap_or_default() { BlockOrigin::NetworkBroadcast } else { BlockOrigin::NetworkInitialSync };
@@ -270,6 +468,18 @@ impl<B: BlockT> ChainSync<B> {
 		let peers: Vec<NodeIndex> = self.peers.keys().map(|p| *p).collect();
 		for peer in peers {
 			self.download_new(protocol, peer);
+			self.download_gap(protocol, peer);
+			self.download_justification(protocol, peer);
+		}
+	}
+
+	/// Request the justification of the imported block from the network. The justification is
+	/// imported by the client once a peer provides it.
+	pub(crate) fn request_justification(&mut self, protocol: &mut Context<B>, hash: B::Hash, number: NumberFor<B>) {
+		if !self.pending_justifications.contains_key(&hash) {
+			self.pending_justifications.insert(hash, (number, HashSet::new()));
+			trace!(target: "sync", "Requesting justification of {} ({})", hash, number);
+			self.maintain_sync(protocol);
 		}
 	}
 
@@ -278,6 +488,13 @@ impl<B: BlockT> ChainSync<B> {
 			self.best_queued_number = number;
 			self.best_queued_hash = *hash;
 		}
+		if self.restored.as_ref().map_or(false, |checkpoint| checkpoint.best_seen <= number) {
+			self.restored = None;
+		}
+		// blocks of the gap are below the blocks that are common with peers
+		if self.gap.map_or(false, |(start, end)| number >= start && number <= end) {
+			return;
+		}
 		// Update common blocks
 		for (_, peer) in self.peers.iter_mut() {
 			trace!(target: "sync", "Updating peer info ours={}, theirs={}", number, peer.best_number);
@@ -288,6 +505,52 @@ impl<B: BlockT> ChainSync<B> {
 		}
 	}
 
+	/// Note that the block has failed to import. Neither the block nor its descendants are
+	/// requested or imported after that.
+	pub fn block_invalid(&mut self, hash: &B::Hash) {
+		trace!(target: "sync", "Block {} is invalid", hash);
+		self.note_invalid(*hash);
+	}
+
+	/// Whether the block has failed to import or is a descendant of such block.
+	pub(crate) fn is_known_invalid(&self, hash: &B::Hash) -> bool {
+		self.invalid_blocks.contains(hash)
+	}
+
+	fn note_invalid(&mut self, hash: B::Hash) {
+		if !self.invalid_blocks.insert(hash) {
+			return;
+		}
+
+		self.invalid_blocks_order.push_back(hash);
+		if self.invalid_blocks_order.len() > MAX_INVALID_BLOCKS {
+			if let Some(oldest) = self.invalid_blocks_order.pop_front() {
+				self.invalid_blocks.remove(&oldest);
+			}
+		}
+	}
+
+	// Drop downloaded blocks that are invalid or descend from the invalid blocks. Blocks are
+	// expected in ascending order, so that the whole invalid chain is dropped.
+	fn drop_invalid_descendants(&mut self, blocks: Vec<blocks::BlockData<B>>) -> Vec<blocks::BlockData<B>> {
+		if self.invalid_blocks.is_empty() {
+			return blocks;
+		}
+
+		let mut valid = Vec::with_capacity(blocks.len());
+		for block in blocks {
+			let hash = block.block.hash;
+			let parent_hash = block.block.header.as_ref().map(|header| *header.parent_hash());
+			if self.is_known_invalid(&hash) || parent_hash.map_or(false, |parent_hash| self.is_known_invalid(&parent_hash)) {
+				trace!(target: "sync", "Dropping block {} descending from an invalid block", hash);
+				self.note_invalid(hash);
+			} else {
+				valid.push(block);
+			}
+		}
+		valid
+	}
+
 	pub(crate) fn update_chain_info(&mut self, best_header: &B::Header) {
 		let hash = best_header.hash();
 		self.block_imported(&hash, best_header.number().clone())
@@ -295,6 +558,12 @@ impl<B: BlockT> ChainSync<B> {
 
 	pub(crate) fn on_block_announce(&mut self, protocol: &mut Context<B>, who: NodeIndex, hash: B::Hash, header: &B::Header) {
 		let number = *header.number();
+		if self.is_known_invalid(&hash) || self.is_known_invalid(header.parent_hash()) {
+			// the peer is on the invalid fork, its best block must not be requested
+			trace!(target: "sync", "Ignoring announce of invalid block from {}: {}", who, hash);
+			self.note_invalid(hash);
+			return;
+		}
 		if let Some(ref mut peer) = self.peers.get_mut(&who) {
 			if number > peer.best_number {
 				peer.best_number = number;
@@ -339,6 +608,7 @@ impl<B: BlockT> ChainSync<B> {
 	pub(crate) fn restart(&mut self, protocol: &mut Context<B>) {
 		self.import_queue.clear();
 		self.blocks.clear();
+		self.gap_requested_to = None;
 		let ids: Vec<NodeIndex> = self.peers.keys().map(|p| *p).collect();
 		for id in ids {
 			self.new_peer(protocol, id);
@@ -418,6 +688,86 @@ impl<B: BlockT> ChainSync<B> {
 		}
 	}
 
+	// Issue a request for a peer to download blocks of the block gap top-down, if the peer is not busy
+	// downloading new blocks. Only one request is issued at a time, so that blocks are imported in order.
+	fn download_gap(&mut self, protocol: &mut Context<B>, who: NodeIndex) {
+		if self.peers.values().any(|p| match p.state { PeerSyncState::DownloadingGap(_) => true, _ => false }) {
+			return;
+		}
+
+		let (gap_start, gap_end) = match protocol.client().block_gap() {
+			Ok(Some(gap)) => gap,
+			Ok(None) => {
+				self.gap = None;
+				self.gap_requested_to = None;
+				return;
+			},
+			Err(e) => {
+				debug!(target: "sync", "Error reading block gap: {:?}", e);
+				return;
+			},
+		};
+
+		// the gap only shrinks while it is backfilled
+		if self.gap.map_or(true, |(start, end)| start != gap_start || end < gap_end) {
+			self.gap = Some((gap_start, gap_end));
+			self.gap_requested_to = None;
+		}
+
+		let top = match self.gap_requested_to {
+			Some(requested_to) if requested_to <= gap_start => return,
+			Some(requested_to) => ::std::cmp::min(gap_end, requested_to - As::sa(1)),
+			None => gap_end,
+		};
+
+		if let Some(ref mut peer) = self.peers.get_mut(&who) {
+			// peer knows our chain above the gap, so it has the blocks of the gap
+			if peer.state != PeerSyncState::Available || peer.common_number <= gap_end {
+				return;
+			}
+
+			let count = ::std::cmp::min(MAX_BLOCKS_TO_REQUEST as u64, (top - gap_start).as_() + 1);
+			trace!(target: "sync", "Requesting gap blocks from {}, ({} down to {})", who, top, top - As::sa(count - 1));
+			let request = message::generic::BlockRequest {
+				id: 0,
+				fields: self.required_block_attributes.clone(),
+				from: message::FromBlock::Number(top),
+				to: None,
+				direction: message::Direction::Descending,
+				max: Some(count as u32),
+			};
+			peer.state = PeerSyncState::DownloadingGap(top);
+			protocol.send_message(who, GenericMessage::BlockRequest(request));
+		}
+	}
+
+	fn download_justification(&mut self, protocol: &mut Context<B>, who: NodeIndex) {
+		let peers = &self.peers;
+		let is_requested = |hash: &B::Hash| peers.values()
+			.any(|p| p.state == PeerSyncState::DownloadingJustification(*hash));
+		let hash = match self.peers.get(&who) {
+			Some(peer) if peer.state == PeerSyncState::Available => self.pending_justifications.iter()
+				.filter(|&(_, &(number, ref asked))| number <= peer.best_number && !asked.contains(&who))
+				.map(|(hash, _)| *hash)
+				.find(|hash| !is_requested(hash)),
+			_ => None,
+		};
+
+		if let (Some(hash), Some(peer)) = (hash, self.peers.get_mut(&who)) {
+			trace!(target: "sync", "Requesting justification of {} from {}", hash, who);
+			let request = message::generic::BlockRequest {
+				id: 0,
+				fields: message::BlockAttributes::HEADER | message::BlockAttributes::JUSTIFICATION,
+				from: message::FromBlock::Hash(hash),
+				to: None,
+				direction: message::Direction::Ascending,
+				max: Some(1),
+			};
+			peer.state = PeerSyncState::DownloadingJustification(hash);
+			protocol.send_message(who, GenericMessage::BlockRequest(request));
+		}
+	}
+
 	fn request_ancestry(protocol: &mut Context<B>, who: NodeIndex, block: NumberFor<B>) {
 		trace!(target: "sync", "Requesting ancestry block #{} from {}", block, who);
 		let request = message::generic::BlockRequest {
@@ -115,6 +115,10 @@ impl<'p> SyncIo for TestIo<'p> {
 		self.to_disconnect.insert(who);
 	}
 
+	fn rotate_peer(&mut self, who: NodeIndex) {
+		self.to_disconnect.insert(who);
+	}
+
 	fn send(&mut self, who: NodeIndex, data: Vec<u8>) {
 		self.packets.push(TestPacket {
 			data: data,
@@ -17,7 +17,9 @@
 use client::backend::Backend;
 use client::blockchain::HeaderBackend as BlockchainHeaderBackend;
 use consensus::BlockOrigin;
-use sync::SyncState;
+use import_queue::BlockRules;
+use message::{self, generic::Message as GenericMessage};
+use sync::{ChainSync, SyncState};
 use Roles;
 use super::*;
 
@@ -55,6 +57,46 @@ fn sync_long_chain_works() {
 	assert!(net.peer(0).client.backend().blockchain().equals_to(net.peer(1).client.backend().blockchain()));
 }
 
+#[test]
+fn sync_checkpoint_is_restored_until_sync_completes() {
+	let mut net = TestNet::new(2);
+	net.peer(1).push_blocks(500, false);
+	net.sync_steps(3);
+	assert_eq!(net.peer(0).sync.status().sync.state, SyncState::Downloading);
+
+	let client = net.peer(0).client.clone();
+	net.peer(0).sync.sync().write().checkpoint(&*client);
+	assert!(client.backend().get_aux(b"sync_checkpoint").unwrap().is_some());
+
+	let import_queue = net.peer(0).sync.sync().read().import_queue();
+	let mut restored = ChainSync::new(Roles::FULL, &client.info().unwrap(), import_queue);
+	restored.restore_checkpoint(&*client);
+	// not syncing until peers are connected again, but the checkpoint is kept
+	assert_eq!(restored.status().best_seen_block, None);
+	assert_eq!(restored.status().state, SyncState::Idle);
+	restored.checkpoint(&*client);
+	assert!(client.backend().get_aux(b"sync_checkpoint").unwrap().is_some());
+
+	net.sync();
+	net.peer(0).sync.sync().write().checkpoint(&*client);
+	assert!(client.backend().get_aux(b"sync_checkpoint").unwrap().is_none());
+}
+
+#[test]
+fn descendants_of_invalid_block_are_not_imported() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(2);
+	net.peer(1).push_blocks(10, false);
+	let bad_hash = net.peer(1).client.block_hash(5).unwrap().unwrap();
+	let mut rules = BlockRules::default();
+	rules.mark_bad(bad_hash);
+	net.peer(0).sync.sync().read().import_queue().set_block_rules(rules);
+
+	net.sync();
+	assert_eq!(net.peer(0).client.info().unwrap().chain.best_number, 4);
+	assert!(net.peer(0).sync.sync().read().is_known_invalid(&bad_hash));
+}
+
 #[test]
 fn sync_no_common_longer_chain_fails() {
 	::env_logger::init().ok();
@@ -135,3 +177,56 @@ fn blocks_are_not_announced_by_light_nodes() {
 	assert_eq!(net.peer(1).client.backend().blockchain().info().unwrap().best_number, 1);
 	assert_eq!(net.peer(2).client.backend().blockchain().info().unwrap().best_number, 0);
 }
+
+#[test]
+fn peers_on_different_forks_are_rejected() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(0);
+
+	let mut forked_config = ProtocolConfig::default();
+	forked_config.fork_id = Some(b"fork".to_vec());
+	net.add_peer(&ProtocolConfig::default());
+	net.add_peer(&forked_config);
+
+	net.peer(0).push_blocks(1, false);
+	net.peer(0).start();
+	net.peer(1).start();
+	net.peer(0).on_connect(1);
+	net.peer(1).on_connect(0);
+
+	while !net.done() {
+		net.sync_step();
+	}
+
+	assert_eq!(net.peer(1).client.backend().blockchain().info().unwrap().best_number, 0);
+	assert_eq!(net.peer(0).sync.status().chain_mismatches, 1);
+	assert_eq!(net.peer(1).sync.status().chain_mismatches, 1);
+}
+
+#[test]
+fn light_serving_cost_is_accounted_per_peer() {
+	::env_logger::init().ok();
+	let mut net = TestNet::new(3);
+	net.peer(0).push_blocks(1, false);
+	net.sync();
+	let best_hash = net.peer(0).client.info().unwrap().chain.best_hash;
+	let request = |id| {
+		let request: message::Message<Block> = GenericMessage::RemoteReadRequest(message::RemoteReadRequest {
+			id,
+			block: best_hash,
+			key: b":code".to_vec(),
+		});
+		TestPacket { data: request.encode_envelope(), recipient: 0 }
+	};
+
+	net.peer(0).receive_message(1, request(0));
+	net.peer(0).receive_message(1, request(1));
+	let light_serving = net.peer(0).sync.status().light_serving;
+	assert_eq!(light_serving.len(), 1);
+	assert_eq!(light_serving[&1].proofs, 2);
+	assert!(light_serving[&1].bytes > 0);
+
+	// the stats of the peer are dropped on disconnect
+	net.peer(0).on_disconnect(1);
+	assert!(net.peer(0).sync.status().light_serving.is_empty());
+}
@@ -0,0 +1,190 @@
+// Copyright 2017-2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Detection of the stalled chain.
+//!
+//! The best block is expected to change at least once every `stall_timeout` while some peer
+//! claims a better block. Otherwise the chain is considered stalled: a portion of the peers is
+//! disconnected, so that the node connects to other peers and recovers from a bad peer set
+//! unattended. Peers that are ahead of us are disconnected first, since they have failed to
+//! provide us their blocks. The peers aren't penalized, since the stall may be our fault. The
+//! stall is reported again after every following `stall_timeout` without a new best block.
+//!
+//! The chain isn't stalled while no peer is ahead of us, e.g. when the authoring has stopped.
+
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+use network_libp2p::NodeIndex;
+
+/// Configuration of the stall watchdog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogConfig {
+	/// Time without a new best block after which the chain is considered stalled.
+	pub stall_timeout: Duration,
+	/// Percentage of the peers that are disconnected when the stall is detected. At least one
+	/// peer is disconnected.
+	pub rotated_peers: usize,
+}
+
+impl Default for WatchdogConfig {
+	fn default() -> Self {
+		WatchdogConfig {
+			stall_timeout: Duration::from_secs(120),
+			rotated_peers: 25,
+		}
+	}
+}
+
+/// Stall of the chain, detected by the watchdog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stall<N> {
+	/// Best block the chain is stalled at.
+	pub best_number: N,
+	/// Time since the best block has changed.
+	pub duration: Duration,
+	/// Peers that should be disconnected.
+	pub rotated: Vec<NodeIndex>,
+}
+
+/// Stall watchdog.
+pub struct StallWatchdog<N> {
+	config: WatchdogConfig,
+	best_number: N,
+	/// Time the best block has changed at, or no peer has been ahead of us.
+	progress: Instant,
+	/// Time the stall has last been reported at.
+	reported: Option<Instant>,
+	stalls: usize,
+}
+
+impl<N: Ord + Copy> StallWatchdog<N> {
+	/// Create new watchdog, starting at given best block.
+	pub fn new(config: WatchdogConfig, best_number: N, now: Instant) -> Self {
+		StallWatchdog {
+			config,
+			best_number,
+			progress: now,
+			reported: None,
+			stalls: 0,
+		}
+	}
+
+	/// Total number of the detected stalls.
+	pub fn stalls(&self) -> usize {
+		self.stalls
+	}
+
+	/// Time since the best block has changed, if the chain is stalled.
+	pub fn stalled_for(&self, now: Instant) -> Option<Duration> {
+		self.reported.map(|_| now - self.progress)
+	}
+
+	/// Check the chain at given time. `peers` are the connected peers along with their best
+	/// blocks. Returns the stall if it is due to be reported.
+	pub fn check(&mut self, best_number: N, peers: Vec<(NodeIndex, N)>, now: Instant) -> Option<Stall<N>> {
+		let peers_ahead = peers.iter().any(|&(_, number)| number > best_number);
+		if best_number != self.best_number || !peers_ahead {
+			self.best_number = best_number;
+			self.progress = now;
+			self.reported = None;
+			return None;
+		}
+
+		let last_check = self.reported.unwrap_or(self.progress);
+		if now - last_check < self.config.stall_timeout {
+			return None;
+		}
+
+		self.reported = Some(now);
+		self.stalls += 1;
+		Some(Stall {
+			best_number,
+			duration: now - self.progress,
+			rotated: self.select_rotated(peers),
+		})
+	}
+
+	fn select_rotated(&self, mut peers: Vec<(NodeIndex, N)>) -> Vec<NodeIndex> {
+		let count = ::std::cmp::max(1, peers.len() * self.config.rotated_peers / 100);
+		peers.sort_by_key(|&(who, number)| (Reverse(number), who));
+		peers.into_iter().take(count).map(|(who, _)| who).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config() -> WatchdogConfig {
+		WatchdogConfig {
+			stall_timeout: Duration::from_secs(10),
+			rotated_peers: 50,
+		}
+	}
+
+	#[test]
+	fn stall_is_detected_only_when_there_are_peers_ahead() {
+		let start = Instant::now();
+		let at = |secs| start + Duration::from_secs(secs);
+		let mut watchdog = StallWatchdog::new(config(), 5u64, start);
+
+		// no peers => not stalled
+		assert_eq!(watchdog.check(5, Vec::new(), at(20)), None);
+		assert_eq!(watchdog.check(5, vec![(1, 6)], at(25)), None);
+
+		// no new best block since the peer ahead has connected
+		let stall = watchdog.check(5, vec![(1, 6)], at(30)).unwrap();
+		assert_eq!(stall.duration, Duration::from_secs(10));
+		assert_eq!(stall.rotated, vec![1]);
+		assert_eq!(watchdog.stalled_for(at(31)), Some(Duration::from_secs(11)));
+
+		// the stall is reported again after another timeout
+		assert_eq!(watchdog.check(5, vec![(2, 6)], at(35)), None);
+		assert_eq!(watchdog.check(5, vec![(2, 6)], at(40)).map(|stall| stall.duration), Some(Duration::from_secs(20)));
+		assert_eq!(watchdog.stalls(), 2);
+
+		// new best block resets the watchdog
+		assert_eq!(watchdog.check(6, vec![(2, 7)], at(45)), None);
+		assert_eq!(watchdog.stalled_for(at(45)), None);
+		assert_eq!(watchdog.check(6, vec![(2, 7)], at(50)), None);
+	}
+
+	#[test]
+	fn chain_is_not_stalled_while_no_peer_is_ahead() {
+		let start = Instant::now();
+		let at = |secs| start + Duration::from_secs(secs);
+		let mut watchdog = StallWatchdog::new(config(), 5u64, start);
+
+		// the peers are at the same or the lower block, e.g. the block authoring has stopped
+		assert_eq!(watchdog.check(5, vec![(1, 5), (2, 4)], at(20)), None);
+		assert_eq!(watchdog.check(5, vec![(1, 5), (2, 4)], at(40)), None);
+		assert_eq!(watchdog.stalls(), 0);
+
+		// the timeout starts once some peer is ahead of us
+		assert_eq!(watchdog.check(5, vec![(1, 6), (2, 4)], at(45)), None);
+		assert_eq!(watchdog.check(5, vec![(1, 6), (2, 4)], at(50)).map(|stall| stall.rotated), Some(vec![1]));
+	}
+
+	#[test]
+	fn peers_ahead_are_rotated_first() {
+		let start = Instant::now();
+		let mut watchdog = StallWatchdog::new(config(), 5u64, start);
+		let peers = vec![(1, 5), (2, 8), (3, 4), (4, 7), (5, 5)];
+		assert_eq!(watchdog.check(5, peers.clone(), start + Duration::from_secs(1)), None);
+		let stall = watchdog.check(5, peers, start + Duration::from_secs(11)).unwrap();
+		assert_eq!(stall.rotated, vec![2, 4]);
+	}
+}
@@ -0,0 +1,621 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Offchain HTTP client.
+//!
+//! The runtime code is executed synchronously, so the requests are performed by the
+//! `HttpWorker` future, running on the tokio runtime. The `HttpApi` sends the requests to the
+//! worker and blocks on the responses, until the deadline given by the runtime at most. The
+//! deadline is capped by the host, so that the runtime can't block the offchain worker forever.
+//!
+//! Only the plain HTTP requests are supported.
+
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::sync::mpsc;
+use std::time::Duration;
+use futures::{Async, Future, Poll, Stream};
+use futures::sync::mpsc as futures_mpsc;
+use hyper::{Body, Chunk, Client, Method, Request, Uri};
+use hyper::client::{HttpConnector, ResponseFuture};
+use hyper::header::{HeaderName, HeaderValue};
+use primitives::offchain::{Timestamp, HttpRequestId, HttpError, HttpRequestStatus};
+
+use now;
+
+/// Configuration of the offchain HTTP client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpConfig {
+	/// Maximal number of the requests that are not completed yet. Further requests are rejected.
+	pub max_concurrent_requests: usize,
+	/// Maximal size of the response body, in bytes. Requests with bigger responses fail.
+	pub max_response_size: usize,
+	/// Hosts the requests are allowed to. The host also allows its subdomains. All hosts are
+	/// denied if empty.
+	pub allowed_hosts: Vec<String>,
+	/// Hosts the requests are denied to, along with their subdomains. Takes precedence over
+	/// the `allowed_hosts`.
+	pub denied_hosts: Vec<String>,
+	/// Maximal time the offchain call may block on the requests, in milliseconds. Later deadlines
+	/// given by the runtime, or their absence, are capped to it.
+	pub max_wait_ms: u64,
+}
+
+impl Default for HttpConfig {
+	fn default() -> Self {
+		HttpConfig {
+			max_concurrent_requests: 16,
+			max_response_size: 1024 * 1024,
+			allowed_hosts: Vec::new(),
+			denied_hosts: Vec::new(),
+			max_wait_ms: 30_000,
+		}
+	}
+}
+
+impl HttpConfig {
+	/// Whether the requests to the host are allowed.
+	pub fn is_host_allowed(&self, host: &str) -> bool {
+		let host = host.to_lowercase();
+		let matches = |pattern: &String| {
+			let pattern = pattern.to_lowercase();
+			host == pattern || host.ends_with(&format!(".{}", pattern))
+		};
+		!self.denied_hosts.iter().any(&matches) && self.allowed_hosts.iter().any(&matches)
+	}
+}
+
+/// Create the HTTP API along with the worker that performs its requests. The worker should be
+/// spawned on the tokio runtime, and completes once the API is dropped.
+pub fn http(config: HttpConfig) -> (HttpApi, HttpWorker) {
+	let (to_worker, from_api) = futures_mpsc::unbounded();
+	let (to_api, from_worker) = mpsc::channel();
+	let max_response_size = config.max_response_size;
+	let max_deadline = now().add_millis(config.max_wait_ms);
+
+	let api = HttpApi {
+		config,
+		max_deadline,
+		to_worker,
+		from_worker,
+		next_id: 0,
+		requests: BTreeMap::new(),
+	};
+	let worker = HttpWorker {
+		to_api,
+		from_api,
+		client: None,
+		requests: Vec::new(),
+		max_response_size,
+	};
+	(api, worker)
+}
+
+/// Message sent by the API to the worker.
+enum ApiToWorker {
+	/// Perform the request.
+	Dispatch {
+		id: HttpRequestId,
+		request: Request<Body>,
+	},
+}
+
+/// Message sent by the worker to the API.
+enum WorkerToApi {
+	/// The response has been received.
+	Response {
+		id: HttpRequestId,
+		status: u16,
+		headers: Vec<(Vec<u8>, Vec<u8>)>,
+	},
+	/// The chunk of the response body has been received.
+	Body {
+		id: HttpRequestId,
+		chunk: Chunk,
+	},
+	/// The whole response body has been received.
+	BodyEnd {
+		id: HttpRequestId,
+	},
+	/// The request has failed.
+	Failed {
+		id: HttpRequestId,
+	},
+}
+
+/// State of the request, as seen by the API.
+enum HttpApiRequest {
+	/// The request is being built and has not been sent to the worker yet.
+	NotDispatched(Request<Body>, futures_mpsc::UnboundedSender<Chunk>),
+	/// The request has been sent to the worker. The sender is None once the body is complete.
+	Dispatched(Option<futures_mpsc::UnboundedSender<Chunk>>),
+	/// The response has been received.
+	Response {
+		status: u16,
+		headers: Vec<(Vec<u8>, Vec<u8>)>,
+		body: Vec<u8>,
+		complete: bool,
+	},
+	/// The request has failed.
+	Failed,
+}
+
+/// Offchain HTTP API. Requests ids are never reused, so the API is expected to be created
+/// for every offchain call.
+pub struct HttpApi {
+	config: HttpConfig,
+	max_deadline: Timestamp,
+	to_worker: futures_mpsc::UnboundedSender<ApiToWorker>,
+	from_worker: mpsc::Receiver<WorkerToApi>,
+	next_id: u16,
+	requests: BTreeMap<HttpRequestId, HttpApiRequest>,
+}
+
+impl HttpApi {
+	/// Start the HTTP request. The request is sent once its body is written, or once its response
+	/// is awaited.
+	pub fn request_start(&mut self, method: &str, uri: &str, meta: &[u8]) -> Result<HttpRequestId, HttpError> {
+		if !meta.is_empty() {
+			return Err(HttpError::Invalid);
+		}
+		if self.requests.len() >= self.config.max_concurrent_requests {
+			debug!(target: "offchain", "Rejecting HTTP request to {}: too many pending requests", uri);
+			return Err(HttpError::Invalid);
+		}
+
+		let method = Method::from_bytes(method.as_bytes()).map_err(|_| HttpError::Invalid)?;
+		let uri = uri.parse::<Uri>().map_err(|_| HttpError::Invalid)?;
+		if uri.scheme_part().map(|scheme| scheme.as_str()) != Some("http") {
+			debug!(target: "offchain", "Rejecting HTTP request to {}: unsupported scheme", uri);
+			return Err(HttpError::Invalid);
+		}
+		if !uri.host().map_or(false, |host| self.config.is_host_allowed(host)) {
+			debug!(target: "offchain", "Rejecting HTTP request to {}: host is not allowed", uri);
+			return Err(HttpError::Invalid);
+		}
+
+		let id = HttpRequestId(self.next_id);
+		self.next_id = self.next_id.checked_add(1).ok_or(HttpError::Invalid)?;
+
+		let (body_sender, body_receiver) = futures_mpsc::unbounded();
+		let body: Box<Stream<Item=Chunk, Error=Box<StdError + Send + Sync>> + Send> = Box::new(body_receiver
+			.map_err(|()| -> Box<StdError + Send + Sync> { "request body channel has failed".into() }));
+		let mut request = Request::new(Body::from(body));
+		*request.method_mut() = method;
+		*request.uri_mut() = uri;
+		self.requests.insert(id, HttpApiRequest::NotDispatched(request, body_sender));
+		Ok(id)
+	}
+
+	/// Add the header to the request that has not been sent yet.
+	pub fn request_add_header(&mut self, id: HttpRequestId, name: &str, value: &str) -> Result<(), HttpError> {
+		let request = match self.requests.get_mut(&id) {
+			Some(HttpApiRequest::NotDispatched(request, _)) => request,
+			_ => return Err(HttpError::Invalid),
+		};
+
+		let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| HttpError::Invalid)?;
+		let value = HeaderValue::from_str(value).map_err(|_| HttpError::Invalid)?;
+		request.headers_mut().append(name, value);
+		Ok(())
+	}
+
+	/// Write the chunk of the request body. An empty chunk completes the body.
+	pub fn request_write_body(&mut self, id: HttpRequestId, chunk: &[u8], deadline: Option<Timestamp>) -> Result<(), HttpError> {
+		if now() >= self.deadline(deadline) {
+			return Err(HttpError::DeadlineReached);
+		}
+
+		self.dispatch(id);
+		self.process_events();
+		match self.requests.get_mut(&id) {
+			Some(HttpApiRequest::Dispatched(body_sender)) => {
+				if chunk.is_empty() {
+					*body_sender = None;
+					return Ok(());
+				}
+
+				match body_sender {
+					Some(sender) => sender.unbounded_send(Chunk::from(chunk.to_vec())).map_err(|_| HttpError::IoError),
+					None => Err(HttpError::Invalid),
+				}
+			},
+			Some(HttpApiRequest::Failed) => Err(HttpError::IoError),
+			_ => Err(HttpError::Invalid),
+		}
+	}
+
+	/// Wait for the responses to the requests. The bodies of the requests are completed. Failed
+	/// requests are forgotten once their status is returned.
+	pub fn response_wait(&mut self, ids: &[HttpRequestId], deadline: Option<Timestamp>) -> Vec<HttpRequestStatus> {
+		let deadline = self.deadline(deadline);
+		for id in ids {
+			self.dispatch(*id);
+			if let Some(HttpApiRequest::Dispatched(body_sender)) = self.requests.get_mut(id) {
+				*body_sender = None;
+			}
+		}
+
+		loop {
+			self.process_events();
+			let is_pending = ids.iter().any(|id| match self.requests.get(id) {
+				Some(HttpApiRequest::Dispatched(_)) => true,
+				_ => false,
+			});
+			if !is_pending || !self.wait_event(deadline) {
+				break;
+			}
+		}
+
+		ids.iter()
+			.map(|id| {
+				let status = match self.requests.get(id) {
+					Some(HttpApiRequest::Response { status, .. }) => HttpRequestStatus::Finished(*status),
+					Some(HttpApiRequest::Dispatched(_)) => HttpRequestStatus::DeadlineReached,
+					Some(HttpApiRequest::Failed) => HttpRequestStatus::IoError,
+					_ => HttpRequestStatus::Invalid,
+				};
+				if status == HttpRequestStatus::IoError {
+					self.requests.remove(id);
+				}
+				status
+			})
+			.collect()
+	}
+
+	/// Headers of the received response. Empty if the response has not been received.
+	pub fn response_headers(&mut self, id: HttpRequestId) -> Vec<(Vec<u8>, Vec<u8>)> {
+		self.process_events();
+		match self.requests.get(&id) {
+			Some(HttpApiRequest::Response { headers, .. }) => headers.clone(),
+			_ => Vec::new(),
+		}
+	}
+
+	/// Read the chunk of the response body into the `buffer`. Returns the number of bytes
+	/// written. The request is forgotten once its whole body is read, or once it fails.
+	pub fn response_read_body(&mut self, id: HttpRequestId, buffer: &mut [u8], deadline: Option<Timestamp>) -> Result<usize, HttpError> {
+		let deadline = self.deadline(deadline);
+		self.dispatch(id);
+		loop {
+			self.process_events();
+			let completed = match self.requests.get_mut(&id) {
+				Some(HttpApiRequest::Response { body, complete, .. }) => {
+					if !body.is_empty() {
+						let read = ::std::cmp::min(buffer.len(), body.len());
+						buffer[..read].copy_from_slice(&body[..read]);
+						body.drain(..read);
+						return Ok(read);
+					}
+					if *complete { Some(Ok(0)) } else { None }
+				},
+				Some(HttpApiRequest::Failed) => Some(Err(HttpError::IoError)),
+				Some(HttpApiRequest::Dispatched(body_sender)) => {
+					*body_sender = None;
+					None
+				},
+				_ => return Err(HttpError::Invalid),
+			};
+			if let Some(result) = completed {
+				self.requests.remove(&id);
+				return result;
+			}
+
+			if !self.wait_event(deadline) {
+				return Err(HttpError::DeadlineReached);
+			}
+		}
+	}
+
+	/// The deadline given by the runtime, capped to the maximal deadline of the API.
+	fn deadline(&self, deadline: Option<Timestamp>) -> Timestamp {
+		match deadline {
+			Some(deadline) if deadline < self.max_deadline => deadline,
+			_ => self.max_deadline,
+		}
+	}
+
+	/// Send the request to the worker, if it has not been sent yet.
+	fn dispatch(&mut self, id: HttpRequestId) {
+		let (request, body_sender) = match self.requests.remove(&id) {
+			Some(HttpApiRequest::NotDispatched(request, body_sender)) => (request, body_sender),
+			Some(request) => {
+				self.requests.insert(id, request);
+				return;
+			},
+			None => return,
+		};
+
+		let state = match self.to_worker.unbounded_send(ApiToWorker::Dispatch { id, request }) {
+			Ok(()) => HttpApiRequest::Dispatched(Some(body_sender)),
+			Err(_) => HttpApiRequest::Failed,
+		};
+		self.requests.insert(id, state);
+	}
+
+	/// Process the messages that have already been received from the worker.
+	fn process_events(&mut self) {
+		while let Ok(event) = self.from_worker.try_recv() {
+			self.process_event(event);
+		}
+	}
+
+	/// Wait for the message from the worker until the deadline. Returns false if the deadline has
+	/// been reached.
+	fn wait_event(&mut self, deadline: Timestamp) -> bool {
+		let now = now();
+		if now >= deadline {
+			return false;
+		}
+		let event = match self.from_worker.recv_timeout(Duration::from_millis(deadline.0 - now.0)) {
+			Ok(event) => Ok(event),
+			Err(mpsc::RecvTimeoutError::Timeout) => return false,
+			Err(mpsc::RecvTimeoutError::Disconnected) => Err(()),
+		};
+
+		match event {
+			Ok(event) => self.process_event(event),
+			Err(()) => {
+				// the worker is gone => none of the pending requests is going to complete
+				for request in self.requests.values_mut() {
+					let is_pending = match *request {
+						HttpApiRequest::Dispatched(_) => true,
+						HttpApiRequest::Response { complete, .. } => !complete,
+						_ => false,
+					};
+					if is_pending {
+						*request = HttpApiRequest::Failed;
+					}
+				}
+			},
+		}
+		true
+	}
+
+	fn process_event(&mut self, event: WorkerToApi) {
+		match event {
+			WorkerToApi::Response { id, status, headers } => {
+				if let Some(request) = self.requests.get_mut(&id) {
+					*request = HttpApiRequest::Response { status, headers, body: Vec::new(), complete: false };
+				}
+			},
+			WorkerToApi::Body { id, chunk } => {
+				if let Some(HttpApiRequest::Response { body, .. }) = self.requests.get_mut(&id) {
+					body.extend_from_slice(&chunk);
+				}
+			},
+			WorkerToApi::BodyEnd { id } => {
+				if let Some(HttpApiRequest::Response { complete, .. }) = self.requests.get_mut(&id) {
+					*complete = true;
+				}
+			},
+			WorkerToApi::Failed { id } => {
+				if let Some(request) = self.requests.get_mut(&id) {
+					*request = HttpApiRequest::Failed;
+				}
+			},
+		}
+	}
+}
+
+/// State of the request, as seen by the worker.
+enum HttpWorkerRequest {
+	/// Waiting for the response.
+	Response(ResponseFuture),
+	/// Reading the response body.
+	Body(Body, usize),
+}
+
+/// Future performing the requests of the `HttpApi`.
+pub struct HttpWorker {
+	to_api: mpsc::Sender<WorkerToApi>,
+	from_api: futures_mpsc::UnboundedReceiver<ApiToWorker>,
+	/// The client is created on the first poll, so that it uses the runtime the worker is
+	/// spawned on.
+	client: Option<Client<HttpConnector, Body>>,
+	requests: Vec<(HttpRequestId, HttpWorkerRequest)>,
+	max_response_size: usize,
+}
+
+impl HttpWorker {
+	/// Poll the request. Returns the messages to the API and whether the request has completed.
+	fn poll_request(&self, id: HttpRequestId, request: &mut HttpWorkerRequest, events: &mut Vec<WorkerToApi>) -> bool {
+		loop {
+			let next = match request {
+				HttpWorkerRequest::Response(response) => match response.poll() {
+					Ok(Async::NotReady) => return false,
+					Ok(Async::Ready(response)) => {
+						let headers = response.headers().iter()
+							.map(|(name, value)| (name.as_str().as_bytes().to_vec(), value.as_bytes().to_vec()))
+							.collect();
+						events.push(WorkerToApi::Response { id, status: response.status().as_u16(), headers });
+						HttpWorkerRequest::Body(response.into_body(), 0)
+					},
+					Err(error) => {
+						debug!(target: "offchain", "HTTP request {:?} has failed: {}", id, error);
+						events.push(WorkerToApi::Failed { id });
+						return true;
+					},
+				},
+				HttpWorkerRequest::Body(body, size) => match body.poll() {
+					Ok(Async::NotReady) => return false,
+					Ok(Async::Ready(Some(chunk))) => {
+						*size += chunk.len();
+						if *size > self.max_response_size {
+							debug!(target: "offchain", "HTTP request {:?} has failed: response is too big", id);
+							events.push(WorkerToApi::Failed { id });
+							return true;
+						}
+						events.push(WorkerToApi::Body { id, chunk });
+						continue;
+					},
+					Ok(Async::Ready(None)) => {
+						events.push(WorkerToApi::BodyEnd { id });
+						return true;
+					},
+					Err(error) => {
+						debug!(target: "offchain", "Reading the body of HTTP request {:?} has failed: {}", id, error);
+						events.push(WorkerToApi::Failed { id });
+						return true;
+					},
+				},
+			};
+			*request = next;
+		}
+	}
+}
+
+impl Future for HttpWorker {
+	type Item = ();
+	type Error = ();
+
+	fn poll(&mut self) -> Poll<(), ()> {
+		loop {
+			match self.from_api.poll() {
+				Ok(Async::Ready(Some(ApiToWorker::Dispatch { id, request }))) => {
+					let client = self.client.get_or_insert_with(Client::new);
+					self.requests.push((id, HttpWorkerRequest::Response(client.request(request))));
+				},
+				// the API is gone => nobody is interested in the pending requests
+				Ok(Async::Ready(None)) | Err(_) => return Ok(Async::Ready(())),
+				Ok(Async::NotReady) => break,
+			}
+		}
+
+		let mut events = Vec::new();
+		for (id, mut request) in ::std::mem::replace(&mut self.requests, Vec::new()) {
+			if !self.poll_request(id, &mut request, &mut events) {
+				self.requests.push((id, request));
+			}
+		}
+
+		for event in events {
+			if self.to_api.send(event).is_err() {
+				return Ok(Async::Ready(()));
+			}
+		}
+		Ok(Async::NotReady)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use hyper::{Response, Server};
+	use hyper::service::service_fn_ok;
+	use tokio::runtime::Runtime;
+	use super::*;
+
+	fn config(max_response_size: usize) -> HttpConfig {
+		HttpConfig {
+			max_concurrent_requests: 2,
+			max_response_size,
+			allowed_hosts: vec!["127.0.0.1".into(), "example.com".into()],
+			denied_hosts: vec!["internal.example.com".into()],
+			max_wait_ms: 30_000,
+		}
+	}
+
+	/// Start the server that echoes the request body, or responds with the big body to the
+	/// requests to `/big`. Returns the runtime and the address of the server.
+	fn start_server(runtime: &mut Runtime) -> String {
+		let server = Server::bind(&([127, 0, 0, 1], 0).into())
+			.serve(|| service_fn_ok(|request: Request<Body>| if request.uri().path() == "/big" {
+				Response::new(Body::from(vec![1u8; 100]))
+			} else {
+				Response::new(request.into_body())
+			}));
+		let address = format!("http://{}", server.local_addr());
+		runtime.spawn(server.map_err(|_| ()));
+		address
+	}
+
+	#[test]
+	fn hosts_are_allowed_and_denied() {
+		let config = config(0);
+		assert!(config.is_host_allowed("example.com"));
+		assert!(config.is_host_allowed("api.EXAMPLE.com"));
+		assert!(!config.is_host_allowed("notexample.com"));
+		assert!(!config.is_host_allowed("internal.example.com"));
+		assert!(!config.is_host_allowed("db.internal.example.com"));
+		assert!(!HttpConfig::default().is_host_allowed("example.com"));
+	}
+
+	#[test]
+	fn requests_are_limited() {
+		let (mut api, _worker) = http(config(0));
+		assert_eq!(api.request_start("GET", "https://example.com", &[]), Err(HttpError::Invalid));
+		assert_eq!(api.request_start("GET", "http://internal.example.com", &[]), Err(HttpError::Invalid));
+		assert_eq!(api.request_start("GET", "http://example.com", &[1]), Err(HttpError::Invalid));
+
+		let id = api.request_start("GET", "http://example.com", &[]).unwrap();
+		api.request_start("GET", "http://example.com", &[]).unwrap();
+		assert_eq!(api.request_start("GET", "http://example.com", &[]), Err(HttpError::Invalid));
+
+		// the worker isn't running => the response is never received
+		let deadline = Some(now().add_millis(20));
+		assert_eq!(api.response_wait(&[id, HttpRequestId(100)], deadline),
+			vec![HttpRequestStatus::DeadlineReached, HttpRequestStatus::Invalid]);
+		assert_eq!(api.request_add_header(id, "X-Test", "test"), Err(HttpError::Invalid));
+		assert_eq!(api.response_read_body(id, &mut [0; 10], Some(now())), Err(HttpError::DeadlineReached));
+	}
+
+	#[test]
+	fn deadline_is_capped_by_host() {
+		let (mut api, _worker) = http(HttpConfig { max_wait_ms: 20, ..config(0) });
+		let id = api.request_start("GET", "http://example.com", &[]).unwrap();
+
+		// the worker isn't running => the wait is only ended by the maximal deadline
+		let far_deadline = Some(now().add_millis(60_000));
+		assert_eq!(api.response_wait(&[id], None), vec![HttpRequestStatus::DeadlineReached]);
+		assert_eq!(api.response_wait(&[id], far_deadline), vec![HttpRequestStatus::DeadlineReached]);
+		assert_eq!(api.response_read_body(id, &mut [0; 10], None), Err(HttpError::DeadlineReached));
+		assert_eq!(api.request_write_body(id, b"hello", None), Err(HttpError::DeadlineReached));
+	}
+
+	#[test]
+	fn request_body_is_sent_and_response_is_read() {
+		let mut runtime = Runtime::new().unwrap();
+		let address = start_server(&mut runtime);
+		let (mut api, worker) = http(config(10));
+		runtime.spawn(worker);
+
+		let id = api.request_start("POST", &address, &[]).unwrap();
+		api.request_add_header(id, "Content-Length", "6").unwrap();
+		api.request_write_body(id, b"hello ", None).unwrap();
+		assert_eq!(api.request_add_header(id, "X-Test", "test"), Err(HttpError::Invalid));
+		assert_eq!(api.response_wait(&[id], None), vec![HttpRequestStatus::Finished(200)]);
+		assert!(api.response_headers(id).iter().any(|(name, _)| &name[..] == b"date"));
+
+		let mut body = Vec::new();
+		let mut buffer = [0u8; 4];
+		loop {
+			match api.response_read_body(id, &mut buffer, None).unwrap() {
+				0 => break,
+				read => body.extend_from_slice(&buffer[..read]),
+			}
+		}
+		assert_eq!(body, b"hello ".to_vec());
+		assert_eq!(api.response_read_body(id, &mut buffer, None), Err(HttpError::Invalid));
+
+		// the response is bigger than allowed
+		let id = api.request_start("GET", &format!("{}/big", address), &[]).unwrap();
+		match api.response_wait(&[id], None)[0] {
+			HttpRequestStatus::Finished(200) =>
+				assert_eq!(api.response_read_body(id, &mut buffer, None), Err(HttpError::IoError)),
+			status => assert_eq!(status, HttpRequestStatus::IoError),
+		}
+	}
+}
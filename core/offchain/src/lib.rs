@@ -0,0 +1,96 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+// tag::description[]
+//! Offchain externalities of the node: the APIs available to the runtime code executed in the
+//! offchain context.
+// end::description[]
+
+#![warn(missing_docs)]
+
+extern crate futures;
+extern crate hyper;
+#[macro_use]
+extern crate log;
+extern crate substrate_primitives as primitives;
+
+#[cfg(test)]
+extern crate tokio;
+
+mod http;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use primitives::offchain::{self, Timestamp, HttpRequestId, HttpError, HttpRequestStatus};
+
+pub use http::{http, HttpApi, HttpConfig, HttpWorker};
+
+/// Offchain externalities, created for every offchain call.
+pub struct Api {
+	http: HttpApi,
+}
+
+impl Api {
+	/// Create new offchain externalities with given HTTP API.
+	pub fn new(http: HttpApi) -> Self {
+		Api { http }
+	}
+}
+
+impl offchain::Externalities for Api {
+	fn timestamp(&mut self) -> Timestamp {
+		now()
+	}
+
+	fn http_request_start(&mut self, method: &str, uri: &str, meta: &[u8]) -> Result<HttpRequestId, HttpError> {
+		self.http.request_start(method, uri, meta)
+	}
+
+	fn http_request_add_header(&mut self, request_id: HttpRequestId, name: &str, value: &str) -> Result<(), HttpError> {
+		self.http.request_add_header(request_id, name, value)
+	}
+
+	fn http_request_write_body(
+		&mut self,
+		request_id: HttpRequestId,
+		chunk: &[u8],
+		deadline: Option<Timestamp>,
+	) -> Result<(), HttpError> {
+		self.http.request_write_body(request_id, chunk, deadline)
+	}
+
+	fn http_response_wait(&mut self, ids: &[HttpRequestId], deadline: Option<Timestamp>) -> Vec<HttpRequestStatus> {
+		self.http.response_wait(ids, deadline)
+	}
+
+	fn http_response_headers(&mut self, request_id: HttpRequestId) -> Vec<(Vec<u8>, Vec<u8>)> {
+		self.http.response_headers(request_id)
+	}
+
+	fn http_response_read_body(
+		&mut self,
+		request_id: HttpRequestId,
+		buffer: &mut [u8],
+		deadline: Option<Timestamp>,
+	) -> Result<usize, HttpError> {
+		self.http.response_read_body(request_id, buffer, deadline)
+	}
+}
+
+/// Current time.
+pub(crate) fn now() -> Timestamp {
+	let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+	Timestamp(since_epoch.as_secs() * 1000 + since_epoch.subsec_millis() as u64)
+}
@@ -18,6 +18,8 @@
 //! Simple Ed25519 API.
 // end::description[]
 
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
 use untrusted;
 use blake2_rfc;
 use ring::{rand, signature};
@@ -52,6 +54,58 @@ pub fn verify<P: AsRef<[u8]>>(sig: &[u8], message: &[u8], public: P) -> bool {
 	}
 }
 
+/// Maximum number of signature checks done ahead of execution that are kept around.
+const PREVERIFIED_CAPACITY: usize = 65536;
+
+/// Results of signature checks done ahead of execution, by hash of the extrinsic carrying them.
+#[derive(Default)]
+struct Preverified {
+	results: HashMap<[u8; 32], ([u8; 32], bool)>,
+	order: VecDeque<[u8; 32]>,
+}
+
+lazy_static! {
+	static ref PREVERIFIED: Mutex<Preverified> = Mutex::new(Default::default());
+}
+
+/// Hash binding a check result to the exact signature, message and key it was made over.
+fn preverified_check(sig: &[u8], message: &[u8], public: &[u8]) -> [u8; 32] {
+	let mut hasher = blake2_rfc::blake2b::Blake2b::new(32);
+	hasher.update(sig);
+	hasher.update(public);
+	hasher.update(message);
+	let mut check = [0u8; 32];
+	check.copy_from_slice(hasher.finalize().as_bytes());
+	check
+}
+
+/// Verify the signature of an extrinsic ahead of its execution, remembering the result under
+/// the extrinsic hash until it is consumed by `verify_preverified`.
+pub fn preverify<P: AsRef<[u8]>>(extrinsic_hash: [u8; 32], sig: &[u8], message: &[u8], public: P) {
+	let valid = verify(sig, message, public.as_ref());
+	let check = preverified_check(sig, message, public.as_ref());
+
+	let mut cache = PREVERIFIED.lock().unwrap_or_else(|e| e.into_inner());
+	if cache.results.insert(extrinsic_hash, (check, valid)).is_none() {
+		cache.order.push_back(extrinsic_hash);
+	}
+	while cache.order.len() > PREVERIFIED_CAPACITY {
+		if let Some(evicted) = cache.order.pop_front() {
+			cache.results.remove(&evicted);
+		}
+	}
+}
+
+/// Verify the signature of the extrinsic with given hash, reusing the result of `preverify`
+/// when it was made over the same signature, message and key. Returns true if the signature is good.
+pub fn verify_preverified<P: AsRef<[u8]>>(extrinsic_hash: &[u8; 32], sig: &[u8], message: &[u8], public: P) -> bool {
+	let cached = PREVERIFIED.lock().unwrap_or_else(|e| e.into_inner()).results.remove(extrinsic_hash);
+	match cached {
+		Some((check, valid)) if check == preverified_check(sig, message, public.as_ref()) => valid,
+		_ => verify(sig, message, public),
+	}
+}
+
 /// A public key.
 #[derive(PartialEq, Eq, Clone, Encode, Decode)]
 pub struct Public(pub [u8; 32]);
@@ -344,6 +398,24 @@ mod test {
 		assert_eq!(cmp, public);
 	}
 
+	#[test]
+	fn preverified_result_requires_matching_signature() {
+		let pair = Pair::from_seed(b"12345678901234567890123456789012");
+		let public = pair.public();
+		let message = b"Something important";
+		let signature = pair.sign(&message[..]);
+
+		preverify([1u8; 32], &signature.0[..], &message[..], &public.0[..]);
+		assert!(verify_preverified(&[1u8; 32], &signature.0[..], &message[..], &public.0[..]));
+
+		// a cached result made over a different message is not reused.
+		preverify([2u8; 32], &signature.0[..], &message[..], &public.0[..]);
+		assert!(!verify_preverified(&[2u8; 32], &signature.0[..], b"Something else", &public.0[..]));
+
+		// results are consumed, leaving the cache clean for the next execution.
+		assert!(PREVERIFIED.lock().unwrap().results.get(&[1u8; 32]).is_none());
+	}
+
 	#[test]
 	fn ss58check_known_works() {
 		let k = "5CGavy93sZgPPjHyziRohwVumxiHXMGmQLyuqQP4ZFx5vRU9";
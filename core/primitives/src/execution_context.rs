@@ -0,0 +1,65 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Context in which the runtime code is executed.
+
+/// Context in which the runtime code is executed. Lets the runtime and the host functions
+/// behave differently depending on why the call is made, e.g. skip the expensive checks
+/// of the blocks that are imported during the major sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum ExecutionContext {
+	/// Importing a block received from the network or produced locally.
+	Importing,
+	/// Importing a block during the major sync.
+	Syncing,
+	/// Building a new block.
+	BlockConstruction,
+	/// Offchain worker execution, the results are never part of a block.
+	Offchain,
+	/// Any other call, e.g. a call made by the RPC.
+	Other,
+}
+
+impl Default for ExecutionContext {
+	fn default() -> Self {
+		ExecutionContext::Other
+	}
+}
+
+impl ExecutionContext {
+	/// Encode the context as the value returned from the `ext_execution_context` host function.
+	pub fn as_u32(&self) -> u32 {
+		match *self {
+			ExecutionContext::Importing => 0,
+			ExecutionContext::Syncing => 1,
+			ExecutionContext::BlockConstruction => 2,
+			ExecutionContext::Offchain => 3,
+			ExecutionContext::Other => 4,
+		}
+	}
+
+	/// Decode the context from the value returned from the `ext_execution_context` host function.
+	pub fn from_u32(value: u32) -> Option<Self> {
+		match value {
+			0 => Some(ExecutionContext::Importing),
+			1 => Some(ExecutionContext::Syncing),
+			2 => Some(ExecutionContext::BlockConstruction),
+			3 => Some(ExecutionContext::Offchain),
+			4 => Some(ExecutionContext::Other),
+			_ => None,
+		}
+	}
+}
@@ -49,6 +49,9 @@ extern crate ring;
 extern crate base58;
 #[cfg(feature = "std")]
 extern crate untrusted;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate lazy_static;
 #[cfg(test)]
 #[macro_use]
 extern crate hex_literal;
@@ -105,6 +108,10 @@ pub mod storage;
 pub mod uint;
 mod authority_id;
 mod changes_trie;
+mod execution_context;
+#[cfg(feature = "std")]
+mod local_keys;
+pub mod offchain;
 
 #[cfg(test)]
 mod tests;
@@ -113,6 +120,9 @@ pub use self::hash::{H160, H256, H512};
 pub use self::uint::U256;
 pub use authority_id::AuthorityId;
 pub use changes_trie::ChangesTrieConfiguration;
+pub use execution_context::ExecutionContext;
+#[cfg(feature = "std")]
+pub use local_keys::LocalKeys;
 
 pub use hash_db::Hasher;
 // Switch back to Blake after PoC-3 is out
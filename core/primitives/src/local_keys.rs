@@ -0,0 +1,34 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Keys held by the node, consulted by the runtime to find out whether the node is one of the
+//! authorities, e.g. to report its own misbehaviour or to decide whether it is eligible to
+//! submit the unsigned extrinsic.
+
+use std::collections::HashSet;
+use AuthorityId;
+
+/// Set of the authority keys held by the node.
+pub trait LocalKeys: Send + Sync {
+	/// Whether the node holds the private key of the given authority.
+	fn has_key(&self, authority: &AuthorityId) -> bool;
+}
+
+impl LocalKeys for HashSet<AuthorityId> {
+	fn has_key(&self, authority: &AuthorityId) -> bool {
+		self.contains(authority)
+	}
+}
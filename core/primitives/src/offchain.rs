@@ -0,0 +1,172 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Types of the offchain API, available to the runtime code executed in the
+//! `ExecutionContext::Offchain` context.
+
+/// Point in time, in milliseconds since the UNIX epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Encode, Decode)]
+pub struct Timestamp(pub u64);
+
+impl Timestamp {
+	/// The timestamp that is `millis` milliseconds later.
+	pub fn add_millis(&self, millis: u64) -> Self {
+		Timestamp(self.0.saturating_add(millis))
+	}
+
+	/// Encode the optional deadline as the value passed to the host functions.
+	pub fn deadline_as_u64(deadline: Option<Timestamp>) -> u64 {
+		deadline.map_or(0, |deadline| deadline.0)
+	}
+
+	/// Decode the optional deadline from the value passed to the host functions.
+	pub fn deadline_from_u64(value: u64) -> Option<Timestamp> {
+		if value == 0 { None } else { Some(Timestamp(value)) }
+	}
+}
+
+/// Identifier of the HTTP request, unique among the pending requests of the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub struct HttpRequestId(pub u16);
+
+/// Error of the HTTP request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum HttpError {
+	/// The deadline has been reached before the operation has completed.
+	DeadlineReached,
+	/// The request has failed: the connection has been lost, the host has failed to answer or
+	/// the response is too big.
+	IoError,
+	/// The request is unknown or it is not in the state that allows the operation.
+	Invalid,
+}
+
+impl HttpError {
+	/// Encode the error as the value returned from the host functions. Zero means success.
+	pub fn as_u32(&self) -> u32 {
+		match *self {
+			HttpError::DeadlineReached => 1,
+			HttpError::IoError => 2,
+			HttpError::Invalid => 3,
+		}
+	}
+
+	/// Decode the result of the operation from the value returned from the host functions.
+	pub fn result_from_u32(value: u32) -> Result<(), HttpError> {
+		match value {
+			0 => Ok(()),
+			1 => Err(HttpError::DeadlineReached),
+			2 => Err(HttpError::IoError),
+			_ => Err(HttpError::Invalid),
+		}
+	}
+}
+
+/// Status of the HTTP request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum HttpRequestStatus {
+	/// The deadline has been reached before the response has been received.
+	DeadlineReached,
+	/// The request has failed.
+	IoError,
+	/// The request is unknown.
+	Invalid,
+	/// The response has been received, with the given status code. The body is read with
+	/// `http_response_read_body`.
+	Finished(u16),
+}
+
+impl HttpRequestStatus {
+	/// Encode the status as the value returned from the host functions.
+	pub fn as_u32(&self) -> u32 {
+		match *self {
+			HttpRequestStatus::Invalid => 0,
+			HttpRequestStatus::DeadlineReached => 10,
+			HttpRequestStatus::IoError => 20,
+			HttpRequestStatus::Finished(code) => code as u32,
+		}
+	}
+
+	/// Decode the status from the value returned from the host functions.
+	pub fn from_u32(value: u32) -> Self {
+		match value {
+			10 => HttpRequestStatus::DeadlineReached,
+			20 => HttpRequestStatus::IoError,
+			100..=999 => HttpRequestStatus::Finished(value as u16),
+			_ => HttpRequestStatus::Invalid,
+		}
+	}
+}
+
+/// Offchain externalities, provided by the node to the runtime code executed in the offchain
+/// context.
+#[cfg(feature = "std")]
+pub trait Externalities {
+	/// Current time.
+	fn timestamp(&mut self) -> Timestamp;
+
+	/// Start the HTTP request with given method and URI. The request is sent once its body is
+	/// written, or once its response is awaited. `meta` is reserved for the future options of
+	/// the request and must be empty.
+	fn http_request_start(&mut self, method: &str, uri: &str, meta: &[u8]) -> Result<HttpRequestId, HttpError>;
+
+	/// Add the header to the request that has not been sent yet.
+	fn http_request_add_header(&mut self, request_id: HttpRequestId, name: &str, value: &str) -> Result<(), HttpError>;
+
+	/// Write the chunk of the request body, waiting until the `deadline` at most. An empty chunk
+	/// completes the body.
+	fn http_request_write_body(
+		&mut self,
+		request_id: HttpRequestId,
+		chunk: &[u8],
+		deadline: Option<Timestamp>,
+	) -> Result<(), HttpError>;
+
+	/// Wait for the responses to the requests until the `deadline` at most. Returns the status
+	/// of every request, in the order of the `ids`.
+	fn http_response_wait(&mut self, ids: &[HttpRequestId], deadline: Option<Timestamp>) -> Vec<HttpRequestStatus>;
+
+	/// Headers of the received response. Empty if the response has not been received.
+	fn http_response_headers(&mut self, request_id: HttpRequestId) -> Vec<(Vec<u8>, Vec<u8>)>;
+
+	/// Read the chunk of the response body into the `buffer`, waiting until the `deadline` at
+	/// most. Returns the number of bytes written, zero once the whole body has been read.
+	fn http_response_read_body(
+		&mut self,
+		request_id: HttpRequestId,
+		buffer: &mut [u8],
+		deadline: Option<Timestamp>,
+	) -> Result<usize, HttpError>;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn http_request_status_is_encoded() {
+		for status in &[
+			HttpRequestStatus::DeadlineReached,
+			HttpRequestStatus::IoError,
+			HttpRequestStatus::Invalid,
+			HttpRequestStatus::Finished(200),
+			HttpRequestStatus::Finished(404),
+		] {
+			assert_eq!(HttpRequestStatus::from_u32(status.as_u32()), *status);
+		}
+		assert_eq!(HttpRequestStatus::from_u32(1000), HttpRequestStatus::Invalid);
+	}
+}
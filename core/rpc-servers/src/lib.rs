@@ -22,22 +22,31 @@
 
 pub extern crate substrate_rpc as apis;
 
+extern crate jsonrpc_core as rpc;
 extern crate jsonrpc_http_server as http;
 extern crate jsonrpc_pubsub as pubsub;
 extern crate jsonrpc_ws_server as ws;
+extern crate parking_lot;
 extern crate serde;
 extern crate sr_primitives;
 
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_json;
+
+mod rate_limit;
 
 use std::io;
+use std::net::IpAddr;
 use sr_primitives::traits::{Block as BlockT, NumberFor};
 
 type Metadata = apis::metadata::Metadata;
-type RpcHandler = pubsub::PubSubHandler<Metadata>;
+type RpcHandler = pubsub::PubSubHandler<Metadata, RateLimiter>;
 pub type HttpServer = http::Server;
 pub type WsServer = ws::Server;
+pub use rate_limit::{RateLimits, RateLimiter, RATE_LIMITED_ERROR, WS_CONNECTION_COST, method_cost};
+pub use apis::{ExecutionPool, ExecutionPoolConfig, EXECUTION_POOL_SATURATED_ERROR};
 
 /// Construct rpc `IoHandler`
 pub fn rpc_handler<Block: BlockT, ExHash, PendingExtrinsics, S, C, A, Y>(
@@ -45,6 +54,7 @@ pub fn rpc_handler<Block: BlockT, ExHash, PendingExtrinsics, S, C, A, Y>(
 	chain: C,
 	author: A,
 	system: Y,
+	rate_limiter: RateLimiter,
 ) -> RpcHandler where
 	Block: BlockT + 'static,
 	ExHash: Send + Sync + 'static + sr_primitives::Serialize + sr_primitives::DeserializeOwned,
@@ -54,7 +64,7 @@ pub fn rpc_handler<Block: BlockT, ExHash, PendingExtrinsics, S, C, A, Y>(
 	A: apis::author::AuthorApi<ExHash, Block::Hash, Block::Extrinsic, PendingExtrinsics, Metadata=Metadata>,
 	Y: apis::system::SystemApi,
 {
-	let mut io = pubsub::PubSubHandler::default();
+	let mut io = pubsub::PubSubHandler::new(rpc::MetaIoHandler::with_middleware(rate_limiter));
 	io.extend_with(state.to_delegate());
 	io.extend_with(chain.to_delegate());
 	io.extend_with(author.to_delegate());
@@ -66,20 +76,44 @@ pub fn rpc_handler<Block: BlockT, ExHash, PendingExtrinsics, S, C, A, Y>(
 pub fn start_http(
 	addr: &std::net::SocketAddr,
 	io: RpcHandler,
+	rate_limiter: &RateLimiter,
 ) -> io::Result<http::Server> {
-	http::ServerBuilder::new(io)
+	let rate_limiter = rate_limiter.clone();
+	http::ServerBuilder::with_meta_extractor(io, move |request: &http::hyper::Request<http::hyper::Body>| {
+		let metadata = Metadata::default();
+		match forwarded_ip(&rate_limiter, request.headers().get("x-forwarded-for").and_then(|value| value.to_str().ok())) {
+			Some(remote_ip) => metadata.with_remote_ip(remote_ip),
+			None => metadata,
+		}
+	})
 		.threads(4)
 		.rest_api(http::RestApi::Unsecure)
 		.cors(http::DomainsValidation::Disabled)
 		.start_http(addr)
 }
 
-/// Start WS server listening on given address.
+/// Start WS server listening on given address. Opening the connection is charged to the IP
+/// address of the client.
 pub fn start_ws(
 	addr: &std::net::SocketAddr,
 	io: RpcHandler,
+	rate_limiter: &RateLimiter,
 ) -> io::Result<ws::Server> {
-	ws::ServerBuilder::with_meta_extractor(io, |context: &ws::RequestContext| Metadata::new(context.sender()))
+	let rate_limiter = rate_limiter.clone();
+	ws::ServerBuilder::with_meta_extractor(io, |context: &ws::RequestContext| Metadata::new(context.sender()).with_connection(context.session_id))
+		.request_middleware(move |request: &ws::ws::Request| {
+			let forwarded_for = request.header("x-forwarded-for").and_then(|value| ::std::str::from_utf8(value).ok());
+			let remote_ip = forwarded_ip(&rate_limiter, forwarded_for);
+			match rate_limiter.charge_ws_connection(remote_ip) {
+				Ok(()) => None,
+				Err(retry_after) => {
+					debug!(target: "rpc", "Rejecting Websockets connection from {:?}: rate limit exceeded", remote_ip);
+					let mut response = ws::ws::Response::new(429, "Too Many Requests", Vec::new());
+					response.headers_mut().push(("Retry-After".into(), format!("{}", (retry_after + 999) / 1000).into_bytes()));
+					Some(response)
+				},
+			}
+		})
 		.start(addr)
 		.map_err(|err| match err {
 			ws::Error(ws::ErrorKind::Io(io), _) => io,
@@ -90,3 +124,9 @@ pub fn start_ws(
 			}
 		})
 }
+
+/// IP address of the client, as reported by the trusted reverse proxies in the
+/// `X-Forwarded-For` header.
+fn forwarded_ip(rate_limiter: &RateLimiter, forwarded_for: Option<&str>) -> Option<IpAddr> {
+	forwarded_for.and_then(|forwarded_for| rate_limiter.client_ip(forwarded_for))
+}
@@ -0,0 +1,390 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rate limiting of the RPC requests.
+//!
+//! Every method has a cost, reflecting the amount of work the node does to serve it. Cost of
+//! the request is spent from the token buckets of the connection it has been received over and
+//! of the IP address it has been sent from. Buckets are refilled at the configured rate and
+//! requests are rejected while buckets are empty.
+//!
+//! The servers don't expose the socket address of the client, so the IP address is only known
+//! from the `X-Forwarded-For` header, which is only trusted if the reverse proxies are configured.
+//! Websockets requests are limited per connection, and opening the connection is limited per IP
+//! address. HTTP requests of the clients with unknown address are limited as if they all have been
+//! sent from the same IP address.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+use rpc::futures::future::{self, Either};
+use rpc::futures::Future;
+use Metadata;
+
+/// Error code of the rejected request, like HTTP `429 Too Many Requests`.
+pub const RATE_LIMITED_ERROR: i64 = 429;
+
+/// Number of seconds a client may spend cost units in a burst at the full rate.
+const BURST_SECS: u64 = 10;
+/// Number of tracked buckets of one kind after which refilled buckets are dropped.
+const MAX_TRACKED_BUCKETS: usize = 4096;
+/// Cost of opening the Websockets connection, spent from the bucket of the IP address.
+pub const WS_CONNECTION_COST: u64 = 10;
+/// Cost of the methods that are not listed in `METHOD_COSTS`.
+const DEFAULT_METHOD_COST: u64 = 1;
+/// Costs of the methods that are more expensive to serve than a plain storage query.
+const METHOD_COSTS: &[(&str, u64)] = &[
+	("state_call", 100),
+	("state_callAt", 100),
+	("system_dryRun", 100),
+	("state_queryStorage", 100),
+	("state_getPairs", 100),
+	("state_getPairsPaged", 100),
+	("state_getMetadata", 20),
+	("state_getStorageNamed", 5),
+	("chain_getBlock", 10),
+	("chain_getExtrinsicProof", 10),
+	("chain_getDivergenceReports", 10),
+	("author_submitExtrinsic", 10),
+	("author_submitRichExtrinsic", 10),
+	("author_submitLocalExtrinsic", 10),
+	("author_submitAndWatchExtrinsic", 10),
+	("author_pendingExtrinsics", 10),
+];
+
+/// Rate limits of the RPC requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimits {
+	/// Cost units per second that may be spent by a single connection. `None` if unlimited.
+	pub per_connection: Option<u64>,
+	/// Cost units per second that may be spent by all clients with the same IP address. `None` if unlimited.
+	pub per_ip: Option<u64>,
+	/// Addresses of the reverse proxies in front of the servers, trusted to report the address
+	/// of the client in the `X-Forwarded-For` header. The header is ignored if empty. The servers
+	/// must only be reachable through these proxies, since the socket address isn't checked.
+	pub trusted_proxies: Vec<IpAddr>,
+}
+
+/// Cost of the method call.
+pub fn method_cost(method: &str) -> u64 {
+	METHOD_COSTS.iter()
+		.find(|&&(name, _)| name == method)
+		.map(|&(_, cost)| cost)
+		.unwrap_or(DEFAULT_METHOD_COST)
+}
+
+/// Cost of the request, which is the total cost of all calls in the batch.
+pub fn request_cost(request: &rpc::Request) -> u64 {
+	let call_cost = |call: &rpc::Call| match *call {
+		rpc::Call::MethodCall(ref call) => method_cost(&call.method),
+		rpc::Call::Notification(ref notification) => method_cost(&notification.method),
+		_ => DEFAULT_METHOD_COST,
+	};
+
+	match *request {
+		rpc::Request::Single(ref call) => call_cost(call),
+		rpc::Request::Batch(ref calls) => calls.iter().map(call_cost).sum(),
+	}
+}
+
+/// RPC middleware, rejecting requests of clients that exceed the rate limits.
+///
+/// The limiter is shared by all servers it is cloned to.
+#[derive(Clone)]
+pub struct RateLimiter {
+	limits: RateLimits,
+	buckets: Arc<Mutex<Buckets>>,
+}
+
+#[derive(Default)]
+struct Buckets {
+	connections: HashMap<u64, Bucket>,
+	/// Buckets of the IP addresses. Clients with unknown address share the `None` bucket.
+	ips: HashMap<Option<IpAddr>, Bucket>,
+}
+
+/// Token bucket, holding cost units multiplied by 1000 (so that it is refilled every millisecond).
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+	tokens: i64,
+	updated: Instant,
+}
+
+impl RateLimiter {
+	/// Create new rate limiter.
+	pub fn new(limits: RateLimits) -> Self {
+		RateLimiter {
+			limits,
+			buckets: Default::default(),
+		}
+	}
+
+	/// IP address of the client, as reported in the `X-Forwarded-For` header. The entries are
+	/// appended by every proxy the request passes, so the last entry not set by one of the
+	/// trusted proxies is the address of the client. The previous entries are set by the client,
+	/// so they aren't trusted.
+	pub fn client_ip(&self, forwarded_for: &str) -> Option<IpAddr> {
+		if self.limits.trusted_proxies.is_empty() {
+			return None;
+		}
+
+		forwarded_for.rsplit(',')
+			.map(|ip| ip.trim().parse::<IpAddr>().ok())
+			.find(|ip| ip.map_or(true, |ip| !self.limits.trusted_proxies.contains(&ip)))
+			.and_then(|ip| ip)
+	}
+
+	/// Spend the cost of opening the Websockets connection from the bucket of the IP address.
+	/// Returns the number of milliseconds after which the connection may be retried if it is
+	/// rejected.
+	pub fn charge_ws_connection(&self, remote_ip: Option<IpAddr>) -> Result<(), u64> {
+		self.charge(None, remote_ip, WS_CONNECTION_COST, Instant::now())
+	}
+
+	/// Spend `cost` units from the buckets of the client. Returns the number of milliseconds
+	/// after which the request may be retried if it is rejected.
+	fn charge(&self, connection: Option<u64>, remote_ip: Option<IpAddr>, cost: u64, now: Instant) -> Result<(), u64> {
+		let mut buckets = self.buckets.lock();
+		let buckets = &mut *buckets;
+		// clients that can't be told apart are charged together
+		let remote_ip = match (connection, remote_ip) {
+			(Some(_), None) => None,
+			(_, remote_ip) => self.limits.per_ip.map(|rate| (remote_ip, rate)),
+		};
+		let connection = connection.and_then(|connection| self.limits.per_connection.map(|rate| (connection, rate)));
+
+		// the request is accepted only if all buckets have enough tokens
+		let mut retry_after = 0;
+		if let Some((connection, rate)) = connection {
+			retry_after = retry_after.max(refill(&mut buckets.connections, connection, rate, cost, now));
+		}
+		if let Some((remote_ip, rate)) = remote_ip {
+			retry_after = retry_after.max(refill(&mut buckets.ips, remote_ip, rate, cost, now));
+		}
+		if retry_after != 0 {
+			return Err(retry_after);
+		}
+
+		let cost = (cost * 1000) as i64;
+		if let Some((connection, _)) = connection {
+			buckets.connections.get_mut(&connection).expect("bucket is inserted by refill; qed").tokens -= cost;
+		}
+		if let Some((remote_ip, _)) = remote_ip {
+			buckets.ips.get_mut(&remote_ip).expect("bucket is inserted by refill; qed").tokens -= cost;
+		}
+		Ok(())
+	}
+}
+
+/// Refill the bucket of the client and return the number of milliseconds after which it'll have
+/// enough tokens to pay the `cost`, or 0 if it already has them.
+///
+/// Client with the full bucket may always pay, even if the cost exceeds the bucket capacity. It goes
+/// into debt then, which is paid off by refilling the bucket.
+fn refill<K: Hash + Eq + Copy>(buckets: &mut HashMap<K, Bucket>, key: K, rate: u64, cost: u64, now: Instant) -> u64 {
+	let rate = rate.max(1);
+	let capacity = (rate * BURST_SECS * 1000) as i64;
+	let rate = rate as i64;
+
+	if buckets.len() >= MAX_TRACKED_BUCKETS && !buckets.contains_key(&key) {
+		buckets.retain(|_, bucket| refilled(bucket, capacity, rate, now).tokens < capacity);
+	}
+
+	let bucket = buckets.entry(key).or_insert_with(|| Bucket { tokens: capacity, updated: now });
+	*bucket = refilled(bucket, capacity, rate, now);
+
+	let required = ((cost * 1000) as i64).min(capacity);
+	if bucket.tokens >= required {
+		return 0;
+	}
+
+	((required - bucket.tokens + rate - 1) / rate) as u64
+}
+
+fn refilled(bucket: &Bucket, capacity: i64, rate: i64, now: Instant) -> Bucket {
+	// `now` may be obtained by the other thread before the bucket has been updated
+	if now <= bucket.updated {
+		return *bucket;
+	}
+
+	let elapsed = now.duration_since(bucket.updated);
+	let elapsed_ms = (elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64) as i64;
+	Bucket {
+		tokens: capacity.min(bucket.tokens.saturating_add(elapsed_ms.saturating_mul(rate))),
+		updated: now,
+	}
+}
+
+fn rate_limited_error(cost: u64, retry_after: u64) -> rpc::Error {
+	rpc::Error {
+		code: rpc::ErrorCode::ServerError(RATE_LIMITED_ERROR),
+		message: "Too many requests".into(),
+		data: Some(json!({
+			"cost": cost,
+			"retryAfterMs": retry_after,
+		})),
+	}
+}
+
+/// Response to the rejected request, with the error for every method call of the request.
+fn rate_limited_response(request: rpc::Request, cost: u64, retry_after: u64) -> Option<rpc::Response> {
+	let failure = |call: rpc::Call| match call {
+		rpc::Call::MethodCall(call) => Some(rpc::Output::Failure(rpc::Failure {
+			jsonrpc: call.jsonrpc,
+			error: rate_limited_error(cost, retry_after),
+			id: call.id,
+		})),
+		_ => None,
+	};
+
+	match request {
+		rpc::Request::Single(call) => failure(call).map(rpc::Response::Single),
+		rpc::Request::Batch(calls) => {
+			let outputs = calls.into_iter().filter_map(failure).collect::<Vec<_>>();
+			if outputs.is_empty() {
+				None
+			} else {
+				Some(rpc::Response::Batch(outputs))
+			}
+		},
+	}
+}
+
+impl rpc::Middleware<Metadata> for RateLimiter {
+	type Future = rpc::FutureResponse;
+
+	fn on_request<F, X>(&self, request: rpc::Request, meta: Metadata, next: F) -> Either<Self::Future, X> where
+		F: FnOnce(rpc::Request, Metadata) -> X + Send,
+		X: Future<Item = Option<rpc::Response>, Error = ()> + Send + 'static,
+	{
+		let cost = request_cost(&request);
+		match self.charge(meta.connection(), meta.remote_ip(), cost, Instant::now()) {
+			Ok(()) => Either::B(next(request, meta)),
+			Err(retry_after) => {
+				debug!(
+					target: "rpc",
+					"Rejecting request of cost {} from {:?}/{:?}: rate limit exceeded",
+					cost, meta.connection(), meta.remote_ip(),
+				);
+				Either::A(Box::new(future::ok(rate_limited_response(request, cost, retry_after))))
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	fn limiter(per_connection: Option<u64>, per_ip: Option<u64>) -> RateLimiter {
+		RateLimiter::new(RateLimits { per_connection, per_ip, trusted_proxies: Vec::new() })
+	}
+
+	#[test]
+	fn method_costs_are_applied() {
+		assert_eq!(method_cost("state_getStorage"), DEFAULT_METHOD_COST);
+		assert_eq!(method_cost("state_call"), 100);
+
+		let request: rpc::Request = ::serde_json::from_str(r#"[
+			{"jsonrpc":"2.0","method":"state_call","params":[],"id":1},
+			{"jsonrpc":"2.0","method":"state_getStorage","params":[],"id":2}
+		]"#).unwrap();
+		assert_eq!(request_cost(&request), 101);
+	}
+
+	#[test]
+	fn requests_are_rejected_when_bucket_is_empty() {
+		let limiter = limiter(Some(10), None);
+		let now = Instant::now();
+
+		// full bucket holds 100 units
+		assert_eq!(limiter.charge(Some(1), None, 60, now), Ok(()));
+		assert_eq!(limiter.charge(Some(1), None, 60, now), Err(2000));
+		// other connections aren't affected
+		assert_eq!(limiter.charge(Some(2), None, 60, now), Ok(()));
+		// bucket is refilled over time
+		assert_eq!(limiter.charge(Some(1), None, 60, now + Duration::from_millis(1500)), Err(500));
+		assert_eq!(limiter.charge(Some(1), None, 60, now + Duration::from_secs(2)), Ok(()));
+	}
+
+	#[test]
+	fn expensive_requests_are_accepted_with_full_bucket() {
+		let limiter = limiter(Some(1), None);
+		let now = Instant::now();
+
+		assert_eq!(limiter.charge(Some(1), None, 100, now), Ok(()));
+		assert_eq!(limiter.charge(Some(1), None, 1, now + Duration::from_secs(89)), Err(2000));
+		assert_eq!(limiter.charge(Some(1), None, 1, now + Duration::from_secs(91)), Ok(()));
+	}
+
+	#[test]
+	fn all_limits_are_checked() {
+		let ip = "127.0.0.1".parse().unwrap();
+		let limiter = limiter(Some(100), Some(10));
+		let now = Instant::now();
+
+		assert_eq!(limiter.charge(Some(1), Some(ip), 100, now), Ok(()));
+		assert!(limiter.charge(Some(2), Some(ip), 100, now).is_err());
+		// rejected request isn't charged
+		assert_eq!(limiter.charge(Some(2), None, 1000, now), Ok(()));
+	}
+
+	#[test]
+	fn requests_of_unknown_clients_share_bucket() {
+		let limiter = limiter(Some(100), Some(10));
+		let now = Instant::now();
+
+		assert_eq!(limiter.charge(None, None, 100, now), Ok(()));
+		assert!(limiter.charge(None, None, 1, now).is_err());
+		// clients with known address or connection aren't affected
+		assert_eq!(limiter.charge(None, Some("127.0.0.1".parse().unwrap()), 1, now), Ok(()));
+		assert_eq!(limiter.charge(Some(1), None, 1, now), Ok(()));
+		assert_eq!(self::limiter(None, Some(10)).charge(Some(1), None, 1000, now), Ok(()));
+	}
+
+	#[test]
+	fn forwarded_for_is_only_trusted_from_configured_proxies() {
+		let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+		let client: IpAddr = "192.168.1.1".parse().unwrap();
+		assert_eq!(limiter(None, Some(1)).client_ip("192.168.1.1"), None);
+
+		let limiter = RateLimiter::new(RateLimits { per_connection: None, per_ip: Some(1), trusted_proxies: vec![proxy] });
+		assert_eq!(limiter.client_ip("192.168.1.1"), Some(client));
+		// entries set by the client are skipped
+		assert_eq!(limiter.client_ip("1.1.1.1, 192.168.1.1"), Some(client));
+		// entries set by the trusted proxies are skipped
+		assert_eq!(limiter.client_ip("1.1.1.1, 192.168.1.1, 10.0.0.1"), Some(client));
+		assert_eq!(limiter.client_ip("10.0.0.1"), None);
+		assert_eq!(limiter.client_ip("1.1.1.1, garbage"), None);
+	}
+
+	#[test]
+	fn ws_connections_are_limited_per_ip() {
+		let ip = "127.0.0.1".parse().unwrap();
+		let limiter = limiter(None, Some(1));
+
+		// full bucket holds 10 units
+		assert_eq!(limiter.charge_ws_connection(Some(ip)), Ok(()));
+		assert!(limiter.charge_ws_connection(Some(ip)).is_err());
+		assert_eq!(limiter.charge_ws_connection(None), Ok(()));
+		assert!(limiter.charge_ws_connection(None).is_err());
+	}
+}
@@ -62,6 +62,16 @@ impl From<Error> for rpc::Error {
 				message: e.description().into(),
 				data: Some(format!("{:?}", e).into()),
 			},
+			Error(ErrorKind::Pool(txpool::error::ErrorKind::QueuedWhileSyncing), _) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(ERROR + 3),
+				message: "Node is syncing. The extrinsic is queued and will be submitted once the sync is complete.".into(),
+				data: None,
+			},
+			Error(ErrorKind::Pool(txpool::error::ErrorKind::SyncQueueFull), _) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(ERROR + 4),
+				message: "Node is syncing and too many extrinsics are queued. Submit the extrinsic once the sync is complete.".into(),
+				data: None,
+			},
 			e => errors::internal(e),
 		}
 	}
@@ -34,8 +34,10 @@ use transaction_pool::{
 use jsonrpc_macros::pubsub;
 use jsonrpc_pubsub::SubscriptionId;
 use primitives::{Bytes, Blake2Hasher, H256};
+use rpc::BoxFuture;
 use rpc::futures::{Sink, Stream, Future};
 use runtime_primitives::{generic, traits};
+use execution_pool::ExecutionPool;
 use subscriptions::Subscriptions;
 
 pub mod error;
@@ -50,12 +52,16 @@ build_rpc_trait! {
 	pub trait AuthorApi<Hash, BlockHash, Extrinsic, PendingExtrinsics> {
 		type Metadata;
 
-		/// Submit extrinsic for inclusion in block.
+		/// Submit extrinsic for inclusion in block. The extrinsic is validated on the execution pool.
 		#[rpc(name = "author_submitRichExtrinsic")]
-		fn submit_rich_extrinsic(&self, Extrinsic) -> Result<Hash>;
+		fn submit_rich_extrinsic(&self, Extrinsic) -> BoxFuture<Hash>;
 		/// Submit hex-encoded extrinsic for inclusion in block.
 		#[rpc(name = "author_submitExtrinsic")]
-		fn submit_extrinsic(&self, Bytes) -> Result<Hash>;
+		fn submit_extrinsic(&self, Bytes) -> BoxFuture<Hash>;
+		/// Submit hex-encoded extrinsic for inclusion in blocks authored by this node only.
+		/// The extrinsic is never propagated to other peers.
+		#[rpc(name = "author_submitLocalExtrinsic")]
+		fn submit_local_extrinsic(&self, Bytes) -> BoxFuture<Hash>;
 
 		/// Returns all pending extrinsics, potentially grouped by sender.
 		#[rpc(name = "author_pendingExtrinsics")]
@@ -64,11 +70,42 @@ build_rpc_trait! {
 		#[pubsub(name = "author_extrinsicUpdate")] {
 			/// Submit an extrinsic to watch.
 			#[rpc(name = "author_submitAndWatchExtrinsic")]
-			fn watch_extrinsic(&self, Self::Metadata, pubsub::Subscriber<Status<Hash, BlockHash>>, Bytes);
+			fn watch_extrinsic(&self, _metadata: Self::Metadata, subscriber: pubsub::Subscriber<Status<ExHash<P>, BlockHash<P>>>, xt: Bytes) {
+		let client = self.client.clone();
+		let pool = self.pool.clone();
+		let submitted = self.execution_pool.execute(move || -> Result<_> {
+			let best_block_hash = client.info()?.chain.best_hash;
+			let dxt = <<P as PoolChainApi>::Block as traits::Block>::Extrinsic::decode(&mut &xt[..]).ok_or(error::Error::from(error::ErrorKind::BadFormat))?;
+			pool
+				.submit_and_watch(&generic::BlockId::hash(best_block_hash), dxt)
+				.map_err(|e| e.into_pool_error()
+					.map(Into::into)
+					.unwrap_or_else(|e| error::ErrorKind::Verification(Box::new(e)).into())
+				)
+		});
+
+		let subscriptions = self.subscriptions.clone();
+		self.subscriptions.spawn(submitted.then(move |submitted| {
+			let watcher = match submitted {
+				Ok(watcher) => watcher,
+				Err(err) => {
+					// reject the subscriber (ignore errors - we don't care if subscriber is no longer there).
+					let _ = subscriber.reject(err);
+					return Ok(());
+				},
+			};
+
+			subscriptions.add(subscriber, move |sink| {
+				sink
+					.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+					.send_all(watcher.into_stream().map(Ok))
+					.map(|_| ())
+			});
+			Ok(())
+		}));
+	}
 
-			/// Unsubscribe from extrinsic watching.
-			#[rpc(name = "author_unwatchExtrinsic")]
-			fn unwatch_extrinsic(&self, SubscriptionId) -> Result<bool>;
+	fn unwatch_extrinsic(&self, SubscriptionId) -> Result<bool>;
 		}
 
 	}
@@ -84,6 +121,8 @@ pub struct Author<B, E, P> where
 	pool: Arc<Pool<P>>,
 	/// Subscriptions manager
 	subscriptions: Subscriptions,
+	/// Pool validating the submitted extrinsics
+	execution_pool: ExecutionPool,
 }
 
 impl<B, E, P> Author<B, E, P> where
@@ -94,15 +133,47 @@ impl<B, E, P> Author<B, E, P> where
 		client: Arc<Client<B, E, <P as PoolChainApi>::Block>>,
 		pool: Arc<Pool<P>>,
 		subscriptions: Subscriptions,
+		execution_pool: ExecutionPool,
 	) -> Self {
 		Author {
 			client,
 			pool,
 			subscriptions,
+			execution_pool,
 		}
 	}
 }
 
+impl<B, E, P> Author<B, E, P> where
+	B: client::backend::Backend<<P as PoolChainApi>::Block, Blake2Hasher> + Send + Sync + 'static,
+	E: client::CallExecutor<<P as PoolChainApi>::Block, Blake2Hasher> + Send + Sync + 'static,
+	P: PoolChainApi + Sync + Send + 'static,
+	P::Block: traits::Block<Hash=H256>,
+	P::Error: 'static,
+{
+	/// Decode the extrinsic and submit it to the pool at the best block, on the execution pool.
+	/// Local extrinsics are never propagated to other peers.
+	fn submit<F>(&self, local: bool, decode: F) -> BoxFuture<ExHash<P>> where
+		F: FnOnce() -> Result<ExtrinsicFor<P>> + Send + 'static,
+	{
+		let client = self.client.clone();
+		let pool = self.pool.clone();
+		self.execution_pool.execute(move || -> Result<_> {
+			let xt = decode()?;
+			let at = generic::BlockId::hash(client.info()?.chain.best_hash);
+			let submitted = if local {
+				pool.submit_local(&at, xt)
+			} else {
+				pool.submit_one(&at, xt)
+			};
+			submitted.map_err(|e| e.into_pool_error()
+				.map(Into::into)
+				.unwrap_or_else(|e| error::ErrorKind::Verification(Box::new(e)).into())
+			)
+		})
+	}
+}
+
 impl<B, E, P> AuthorApi<ExHash<P>, BlockHash<P>, ExtrinsicFor<P>, Vec<ExtrinsicFor<P>>> for Author<B, E, P> where
 	B: client::backend::Backend<<P as PoolChainApi>::Block, Blake2Hasher> + Send + Sync + 'static,
 	E: client::CallExecutor<<P as PoolChainApi>::Block, Blake2Hasher> + Send + Sync + 'static,
@@ -112,19 +183,16 @@ impl<B, E, P> AuthorApi<ExHash<P>, BlockHash<P>, ExtrinsicFor<P>, Vec<ExtrinsicF
 {
 	type Metadata = ::metadata::Metadata;
 
-	fn submit_extrinsic(&self, xt: Bytes) -> Result<ExHash<P>> {
-		let dxt = Decode::decode(&mut &xt[..]).ok_or(error::Error::from(error::ErrorKind::BadFormat))?;
-		self.submit_rich_extrinsic(dxt)
+	fn submit_extrinsic(&self, xt: Bytes) -> BoxFuture<ExHash<P>> {
+		self.submit(false, move || Decode::decode(&mut &xt[..]).ok_or(error::Error::from(error::ErrorKind::BadFormat)))
 	}
 
-	fn submit_rich_extrinsic(&self, xt: <<P as PoolChainApi>::Block as traits::Block>::Extrinsic) -> Result<ExHash<P>> {
-		let best_block_hash = self.client.info()?.chain.best_hash;
-		self.pool
-			.submit_one(&generic::BlockId::hash(best_block_hash), xt)
-			.map_err(|e| e.into_pool_error()
-				.map(Into::into)
-				.unwrap_or_else(|e| error::ErrorKind::Verification(Box::new(e)).into())
-			)
+	fn submit_rich_extrinsic(&self, xt: <<P as PoolChainApi>::Block as traits::Block>::Extrinsic) -> BoxFuture<ExHash<P>> {
+		self.submit(false, move || Ok(xt))
+	}
+
+	fn submit_local_extrinsic(&self, xt: Bytes) -> BoxFuture<ExHash<P>> {
+		self.submit(true, move || Decode::decode(&mut &xt[..]).ok_or(error::Error::from(error::ErrorKind::BadFormat)))
 	}
 
 	fn pending_extrinsics(&self) -> Result<Vec<ExtrinsicFor<P>>> {
@@ -47,18 +47,35 @@ fn submit_transaction_should_not_cause_error() {
 		client: client.clone(),
 		pool: Arc::new(Pool::new(Default::default(), ChainApi::new(client))),
 		subscriptions: Subscriptions::new(runtime.executor()),
+		execution_pool: ExecutionPool::new(Default::default()),
 	};
 	let h: H256 = hex!("e10ad66bce51ef3e2a1167934ce3740d2d8c703810f9b314e89f2e783f75e826").into();
 
 	assert_matches!(
-		AuthorApi::submit_extrinsic(&p, uxt(Keyring::Alice, 1).encode().into()),
+		AuthorApi::submit_extrinsic(&p, uxt(Keyring::Alice, 1).encode().into()).wait(),
 		Ok(h2) if h == h2
 	);
 	assert!(
-		AuthorApi::submit_extrinsic(&p, uxt(Keyring::Alice, 1).encode().into()).is_err()
+		AuthorApi::submit_extrinsic(&p, uxt(Keyring::Alice, 1).encode().into()).wait().is_err()
 	);
 }
 
+#[test]
+fn submit_local_transaction_should_not_be_propagated() {
+	let runtime = runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let pool = Arc::new(Pool::new(Default::default(), ChainApi::new(client.clone())));
+	let p = Author {
+		client,
+		pool: pool.clone(),
+		subscriptions: Subscriptions::new(runtime.executor()),
+		execution_pool: ExecutionPool::new(Default::default()),
+	};
+
+	let hash = AuthorApi::submit_local_extrinsic(&p, uxt(Keyring::Alice, 0).encode().into()).wait().unwrap();
+	assert_eq!(pool.ready().map(|tx| (tx.hash, tx.propagate)).collect::<Vec<_>>(), vec![(hash, false)]);
+}
+
 #[test]
 fn submit_rich_transaction_should_not_cause_error() {
 	let runtime = runtime::Runtime::new().unwrap();
@@ -67,15 +84,16 @@ fn submit_rich_transaction_should_not_cause_error() {
 		client: client.clone(),
 		pool: Arc::new(Pool::new(Default::default(), ChainApi::new(client.clone()))),
 		subscriptions: Subscriptions::new(runtime.executor()),
+		execution_pool: ExecutionPool::new(Default::default()),
 	};
 	let h: H256 = hex!("fccc48291473c53746cd267cf848449edd7711ee6511fba96919d5f9f4859e4f").into();
 
 	assert_matches!(
-		AuthorApi::submit_rich_extrinsic(&p, uxt(Keyring::Alice, 0)),
+		AuthorApi::submit_rich_extrinsic(&p, uxt(Keyring::Alice, 0)).wait(),
 		Ok(h2) if h == h2
 	);
 	assert!(
-		AuthorApi::submit_rich_extrinsic(&p, uxt(Keyring::Alice, 0)).is_err()
+		AuthorApi::submit_rich_extrinsic(&p, uxt(Keyring::Alice, 0)).wait().is_err()
 	);
 }
 
@@ -89,6 +107,7 @@ fn should_watch_extrinsic() {
 		client,
 		pool: pool.clone(),
 		subscriptions: Subscriptions::new(runtime.executor()),
+		execution_pool: ExecutionPool::new(Default::default()),
 	};
 	let (subscriber, id_rx, data) = ::jsonrpc_macros::pubsub::Subscriber::new_test("test");
 
@@ -108,7 +127,7 @@ fn should_watch_extrinsic() {
 		let signature = Keyring::from_raw_public(tx.from.0).unwrap().sign(&tx.encode()).into();
 		Extrinsic { transfer: tx, signature }
 	};
-	AuthorApi::submit_rich_extrinsic(&p, replacement).unwrap();
+	AuthorApi::submit_rich_extrinsic(&p, replacement).wait().unwrap();
 	let (res, data) = runtime.block_on(data.into_future()).unwrap();
 	assert_eq!(
 		res,
@@ -129,9 +148,10 @@ fn should_return_pending_extrinsics() {
 		client,
 		pool: pool.clone(),
 		subscriptions: Subscriptions::new(runtime.executor()),
+		execution_pool: ExecutionPool::new(Default::default()),
 	};
 	let ex = uxt(Keyring::Alice, 0);
-	AuthorApi::submit_rich_extrinsic(&p, ex.clone()).unwrap();
+	AuthorApi::submit_rich_extrinsic(&p, ex.clone()).wait().unwrap();
  	assert_matches!(
 		p.pending_extrinsics(),
 		Ok(ref expected) if expected == &vec![ex]
@@ -0,0 +1,119 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC types of native/wasm divergence reports.
+
+use client;
+use primitives::Bytes;
+
+/// Report of a runtime call which has produced different results when executed
+/// natively and in wasm.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DivergenceReport<Hash> {
+	/// Hash of the block at which the call has been executed.
+	pub block: Hash,
+	/// Name of the runtime method that has been called.
+	pub method: String,
+	/// Result of the native execution.
+	pub native_result: ExecutionResult,
+	/// Result of the wasm execution.
+	pub wasm_result: ExecutionResult,
+	/// Storage entries that have been changed differently by both executions.
+	pub storage_diff: Vec<StorageDiffEntry>,
+}
+
+/// Result of a single execution.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExecutionResult {
+	/// Execution has succeeded with given output.
+	Ok(Bytes),
+	/// Execution has failed with given error.
+	Err(String),
+}
+
+/// Storage entry that has been changed differently by native and wasm executions.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageDiffEntry {
+	/// Storage key of the child trie, if the entry belongs to one.
+	pub child_storage_key: Option<Bytes>,
+	/// Key of the entry.
+	pub key: Bytes,
+	/// Change made by the native execution.
+	pub native: StorageChange,
+	/// Change made by the wasm execution.
+	pub wasm: StorageChange,
+}
+
+/// Change of a storage entry made by a single execution.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageChange {
+	/// Entry has not been touched.
+	Unchanged,
+	/// Entry has been deleted.
+	Deleted,
+	/// Entry has been set to given value.
+	Set(Bytes),
+}
+
+impl From<Option<Option<Vec<u8>>>> for StorageChange {
+	fn from(change: Option<Option<Vec<u8>>>) -> Self {
+		match change {
+			None => StorageChange::Unchanged,
+			Some(None) => StorageChange::Deleted,
+			Some(Some(value)) => StorageChange::Set(value.into()),
+		}
+	}
+}
+
+impl From<Result<Vec<u8>, String>> for ExecutionResult {
+	fn from(result: Result<Vec<u8>, String>) -> Self {
+		match result {
+			Ok(output) => ExecutionResult::Ok(output.into()),
+			Err(error) => ExecutionResult::Err(error),
+		}
+	}
+}
+
+impl<Hash> From<client::DivergenceReport<Hash>> for DivergenceReport<Hash> {
+	fn from(report: client::DivergenceReport<Hash>) -> Self {
+		let top = report.storage_diff.top.into_iter()
+			.map(|(key, native, wasm)| StorageDiffEntry {
+				child_storage_key: None,
+				key: key.into(),
+				native: native.into(),
+				wasm: wasm.into(),
+			});
+		let children = report.storage_diff.children.into_iter()
+			.map(|(storage_key, key, native, wasm)| StorageDiffEntry {
+				child_storage_key: Some(storage_key.into()),
+				key: key.into(),
+				native: native.into(),
+				wasm: wasm.into(),
+			});
+
+		DivergenceReport {
+			block: report.block,
+			method: report.method,
+			native_result: report.native_result.into(),
+			wasm_result: report.wasm_result.into(),
+			storage_diff: top.chain(children).collect(),
+		}
+	}
+}
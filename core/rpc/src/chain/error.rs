@@ -29,13 +29,25 @@ error_chain! {
 			description("not yet implemented"),
 			display("Method Not Implemented"),
 		}
+		/// Block belongs to the part of the history that has not been downloaded yet.
+		NotYetAvailable(block: String) {
+			description("block not yet available"),
+			display("Block {} is not yet available, the history is still being downloaded", block),
+		}
 	}
 }
 
+const ERROR: i64 = 2000;
+
 impl From<Error> for rpc::Error {
 	fn from(e: Error) -> Self {
 		match e {
 			Error(ErrorKind::Unimplemented, _) => errors::unimplemented(),
+			Error(ErrorKind::NotYetAvailable(block), _) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(ERROR + 1),
+				message: format!("Block {} is not yet available", block),
+				data: Some("The history is still being downloaded".into()),
+			},
 			e => errors::internal(e),
 		}
 	}
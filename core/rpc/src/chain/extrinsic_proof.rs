@@ -0,0 +1,34 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC types of extrinsic inclusion proofs.
+
+use primitives::Bytes;
+
+/// Extrinsic along with the proof of its inclusion into the extrinsics trie of the block.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtrinsicProof<Hash, Extrinsic> {
+	/// Hash of the block the extrinsic is included into.
+	pub block: Hash,
+	/// Index of the extrinsic within the block.
+	pub index: u32,
+	/// The extrinsic itself.
+	pub extrinsic: Extrinsic,
+	/// Trie nodes proving inclusion of the encoded extrinsic under its compact-encoded
+	/// index into the trie with the header's extrinsics root.
+	pub proof: Vec<Bytes>,
+}
@@ -18,24 +18,28 @@
 
 use std::sync::Arc;
 
-use client::{self, Client, BlockchainEvents};
+use client::{self, Client, BlockchainEvents, Notification};
 use jsonrpc_macros::{pubsub, Trailing};
 use jsonrpc_pubsub::SubscriptionId;
 use rpc::Result as RpcResult;
 use rpc::futures::{stream, Future, Sink, Stream};
 use primitives::H256;
-use runtime_primitives::generic::{BlockId, SignedBlock};
+use runtime_primitives::generic::{BlockId, SignedBlock, Block as RuntimeBlock};
 use runtime_primitives::traits::{Block as BlockT, Header, NumberFor};
 use runtime_version::RuntimeVersion;
 use primitives::{Blake2Hasher, storage};
 
 use subscriptions::Subscriptions;
 
+mod divergence;
 mod error;
+mod extrinsic_proof;
 #[cfg(test)]
 mod tests;
 
-use self::error::Result;
+use self::error::{ErrorKind, Result};
+pub use self::divergence::{DivergenceReport, ExecutionResult, StorageDiffEntry, StorageChange};
+pub use self::extrinsic_proof::ExtrinsicProof;
 
 build_rpc_trait! {
 	/// Substrate blockchain API
@@ -60,6 +64,15 @@ build_rpc_trait! {
 		#[rpc(name = "chain_getRuntimeVersion")]
 		fn runtime_version(&self, Trailing<Hash>) -> Result<RuntimeVersion>;
 
+		/// Get extrinsic with given index from the block, along with the proof of its
+		/// inclusion against the block extrinsics root.
+		#[rpc(name = "chain_getExtrinsicProof")]
+		fn extrinsic_proof(&self, Hash, u32) -> Result<Option<ExtrinsicProof<Hash, Extrinsic>>>;
+
+		/// Get the most recent reports of diverging native and wasm executions, oldest first.
+		#[rpc(name = "chain_getDivergenceReports")]
+		fn divergence_reports(&self) -> Result<Vec<DivergenceReport<Hash>>>;
+
 		#[pubsub(name = "chain_newHead")] {
 			/// New head subscription
 			#[rpc(name = "chain_subscribeNewHead", alias = ["subscribe_newHead", ])]
@@ -128,14 +141,25 @@ impl<B, E, Block> ChainApi<Block::Hash, Block::Header, NumberFor<Block>, Block::
 	fn block(&self, hash: Trailing<Block::Hash>)
 		-> Result<Option<SignedBlock<Block::Header, Block::Extrinsic>>>
 	{
-		let hash = self.unwrap_or_best(hash)?;
-		Ok(self.client.block(&BlockId::Hash(hash))?)
+		let id = BlockId::Hash(self.unwrap_or_best(hash)?);
+		// the light client retrieves the body from the remote nodes
+		let block = (self.client.header(&id)?, self.client.body_future(&id).wait()?, self.client.justification(&id)?);
+		Ok(match block {
+			(Some(header), Some(extrinsics), Some(justification)) =>
+				Some(SignedBlock { block: RuntimeBlock { header, extrinsics }, justification }),
+			_ => None,
+		})
 	}
 
 	fn block_hash(&self, number: Trailing<NumberFor<Block>>) -> Result<Option<Block::Hash>> {
 		Ok(match number.into() {
 			None => Some(self.client.info()?.chain.best_hash),
-			Some(number) => self.client.header(&BlockId::number(number))?.map(|h| h.hash()),
+			Some(number) => {
+				if self.client.block_gap()?.map_or(false, |(start, end)| number >= start && number <= end) {
+					return Err(ErrorKind::NotYetAvailable(format!("#{}", number)).into());
+				}
+				self.client.block_hash_future(number).wait()?
+			},
 		})
 	}
 
@@ -144,6 +168,20 @@ impl<B, E, Block> ChainApi<Block::Hash, Block::Header, NumberFor<Block>, Block::
 		Ok(self.client.runtime_version_at(&BlockId::Hash(at))?)
 	}
 
+	fn extrinsic_proof(&self, hash: Block::Hash, index: u32) -> Result<Option<ExtrinsicProof<Block::Hash, Block::Extrinsic>>> {
+		Ok(self.client.extrinsic_proof(&BlockId::Hash(hash), index)?
+			.map(|(extrinsic, proof)| ExtrinsicProof {
+				block: hash,
+				index,
+				extrinsic,
+				proof: proof.into_iter().map(Into::into).collect(),
+			}))
+	}
+
+	fn divergence_reports(&self) -> Result<Vec<DivergenceReport<Block::Hash>>> {
+		Ok(self.client.divergence_reports()?.into_iter().map(Into::into).collect())
+	}
+
 	fn subscribe_new_head(&self, _metadata: Self::Metadata, subscriber: pubsub::Subscriber<Block::Header>) {
 		self.subscriptions.add(subscriber, |sink| {
 			// send current head right at the start.
@@ -154,10 +192,23 @@ impl<B, E, Block> ChainApi<Block::Hash, Block::Header, NumberFor<Block>, Block::
 				})
 				.map_err(Into::into);
 
-			// send further subscriptions
-			let stream = self.client.import_notification_stream()
-				.filter(|notification| notification.is_new_best)
-				.map(|notification| Ok(notification.header))
+			// send further subscriptions, keeping the last announced head pinned
+			// until the subscription is dropped.
+			let client = self.client.clone();
+			let mut _pinned = None;
+			let stream = self.client.import_notification_stream("rpc-new-head")
+				.filter_map(move |notification| {
+					let header = match notification {
+						Notification::Item(notification) => match notification.is_new_best {
+							true => notification.header,
+							false => return None,
+						},
+						// notifications have been dropped => send the current head
+						Notification::Lagged(_) => client.best_block_header().ok()?,
+					};
+					_pinned = client.pin_block(header.hash()).ok();
+					Some(Ok(header))
+				})
 				.map_err(|e| warn!("Block notification stream error: {:?}", e));
 
 			sink
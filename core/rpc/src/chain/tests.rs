@@ -16,7 +16,7 @@
 
 use super::*;
 use jsonrpc_macros::pubsub;
-use test_client::{self, TestClient};
+use test_client::{self, runtime, keyring::Keyring, TestClient, BlockBuilderExt};
 use test_client::runtime::{Block, Header};
 use consensus::BlockOrigin;
 
@@ -206,3 +206,59 @@ fn should_return_runtime_version() {
 		}
 	);
 }
+
+#[test]
+fn should_return_divergence_reports() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+
+	let client = Chain {
+		client: Arc::new(test_client::new()),
+		subscriptions: Subscriptions::new(remote),
+	};
+
+	assert_matches!(
+		client.divergence_reports(),
+		Ok(ref reports) if reports.is_empty()
+	);
+}
+
+#[test]
+fn should_return_extrinsic_proof() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+
+	let api = Chain {
+		client: Arc::new(test_client::new()),
+		subscriptions: Subscriptions::new(remote),
+	};
+
+	let mut builder = api.client.new_block().unwrap();
+	builder.push_transfer(runtime::Transfer {
+		from: Keyring::Alice.to_raw_public().into(),
+		to: Keyring::Ferdie.to_raw_public().into(),
+		amount: 42,
+		nonce: 0,
+	}).unwrap();
+	let block = builder.bake().unwrap();
+	let block_hash = block.hash();
+	api.client.justify_and_import(BlockOrigin::Own, block).unwrap();
+
+	let header = api.client.header(&BlockId::Hash(block_hash)).unwrap().unwrap();
+	let proof = api.extrinsic_proof(block_hash, 0).unwrap().unwrap();
+	assert_eq!(proof.block, block_hash);
+	assert_eq!(proof.index, 0);
+	assert_eq!(
+		client::check_extrinsic_proof::<_, Blake2Hasher, runtime::Extrinsic>(
+			&header,
+			0,
+			proof.proof.into_iter().map(|node| node.0).collect(),
+		).unwrap(),
+		proof.extrinsic
+	);
+
+	assert_matches!(
+		api.extrinsic_proof(block_hash, 1),
+		Ok(None)
+	);
+}
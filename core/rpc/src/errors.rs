@@ -24,6 +24,14 @@ pub fn unimplemented() -> rpc::Error {
 	}
 }
 
+pub fn unsafe_denied() -> rpc::Error {
+	rpc::Error {
+		code: rpc::ErrorCode::ServerError(2),
+		message: "Method is unsafe and not allowed on this node".into(),
+		data: None,
+	}
+}
+
 pub fn internal<E: ::std::fmt::Debug>(e: E) -> rpc::Error {
 	warn!("Unknown error: {:?}", e);
 	rpc::Error {
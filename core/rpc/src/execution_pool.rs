@@ -0,0 +1,193 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Thread pool executing the runtime calls of the RPC requests.
+//!
+//! Runtime calls may take arbitrarily long, so they're moved off the RPC server threads, which
+//! keep serving the cheap requests meanwhile. The number of calls waiting for a free thread is
+//! limited, requests that don't fit into the queue are rejected right away.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, mpsc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use parking_lot::Mutex;
+use rpc::{self, BoxFuture};
+use rpc::futures::{future, Future};
+use rpc::futures::sync::oneshot;
+
+/// Error code of the request rejected because the execution pool is saturated, like
+/// HTTP `503 Service Unavailable`.
+pub const EXECUTION_POOL_SATURATED_ERROR: i64 = 503;
+
+/// Configuration of the execution pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionPoolConfig {
+	/// Number of threads executing the runtime calls.
+	pub threads: usize,
+	/// Maximal number of runtime calls that are executed or waiting for a free thread.
+	pub max_queued: usize,
+}
+
+impl Default for ExecutionPoolConfig {
+	fn default() -> Self {
+		ExecutionPoolConfig {
+			threads: 4,
+			max_queued: 64,
+		}
+	}
+}
+
+trait Job: Send {
+	fn run(self: Box<Self>);
+}
+
+impl<F: FnOnce() + Send> Job for F {
+	fn run(self: Box<Self>) {
+		(*self)()
+	}
+}
+
+/// Pool of threads executing the runtime calls. The threads are stopped once all clones of
+/// the pool are dropped.
+#[derive(Clone)]
+pub struct ExecutionPool {
+	sender: Arc<Mutex<mpsc::Sender<Box<Job>>>>,
+	queued: Arc<AtomicUsize>,
+	max_queued: usize,
+}
+
+impl ExecutionPool {
+	/// Create new pool and start its threads.
+	pub fn new(config: ExecutionPoolConfig) -> Self {
+		let (sender, receiver) = mpsc::channel::<Box<Job>>();
+		let receiver = Arc::new(Mutex::new(receiver));
+		for index in 0..config.threads.max(1) {
+			let receiver = receiver.clone();
+			let spawned = thread::Builder::new()
+				.name(format!("rpc-execution-{}", index))
+				.spawn(move || loop {
+					let job = match receiver.lock().recv() {
+						Ok(job) => job,
+						Err(_) => break,
+					};
+					job.run();
+				});
+			if let Err(e) = spawned {
+				warn!(target: "rpc", "Failed to spawn RPC execution thread: {:?}", e);
+			}
+		}
+
+		ExecutionPool {
+			sender: Arc::new(Mutex::new(sender)),
+			queued: Arc::new(AtomicUsize::new(0)),
+			max_queued: config.max_queued.max(1),
+		}
+	}
+
+	/// Execute the call on the pool. Fails with `EXECUTION_POOL_SATURATED_ERROR` if there are
+	/// already `max_queued` calls waiting or being executed. A panicking call fails with the
+	/// internal error and leaves the executing thread serving the next calls.
+	pub fn execute<F, T, E>(&self, call: F) -> BoxFuture<T> where
+		F: FnOnce() -> Result<T, E> + Send + 'static,
+		T: Send + 'static,
+		E: Into<rpc::Error> + Send + 'static,
+	{
+		let queued = self.queued.fetch_add(1, Ordering::AcqRel);
+		if queued >= self.max_queued {
+			self.queued.fetch_sub(1, Ordering::AcqRel);
+			debug!(target: "rpc", "Rejecting runtime call: {} calls are queued already", queued);
+			return Box::new(future::err(saturated_error(self.max_queued)));
+		}
+
+		let (result_sender, result_receiver) = oneshot::channel();
+		let counter = self.queued.clone();
+		let job = move || {
+			let result = match panic::catch_unwind(AssertUnwindSafe(call)) {
+				Ok(result) => result.map_err(Into::into),
+				Err(_) => {
+					warn!(target: "rpc", "Runtime call has panicked");
+					Err(rpc::Error::internal_error())
+				},
+			};
+			counter.fetch_sub(1, Ordering::AcqRel);
+			let _ = result_sender.send(result);
+		};
+		if self.sender.lock().send(Box::new(job)).is_err() {
+			self.queued.fetch_sub(1, Ordering::AcqRel);
+			return Box::new(future::err(saturated_error(self.max_queued)));
+		}
+
+		Box::new(result_receiver
+			.map_err(|_| rpc::Error::internal_error())
+			.and_then(|result| result))
+	}
+}
+
+fn saturated_error(max_queued: usize) -> rpc::Error {
+	rpc::Error {
+		code: rpc::ErrorCode::ServerError(EXECUTION_POOL_SATURATED_ERROR),
+		message: "Runtime execution pool is saturated".into(),
+		data: Some(max_queued.into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::mpsc::channel;
+
+	#[test]
+	fn calls_are_executed_on_pool() {
+		let pool = ExecutionPool::new(Default::default());
+		let rpc_thread = thread::current().id();
+		let result = pool.execute(move || Ok::<_, rpc::Error>(thread::current().id() != rpc_thread));
+		assert_eq!(result.wait(), Ok(true));
+
+		let result = pool.execute(|| Err::<(), _>(rpc::Error::invalid_request()));
+		assert_eq!(result.wait(), Err(rpc::Error::invalid_request()));
+	}
+
+	#[test]
+	fn calls_are_rejected_when_saturated() {
+		let pool = ExecutionPool::new(ExecutionPoolConfig { threads: 1, max_queued: 2 });
+		let (unblock, blocked) = channel::<()>();
+		let blocked = Arc::new(Mutex::new(blocked));
+
+		let calls: Vec<_> = (0..2).map(|_| {
+			let blocked = blocked.clone();
+			pool.execute(move || blocked.lock().recv().map_err(|_| rpc::Error::internal_error()))
+		}).collect();
+		let rejected = pool.execute(|| Ok::<_, rpc::Error>(()));
+		assert_eq!(rejected.wait().unwrap_err().code, rpc::ErrorCode::ServerError(EXECUTION_POOL_SATURATED_ERROR));
+
+		unblock.send(()).unwrap();
+		unblock.send(()).unwrap();
+		for call in calls {
+			assert_eq!(call.wait(), Ok(()));
+		}
+		assert_eq!(pool.execute(|| Ok::<_, rpc::Error>(())).wait(), Ok(()));
+	}
+
+	#[test]
+	fn panicking_calls_do_not_stop_pool() {
+		let pool = ExecutionPool::new(ExecutionPoolConfig { threads: 1, max_queued: 1 });
+		let result = pool.execute(|| -> Result<(), rpc::Error> { panic!("runtime call panicked") });
+		assert_eq!(result.wait(), Err(rpc::Error::internal_error()));
+		assert_eq!(pool.execute(|| Ok::<_, rpc::Error>(())).wait(), Ok(()));
+	}
+}
@@ -24,6 +24,8 @@ extern crate jsonrpc_core as rpc;
 extern crate jsonrpc_pubsub;
 extern crate parking_lot;
 extern crate parity_codec as codec;
+extern crate serde;
+extern crate serde_json;
 extern crate substrate_client as client;
 extern crate substrate_transaction_pool as transaction_pool;
 extern crate substrate_primitives as primitives;
@@ -37,6 +39,8 @@ extern crate error_chain;
 extern crate jsonrpc_macros;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 
 #[cfg(test)]
 #[macro_use]
@@ -52,9 +56,11 @@ extern crate substrate_consensus_common as consensus;
 extern crate rustc_hex;
 
 mod errors;
+mod execution_pool;
 mod helpers;
 mod subscriptions;
 
+pub use execution_pool::{ExecutionPool, ExecutionPoolConfig, EXECUTION_POOL_SATURATED_ERROR};
 pub use subscriptions::Subscriptions;
 
 pub mod author;
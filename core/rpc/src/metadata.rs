@@ -15,6 +15,7 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 //! RPC Metadata
+use std::net::IpAddr;
 use std::sync::Arc;
 
 use jsonrpc_pubsub::{Session, PubSubMetadata};
@@ -28,6 +29,8 @@ use rpc::futures::sync::mpsc;
 #[derive(Default, Clone)]
 pub struct Metadata {
 	session: Option<Arc<Session>>,
+	connection: Option<u64>,
+	remote_ip: Option<IpAddr>,
 }
 
 impl ::rpc::Metadata for Metadata {}
@@ -42,9 +45,33 @@ impl Metadata {
 	pub fn new(transport: mpsc::Sender<String>) -> Self {
 		Metadata {
 			session: Some(Arc::new(Session::new(transport))),
+			connection: None,
+			remote_ip: None,
 		}
 	}
 
+	/// Set id of the persistent connection the request has been received over.
+	pub fn with_connection(mut self, connection: u64) -> Self {
+		self.connection = Some(connection);
+		self
+	}
+
+	/// Set IP address of the remote client.
+	pub fn with_remote_ip(mut self, remote_ip: IpAddr) -> Self {
+		self.remote_ip = Some(remote_ip);
+		self
+	}
+
+	/// Id of the persistent connection the request has been received over, if known.
+	pub fn connection(&self) -> Option<u64> {
+		self.connection
+	}
+
+	/// IP address of the remote client, if known.
+	pub fn remote_ip(&self) -> Option<IpAddr> {
+		self.remote_ip
+	}
+
 	/// Create new `Metadata` for tests.
 	#[cfg(test)]
 	pub fn new_test() -> (mpsc::Receiver<String>, Self) {
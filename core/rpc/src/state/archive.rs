@@ -0,0 +1,58 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Access to the state that has been pruned from the local database.
+
+use client::light::fetcher::{Fetcher, RemoteCallRequest, RemoteReadRequest};
+use primitives::storage::{StorageKey, StorageData};
+use rpc::futures::{Future, IntoFuture};
+use runtime_primitives::traits::{Block as BlockT, Header as HeaderT};
+
+use super::error::Result;
+
+/// Remote source of the pruned state, e.g. an archive node. The data is checked against the
+/// locally known header of the block before it is returned.
+pub trait ArchiveFallback<Block: BlockT>: Send + Sync {
+	/// Read the storage value at the state of the block.
+	fn storage(&self, header: Block::Header, key: &StorageKey) -> Result<Option<StorageData>>;
+
+	/// Call the runtime method at the state of the block and return the encoded result.
+	fn call(&self, header: Block::Header, method: &str, call_data: &[u8]) -> Result<Vec<u8>>;
+}
+
+impl<Block: BlockT, F: Fetcher<Block>> ArchiveFallback<Block> for F {
+	fn storage(&self, header: Block::Header, key: &StorageKey) -> Result<Option<StorageData>> {
+		let mut values = self.remote_read(RemoteReadRequest {
+			block: header.hash(),
+			header,
+			keys: vec![key.0.clone()],
+			retry_count: None,
+		}).into_future().wait()?;
+		Ok(values.pop().and_then(|value| value).map(StorageData))
+	}
+
+	fn call(&self, header: Block::Header, method: &str, call_data: &[u8]) -> Result<Vec<u8>> {
+		let result = self.remote_call(RemoteCallRequest {
+			block: header.hash(),
+			header,
+			method: method.into(),
+			call_data: call_data.to_vec(),
+			trace_keys: false,
+			retry_count: None,
+		}).into_future().wait()?;
+		Ok(result.return_data)
+	}
+}
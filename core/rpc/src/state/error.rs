@@ -35,13 +35,25 @@ error_chain! {
 			description("not implemented yet"),
 			display("Method Not Implemented"),
 		}
+		/// There are more storage entries with given prefix than may be returned at once.
+		TooManyPairs(max: u32) {
+			description("too many storage entries"),
+			display("More than {} storage entries with given prefix, use state_getPairsPaged", max),
+		}
 	}
 }
 
+const ERROR: i64 = 3000;
+
 impl From<Error> for rpc::Error {
 	fn from(e: Error) -> Self {
 		match e {
 			Error(ErrorKind::Unimplemented, _) => errors::unimplemented(),
+			Error(ErrorKind::TooManyPairs(max), _) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(ERROR + 1),
+				message: format!("More than {} storage entries with given prefix, use state_getPairsPaged", max),
+				data: None,
+			},
 			e => errors::internal(e),
 		}
 	}
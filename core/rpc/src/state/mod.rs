@@ -22,6 +22,7 @@ use std::{
 };
 
 use client::{self, Client, CallExecutor, BlockchainEvents, runtime_api::Metadata};
+use client::storage_query::{TypeRegistry, Value};
 use jsonrpc_macros::Trailing;
 use jsonrpc_macros::pubsub;
 use jsonrpc_pubsub::SubscriptionId;
@@ -29,32 +30,50 @@ use primitives::H256;
 use primitives::hexdisplay::HexDisplay;
 use primitives::storage::{StorageKey, StorageData, StorageChangeSet};
 use primitives::{Blake2Hasher, Bytes};
-use rpc::Result as RpcResult;
-use rpc::futures::{stream, Future, Sink, Stream};
+use rpc::{BoxFuture, Result as RpcResult};
+use rpc::futures::{future, stream, Future, Sink, Stream};
 use runtime_primitives::generic::BlockId;
 use runtime_primitives::traits::{Block as BlockT, Header};
 
+use execution_pool::ExecutionPool;
 use subscriptions::Subscriptions;
 
+mod archive;
 mod error;
 #[cfg(test)]
 mod tests;
 
 use self::error::Result;
 
+pub use self::archive::ArchiveFallback;
+
+/// Maximal number of storage entries returned by `state_getPairs` and `state_getPairsPaged`.
+pub const MAX_PAIRS_PAGE: u32 = 1000;
+
 build_rpc_trait! {
 	/// Substrate state API
 	pub trait StateApi<Hash> {
 		type Metadata;
 
-		/// Call a contract at a block's state.
+		/// Call a contract at a block's state. The call is executed on the execution pool.
 		#[rpc(name = "state_call", alias = ["state_callAt", ])]
-		fn call(&self, String, Bytes, Trailing<Hash>) -> Result<Bytes>;
+		fn call(&self, String, Bytes, Trailing<Hash>) -> BoxFuture<Bytes>;
 
 		/// Returns a storage entry at a specific block's state.
 		#[rpc(name = "state_getStorage", alias = ["state_getStorageAt", ])]
 		fn storage(&self, StorageKey, Trailing<Hash>) -> Result<Option<StorageData>>;
 
+		/// Returns all storage entries with given key prefix at a block's state. Fails if there are
+		/// more than `MAX_PAIRS_PAGE` of them.
+		#[rpc(name = "state_getPairs")]
+		fn storage_pairs(&self, StorageKey, Trailing<Hash>) -> Result<Vec<(StorageKey, StorageData)>>;
+
+		/// Returns at most given number of storage entries with given key prefix at a block's state,
+		/// whose keys follow given key (if any), in the order of the keys. At most `MAX_PAIRS_PAGE`
+		/// entries are returned at once.
+		#[rpc(name = "state_getPairsPaged")]
+		fn storage_pairs_paged(&self, StorageKey, u32, Option<StorageKey>, Trailing<Hash>) -> Result<Vec<(StorageKey, StorageData)>>;
+
 		/// Returns the hash of a storage entry at a block's state.
 		#[rpc(name = "state_getStorageHash", alias = ["state_getStorageHashAt", ])]
 		fn storage_hash(&self, StorageKey, Trailing<Hash>) -> Result<Option<Hash>>;
@@ -63,6 +82,11 @@ build_rpc_trait! {
 		#[rpc(name = "state_getStorageSize", alias = ["state_getStorageSizeAt", ])]
 		fn storage_size(&self, StorageKey, Trailing<Hash>) -> Result<Option<u64>>;
 
+		/// Returns a storage entry, addressed by module and item names, decoded using the runtime
+		/// metadata. The key of a map item is given encoded.
+		#[rpc(name = "state_getStorageNamed")]
+		fn storage_named(&self, String, String, Option<Bytes>, Trailing<Hash>) -> Result<Option<Value>>;
+
 		/// Returns the runtime metadata as an opaque blob.
 		#[rpc(name = "state_getMetadata")]
 		fn metadata(&self, Trailing<Hash>) -> Result<Bytes>;
@@ -92,16 +116,28 @@ pub struct State<B, E, Block: BlockT> {
 	client: Arc<Client<B, E, Block>>,
 	/// Current subscriptions.
 	subscriptions: Subscriptions,
+	/// Pool executing the runtime calls.
+	execution_pool: ExecutionPool,
+	/// Source of the state pruned from the local database.
+	archive: Option<Arc<ArchiveFallback<Block>>>,
 }
 
 impl<B, E, Block: BlockT> State<B, E, Block> {
 	/// Create new State API RPC handler.
-	pub fn new(client: Arc<Client<B, E, Block>>, subscriptions: Subscriptions) -> Self {
+	pub fn new(client: Arc<Client<B, E, Block>>, subscriptions: Subscriptions, execution_pool: ExecutionPool) -> Self {
 		Self {
 			client,
 			subscriptions,
+			execution_pool,
+			archive: None,
 		}
 	}
+
+	/// Serve the queries targeting the pruned state from the given archive instead of failing.
+	pub fn with_archive_fallback(mut self, archive: Arc<ArchiveFallback<Block>>) -> Self {
+		self.archive = Some(archive);
+		self
+	}
 }
 
 impl<B, E, Block> State<B, E, Block> where
@@ -121,23 +157,66 @@ impl<B, E, Block> StateApi<Block::Hash> for State<B, E, Block> where
 {
 	type Metadata = ::metadata::Metadata;
 
-	fn call(&self, method: String, data: Bytes, block: Trailing<Block::Hash>) -> Result<Bytes> {
-		let block = self.unwrap_or_best(block)?;
+	fn call(&self, method: String, data: Bytes, block: Trailing<Block::Hash>) -> BoxFuture<Bytes> {
+		let block = match self.unwrap_or_best(block) {
+			Ok(block) => block,
+			Err(e) => return Box::new(future::err(e.into())),
+		};
 		trace!(target: "rpc", "Calling runtime at {:?} for method {} ({})", block, method, HexDisplay::from(&data.0));
-		let return_data = self.client
-			.executor()
-			.call(
-				&BlockId::Hash(block),
-				&method, &data.0
-			)?
-			.return_data;
-		Ok(Bytes(return_data))
+		let client = self.client.clone();
+		let archive = self.archive.clone();
+		self.execution_pool.execute(move || -> Result<Bytes> {
+			let return_data = match client.executor().call(&BlockId::Hash(block), &method, &data.0) {
+				Ok(result) => result.return_data,
+				Err(error) => match pruned_state_fallback(&client, archive.as_ref(), block) {
+					Some((archive, header)) => {
+						debug!(target: "rpc", "State at {:?} is pruned, calling {} at the archive", block, method);
+						archive.call(header, &method, &data.0)?
+					},
+					None => return Err(error.into()),
+				},
+			};
+			Ok(Bytes(return_data))
+		})
 	}
 
 	fn storage(&self, key: StorageKey, block: Trailing<Block::Hash>) -> Result<Option<StorageData>> {
 		let block = self.unwrap_or_best(block)?;
 		trace!(target: "rpc", "Querying storage at {:?} for key {}", block, HexDisplay::from(&key.0));
-		Ok(self.client.storage(&BlockId::Hash(block), &key)?)
+		match self.client.storage(&BlockId::Hash(block), &key) {
+			Ok(value) => Ok(value),
+			Err(error) => match pruned_state_fallback(&self.client, self.archive.as_ref(), block) {
+				Some((archive, header)) => {
+					debug!(target: "rpc", "State at {:?} is pruned, querying the archive", block);
+					archive.storage(header, &key)
+				},
+				None => Err(error.into()),
+			},
+		}
+	}
+
+	fn storage_pairs(&self, key_prefix: StorageKey, block: Trailing<Block::Hash>) -> Result<Vec<(StorageKey, StorageData)>> {
+		let block = self.unwrap_or_best(block)?;
+		trace!(target: "rpc", "Querying storage pairs at {:?} for key prefix {}", block, HexDisplay::from(&key_prefix.0));
+		let max = MAX_PAIRS_PAGE as usize;
+		let pairs = self.client.storage_pairs_paged(&BlockId::Hash(block), &key_prefix, None, max + 1)?;
+		if pairs.len() > max {
+			return Err(error::ErrorKind::TooManyPairs(MAX_PAIRS_PAGE).into());
+		}
+		Ok(pairs)
+	}
+
+	fn storage_pairs_paged(
+		&self,
+		key_prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Trailing<Block::Hash>,
+	) -> Result<Vec<(StorageKey, StorageData)>> {
+		let block = self.unwrap_or_best(block)?;
+		trace!(target: "rpc", "Querying {} storage pairs at {:?} for key prefix {}", count, block, HexDisplay::from(&key_prefix.0));
+		let count = ::std::cmp::min(count, MAX_PAIRS_PAGE) as usize;
+		Ok(self.client.storage_pairs_paged(&BlockId::Hash(block), &key_prefix, start_key.as_ref(), count)?)
 	}
 
 	fn storage_hash(&self, key: StorageKey, block: Trailing<Block::Hash>) -> Result<Option<Block::Hash>> {
@@ -149,6 +228,13 @@ impl<B, E, Block> StateApi<Block::Hash> for State<B, E, Block> where
 		Ok(self.storage(key, block)?.map(|x| x.0.len() as u64))
 	}
 
+	fn storage_named(&self, module: String, item: String, key: Option<Bytes>, block: Trailing<Block::Hash>) -> Result<Option<Value>> {
+		let block = self.unwrap_or_best(block)?;
+		trace!(target: "rpc", "Querying storage item {}::{} at {:?}", module, item, block);
+		let key = key.as_ref().map(|key| &key.0[..]);
+		Ok(self.client.storage_named(&BlockId::Hash(block), &module, &item, key, &TypeRegistry::default())?)
+	}
+
 	fn metadata(&self, block: Trailing<Block::Hash>) -> Result<Bytes> {
 		let block = self.unwrap_or_best(block)?;
 		self.client.metadata(&BlockId::Hash(block)).map(Bytes).map_err(Into::into)
@@ -190,32 +276,24 @@ impl<B, E, Block> StateApi<Block::Hash> for State<B, E, Block> where
 					blocks.reverse();
 					blocks
 				};
-				let mut result = Vec::new();
-				let mut last_state: HashMap<_, Option<_>> = Default::default();
-				for block in blocks {
-					let mut changes = vec![];
-					let id = BlockId::hash(block.clone());
-
-					for key in &keys {
-						let (has_changed, data) = {
-							let curr_data = self.client.storage(&id, key)?;
-							let prev_data = last_state.get(key).and_then(|x| x.as_ref());
-
-							(curr_data.as_ref() != prev_data, curr_data)
-						};
-
-						if has_changed {
-							changes.push((key.clone(), data.clone()));
+				let mut changes: HashMap<Block::Hash, Vec<_>> = Default::default();
+				for key in &keys {
+					let history = self.client.storage_history(key, from.hash(), to.hash())?;
+					for (index, (block, value)) in history.into_iter().enumerate() {
+						// initial values are only reported for existing entries
+						if index == 0 && value.is_none() {
+							continue;
 						}
-
-						last_state.insert(key.clone(), data);
+						changes.entry(block).or_insert_with(Vec::new).push((key.clone(), value));
 					}
+				}
 
-					result.push(StorageChangeSet {
+				let result = blocks.into_iter()
+					.map(|block| StorageChangeSet {
+						changes: changes.remove(&block).unwrap_or_default(),
 						block,
-						changes,
-					});
-				}
+					})
+					.collect();
 				Ok(result)
 			},
 			(from, to) => bail!(invalid_block_range(from, to, "Invalid range or unknown block".into())),
@@ -272,6 +350,26 @@ impl<B, E, Block> StateApi<Block::Hash> for State<B, E, Block> where
 	}
 }
 
+/// Returns the archive fallback along with the header of the block if the block is known, but
+/// its state has been pruned from the local database.
+fn pruned_state_fallback<B, E, Block>(
+	client: &Client<B, E, Block>,
+	archive: Option<&Arc<ArchiveFallback<Block>>>,
+	block: Block::Hash,
+) -> Option<(Arc<ArchiveFallback<Block>>, Block::Header)> where
+	Block: BlockT<Hash=H256>,
+	B: client::backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	let archive = archive?;
+	let id = BlockId::Hash(block);
+	if client.state_at(&id).is_ok() {
+		return None;
+	}
+	let header = client.header(&id).ok()??;
+	Some((archive.clone(), header))
+}
+
 fn invalid_block_range<H: Header>(from: Option<H>, to: Option<H>, reason: String) -> error::ErrorKind {
 	let to_string = |x: Option<H>| match x {
 		None => "unknown hash".into(),
@@ -15,7 +15,6 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use super::*;
-use self::error::{Error, ErrorKind};
 
 use consensus::BlockOrigin;
 use jsonrpc_macros::pubsub;
@@ -27,7 +26,7 @@ fn should_return_storage() {
 	let core = ::tokio::runtime::Runtime::new().unwrap();
 	let client = Arc::new(test_client::new());
 	let genesis_hash = client.genesis_hash();
-	let client = State::new(client, Subscriptions::new(core.executor()));
+	let client = State::new(client, Subscriptions::new(core.executor()), ExecutionPool::new(Default::default()));
 
 	assert_matches!(
 		client.storage(StorageKey(vec![10]), Some(genesis_hash).into()),
@@ -36,16 +35,52 @@ fn should_return_storage() {
 }
 
 #[test]
-fn should_call_contract() {
+fn should_return_storage_pairs() {
 	let core = ::tokio::runtime::Runtime::new().unwrap();
 	let client = Arc::new(test_client::new());
 	let genesis_hash = client.genesis_hash();
-	let client = State::new(client, Subscriptions::new(core.executor()));
+	let client = State::new(client, Subscriptions::new(core.executor()), ExecutionPool::new(Default::default()));
 
 	assert_matches!(
-		client.call("balanceOf".into(), Bytes(vec![1,2,3]), Some(genesis_hash).into()),
-		Err(Error(ErrorKind::Client(client::error::ErrorKind::Execution(_)), _))
-	)
+		client.storage_pairs(StorageKey(b":code".to_vec()), Some(genesis_hash).into()),
+		Ok(ref pairs) if pairs.len() == 1 && pairs[0].0 == StorageKey(b":code".to_vec())
+	);
+	assert_matches!(
+		client.storage_pairs(StorageKey(vec![10]), Some(genesis_hash).into()),
+		Ok(ref pairs) if pairs.is_empty()
+	);
+}
+
+#[test]
+fn should_return_storage_pairs_paged() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let genesis_hash = client.genesis_hash();
+	let client = State::new(client, Subscriptions::new(core.executor()), ExecutionPool::new(Default::default()));
+	let all = client.storage_pairs(StorageKey(Vec::new()), Some(genesis_hash).into()).unwrap();
+	assert!(all.len() > 2);
+
+	let first = client.storage_pairs_paged(StorageKey(Vec::new()), 2, None, Some(genesis_hash).into()).unwrap();
+	assert_eq!(first.len(), 2);
+	let rest = client.storage_pairs_paged(
+		StorageKey(Vec::new()),
+		MAX_PAIRS_PAGE,
+		Some(first[1].0.clone()),
+		Some(genesis_hash).into(),
+	).unwrap();
+	assert_eq!(first.into_iter().chain(rest).collect::<Vec<_>>(), all);
+}
+
+#[test]
+fn should_call_contract() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let genesis_hash = client.genesis_hash();
+	let client = State::new(client, Subscriptions::new(core.executor()), ExecutionPool::new(Default::default()));
+
+	let error = client.call("balanceOf".into(), Bytes(vec![1,2,3]), Some(genesis_hash).into()).wait().unwrap_err();
+	assert_eq!(error.code, ::rpc::ErrorCode::InternalError);
+	assert_matches!(error.data, Some(ref data) if data.as_str().map_or(false, |data| data.contains("Execution")));
 }
 
 #[test]
@@ -55,7 +90,7 @@ fn should_notify_about_storage_changes() {
 	let (subscriber, id, transport) = pubsub::Subscriber::new_test("test");
 
 	{
-		let api = State::new(Arc::new(test_client::new()), Subscriptions::new(remote));
+		let api = State::new(Arc::new(test_client::new()), Subscriptions::new(remote), ExecutionPool::new(Default::default()));
 
 		api.subscribe_storage(Default::default(), subscriber, None.into());
 
@@ -86,7 +121,7 @@ fn should_send_initial_storage_changes_and_notifications() {
 	let (subscriber, id, transport) = pubsub::Subscriber::new_test("test");
 
 	{
-		let api = State::new(Arc::new(test_client::new()), Subscriptions::new(remote));
+		let api = State::new(Arc::new(test_client::new()), Subscriptions::new(remote), ExecutionPool::new(Default::default()));
 
 		api.subscribe_storage(Default::default(), subscriber, Some(vec![
 			StorageKey("a52da2b7c269da1366b3ed1cdb7299ce".from_hex().unwrap()),
@@ -119,7 +154,7 @@ fn should_send_initial_storage_changes_and_notifications() {
 fn should_query_storage() {
 	let core = ::tokio::runtime::Runtime::new().unwrap();
 	let client = Arc::new(test_client::new());
-	let api = State::new(client.clone(), Subscriptions::new(core.executor()));
+	let api = State::new(client.clone(), Subscriptions::new(core.executor()), ExecutionPool::new(Default::default()));
 
 	let add_block = |nonce| {
 		let mut builder = client.new_block().unwrap();
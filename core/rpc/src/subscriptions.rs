@@ -90,6 +90,13 @@ impl Subscriptions {
 		}
 	}
 
+	/// Spawn the future on the underlying event loop.
+	pub fn spawn<F>(&self, future: F) where
+		F: future::Future<Item=(), Error=()> + Send + 'static,
+	{
+		self.executor.spawn(future);
+	}
+
 	/// Cancel subscription.
 	///
 	/// Returns true if subscription existed or false otherwise.
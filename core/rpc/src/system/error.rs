@@ -16,17 +16,42 @@
 
 //! System RPC module errors.
 
+use client;
 use rpc;
 
 use errors;
 
 error_chain! {
+	links {
+		Client(client::error::Error, client::error::ErrorKind) #[doc = "Client error"];
+	}
+
 	errors {
 		/// Not implemented yet
 		Unimplemented {
 			description("not yet implemented"),
 			display("Method Not Implemented"),
 		}
+		/// Invalid log filter directives
+		InvalidLogFilter(e: String) {
+			description("invalid log filter"),
+			display("Invalid log filter: {}", e),
+		}
+		/// Invalid peer address or id.
+		InvalidPeer(e: String) {
+			description("invalid peer"),
+			display("Invalid peer: {}", e),
+		}
+		/// Unsafe method called on the node that doesn't allow it.
+		UnsafeRpcCalled {
+			description("unsafe method called"),
+			display("Method is unsafe and not allowed on this node"),
+		}
+		/// Incorrect extrinsic format.
+		BadFormat {
+			description("bad format"),
+			display("Invalid extrinsic format"),
+		}
 	}
 }
 
@@ -34,6 +59,10 @@ impl From<Error> for rpc::Error {
 	fn from(e: Error) -> Self {
 		match e {
 			Error(ErrorKind::Unimplemented, _) => errors::unimplemented(),
+			Error(ErrorKind::InvalidLogFilter(e), _) => rpc::Error::invalid_params(e),
+			Error(ErrorKind::InvalidPeer(e), _) => rpc::Error::invalid_params(e),
+			Error(ErrorKind::UnsafeRpcCalled, _) => errors::unsafe_denied(),
+			Error(ErrorKind::BadFormat, _) => rpc::Error::invalid_params("Invalid extrinsic format"),
 			e => errors::internal(e),
 		}
 	}
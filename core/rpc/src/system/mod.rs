@@ -21,8 +21,25 @@ pub mod error;
 #[cfg(test)]
 mod tests;
 
+use std::sync::Arc;
+
+use client::{self, Client, CallExecutor};
+use codec::Decode;
+use jsonrpc_macros::Trailing;
+use primitives::{Blake2Hasher, Bytes, H256};
+use rpc::BoxFuture;
+use rpc::futures::future;
+use runtime_primitives::{ApplyOutcome, ApplyError};
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::Block as BlockT;
+
+use execution_pool::ExecutionPool;
+
 use self::error::Result;
 
+/// Arbitrary properties of the chain, defined in its chain spec.
+pub type Properties = ::serde_json::map::Map<String, ::serde_json::Value>;
+
 build_rpc_trait! {
 	/// Substrate system RPC API
 	pub trait SystemApi {
@@ -37,5 +54,228 @@ build_rpc_trait! {
 		/// Get the chain's type. Given as a string identifier.
 		#[rpc(name = "system_chain")]
 		fn system_chain(&self) -> Result<String>;
+
+		/// Get a custom set of properties of the chain, like the token symbol and decimals.
+		/// Given as a JSON object, empty if the chain spec doesn't define any.
+		#[rpc(name = "system_properties")]
+		fn system_properties(&self) -> Result<Properties>;
+
+		/// Add log filter directives, e.g. `sync=debug,txpool=trace`, overriding the log levels
+		/// of the given targets until the filter is reset. The directive replaces the directive
+		/// added earlier for the same target. Unsafe.
+		#[rpc(name = "system_addLogFilter")]
+		fn system_add_log_filter(&self, directives: String) -> Result<()>;
+
+		/// Reset the log filter to the directives the node has been started with. Unsafe.
+		#[rpc(name = "system_resetLogFilter")]
+		fn system_reset_log_filter(&self) -> Result<()>;
+	}
+}
+
+/// Connected peer, as seen by the network.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerInfo<Hash, Number> {
+	/// Peer id, `None` if the peer is being disconnected.
+	pub peer_id: Option<String>,
+	/// Roles of the peer.
+	pub roles: String,
+	/// Version of the protocol used by the peer.
+	pub protocol_version: u32,
+	/// Hash of the best block of the peer.
+	pub best_hash: Hash,
+	/// Number of the best block of the peer.
+	pub best_number: Number,
+	/// Cost of serving light client requests to the peer, if any have been served.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub light_serving: Option<LightServing>,
+}
+
+/// Cost of serving light client requests to a peer, since it has connected.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LightServing {
+	/// Number of proofs served.
+	pub proofs: u64,
+	/// Total size of served proofs, in bytes.
+	pub bytes: u64,
+	/// Time spent generating proofs, in milliseconds.
+	pub cpu_ms: u64,
+}
+
+/// Health of the node.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Health {
+	/// Number of connected peers.
+	pub peers: usize,
+	/// Whether the node is syncing.
+	pub is_syncing: bool,
+	/// Total number of the chain stalls detected since the start.
+	pub stalls: usize,
+	/// Seconds since the best block has changed, if the chain is stalled.
+	pub stalled_secs: Option<u64>,
+}
+
+build_rpc_trait! {
+	/// Substrate system RPC API managing the network of the node
+	pub trait SystemNetworkApi<Hash, Number> {
+		/// Get the peers the node is connected to and has completed the handshake with.
+		#[rpc(name = "system_peers")]
+		fn system_peers(&self) -> Result<Vec<PeerInfo<Hash, Number>>>;
+
+		/// Get the health of the node. The chain stalls are only detected if the watchdog is enabled.
+		#[rpc(name = "system_health")]
+		fn system_health(&self) -> Result<Health>;
+
+		/// Add a reserved peer, given as its multiaddress ending with `/p2p/<peer id>`. The node
+		/// keeps connecting to the reserved peers, even if it denies other peers. Unsafe.
+		#[rpc(name = "system_addReservedPeer")]
+		fn system_add_reserved_peer(&self, String) -> Result<()>;
+
+		/// Remove the reservation of the peer, given as its peer id or multiaddress. The peer is
+		/// disconnected if the node denies the peers that aren't reserved. Unsafe.
+		#[rpc(name = "system_removeReservedPeer")]
+		fn system_remove_reserved_peer(&self, String) -> Result<()>;
+	}
+}
+
+/// System RPC handler denying the methods that manage the node, unless the unsafe methods are
+/// allowed. The other methods are passed to the wrapped handler.
+pub struct DenyUnsafe<T> {
+	inner: T,
+	allow_unsafe: bool,
+}
+
+impl<T> DenyUnsafe<T> {
+	/// Wrap the handler.
+	pub fn new(inner: T, allow_unsafe: bool) -> Self {
+		DenyUnsafe {
+			inner,
+			allow_unsafe,
+		}
+	}
+
+	fn check(&self) -> Result<()> {
+		if self.allow_unsafe {
+			Ok(())
+		} else {
+			Err(error::ErrorKind::UnsafeRpcCalled.into())
+		}
+	}
+}
+
+impl<T: SystemApi> SystemApi for DenyUnsafe<T> {
+	fn system_name(&self) -> Result<String> {
+		self.inner.system_name()
+	}
+
+	fn system_version(&self) -> Result<String> {
+		self.inner.system_version()
+	}
+
+	fn system_chain(&self) -> Result<String> {
+		self.inner.system_chain()
+	}
+
+	fn system_properties(&self) -> Result<Properties> {
+		self.inner.system_properties()
+	}
+
+	fn system_add_log_filter(&self, directives: String) -> Result<()> {
+		self.check()?;
+		self.inner.system_add_log_filter(directives)
+	}
+
+	fn system_reset_log_filter(&self) -> Result<()> {
+		self.check()?;
+		self.inner.system_reset_log_filter()
+	}
+}
+
+impl<Hash, Number, T> SystemNetworkApi<Hash, Number> for DenyUnsafe<T> where
+	T: SystemNetworkApi<Hash, Number>,
+{
+	fn system_peers(&self) -> Result<Vec<PeerInfo<Hash, Number>>> {
+		self.inner.system_peers()
+	}
+
+	fn system_health(&self) -> Result<Health> {
+		self.inner.system_health()
+	}
+
+	fn system_add_reserved_peer(&self, peer: String) -> Result<()> {
+		self.check()?;
+		self.inner.system_add_reserved_peer(peer)
+	}
+
+	fn system_remove_reserved_peer(&self, peer: String) -> Result<()> {
+		self.check()?;
+		self.inner.system_remove_reserved_peer(peer)
+	}
+}
+
+/// Outcome of the extrinsic dry run.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunResult {
+	/// Dispatch outcome, if the extrinsic has been applied.
+	pub outcome: Option<ApplyOutcome>,
+	/// Reason why the extrinsic couldn't be applied.
+	pub error: Option<ApplyError>,
+	/// Encoded events deposited while applying the extrinsic, if the runtime exposes them.
+	pub events: Option<Bytes>,
+}
+
+build_rpc_trait! {
+	/// Substrate system RPC API requiring access to the chain state
+	pub trait SystemStateApi<Hash> {
+		/// Apply the extrinsic on top of a block's state, without changing either the chain or the
+		/// transaction pool. Returns the dispatch outcome and the emitted events. The extrinsic
+		/// is applied on the execution pool.
+		#[rpc(name = "system_dryRun")]
+		fn system_dry_run(&self, Bytes, Trailing<Hash>) -> BoxFuture<DryRunResult>;
+	}
+}
+
+/// System API requiring access to the chain state.
+pub struct SystemState<B, E, Block: BlockT> {
+	/// Substrate client.
+	client: Arc<Client<B, E, Block>>,
+	/// Pool executing the runtime calls.
+	execution_pool: ExecutionPool,
+}
+
+impl<B, E, Block: BlockT> SystemState<B, E, Block> {
+	/// Create new SystemState API RPC handler.
+	pub fn new(client: Arc<Client<B, E, Block>>, execution_pool: ExecutionPool) -> Self {
+		SystemState {
+			client,
+			execution_pool,
+		}
+	}
+}
+
+impl<B, E, Block> SystemStateApi<Block::Hash> for SystemState<B, E, Block> where
+	Block: BlockT<Hash=H256> + 'static,
+	B: client::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Clone + Send + Sync + 'static,
+{
+	fn system_dry_run(&self, xt: Bytes, block: Trailing<Block::Hash>) -> BoxFuture<DryRunResult> {
+		let client = self.client.clone();
+		let block = match ::helpers::unwrap_or_else(|| -> Result<_> { Ok(client.info()?.chain.best_hash) }, block) {
+			Ok(block) => block,
+			Err(e) => return Box::new(future::err(e.into())),
+		};
+		trace!(target: "rpc", "Dry running extrinsic at {:?}", block);
+		self.execution_pool.execute(move || -> Result<DryRunResult> {
+			let xt = Decode::decode(&mut &xt[..]).ok_or(error::Error::from(error::ErrorKind::BadFormat))?;
+			let (result, events) = client.dry_run_extrinsic(&BlockId::Hash(block), &xt)?;
+			Ok(DryRunResult {
+				outcome: result.ok(),
+				error: result.err(),
+				events: events.map(Bytes),
+			})
+		})
 	}
 }
@@ -17,6 +17,10 @@
 use super::*;
 use super::error::*;
 
+use codec::Encode;
+use rpc::futures::Future;
+use test_client::{self, runtime, keyring::Keyring, TestClient};
+
 impl SystemApi for () {
 	fn system_name(&self) -> Result<String> {
 		Ok("testclient".into())
@@ -27,6 +31,39 @@ impl SystemApi for () {
 	fn system_chain(&self) -> Result<String> {
 		Ok("testchain".into())
 	}
+	fn system_properties(&self) -> Result<Properties> {
+		let mut properties = Properties::new();
+		properties.insert("tokenSymbol".into(), "TST".into());
+		properties.insert("tokenDecimals".into(), 15.into());
+		Ok(properties)
+	}
+	fn system_add_log_filter(&self, directives: String) -> Result<()> {
+		if directives.is_empty() {
+			return Err(ErrorKind::InvalidLogFilter(directives).into());
+		}
+		Ok(())
+	}
+	fn system_reset_log_filter(&self) -> Result<()> {
+		Ok(())
+	}
+}
+
+impl SystemNetworkApi<u64, u64> for () {
+	fn system_peers(&self) -> Result<Vec<PeerInfo<u64, u64>>> {
+		Ok(Vec::new())
+	}
+	fn system_health(&self) -> Result<Health> {
+		Ok(Health { peers: 0, is_syncing: false, stalls: 0, stalled_secs: None })
+	}
+	fn system_add_reserved_peer(&self, peer: String) -> Result<()> {
+		if peer.is_empty() {
+			return Err(ErrorKind::InvalidPeer(peer).into());
+		}
+		Ok(())
+	}
+	fn system_remove_reserved_peer(&self, _peer: String) -> Result<()> {
+		Ok(())
+	}
 }
 
 #[test]
@@ -52,3 +89,120 @@ fn system_chain_works() {
 		"testchain".to_owned()
 	);
 }
+
+#[test]
+fn system_properties_works() {
+	assert_eq!(
+		::serde_json::to_string(&SystemApi::system_properties(&()).unwrap()).unwrap(),
+		r#"{"tokenDecimals":15,"tokenSymbol":"TST"}"#
+	);
+}
+
+#[test]
+fn invalid_log_filter_is_reported_as_invalid_params() {
+	assert_matches!(
+		SystemApi::system_add_log_filter(&(), String::new()).map_err(::rpc::Error::from),
+		Err(::rpc::Error { code: ::rpc::ErrorCode::InvalidParams, .. })
+	);
+	assert!(SystemApi::system_add_log_filter(&(), "sync=debug".into()).is_ok());
+}
+
+#[test]
+fn reserved_peers_are_only_managed_if_unsafe_methods_are_allowed() {
+	let denied = DenyUnsafe::new((), false);
+	assert_matches!(
+		SystemNetworkApi::<u64, u64>::system_add_reserved_peer(&denied, "/p2p/QmPeer".into()).map_err(::rpc::Error::from),
+		Err(::rpc::Error { code: ::rpc::ErrorCode::ServerError(2), .. })
+	);
+	assert_matches!(
+		SystemNetworkApi::<u64, u64>::system_remove_reserved_peer(&denied, "QmPeer".into()),
+		Err(Error(ErrorKind::UnsafeRpcCalled, _))
+	);
+	// safe methods are passed through
+	assert_eq!(SystemNetworkApi::<u64, u64>::system_health(&denied).unwrap().peers, 0);
+
+	let allowed = DenyUnsafe::new((), true);
+	assert!(SystemNetworkApi::<u64, u64>::system_add_reserved_peer(&allowed, "/p2p/QmPeer".into()).is_ok());
+	assert!(SystemNetworkApi::<u64, u64>::system_remove_reserved_peer(&allowed, "QmPeer".into()).is_ok());
+	assert_matches!(
+		SystemNetworkApi::<u64, u64>::system_add_reserved_peer(&allowed, String::new()),
+		Err(Error(ErrorKind::InvalidPeer(_), _))
+	);
+}
+
+#[test]
+fn log_filter_is_only_changed_if_unsafe_methods_are_allowed() {
+	let denied = DenyUnsafe::new((), false);
+	assert_matches!(
+		SystemApi::system_add_log_filter(&denied, "sync=debug".into()),
+		Err(Error(ErrorKind::UnsafeRpcCalled, _))
+	);
+	assert_matches!(SystemApi::system_reset_log_filter(&denied), Err(Error(ErrorKind::UnsafeRpcCalled, _)));
+	assert_eq!(SystemApi::system_name(&denied).unwrap(), "testclient".to_owned());
+
+	let allowed = DenyUnsafe::new((), true);
+	assert!(SystemApi::system_add_log_filter(&allowed, "sync=debug".into()).is_ok());
+	assert!(SystemApi::system_reset_log_filter(&allowed).is_ok());
+}
+
+#[test]
+fn peer_info_is_serialized_in_camel_case() {
+	let peer = PeerInfo {
+		peer_id: Some("QmPeer".into()),
+		roles: "FULL".into(),
+		protocol_version: 2,
+		best_hash: 5u64,
+		best_number: 10u64,
+		light_serving: None,
+	};
+	assert_eq!(
+		::serde_json::to_string(&peer).unwrap(),
+		r#"{"peerId":"QmPeer","roles":"FULL","protocolVersion":2,"bestHash":5,"bestNumber":10}"#
+	);
+}
+
+#[test]
+fn light_serving_cost_of_peer_is_serialized() {
+	let peer = PeerInfo {
+		peer_id: None,
+		roles: "LIGHT".into(),
+		protocol_version: 2,
+		best_hash: 5u64,
+		best_number: 10u64,
+		light_serving: Some(LightServing { proofs: 3, bytes: 1024, cpu_ms: 7 }),
+	};
+	let serialized = ::serde_json::to_string(&peer).unwrap();
+	assert_eq!(
+		serialized,
+		r#"{"peerId":null,"roles":"LIGHT","protocolVersion":2,"bestHash":5,"bestNumber":10,"lightServing":{"proofs":3,"bytes":1024,"cpuMs":7}}"#
+	);
+	assert_eq!(::serde_json::from_str::<PeerInfo<u64, u64>>(&serialized).unwrap(), peer);
+}
+
+#[test]
+fn system_dry_run_works() {
+	let client = Arc::new(test_client::new());
+	let genesis_hash = client.genesis_hash();
+	let api = SystemState::new(client.clone(), ExecutionPool::new(Default::default()));
+	let transfer = runtime::Transfer {
+		from: Keyring::Alice.to_raw_public().into(),
+		to: Keyring::Bob.to_raw_public().into(),
+		amount: 5,
+		nonce: 0,
+	};
+	let signature = Keyring::Alice.sign(&transfer.encode()).into();
+	let xt = runtime::Extrinsic { transfer, signature }.encode();
+
+	assert_eq!(
+		api.system_dry_run(xt.clone().into(), Some(genesis_hash).into()).wait().unwrap(),
+		DryRunResult { outcome: Some(ApplyOutcome::Success), error: None, events: None }
+	);
+	// the dry run is repeatable as nothing is changed
+	assert_eq!(
+		api.system_dry_run(xt.into(), None.into()).wait().unwrap().outcome,
+		Some(ApplyOutcome::Success)
+	);
+	assert_eq!(client.info().unwrap().chain.best_number, 0);
+
+	assert!(api.system_dry_run(vec![1, 2, 3].into(), None.into()).wait().is_err());
+}
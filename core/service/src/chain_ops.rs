@@ -20,17 +20,24 @@ use std::{self, io::{Read, Write}};
 use futures::Future;
 use serde_json;
 
+use primitives::{Blake2Hasher, H256};
+use runtime_primitives::BuildStorage;
 use runtime_primitives::generic::{SignedBlock, BlockId};
 use runtime_primitives::traits::{As, Block, Header};
 use network::import_queue::{ImportQueue, BlockData};
 use network::message;
 
+use client::backend::{Backend, BlockImportOperation, CompactionProfile, MaintenanceProgress};
+use client::in_mem;
 use consensus_common::BlockOrigin;
-use components::{self, Components, ServiceFactory, FactoryFullConfiguration, FactoryBlockNumber, RuntimeGenesis};
+use client_db;
+use components::{self, Components, ServiceFactory, FactoryFullConfiguration, FactoryBlock, FactoryBlockNumber, FactoryGenesis, RuntimeGenesis};
 use new_client;
 use codec::{Decode, Encode};
 use error;
 use chain_spec::ChainSpec;
+use fork;
+use keystore;
 
 /// Export a range of blocks to a binary stream.
 pub fn export_blocks<F, E, W>(config: FactoryFullConfiguration<F>, exit: E, mut output: W, from: FactoryBlockNumber<F>, to: Option<FactoryBlockNumber<F>>, json: bool) -> error::Result<()>
@@ -143,9 +150,161 @@ pub fn revert_chain<F>(config: FactoryFullConfiguration<F>, blocks: FactoryBlock
 	Ok(())
 }
 
-/// Build a chain spec json
-pub fn build_spec<G>(spec: ChainSpec<G>, raw: bool) -> error::Result<String>
-	where G: RuntimeGenesis,
+/// Compact the chain database, reclaiming the space left by pruned data. Fails if the database is
+/// used by a running node.
+pub fn compact_db<F, E>(config: FactoryFullConfiguration<F>, exit: E, profile: CompactionProfile) -> error::Result<()>
+	where F: ServiceFactory, E: Future<Item=(),Error=()> + Send + 'static,
+{
+	struct Progress(std::sync::mpsc::Receiver<()>);
+
+	impl MaintenanceProgress for Progress {
+		fn on_progress(&self, column: u32, columns: u32, entries: u64) -> bool {
+			info!("Compacting column {}/{}: {} entries", column + 1, columns, entries);
+			self.0.try_recv().is_err()
+		}
+	}
+
+	let db_settings = client_db::DatabaseSettings {
+		cache_size: None,
+		path: config.database_path.as_str().into(),
+		pruning: config.pruning.clone(),
+		read_only: false,
+		memory_budget: None,
+	};
+
+	let (exit_send, exit_recv) = std::sync::mpsc::channel();
+	::std::thread::spawn(move || {
+		let _ = exit.wait();
+		let _ = exit_send.send(());
+	});
+
+	info!("Compacting database at {}", config.database_path);
+	match client_db::compact_database(&db_settings, &profile, &Progress(exit_recv))? {
+		true => info!("Database compacted"),
+		false => info!("Database compaction aborted"),
+	}
+	Ok(())
+}
+
+/// Check consistency of the chain database, logging the found inconsistencies.
+pub fn check_db<F>(config: FactoryFullConfiguration<F>) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let client = new_client::<F>(&config)?;
+	info!("Checking database at {}", config.database_path);
+	let issues = client.backend().check_integrity()?;
+	for issue in &issues {
+		warn!("{}", issue);
+	}
+	match issues.len() {
+		0 => {
+			info!("Database is consistent");
+			Ok(())
+		},
+		count => Err(format!("Found {} database inconsistencies", count).into()),
+	}
+}
+
+/// Apply the schema migrations the chain database is missing, or only list them if `dry_run` is set.
+pub fn migrate_db<F>(config: FactoryFullConfiguration<F>, dry_run: bool) -> error::Result<()>
+	where F: ServiceFactory,
+{
+	let db_settings = client_db::DatabaseSettings {
+		cache_size: None,
+		path: config.database_path.as_str().into(),
+		pruning: config.pruning.clone(),
+		read_only: config.database_read_only,
+		memory_budget: None,
+	};
+	let migrations = client_db::migrate_database(&db_settings, dry_run)?;
+	if migrations.is_empty() {
+		info!("Database at {} is up to date", config.database_path);
+	}
+	for (version, description) in migrations {
+		match dry_run {
+			true => info!("Pending migration to version {}: {}", version, description),
+			false => info!("Applied migration to version {}: {}", version, description),
+		}
+	}
+	Ok(())
+}
+
+/// Export snapshot of the light client database, which may be used to bootstrap other light clients.
+pub fn export_light_snapshot<F, W>(config: FactoryFullConfiguration<F>, mut output: W) -> error::Result<()>
+	where F: ServiceFactory, W: Write,
+{
+	let db_settings = client_db::DatabaseSettings {
+		cache_size: None,
+		path: config.database_path.as_str().into(),
+		pruning: config.pruning.clone(),
+		read_only: config.database_read_only,
+		memory_budget: None,
+	};
+	let storage = client_db::light::LightStorage::<FactoryBlock<F>>::new(db_settings)?;
+	let snapshot = storage.export_snapshot()?;
+	info!("Exporting light snapshot: {} CHTs, {} headers", snapshot.cht_roots.len(), snapshot.headers.len());
+	output.write_all(&snapshot.encode())?;
+	Ok(())
+}
+
+/// Build a chain spec json. The raw spec has the state root of the genesis block pinned.
+pub fn build_spec<F>(mut spec: ChainSpec<FactoryGenesis<F>>, raw: bool) -> error::Result<String>
+	where F: ServiceFactory,
 {
+	if raw {
+		let root = genesis_state_root::<F>(&spec)?;
+		spec.set_genesis_state_root(root);
+	}
 	Ok(spec.to_json(raw)?)
 }
+
+/// Compute the state root of the genesis block built from the chain spec.
+pub fn genesis_state_root<F>(spec: &ChainSpec<FactoryGenesis<F>>) -> error::Result<H256>
+	where F: ServiceFactory,
+{
+	let backend = in_mem::Backend::<FactoryBlock<F>, Blake2Hasher>::new();
+	let mut op = backend.begin_operation(BlockId::Hash(Default::default()))?;
+	let (storage, children_storage) = spec.build_storage()?;
+	Ok(op.reset_storage(storage, children_storage)?)
+}
+
+/// Check that the state root of the genesis block built from the chain spec equals the expected
+/// root, or the root pinned in the spec if `None`. Returns the computed root.
+pub fn verify_spec<F>(spec: &ChainSpec<FactoryGenesis<F>>, expected: Option<H256>) -> error::Result<H256>
+	where F: ServiceFactory,
+{
+	let expected = expected.or_else(|| spec.genesis_state_root())
+		.ok_or_else(|| error::Error::from("Chain spec has no pinned genesis state root"))?;
+	let computed = genesis_state_root::<F>(spec)?;
+	if computed != expected {
+		return Err(error::ErrorKind::GenesisStateRootMismatch(expected, computed).into());
+	}
+	Ok(computed)
+}
+
+/// Build a spec of a local fork of the live chain, starting from the state of the block with given
+/// number (the best block if `None`), fetched over JSON-RPC from the node at given url.
+///
+/// The authority set of the fork is replaced with the keys derived from given seeds.
+pub fn fork_spec<G>(spec: &ChainSpec<G>, url: &str, number: Option<u64>, authority_seeds: &[String]) -> error::Result<ChainSpec<G>>
+	where G: RuntimeGenesis,
+{
+	let mut snapshot = fork::fetch_snapshot(url, number)?;
+	info!("Forking {} at #{} ({}): {} storage entries", spec.name(), snapshot.number, snapshot.hash, snapshot.storage.len());
+
+	let authorities: Vec<_> = authority_seeds.iter()
+		.map(|seed| keystore::pair_from_seed(seed).public().0.into())
+		.collect();
+	fork::set_authorities(&mut snapshot.storage, &authorities);
+
+	Ok(ChainSpec::from_storage(
+		&format!("{} (fork at #{})", spec.name(), snapshot.number),
+		&format!("{}_fork_{}", spec.id(), snapshot.number),
+		snapshot.storage,
+		Vec::new(),
+		None,
+		spec.protocol_id(),
+		spec.consensus_engine(),
+		spec.properties().cloned(),
+	))
+}
@@ -19,8 +19,11 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::path::PathBuf;
+use primitives::{Bytes, H256};
 use primitives::storage::{StorageKey, StorageData};
 use runtime_primitives::{BuildStorage, StorageMap, ChildrenStorageMap};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use serde_json as json;
 use components::RuntimeGenesis;
 
@@ -28,6 +31,7 @@ enum GenesisSource<G> {
 	File(PathBuf),
 	Embedded(&'static [u8]),
 	Factory(fn() -> G),
+	Storage(HashMap<StorageKey, StorageData>),
 }
 
 impl<G: RuntimeGenesis> Clone for GenesisSource<G> {
@@ -36,6 +40,7 @@ impl<G: RuntimeGenesis> Clone for GenesisSource<G> {
 			GenesisSource::File(ref path) => GenesisSource::File(path.clone()),
 			GenesisSource::Embedded(d) => GenesisSource::Embedded(d),
 			GenesisSource::Factory(f) => GenesisSource::Factory(f),
+			GenesisSource::Storage(ref storage) => GenesisSource::Storage(storage.clone()),
 		}
 	}
 }
@@ -58,6 +63,7 @@ impl<G: RuntimeGenesis> GenesisSource<G> {
 				Ok(genesis.genesis)
 			},
 			GenesisSource::Factory(f) => Ok(Genesis::Runtime(f())),
+			GenesisSource::Storage(ref storage) => Ok(Genesis::Raw(storage.clone())),
 		}
 	}
 }
@@ -88,6 +94,53 @@ struct ChainSpecFile {
 	pub telemetry_url: Option<String>,
 	pub protocol_id: Option<String>,
 	pub consensus_engine: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub properties: Option<Properties>,
+	#[serde(default, skip_serializing_if = "json::Map::is_empty")]
+	pub extensions: json::Map<String, json::Value>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub genesis_state_root: Option<H256>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub fork_id: Option<String>,
+}
+
+/// Arbitrary properties of the chain, like the token symbol (`tokenSymbol`), number of its decimals
+/// (`tokenDecimals`) and the SS58 address format (`ss58Format`). Exposed to the RPC clients, so that
+/// they can format the values of the chain without knowing it in advance.
+pub type Properties = json::map::Map<String, json::Value>;
+
+/// Typed chain spec extension, e.g. consensus engine specific parameters.
+///
+/// Extensions are stored under their `NAME` in the `extensions` section of the chain spec and
+/// are only decoded by the components that use them. Specs carrying unknown extensions are
+/// still accepted. To stay forward-compatible, extensions should not deny unknown fields and
+/// fields added later should have a `#[serde(default)]`.
+pub trait ChainSpecExtension: Serialize + DeserializeOwned {
+	/// Key under which the extension is stored in the chain spec.
+	const NAME: &'static str;
+}
+
+/// Chain spec extension with known bad blocks, blocks required on the canonical chain and
+/// block resource limits.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockRulesExtension {
+	/// Hashes of blocks that are never imported.
+	#[serde(default)]
+	pub bad_blocks: Vec<Bytes>,
+	/// Block numbers with the only hash that may be imported at that height.
+	#[serde(default)]
+	pub fork_blocks: Vec<(u64, Bytes)>,
+	/// Maximal size of an encoded block, in bytes.
+	#[serde(default)]
+	pub max_block_size: Option<u64>,
+	/// Maximal number of extrinsics in a block.
+	#[serde(default)]
+	pub max_extrinsics: Option<u64>,
+}
+
+impl ChainSpecExtension for BlockRulesExtension {
+	const NAME: &'static str = "blockRules";
 }
 
 /// A configuration of a chain. Can be used to build a genesis block.
@@ -130,6 +183,44 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 		self.spec.consensus_engine.as_ref().map(String::as_str)
 	}
 
+	pub fn properties(&self) -> Option<&Properties> {
+		self.spec.properties.as_ref()
+	}
+
+	/// Get the id of the fork of the chain, distinguishing it from the other chains with the same
+	/// genesis block. Peers announcing a different fork id are rejected.
+	pub fn fork_id(&self) -> Option<&str> {
+		self.spec.fork_id.as_ref().map(String::as_str)
+	}
+
+	/// Get the pinned state root of the genesis block, if the chain spec defines it.
+	pub fn genesis_state_root(&self) -> Option<H256> {
+		self.spec.genesis_state_root
+	}
+
+	/// Pin the state root of the genesis block.
+	pub fn set_genesis_state_root(&mut self, root: H256) {
+		self.spec.genesis_state_root = Some(root);
+	}
+
+	/// Get the extension of given type, if the chain spec defines it.
+	pub fn extension<E: ChainSpecExtension>(&self) -> Result<Option<E>, String> {
+		match self.spec.extensions.get(E::NAME) {
+			Some(value) => json::from_value(value.clone())
+				.map(Some)
+				.map_err(|e| format!("Error parsing chain spec extension {}: {}", E::NAME, e)),
+			None => Ok(None),
+		}
+	}
+
+	/// Set the extension of given type, replacing the existing one.
+	pub fn set_extension<E: ChainSpecExtension>(&mut self, extension: E) -> Result<(), String> {
+		let value = json::to_value(extension)
+			.map_err(|e| format!("Error generating chain spec extension {}: {}", E::NAME, e))?;
+		self.spec.extensions.insert(E::NAME.to_owned(), value);
+		Ok(())
+	}
+
 	/// Parse json content into a `ChainSpec`
 	pub fn from_embedded(json: &'static [u8]) -> Result<Self, String> {
 		let spec = json::from_slice(json).map_err(|e| format!("Error parsing spec file: {}", e))?;
@@ -158,6 +249,7 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 		telemetry_url: Option<&str>,
 		protocol_id: Option<&str>,
 		consensus_engine: Option<&str>,
+		properties: Option<Properties>,
 	) -> Self
 	{
 		let spec = ChainSpecFile {
@@ -167,6 +259,10 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 			telemetry_url: telemetry_url.map(str::to_owned),
 			protocol_id: protocol_id.map(str::to_owned),
 			consensus_engine: consensus_engine.map(str::to_owned),
+			properties,
+			extensions: Default::default(),
+			genesis_state_root: None,
+			fork_id: None,
 		};
 		ChainSpec {
 			spec,
@@ -174,6 +270,36 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 		}
 	}
 
+	/// Create spec with given raw genesis storage.
+	pub fn from_storage(
+		name: &str,
+		id: &str,
+		storage: HashMap<StorageKey, StorageData>,
+		boot_nodes: Vec<String>,
+		telemetry_url: Option<&str>,
+		protocol_id: Option<&str>,
+		consensus_engine: Option<&str>,
+		properties: Option<Properties>,
+	) -> Self
+	{
+		let spec = ChainSpecFile {
+			name: name.to_owned(),
+			id: id.to_owned(),
+			boot_nodes: boot_nodes,
+			telemetry_url: telemetry_url.map(str::to_owned),
+			protocol_id: protocol_id.map(str::to_owned),
+			consensus_engine: consensus_engine.map(str::to_owned),
+			properties,
+			extensions: Default::default(),
+			genesis_state_root: None,
+			fork_id: None,
+		};
+		ChainSpec {
+			spec,
+			genesis: GenesisSource::Storage(storage),
+		}
+	}
+
 	/// Dump to json string.
 	pub fn to_json(self, raw: bool) -> Result<String, String> {
 		#[derive(Serialize, Deserialize)]
@@ -185,7 +311,11 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 		};
 		let genesis = match (raw, self.genesis.resolve()?) {
 			(true, Genesis::Runtime(g)) => {
-				let storage = g.build_storage()?.0.into_iter()
+				let (storage, children_storage) = g.build_storage()?;
+				if !children_storage.is_empty() {
+					return Err("Child storage can't be represented in raw genesis".into());
+				}
+				let storage = storage.into_iter()
 					.map(|(k, v)| (StorageKey(k), StorageData(v)))
 					.collect();
 
@@ -200,3 +330,58 @@ impl<G: RuntimeGenesis> ChainSpec<G> {
 		json::to_string_pretty(&spec).map_err(|e| format!("Error generating spec json: {}", e))
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Serialize, Deserialize, Debug, PartialEq)]
+	struct TestExtension {
+		slot_duration: u64,
+	}
+
+	impl ChainSpecExtension for TestExtension {
+		const NAME: &'static str = "test";
+	}
+
+	fn reparse(spec: ChainSpec<StorageMap>) -> ChainSpec<StorageMap> {
+		let json = spec.to_json(true).unwrap();
+		ChainSpec::from_embedded(Box::leak(json.into_bytes().into_boxed_slice())).unwrap()
+	}
+
+	#[test]
+	fn extensions_are_stored_in_spec() {
+		let mut spec = ChainSpec::<StorageMap>::from_storage("Test", "test", Default::default(), vec![], None, None, None, None);
+		assert_eq!(spec.extension::<TestExtension>().unwrap(), None);
+
+		spec.set_extension(TestExtension { slot_duration: 5 }).unwrap();
+		spec.set_extension(BlockRulesExtension { max_extrinsics: Some(10), ..Default::default() }).unwrap();
+		let spec = reparse(spec);
+		assert_eq!(spec.extension::<TestExtension>().unwrap(), Some(TestExtension { slot_duration: 5 }));
+		assert_eq!(spec.extension::<BlockRulesExtension>().unwrap().unwrap().max_extrinsics, Some(10));
+	}
+
+	#[test]
+	fn unknown_extensions_are_accepted_and_invalid_are_reported() {
+		let json = br#"{
+			"name": "Test",
+			"id": "test",
+			"bootNodes": [],
+			"telemetryUrl": null,
+			"protocolId": null,
+			"consensusEngine": null,
+			"extensions": {
+				"unknown": { "anything": [1, 2, 3] },
+				"test": { "slot_duration": "five" },
+				"blockRules": { "maxBlockSize": 1024, "addedLater": true }
+			},
+			"genesis": { "raw": {} }
+		}"#;
+		let spec = ChainSpec::<StorageMap>::from_embedded(json).unwrap();
+		assert!(spec.extension::<TestExtension>().is_err());
+
+		let rules = spec.extension::<BlockRulesExtension>().unwrap().unwrap();
+		assert_eq!(rules.max_block_size, Some(1024));
+		assert!(rules.bad_blocks.is_empty());
+	}
+}
@@ -16,21 +16,24 @@
 
 //! Substrate service components.
 
-use std::sync::Arc;
+use std::io::Read;
+use std::sync::{Arc, Weak};
 use std::marker::PhantomData;
 use std::ops::Deref;
 use serde::{Serialize, de::DeserializeOwned};
 use tokio::runtime::TaskExecutor;
-use chain_spec::ChainSpec;
+use futures::Future;
+use chain_spec::{ChainSpec, BlockRulesExtension};
 use client_db;
-use client::{self, Client};
+use client::{self, Client, blockchain::HeaderBackend};
 use {error, Service};
-use network::{self, OnDemand, import_queue::ImportQueue};
+use network::{self, import_queue::{ImportQueue, BlockRules, BlockLimits}};
 use substrate_executor::{NativeExecutor, NativeExecutionDispatch};
 use transaction_pool::txpool::{self, Options as TransactionPoolOptions, Pool as TransactionPool};
-use runtime_primitives::{traits::Block as BlockT, traits::Header as HeaderT, BuildStorage};
+use runtime_primitives::{traits::Block as BlockT, traits::Header as HeaderT, traits::As, BuildStorage};
 use config::Configuration;
-use primitives::{H256, Blake2Hasher};
+use primitives::{H256, Blake2Hasher, hexdisplay::HexDisplay, storage::well_known_keys};
+use codec::Decode;
 
 // Type aliases.
 // These exist mainly to avoid typing `<F as Factory>::Foo` all over the code.
@@ -53,19 +56,26 @@ pub type FullExecutor<F> = client::LocalCallExecutor<
 	CodeExecutor<F>,
 >;
 
+/// On-demand fetcher type for a factory.
+pub type FactoryOnDemand<F> = network::OnDemand<<F as ServiceFactory>::Block, NetworkService<F>>;
+
+/// Light client fetcher type for a factory. Requests are resent to the fallback nodes if the
+/// connected full nodes fail to answer them.
+pub type LightFetcher<F> = client::light::failover::FailoverFetcher<FactoryOnDemand<F>, FactoryOnDemand<F>>;
+
 /// Light client backend type for a factory.
 pub type LightBackend<F> = client::light::backend::Backend<
 	client_db::light::LightStorage<<F as ServiceFactory>::Block>,
-	network::OnDemand<<F as ServiceFactory>::Block, NetworkService<F>>,
+	LightFetcher<F>,
 >;
 
 /// Light client executor type for a factory.
 pub type LightExecutor<F> = client::light::call_executor::RemoteCallExecutor<
 	client::light::blockchain::Blockchain<
 		client_db::light::LightStorage<<F as ServiceFactory>::Block>,
-		network::OnDemand<<F as ServiceFactory>::Block, NetworkService<F>>
+		LightFetcher<F>
 	>,
-	network::OnDemand<<F as ServiceFactory>::Block, NetworkService<F>>,
+	LightFetcher<F>,
 	Blake2Hasher,
 >;
 
@@ -75,6 +85,13 @@ pub type FullClient<F> = Client<FullBackend<F>, FullExecutor<F>, <F as ServiceFa
 /// Light client type for a factory.
 pub type LightClient<F> = Client<LightBackend<F>, LightExecutor<F>, <F as ServiceFactory>::Block>;
 
+/// Light client verifier for a factory, wrapping the verifier of the consensus engine.
+pub type LightVerifier<F, V> = network::import_queue::LightVerifier<
+	V,
+	client_db::light::LightStorage<<F as ServiceFactory>::Block>,
+	LightFetcher<F>,
+>;
+
 /// `ChainSpec` specialization for a factory.
 pub type FactoryChainSpec<F> = ChainSpec<<F as ServiceFactory>::Genesis>;
 
@@ -189,6 +206,23 @@ pub trait ServiceFactory: 'static + Sized {
 			Err("Chain Specification doesn't contain any consensus_engine name".into())
 		}
 	}
+
+	/// Verifier of the justifications of the consensus engine. The client rejects all the
+	/// justifications (and the light client all the headers) without it.
+	fn build_justification_verifier(
+		config: &FactoryFullConfiguration<Self>,
+	) -> Result<Box<client::JustificationVerifier<Self::Block>>, error::Error> {
+		if let Some(name) = config.chain_spec.consensus_engine() {
+			match name {
+				#[cfg(feature = "rhd")]
+				"rhd" => Ok(Box::new(::rhd::JustificationVerifier::<Self::Block>::default())),
+				_ => Err(format!("Chain Specification defines unknown consensus engine '{}'", name).into())
+			}
+
+		} else {
+			Err("Chain Specification doesn't contain any consensus_engine name".into())
+		}
+	}
 }
 
 /// A collection of types and function to generalise over full / light client type.
@@ -208,14 +242,16 @@ pub trait Components: 'static {
 	/// Our Import Queue
 	type ImportQueue: ImportQueue<FactoryBlock<Self::Factory>> + 'static;
 
-	/// Create client.
+	/// Create client, along with the on-demand fetcher of the light client and the fetcher of its
+	/// fallback nodes.
 	fn build_client(
 		config: &FactoryFullConfiguration<Self::Factory>,
 		executor: CodeExecutor<Self::Factory>,
 	)
 		-> Result<(
 			Arc<ComponentClient<Self>>,
-			Option<Arc<OnDemand<FactoryBlock<Self::Factory>, NetworkService<Self::Factory>>>>
+			Option<Arc<FactoryOnDemand<Self::Factory>>>,
+			Option<Arc<FactoryOnDemand<Self::Factory>>>,
 		), error::Error>;
 
 	/// Create extrinsic pool.
@@ -227,6 +263,69 @@ pub trait Components: 'static {
 		config: &FactoryFullConfiguration<Self::Factory>,
 		client: Arc<ComponentClient<Self>>
 	) -> Result<Self::ImportQueue, error::Error>;
+
+	/// Task to spawn once the new best block is imported while the node isn't major syncing, if any.
+	fn on_new_best_block(
+		client: &ComponentClient<Self>,
+		header: <FactoryBlock<Self::Factory> as BlockT>::Header,
+	) -> Option<Box<Future<Item=(), Error=()> + Send>>;
+}
+
+/// Collect block import rules from the chain spec and the operator-supplied configuration.
+fn block_rules<F: ServiceFactory>(
+	config: &FactoryFullConfiguration<F>
+) -> Result<BlockRules<FactoryBlock<F>>, error::Error> {
+	fn decode_hash<F: ServiceFactory>(bytes: &[u8]) -> Result<<FactoryBlock<F> as BlockT>::Hash, error::Error> {
+		let mut input = bytes;
+		match Decode::decode(&mut input) {
+			Some(hash) if input.is_empty() => Ok(hash),
+			_ => Err(format!("Invalid block hash: 0x{}", HexDisplay::from(&bytes)).into()),
+		}
+	}
+
+	let extension = config.chain_spec.extension::<BlockRulesExtension>()?.unwrap_or_default();
+	let mut rules = BlockRules::default();
+	for hash in extension.bad_blocks.iter().chain(config.bad_blocks.iter()) {
+		rules.mark_bad(decode_hash::<F>(hash)?);
+	}
+	for &(number, ref hash) in extension.fork_blocks.iter().chain(config.fork_blocks.iter()) {
+		rules.require_hash(As::sa(number), decode_hash::<F>(hash)?);
+	}
+	rules.set_limits(BlockLimits {
+		max_block_size: extension.max_block_size.map(|size| size as usize),
+		max_extrinsics: extension.max_extrinsics.map(|count| count as usize),
+	});
+	Ok(rules)
+}
+
+/// Bootstrap the light client database from the snapshot file, unless the database is already initialized.
+fn import_light_snapshot<F: ServiceFactory>(
+	storage: &client_db::light::LightStorage<FactoryBlock<F>>,
+	path: &str,
+) -> Result<(), error::Error> {
+	if storage.info()?.best_hash != Default::default() {
+		return Ok(());
+	}
+
+	let mut encoded = Vec::new();
+	::std::fs::File::open(path)?.read_to_end(&mut encoded)?;
+	let snapshot = Decode::decode(&mut &encoded[..])
+		.ok_or_else(|| format!("Error decoding light snapshot {}", path))?;
+	storage.import_snapshot(snapshot)?;
+	info!("Imported light snapshot from {}", path);
+	Ok(())
+}
+
+/// Read the trusted checkpoint of the light client from the file with the encoded header and
+/// authorities set of the checkpoint block.
+fn read_light_checkpoint<F: ServiceFactory>(
+	path: &str,
+) -> Result<client::light::backend::Checkpoint<<FactoryBlock<F> as BlockT>::Header>, error::Error> {
+	let mut encoded = Vec::new();
+	::std::fs::File::open(path)?.read_to_end(&mut encoded)?;
+	let (header, authorities) = Decode::decode(&mut &encoded[..])
+		.ok_or_else(|| format!("Error decoding light checkpoint {}", path))?;
+	Ok(client::light::backend::Checkpoint { header, authorities })
 }
 
 /// A struct that implement `Components` for the full client.
@@ -247,21 +346,26 @@ impl<Factory: ServiceFactory> Components for FullComponents<Factory> {
 	)
 		-> Result<(
 			Arc<ComponentClient<Self>>,
-			Option<Arc<OnDemand<FactoryBlock<Self::Factory>, NetworkService<Self::Factory>>>>
+			Option<Arc<FactoryOnDemand<Self::Factory>>>,
+			Option<Arc<FactoryOnDemand<Self::Factory>>>,
 		), error::Error>
 	{
 		let db_settings = client_db::DatabaseSettings {
 			cache_size: None,
 			path: config.database_path.as_str().into(),
 			pruning: config.pruning.clone(),
+			read_only: config.database_read_only,
+			memory_budget: config.memory_budget.clone(),
 		};
-		Ok((Arc::new(client_db::new_client(
+		let client = client_db::new_client(
 			db_settings,
 			executor,
 			&config.chain_spec,
 			config.block_execution_strategy,
 			config.api_execution_strategy,
-		)?), None))
+		)?;
+		client.set_justification_verifier(Factory::build_justification_verifier(config)?);
+		Ok((Arc::new(client), None, None))
 	}
 
 	fn build_transaction_pool(config: TransactionPoolOptions, client: Arc<ComponentClient<Self>>)
@@ -274,7 +378,16 @@ impl<Factory: ServiceFactory> Components for FullComponents<Factory> {
 		config: &FactoryFullConfiguration<Self::Factory>,
 		client: Arc<ComponentClient<Self>>
 	) -> Result<Self::ImportQueue, error::Error> {
-		Factory::build_full_import_queue(config, client)
+		let queue = Factory::build_full_import_queue(config, client)?;
+		queue.set_block_rules(block_rules::<Factory>(config)?);
+		Ok(queue)
+	}
+
+	fn on_new_best_block(
+		_client: &ComponentClient<Self>,
+		_header: <FactoryBlock<Self::Factory> as BlockT>::Header,
+	) -> Option<Box<Future<Item=(), Error=()> + Send>> {
+		None
 	}
 }
 
@@ -296,22 +409,68 @@ impl<Factory: ServiceFactory> Components for LightComponents<Factory> {
 	)
 		-> Result<(
 			Arc<ComponentClient<Self>>,
-			Option<Arc<OnDemand<FactoryBlock<Self::Factory>,
-			NetworkService<Self::Factory>>>>
+			Option<Arc<FactoryOnDemand<Self::Factory>>>,
+			Option<Arc<FactoryOnDemand<Self::Factory>>>,
 		), error::Error>
 	{
 		let db_settings = client_db::DatabaseSettings {
 			cache_size: None,
 			path: config.database_path.as_str().into(),
 			pruning: config.pruning.clone(),
+			read_only: config.database_read_only,
+			memory_budget: None,
 		};
 		let db_storage = client_db::light::LightStorage::new(db_settings)?;
+		if let Some(ref path) = config.light_snapshot {
+			import_light_snapshot::<Factory>(&db_storage, path)?;
+		}
 		let light_blockchain = client::light::new_light_blockchain(db_storage);
+		light_blockchain.set_headers_pruning(config.light_headers_pruning);
 		let fetch_checker = Arc::new(client::light::new_fetch_checker::<_, Blake2Hasher>(executor));
-		let fetcher = Arc::new(network::OnDemand::new(fetch_checker));
-		let client_backend = client::light::new_light_backend(light_blockchain, fetcher.clone());
+		let mut fetcher_config = config.light_fetcher.clone();
+		let fallback_on_demand = match config.light_fallback_nodes.is_empty() {
+			true => None,
+			false => {
+				// the requests no connected peer can serve are resent to the fallback nodes
+				fetcher_config.unserved_timeout = Some(fetcher_config.request_timeout);
+				Some(Arc::new(network::OnDemand::with_config(fetch_checker.clone(), config.light_fetcher.clone())))
+			},
+		};
+		let on_demand = Arc::new(network::OnDemand::with_config(fetch_checker, fetcher_config));
+		let fetcher = Arc::new(client::light::failover::FailoverFetcher::new(
+			Arc::downgrade(&on_demand),
+			fallback_on_demand.as_ref().map(Arc::downgrade).unwrap_or_else(Weak::new),
+		));
+		let checkpoint = match config.light_checkpoint {
+			Some(ref path) => Some(read_light_checkpoint::<Factory>(path)?),
+			None => None,
+		};
+		let client_backend = client::light::new_light_backend(
+			light_blockchain,
+			fetcher.clone(),
+			config.light_read_cache_size,
+			checkpoint,
+		)?;
+		if let Some(ref budget) = config.memory_budget {
+			client_backend.set_memory_budget(budget);
+		}
+		// the code and authorities are read by almost every block import and runtime call; the
+		// keys of the authorities are pinned once their number is read
+		client_backend.pin_storage_keys(vec![
+			well_known_keys::CODE.to_vec(),
+			well_known_keys::AUTHORITY_COUNT.to_vec(),
+		]);
+		let misbehavior_log = Arc::new(
+			client::light::misbehavior::AuxMisbehaviorLog::<_, FactoryBlock<Factory>, Blake2Hasher>::new(client_backend.clone())
+		);
+		for on_demand in Some(&on_demand).into_iter().chain(fallback_on_demand.as_ref()) {
+			on_demand.set_misbehavior_log(misbehavior_log.clone());
+		}
 		let client = client::light::new_light(client_backend, fetcher.clone(), &config.chain_spec)?;
-		Ok((Arc::new(client), Some(fetcher)))
+		// the light client doesn't execute the blocks, so it can't start without checking the headers
+		client.set_justification_verifier(Factory::build_justification_verifier(config)
+			.map_err(|e| format!("Light client requires the justification verifier: {}", e))?);
+		Ok((Arc::new(client), Some(on_demand), fallback_on_demand))
 	}
 
 	fn build_transaction_pool(config: TransactionPoolOptions, client: Arc<ComponentClient<Self>>)
@@ -324,6 +483,16 @@ impl<Factory: ServiceFactory> Components for LightComponents<Factory> {
 		config: &FactoryFullConfiguration<Self::Factory>,
 		client: Arc<ComponentClient<Self>>
 	) -> Result<Self::ImportQueue, error::Error> {
-		Factory::build_light_import_queue(config, client)
+		let queue = Factory::build_light_import_queue(config, client)?;
+		queue.set_block_rules(block_rules::<Factory>(config)?);
+		Ok(queue)
+	}
+
+	fn on_new_best_block(
+		client: &ComponentClient<Self>,
+		header: <FactoryBlock<Self::Factory> as BlockT>::Header,
+	) -> Option<Box<Future<Item=(), Error=()> + Send>> {
+		// the pinned keys are read by the single request, instead of a request per block import
+		Some(Box::new(client.backend().refresh_pinned_keys::<FactoryBlock<Factory>>(header)))
 	}
 }
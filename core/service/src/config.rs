@@ -17,13 +17,20 @@
 //! Service configuration.
 
 use std::net::SocketAddr;
+use std::time::Duration;
 use transaction_pool;
 use chain_spec::ChainSpec;
-pub use client::ExecutionStrategy;
+pub use client::{ExecutionStrategy, ForkChoiceRule};
+pub use client::light::blockchain::HeadersPruning;
+pub use client::memory_budget::MemoryBudget;
 pub use network::Roles;
-pub use network::NetworkConfiguration;
+pub use network::{FetcherConfig, NetworkConfiguration, WatchdogConfig};
 pub use client_db::PruningMode;
+pub use rpc::RateLimits as RpcRateLimits;
+pub use rpc::ExecutionPoolConfig as RpcExecutionPoolConfig;
+pub use offchain::HttpConfig as OffchainHttpConfig;
 use runtime_primitives::BuildStorage;
+use primitives::Bytes;
 use serde::{Serialize, de::DeserializeOwned};
 use target_info::Target;
 
@@ -40,16 +47,29 @@ pub struct Configuration<C, G: Serialize + DeserializeOwned + BuildStorage> {
 	pub roles: Roles,
 	/// Extrinsic pool configuration.
 	pub transaction_pool: transaction_pool::txpool::Options,
+	/// Save the transaction pool on shutdown and periodically, and restore it on startup.
+	pub persist_transaction_pool: bool,
 	/// Network configuration.
 	pub network: NetworkConfiguration,
 	/// Path to key files.
 	pub keystore_path: String,
 	/// Path to the database.
 	pub database_path: String,
+	/// Open the checkpoint of the existing database without writing to it, so that the database
+	/// of the running node may be queried. Importing blocks fails.
+	pub database_read_only: bool,
 	/// Pruning settings.
 	pub pruning: PruningMode,
+	/// Address of the archive node (with the `/p2p/` peer id) queried by the RPC for the state that
+	/// has been pruned from the local database. Such queries fail if `None`.
+	pub archive_fallback: Option<String>,
 	/// Additional key seeds.
 	pub keys: Vec<String>,
+	/// Hashes of blocks that must not be imported, in addition to those listed in the chain spec.
+	pub bad_blocks: Vec<Bytes>,
+	/// Block numbers with the only hash that may be imported at that height, in addition to those
+	/// listed in the chain spec.
+	pub fork_blocks: Vec<(u64, Bytes)>,
 	/// Chain configuration.
 	pub chain_spec: ChainSpec<G>,
 	/// Custom configuration.
@@ -64,8 +84,53 @@ pub struct Configuration<C, G: Serialize + DeserializeOwned + BuildStorage> {
 	pub rpc_http: Option<SocketAddr>,
 	/// RPC over Websockets binding address. `None` if disabled.
 	pub rpc_ws: Option<SocketAddr>,
+	/// Rate limits of the RPC requests, shared by the HTTP and Websockets servers.
+	pub rpc_rate_limits: RpcRateLimits,
+	/// Pool executing the runtime calls of the RPC requests, shared by the HTTP and Websockets servers.
+	pub rpc_execution_pool: RpcExecutionPoolConfig,
+	/// Allow the RPC methods managing the node, like adding the reserved peers.
+	pub rpc_unsafe: bool,
 	/// Telemetry service URL. `None` if disabled.
 	pub telemetry_url: Option<String>,
+	/// Author blocks on demand, via the `engine_*` RPC, instead of running the consensus engine.
+	pub manual_seal: bool,
+	/// Author and finalize a block for every transaction imported to the pool. Requires `manual_seal`.
+	pub instant_seal: bool,
+	/// Don't build changes tries of blocks imported during major sync, build them in background afterwards.
+	pub skip_changes_tries_on_sync: bool,
+	/// Compact the database once the node has been idle for this period: the best block hasn't
+	/// changed and the node isn't major syncing. `None` if disabled.
+	pub idle_compaction: Option<Duration>,
+	/// Rule used to choose the best block among the blocks of the same height.
+	pub fork_choice: ForkChoiceRule,
+	/// Path to the light client database snapshot, which is imported if the light client database is empty.
+	pub light_snapshot: Option<String>,
+	/// Path to the encoded header and authorities set of the trusted checkpoint the light client is synced from.
+	pub light_checkpoint: Option<String>,
+	/// Number of distinct peers whose announced heads are cross-checked by the light client. Disabled if zero.
+	pub light_head_providers: usize,
+	/// Size of the remote read results cached by the light client, in bytes.
+	pub light_read_cache_size: usize,
+	/// Pruning of the light client headers which are covered by CHTs.
+	pub light_headers_pruning: HeadersPruning,
+	/// Retry policy of the requests sent by the light client to the full nodes.
+	pub light_fetcher: FetcherConfig,
+	/// Watch the storage keys of the light client subscriptions by reading them from the full nodes
+	/// at every new best block, instead of subscribing to the changes pushed by the full nodes.
+	pub light_storage_polling: bool,
+	/// Addresses of the full nodes the failed light client requests are resent to.
+	pub light_fallback_nodes: Vec<String>,
+	/// Maximal estimated size of the execution proof of the authored blocks, in bytes. `None` if unlimited.
+	pub proof_budget: Option<usize>,
+	/// Detection of the stalled chain, rotating the peers when the best block doesn't change for too long.
+	/// Disabled if `None`.
+	pub watchdog: Option<WatchdogConfig>,
+	/// Memory budget shared by the caches of the node (the trie node and header caches of the full client,
+	/// the remote read cache of the light client) and the transaction pool. The components are shrunk when
+	/// it's exceeded. `None` if unlimited.
+	pub memory_budget: Option<MemoryBudget>,
+	/// HTTP client of the offchain worker. The requests to all hosts are denied by default.
+	pub offchain_http: OffchainHttpConfig,
 }
 
 impl<C: Default, G: Serialize + DeserializeOwned + BuildStorage> Configuration<C, G> {
@@ -79,17 +144,42 @@ impl<C: Default, G: Serialize + DeserializeOwned + BuildStorage> Configuration<C
 			name: Default::default(),
 			roles: Roles::FULL,
 			transaction_pool: Default::default(),
+			persist_transaction_pool: false,
 			network: Default::default(),
 			keystore_path: Default::default(),
 			database_path: Default::default(),
+			database_read_only: false,
 			keys: Default::default(),
+			bad_blocks: Default::default(),
+			fork_blocks: Default::default(),
 			custom: Default::default(),
 			pruning: PruningMode::default(),
+			archive_fallback: None,
 			block_execution_strategy: ExecutionStrategy::Both,
 			api_execution_strategy: ExecutionStrategy::Both,
 			rpc_http: None,
 			rpc_ws: None,
+			rpc_rate_limits: Default::default(),
+			rpc_execution_pool: Default::default(),
+			rpc_unsafe: false,
 			telemetry_url: None,
+			manual_seal: false,
+			instant_seal: false,
+			skip_changes_tries_on_sync: false,
+			idle_compaction: None,
+			fork_choice: Default::default(),
+			light_snapshot: None,
+			light_checkpoint: None,
+			light_head_providers: 0,
+			light_read_cache_size: ::client::light::backend::DEFAULT_REMOTE_READ_CACHE_SIZE,
+			light_headers_pruning: Default::default(),
+			light_fetcher: Default::default(),
+			light_storage_polling: false,
+			light_fallback_nodes: Vec::new(),
+			proof_budget: None,
+			watchdog: None,
+			memory_budget: None,
+			offchain_http: Default::default(),
 		};
 		configuration.network.boot_nodes = configuration.chain_spec.boot_nodes().to_vec();
 		configuration.telemetry_url = configuration.chain_spec.telemetry_url().map(str::to_owned);
@@ -18,12 +18,13 @@
 
 // FIXME: move this into substrate-consensus-common - https://github.com/paritytech/substrate/issues/1021
 
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::{self, Duration, Instant};
 use std;
 
 use client::{self, error, Client as SubstrateClient, CallExecutor};
-use client::runtime_api::{Core, BlockBuilder as BlockBuilderAPI, id::BLOCK_BUILDER};
+use client::runtime_api::{Core, BlockBuilder as BlockBuilderAPI, id::BlockBuilderApi};
 use codec::{Decode, Encode};
 use consensus_common::{self, InherentData, evaluation, offline_tracker::OfflineTracker};
 use primitives::{H256, AuthorityId, ed25519, Blake2Hasher};
@@ -44,6 +45,9 @@ const MAX_TRANSACTIONS_SIZE: usize = 4 * 1024 * 1024;
 pub trait BlockBuilder<Block: BlockT> {
 	/// Push an extrinsic onto the block. Fails if the extrinsic is invalid.
 	fn push_extrinsic(&mut self, extrinsic: <Block as BlockT>::Extrinsic) -> Result<(), error::Error>;
+
+	/// Estimated size of the proof of the pushed extrinsics, if the proof is being recorded.
+	fn proof_size(&self) -> Option<usize>;
 }
 
 /// Local client abstraction for the consensus.
@@ -58,13 +62,19 @@ pub trait AuthoringApi:
 	/// The error used by this API type.
 	type Error: std::error::Error;
 
-	/// Build a block on top of the given, with inherent extrinsics pre-pushed.
+	/// Build a block on top of the given, with inherent extrinsics pre-pushed. If the proof budget
+	/// is given, the proof of the whole block execution, from its initialisation through its
+	/// finalisation, is recorded and its estimated size is returned along with the block.
 	fn build_block<F: FnMut(&mut BlockBuilder<Self::Block>) -> ()>(
 		&self,
 		at: &BlockId<Self::Block>,
 		inherent_data: InherentData,
+		proof_budget: Option<usize>,
 		build_ctx: F,
-	) -> Result<Self::Block, error::Error>;
+	) -> Result<(Self::Block, Option<usize>), error::Error>;
+
+	/// Whether the state of the given block is available, so that blocks can be built on top of it.
+	fn has_state_at(&self, at: &BlockId<Self::Block>) -> Result<bool, error::Error>;
 }
 
 impl<'a, B, E, Block> BlockBuilder<Block> for client::block_builder::BlockBuilder<'a, B, E, Block, Blake2Hasher> where
@@ -75,6 +85,10 @@ impl<'a, B, E, Block> BlockBuilder<Block> for client::block_builder::BlockBuilde
 	fn push_extrinsic(&mut self, extrinsic: <Block as BlockT>::Extrinsic) -> Result<(), error::Error> {
 		client::block_builder::BlockBuilder::push(self, extrinsic).map_err(Into::into)
 	}
+
+	fn proof_size(&self) -> Option<usize> {
+		client::block_builder::BlockBuilder::proof_size(self)
+	}
 }
 
 impl<'a, B, E, Block> AuthoringApi for SubstrateClient<B, E, Block> where
@@ -89,19 +103,33 @@ impl<'a, B, E, Block> AuthoringApi for SubstrateClient<B, E, Block> where
 		&self,
 		at: &BlockId<Self::Block>,
 		inherent_data: InherentData,
+		proof_budget: Option<usize>,
 		mut build_ctx: F,
-	) -> Result<Self::Block, error::Error> {
-		let runtime_version = self.runtime_version_at(at)?;
-
-		let mut block_builder = self.new_block_at(at)?;
-		if runtime_version.has_api(BLOCK_BUILDER, 1) {
+	) -> Result<(Self::Block, Option<usize>), error::Error> {
+		let mut block_builder = match proof_budget {
+			Some(_) => self.new_block_at_recording_proof(at, proof_budget)?,
+			None => self.new_block_at(at)?,
+		};
+		if self.has_api::<BlockBuilderApi>(at)? {
 			self.inherent_extrinsics(at, &inherent_data)?
 				.into_iter().try_for_each(|i| block_builder.push(i))?;
 		}
 
 		build_ctx(&mut block_builder);
 
-		block_builder.bake().map_err(Into::into)
+		block_builder.bake_with_proof_size().map_err(Into::into)
+	}
+
+	fn has_state_at(&self, at: &BlockId<Self::Block>) -> Result<bool, error::Error> {
+		match self.state_at(at) {
+			Ok(_) => Ok(true),
+			Err(e) => {
+				if let error::ErrorKind::UnknownBlock(_) = *e.kind() {
+					return Ok(false);
+				}
+				Err(e)
+			},
+		}
 	}
 }
 
@@ -118,6 +146,9 @@ pub struct ProposerFactory<C, A> where
 	pub offline: SharedOfflineTracker,
 	/// Force delay in evaluation this long.
 	pub force_delay: Timestamp,
+	/// Maximal estimated size of the proof of the extrinsics included into the proposed block.
+	/// The proof is recorded only if the budget is set.
+	pub proof_budget: Option<usize>,
 }
 
 impl<C, A> consensus_common::Environment<<C as AuthoringApi>::Block> for ProposerFactory<C, A> where
@@ -134,10 +165,33 @@ impl<C, A> consensus_common::Environment<<C as AuthoringApi>::Block> for Propose
 		_: &[AuthorityId],
 		_: Arc<ed25519::Pair>,
 	) -> Result<Self::Proposer, error::Error> {
+		self.init_with_timestamp(parent_header, current_timestamp() + self.force_delay)
+	}
+}
+
+impl<C, A> ProposerFactory<C, A> where
+	C: AuthoringApi,
+	A: txpool::ChainApi<Block=<C as AuthoringApi>::Block>,
+	client::error::Error: From<<C as AuthoringApi>::Error>
+{
+	/// Create a proposer stamping the block with at least given timestamp, instead of the current
+	/// time. This allows blocks to be authored faster than the block period of the chain.
+	pub fn init_with_timestamp(
+		&self,
+		parent_header: &<<C as AuthoringApi>::Block as BlockT>::Header,
+		minimum_timestamp: Timestamp,
+	) -> Result<Proposer<C, A>, error::Error> {
 		let parent_hash = parent_header.hash();
 
 		let id = BlockId::hash(parent_hash);
 
+		// the parent isn't necessarily the best block, so its state may have been pruned.
+		if !self.client.has_state_at(&id)? {
+			return Err(error::ErrorKind::UnknownBlock(
+				format!("Cannot build on top of {:?}: state is not available", parent_hash)
+			).into());
+		}
+
 		let authorities: Vec<AuthorityId> = self.client.authorities(&id)?;
 		self.offline.write().note_new_block(&authorities[..]);
 
@@ -153,7 +207,8 @@ impl<C, A> consensus_common::Environment<<C as AuthoringApi>::Block> for Propose
 			transaction_pool: self.transaction_pool.clone(),
 			offline: self.offline.clone(),
 			authorities,
-			minimum_timestamp: current_timestamp() + self.force_delay,
+			minimum_timestamp,
+			proof_budget: self.proof_budget,
 		};
 
 		Ok(proposer)
@@ -171,6 +226,7 @@ pub struct Proposer<C: AuthoringApi, A: txpool::ChainApi> {
 	offline: SharedOfflineTracker,
 	authorities: Vec<AuthorityId>,
 	minimum_timestamp: u64,
+	proof_budget: Option<usize>,
 }
 
 impl<C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Proposer<C, A> where
@@ -182,6 +238,19 @@ impl<C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Proposer<C
 	type Error = error::Error;
 
 	fn propose(&self) -> Result<<C as AuthoringApi>::Block, error::Error> {
+		self.propose_with_proof_size().map(|(block, _)| block)
+	}
+}
+
+impl<C, A> Proposer<C, A> where
+	C: AuthoringApi,
+	A: txpool::ChainApi<Block=<C as AuthoringApi>::Block>,
+	client::error::Error: From<<C as AuthoringApi>::Error>
+{
+	/// Propose a block, returning it along with the estimated size of the proof of its
+	/// extrinsics if the proof budget is set. Extrinsics that don't fit into the budget are
+	/// left in the pool.
+	pub fn propose_with_proof_size(&self) -> Result<(<C as AuthoringApi>::Block, Option<usize>), error::Error> {
 		use runtime_primitives::traits::BlakeTwo256;
 
 		const MAX_VOTE_OFFLINE_SECONDS: Duration = Duration::from_secs(60);
@@ -206,23 +275,40 @@ impl<C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Proposer<C
 			offline_indices,
 		};
 
-		let block = self.client.build_block(
+		let (block, proof_size) = self.client.build_block(
 			&self.parent_id,
 			inherent_data,
+			self.proof_budget,
 			|block_builder| {
 				let mut unqueue_invalid = Vec::new();
 				let mut pending_size = 0;
+				// tags provided by transactions left out of the block
+				let mut skipped_tags = HashSet::new();
 				let pending_iterator = self.transaction_pool.ready();
 
+				// ready transactions come in order of priority, so when the block is getting full
+				// the ones left out are those with the lowest priority that would not fit.
 				for pending in pending_iterator {
+					if pending.requires.iter().any(|tag| skipped_tags.contains(tag)) {
+						skipped_tags.extend(pending.provides.iter().cloned());
+						continue;
+					}
+
 					// TODO [ToDr] Probably get rid of it, and validate in runtime.
 					let encoded_size = pending.data.encode().len();
-					if pending_size + encoded_size >= MAX_TRANSACTIONS_SIZE { break }
+					if pending_size + encoded_size >= MAX_TRANSACTIONS_SIZE {
+						skipped_tags.extend(pending.provides.iter().cloned());
+						continue;
+					}
 
 					match block_builder.push_extrinsic(pending.data.clone()) {
 						Ok(()) => {
 							pending_size += encoded_size;
 						}
+						Err(error::Error(error::ErrorKind::ProofBudgetExceeded(size, budget), _)) => {
+							trace!(target: "transaction-pool", "Transaction {:?} exceeds the proof budget: {} > {}", pending.hash, size, budget);
+							skipped_tags.extend(pending.provides.iter().cloned());
+						}
 						Err(e) => {
 							trace!(target: "transaction-pool", "Invalid transaction: {}", e);
 							unqueue_invalid.push(pending.hash.clone());
@@ -242,6 +328,9 @@ impl<C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Proposer<C
 			  .collect::<Vec<_>>()
 			  .join(", ")
 			 );
+		if let Some(proof_size) = proof_size {
+			debug!("Estimated proof size of the proposed block: {} bytes", proof_size);
+		}
 
 		let substrate_block = Decode::decode(&mut block.encode().as_slice())
 			.expect("blocks are defined to serialize to substrate blocks correctly; qed");
@@ -252,7 +341,7 @@ impl<C, A> consensus_common::Proposer<<C as AuthoringApi>::Block> for Proposer<C
 			self.parent_number,
 		).is_ok());
 
-		Ok(substrate_block)
+		Ok((substrate_block, proof_size))
 	}
 }
 
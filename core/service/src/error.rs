@@ -19,6 +19,7 @@
 use client;
 use network;
 use keystore;
+use primitives::H256;
 
 error_chain! {
 	foreign_links {
@@ -31,5 +32,10 @@ error_chain! {
 	}
 
 	errors {
+		/// Genesis state root computed from the chain spec differs from the pinned one.
+		GenesisStateRootMismatch(expected: H256, computed: H256) {
+			description("genesis state root mismatch"),
+			display("Genesis state root mismatch: expected {:?}, computed {:?}", expected, computed),
+		}
 	}
 }
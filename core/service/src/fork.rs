@@ -0,0 +1,203 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Local forks of live chains.
+//!
+//! The state of a live chain block is fetched over JSON-RPC and used as the genesis state
+//! of a local chain. Its authority set is replaced with local keys, so that blocks may be
+//! authored locally, e.g. to test runtime upgrades against the real state.
+
+use std::collections::HashMap;
+use futures::{Future, Stream};
+use hyper;
+use serde_json::{self as json, Value};
+use tokio::runtime::current_thread::Runtime;
+
+use codec::Encode;
+use primitives::{AuthorityId, H256};
+use primitives::storage::{StorageKey, StorageData, well_known_keys};
+use substrate_rpc::state::MAX_PAIRS_PAGE;
+use error;
+
+/// State of a live chain block.
+pub struct Snapshot {
+	/// Hash of the block.
+	pub hash: H256,
+	/// Number of the block.
+	pub number: u64,
+	/// Storage of the block state.
+	pub storage: HashMap<StorageKey, StorageData>,
+}
+
+/// Fetch state of the block with given number (the best block if `None`) from the node
+/// serving JSON-RPC over HTTP at given url. The storage is fetched in pages of
+/// `MAX_PAIRS_PAGE` entries.
+pub fn fetch_snapshot(url: &str, number: Option<u64>) -> error::Result<Snapshot> {
+	let uri: hyper::Uri = url.parse().map_err(|e| format!("Invalid fork url {}: {}", url, e))?;
+	let mut runtime = Runtime::new()?;
+	let mut call = |method: &str, params: Value| rpc_call(&mut runtime, &uri, method, params);
+
+	let params = match number {
+		Some(number) => json!([number]),
+		None => json!([]),
+	};
+	let hash: Option<H256> = json::from_value(call("chain_getBlockHash", params)?)
+		.map_err(|e| format!("Invalid block hash: {}", e))?;
+	let hash = hash.ok_or_else(|| format!("Block {:?} is unknown to the remote node", number))?;
+
+	let header = call("chain_getHeader", json!([hash]))?;
+	let number = header.get("number").and_then(Value::as_u64)
+		.ok_or_else(|| format!("Invalid header of block {}", hash))?;
+
+	let mut storage = HashMap::new();
+	let mut start_key: Option<StorageKey> = None;
+	loop {
+		let params = json!(["0x", MAX_PAIRS_PAGE, start_key, hash]);
+		let page: Vec<(StorageKey, StorageData)> = json::from_value(call("state_getPairsPaged", params)?)
+			.map_err(|e| format!("Invalid storage of block {}: {}", hash, e))?;
+		let is_last = page.len() < MAX_PAIRS_PAGE as usize;
+		start_key = page.last().map(|&(ref key, _)| key.clone());
+		storage.extend(page);
+		if is_last {
+			break;
+		}
+	}
+
+	Ok(Snapshot {
+		hash,
+		number,
+		storage,
+	})
+}
+
+/// Replace the authority set in the storage with given authorities.
+pub fn set_authorities(storage: &mut HashMap<StorageKey, StorageData>, authorities: &[AuthorityId]) {
+	storage.retain(|key, _| !key.0.starts_with(well_known_keys::AUTHORITY_PREFIX));
+	storage.insert(
+		StorageKey(well_known_keys::AUTHORITY_COUNT.to_vec()),
+		StorageData((authorities.len() as u32).encode()),
+	);
+	for (index, authority) in authorities.iter().enumerate() {
+		let mut key = well_known_keys::AUTHORITY_PREFIX.to_vec();
+		(index as u32).encode_to(&mut key);
+		storage.insert(StorageKey(key), StorageData(authority.encode()));
+	}
+}
+
+fn rpc_call(runtime: &mut Runtime, uri: &hyper::Uri, method: &str, params: Value) -> error::Result<Value> {
+	let request = json!({
+		"jsonrpc": "2.0",
+		"id": 1,
+		"method": method,
+		"params": params,
+	});
+	let request = hyper::Request::post(uri.clone())
+		.header(hyper::header::CONTENT_TYPE, "application/json")
+		.body(hyper::Body::from(request.to_string()))
+		.map_err(|e| format!("Error building {} request: {}", method, e))?;
+	let response = runtime.block_on(hyper::Client::new()
+		.request(request)
+		.and_then(|response| response.into_body().concat2()))
+		.map_err(|e| format!("Error calling {}: {}", method, e))?;
+
+	match json::from_slice(&response) {
+		Ok(Value::Object(mut response)) => {
+			if let Some(error) = response.remove("error") {
+				return Err(format!("Remote node has failed to execute {}: {}", method, error).into());
+			}
+			response.remove("result")
+				.ok_or_else(|| format!("Invalid {} response: no result", method).into())
+		},
+		Ok(_) => Err(format!("Invalid {} response", method).into()),
+		Err(e) => Err(format!("Invalid {} response: {}", method, e).into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hyper::{Body, Request, Response, Server};
+	use hyper::service::service_fn;
+
+	const NUMBER_OF_ENTRIES: u8 = 250;
+
+	fn remote_storage() -> Vec<(StorageKey, StorageData)> {
+		(0..NUMBER_OF_ENTRIES)
+			.flat_map(|a| (0..8).map(move |b| (StorageKey(vec![a, b]), StorageData(vec![a ^ b]))))
+			.collect()
+	}
+
+	fn respond(method: &str, params: &Value) -> Value {
+		match method {
+			"chain_getBlockHash" => json!(H256::from(42)),
+			"chain_getHeader" => json!({ "number": 42 }),
+			"state_getPairsPaged" => {
+				let count = params[1].as_u64().unwrap() as usize;
+				let start_key: Option<StorageKey> = json::from_value(params[2].clone()).unwrap();
+				let page: Vec<_> = remote_storage().into_iter()
+					.filter(|&(ref key, _)| start_key.as_ref().map_or(true, |start_key| key > start_key))
+					.take(count)
+					.collect();
+				json!(page)
+			},
+			_ => panic!("Unexpected method {}", method),
+		}
+	}
+
+	#[test]
+	fn snapshot_is_fetched_page_by_page() {
+		let mut runtime = ::tokio::runtime::Runtime::new().unwrap();
+		let server = Server::bind(&([127, 0, 0, 1], 0).into())
+			.serve(|| service_fn(|request: Request<Body>| {
+				request.into_body().concat2().map(|body| {
+					let request: Value = json::from_slice(&body).unwrap();
+					let result = respond(request["method"].as_str().unwrap(), &request["params"]);
+					Response::new(Body::from(json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string()))
+				})
+			}));
+		let url = format!("http://{}", server.local_addr());
+		runtime.spawn(server.map_err(|e| panic!("Server error: {:?}", e)));
+
+		let snapshot = fetch_snapshot(&url, Some(42)).unwrap();
+		assert_eq!(snapshot.hash, H256::from(42));
+		assert_eq!(snapshot.number, 42);
+		assert!(snapshot.storage.len() > MAX_PAIRS_PAGE as usize);
+		assert_eq!(snapshot.storage, remote_storage().into_iter().collect());
+		runtime.shutdown_now().wait().unwrap();
+	}
+
+	#[test]
+	fn authorities_are_replaced() {
+		let authority_key = |index: u32| {
+			let mut key = well_known_keys::AUTHORITY_PREFIX.to_vec();
+			index.encode_to(&mut key);
+			StorageKey(key)
+		};
+		let mut storage = HashMap::new();
+		storage.insert(StorageKey(well_known_keys::AUTHORITY_COUNT.to_vec()), StorageData(2u32.encode()));
+		storage.insert(authority_key(0), StorageData(AuthorityId::from([1; 32]).encode()));
+		storage.insert(authority_key(1), StorageData(AuthorityId::from([2; 32]).encode()));
+		storage.insert(StorageKey(b":code".to_vec()), StorageData(vec![42]));
+
+		set_authorities(&mut storage, &[AuthorityId::from([3; 32])]);
+
+		let mut expected = HashMap::new();
+		expected.insert(StorageKey(well_known_keys::AUTHORITY_COUNT.to_vec()), StorageData(1u32.encode()));
+		expected.insert(authority_key(0), StorageData(AuthorityId::from([3; 32]).encode()));
+		expected.insert(StorageKey(b":code".to_vec()), StorageData(vec![42]));
+		assert_eq!(storage, expected);
+	}
+}
@@ -24,22 +24,30 @@
 extern crate futures;
 extern crate exit_future;
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
 extern crate parking_lot;
 extern crate substrate_keystore as keystore;
 extern crate substrate_primitives as primitives;
 extern crate sr_primitives as runtime_primitives;
 extern crate substrate_consensus_common as consensus_common;
+extern crate substrate_consensus_manual_seal as manual_seal;
+extern crate substrate_finality_grandpa as grandpa;
+#[cfg(feature = "rhd")]
+extern crate substrate_consensus_rhd as rhd;
 extern crate substrate_network as network;
+extern crate substrate_offchain as offchain;
 extern crate substrate_executor;
 extern crate substrate_client as client;
 extern crate substrate_client_db as client_db;
 extern crate parity_codec as codec;
 extern crate substrate_transaction_pool as transaction_pool;
+extern crate substrate_logger as logger;
 extern crate substrate_rpc;
 extern crate substrate_rpc_servers as rpc;
 extern crate target_info;
 extern crate tokio;
+extern crate hyper;
 
 #[macro_use]
 extern crate substrate_telemetry as tel;
@@ -55,38 +63,51 @@ extern crate serde_derive;
 mod components;
 mod error;
 mod chain_spec;
+mod fork;
+mod maintenance;
+mod task_manager;
 pub mod config;
 pub mod chain_ops;
 pub mod consensus;
 
 use std::io;
 use std::net::SocketAddr;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
 #[doc(hidden)]
 pub use std::{ops::Deref, result::Result, sync::Arc};
 use futures::prelude::*;
 use parking_lot::{Mutex, RwLock};
 use keystore::Store as Keystore;
-use client::BlockchainEvents;
-use runtime_primitives::traits::{Header, As};
+use network::{ManageNetwork, SyncProvider};
+use client::{BlockchainEvents, Notification};
+use primitives::{AuthorityId, Blake2Hasher};
+use runtime_primitives::traits::{Block as BlockT, Header, As, NumberFor};
 use runtime_primitives::generic::BlockId;
 use exit_future::Signal;
+use manual_seal::ManualSealApi;
+use grandpa::GrandpaApi;
+use rpc::apis::system::{SystemNetworkApi, SystemStateApi};
 #[doc(hidden)]
 pub use tokio::runtime::TaskExecutor;
 use substrate_executor::NativeExecutor;
 use codec::{Encode, Decode};
 
 pub use self::error::{ErrorKind, Error};
-pub use config::{Configuration, Roles, PruningMode};
-pub use chain_spec::ChainSpec;
+pub use config::{Configuration, FetcherConfig, HeadersPruning, Roles, PruningMode, RpcRateLimits, RpcExecutionPoolConfig,
+	WatchdogConfig, OffchainHttpConfig, MemoryBudget};
+pub use chain_spec::{ChainSpec, ChainSpecExtension, BlockRulesExtension, Properties};
+pub use task_manager::TaskManager;
 pub use transaction_pool::txpool::{self, Pool as TransactionPool, Options as TransactionPoolOptions, ChainApi, IntoPoolError};
-pub use client::ExecutionStrategy;
+pub use client::{ExecutionStrategy, ForkChoiceRule, JustificationVerifier};
 
 use consensus_common::offline_tracker::OfflineTracker;
-pub use consensus::ProposerFactory;
+use consensus_common::SyncOracle;
+pub use consensus::{Proposer, ProposerFactory};
 pub use components::{ServiceFactory, FullBackend, FullExecutor, LightBackend,
-	LightExecutor, Components, PoolApi, ComponentClient,
-	ComponentBlock, FullClient, LightClient, FullComponents, LightComponents,
+	LightExecutor, LightFetcher, FactoryOnDemand, Components, PoolApi, ComponentClient,
+	ComponentBlock, FullClient, LightClient, LightVerifier, FullComponents, LightComponents,
 	CodeExecutor, NetworkService, FactoryChainSpec, FactoryBlock,
 	FactoryFullConfiguration, RuntimeGenesis, FactoryGenesis,
 	ComponentExHash, ComponentExtrinsic, FactoryExtrinsic,
@@ -94,26 +115,51 @@ pub use components::{ServiceFactory, FullBackend, FullExecutor, LightBackend,
 
 const DEFAULT_PROTOCOL_ID: &'static str = "sup";
 
+/// Interval (in milliseconds) at which the transaction pool is notified of the major sync state.
+const POOL_SYNC_STATE_INTERVAL_MS: u64 = 1000;
+/// Interval (in milliseconds) at which changes tries skipped during major sync are backfilled.
+const CHANGES_TRIES_BACKFILL_INTERVAL_MS: u64 = 500;
+/// Number of blocks whose changes tries are backfilled at once.
+const CHANGES_TRIES_BACKFILL_BATCH: u64 = 64;
+/// Interval (in milliseconds) at which the transaction pool is saved, if persistence is enabled.
+const POOL_PERSIST_INTERVAL_MS: u64 = 60_000;
+/// Interval (in milliseconds) at which the size of the transaction pool is reported to the memory budget.
+const POOL_MEMORY_BUDGET_INTERVAL_MS: u64 = 1000;
+/// Weight of the transaction pool in the memory budget.
+const POOL_MEMORY_BUDGET_WEIGHT: usize = 1;
+
 /// Substrate service.
 pub struct Service<Components: components::Components> {
 	client: Arc<ComponentClient<Components>>,
 	network: Option<Arc<components::NetworkService<Components::Factory>>>,
+	on_demand: Option<Arc<network::OnDemand<ComponentBlock<Components>, components::NetworkService<Components::Factory>>>>,
 	transaction_pool: Arc<TransactionPool<Components::TransactionPoolApi>>,
+	persist_transaction_pool: bool,
 	keystore: Keystore,
 	exit: ::exit_future::Exit,
-	signal: Option<Signal>,
+	signal: Mutex<Option<Signal>>,
 	proposer: Arc<ProposerFactory<ComponentClient<Components>, Components::TransactionPoolApi>>,
+	manual_seal: Option<ManualSealChannel<ComponentBlock<Components>>>,
+	grandpa_round_state: grandpa::SharedRoundState<<ComponentBlock<Components> as BlockT>::Hash>,
 	_rpc_http: Option<rpc::HttpServer>,
 	_rpc_ws: Option<Mutex<rpc::WsServer>>, // WsServer is not `Sync`, but the service needs to be.
 	_telemetry: Option<tel::Telemetry>,
 }
 
+/// Channel of the manual seal engine commands.
+struct ManualSealChannel<Block: BlockT> {
+	sender: manual_seal::CommandSender<Block::Hash>,
+	receiver: Mutex<Option<manual_seal::CommandReceiver<Block::Hash>>>,
+	/// Set once the commands stream is taken by the engine.
+	engine_started: Arc<AtomicBool>,
+}
+
 /// Creates bare client without any networking.
 pub fn new_client<Factory: components::ServiceFactory>(config: &FactoryFullConfiguration<Factory>)
 	-> Result<Arc<ComponentClient<components::FullComponents<Factory>>>, error::Error>
 {
 	let executor = NativeExecutor::new();
-	let (client, _) = components::FullComponents::<Factory>::build_client(
+	let (client, _, _) = components::FullComponents::<Factory>::build_client(
 		config,
 		executor,
 	)?;
@@ -129,7 +175,7 @@ impl<Components> Service<Components>
 {
 	/// Creates a new service.
 	pub fn new(
-		config: FactoryFullConfiguration<Components::Factory>,
+		mut config: FactoryFullConfiguration<Components::Factory>,
 		task_executor: TaskExecutor,
 	)
 		-> Result<Self, error::Error>
@@ -156,7 +202,35 @@ impl<Components> Service<Components>
 			}
 		};
 
-		let (client, on_demand) = Components::build_client(&config, executor)?;
+		let (client, on_demand, fallback_on_demand) = Components::build_client(&config, executor.clone())?;
+		// the light client requests failed by the other peers are resent to the fallback nodes
+		let mut fallback_peers = Vec::new();
+		if fallback_on_demand.is_some() {
+			for address in &config.light_fallback_nodes {
+				let (peer_id, _) = network::parse_str_addr(address).map_err(network::error::Error::from)?;
+				config.network.reserved_nodes.push(address.clone());
+				info!("Failed light client requests are resent to {}", peer_id.to_base58());
+				fallback_peers.push(peer_id);
+			}
+		}
+
+		// the pruned state is fetched from the archive node and checked like the light client does
+		let (archive_fetcher, archive_peer) = match config.archive_fallback.clone() {
+			Some(ref address) if !config.roles.contains(Roles::LIGHT) => {
+				let (peer_id, _) = network::parse_str_addr(address).map_err(network::error::Error::from)?;
+				config.network.reserved_nodes.push(address.clone());
+				let fetch_checker = Arc::new(client::light::new_fetch_checker::<_, Blake2Hasher>(executor));
+				let fetcher = Arc::new(network::OnDemand::with_config(fetch_checker, config.light_fetcher.clone()));
+				info!("Pruned state is fetched from the archive node {}", peer_id.to_base58());
+				(Some(fetcher), Some(peer_id))
+			},
+			_ => (None, None),
+		};
+		client.set_fork_choice_rule(config.fork_choice);
+		if config.roles == Roles::AUTHORITY {
+			let local_keys: HashSet<AuthorityId> = keystore.contents()?.into_iter().map(Into::into).collect();
+			client.set_local_keys(Arc::new(local_keys));
+		}
 		let import_queue = Components::build_import_queue(&config, client.clone())?;
 		let best_header = client.best_block_header()?;
 
@@ -169,18 +243,44 @@ impl<Components> Service<Components>
 			Components::build_transaction_pool(config.transaction_pool, client.clone())?
 		);
 		let transaction_pool_adapter = TransactionPoolAdapter::<Components> {
-			imports_external_transactions: !(config.roles == Roles::LIGHT),
+			imports_external_transactions: !config.roles.contains(Roles::LIGHT),
 			pool: transaction_pool.clone(),
 			client: client.clone(),
 		 };
 
+		if config.persist_transaction_pool {
+			// revalidate the extrinsics saved on shutdown; the ones that are no longer valid are dropped
+			let best_block_id = BlockId::hash(best_header.hash());
+			match transaction_pool::persistence::load::<Components::TransactionPoolApi, _>(&**client.backend()) {
+				Ok(extrinsics) => {
+					let saved = extrinsics.len();
+					let restored = transaction_pool.submit_exported(&best_block_id, extrinsics)
+						.map(|results| results.into_iter().filter(Result::is_ok).count())
+						.unwrap_or(0);
+					if let Err(e) = transaction_pool.clear_stale(&best_block_id) {
+						warn!("Error removing stale extrinsics: {:?}", e);
+					}
+					info!("Restored {} of {} saved extrinsics", restored, saved);
+				},
+				Err(e) => warn!("Error loading saved extrinsics: {:?}", e),
+			}
+		}
+
 		let network_params = network::Params {
 			config: network::ProtocolConfig {
 				roles: config.roles,
+				light_head_providers: config.light_head_providers,
+				on_demand_peers: archive_peer.into_iter().collect(),
+				fallback_on_demand_peers: fallback_peers,
+				fork_id: config.chain_spec.fork_id().map(|fork_id| fork_id.as_bytes().to_vec()),
+				watchdog: config.watchdog.clone(),
+				..Default::default()
 			},
 			network_config: config.network,
 			chain: client.clone(),
-			on_demand: on_demand.clone()
+			on_demand: on_demand.clone().or_else(|| archive_fetcher.clone())
+				.map(|d| d as Arc<network::OnDemandService<ComponentBlock<Components>>>),
+			fallback_on_demand: fallback_on_demand.clone()
 				.map(|d| d as Arc<network::OnDemandService<ComponentBlock<Components>>>),
 			transaction_pool: Arc::new(transaction_pool_adapter),
 			specialization: network_protocol,
@@ -195,17 +295,79 @@ impl<Components> Service<Components>
 		&mut protocol_id[0..id_len].copy_from_slice(&protocol_id_full[0..id_len]);
 
 		let network = network::Service::new(network_params, protocol_id, import_queue)?;
-		on_demand.map(|on_demand| on_demand.set_service_link(Arc::downgrade(&network)));
+		if let Some(ref on_demand) = on_demand {
+			on_demand.set_service_link(Arc::downgrade(&network));
+			on_demand.set_storage_changes_sink(client.clone());
+		}
+		if let Some(ref archive_fetcher) = archive_fetcher {
+			archive_fetcher.set_service_link(Arc::downgrade(&network));
+		}
+		if let Some(ref fallback_on_demand) = fallback_on_demand {
+			fallback_on_demand.set_service_link(Arc::downgrade(&network));
+		}
 
 		{
 			// block notifications
 			let network = Arc::downgrade(&network);
 			let txpool = transaction_pool.clone();
+			let storage_client = Arc::downgrade(&client);
+			let on_demand = on_demand.clone();
+			let storage_watch = match (config.light_storage_polling, on_demand.as_ref()) {
+				(true, Some(on_demand)) => Some(client::light::storage_watch::StorageWatch::new(on_demand.clone())),
+				_ => None,
+			};
+			let watch_executor = task_executor.clone();
 
-			let events = client.import_notification_stream()
+			let lagged_client = Arc::downgrade(&client);
+			let events = client.import_notification_stream("service")
 				.for_each(move |notification| {
+					let notification = match notification {
+						Notification::Item(notification) => notification,
+						Notification::Lagged(missed) => {
+							// the pruning tags of the missed blocks are unknown => resync with the best block
+							debug!("Missed {} import notifications, resyncing with the best block", missed);
+							let client = match lagged_client.upgrade() {
+								Some(client) => client,
+								None => return Ok(()),
+							};
+							let header = client.best_block_header()
+								.map_err(|e| warn!("Error reading best block: {:?}", e))?;
+							let hash = header.hash();
+							if let Some(network) = network.upgrade() {
+								network.on_block_imported(hash, &header);
+							}
+							txpool.revalidate(&BlockId::hash(hash))
+								.map_err(|e| warn!("Error revalidating extrinsics: {:?}", e))?;
+							return Ok(());
+						},
+					};
 					if let Some(network) = network.upgrade() {
 						network.on_block_imported(notification.hash, &notification.header);
+						if notification.is_new_best && !network.is_major_syncing() {
+							let task = storage_client.upgrade().and_then(|client|
+								Components::on_new_best_block(&client, notification.header.clone()));
+							if let Some(task) = task {
+								watch_executor.spawn(task);
+							}
+						}
+					}
+					// keep keys watched at full nodes in sync with local storage subscriptions
+					if let (Some(on_demand), Some(client)) = (on_demand.as_ref(), storage_client.upgrade()) {
+						let keys = client.storage_listened_keys().into_iter().map(|key| key.0).collect();
+						match storage_watch {
+							Some(ref storage_watch) => if notification.is_new_best {
+								let hash = notification.hash;
+								let storage_client = storage_client.clone();
+								let changes = storage_watch.on_new_best::<ComponentBlock<Components>>(notification.header.clone(), keys)
+									.map(move |changes| match storage_client.upgrade() {
+										Some(ref client) if !changes.is_empty() => client.notify_storage_changes(&hash, changes),
+										_ => (),
+									})
+									.map_err(|e| warn!("Error reading watched storage keys: {:?}", e));
+								watch_executor.spawn(changes);
+							},
+							None => on_demand.subscribe_storage(keys),
+						}
 					}
 					txpool.prune_tags(&BlockId::hash(notification.hash), notification.tags)
 						.map_err(|e| warn!("Error removing extrinsics: {:?}", e))?;
@@ -233,30 +395,242 @@ impl<Components> Service<Components>
 			task_executor.spawn(events);
 		}
 
+		{
+			// queue extrinsics submitted while major syncing, import them once synced
+			let client = Arc::downgrade(&client);
+			let network = Arc::downgrade(&network);
+			let txpool = Arc::downgrade(&transaction_pool);
+			let sync_state = ::tokio::timer::Interval::new_interval(Duration::from_millis(POOL_SYNC_STATE_INTERVAL_MS))
+				.map_err(|e| warn!("Pool sync state timer error: {:?}", e))
+				.for_each(move |_| {
+					let (client, network, txpool) = match (client.upgrade(), network.upgrade(), txpool.upgrade()) {
+						(Some(client), Some(network), Some(txpool)) => (client, network, txpool),
+						_ => return Ok(()),
+					};
+					let major_syncing = network.is_major_syncing();
+					if major_syncing == txpool.is_major_syncing() {
+						return Ok(());
+					}
+					let best_block_id = match client.info() {
+						Ok(info) => BlockId::hash(info.chain.best_hash),
+						Err(e) => {
+							warn!("Error reading best block: {:?}", e);
+							return Ok(());
+						},
+					};
+					match txpool.set_major_syncing(&best_block_id, major_syncing) {
+						Ok(imported) => for result in imported {
+							if let Err(e) = result {
+								debug!("Error importing extrinsic queued while syncing: {:?}", e);
+							}
+						},
+						Err(e) => warn!("Error importing extrinsics queued while syncing: {:?}", e),
+					}
+					Ok(())
+				})
+				.select(exit.clone())
+				.then(|_| Ok(()));
+
+			task_executor.spawn(sync_state);
+		}
+
+		if let Some(ref budget) = config.memory_budget {
+			// keep the pool within its share of the memory budget
+			let account = budget.register("transaction_pool", POOL_MEMORY_BUDGET_WEIGHT);
+			let txpool = Arc::downgrade(&transaction_pool);
+			let report = ::tokio::timer::Interval::new_interval(Duration::from_millis(POOL_MEMORY_BUDGET_INTERVAL_MS))
+				.map_err(|e| warn!("Pool memory budget timer error: {:?}", e))
+				.for_each(move |_| {
+					let txpool = match txpool.upgrade() {
+						Some(txpool) => txpool,
+						None => return Ok(()),
+					};
+					if let Some(size) = account.requested_size() {
+						txpool.shrink_to(size);
+					}
+					// the pool is accounted with the size it has once shrunk
+					if let Some(size) = account.report(txpool.bytes()) {
+						txpool.shrink_to(size);
+						account.report(txpool.bytes());
+					}
+					Ok(())
+				})
+				.select(exit.clone())
+				.then(|_| Ok(()));
+
+			task_executor.spawn(report);
+		}
+
+		if config.persist_transaction_pool {
+			// save the pool periodically, so the extrinsics survive an unclean shutdown
+			let client = Arc::downgrade(&client);
+			let txpool = Arc::downgrade(&transaction_pool);
+			let persist = ::tokio::timer::Interval::new_interval(Duration::from_millis(POOL_PERSIST_INTERVAL_MS))
+				.map_err(|e| warn!("Pool persistence timer error: {:?}", e))
+				.for_each(move |_| {
+					if let (Some(client), Some(txpool)) = (client.upgrade(), txpool.upgrade()) {
+						if let Err(e) = transaction_pool::persistence::save(&*txpool, &**client.backend()) {
+							warn!("Error saving extrinsics: {:?}", e);
+						}
+					}
+					Ok(())
+				})
+				.select(exit.clone())
+				.then(|_| Ok(()));
+
+			task_executor.spawn(persist);
+		}
+
+		if config.skip_changes_tries_on_sync {
+			// build changes tries skipped during major sync once the node is synced
+			client.set_skip_changes_tries_on_sync(true);
+			let status_client = Arc::downgrade(&client);
+			let network = Arc::downgrade(&network);
+			let backfill_client = Arc::downgrade(&client);
+			maintenance::spawn_changes_tries_backfill(
+				Duration::from_millis(CHANGES_TRIES_BACKFILL_INTERVAL_MS),
+				move || match (status_client.upgrade(), network.upgrade()) {
+					(Some(_), Some(network)) => Some(network.is_major_syncing()),
+					_ => None,
+				},
+				move || match backfill_client.upgrade() {
+					Some(client) => client.backfill_changes_tries(CHANGES_TRIES_BACKFILL_BATCH),
+					None => Ok(0),
+				},
+			)?;
+		}
+
+		if let (Some(idle_period), false) = (config.idle_compaction, config.roles.contains(Roles::LIGHT)) {
+			let status_client = Arc::downgrade(&client);
+			let network = Arc::downgrade(&network);
+			let compact_client = Arc::downgrade(&client);
+			maintenance::spawn_idle_compaction(
+				idle_period,
+				move || match (status_client.upgrade(), network.upgrade()) {
+					(Some(client), Some(network)) => client.info().ok()
+						.map(|info| (info.chain.best_hash, network.is_major_syncing())),
+					_ => None,
+				},
+				move |progress| match compact_client.upgrade() {
+					Some(client) => client::backend::Backend::maintenance(&**client.backend(), Default::default(), progress),
+					None => Ok(false),
+				},
+			)?;
+		}
+
+		if !config.roles.contains(Roles::LIGHT) {
+			// run the offchain worker of the runtime at the new best blocks once the node is synced.
+			// The runtime code blocks on the HTTP responses, so it's executed by the dedicated thread,
+			// while the requests are performed on the executor.
+			let (sender, receiver) = ::std::sync::mpsc::sync_channel::<<ComponentBlock<Components> as BlockT>::Hash>(1);
+			let worker_client = Arc::downgrade(&client);
+			let http_config = config.offchain_http.clone();
+			let http_executor = task_executor.clone();
+			::std::thread::Builder::new()
+				.name("offchain-worker".into())
+				.spawn(move || for hash in receiver {
+					let client = match worker_client.upgrade() {
+						Some(client) => client,
+						None => break,
+					};
+					let (http, http_worker) = offchain::http(http_config.clone());
+					http_executor.spawn(http_worker);
+					if let Err(e) = client.offchain_worker_at(&BlockId::hash(hash), &mut offchain::Api::new(http)) {
+						warn!("Error running offchain worker at {:?}: {:?}", hash, e);
+					}
+				})?;
+
+			let network = Arc::downgrade(&network);
+			let best_client = Arc::downgrade(&client);
+			let events = client.import_notification_stream("offchain-worker")
+				.filter_map(move |notification| match notification {
+					Notification::Item(notification) => match notification.is_new_best {
+						true => Some(notification.hash),
+						false => None,
+					},
+					// notifications have been dropped => run at the current best block
+					Notification::Lagged(_) => best_client.upgrade()
+						.and_then(|client| client.info().ok())
+						.map(|info| info.chain.best_hash),
+				})
+				.for_each(move |hash| {
+					if network.upgrade().map_or(false, |network| !network.is_major_syncing()) {
+						// the block is skipped while the worker is busy with the previous one
+						let _ = sender.try_send(hash);
+					}
+					Ok(())
+				})
+				.select(exit.clone())
+				.then(|_| Ok(()));
+
+			task_executor.spawn(events);
+		}
+
+		let manual_seal = if config.manual_seal {
+			let (sender, receiver) = manual_seal::channel();
+			Some(ManualSealChannel {
+				sender,
+				receiver: Mutex::new(Some(receiver)),
+				engine_started: Arc::new(AtomicBool::new(false)),
+			})
+		} else {
+			None
+		};
+
+		// the state of the GRANDPA voter started by the node, served by the `grandpa_*` RPC
+		let grandpa_round_state = grandpa::SharedRoundState::default();
+
 		// RPC
 		let rpc_config = RpcConfig {
 			chain_name: config.chain_spec.name().to_string(),
+			properties: config.chain_spec.properties().cloned().unwrap_or_default(),
 			impl_name: config.impl_name,
 			impl_version: config.impl_version,
 		};
 
+		let rpc_unsafe = config.rpc_unsafe;
 		let (rpc_http, rpc_ws) = {
+			let rate_limiter = rpc::RateLimiter::new(config.rpc_rate_limits.clone());
+			let execution_pool = rpc::ExecutionPool::new(config.rpc_execution_pool.clone());
 			let handler = || {
 				let client = client.clone();
 				let subscriptions = rpc::apis::Subscriptions::new(task_executor.clone());
 				let chain = rpc::apis::chain::Chain::new(client.clone(), subscriptions.clone());
-				let state = rpc::apis::state::State::new(client.clone(), subscriptions.clone());
-				let author = rpc::apis::author::Author::new(client.clone(), transaction_pool.clone(), subscriptions.clone());
-				rpc::rpc_handler::<ComponentBlock<Components>, ComponentExHash<Components>, _, _, _, _, _>(
+				let state = rpc::apis::state::State::new(client.clone(), subscriptions.clone(), execution_pool.clone());
+				let state = match archive_fetcher {
+					Some(ref archive_fetcher) => state.with_archive_fallback(archive_fetcher.clone()),
+					None => state,
+				};
+				let author = rpc::apis::author::Author::new(
+					client.clone(),
+					transaction_pool.clone(),
+					subscriptions.clone(),
+					execution_pool.clone(),
+				);
+				let mut handler = rpc::rpc_handler::<ComponentBlock<Components>, ComponentExHash<Components>, _, _, _, _, _>(
 					state,
 					chain,
 					author,
-					rpc_config.clone(),
-				)
+					rpc::apis::system::DenyUnsafe::new(rpc_config.clone(), rpc_unsafe),
+					rate_limiter.clone(),
+				);
+				handler.extend_with(rpc::apis::system::SystemState::new(client.clone(), execution_pool.clone()).to_delegate());
+				handler.extend_with(rpc::apis::system::DenyUnsafe::new(
+					RpcNetwork::<Components> { network: Arc::downgrade(&network) },
+					rpc_unsafe,
+				).to_delegate());
+				if let Some(ref manual_seal) = manual_seal {
+					handler.extend_with(manual_seal::ManualSeal::new(
+						manual_seal.sender.clone(),
+						manual_seal.engine_started.clone(),
+					).to_delegate());
+				}
+				handler.extend_with(grandpa::Grandpa::new(grandpa_round_state.clone()).to_delegate());
+				handler
 			};
 			(
-				maybe_start_server(config.rpc_http, |address| rpc::start_http(address, handler()))?,
-				maybe_start_server(config.rpc_ws, |address| rpc::start_ws(address, handler()))?,
+				maybe_start_server(config.rpc_http, |address| rpc::start_http(address, handler(), &rate_limiter))?,
+				maybe_start_server(config.rpc_ws, |address| rpc::start_ws(address, handler(), &rate_limiter))?,
 			)
 		};
 
@@ -264,7 +638,8 @@ impl<Components> Service<Components>
 			client: client.clone(),
 			transaction_pool: transaction_pool.clone(),
 			offline: Arc::new(RwLock::new(OfflineTracker::new())),
-			force_delay: 0 // FIXME: allow this to be configured
+			force_delay: 0, // FIXME: allow this to be configured
+			proof_budget: config.proof_budget,
 		});
 
 		// Telemetry
@@ -297,10 +672,14 @@ impl<Components> Service<Components>
 		Ok(Service {
 			client: client,
 			network: Some(network),
+			on_demand,
 			transaction_pool: transaction_pool,
-			signal: Some(signal),
+			persist_transaction_pool: config.persist_transaction_pool,
+			signal: Mutex::new(Some(signal)),
 			keystore: keystore,
 			proposer,
+			manual_seal,
+			grandpa_round_state,
 			exit,
 			_rpc_http: rpc_http,
 			_rpc_ws: rpc_ws.map(Mutex::new),
@@ -324,11 +703,47 @@ impl<Components> Service<Components> where
 		self.proposer.clone()
 	}
 
+	/// Take the stream of manual seal engine commands, sent via the `engine_*` RPC, to run the
+	/// engine on. Returns `None` if manual sealing is disabled or the stream has already been taken.
+	/// The RPC fails immediately until the stream is taken.
+	pub fn manual_seal_commands(&self)
+		-> Option<manual_seal::CommandReceiver<<ComponentBlock<Components> as BlockT>::Hash>>
+	{
+		self.manual_seal.as_ref().and_then(|manual_seal| {
+			let receiver = manual_seal.receiver.lock().take();
+			if receiver.is_some() {
+				manual_seal.engine_started.store(true, Ordering::Release);
+			}
+			receiver
+		})
+	}
+
+	/// Get the sender of manual seal engine commands. Returns `None` if manual sealing is disabled.
+	pub fn manual_seal_sender(&self)
+		-> Option<manual_seal::CommandSender<<ComponentBlock<Components> as BlockT>::Hash>>
+	{
+		self.manual_seal.as_ref().map(|manual_seal| manual_seal.sender.clone())
+	}
+
+	/// Get the round state to be kept up to date by the GRANDPA voter, so that it is served by the
+	/// `grandpa_roundState` RPC. `None` is served until the voter is started.
+	pub fn grandpa_round_state(&self) -> grandpa::SharedRoundState<<ComponentBlock<Components> as BlockT>::Hash> {
+		self.grandpa_round_state.clone()
+	}
+
 	/// Get shared network instance.
 	pub fn network(&self) -> Arc<components::NetworkService<Components::Factory>> {
 		self.network.as_ref().expect("self.network always Some").clone()
 	}
 
+	/// Get responses of remote peers that have failed verification. Always empty for full nodes.
+	pub fn misbehavior_records(&self) -> Result<Vec<client::light::misbehavior::MisbehaviorRecord>, error::Error> {
+		match self.on_demand {
+			Some(ref on_demand) => Ok(on_demand.misbehavior_records()?),
+			None => Ok(Vec::new()),
+		}
+	}
+
 	/// Get shared extrinsic pool instance.
 	pub fn transaction_pool(&self) -> Arc<TransactionPool<Components::TransactionPoolApi>> {
 		self.transaction_pool.clone()
@@ -343,6 +758,14 @@ impl<Components> Service<Components> where
 	pub fn on_exit(&self) -> ::exit_future::Exit {
 		self.exit.clone()
 	}
+
+	/// Stop the background tasks of the service, resolving `on_exit`. The service is also stopped
+	/// when it's dropped.
+	pub fn stop(&self) {
+		if let Some(signal) = self.signal.lock().take() {
+			signal.fire();
+		}
+	}
 }
 
 
@@ -350,11 +773,16 @@ impl<Components> Drop for Service<Components> where Components: components::Comp
 	fn drop(&mut self) {
 		debug!(target: "service", "Substrate service shutdown");
 
+		if self.persist_transaction_pool {
+			match transaction_pool::persistence::save(&*self.transaction_pool, &**self.client.backend()) {
+				Ok(saved) => debug!(target: "service", "Saved {} extrinsics", saved),
+				Err(e) => warn!("Error saving extrinsics: {:?}", e),
+			}
+		}
+
 		drop(self.network.take());
 
-		if let Some(signal) = self.signal.take() {
-			signal.fire();
-		}
+		self.stop();
 	}
 }
 
@@ -379,6 +807,7 @@ fn maybe_start_server<T, F>(address: Option<SocketAddr>, start: F) -> Result<Opt
 #[derive(Clone)]
 struct RpcConfig {
 	chain_name: String,
+	properties: Properties,
 	impl_name: &'static str,
 	impl_version: &'static str,
 }
@@ -395,6 +824,80 @@ impl substrate_rpc::system::SystemApi for RpcConfig {
 	fn system_chain(&self) -> substrate_rpc::system::error::Result<String> {
 		Ok(self.chain_name.clone())
 	}
+
+	fn system_properties(&self) -> substrate_rpc::system::error::Result<substrate_rpc::system::Properties> {
+		Ok(self.properties.clone())
+	}
+
+	fn system_add_log_filter(&self, directives: String) -> substrate_rpc::system::error::Result<()> {
+		logger::add_directives(&directives)
+			.map_err(|e| substrate_rpc::system::error::ErrorKind::InvalidLogFilter(e.to_string()).into())
+	}
+
+	fn system_reset_log_filter(&self) -> substrate_rpc::system::error::Result<()> {
+		logger::reset_directives()
+			.map_err(|e| substrate_rpc::system::error::ErrorKind::InvalidLogFilter(e.to_string()).into())
+	}
+}
+
+/// Network management RPC handler.
+struct RpcNetwork<Components: components::Components> {
+	network: ::std::sync::Weak<components::NetworkService<Components::Factory>>,
+}
+
+impl<Components: components::Components> RpcNetwork<Components> {
+	fn network(&self) -> substrate_rpc::system::error::Result<Arc<components::NetworkService<Components::Factory>>> {
+		self.network.upgrade().ok_or_else(|| "Network service is stopped".into())
+	}
+}
+
+impl<Components> SystemNetworkApi<<ComponentBlock<Components> as BlockT>::Hash, NumberFor<ComponentBlock<Components>>> for RpcNetwork<Components> where
+	Components: components::Components,
+{
+	fn system_peers(&self) -> substrate_rpc::system::error::Result<Vec<
+		substrate_rpc::system::PeerInfo<<ComponentBlock<Components> as BlockT>::Hash, NumberFor<ComponentBlock<Components>>>
+	>> {
+		let network = self.network()?;
+		let light_serving = network.status().light_serving;
+		Ok(network.peers().into_iter()
+			.map(|(index, peer_id, info)| substrate_rpc::system::PeerInfo {
+				peer_id: peer_id.map(|peer_id| peer_id.to_base58()),
+				roles: format!("{:?}", info.roles),
+				protocol_version: info.protocol_version,
+				best_hash: info.best_hash,
+				best_number: info.best_number,
+				light_serving: light_serving.get(&index).map(|stats| substrate_rpc::system::LightServing {
+					proofs: stats.proofs,
+					bytes: stats.bytes,
+					cpu_ms: stats.cpu_time.as_secs() * 1000 + stats.cpu_time.subsec_millis() as u64,
+				}),
+			})
+			.collect())
+	}
+
+	fn system_health(&self) -> substrate_rpc::system::error::Result<substrate_rpc::system::Health> {
+		let status = self.network()?.status();
+		Ok(substrate_rpc::system::Health {
+			peers: status.num_peers,
+			is_syncing: status.sync.is_major_syncing(),
+			stalls: status.stalls,
+			stalled_secs: status.stalled_for.map(|duration| duration.as_secs()),
+		})
+	}
+
+	fn system_add_reserved_peer(&self, peer: String) -> substrate_rpc::system::error::Result<()> {
+		self.network()?.add_reserved_peer(peer)
+			.map_err(|e| substrate_rpc::system::error::ErrorKind::InvalidPeer(e).into())
+	}
+
+	fn system_remove_reserved_peer(&self, peer: String) -> substrate_rpc::system::error::Result<()> {
+		// a bare peer id is accepted as well as the full address
+		let address = if peer.starts_with('/') { peer } else { format!("/p2p/{}", peer) };
+		let (peer_id, _) = network::parse_str_addr(&address)
+			.map_err(|e| substrate_rpc::system::error::ErrorKind::InvalidPeer(format!("{:?}", e)))?;
+		self.network()?.remove_reserved_peer(peer_id);
+		Ok(())
+	}
 }
 
 /// Transaction pool adapter.
@@ -418,6 +921,7 @@ impl<C: Components> TransactionPoolAdapter<C> {
 impl<C: Components> network::TransactionPool<ComponentExHash<C>, ComponentBlock<C>> for TransactionPoolAdapter<C> {
 	fn transactions(&self) -> Vec<(ComponentExHash<C>, ComponentExtrinsic<C>)> {
 		self.pool.ready()
+			.filter(|t| t.propagate)
 			.map(|t| {
 				let hash = t.hash.clone();
 				let ex: ComponentExtrinsic<C> = t.data.clone();
@@ -530,6 +1034,8 @@ macro_rules! construct_simple_service {
 /// 		ImportQueue = BasicQueue<Block, NoneVerifier>
 /// 			{ |_, _| Ok(BasicQueue::new(Arc::new(NoneVerifier {}))) }
 /// 			{ |_, _| Ok(BasicQueue::new(Arc::new(NoneVerifier {}))) },
+///         // Declare the verifier of the justifications of the consensus engine.
+/// 		JustificationVerifier = { |_| Ok(Box::new(EngineJustificationVerifier::default())) },
 /// 	}
 /// }
 /// ```
@@ -551,6 +1057,7 @@ macro_rules! construct_service_factory {
 				{ $( $full_import_queue_init:tt )* },
 			LightImportQueue = $light_import_queue:ty
 				{ $( $light_import_queue_init:tt )* },
+			JustificationVerifier = { $( $justification_verifier_init:tt )* },
 		}
 	) => {
 		$( #[$attr] )*
@@ -606,6 +1113,12 @@ macro_rules! construct_service_factory {
 				( $( $light_import_queue_init )* ) (config, client)
 			}
 
+			fn build_justification_verifier(
+				config: &$crate::FactoryFullConfiguration<Self>,
+			) -> $crate::Result<Box<$crate::JustificationVerifier<Self::Block>>, $crate::Error> {
+				( $( $justification_verifier_init )* ) (config)
+			}
+
 			fn new_light(
 				config: $crate::FactoryFullConfiguration<Self>,
 				executor: $crate::TaskExecutor
@@ -624,3 +1137,39 @@ macro_rules! construct_service_factory {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_rpc::system::{DenyUnsafe, SystemApi};
+	use substrate_rpc::system::error::{Error as SystemError, ErrorKind as SystemErrorKind};
+
+	#[test]
+	fn log_filter_rpc_changes_the_filter_if_unsafe_methods_are_allowed() {
+		let rpc_config = RpcConfig {
+			chain_name: "Test".into(),
+			properties: Default::default(),
+			impl_name: "substrate-test",
+			impl_version: "0.1.0",
+		};
+		logger::init(&["info"], |_, _| Ok(())).unwrap();
+
+		let denied = DenyUnsafe::new(rpc_config.clone(), false);
+		match denied.system_add_log_filter("sync=debug".into()) {
+			Err(SystemError(SystemErrorKind::UnsafeRpcCalled, _)) => (),
+			result => panic!("Unexpected result: {:?}", result),
+		}
+		assert!(!log_enabled!(target: "sync", ::log::Level::Debug));
+
+		let allowed = DenyUnsafe::new(rpc_config, true);
+		allowed.system_add_log_filter("sync=debug".into()).unwrap();
+		assert!(log_enabled!(target: "sync", ::log::Level::Debug));
+		match allowed.system_add_log_filter("sync=loud".into()) {
+			Err(SystemError(SystemErrorKind::InvalidLogFilter(_), _)) => (),
+			result => panic!("Unexpected result: {:?}", result),
+		}
+
+		allowed.system_reset_log_filter().unwrap();
+		assert!(!log_enabled!(target: "sync", ::log::Level::Debug));
+	}
+}
@@ -0,0 +1,207 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Database maintenance in the background: compaction during the idle periods of the node and
+//! backfill of the changes tries skipped during major sync.
+
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+use client::backend::MaintenanceProgress;
+use client::error::Result as ClientResult;
+
+/// Interval (in seconds) at which the node is checked for being idle.
+const IDLE_CHECK_INTERVAL_SEC: u64 = 10;
+/// Maximal delay (in seconds) between the changes tries backfill attempts that keep failing.
+const MAX_BACKFILL_BACKOFF_SEC: u64 = 300;
+
+/// Tracks how long the node has been idle: the best block hasn't changed and the node isn't
+/// major syncing. The database is compacted once per idle period.
+pub struct IdleTracker<H> {
+	idle_period: Duration,
+	best: Option<H>,
+	idle_since: Instant,
+	compacted: bool,
+}
+
+impl<H: PartialEq> IdleTracker<H> {
+	/// Create the tracker, compacting the database after `idle_period` of inactivity.
+	pub fn new(idle_period: Duration, now: Instant) -> Self {
+		IdleTracker {
+			idle_period,
+			best: None,
+			idle_since: now,
+			compacted: false,
+		}
+	}
+
+	/// Note the state of the node. Returns `true` if the database should be compacted now.
+	pub fn on_check(&mut self, best: H, major_syncing: bool, now: Instant) -> bool {
+		if major_syncing || self.best.as_ref() != Some(&best) {
+			self.best = Some(best);
+			self.idle_since = now;
+			self.compacted = false;
+			return false;
+		}
+
+		!self.compacted && now.duration_since(self.idle_since) >= self.idle_period
+	}
+
+	/// Note the result of the compaction. The aborted compaction is retried once the node is idle
+	/// for another period.
+	pub fn on_compacted(&mut self, completed: bool, now: Instant) {
+		self.compacted = completed;
+		self.idle_since = now;
+	}
+}
+
+/// Progress of the idle-time compaction, aborted once the service is stopped.
+struct Progress<'a>(&'a Fn() -> bool);
+
+impl<'a> MaintenanceProgress for Progress<'a> {
+	fn on_progress(&self, column: u32, columns: u32, entries: u64) -> bool {
+		debug!(target: "db", "Compacting column {}/{}: {} entries", column + 1, columns, entries);
+		(self.0)()
+	}
+}
+
+/// Spawn the thread compacting the database during the idle periods of the node. `status` returns
+/// the best block hash and whether the node is major syncing, or `None` once the service is
+/// stopped, which stops the thread. `compact` runs the backend maintenance, which is aborted by
+/// the writes of the node.
+pub fn spawn_idle_compaction<H, S, C>(idle_period: Duration, status: S, compact: C) -> io::Result<()> where
+	H: PartialEq + Send + 'static,
+	S: Fn() -> Option<(H, bool)> + Send + 'static,
+	C: Fn(&MaintenanceProgress) -> ClientResult<bool> + Send + 'static,
+{
+	thread::Builder::new()
+		.name("db-compaction".into())
+		.spawn(move || {
+			let mut tracker = IdleTracker::new(idle_period, Instant::now());
+			loop {
+				thread::sleep(Duration::from_secs(IDLE_CHECK_INTERVAL_SEC));
+				let (best, major_syncing) = match status() {
+					Some(status) => status,
+					None => break,
+				};
+				if !tracker.on_check(best, major_syncing, Instant::now()) {
+					continue;
+				}
+
+				info!("Compacting the database while the node is idle");
+				let completed = match compact(&Progress(&|| status().is_some())) {
+					Ok(true) => {
+						info!("Database compacted");
+						true
+					},
+					Ok(false) => {
+						info!("Database compaction aborted");
+						false
+					},
+					Err(e) => {
+						warn!("Error compacting the database: {:?}", e);
+						false
+					},
+				};
+				tracker.on_compacted(completed, Instant::now());
+			}
+		})
+		.map(|_| ())
+}
+
+/// Delay before the next changes tries backfill attempt, doubled after every consecutive error.
+pub fn backfill_delay(interval: Duration, errors: u32) -> Duration {
+	let max_delay = Duration::from_secs(MAX_BACKFILL_BACKOFF_SEC);
+	interval.checked_mul(1u32.checked_shl(errors).unwrap_or(u32::max_value()))
+		.map_or(max_delay, |delay| ::std::cmp::min(delay, max_delay))
+}
+
+/// Spawn the thread building the changes tries skipped during major sync, since building them
+/// re-executes the blocks. `status` returns whether the node is major syncing, or `None` once the
+/// service is stopped, which stops the thread. `backfill` builds the tries of the next batch of
+/// blocks; it's only called while the node isn't major syncing and is retried with backoff on errors.
+pub fn spawn_changes_tries_backfill<S, B>(interval: Duration, status: S, backfill: B) -> io::Result<()> where
+	S: Fn() -> Option<bool> + Send + 'static,
+	B: Fn() -> ClientResult<u64> + Send + 'static,
+{
+	thread::Builder::new()
+		.name("changes-tries-backfill".into())
+		.spawn(move || {
+			let mut errors = 0;
+			loop {
+				thread::sleep(backfill_delay(interval, errors));
+				match status() {
+					Some(false) => (),
+					Some(true) => continue,
+					None => break,
+				}
+
+				match backfill() {
+					Ok(built) => {
+						if built != 0 {
+							debug!(target: "db", "Backfilled {} changes tries", built);
+						}
+						errors = 0;
+					},
+					Err(e) => {
+						warn!("Error backfilling changes tries: {:?}", e);
+						errors += 1;
+					},
+				}
+			}
+		})
+		.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn database_is_compacted_once_per_idle_period() {
+		let start = Instant::now();
+		let at = |secs| start + Duration::from_secs(secs);
+		let mut tracker = IdleTracker::new(Duration::from_secs(60), start);
+
+		// not idle while major syncing or importing blocks
+		assert!(!tracker.on_check(1, true, at(10)));
+		assert!(!tracker.on_check(1, false, at(60)));
+		assert!(!tracker.on_check(2, false, at(100)));
+		assert!(!tracker.on_check(2, false, at(150)));
+		assert!(tracker.on_check(2, false, at(160)));
+
+		// aborted compaction is retried after another idle period
+		tracker.on_compacted(false, at(170));
+		assert!(!tracker.on_check(2, false, at(200)));
+		assert!(tracker.on_check(2, false, at(230)));
+
+		// completed compaction isn't repeated until the node has been busy
+		tracker.on_compacted(true, at(240));
+		assert!(!tracker.on_check(2, false, at(1000)));
+		assert!(!tracker.on_check(3, false, at(1010)));
+		assert!(tracker.on_check(3, false, at(1070)));
+	}
+
+	#[test]
+	fn backfill_is_backed_off_on_errors() {
+		let interval = Duration::from_millis(500);
+		assert_eq!(backfill_delay(interval, 0), interval);
+		assert_eq!(backfill_delay(interval, 1), Duration::from_secs(1));
+		assert_eq!(backfill_delay(interval, 3), Duration::from_secs(4));
+		assert_eq!(backfill_delay(interval, 20), Duration::from_secs(MAX_BACKFILL_BACKOFF_SEC));
+		assert_eq!(backfill_delay(interval, 100), Duration::from_secs(MAX_BACKFILL_BACKOFF_SEC));
+	}
+}
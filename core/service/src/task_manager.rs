@@ -0,0 +1,187 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Hosting of several services in a single process.
+
+use std::any::Any;
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use exit_future::{Exit, Signal};
+use tokio::runtime::TaskExecutor;
+
+use components::{Components, ServiceFactory, FactoryFullConfiguration, FullComponents, LightComponents};
+use error;
+use Service;
+
+/// Runs independent services on a shared task executor, e.g. a full node of one chain next to
+/// a light client of another one. Every service has its own client, network and RPC servers,
+/// configured by its own chain spec, base path and ports.
+///
+/// Handles of the started services may be shared between each other. Services are stopped in
+/// reverse order when the manager is dropped: their background tasks exit, even if handles to
+/// them are still kept, and the rest of a service is released with its last handle.
+pub struct TaskManager {
+	executor: TaskExecutor,
+	services: Vec<Box<HostedService>>,
+	paths: HashSet<String>,
+	addresses: HashSet<String>,
+	telemetry: Option<String>,
+	signal: Option<Signal>,
+	exit: Exit,
+}
+
+/// Service started by the manager.
+trait HostedService: Send + Sync {
+	/// Name of the service, as configured.
+	fn name(&self) -> &str;
+	/// Type-erased handle to the service, for the typed lookups.
+	fn handle(&self) -> Arc<Any + Send + Sync>;
+	/// Stop the background tasks of the service.
+	fn stop(&self);
+}
+
+struct Hosted<C, S> {
+	name: String,
+	service: Arc<S>,
+	_components: PhantomData<fn() -> C>,
+}
+
+impl<C, S> HostedService for Hosted<C, S> where
+	C: Components,
+	S: Deref<Target = Service<C>> + Send + Sync + 'static,
+{
+	fn name(&self) -> &str {
+		&self.name
+	}
+
+	fn handle(&self) -> Arc<Any + Send + Sync> {
+		self.service.clone()
+	}
+
+	fn stop(&self) {
+		self.service.stop();
+	}
+}
+
+impl TaskManager {
+	/// Create a new manager spawning tasks of services on the given executor.
+	pub fn new(executor: TaskExecutor) -> Self {
+		let (signal, exit) = ::exit_future::signal();
+		TaskManager {
+			executor,
+			services: Vec::new(),
+			paths: HashSet::new(),
+			addresses: HashSet::new(),
+			telemetry: None,
+			signal: Some(signal),
+			exit,
+		}
+	}
+
+	/// Get the executor shared by the services.
+	pub fn executor(&self) -> TaskExecutor {
+		self.executor.clone()
+	}
+
+	/// Get a handle to a future that will resolve once the manager has stopped its services.
+	pub fn on_exit(&self) -> Exit {
+		self.exit.clone()
+	}
+
+	/// Start a full node of the chain configured by `config`.
+	pub fn start_full<F: ServiceFactory>(&mut self, config: FactoryFullConfiguration<F>) -> Result<Arc<F::FullService>, error::Error> {
+		self.start_service::<FullComponents<F>, _, _>(config, F::new_full)
+	}
+
+	/// Start a light client of the chain configured by `config`.
+	pub fn start_light<F: ServiceFactory>(&mut self, config: FactoryFullConfiguration<F>) -> Result<Arc<F::LightService>, error::Error> {
+		self.start_service::<LightComponents<F>, _, _>(config, F::new_light)
+	}
+
+	/// Start a service with the given configuration, using `build` to construct it. Fails if the
+	/// name, database, keystore or any of the addresses is already used by another service of the
+	/// manager.
+	///
+	/// Telemetry is global to the process, so it is only reported by the first service configured
+	/// with a telemetry url.
+	pub fn start_service<C, S, B>(&mut self, mut config: FactoryFullConfiguration<C::Factory>, build: B) -> Result<Arc<S>, error::Error> where
+		C: Components,
+		S: Deref<Target = Service<C>> + Send + Sync + 'static,
+		B: FnOnce(FactoryFullConfiguration<C::Factory>, TaskExecutor) -> Result<S, error::Error>,
+	{
+		if self.services.iter().any(|service| service.name() == config.name) {
+			return Err(format!("Name {} is already used by another service", config.name).into());
+		}
+		let paths = vec![config.database_path.clone(), config.keystore_path.clone()];
+		let addresses: Vec<String> = config.network.listen_addresses.iter().map(|a| a.to_string())
+			.chain(config.rpc_http.iter().chain(config.rpc_ws.iter()).map(|a| a.to_string()))
+			.collect();
+		if let Some(path) = paths.iter().find(|p| self.paths.contains(*p)) {
+			return Err(format!("Path {} is already used by another service", path).into());
+		}
+		if let Some(address) = addresses.iter().find(|a| self.addresses.contains(*a)) {
+			return Err(format!("Address {} is already used by another service", address).into());
+		}
+
+		match self.telemetry {
+			Some(ref name) if config.telemetry_url.is_some() => {
+				warn!("Telemetry is reported by service {}, disabling it for {}", name, config.name);
+				config.telemetry_url = None;
+			},
+			Some(_) => (),
+			None => if config.telemetry_url.is_some() {
+				self.telemetry = Some(config.name.clone());
+			},
+		}
+
+		info!("Starting service {} of chain {}", config.name, config.chain_spec.name());
+		let name = config.name.clone();
+		let service = Arc::new(build(config, self.executor.clone())?);
+		self.paths.extend(paths);
+		self.addresses.extend(addresses);
+		self.services.push(Box::new(Hosted {
+			name,
+			service: service.clone(),
+			_components: PhantomData,
+		}));
+		Ok(service)
+	}
+
+	/// Get a handle to the service started under the given name, e.g. to the full node of another
+	/// chain. Returns `None` if there's no such service or it has a different type.
+	pub fn service<S: Send + Sync + 'static>(&self, name: &str) -> Option<Arc<S>> {
+		self.services.iter()
+			.find(|service| service.name() == name)
+			.and_then(|service| service.handle().downcast::<S>().ok())
+	}
+}
+
+impl Drop for TaskManager {
+	fn drop(&mut self) {
+		// stop services in reverse order, so those started later may depend on earlier ones
+		while let Some(service) = self.services.pop() {
+			debug!(target: "service", "Stopping service {}", service.name());
+			service.stop();
+		}
+
+		if let Some(signal) = self.signal.take() {
+			signal.fire();
+		}
+	}
+}
@@ -28,12 +28,14 @@ extern crate substrate_network as network;
 extern crate substrate_primitives as primitives;
 extern crate substrate_client as client;
 extern crate substrate_consensus_common as consensus;
+extern crate substrate_consensus_manual_seal as manual_seal;
 extern crate sr_primitives;
 use std::iter;
 use std::sync::Arc;
 use std::net::Ipv4Addr;
 use std::time::Duration;
-use futures::Stream;
+use futures::{Future, Stream};
+use futures::sync::oneshot;
 use tempdir::TempDir;
 use tokio::runtime::Runtime;
 use tokio::timer::Interval;
@@ -46,6 +48,7 @@ use service::{
 	FactoryChainSpec,
 	Roles,
 	FactoryExtrinsic,
+	TaskManager,
 };
 use network::{NetworkConfiguration, NonReservedPeerMode, Protocol, SyncProvider, ManageNetwork};
 use sr_primitives::traits::As;
@@ -119,11 +122,16 @@ fn node_config<F: ServiceFactory> (
 		impl_commit: "",
 		roles: role,
 		transaction_pool: Default::default(),
+		persist_transaction_pool: false,
 		network: network_config,
 		keystore_path: root.join("key").to_str().unwrap().into(),
 		database_path: root.join("db").to_str().unwrap().into(),
+		database_read_only: false,
 		pruning: Default::default(),
+		archive_fallback: None,
 		keys: keys,
+		bad_blocks: Default::default(),
+		fork_blocks: Default::default(),
 		chain_spec: (*spec).clone(),
 		custom: Default::default(),
 		name: format!("Node {}", index),
@@ -131,7 +139,27 @@ fn node_config<F: ServiceFactory> (
 		api_execution_strategy: ExecutionStrategy::NativeWhenPossible,
 		rpc_http: None,
 		rpc_ws: None,
+		rpc_rate_limits: Default::default(),
+		rpc_execution_pool: Default::default(),
+		rpc_unsafe: false,
 		telemetry_url: None,
+		manual_seal: false,
+		instant_seal: false,
+		skip_changes_tries_on_sync: false,
+		idle_compaction: None,
+		fork_choice: Default::default(),
+		light_snapshot: None,
+		light_checkpoint: None,
+		light_head_providers: 0,
+		light_read_cache_size: 0,
+		light_headers_pruning: Default::default(),
+		light_fetcher: Default::default(),
+		light_storage_polling: false,
+		light_fallback_nodes: Vec::new(),
+		proof_budget: None,
+		watchdog: None,
+		memory_budget: None,
+		offchain_http: Default::default(),
 	}
 }
 
@@ -280,3 +308,102 @@ where
 		service.client().info().unwrap().chain.finalized_number >= As::sa(NUM_BLOCKS)
 	);
 }
+
+pub fn manual_seal<F>(spec: FactoryChainSpec<F>, authority: String)
+where
+	F: ServiceFactory,
+{
+	const NUM_BLOCKS: u64 = 10;
+	::env_logger::init().ok();
+	let temp = TempDir::new("substrate-manual-seal-test").expect("Error creating test dir");
+	{
+		let runtime = Runtime::new().expect("Error creating tokio runtime");
+		let mut config = node_config::<F>(0, &spec, Roles::AUTHORITY, Some(authority), 30700, &temp);
+		config.manual_seal = true;
+		let service = F::new_full(config, runtime.executor()).expect("Error creating test node service");
+		let sender = service.manual_seal_sender().expect("Manual seal is enabled");
+		info!("Checking manual seal");
+		for i in 0 .. NUM_BLOCKS {
+			let (tx, rx) = oneshot::channel();
+			sender.unbounded_send(manual_seal::EngineCommand::CreateBlock { parent: None, finalize: i % 2 == 1, sender: tx })
+				.expect("Manual seal engine is running");
+			rx.wait().expect("Manual seal engine is running").expect("Error creating block");
+		}
+		let info = service.client().info().unwrap().chain;
+		assert_eq!(info.best_number, As::sa(NUM_BLOCKS));
+		assert_eq!(info.finalized_number, As::sa(NUM_BLOCKS));
+	}
+	temp.close().expect("Error removing temp dir");
+}
+
+pub fn task_manager<F>(full_spec: FactoryChainSpec<F>, light_spec: FactoryChainSpec<F>)
+where
+	F: ServiceFactory,
+{
+	::env_logger::init().ok();
+	let temp = TempDir::new("substrate-task-manager-test").expect("Error creating test dir");
+	{
+		let runtime = Runtime::new().expect("Error creating tokio runtime");
+		let mut manager = TaskManager::new(runtime.executor());
+		info!("Checking services of different chains");
+		let full = manager.start_full::<F>(node_config::<F>(0, &full_spec, Roles::FULL, None, 30800, &temp))
+			.expect("Error creating full node service");
+		let light = manager.start_light::<F>(node_config::<F>(1, &light_spec, Roles::LIGHT, None, 30800, &temp))
+			.expect("Error creating light client service");
+		assert!(full.client().info().unwrap().chain.genesis_hash != light.client().info().unwrap().chain.genesis_hash);
+
+		let handle = manager.service::<F::LightService>("Node 1").expect("Light client is hosted");
+		assert!(Arc::ptr_eq(&handle, &light));
+		assert!(manager.service::<F::FullService>("Node 1").is_none());
+		assert!(manager.service::<F::FullService>("Node 2").is_none());
+
+		// the name, database and port of the full node are already taken
+		assert!(manager.start_full::<F>(node_config::<F>(0, &full_spec, Roles::FULL, None, 30900, &temp)).is_err());
+		let mut config = node_config::<F>(0, &full_spec, Roles::FULL, None, 30900, &temp);
+		config.name = "Node 2".into();
+		assert!(manager.start_full::<F>(config).is_err());
+		assert!(manager.start_full::<F>(node_config::<F>(2, &full_spec, Roles::FULL, None, 30798, &temp)).is_err());
+
+		// the kept handles are stopped with the manager
+		let manager_exit = manager.on_exit();
+		drop(manager);
+		manager_exit.wait().expect("Manager has stopped");
+		full.on_exit().wait().expect("Full node has stopped");
+		light.on_exit().wait().expect("Light client has stopped");
+	}
+	temp.close().expect("Error removing temp dir");
+}
+
+pub fn light_fallback<F>(spec: FactoryChainSpec<F>)
+where
+	F: ServiceFactory,
+{
+	::env_logger::init().ok();
+	let temp = TempDir::new("substrate-light-fallback-test").expect("Error creating test dir");
+	{
+		let mut runtime = Runtime::new().expect("Error creating tokio runtime");
+		let full = F::new_full(node_config::<F>(0, &spec, Roles::FULL, None, 31000, &temp), runtime.executor())
+			.expect("Error creating full node service");
+		let mut config = node_config::<F>(1, &spec, Roles::LIGHT, None, 31000, &temp);
+		config.light_fetcher.request_timeout = Duration::from_secs(1);
+		config.light_fallback_nodes = vec![full.network().node_id().expect("No node address")];
+		let light = Arc::new(F::new_light(config, runtime.executor()).expect("Error creating light client service"));
+
+		info!("Checking light client fallback");
+		let connecting = light.clone();
+		let interval = Interval::new_interval(Duration::from_millis(100)).map_err(|_| ()).for_each(move |_| {
+			match connecting.network().status().num_peers {
+				0 => Ok(()),
+				_ => Err(()),
+			}
+		});
+		runtime.block_on(interval).ok();
+
+		// the only connected full node is the fallback node, so the request fails over to it
+		let genesis = BlockId::number(As::sa(0));
+		let version = light.client().runtime_version_at(&genesis).expect("Request is served by the fallback node");
+		assert_eq!(version, full.client().runtime_version_at(&genesis).unwrap());
+		assert!(light.network().status().on_demand_peers.iter().any(|&(_, ref stats)| stats.valid_responses == 1));
+	}
+	temp.close().expect("Error removing temp dir");
+}
@@ -429,9 +429,17 @@ macro_rules! decl_apis {
 	};
 }
 
+/// Identity of a runtime API, used to query whether the runtime provides it.
+pub trait RuntimeApiInfo {
+	/// The ApiId of the API.
+	const ID: ApiId;
+	/// The latest version of the API known to this node.
+	const VERSION: u32;
+}
+
 /// The ApiIds for the various standard runtime APIs.
 pub mod id {
-	use super::ApiId;
+	use super::{ApiId, RuntimeApiInfo};
 	
 	/// ApiId for the BlockBuilder trait.
 	pub const BLOCK_BUILDER: ApiId = *b"blkbuild";
@@ -441,6 +449,53 @@ pub mod id {
 
 	/// ApiId for the Metadata trait.
 	pub const METADATA: ApiId = *b"metadata";
+
+	/// ApiId for the DisabledValidators trait.
+	pub const DISABLED_VALIDATORS: ApiId = *b"disabled";
+
+	/// ApiId for the OffchainWorker trait.
+	pub const OFFCHAIN_WORKER: ApiId = *b"offchwrk";
+
+	/// The BlockBuilder API.
+	pub struct BlockBuilderApi;
+
+	impl RuntimeApiInfo for BlockBuilderApi {
+		const ID: ApiId = BLOCK_BUILDER;
+		const VERSION: u32 = 1;
+	}
+
+	/// The TaggedTransactionQueue API. Version 1 of `validate_transaction` returns validity
+	/// without the `propagate` flag.
+	pub struct TaggedTransactionQueueApi;
+
+	impl RuntimeApiInfo for TaggedTransactionQueueApi {
+		const ID: ApiId = TAGGED_TRANSACTION_QUEUE;
+		const VERSION: u32 = 2;
+	}
+
+	/// The Metadata API.
+	pub struct MetadataApi;
+
+	impl RuntimeApiInfo for MetadataApi {
+		const ID: ApiId = METADATA;
+		const VERSION: u32 = 1;
+	}
+
+	/// The DisabledValidators API.
+	pub struct DisabledValidatorsApi;
+
+	impl RuntimeApiInfo for DisabledValidatorsApi {
+		const ID: ApiId = DISABLED_VALIDATORS;
+		const VERSION: u32 = 1;
+	}
+
+	/// The OffchainWorker API.
+	pub struct OffchainWorkerApi;
+
+	impl RuntimeApiInfo for OffchainWorkerApi {
+		const ID: ApiId = OFFCHAIN_WORKER;
+		const VERSION: u32 = 1;
+	}
 }
 
 decl_apis! {
@@ -456,6 +511,22 @@ decl_apis! {
 		fn metadata() -> Data;
 	}
 
+	/// The `DisabledValidators` api trait for querying the authorities disabled by the runtime, e.g.
+	/// for misbehavior. The API is optional, no authorities are disabled if the runtime doesn't
+	/// provide it.
+	pub trait DisabledValidators<Block: BlockT, AuthorityId> {
+		/// Authorities whose votes must not be counted by the consensus engines.
+		fn disabled_validators() -> Vec<AuthorityId>;
+	}
+
+	/// The `OffchainWorker` api trait for the code executed by the node after the import of every
+	/// new best block, with access to the offchain externalities. The API is optional, nothing is
+	/// executed if the runtime doesn't provide it.
+	pub trait OffchainWorker<Block: BlockT> {
+		/// Run the offchain worker at the block of given number.
+		fn offchain_worker<Number>(number: Number);
+	}
+
 	/// The `OldTxQueue` api trait for interfering with the old transaction queue.
 	pub trait OldTxQueue {
 		fn account_nonce<AccountId, Index>(account: AccountId) -> Index;
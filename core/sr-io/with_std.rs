@@ -29,12 +29,13 @@ pub extern crate parity_codec as codec;
 // re-export hashing functions.
 pub use primitives::{blake2_256, twox_128, twox_256, ed25519};
 
-pub use primitives::{Blake2Hasher};
+pub use primitives::{offchain, Blake2Hasher, ExecutionContext};
 // Switch to this after PoC-3
 // pub use primitives::BlakeHasher;
 pub use substrate_state_machine::{Externalities, TestExternalities};
 use primitives::hexdisplay::HexDisplay;
-use primitives::H256;
+use primitives::offchain::{Timestamp, HttpRequestId, HttpError, HttpRequestStatus};
+use primitives::{H256, AuthorityId};
 use hash_db::Hasher;
 
 // TODO: use the real error, not NoError.
@@ -142,6 +143,109 @@ pub fn chain_id() -> u64 {
 	).unwrap_or(0)
 }
 
+/// Randomness for the given subject, derived from the hashes of the recent blocks. `None` if
+/// not called in the context of a block. The block can't be executed by the nodes that lack any
+/// of these blocks.
+pub fn chain_randomness(subject: &[u8]) -> Option<H256> {
+	ext::with(|ext|
+		ext.chain_randomness(subject)
+	).unwrap_or(None)
+}
+
+/// Hash of the ancestor of the block being executed at the given depth (1 is the parent).
+/// `None` if the ancestor is too old or not called in the context of a block. The block can't be
+/// executed by the nodes that lack any of the recent ancestors.
+pub fn recent_block_hash(depth: u32) -> Option<H256> {
+	ext::with(|ext|
+		ext.recent_block_hash(depth)
+	).unwrap_or(None)
+}
+
+/// Context in which the runtime code is executed.
+pub fn execution_context() -> ExecutionContext {
+	ext::with(|ext|
+		ext.execution_context()
+	).unwrap_or_default()
+}
+
+/// Whether the node holds the key of the given authority. Always false unless the code is
+/// executed in the `Offchain` context.
+pub fn has_local_key(authority: &AuthorityId) -> bool {
+	ext::with(|ext|
+		ext.has_local_key(authority)
+	).unwrap_or(false)
+}
+
+/// Check whether the hash is the hash of one of the `max_depth` most recent ancestors of the
+/// block being executed.
+pub fn is_recent_ancestor(hash: &[u8], max_depth: u32) -> bool {
+	(1..=max_depth)
+		.map(recent_block_hash)
+		.take_while(Option::is_some)
+		.any(|ancestor| ancestor.map_or(false, |ancestor| AsRef::<[u8]>::as_ref(&ancestor) == hash))
+}
+
+/// Execute the closure with the offchain externalities.
+fn with_offchain<R, F: FnOnce(&mut offchain::Externalities) -> R>(f: F, msg: &'static str) -> R {
+	ext::with(|ext| ext.offchain().map(f).expect(msg))
+		.expect("offchain API cannot be called outside of an Externalities-provided environment.")
+}
+
+/// Current time. Only available in the offchain context.
+pub fn timestamp() -> Timestamp {
+	with_offchain(|offchain| offchain.timestamp(), "timestamp can be called only in the offchain context")
+}
+
+/// Start the HTTP request. Only available in the offchain context.
+pub fn http_request_start(method: &str, uri: &str, meta: &[u8]) -> Result<HttpRequestId, HttpError> {
+	with_offchain(
+		|offchain| offchain.http_request_start(method, uri, meta),
+		"http_request_start can be called only in the offchain context"
+	)
+}
+
+/// Add the header to the HTTP request that has not been sent yet.
+pub fn http_request_add_header(request_id: HttpRequestId, name: &str, value: &str) -> Result<(), HttpError> {
+	with_offchain(
+		|offchain| offchain.http_request_add_header(request_id, name, value),
+		"http_request_add_header can be called only in the offchain context"
+	)
+}
+
+/// Write the chunk of the HTTP request body. An empty chunk completes the body.
+pub fn http_request_write_body(request_id: HttpRequestId, chunk: &[u8], deadline: Option<Timestamp>) -> Result<(), HttpError> {
+	with_offchain(
+		|offchain| offchain.http_request_write_body(request_id, chunk, deadline),
+		"http_request_write_body can be called only in the offchain context"
+	)
+}
+
+/// Wait for the responses to the HTTP requests. Returns the status of every request, in the
+/// order of the `ids`.
+pub fn http_response_wait(ids: &[HttpRequestId], deadline: Option<Timestamp>) -> Vec<HttpRequestStatus> {
+	with_offchain(
+		|offchain| offchain.http_response_wait(ids, deadline),
+		"http_response_wait can be called only in the offchain context"
+	)
+}
+
+/// Headers of the received HTTP response. Empty if the response has not been received.
+pub fn http_response_headers(request_id: HttpRequestId) -> Vec<(Vec<u8>, Vec<u8>)> {
+	with_offchain(
+		|offchain| offchain.http_response_headers(request_id),
+		"http_response_headers can be called only in the offchain context"
+	)
+}
+
+/// Read the chunk of the HTTP response body into the `buffer`. Returns the number of bytes
+/// written, zero once the whole body has been read. The chunk may be shorter than the buffer.
+pub fn http_response_read_body(request_id: HttpRequestId, buffer: &mut [u8], deadline: Option<Timestamp>) -> Result<usize, HttpError> {
+	with_offchain(
+		|offchain| offchain.http_response_read_body(request_id, buffer, deadline),
+		"http_response_read_body can be called only in the offchain context"
+	)
+}
+
 /// "Commit" all existing operations and compute the resultant storage root.
 pub fn storage_root() -> H256 {
 	ext::with(|ext|
@@ -200,6 +304,12 @@ pub fn ed25519_verify<P: AsRef<[u8]>>(sig: &[u8; 64], msg: &[u8], pubkey: P) ->
 	ed25519::verify(sig, msg, pubkey)
 }
 
+/// Verify a ed25519 signature of the extrinsic with given hash, reusing the check done
+/// by the node ahead of execution if there was one.
+pub fn ed25519_verify_preverified<P: AsRef<[u8]>>(extrinsic_hash: &[u8; 32], sig: &[u8; 64], msg: &[u8], pubkey: P) -> bool {
+	ed25519::verify_preverified(extrinsic_hash, sig, msg, pubkey)
+}
+
 /// Execute the given closure with global function available whose functionality routes into the
 /// externalities `ext`. Forwards the value that the closure returns.
 // NOTE: need a concrete hasher here due to limitations of the `environmental!` macro, otherwise a type param would have been fine I think.
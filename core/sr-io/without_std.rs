@@ -27,7 +27,9 @@ pub extern crate parity_codec as codec;
 use core::intrinsics;
 use rstd::vec::Vec;
 use hash_db::Hasher;
-use primitives::Blake2Hasher;
+use primitives::{Blake2Hasher, AuthorityId};
+use primitives::offchain::{Timestamp, HttpRequestId, HttpError, HttpRequestStatus};
+pub use primitives::{offchain, ExecutionContext};
 pub use rstd::{mem, slice};
 
 #[panic_handler]
@@ -75,10 +77,22 @@ extern "C" {
 	fn ext_storage_changes_root(block: u64, result: *mut u8) -> u32;
 	fn ext_blake2_256_enumerated_trie_root(values_data: *const u8, lens_data: *const u32, lens_len: u32, result: *mut u8);
 	fn ext_chain_id() -> u64;
+	fn ext_chain_randomness(subject_data: *const u8, subject_len: u32, result: *mut u8) -> u32;
+	fn ext_recent_block_hash(depth: u32, result: *mut u8) -> u32;
+	fn ext_execution_context() -> u32;
+	fn ext_has_local_key(authority_data: *const u8) -> u32;
+	fn ext_offchain_timestamp() -> u64;
+	fn ext_http_request_start(method_data: *const u8, method_len: u32, uri_data: *const u8, uri_len: u32, meta_data: *const u8, meta_len: u32) -> u32;
+	fn ext_http_request_add_header(request_id: u32, name_data: *const u8, name_len: u32, value_data: *const u8, value_len: u32) -> u32;
+	fn ext_http_request_write_body(request_id: u32, chunk_data: *const u8, chunk_len: u32, deadline: u64) -> u32;
+	fn ext_http_response_wait(ids_data: *const u32, ids_len: u32, statuses_data: *mut u32, deadline: u64);
+	fn ext_http_response_headers(request_id: u32, written_out: *mut u32) -> *mut u8;
+	fn ext_http_response_read_body(request_id: u32, buffer_data: *mut u8, buffer_len: u32, deadline: u64, written_out: *mut u32) -> u32;
 	fn ext_blake2_256(data: *const u8, len: u32, out: *mut u8);
 	fn ext_twox_128(data: *const u8, len: u32, out: *mut u8);
 	fn ext_twox_256(data: *const u8, len: u32, out: *mut u8);
 	fn ext_ed25519_verify(msg_data: *const u8, msg_len: u32, sig_data: *const u8, pubkey_data: *const u8) -> u32;
+	fn ext_ed25519_verify_preverified(extrinsic_hash_data: *const u8, msg_data: *const u8, msg_len: u32, sig_data: *const u8, pubkey_data: *const u8) -> u32;
 }
 
 /// Ensures we use the right crypto when calling into native
@@ -317,6 +331,140 @@ pub fn chain_id() -> u64 {
 	}
 }
 
+/// Randomness for the given subject, derived from the hashes of the recent blocks. `None` if
+/// not called in the context of a block. The block can't be executed by the nodes that lack any
+/// of these blocks.
+pub fn chain_randomness(subject: &[u8]) -> Option<[u8; 32]> {
+	let mut result: [u8; 32] = Default::default();
+	let is_set = unsafe {
+		ext_chain_randomness(subject.as_ptr(), subject.len() as u32, result.as_mut_ptr())
+	};
+
+	if is_set != 0 {
+		Some(result)
+	} else {
+		None
+	}
+}
+
+/// Hash of the ancestor of the block being executed at the given depth (1 is the parent).
+/// `None` if the ancestor is too old or not called in the context of a block. The block can't be
+/// executed by the nodes that lack any of the recent ancestors.
+pub fn recent_block_hash(depth: u32) -> Option<[u8; 32]> {
+	let mut result: [u8; 32] = Default::default();
+	let is_set = unsafe {
+		ext_recent_block_hash(depth, result.as_mut_ptr())
+	};
+
+	if is_set != 0 {
+		Some(result)
+	} else {
+		None
+	}
+}
+
+/// Context in which the runtime code is executed.
+pub fn execution_context() -> ExecutionContext {
+	ExecutionContext::from_u32(unsafe { ext_execution_context() }).unwrap_or_default()
+}
+
+/// Whether the node holds the key of the given authority. Always false unless the code is
+/// executed in the `Offchain` context.
+pub fn has_local_key(authority: &AuthorityId) -> bool {
+	unsafe { ext_has_local_key(authority.0.as_ptr()) != 0 }
+}
+
+/// Check whether the hash is the hash of one of the `max_depth` most recent ancestors of the
+/// block being executed.
+pub fn is_recent_ancestor(hash: &[u8], max_depth: u32) -> bool {
+	(1..=max_depth)
+		.map(recent_block_hash)
+		.take_while(Option::is_some)
+		.any(|ancestor| ancestor.map_or(false, |ancestor| &ancestor[..] == hash))
+}
+
+/// Current time. Only available in the offchain context.
+pub fn timestamp() -> Timestamp {
+	Timestamp(unsafe { ext_offchain_timestamp() })
+}
+
+/// Start the HTTP request. Only available in the offchain context.
+pub fn http_request_start(method: &str, uri: &str, meta: &[u8]) -> Result<HttpRequestId, HttpError> {
+	let id = unsafe {
+		ext_http_request_start(
+			method.as_ptr(), method.len() as u32,
+			uri.as_ptr(), uri.len() as u32,
+			meta.as_ptr(), meta.len() as u32
+		)
+	};
+
+	if id > u16::max_value() as u32 {
+		Err(HttpError::Invalid)
+	} else {
+		Ok(HttpRequestId(id as u16))
+	}
+}
+
+/// Add the header to the HTTP request that has not been sent yet.
+pub fn http_request_add_header(request_id: HttpRequestId, name: &str, value: &str) -> Result<(), HttpError> {
+	HttpError::result_from_u32(unsafe {
+		ext_http_request_add_header(
+			request_id.0 as u32,
+			name.as_ptr(), name.len() as u32,
+			value.as_ptr(), value.len() as u32
+		)
+	})
+}
+
+/// Write the chunk of the HTTP request body. An empty chunk completes the body.
+pub fn http_request_write_body(request_id: HttpRequestId, chunk: &[u8], deadline: Option<Timestamp>) -> Result<(), HttpError> {
+	HttpError::result_from_u32(unsafe {
+		ext_http_request_write_body(
+			request_id.0 as u32,
+			chunk.as_ptr(), chunk.len() as u32,
+			Timestamp::deadline_as_u64(deadline)
+		)
+	})
+}
+
+/// Wait for the responses to the HTTP requests. Returns the status of every request, in the
+/// order of the `ids`.
+pub fn http_response_wait(ids: &[HttpRequestId], deadline: Option<Timestamp>) -> Vec<HttpRequestStatus> {
+	let ids = ids.iter().map(|id| id.0 as u32).collect::<Vec<_>>();
+	let mut statuses = Vec::new();
+	statuses.resize(ids.len(), 0u32);
+	unsafe {
+		ext_http_response_wait(ids.as_ptr(), ids.len() as u32, statuses.as_mut_ptr(), Timestamp::deadline_as_u64(deadline));
+	}
+	statuses.into_iter().map(HttpRequestStatus::from_u32).collect()
+}
+
+/// Headers of the received HTTP response. Empty if the response has not been received.
+pub fn http_response_headers(request_id: HttpRequestId) -> Vec<(Vec<u8>, Vec<u8>)> {
+	let mut length: u32 = 0;
+	unsafe {
+		let ptr = ext_http_response_headers(request_id.0 as u32, &mut length);
+		let encoded = slice::from_raw_parts(ptr, length as usize).to_vec();
+		ext_free(ptr);
+		codec::Decode::decode(&mut &encoded[..]).unwrap_or_default()
+	}
+}
+
+/// Read the chunk of the HTTP response body into the `buffer`. Returns the number of bytes
+/// written, zero once the whole body has been read. The chunk may be shorter than the buffer.
+pub fn http_response_read_body(request_id: HttpRequestId, buffer: &mut [u8], deadline: Option<Timestamp>) -> Result<usize, HttpError> {
+	let mut written: u32 = 0;
+	let result = unsafe {
+		ext_http_response_read_body(
+			request_id.0 as u32,
+			buffer.as_mut_ptr(), buffer.len() as u32,
+			Timestamp::deadline_as_u64(deadline),
+			&mut written
+		)
+	};
+	HttpError::result_from_u32(result).map(|_| written as usize)
+}
+
 /// Conduct a 256-bit Blake2 hash.
 pub fn blake2_256(data: &[u8]) -> [u8; 32] {
 	let mut result: [u8; 32] = Default::default();
@@ -351,6 +499,14 @@ pub fn ed25519_verify<P: AsRef<[u8]>>(sig: &[u8; 64], msg: &[u8], pubkey: P) ->
 	}
 }
 
+/// Verify a ed25519 signature of the extrinsic with given hash, reusing the check done
+/// by the node ahead of execution if there was one.
+pub fn ed25519_verify_preverified<P: AsRef<[u8]>>(extrinsic_hash: &[u8; 32], sig: &[u8; 64], msg: &[u8], pubkey: P) -> bool {
+	unsafe {
+		ext_ed25519_verify_preverified(extrinsic_hash.as_ptr(), msg.as_ptr(), msg.len() as u32, sig.as_ptr(), pubkey.as_ref().as_ptr()) == 0
+	}
+}
+
 /// Trait for things which can be printed.
 pub trait Printable {
 	fn print(self);
@@ -70,6 +70,9 @@ pub enum DigestItem<Hash, AuthorityId> {
 	Seal(u64, Signature),
 	/// Any 'non-system' digest item, opaque to the native code.
 	Other(Vec<u8>),
+	/// System digest item supplied by the block author before the runtime is executed (e.g.
+	/// slot number or randomness). It is made available to the runtime at block initialisation.
+	PreRuntime(Vec<u8>),
 }
 
 
@@ -87,6 +90,8 @@ pub enum DigestItemRef<'a, Hash: 'a, AuthorityId: 'a> {
 	/// Any 'non-system' digest item, opaque to the native code.
 	/// Reference to `DigestItem::Other`.
 	Other(&'a Vec<u8>),
+	/// Reference to `DigestItem::PreRuntime`.
+	PreRuntime(&'a Vec<u8>),
 }
 
 /// Type of the digest item. Used to gain explicit control over `DigestItem` encoding
@@ -100,6 +105,7 @@ enum DigestItemType {
 	AuthoritiesChange,
 	ChangesTrieRoot,
 	Seal,
+	PreRuntime,
 }
 
 impl<Hash, AuthorityId> DigestItem<Hash, AuthorityId> {
@@ -118,6 +124,7 @@ impl<Hash, AuthorityId> DigestItem<Hash, AuthorityId> {
 			DigestItem::ChangesTrieRoot(ref v) => DigestItemRef::ChangesTrieRoot(v),
 			DigestItem::Seal(ref v, ref s) => DigestItemRef::Seal(v, s),
 			DigestItem::Other(ref v) => DigestItemRef::Other(v),
+			DigestItem::PreRuntime(ref v) => DigestItemRef::PreRuntime(v),
 		}
 	}
 }
@@ -133,6 +140,10 @@ impl<Hash: Codec + Member, AuthorityId: Codec + Member> traits::DigestItem for D
 	fn as_changes_trie_root(&self) -> Option<&Hash> {
 		self.dref().as_changes_trie_root()
 	}
+
+	fn as_pre_runtime(&self) -> Option<&[u8]> {
+		self.dref().as_pre_runtime()
+	}
 }
 
 impl<Hash: Encode, AuthorityId: Encode> Encode for DigestItem<Hash, AuthorityId> {
@@ -158,6 +169,9 @@ impl<Hash: Decode, AuthorityId: Decode> Decode for DigestItem<Hash, AuthorityId>
 			DigestItemType::Other => Some(DigestItem::Other(
 				Decode::decode(input)?,
 			)),
+			DigestItemType::PreRuntime => Some(DigestItem::PreRuntime(
+				Decode::decode(input)?,
+			)),
 		}
 	}
 }
@@ -176,6 +190,13 @@ impl<'a, Hash: Codec + Member, AuthorityId: Codec + Member> DigestItemRef<'a, Ha
 			_ => None,
 		}
 	}
+
+	pub fn as_pre_runtime(&self) -> Option<&'a [u8]> {
+		match *self {
+			DigestItemRef::PreRuntime(ref data) => Some(data),
+			_ => None,
+		}
+	}
 }
 
 impl<'a, Hash: Encode, AuthorityId: Encode> Encode for DigestItemRef<'a, Hash, AuthorityId> {
@@ -199,6 +220,10 @@ impl<'a, Hash: Encode, AuthorityId: Encode> Encode for DigestItemRef<'a, Hash, A
 				DigestItemType::Other.encode_to(&mut v);
 				val.encode_to(&mut v);
 			},
+			DigestItemRef::PreRuntime(val) => {
+				DigestItemType::PreRuntime.encode_to(&mut v);
+				val.encode_to(&mut v);
+			},
 		}
 
 		v
@@ -20,6 +20,7 @@
 use std::fmt;
 
 use rstd::prelude::*;
+use runtime_io;
 use codec::{Decode, Encode, Input};
 use traits::{self, Member, SimpleArithmetic, MaybeDisplay, CurrentHeight, BlockNumberToHash, Lookup,
 	Checkable, Extrinsic};
@@ -67,10 +68,10 @@ impl<Address, Index, Call, Signature> Extrinsic for UncheckedMortalExtrinsic<Add
 impl<Address, AccountId, Index, Call, Signature, Context, Hash, BlockNumber> Checkable<Context>
 	for UncheckedMortalExtrinsic<Address, Index, Call, Signature>
 where
-	Address: Member + MaybeDisplay,
+	Address: Encode + Member + MaybeDisplay,
 	Index: Encode + Member + MaybeDisplay + SimpleArithmetic,
 	Call: Encode + Member,
-	Signature: Member + traits::Verify<Signer=AccountId>,
+	Signature: Encode + Member + traits::Verify<Signer=AccountId>,
 	AccountId: Member + MaybeDisplay,
 	BlockNumber: SimpleArithmetic,
 	Hash: Encode,
@@ -81,13 +82,17 @@ where
 	type Checked = CheckedExtrinsic<AccountId, Index, Call>;
 
 	fn check(self, context: &Context) -> Result<Self::Checked, &'static str> {
+		let extrinsic_hash = match self.signature {
+			Some(_) => runtime_io::blake2_256(&self.encode()),
+			None => Default::default(),
+		};
 		Ok(match self.signature {
 			Some((signed, signature, index, era)) => {
 				let h = context.block_number_to_hash(BlockNumber::sa(era.birth(context.current_height().as_())))
 					.ok_or("transaction birth block ancient")?;
 				let payload = (index, self.function, era, h);
 				let signed = context.lookup(signed)?;
-				if !::verify_encoded_lazy(&signature, &payload, &signed) {
+				if !::verify_preverified_encoded_lazy(&signature, &extrinsic_hash, &payload, &signed) {
 					return Err("bad signature in extrinsic")
 				}
 				CheckedExtrinsic {
@@ -200,6 +200,9 @@ impl Verify for Ed25519Signature {
 	fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &Self::Signer) -> bool {
 		runtime_io::ed25519_verify(&(self.0).0, msg.get(), &signer.0[..])
 	}
+	fn verify_preverified<L: Lazy<[u8]>>(&self, extrinsic_hash: &[u8; 32], mut msg: L, signer: &Self::Signer) -> bool {
+		runtime_io::ed25519_verify_preverified(extrinsic_hash, &(self.0).0, msg.get(), &signer.0[..])
+	}
 }
 
 impl From<H512> for Ed25519Signature {
@@ -249,30 +252,45 @@ impl codec::Encode for ApplyError {
 /// Result from attempt to apply an extrinsic.
 pub type ApplyResult = Result<ApplyOutcome, ApplyError>;
 
-/// Verify a signature on an encoded value in a lazy manner. This can be
-/// an optimization if the signature scheme has an "unsigned" escape hash.
-pub fn verify_encoded_lazy<V: Verify, T: codec::Encode>(sig: &V, item: &T, signer: &V::Signer) -> bool {
-	// The `Lazy<T>` trait expresses something like `X: FnMut<Output = for<'a> &'a T>`.
-	// unfortunately this is a lifetime relationship that can't
-	// be expressed without generic associated types, better unification of HRTBs in type position,
-	// and some kind of integration into the Fn* traits.
-	struct LazyEncode<F> {
-		inner: F,
-		encoded: Option<Vec<u8>>,
-	}
+// The `Lazy<T>` trait expresses something like `X: FnMut<Output = for<'a> &'a T>`.
+// unfortunately this is a lifetime relationship that can't
+// be expressed without generic associated types, better unification of HRTBs in type position,
+// and some kind of integration into the Fn* traits.
+struct LazyEncode<F> {
+	inner: F,
+	encoded: Option<Vec<u8>>,
+}
 
-	impl<F: Fn() -> Vec<u8>> traits::Lazy<[u8]> for LazyEncode<F> {
-		fn get(&mut self) -> &[u8] {
-			self.encoded.get_or_insert_with(&self.inner).as_slice()
-		}
+impl<F: Fn() -> Vec<u8>> traits::Lazy<[u8]> for LazyEncode<F> {
+	fn get(&mut self) -> &[u8] {
+		self.encoded.get_or_insert_with(&self.inner).as_slice()
 	}
+}
 
+/// Verify a signature on an encoded value in a lazy manner. This can be
+/// an optimization if the signature scheme has an "unsigned" escape hash.
+pub fn verify_encoded_lazy<V: Verify, T: codec::Encode>(sig: &V, item: &T, signer: &V::Signer) -> bool {
 	sig.verify(
 		LazyEncode { inner: || item.encode(), encoded: None },
 		signer,
 	)
 }
 
+/// Verify a signature on an encoded value of the extrinsic with given hash in a lazy manner,
+/// reusing the check the node may have done ahead of execution.
+pub fn verify_preverified_encoded_lazy<V: Verify, T: codec::Encode>(
+	sig: &V,
+	extrinsic_hash: &[u8; 32],
+	item: &T,
+	signer: &V::Signer,
+) -> bool {
+	sig.verify_preverified(
+		extrinsic_hash,
+		LazyEncode { inner: || item.encode(), encoded: None },
+		signer,
+	)
+}
+
 #[macro_export]
 macro_rules! __impl_outer_config_types {
 	(
@@ -400,6 +418,10 @@ macro_rules! impl_outer_log {
 			fn as_changes_trie_root(&self) -> Option<&Self::Hash> {
 				self.dref().and_then(|dref| dref.as_changes_trie_root())
 			}
+
+			fn as_pre_runtime(&self) -> Option<&[u8]> {
+				self.dref().and_then(|dref| dref.as_pre_runtime())
+			}
 		}
 
 		impl From<$crate::generic::DigestItem<$($genarg),*>> for $name {
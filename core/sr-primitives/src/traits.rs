@@ -45,6 +45,11 @@ pub trait Verify {
 	type Signer;
 	/// Verify a signature. Return `true` if signature is valid for the value.
 	fn verify<L: Lazy<[u8]>>(&self, msg: L, signer: &Self::Signer) -> bool;
+	/// Verify the signature of the extrinsic with given hash, which the node may have checked
+	/// ahead of execution. Return `true` if signature is valid for the value.
+	fn verify_preverified<L: Lazy<[u8]>>(&self, _extrinsic_hash: &[u8; 32], msg: L, signer: &Self::Signer) -> bool {
+		self.verify(msg, signer)
+	}
 }
 
 /// Some sort of check on the origin is performed by this object.
@@ -548,6 +553,9 @@ pub trait DigestItem: Codec + Member {
 
 	/// Returns Some if the entry is the `ChangesTrieRoot` entry.
 	fn as_changes_trie_root(&self) -> Option<&Self::Hash>;
+
+	/// Returns Some if the entry is the `PreRuntime` entry.
+	fn as_pre_runtime(&self) -> Option<&[u8]>;
 }
 
 /// Something that provides an inherent for a runtime.
@@ -37,7 +37,69 @@ pub enum TransactionValidity {
 		priority: TransactionPriority,
 		requires: Vec<TransactionTag>,
 		provides: Vec<TransactionTag>,
-		longevity: TransactionLongevity
+		longevity: TransactionLongevity,
+		/// Should the transaction be propagated to other peers. `false` means the transaction
+		/// is only included in blocks authored by the node that received it.
+		propagate: bool,
 	},
 	Unknown,
 }
+
+/// Transaction validity returned by runtimes declaring version 1 of the transaction queue API,
+/// which lacks the `propagate` flag.
+#[derive(Clone, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum TransactionValidityV1 {
+	Invalid,
+	Valid {
+		priority: TransactionPriority,
+		requires: Vec<TransactionTag>,
+		provides: Vec<TransactionTag>,
+		longevity: TransactionLongevity,
+	},
+	Unknown,
+}
+
+impl From<TransactionValidityV1> for TransactionValidity {
+	fn from(validity: TransactionValidityV1) -> Self {
+		match validity {
+			TransactionValidityV1::Invalid => TransactionValidity::Invalid,
+			TransactionValidityV1::Valid { priority, requires, provides, longevity } => TransactionValidity::Valid {
+				priority,
+				requires,
+				provides,
+				longevity,
+				propagate: true,
+			},
+			TransactionValidityV1::Unknown => TransactionValidity::Unknown,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::{Encode, Decode};
+
+	#[test]
+	fn v1_validity_is_propagated() {
+		let v1 = TransactionValidityV1::Valid {
+			priority: 1,
+			requires: vec![vec![1]],
+			provides: vec![vec![2]],
+			longevity: 64,
+		};
+
+		// validity returned by the runtime is decoded as V1 and converted
+		let decoded = TransactionValidityV1::decode(&mut &v1.encode()[..]).unwrap();
+		assert_eq!(TransactionValidity::from(decoded), TransactionValidity::Valid {
+			priority: 1,
+			requires: vec![vec![1]],
+			provides: vec![vec![2]],
+			longevity: 64,
+			propagate: true,
+		});
+		assert_eq!(TransactionValidity::from(TransactionValidityV1::Invalid), TransactionValidity::Invalid);
+		assert_eq!(TransactionValidity::from(TransactionValidityV1::Unknown), TransactionValidity::Unknown);
+	}
+}
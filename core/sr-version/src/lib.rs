@@ -131,6 +131,11 @@ impl RuntimeVersion {
 	pub fn has_api(&self, api: ApiId, version: u32) -> bool {
 		self.apis.iter().any(|&(ref s, v)| &api == s && version == v)
 	}
+
+	/// Returns the version of a particular API declared by this runtime, if any.
+	pub fn api_version(&self, api: ApiId) -> Option<u32> {
+		self.apis.iter().find(|&&(ref s, _)| &api == s).map(|&(_, v)| v)
+	}
 }
 
 #[cfg(feature = "std")]
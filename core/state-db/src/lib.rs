@@ -46,7 +46,7 @@ mod pruning;
 use std::fmt;
 use parking_lot::RwLock;
 use codec::Codec;
-use std::collections::HashSet;
+use std::collections::HashMap;
 use noncanonical::NonCanonicalOverlay;
 use pruning::RefWindow;
 
@@ -169,7 +169,7 @@ struct StateDbSync<BlockHash: Hash, Key: Hash> {
 	mode: PruningMode,
 	non_canonical: NonCanonicalOverlay<BlockHash, Key>,
 	pruning: Option<RefWindow<BlockHash, Key>>,
-	pinned: HashSet<BlockHash>,
+	pinned: HashMap<BlockHash, u32>,
 }
 
 impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
@@ -257,7 +257,7 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 				}
 
 				let pinned = &self.pinned;
-				if pruning.next_hash().map_or(false, |h| pinned.contains(&h)) {
+				if pruning.next_hash().map_or(false, |h| pinned.contains_key(&h)) {
 					break;
 				}
 				pruning.prune_one(commit);
@@ -280,11 +280,24 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 	}
 
 	pub fn pin(&mut self, hash: &BlockHash) {
-		self.pinned.insert(hash.clone());
+		*self.pinned.entry(hash.clone()).or_insert(0) += 1;
 	}
 
 	pub fn unpin(&mut self, hash: &BlockHash) {
-		self.pinned.remove(hash);
+		let remove = match self.pinned.get_mut(hash) {
+			Some(refs) => {
+				*refs -= 1;
+				*refs == 0
+			},
+			None => false,
+		};
+		if remove {
+			self.pinned.remove(hash);
+		}
+	}
+
+	pub fn is_pinned(&self, hash: &BlockHash) -> bool {
+		self.pinned.contains_key(hash)
 	}
 
 	pub fn get<D: HashDb<Hash=Key>>(&self, key: &Key, db: &D) -> Result<Option<DBValue>, Error<D::Error>> {
@@ -320,15 +333,21 @@ impl<BlockHash: Hash, Key: Hash> StateDb<BlockHash, Key> {
 	}
 
 	/// Prevents pruning of specified block and its descendants.
+	/// Pins are reference counted: each `pin` must be matched by an `unpin`.
 	pub fn pin(&self, hash: &BlockHash) {
 		self.db.write().pin(hash)
 	}
 
-	/// Allows pruning of specified block.
+	/// Releases one pin of the specified block. The block may be pruned once all pins are released.
 	pub fn unpin(&self, hash: &BlockHash) {
 		self.db.write().unpin(hash)
 	}
 
+	/// Check if block is currently pinned.
+	pub fn is_pinned(&self, hash: &BlockHash) -> bool {
+		self.db.read().is_pinned(hash)
+	}
+
 	/// Get a value from non-canonical/pruning overlay or the backing DB.
 	pub fn get<D: HashDb<Hash=Key>>(&self, key: &Key, db: &D) -> Result<Option<DBValue>, Error<D::Error>> {
 		self.db.read().get(key, db)
@@ -418,4 +437,30 @@ mod tests {
 		assert!(!sdb.is_pruned(1));
 		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
 	}
+
+	#[test]
+	fn pinned_block_is_not_pruned_until_all_pins_released() {
+		let mut db = make_db(&[91, 921]);
+		let state_db = StateDb::new(PruningMode::keep_blocks(0), &db).unwrap();
+		db.commit(&state_db.insert_block::<io::Error>(&H256::from(1), 1, &H256::from(0), make_changeset(&[1], &[91])).unwrap());
+		db.commit(&state_db.insert_block::<io::Error>(&H256::from(2), 2, &H256::from(1), make_changeset(&[2], &[921])).unwrap());
+
+		state_db.pin(&H256::from(1));
+		state_db.pin(&H256::from(1));
+		db.commit(&state_db.canonicalize_block(&H256::from(1)));
+		assert!(db.data_eq(&make_db(&[1, 91, 921])));
+
+		state_db.unpin(&H256::from(1));
+		assert!(state_db.is_pinned(&H256::from(1)));
+		db.commit(&state_db.canonicalize_block(&H256::from(2)));
+		assert!(!state_db.is_pruned(1));
+		assert!(db.data_eq(&make_db(&[1, 2, 91, 921])));
+
+		state_db.unpin(&H256::from(1));
+		assert!(!state_db.is_pinned(&H256::from(1)));
+		db.commit(&state_db.insert_block::<io::Error>(&H256::from(3), 3, &H256::from(2), make_changeset(&[3], &[])).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from(3)));
+		assert!(state_db.is_pruned(2));
+		assert!(db.data_eq(&make_db(&[1, 2, 3])));
+	}
 }
@@ -18,7 +18,7 @@
 
 use std::{error, fmt};
 use std::cmp::Ord;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::marker::PhantomData;
 use hash_db::Hasher;
 use trie_backend::TrieBackend;
@@ -63,6 +63,32 @@ pub trait Backend<H: Hasher> {
 	/// call `f` for each of those keys.
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F);
 
+	/// Get at most `count` entries with keys starting with the given prefix that follow the
+	/// `start_key` (all the entries with the prefix if it's `None`), in the order of the keys.
+	fn pairs_with_prefix_from(
+		&self,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: usize,
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::Error> {
+		let mut keys = Vec::new();
+		self.for_keys_with_prefix(prefix, |key| {
+			if start_key.map_or(true, |start_key| key > start_key) {
+				keys.push(key.to_vec());
+			}
+		});
+		keys.sort();
+		keys.truncate(count);
+
+		let mut pairs = Vec::with_capacity(keys.len());
+		for key in keys {
+			if let Some(value) = self.storage(&key)? {
+				pairs.push((key, value));
+			}
+		}
+		Ok(pairs)
+	}
+
 	/// Calculate the storage root, with given delta over what is already stored in
 	/// the backend, and produce a "transaction" that can be used to commit.
 	fn storage_root<I>(&self, delta: I) -> (H::Out, Self::Transaction)
@@ -81,6 +107,23 @@ pub trait Backend<H: Hasher> {
 	/// Get all key/value pairs into a Vec.
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)>;
 
+	/// Get all keys that have different values in this and the `other` state, along with the
+	/// values from both states, in the order of the keys.
+	fn storage_diff(&self, other: &Self) -> Result<Vec<(Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>)>, Self::Error> {
+		let mut values: BTreeMap<Vec<u8>, (Option<Vec<u8>>, Option<Vec<u8>>)> = BTreeMap::new();
+		for (key, value) in self.pairs() {
+			values.entry(key).or_default().0 = Some(value);
+		}
+		for (key, value) in other.pairs() {
+			values.entry(key).or_default().1 = Some(value);
+		}
+
+		Ok(values.into_iter()
+			.filter(|(_, (value, other_value))| value != other_value)
+			.map(|(key, (value, other_value))| (key, value, other_value))
+			.collect())
+	}
+
 	/// Try convert into trie backend.
 	fn try_into_trie_backend(self) -> Option<TrieBackend<Self::TrieBackendStorage, H>>;
 }
@@ -0,0 +1,178 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Repeated execution of a call against a frozen state, for runtime benchmarking.
+
+use std::time::{Duration, Instant};
+use hash_db::Hasher;
+use heapsize::HeapSizeOf;
+use backend::Backend;
+use changes_trie::InMemoryStorage as InMemoryChangesTrieStorage;
+use overlayed_changes::OverlayedChanges;
+use tracking_backend::{TrackingBackend, DbAccessCounts};
+use {execute, CodeExecutor, Error, ExecutionStrategy};
+
+/// Single benchmark run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchmarkRun {
+	/// Time spent executing the call.
+	pub duration: Duration,
+	/// Backend accesses made by the call.
+	pub db_access: DbAccessCounts,
+}
+
+/// Distribution of the execution times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimingStats {
+	/// Fastest run.
+	pub min: Duration,
+	/// Slowest run.
+	pub max: Duration,
+	/// Mean run time.
+	pub mean: Duration,
+	/// Median run time.
+	pub median: Duration,
+	/// 95th percentile of run times.
+	pub p95: Duration,
+}
+
+/// Result of the call benchmark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchmarkResult {
+	/// Output of the call. Since the state is frozen, it is the same for all runs.
+	pub output: Vec<u8>,
+	/// All runs, in execution order.
+	pub runs: Vec<BenchmarkRun>,
+}
+
+impl BenchmarkResult {
+	/// Distribution of the run times. `None` if there were no runs.
+	pub fn timing_stats(&self) -> Option<TimingStats> {
+		if self.runs.is_empty() {
+			return None;
+		}
+
+		let mut durations: Vec<_> = self.runs.iter().map(|run| run.duration).collect();
+		durations.sort();
+		let total = durations.iter().fold(Duration::default(), |total, duration| total + *duration);
+		let percentile = |p: usize| durations[(durations.len() - 1) * p / 100];
+		Some(TimingStats {
+			min: durations[0],
+			max: durations[durations.len() - 1],
+			mean: total / durations.len() as u32,
+			median: percentile(50),
+			p95: percentile(95),
+		})
+	}
+}
+
+/// Execute the call `repeat` times against the same state: the `backend` with the `overlay`
+/// applied on top of it. Writes made by every run are discarded before the next one.
+pub fn benchmark<H, B, Exec>(
+	backend: &B,
+	overlay: &OverlayedChanges,
+	exec: &Exec,
+	method: &str,
+	call_data: &[u8],
+	strategy: ExecutionStrategy,
+	repeat: u32,
+) -> Result<BenchmarkResult, Box<Error>>
+where
+	H: Hasher,
+	Exec: CodeExecutor<H>,
+	B: Backend<H>,
+	H::Out: Ord + HeapSizeOf,
+{
+	let mut output = Vec::new();
+	let mut runs = Vec::with_capacity(repeat as usize);
+	for _ in 0..repeat {
+		let tracking_backend = TrackingBackend::new(backend);
+		let mut overlay = overlay.clone();
+		let started = Instant::now();
+		let (result, _, _) = execute::<H, _, InMemoryChangesTrieStorage<H>, _>(
+			&tracking_backend,
+			None,
+			&mut overlay,
+			exec,
+			method,
+			call_data,
+			strategy,
+		)?;
+		runs.push(BenchmarkRun {
+			duration: started.elapsed(),
+			db_access: tracking_backend.counts(),
+		});
+		output = result;
+	}
+
+	Ok(BenchmarkResult { output, runs })
+}
+
+#[cfg(test)]
+mod tests {
+	use primitives::Blake2Hasher;
+	use primitives::storage::well_known_keys;
+	use backend::InMemory;
+	use Externalities;
+	use super::*;
+
+	struct IncrementingExecutor;
+
+	impl<H: Hasher> CodeExecutor<H> for IncrementingExecutor {
+		type Error = u8;
+
+		fn call<E: Externalities<H>>(
+			&self,
+			ext: &mut E,
+			_heap_pages: usize,
+			_code: &[u8],
+			_method: &str,
+			_data: &[u8],
+			_use_native: bool
+		) -> (Result<Vec<u8>, Self::Error>, bool) {
+			let value = ext.storage(b"counter").unwrap_or_default();
+			let mut incremented = value.clone();
+			incremented.push(1);
+			ext.place_storage(b"counter".to_vec(), Some(incremented));
+			(Ok(value), true)
+		}
+	}
+
+	#[test]
+	fn benchmark_runs_against_frozen_state() {
+		let backend: InMemory<Blake2Hasher> = vec![
+			(well_known_keys::CODE.to_vec(), b"code".to_vec()),
+			(b"counter".to_vec(), vec![7]),
+		].into_iter().collect::<::std::collections::HashMap<_, _>>().into();
+
+		let result = benchmark(
+			&backend,
+			&Default::default(),
+			&IncrementingExecutor,
+			"test",
+			&[],
+			ExecutionStrategy::NativeWhenPossible,
+			5,
+		).unwrap();
+
+		assert_eq!(result.output, vec![7]);
+		assert_eq!(result.runs.len(), 5);
+		assert!(result.runs.iter().all(|run| run.db_access == result.runs[0].db_access));
+		assert!(result.runs[0].db_access.storage_reads > 0);
+		let stats = result.timing_stats().unwrap();
+		assert!(stats.min <= stats.median && stats.median <= stats.p95 && stats.p95 <= stats.max);
+	}
+}
@@ -124,14 +124,14 @@ fn prepare_digest_input<'a, S, H>(
 			if let Some(InputKey::ExtrinsicIndex(trie_key)) = Decode::decode(&mut &key[..]) {
 				digest_map.entry(trie_key.key).or_default()
 					.insert(digest_build_block);
-			});
+			})?;
 
 		let digest_prefix = DigestIndex::key_neutral_prefix(digest_build_block);
 		trie_storage.for_keys_with_prefix(&digest_prefix, |key|
 			if let Some(InputKey::DigestIndex(trie_key)) = Decode::decode(&mut &key[..]) {
 				digest_map.entry(trie_key.key).or_default()
 					.insert(digest_build_block);
-			});
+			})?;
 	}
 
 	Ok(digest_map.into_iter()
@@ -220,6 +220,9 @@ mod test {
 				}),
 			].into_iter().collect(),
 			changes_trie_config: Some(Configuration { digest_interval: 4, digest_levels: 2 }),
+			trusted_changes_trie_root: None,
+			chain_entropy: None,
+			recent_block_hashes: Vec::new(),
 		};
 
 		(backend, storage, changes)
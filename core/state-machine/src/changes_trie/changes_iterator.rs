@@ -24,7 +24,7 @@ use hash_db::{HashDB, Hasher};
 use heapsize::HeapSizeOf;
 use substrate_trie::{Recorder, MemoryDB};
 use changes_trie::{Configuration, RootsStorage, Storage};
-use changes_trie::input::{DigestIndex, ExtrinsicIndex, DigestIndexValue, ExtrinsicIndexValue};
+use changes_trie::input::{DigestIndex, ExtrinsicIndex, DigestIndexValue, ExtrinsicIndexValue, InputKey};
 use changes_trie::storage::{TrieBackendAdapter, InMemoryStorage};
 use proving_backend::ProvingBackendEssence;
 use trie_backend_essence::{TrieBackendEssence};
@@ -125,6 +125,26 @@ pub fn key_changes_proof_check<S: RootsStorage<H>, H: Hasher>(
 	}.collect()
 }
 
+/// Return all keys that have been changed at given block, according to the changes trie of
+/// this block. Returns None if there's no changes trie for the block.
+pub fn block_changed_keys<S: Storage<H>, H: Hasher>(
+	storage: &S,
+	block: u64,
+) -> Result<Option<Vec<Vec<u8>>>, String> where H::Out: HeapSizeOf {
+	let root = match storage.root(block)? {
+		Some(root) => root,
+		None => return Ok(None),
+	};
+
+	let mut keys = Vec::new();
+	TrieBackendEssence::<_, H>::new(TrieBackendAdapter::new(storage), root)
+		.for_keys_with_prefix(&ExtrinsicIndex::key_neutral_prefix(block), |key|
+			if let Some(InputKey::ExtrinsicIndex(trie_key)) = Decode::decode(&mut &key[..]) {
+				keys.push(trie_key.key);
+			})?;
+	Ok(Some(keys))
+}
+
 /// Surface iterator - only traverses top-level digests from given range and tries to find
 /// all digest changes for the key.
 pub struct SurfaceIterator<'a> {
@@ -423,6 +443,16 @@ mod tests {
 		(config, backend)
 	}
 
+	#[test]
+	fn block_changed_keys_works() {
+		let (_, storage) = prepare_for_drilldown();
+		let changed_keys = |block| block_changed_keys::<InMemoryStorage<Blake2Hasher>, Blake2Hasher>(&storage, block);
+		assert_eq!(changed_keys(8), Ok(Some(vec![vec![42]])));
+		// digest entries are not reported
+		assert_eq!(changed_keys(4), Ok(Some(vec![])));
+		assert_eq!(changed_keys(100), Ok(None));
+	}
+
 	#[test]
 	fn drilldown_iterator_works() {
 		let (config, storage) = prepare_for_drilldown();
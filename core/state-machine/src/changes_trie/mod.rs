@@ -43,7 +43,7 @@ mod prune;
 mod storage;
 
 pub use self::storage::InMemoryStorage;
-pub use self::changes_iterator::{key_changes, key_changes_proof, key_changes_proof_check};
+pub use self::changes_iterator::{key_changes, key_changes_proof, key_changes_proof_check, block_changed_keys};
 pub use self::prune::prune;
 
 use hash_db::Hasher;
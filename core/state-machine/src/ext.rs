@@ -19,8 +19,9 @@
 use std::{error, fmt, cmp::Ord};
 use backend::{Backend, Consolidate};
 use changes_trie::{Storage as ChangesTrieStorage, compute_changes_trie_root};
-use {Externalities, OverlayedChanges};
+use {Externalities, ExecutionContext, OverlayedChanges};
 use hash_db::Hasher;
+use primitives::{offchain, AuthorityId, LocalKeys};
 use primitives::storage::well_known_keys::is_child_storage_key;
 use substrate_trie::{MemoryDB, TrieDBMut, TrieMut, default_child_trie_root, is_child_trie_key_valid};
 use heapsize::HeapSizeOf;
@@ -80,6 +81,12 @@ where
 	/// `storage_changes_root` is called matters + we need to remember additional
 	/// data at this moment (block number).
 	changes_trie_transaction: Option<(u64, MemoryDB<H>, H::Out)>,
+	/// The context in which the code is executed.
+	context: ExecutionContext,
+	/// Offchain externalities, only available to the offchain calls.
+	offchain: Option<&'a mut offchain::Externalities>,
+	/// Keys held by the node, only available to the block construction and offchain calls.
+	local_keys: Option<&'a LocalKeys>,
 }
 
 impl<'a, H, B, T> Ext<'a, H, B, T>
@@ -97,9 +104,31 @@ where
 			storage_transaction: None,
 			changes_trie_storage,
 			changes_trie_transaction: None,
+			context: ExecutionContext::Other,
+			offchain: None,
+			local_keys: None,
 		}
 	}
 
+	/// Set the context in which the code is executed.
+	pub fn with_context(mut self, context: ExecutionContext) -> Self {
+		self.context = context;
+		self
+	}
+
+	/// Provide the offchain externalities to the executed code.
+	pub fn with_offchain(mut self, offchain: &'a mut offchain::Externalities) -> Self {
+		self.offchain = Some(offchain);
+		self
+	}
+
+	/// Provide the keys held by the node to the executed code. The keys are ignored unless the
+	/// code is executed in the `Offchain` context.
+	pub fn with_local_keys(mut self, local_keys: &'a LocalKeys) -> Self {
+		self.local_keys = Some(local_keys);
+		self
+	}
+
 	/// Get the transaction necessary to update the backend.
 	pub fn transaction(mut self) -> (B::Transaction, Option<MemoryDB<H>>) {
 		let _ = self.storage_root();
@@ -253,6 +282,30 @@ where
 		42
 	}
 
+	fn chain_randomness(&self, subject: &[u8]) -> Option<H::Out> {
+		self.overlay.chain_randomness::<H>(subject)
+	}
+
+	fn recent_block_hash(&self, depth: u32) -> Option<H::Out> {
+		self.overlay.recent_block_hash::<H>(depth)
+	}
+
+	fn execution_context(&self) -> ExecutionContext {
+		self.context
+	}
+
+	fn offchain(&mut self) -> Option<&mut offchain::Externalities> {
+		self.offchain.as_mut().map(|offchain| &mut **offchain as _)
+	}
+
+	fn has_local_key(&self, authority: &AuthorityId) -> bool {
+		match self.context {
+			ExecutionContext::Offchain =>
+				self.local_keys.map_or(false, |local_keys| local_keys.has_key(authority)),
+			_ => false,
+		}
+	}
+
 	fn storage_root(&mut self) -> H::Out {
 		if let Some((_, ref root)) = self.storage_transaction {
 			return root.clone();
@@ -289,6 +342,15 @@ where
 	}
 
 	fn storage_changes_root(&mut self, block: u64) -> Option<H::Out> {
+		if let Some(ref trusted_root) = self.overlay.trusted_changes_trie_root {
+			let mut root = H::Out::default();
+			if root.as_ref().len() == trusted_root.len() {
+				root.as_mut().copy_from_slice(trusted_root);
+				self.changes_trie_transaction = None;
+				return Some(root);
+			}
+		}
+
 		let root_and_tx = compute_changes_trie_root::<_, T, H>(
 			self.backend,
 			self.changes_trie_storage.clone(),
@@ -315,6 +377,7 @@ where
 
 #[cfg(test)]
 mod tests {
+	use std::collections::HashSet;
 	use codec::Encode;
 	use primitives::{Blake2Hasher};
 	use primitives::storage::well_known_keys::EXTRINSIC_INDEX;
@@ -345,6 +408,9 @@ mod tests {
 				digest_interval: 0,
 				digest_levels: 0,
 			}),
+			trusted_changes_trie_root: None,
+			chain_entropy: None,
+			recent_block_hashes: Vec::new(),
 		}
 	}
 
@@ -386,4 +452,81 @@ mod tests {
 		assert_eq!(ext.storage_changes_root(100),
 			Some(hex!("bcf494e41e29a15c9ae5caa053fe3cb8b446ee3e02a254efbdec7a19235b76e4").into()));
 	}
+
+	#[test]
+	fn storage_changes_root_is_trusted_when_provided() {
+		let mut overlay = prepare_overlay_with_changes();
+		overlay.trust_changes_trie_root(vec![42; 32]);
+		let storage = TestChangesTrieStorage::new();
+		let backend = TestBackend::default();
+		let mut ext = TestExt::new(&mut overlay, &backend, Some(&storage));
+		assert_eq!(ext.storage_changes_root(100), Some([42; 32].into()));
+		assert!(ext.changes_trie_transaction.is_none());
+	}
+
+	#[test]
+	fn chain_randomness_is_derived_from_entropy_and_subject() {
+		let mut overlay = OverlayedChanges::default();
+		let backend = TestBackend::default();
+		assert_eq!(TestExt::new(&mut overlay, &backend, None).chain_randomness(b"subject"), None);
+
+		overlay.set_chain_entropy(vec![42; 32]);
+		let ext = TestExt::new(&mut overlay, &backend, None);
+		let randomness = ext.chain_randomness(b"subject").unwrap();
+		assert_eq!(randomness, Blake2Hasher::hash(&[&[42u8; 32][..], b"subject"].concat()));
+		assert!(ext.chain_randomness(b"another subject").unwrap() != randomness);
+	}
+
+	#[test]
+	fn recent_block_hashes_are_provided_by_depth() {
+		let mut overlay = OverlayedChanges::default();
+		let backend = TestBackend::default();
+		assert_eq!(TestExt::new(&mut overlay, &backend, None).recent_block_hash(1), None);
+
+		overlay.set_recent_block_hashes(vec![vec![2; 32], vec![1; 32]]);
+		let ext = TestExt::new(&mut overlay, &backend, None);
+		assert_eq!(ext.recent_block_hash(0), None);
+		assert_eq!(ext.recent_block_hash(1), Some([2; 32].into()));
+		assert_eq!(ext.recent_block_hash(2), Some([1; 32].into()));
+		assert_eq!(ext.recent_block_hash(3), None);
+	}
+
+	#[test]
+	fn execution_context_is_provided() {
+		let mut overlay = OverlayedChanges::default();
+		let backend = TestBackend::default();
+		assert_eq!(TestExt::new(&mut overlay, &backend, None).execution_context(), ExecutionContext::Other);
+
+		let ext = TestExt::new(&mut overlay, &backend, None).with_context(ExecutionContext::Syncing);
+		assert_eq!(ext.execution_context(), ExecutionContext::Syncing);
+	}
+
+	#[test]
+	fn local_keys_are_only_available_to_offchain_context() {
+		let mut overlay = OverlayedChanges::default();
+		let backend = TestBackend::default();
+		let local_keys: HashSet<AuthorityId> = vec![AuthorityId([1; 32])].into_iter().collect();
+
+		let ext = TestExt::new(&mut overlay, &backend, None).with_context(ExecutionContext::Offchain);
+		assert!(!ext.has_local_key(&AuthorityId([1; 32])));
+
+		let ext = TestExt::new(&mut overlay, &backend, None)
+			.with_context(ExecutionContext::Offchain)
+			.with_local_keys(&local_keys);
+		assert!(ext.has_local_key(&AuthorityId([1; 32])));
+		assert!(!ext.has_local_key(&AuthorityId([2; 32])));
+
+		let contexts = [
+			ExecutionContext::Importing,
+			ExecutionContext::Syncing,
+			ExecutionContext::BlockConstruction,
+			ExecutionContext::Other,
+		];
+		for context in &contexts {
+			let ext = TestExt::new(&mut overlay, &backend, None)
+				.with_context(*context)
+				.with_local_keys(&local_keys);
+			assert!(!ext.has_local_key(&AuthorityId([1; 32])));
+		}
+	}
 }
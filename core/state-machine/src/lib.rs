@@ -40,9 +40,11 @@ use std::fmt;
 use hash_db::Hasher;
 use heapsize::HeapSizeOf;
 use codec::Decode;
+use primitives::{offchain, AuthorityId, LocalKeys};
 use primitives::storage::well_known_keys;
 
 pub mod backend;
+mod benchmark;
 mod changes_trie;
 mod ext;
 mod testing;
@@ -50,19 +52,23 @@ mod overlayed_changes;
 mod proving_backend;
 mod trie_backend;
 mod trie_backend_essence;
+mod tracking_backend;
 
 pub use trie::{TrieMut, TrieDBMut, DBValue, MemoryDB};
 pub use testing::TestExternalities;
 pub use ext::Ext;
 pub use backend::Backend;
+pub use primitives::ExecutionContext;
 pub use changes_trie::{Storage as ChangesTrieStorage,
 	RootsStorage as ChangesTrieRootsStorage,
 	InMemoryStorage as InMemoryChangesTrieStorage,
-	key_changes, key_changes_proof, key_changes_proof_check,
+	key_changes, key_changes_proof, key_changes_proof_check, block_changed_keys,
 	prune as prune_changes_tries};
-pub use overlayed_changes::OverlayedChanges;
+pub use overlayed_changes::{OverlayedChanges, StorageDiff};
 pub use trie_backend_essence::Storage;
 pub use trie_backend::TrieBackend;
+pub use benchmark::{benchmark, BenchmarkResult, BenchmarkRun, TimingStats};
+pub use tracking_backend::{TrackingBackend, DbAccessCounts};
 
 /// Default num of pages for the heap
 const DEFAULT_HEAP_PAGES :u64 = 1024;
@@ -158,6 +164,37 @@ pub trait Externalities<H: Hasher> {
 
 	/// Get the change trie root of the current storage overlay at given block.
 	fn storage_changes_root(&mut self, block: u64) -> Option<H::Out> where H::Out: Ord;
+
+	/// Get the randomness for the given subject, derived from the hashes of the recent blocks.
+	///
+	/// Returns None if the call isn't made in the context of a block.
+	fn chain_randomness(&self, subject: &[u8]) -> Option<H::Out>;
+
+	/// Get the hash of the ancestor of the block being executed at the given depth (1 is the
+	/// parent). Only the hashes of the recent ancestors are available.
+	///
+	/// Returns None if the ancestor is too old or the call isn't made in the context of a block.
+	fn recent_block_hash(&self, depth: u32) -> Option<H::Out>;
+
+	/// Get the context in which the code is executed.
+	fn execution_context(&self) -> ExecutionContext {
+		ExecutionContext::Other
+	}
+
+	/// Get the offchain externalities.
+	///
+	/// Returns None if the call isn't made in the offchain context.
+	fn offchain(&mut self) -> Option<&mut offchain::Externalities> {
+		None
+	}
+
+	/// Whether the node holds the key of the given authority.
+	///
+	/// Returns false unless the call is made in the `Offchain` context with the local keys
+	/// provided, so that the answer never affects the blocks, whether built or imported.
+	fn has_local_key(&self, _authority: &AuthorityId) -> bool {
+		false
+	}
 }
 
 /// Code execution engine.
@@ -195,7 +232,8 @@ pub enum ExecutionManager<F> {
 	NativeWhenPossible,
 	/// Use the given wasm module.
 	AlwaysWasm,
-	/// Run with both the wasm and the native variant (if compatible). Call `F` in the case of any discrepency.
+	/// Run with both the wasm and the native variant (if compatible). Call `F` in the case of any discrepency
+	/// in either the results or the storage changes of the two runs.
 	Both(F),
 }
 
@@ -210,12 +248,12 @@ impl<'a, F> From<&'a ExecutionManager<F>> for ExecutionStrategy {
 }
 
 /// Evaluate to ExecutionManager::NativeWhenPossible, without having to figure out the type.
-pub fn native_when_possible<E>() -> ExecutionManager<fn(Result<Vec<u8>, E>, Result<Vec<u8>, E>)->Result<Vec<u8>, E>> {
+pub fn native_when_possible<E>() -> ExecutionManager<fn(Result<Vec<u8>, E>, Result<Vec<u8>, E>, StorageDiff)->Result<Vec<u8>, E>> {
 	ExecutionManager::NativeWhenPossible
 }
 
 /// Evaluate to ExecutionManager::NativeWhenPossible, without having to figure out the type.
-pub fn always_wasm<E>() -> ExecutionManager<fn(Result<Vec<u8>, E>, Result<Vec<u8>, E>)->Result<Vec<u8>, E>> {
+pub fn always_wasm<E>() -> ExecutionManager<fn(Result<Vec<u8>, E>, Result<Vec<u8>, E>, StorageDiff)->Result<Vec<u8>, E>> {
 	ExecutionManager::AlwaysWasm
 }
 
@@ -227,6 +265,8 @@ pub fn always_wasm<E>() -> ExecutionManager<fn(Result<Vec<u8>, E>, Result<Vec<u8
 ///
 /// Note: changes to code will be in place if this call is made again. For running partial
 /// blocks (e.g. a transaction at a time), ensure a different method is used.
+///
+/// The code is executed in the `ExecutionContext::Other` context.
 pub fn execute<H, B, T, Exec>(
 	backend: &B,
 	changes_trie_storage: Option<&T>,
@@ -250,17 +290,56 @@ where
 		exec,
 		method,
 		call_data,
+		ExecutionContext::Other,
+		None,
 		match strategy {
 			ExecutionStrategy::AlwaysWasm => ExecutionManager::AlwaysWasm,
 			ExecutionStrategy::NativeWhenPossible => ExecutionManager::NativeWhenPossible,
-			ExecutionStrategy::Both => ExecutionManager::Both(|wasm_result, native_result| {
+			ExecutionStrategy::Both => ExecutionManager::Both(|wasm_result, native_result, storage_diff| {
 				warn!("Consensus error between wasm {:?} and native {:?}. Using wasm.", wasm_result, native_result);
+				warn!("   Storage diff {:?}", storage_diff);
 				wasm_result
 			}),
 		},
 	)
 }
 
+/// Execute the offchain call using the given state backend and call executor. The code is
+/// executed in the `ExecutionContext::Offchain` context with access to the offchain
+/// externalities. The changes made by the call are discarded.
+pub fn execute_offchain<H, B, T, Exec>(
+	backend: &B,
+	changes_trie_storage: Option<&T>,
+	exec: &Exec,
+	method: &str,
+	call_data: &[u8],
+	offchain: &mut offchain::Externalities,
+	local_keys: Option<&LocalKeys>,
+) -> Result<Vec<u8>, Box<Error>>
+where
+	H: Hasher,
+	Exec: CodeExecutor<H>,
+	B: Backend<H>,
+	T: ChangesTrieStorage<H>,
+	H::Out: Ord + HeapSizeOf,
+{
+	let mut overlay = OverlayedChanges::default();
+	let code = try_read_overlay_value(&overlay, backend, well_known_keys::CODE)?
+		.ok_or_else(|| Box::new(ExecutionError::CodeEntryDoesNotExist) as Box<Error>)?
+		.to_vec();
+	let heap_pages = try_read_overlay_value(&overlay, backend, well_known_keys::HEAP_PAGES)?
+		.and_then(|v| u64::decode(&mut &v[..])).unwrap_or(DEFAULT_HEAP_PAGES) as usize;
+
+	let mut externalities = ext::Ext::new(&mut overlay, backend, changes_trie_storage)
+		.with_context(ExecutionContext::Offchain)
+		.with_offchain(offchain);
+	if let Some(local_keys) = local_keys {
+		externalities = externalities.with_local_keys(local_keys);
+	}
+	exec.call(&mut externalities, heap_pages, &code, method, call_data, true).0
+		.map_err(|e| Box::new(e) as _)
+}
+
 /// Execute a call using the given state backend, overlayed changes, and call executor.
 /// Produces a state-backend-specific "transaction" which can be used to apply the changes
 /// to the backing store, such as the disk.
@@ -269,6 +348,8 @@ where
 ///
 /// Note: changes to code will be in place if this call is made again. For running partial
 /// blocks (e.g. a transaction at a time), ensure a different method is used.
+///
+/// The `local_keys` are ignored unless the code is executed in the `Offchain` context.
 pub fn execute_using_consensus_failure_handler<H, B, T, Exec, Handler>(
 	backend: &B,
 	changes_trie_storage: Option<&T>,
@@ -276,6 +357,8 @@ pub fn execute_using_consensus_failure_handler<H, B, T, Exec, Handler>(
 	exec: &Exec,
 	method: &str,
 	call_data: &[u8],
+	context: ExecutionContext,
+	local_keys: Option<&LocalKeys>,
 	manager: ExecutionManager<Handler>,
 ) -> Result<(Vec<u8>, B::Transaction, Option<MemoryDB<H>>), Box<Error>>
 where
@@ -284,7 +367,7 @@ where
 	B: Backend<H>,
 	T: ChangesTrieStorage<H>,
 	H::Out: Ord + HeapSizeOf,
-	Handler: FnOnce(Result<Vec<u8>, Exec::Error>, Result<Vec<u8>, Exec::Error>) -> Result<Vec<u8>, Exec::Error>
+	Handler: FnOnce(Result<Vec<u8>, Exec::Error>, Result<Vec<u8>, Exec::Error>, StorageDiff) -> Result<Vec<u8>, Exec::Error>
 {
 	let strategy: ExecutionStrategy = (&manager).into();
 
@@ -312,11 +395,15 @@ where
 	init_overlay(overlay, false)?;
 
 	let result = {
-		let mut orig_prospective = overlay.prospective.clone();
+		let orig_prospective = overlay.prospective.clone();
 
 		let (result, was_native, storage_delta, changes_delta) = {
 			let ((result, was_native), (storage_delta, changes_delta)) = {
-				let mut externalities = ext::Ext::new(overlay, backend, changes_trie_storage);
+				let mut externalities = ext::Ext::new(overlay, backend, changes_trie_storage)
+					.with_context(context);
+				if let Some(local_keys) = local_keys {
+					externalities = externalities.with_local_keys(local_keys);
+				}
 				(
 					exec.call(
 						&mut externalities,
@@ -337,11 +424,15 @@ where
 		let (result, storage_delta, changes_delta) = if let (true, ExecutionManager::Both(on_consensus_failure)) =
 			(was_native, manager)
 		{
-			overlay.prospective = orig_prospective.clone();
+			let native_prospective = ::std::mem::replace(&mut overlay.prospective, orig_prospective);
 
 			let (wasm_result, wasm_storage_delta, wasm_changes_delta) = {
 				let ((result, _), (storage_delta, changes_delta)) = {
-					let mut externalities = ext::Ext::new(overlay, backend, changes_trie_storage);
+					let mut externalities = ext::Ext::new(overlay, backend, changes_trie_storage)
+						.with_context(context);
+					if let Some(local_keys) = local_keys {
+						externalities = externalities.with_local_keys(local_keys);
+					}
 					(
 						exec.call(
 							&mut externalities,
@@ -357,13 +448,14 @@ where
 				(result, storage_delta, changes_delta)
 			};
 
-			if (result.is_ok() && wasm_result.is_ok() && result.as_ref().unwrap() == wasm_result.as_ref().unwrap()/* && delta == wasm_delta*/)
+			let storage_diff = StorageDiff::between(&native_prospective, &overlay.prospective);
+			if (result.is_ok() && wasm_result.is_ok() && result.as_ref().unwrap() == wasm_result.as_ref().unwrap() && storage_diff.is_empty())
 				|| (result.is_err() && wasm_result.is_err())
 			{
 				(result, storage_delta, changes_delta)
 			} else {
 				// Consensus error.
-				(on_consensus_failure(wasm_result, result), wasm_storage_delta, wasm_changes_delta)
+				(on_consensus_failure(wasm_result, result, storage_diff), wasm_storage_delta, wasm_changes_delta)
 			}
 		} else {
 			(result, storage_delta, changes_delta)
@@ -379,6 +471,30 @@ where
 	result.map_err(|e| Box::new(e) as _)
 }
 
+/// Produce the state-backend-specific "transaction" of the changes that are already in the
+/// overlay, without executing any code. The changes trie transaction is built for the given
+/// block if changes tries are enabled.
+///
+/// Returns the storage root and the changes trie root after the changes along with the transactions.
+pub fn overlay_transaction<H, B, T>(
+	backend: &B,
+	changes_trie_storage: Option<&T>,
+	overlay: &mut OverlayedChanges,
+	block: u64,
+) -> (H::Out, Option<H::Out>, B::Transaction, Option<MemoryDB<H>>)
+where
+	H: Hasher,
+	B: Backend<H>,
+	T: ChangesTrieStorage<H>,
+	H::Out: Ord + HeapSizeOf,
+{
+	let mut ext = ext::Ext::new(overlay, backend, changes_trie_storage);
+	let root = ext.storage_root();
+	let changes_trie_root = ext.storage_changes_root(block);
+	let (storage_transaction, changes_trie_transaction) = ext.transaction();
+	(root, changes_trie_root, storage_transaction, changes_trie_transaction)
+}
+
 /// Prove execution using the given state backend, overlayed changes, and call executor.
 /// Produces a state-backend-specific "transaction" which can be used to apply the changes
 /// to the backing store, such as the disk.
@@ -395,6 +511,26 @@ pub fn prove_execution<B, H, Exec>(
 	method: &str,
 	call_data: &[u8],
 ) -> Result<(Vec<u8>, Vec<Vec<u8>>), Box<Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+	Exec: CodeExecutor<H>,
+	H::Out: Ord + HeapSizeOf,
+{
+	prove_execution_with_keys(backend, overlay, exec, method, call_data)
+		.map(|(result, proof, _)| (result, proof))
+}
+
+/// Same as `prove_execution`, but also returns storage keys read from the backend during
+/// execution, in order of the first access. Keys that have been served from the overlay
+/// are not included.
+pub fn prove_execution_with_keys<B, H, Exec>(
+	backend: B,
+	overlay: &mut OverlayedChanges,
+	exec: &Exec,
+	method: &str,
+	call_data: &[u8],
+) -> Result<(Vec<u8>, Vec<Vec<u8>>, Vec<Vec<u8>>), Box<Error>>
 where
 	B: Backend<H>,
 	H: Hasher,
@@ -413,8 +549,8 @@ where
 		call_data,
 		ExecutionStrategy::NativeWhenPossible
 	)?;
-	let proof = proving_backend.extract_proof();
-	Ok((result, proof))
+	let (proof, keys) = proving_backend.extract_proof_and_keys();
+	Ok((result, proof, keys))
 }
 
 /// Check execution proof, generated by `prove_execution` call.
@@ -454,6 +590,29 @@ where
 	Ok((result, proving_backend.extract_proof()))
 }
 
+/// Generate single proof of the storage values of all given keys.
+pub fn prove_read_keys<B, H, I>(
+	backend: B,
+	keys: I,
+) -> Result<(Vec<Option<Vec<u8>>>, Vec<Vec<u8>>), Box<Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+	I: IntoIterator,
+	I::Item: AsRef<[u8]>,
+
+	H::Out: Ord + HeapSizeOf
+{
+	let trie_backend = backend.try_into_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<Error>)?;
+	let proving_backend = proving_backend::ProvingBackend::<_, H>::new(trie_backend);
+	let values = keys.into_iter()
+		.map(|key| proving_backend.storage(key.as_ref()))
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|e| Box::new(e) as Box<Error>)?;
+	Ok((values, proving_backend.extract_proof()))
+}
+
 /// Check storage read proof, generated by `prove_read` call.
 pub fn read_proof_check<H>(
 	root: H::Out,
@@ -469,6 +628,181 @@ where
 	backend.storage(key).map_err(|e| Box::new(e) as Box<Error>)
 }
 
+/// Check storage read proof of multiple keys, generated by `prove_read_keys` call. Returns
+/// values in the order of the keys.
+pub fn read_keys_proof_check<H, I>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+	keys: I,
+) -> Result<Vec<Option<Vec<u8>>>, Box<Error>>
+where
+	H: Hasher,
+	I: IntoIterator,
+	I::Item: AsRef<[u8]>,
+
+	H::Out: Ord + HeapSizeOf
+{
+	let backend = proving_backend::create_proof_check_backend::<H>(root, proof)?;
+	read_keys_from_backend(&backend, keys)
+}
+
+/// Check storage read proof of multiple keys, the nodes of which have already been inserted
+/// into the `nodes` database.
+pub fn read_keys_proof_nodes_check<H, I>(
+	root: H::Out,
+	nodes: MemoryDB<H>,
+	keys: I,
+) -> Result<Vec<Option<Vec<u8>>>, Box<Error>>
+where
+	H: Hasher,
+	I: IntoIterator,
+	I::Item: AsRef<[u8]>,
+
+	H::Out: Ord + HeapSizeOf
+{
+	let backend = proving_backend::create_proof_check_backend_from_nodes::<H>(root, nodes)?;
+	read_keys_from_backend(&backend, keys)
+}
+
+fn read_keys_from_backend<H, I>(
+	backend: &TrieBackend<MemoryDB<H>, H>,
+	keys: I,
+) -> Result<Vec<Option<Vec<u8>>>, Box<Error>>
+where
+	H: Hasher,
+	I: IntoIterator,
+	I::Item: AsRef<[u8]>,
+
+	H::Out: Ord + HeapSizeOf
+{
+	keys.into_iter()
+		.map(|key| backend.storage(key.as_ref()))
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|e| Box::new(e) as Box<Error>)
+}
+
+/// Generate child storage read proof. The proof contains the nodes of the main trie proving the
+/// root of the child trie and the nodes of the child trie proving the value.
+pub fn prove_child_read<B, H>(
+	backend: B,
+	storage_key: &[u8],
+	key: &[u8],
+) -> Result<(Option<Vec<u8>>, Vec<Vec<u8>>), Box<Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+
+	H::Out: Ord + HeapSizeOf
+{
+	let trie_backend = backend.try_into_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<Error>)?;
+	let proving_backend = proving_backend::ProvingBackend::<_, H>::new(trie_backend);
+	let result = proving_backend.child_storage(storage_key, key).map_err(|e| Box::new(e) as Box<Error>)?;
+	Ok((result, proving_backend.extract_proof()))
+}
+
+/// Check child storage read proof, generated by `prove_child_read` call. The child trie root
+/// is read from the main trie part of the proof.
+pub fn child_read_proof_check<H>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+	storage_key: &[u8],
+	key: &[u8],
+) -> Result<Option<Vec<u8>>, Box<Error>>
+where
+	H: Hasher,
+
+	H::Out: Ord + HeapSizeOf
+{
+	let backend = proving_backend::create_proof_check_backend::<H>(root, proof)?;
+	backend.child_storage(storage_key, key).map_err(|e| Box::new(e) as Box<Error>)
+}
+
+/// Check child storage read proof, the nodes of which have already been inserted into the
+/// `nodes` database.
+pub fn child_read_proof_nodes_check<H>(
+	root: H::Out,
+	nodes: MemoryDB<H>,
+	storage_key: &[u8],
+	key: &[u8],
+) -> Result<Option<Vec<u8>>, Box<Error>>
+where
+	H: Hasher,
+
+	H::Out: Ord + HeapSizeOf
+{
+	let backend = proving_backend::create_proof_check_backend_from_nodes::<H>(root, nodes)?;
+	backend.child_storage(storage_key, key).map_err(|e| Box::new(e) as Box<Error>)
+}
+
+/// Generate proof of the roots of all child tries, which are stored in the main trie under
+/// the keys starting with `CHILD_STORAGE_KEY_PREFIX`.
+pub fn prove_child_roots<B, H>(backend: B) -> Result<Vec<Vec<u8>>, Box<Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+
+	H::Out: Ord + HeapSizeOf
+{
+	let trie_backend = backend.try_into_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<Error>)?;
+	let mut read_overlay = MemoryDB::default();
+	let eph = trie_backend_essence::Ephemeral::new(trie_backend.backend_storage(), &mut read_overlay);
+	trie::prove_prefix::<H>(&eph, trie_backend.root(), well_known_keys::CHILD_STORAGE_KEY_PREFIX)
+		.map_err(|e| Box::new(format!("Trie lookup error: {}", e)) as Box<Error>)
+}
+
+/// Check child tries roots proof, generated by `prove_child_roots` call. Returns the storage
+/// keys and the roots of all child tries.
+pub fn child_roots_proof_check<H>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Box<Error>>
+where
+	H: Hasher,
+{
+	trie::check_prefix_proof::<H>(&root, well_known_keys::CHILD_STORAGE_KEY_PREFIX, proof)
+		.map_err(|e| Box::new(format!("Invalid child tries roots proof: {}", e)) as Box<Error>)
+}
+
+/// Generate proof of the page of storage entries with keys starting with the `prefix`. The page
+/// starts from the first such entry with the key that is greater than or equal to the `start_key`
+/// and contains at most `limit` entries.
+pub fn prove_read_prefix<B, H>(
+	backend: B,
+	prefix: &[u8],
+	start_key: &[u8],
+	limit: usize,
+) -> Result<Vec<Vec<u8>>, Box<Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+
+	H::Out: Ord + HeapSizeOf
+{
+	let trie_backend = backend.try_into_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<Error>)?;
+	let mut read_overlay = MemoryDB::default();
+	let eph = trie_backend_essence::Ephemeral::new(trie_backend.backend_storage(), &mut read_overlay);
+	trie::prove_prefix_page::<H>(&eph, trie_backend.root(), prefix, start_key, limit)
+		.map_err(|e| Box::new(format!("Trie lookup error: {}", e)) as Box<Error>)
+}
+
+/// Check storage prefix read proof, generated by `prove_read_prefix` call. Returns the proved
+/// entries and the flag that is true if there are no more entries with the `prefix` after them.
+pub fn read_prefix_proof_check<H>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+	prefix: &[u8],
+	start_key: &[u8],
+) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Box<Error>>
+where
+	H: Hasher,
+{
+	trie::check_prefix_page_proof::<H>(&root, prefix, start_key, proof)
+		.map_err(|e| Box::new(format!("Invalid storage prefix read proof: {}", e)) as Box<Error>)
+}
+
 /// Sets overlayed changes' changes trie configuration. Returns error if configuration
 /// differs from previous OR config decode has failed.
 pub(crate) fn set_changes_trie_config(overlay: &mut OverlayedChanges, config: Option<Vec<u8>>, final_check: bool) -> Result<(), Box<Error>> {
@@ -574,6 +908,29 @@ mod tests {
 		).unwrap().0, vec![66]);
 	}
 
+	#[test]
+	fn overlay_transaction_works() {
+		let backend = trie_backend::tests::test_trie();
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_storage(b"value1".to_vec(), Some(vec![42]));
+		overlay.set_storage(b"value2".to_vec(), None);
+		overlay.commit_prospective();
+
+		let expected_root = backend.storage_root(vec![
+			(b"value1".to_vec(), Some(vec![42])),
+			(b"value2".to_vec(), None),
+		]).0;
+		let (root, changes_trie_root, _, changes_trie_transaction) = overlay_transaction(
+			&backend,
+			Some(&InMemoryChangesTrieStorage::<Blake2Hasher>::new()),
+			&mut overlay,
+			1,
+		);
+		assert_eq!(root, expected_root);
+		assert!(changes_trie_root.is_none());
+		assert!(changes_trie_transaction.is_none());
+	}
+
 	#[test]
 	fn dual_execution_strategy_detects_consensus_failure() {
 		let mut consensus_failed = false;
@@ -589,7 +946,9 @@ mod tests {
 			},
 			"test",
 			&[],
-			ExecutionManager::Both(|we, _ne| {
+			ExecutionContext::Other,
+			None,
+			ExecutionManager::Both(|we, _ne, _diff| {
 				consensus_failed = true;
 				println!("HELLO!");
 				we
@@ -691,6 +1050,95 @@ mod tests {
 		assert_eq!(local_result2, false);
 	}
 
+	#[test]
+	fn prove_read_keys_and_proof_check_works() {
+		let remote_backend = trie_backend::tests::test_trie();
+		let remote_root = remote_backend.storage_root(::std::iter::empty()).0;
+		let (values, remote_proof) = prove_read_keys(remote_backend, &[&b"value1"[..], b"value2", b"value3"]).unwrap();
+		assert_eq!(values, vec![Some(vec![42]), Some(vec![24]), None]);
+
+		// every key is provable with the same proof
+		for (key, value) in &[(&b"value1"[..], Some(vec![42])), (b"value2", Some(vec![24])), (b"value3", None)] {
+			assert_eq!(read_proof_check::<Blake2Hasher>(remote_root, remote_proof.clone(), key).unwrap(), *value);
+		}
+		assert_eq!(
+			read_keys_proof_check::<Blake2Hasher, _>(remote_root, remote_proof.clone(), &[&b"value3"[..], b"value1"]).unwrap(),
+			vec![None, Some(vec![42])],
+		);
+		assert!(read_keys_proof_check::<Blake2Hasher, _>(remote_root, remote_proof, &[&b"value1"[..], &[0xff][..]]).is_err());
+	}
+
+	#[test]
+	fn prove_child_read_and_proof_check_works() {
+		let child_key = [well_known_keys::CHILD_STORAGE_KEY_PREFIX, &b"default:1"[..]].concat();
+		let mut mdb = MemoryDB::<Blake2Hasher>::default();
+		let mut child_root = Default::default();
+		{
+			let mut trie = TrieDBMut::new(&mut mdb, &mut child_root);
+			trie.insert(b"value1", &[1; 40]).unwrap();
+			trie.insert(b"value2", &[2; 40]).unwrap();
+		}
+		let mut root = Default::default();
+		{
+			let mut trie = TrieDBMut::new(&mut mdb, &mut root);
+			trie.insert(b":code", b"return 42").unwrap();
+			trie.insert(&child_key, child_root.as_ref()).unwrap();
+		}
+
+		// fetch child read proof from 'remote' full node
+		let (value, remote_proof) = prove_child_read(TrieBackend::new(mdb, root), &child_key, b"value2").unwrap();
+		assert_eq!(value, Some(vec![2; 40]));
+		// check proof locally
+		assert_eq!(child_read_proof_check::<Blake2Hasher>(root, remote_proof.clone(), &child_key, b"value2").unwrap(), Some(vec![2; 40]));
+		assert!(child_read_proof_check::<Blake2Hasher>(root, remote_proof.clone(), &child_key, b"value1").is_err());
+		assert!(child_read_proof_check::<Blake2Hasher>(Default::default(), remote_proof, &child_key, b"value2").is_err());
+	}
+
+	#[test]
+	fn prove_child_roots_and_proof_check_works() {
+		let child_key = |name: &[u8]| [well_known_keys::CHILD_STORAGE_KEY_PREFIX, name].concat();
+		let mut root = Default::default();
+		let mut mdb = MemoryDB::<Blake2Hasher>::default();
+		{
+			let mut trie = TrieDBMut::new(&mut mdb, &mut root);
+			trie.insert(b":code", b"return 42").unwrap();
+			trie.insert(&child_key(b"default:1"), &[1; 32]).unwrap();
+			trie.insert(&child_key(b"default:2"), &[2; 32]).unwrap();
+			trie.insert(b"value", &[42]).unwrap();
+		}
+
+		// fetch child roots proof from 'remote' full node
+		let remote_proof = prove_child_roots(TrieBackend::new(mdb, root)).unwrap();
+		// check proof locally
+		assert_eq!(child_roots_proof_check::<Blake2Hasher>(root, remote_proof.clone()).unwrap(), vec![
+			(child_key(b"default:1"), vec![1; 32]),
+			(child_key(b"default:2"), vec![2; 32]),
+		]);
+		assert!(child_roots_proof_check::<Blake2Hasher>(Default::default(), remote_proof).is_err());
+	}
+
+	#[test]
+	fn prove_read_prefix_and_proof_check_works() {
+		let mut root = Default::default();
+		let mut mdb = MemoryDB::<Blake2Hasher>::default();
+		{
+			let mut trie = TrieDBMut::new(&mut mdb, &mut root);
+			trie.insert(b":code", b"return 42").unwrap();
+			trie.insert(b"value1", &[1; 40]).unwrap();
+			trie.insert(b"value2", &[2; 40]).unwrap();
+			trie.insert(b"xyz", &[3; 40]).unwrap();
+		}
+
+		// fetch prefix read proof from 'remote' full node
+		let remote_proof = prove_read_prefix(TrieBackend::new(mdb, root), b"value", b"", 10).unwrap();
+		// check proof locally
+		assert_eq!(read_prefix_proof_check::<Blake2Hasher>(root, remote_proof.clone(), b"value", b"").unwrap(), (vec![
+			(b"value1".to_vec(), vec![1; 40]),
+			(b"value2".to_vec(), vec![2; 40]),
+		], true));
+		assert!(read_prefix_proof_check::<Blake2Hasher>(Default::default(), remote_proof, b"value", b"").is_err());
+	}
+
 	#[test]
 	fn cannot_change_changes_trie_config() {
 		assert!(execute(
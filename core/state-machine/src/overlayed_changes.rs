@@ -17,9 +17,10 @@
 //! The overlayed changes to state.
 
 #[cfg(test)] use std::iter::FromIterator;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use codec::Decode;
 use changes_trie::{NO_EXTRINSIC_INDEX, Configuration as ChangesTrieConfig};
+use hash_db::Hasher;
 use primitives::storage::well_known_keys::EXTRINSIC_INDEX;
 
 /// The overlayed changes to state to be queried on top of the backend.
@@ -35,6 +36,14 @@ pub struct OverlayedChanges {
 	/// Changes trie configuration. None by default, but could be installed by the
 	/// runtime if it supports change tries.
 	pub(crate) changes_trie_config: Option<ChangesTrieConfig>,
+	/// Changes trie root returned to the runtime instead of building the changes trie.
+	pub(crate) trusted_changes_trie_root: Option<Vec<u8>>,
+	/// Chain-derived entropy of the block being executed. None if the call isn't made in the
+	/// context of a block.
+	pub(crate) chain_entropy: Option<Vec<u8>>,
+	/// Hashes of the recent ancestors of the block being executed, starting from the parent.
+	/// Empty if the call isn't made in the context of a block.
+	pub(crate) recent_block_hashes: Vec<Vec<u8>>,
 }
 
 /// The storage value, used inside OverlayedChanges.
@@ -68,6 +77,56 @@ impl FromIterator<(Vec<u8>, OverlayedValue)> for OverlayedChangeSet {
 	}
 }
 
+/// Difference between storage changes made by the native and the wasm execution of the same call.
+///
+/// Values are `None` if the key has not been touched by the execution and `Some(None)` if the
+/// key has been deleted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StorageDiff {
+	/// Differing top-level entries as `(key, native value, wasm value)`.
+	pub top: Vec<(Vec<u8>, Option<Option<Vec<u8>>>, Option<Option<Vec<u8>>>)>,
+	/// Differing child storage entries as `(storage key, key, native value, wasm value)`.
+	pub children: Vec<(Vec<u8>, Vec<u8>, Option<Option<Vec<u8>>>, Option<Option<Vec<u8>>>)>,
+}
+
+impl StorageDiff {
+	/// Compute the difference between change sets of the native and the wasm execution.
+	pub(crate) fn between(native: &OverlayedChangeSet, wasm: &OverlayedChangeSet) -> Self {
+		let mut diff = StorageDiff::default();
+
+		let top_keys: BTreeSet<_> = native.top.keys().chain(wasm.top.keys()).collect();
+		for key in top_keys {
+			let native_value = native.top.get(key).map(|v| v.value.clone());
+			let wasm_value = wasm.top.get(key).map(|v| v.value.clone());
+			if native_value != wasm_value {
+				diff.top.push((key.clone(), native_value, wasm_value));
+			}
+		}
+
+		let no_changes = HashMap::new();
+		let storage_keys: BTreeSet<_> = native.children.keys().chain(wasm.children.keys()).collect();
+		for storage_key in storage_keys {
+			let native_child = native.children.get(storage_key).map(|c| &c.1).unwrap_or(&no_changes);
+			let wasm_child = wasm.children.get(storage_key).map(|c| &c.1).unwrap_or(&no_changes);
+			let keys: BTreeSet<_> = native_child.keys().chain(wasm_child.keys()).collect();
+			for key in keys {
+				let native_value = native_child.get(key).cloned();
+				let wasm_value = wasm_child.get(key).cloned();
+				if native_value != wasm_value {
+					diff.children.push((storage_key.clone(), key.clone(), native_value, wasm_value));
+				}
+			}
+		}
+
+		diff
+	}
+
+	/// Whether both executions have made the same changes.
+	pub fn is_empty(&self) -> bool {
+		self.top.is_empty() && self.children.is_empty()
+	}
+}
+
 impl OverlayedChangeSet {
 	/// Whether the change set is empty.
 	pub fn is_empty(&self) -> bool {
@@ -98,6 +157,52 @@ impl OverlayedChanges {
 		true
 	}
 
+	/// Skip building the changes trie, returning given root to the runtime instead.
+	///
+	/// Used when importing blocks with already known changes trie root (i.e. the root from the
+	/// header of the block being imported), whose changes trie is built later.
+	pub fn trust_changes_trie_root(&mut self, root: Vec<u8>) {
+		self.trusted_changes_trie_root = Some(root);
+	}
+
+	/// Provide the chain-derived entropy of the block being executed to the runtime.
+	pub fn set_chain_entropy(&mut self, entropy: Vec<u8>) {
+		self.chain_entropy = Some(entropy);
+	}
+
+	/// Provide the hashes of the recent ancestors of the block being executed to the runtime,
+	/// starting from the parent.
+	pub fn set_recent_block_hashes(&mut self, hashes: Vec<Vec<u8>>) {
+		self.recent_block_hashes = hashes;
+	}
+
+	/// Hash of the ancestor of the block being executed at the given depth (1 is the parent).
+	pub(crate) fn recent_block_hash<H: Hasher>(&self, depth: u32) -> Option<H::Out> {
+		if depth == 0 {
+			return None;
+		}
+
+		self.recent_block_hashes.get(depth as usize - 1).and_then(|hash| {
+			let mut out = H::Out::default();
+			if out.as_ref().len() != hash.len() {
+				return None;
+			}
+			out.as_mut().copy_from_slice(hash);
+			Some(out)
+		})
+	}
+
+	/// Randomness for the given subject, derived from the chain entropy. Different subjects get
+	/// independent values within the same block.
+	pub(crate) fn chain_randomness<H: Hasher>(&self, subject: &[u8]) -> Option<H::Out> {
+		self.chain_entropy.as_ref().map(|entropy| {
+			let mut seed = Vec::with_capacity(entropy.len() + subject.len());
+			seed.extend_from_slice(entropy);
+			seed.extend_from_slice(subject);
+			H::hash(&seed)
+		})
+	}
+
 	/// Returns a double-Option: None if the key is unknown (i.e. and the query should be refered
 	/// to the backend); Some(None) if the key has been deleted. Some(Some(...)) for a key whose
 	/// value has been set.
@@ -473,4 +578,25 @@ mod tests {
 		assert_eq!(overlay.prospective,
 			Default::default());
 	}
+
+	#[test]
+	fn storage_diff_contains_only_differing_changes() {
+		let mut native = OverlayedChanges::default();
+		native.set_storage(vec![1], Some(vec![1]));
+		native.set_storage(vec![2], Some(vec![2]));
+		native.set_storage(vec![3], None);
+		let mut wasm = OverlayedChanges::default();
+		wasm.set_storage(vec![1], Some(vec![1]));
+		wasm.set_storage(vec![2], Some(vec![20]));
+		wasm.set_storage(vec![4], Some(vec![4]));
+
+		let diff = StorageDiff::between(&native.prospective, &wasm.prospective);
+		assert_eq!(diff.top, vec![
+			(vec![2], Some(Some(vec![2])), Some(Some(vec![20]))),
+			(vec![3], Some(None), None),
+			(vec![4], None, Some(Some(vec![4]))),
+		]);
+		assert!(diff.children.is_empty());
+		assert!(StorageDiff::between(&native.prospective, &native.prospective).is_empty());
+	}
 }
@@ -17,6 +17,7 @@
 //! Proving state machine backend.
 
 use std::cell::RefCell;
+use std::collections::HashSet;
 use hash_db::Hasher;
 use heapsize::HeapSizeOf;
 use hash_db::HashDB;
@@ -87,6 +88,23 @@ impl<'a, S, H> ProvingBackendEssence<'a, S, H>
 pub struct ProvingBackend<S: TrieBackendStorage<H>, H: Hasher> {
 	backend: TrieBackend<S, H>,
 	proof_recorder: RefCell<Recorder<H::Out>>,
+	accessed_keys: RefCell<AccessedKeys>,
+}
+
+/// Storage keys read from the backend, in order of the first access.
+#[derive(Default)]
+struct AccessedKeys {
+	keys: Vec<Vec<u8>>,
+	known: HashSet<Vec<u8>>,
+}
+
+impl AccessedKeys {
+	fn note(&mut self, key: &[u8]) {
+		if !self.known.contains(key) {
+			self.known.insert(key.to_vec());
+			self.keys.push(key.to_vec());
+		}
+	}
 }
 
 impl<S: TrieBackendStorage<H>, H: Hasher> ProvingBackend<S, H> {
@@ -95,16 +113,25 @@ impl<S: TrieBackendStorage<H>, H: Hasher> ProvingBackend<S, H> {
 		ProvingBackend {
 			backend,
 			proof_recorder: RefCell::new(Recorder::new()),
+			accessed_keys: RefCell::new(Default::default()),
 		}
 	}
 
 	/// Consume the backend, extracting the gathered proof in lexicographical order
 	/// by value.
 	pub fn extract_proof(self) -> Vec<Vec<u8>> {
-		self.proof_recorder.into_inner().drain()
+		self.extract_proof_and_keys().0
+	}
+
+	/// Consume the backend, extracting the gathered proof and the storage keys that have been
+	/// read, in order of the first access. Reads of child storage are reported by the key of
+	/// the child storage.
+	pub fn extract_proof_and_keys(self) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+		let proof = self.proof_recorder.into_inner().drain()
 			.into_iter()
 			.map(|n| n.data.to_vec())
-			.collect()
+			.collect();
+		(proof, self.accessed_keys.into_inner().keys)
 	}
 }
 
@@ -119,6 +146,7 @@ impl<S, H> Backend<H> for ProvingBackend<S, H>
 	type TrieBackendStorage = MemoryDB<H>;
 
 	fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.accessed_keys.borrow_mut().note(key);
 		ProvingBackendEssence {
 			backend: self.backend.essence(),
 			proof_recorder: &mut *self.proof_recorder.try_borrow_mut()
@@ -127,6 +155,7 @@ impl<S, H> Backend<H> for ProvingBackend<S, H>
 	}
 
 	fn child_storage(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.accessed_keys.borrow_mut().note(storage_key);
 		ProvingBackendEssence {
 			backend: self.backend.essence(),
 			proof_recorder: &mut *self.proof_recorder.try_borrow_mut()
@@ -142,6 +171,15 @@ impl<S, H> Backend<H> for ProvingBackend<S, H>
 		self.backend.for_keys_with_prefix(prefix, f)
 	}
 
+	fn pairs_with_prefix_from(
+		&self,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: usize,
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::Error> {
+		self.backend.pairs_with_prefix_from(prefix, start_key, count)
+	}
+
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
 		self.backend.pairs()
 	}
@@ -179,6 +217,19 @@ where
 		db.insert(&item);
 	}
 
+	create_proof_check_backend_from_nodes(root, db)
+}
+
+/// Create proof check backend from the proof nodes that have already been inserted into the
+/// database, e.g. along with the hashes that are known to the caller.
+pub fn create_proof_check_backend_from_nodes<H>(
+	root: H::Out,
+	db: MemoryDB<H>,
+) -> Result<TrieBackend<MemoryDB<H>, H>, Box<Error>>
+where
+	H: Hasher,
+	H::Out: HeapSizeOf,
+{
 	if !db.contains(&root) {
 		return Err(Box::new(ExecutionError::InvalidProof) as Box<Error>);
 	}
@@ -209,6 +260,17 @@ mod tests {
 		assert!(!backend.extract_proof().is_empty());
 	}
 
+	#[test]
+	fn accessed_keys_are_reported_in_order_of_first_access() {
+		let backend = test_proving();
+		backend.storage(b"key").unwrap();
+		backend.storage(b"value2").unwrap();
+		backend.storage(b"key").unwrap();
+		let (proof, keys) = backend.extract_proof_and_keys();
+		assert!(!proof.is_empty());
+		assert_eq!(keys, vec![b"key".to_vec(), b"value2".to_vec()]);
+	}
+
 	#[test]
 	fn proof_is_invalid_when_does_not_contains_root() {
 		assert!(create_proof_check_backend::<Blake2Hasher>(1.into(), vec![]).is_err());
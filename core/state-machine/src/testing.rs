@@ -54,6 +54,16 @@ impl<H: Hasher> TestExternalities<H> where H::Out: HeapSizeOf {
 	pub fn insert(&mut self, k: Vec<u8>, v: Vec<u8>) -> Option<Vec<u8>> {
 		self.inner.insert(k, v)
 	}
+
+	/// Set the chain entropy the randomness is derived from.
+	pub fn set_chain_entropy(&mut self, entropy: Vec<u8>) {
+		self.changes.set_chain_entropy(entropy);
+	}
+
+	/// Set the hashes of the recent blocks, starting from the parent of the executed block.
+	pub fn set_recent_block_hashes(&mut self, hashes: Vec<Vec<u8>>) {
+		self.changes.set_recent_block_hashes(hashes);
+	}
 }
 
 impl<H: Hasher> ::std::fmt::Debug for TestExternalities<H> where H::Out: HeapSizeOf {
@@ -128,6 +138,14 @@ impl<H: Hasher> Externalities<H> for TestExternalities<H> where H::Out: Ord + He
 
 	fn chain_id(&self) -> u64 { 42 }
 
+	fn chain_randomness(&self, subject: &[u8]) -> Option<H::Out> {
+		self.changes.chain_randomness::<H>(subject)
+	}
+
+	fn recent_block_hash(&self, depth: u32) -> Option<H::Out> {
+		self.changes.recent_block_hash::<H>(depth)
+	}
+
 	fn storage_root(&mut self) -> H::Out {
 		trie_root::<H, _, _, _>(self.inner.clone())
 	}
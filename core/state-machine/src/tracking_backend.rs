@@ -0,0 +1,176 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! State backend that tracks accesses to the wrapped backend.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+use hash_db::Hasher;
+use backend::Backend;
+use trie_backend::TrieBackend;
+
+/// Number of backend accesses made by a single execution.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DbAccessCounts {
+	/// Number of top-level storage reads.
+	pub storage_reads: u64,
+	/// Number of child storage reads.
+	pub child_storage_reads: u64,
+	/// Number of key iterations (by prefix or over child storage).
+	pub key_iterations: u64,
+}
+
+/// Backend that counts accesses to the wrapped backend and records the top-level
+/// storage keys that have been read.
+pub struct TrackingBackend<'a, B: 'a> {
+	backend: &'a B,
+	counts: Cell<DbAccessCounts>,
+	read_keys: RefCell<BTreeSet<Vec<u8>>>,
+}
+
+impl<'a, B> TrackingBackend<'a, B> {
+	/// Create new tracking backend, wrapping the given backend.
+	pub fn new(backend: &'a B) -> Self {
+		TrackingBackend {
+			backend,
+			counts: Cell::new(Default::default()),
+			read_keys: RefCell::new(BTreeSet::new()),
+		}
+	}
+
+	/// Number of accesses made so far.
+	pub fn counts(&self) -> DbAccessCounts {
+		self.counts.get()
+	}
+
+	/// Top-level storage keys that have been read so far, in lexicographical order.
+	pub fn read_keys(&self) -> Vec<Vec<u8>> {
+		self.read_keys.borrow().iter().cloned().collect()
+	}
+
+	fn note<F: FnOnce(&mut DbAccessCounts)>(&self, f: F) {
+		let mut counts = self.counts.get();
+		f(&mut counts);
+		self.counts.set(counts);
+	}
+
+	fn note_read(&self, key: &[u8]) {
+		self.note(|counts| counts.storage_reads += 1);
+		self.read_keys.borrow_mut().insert(key.to_vec());
+	}
+}
+
+impl<'a, H, B> Backend<H> for TrackingBackend<'a, B>
+	where
+		H: Hasher,
+		B: Backend<H>,
+{
+	type Error = B::Error;
+	type Transaction = B::Transaction;
+	type TrieBackendStorage = B::TrieBackendStorage;
+
+	fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.note_read(key);
+		self.backend.storage(key)
+	}
+
+	fn child_storage(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.note(|counts| counts.child_storage_reads += 1);
+		self.backend.child_storage(storage_key, key)
+	}
+
+	fn exists_storage(&self, key: &[u8]) -> Result<bool, Self::Error> {
+		self.note_read(key);
+		self.backend.exists_storage(key)
+	}
+
+	fn exists_child_storage(&self, storage_key: &[u8], key: &[u8]) -> Result<bool, Self::Error> {
+		self.note(|counts| counts.child_storage_reads += 1);
+		self.backend.exists_child_storage(storage_key, key)
+	}
+
+	fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], f: F) {
+		self.note(|counts| counts.key_iterations += 1);
+		self.backend.for_keys_in_child_storage(storage_key, f)
+	}
+
+	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
+		self.note(|counts| counts.key_iterations += 1);
+		self.backend.for_keys_with_prefix(prefix, f)
+	}
+
+	fn pairs_with_prefix_from(
+		&self,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: usize,
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::Error> {
+		self.note(|counts| counts.key_iterations += 1);
+		self.backend.pairs_with_prefix_from(prefix, start_key, count)
+	}
+
+	fn storage_root<I>(&self, delta: I) -> (H::Out, Self::Transaction)
+	where
+		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
+		H::Out: Ord,
+	{
+		self.backend.storage_root(delta)
+	}
+
+	fn child_storage_root<I>(&self, storage_key: &[u8], delta: I) -> (Vec<u8>, bool, Self::Transaction)
+	where
+		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
+		H::Out: Ord,
+	{
+		self.backend.child_storage_root(storage_key, delta)
+	}
+
+	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+		self.backend.pairs()
+	}
+
+	fn try_into_trie_backend(self) -> Option<TrieBackend<Self::TrieBackendStorage, H>> {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use primitives::Blake2Hasher;
+	use backend::InMemory;
+	use super::*;
+
+	#[test]
+	fn tracking_backend_records_reads() {
+		let backend: InMemory<Blake2Hasher> = vec![
+			(b"a".to_vec(), vec![1]),
+			(b"b".to_vec(), vec![2]),
+		].into_iter().collect::<::std::collections::HashMap<_, _>>().into();
+		let tracking = TrackingBackend::new(&backend);
+
+		assert_eq!(tracking.storage(b"b").unwrap(), Some(vec![2]));
+		assert_eq!(tracking.storage(b"c").unwrap(), None);
+		assert!(tracking.exists_storage(b"b").unwrap());
+		tracking.for_keys_with_prefix(b"", |_| ());
+
+		assert_eq!(tracking.read_keys(), vec![b"b".to_vec(), b"c".to_vec()]);
+		assert_eq!(tracking.counts(), DbAccessCounts {
+			storage_reads: 3,
+			child_storage_reads: 0,
+			key_iterations: 1,
+		});
+	}
+}
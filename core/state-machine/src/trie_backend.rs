@@ -69,13 +69,31 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 	}
 
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
-		self.essence.for_keys_with_prefix(prefix, f)
+		if let Err(e) = self.essence.for_keys_with_prefix(prefix, f) {
+			debug!(target: "trie", "{}", e);
+		}
+	}
+
+	fn pairs_with_prefix_from(
+		&self,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: usize,
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Self::Error> {
+		self.essence.pairs_with_prefix_from(prefix, start_key, count)
 	}
 
 	fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], f: F) {
 		self.essence.for_keys_in_child_storage(storage_key, f)
 	}
 
+	fn storage_diff(&self, other: &Self) -> Result<Vec<(Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>)>, Self::Error> {
+		let mut values = Vec::new();
+		self.essence.diff(&other.essence, |key, value, other_value|
+			values.push((key.to_vec(), value.map(|v| v.to_vec()), other_value.map(|v| v.to_vec()))))?;
+		Ok(values)
+	}
+
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
 		let mut read_overlay = MemoryDB::default();	// TODO: use new for correctness
 		let eph = Ephemeral::new(self.essence.backend_storage(), &mut read_overlay);
@@ -161,9 +179,10 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 
 #[cfg(test)]
 pub mod tests {
-	use std::collections::HashSet;
+	use std::collections::{HashMap, HashSet};
 	use primitives::{Blake2Hasher, H256};
 	use trie::{TrieMut, TrieDBMut};
+	use backend::InMemory;
 	use super::*;
 
 	fn test_db() -> (MemoryDB<Blake2Hasher>, H256) {
@@ -210,6 +229,23 @@ pub mod tests {
 		).pairs().is_empty());
 	}
 
+	#[test]
+	fn pairs_with_prefix_are_paged_from_start_key() {
+		let trie = test_trie();
+		let page = |start_key: Option<&[u8]>, count| trie.pairs_with_prefix_from(b"value", start_key, count).unwrap();
+
+		assert_eq!(page(None, 10), vec![(b"value1".to_vec(), vec![42]), (b"value2".to_vec(), vec![24])]);
+		assert_eq!(page(None, 1), vec![(b"value1".to_vec(), vec![42])]);
+		assert_eq!(page(Some(&b"value1"[..]), 1), vec![(b"value2".to_vec(), vec![24])]);
+		assert!(page(Some(&b"value2"[..]), 1).is_empty());
+		assert_eq!(page(Some(&b"key"[..]), 1), vec![(b"value1".to_vec(), vec![42])]);
+		assert!(page(None, 0).is_empty());
+
+		// the default implementation returns the same pages
+		let in_memory: InMemory<Blake2Hasher> = trie.pairs().into_iter().collect::<HashMap<_, _>>().into();
+		assert_eq!(in_memory.pairs_with_prefix_from(b"value", Some(&b"value1"[..]), 1).unwrap(), page(Some(&b"value1"[..]), 1));
+	}
+
 	#[test]
 	fn storage_root_is_non_default() {
 		assert!(test_trie().storage_root(::std::iter::empty()).0 != H256([0; 32]));
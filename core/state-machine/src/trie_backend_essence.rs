@@ -22,7 +22,7 @@ use std::ops::Deref;
 use std::sync::Arc;
 use hash_db::{self, Hasher};
 use heapsize::HeapSizeOf;
-use trie::{TrieDB, Trie, MemoryDB, DBValue, TrieError, default_child_trie_root, read_trie_value, read_child_trie_value, for_keys_in_child_trie};
+use trie::{TrieDB, Trie, MemoryDB, DBValue, TrieError, default_child_trie_root, read_trie_value, read_child_trie_value, for_keys_in_child_trie, diff_tries};
 use changes_trie::Storage as ChangesTrieStorage;
 
 /// Patricia trie-based storage trait.
@@ -105,8 +105,9 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> where H::Out:
 		}
 	}
 
-	/// Execute given closure for all keys starting with prefix.
-	pub fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], mut f: F) {
+	/// Execute given closure for all keys starting with prefix. Fails if some trie node is missing
+	/// or can't be decoded.
+	pub fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], mut f: F) -> Result<(), String> {
 		let mut read_overlay = MemoryDB::default();
 		let eph = Ephemeral {
 			storage: &self.storage,
@@ -132,9 +133,74 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> where H::Out:
 			Ok(())
 		};
 
-		if let Err(e) = iter() {
-			debug!(target: "trie", "Error while iterating by prefix: {}", e);
-		}
+		iter().map_err(|e| format!("Error while iterating by prefix: {}", e))
+	}
+
+	/// Execute given closure for all keys that have different values in this trie and in the
+	/// `other` trie, passing the values from both tries. Tries are compared node by node, so
+	/// the nodes shared by both tries aren't read.
+	pub fn diff<F>(&self, other: &Self, f: F) -> Result<(), String> where
+		F: FnMut(&[u8], Option<&[u8]>, Option<&[u8]>),
+	{
+		let mut read_overlay = MemoryDB::default();
+		let eph = Ephemeral {
+			storage: &self.storage,
+			overlay: &mut read_overlay,
+		};
+		let mut other_read_overlay = MemoryDB::default();
+		let other_eph = Ephemeral {
+			storage: &other.storage,
+			overlay: &mut other_read_overlay,
+		};
+
+		diff_tries::<H, F>(&eph, &self.root, &other_eph, &other.root, f)
+			.map_err(|e| format!("Error while comparing tries: {}", e))
+	}
+
+	/// Get at most `count` entries with keys starting with the prefix that follow the `start_key`,
+	/// in the order of the keys. The iteration starts at the `start_key`, so the entries before it
+	/// aren't read.
+	pub fn pairs_with_prefix_from(
+		&self,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: usize,
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, String> {
+		let mut read_overlay = MemoryDB::default();
+		let eph = Ephemeral {
+			storage: &self.storage,
+			overlay: &mut read_overlay,
+		};
+
+		let collect = || -> Result<_, Box<TrieError<H::Out>>> {
+			let trie = TrieDB::<H>::new(&eph, &self.root)?;
+			let mut iter = trie.iter()?;
+			iter.seek(match start_key {
+				Some(start_key) if start_key > prefix => start_key,
+				_ => prefix,
+			})?;
+
+			let mut pairs = Vec::new();
+			for x in iter {
+				if pairs.len() >= count {
+					break;
+				}
+
+				let (key, value) = x?;
+				if !key.starts_with(prefix) {
+					break;
+				}
+				if start_key.map_or(false, |start_key| &key[..] <= start_key) {
+					continue;
+				}
+
+				pairs.push((key, value.to_vec()));
+			}
+
+			Ok(pairs)
+		};
+
+		collect().map_err(|e| format!("Trie lookup error: {}", e))
 	}
 }
 
@@ -0,0 +1,214 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Block import simulation for the conformance testing. A canned sequence of blocks, including
+//! the invalid ones, is fed into the import pipeline of the client and every import is recorded
+//! as the event. The log of events only depends on the sequence, so the logs produced by the
+//! different client versions or backends are expected to match.
+
+use std::fmt;
+use client::{self, Client};
+use client::blockchain::tree_route;
+use consensus::{BlockImport, BlockOrigin, ImportBlock, ImportResult};
+use keyring::Keyring;
+use primitives::Blake2Hasher;
+use runtime::{self, Transfer};
+use runtime_primitives::generic::BlockId;
+use runtime_primitives::traits::Header as HeaderT;
+use backend;
+use ::BlockBuilderExt;
+use ::TestClient;
+
+/// Which parts of the blocks are imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+	/// Headers along with the bodies, as the full client imports them.
+	Full,
+	/// Headers only, as the light client imports them.
+	HeadersOnly,
+}
+
+/// Reason the block has been rejected for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+	/// The block is already in the import queue.
+	AlreadyQueued,
+	/// The block is already in the chain.
+	AlreadyInChain,
+	/// The block or its parent is known to be bad.
+	KnownBad,
+	/// The parent of the block is not in the chain.
+	UnknownParent,
+	/// The import has failed, e.g. the execution of the block has failed. The error itself is
+	/// not recorded, since it differs between the client versions.
+	Failed,
+}
+
+/// Outcome of the import of the single block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportEvent {
+	/// The block has been imported. `best` is true if the block has become the best block on
+	/// top of the previous best block.
+	Accept {
+		/// Number of the block.
+		number: u64,
+		/// Hash of the block.
+		hash: runtime::Hash,
+		/// Whether the block is the new best block.
+		best: bool,
+	},
+	/// The block has been imported and has become the best block of another fork.
+	Reorg {
+		/// Number of the block.
+		number: u64,
+		/// Hash of the block.
+		hash: runtime::Hash,
+		/// Number of the common ancestor of the previous and the new best blocks.
+		common: u64,
+		/// Hashes of the blocks that have been retracted from the best chain.
+		retracted: Vec<runtime::Hash>,
+	},
+	/// The block has been rejected.
+	Reject {
+		/// Number of the block.
+		number: u64,
+		/// Hash of the block.
+		hash: runtime::Hash,
+		/// Reason the block has been rejected for.
+		reason: RejectReason,
+	},
+}
+
+impl fmt::Display for ImportEvent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ImportEvent::Accept { number, ref hash, best } =>
+				write!(f, "accept #{} {:?}{}", number, hash, if best { " best" } else { "" }),
+			ImportEvent::Reorg { number, ref hash, common, ref retracted } =>
+				write!(f, "reorg #{} {:?} common #{} retracted {:?}", number, hash, common, retracted),
+			ImportEvent::Reject { number, ref hash, reason } =>
+				write!(f, "reject #{} {:?} {:?}", number, hash, reason),
+		}
+	}
+}
+
+/// Format the events as the log, one event per line.
+pub fn event_log(events: &[ImportEvent]) -> String {
+	events.iter().map(|event| format!("{}\n", event)).collect()
+}
+
+/// Canned sequence of blocks on top of the genesis block of the test client:
+///
+/// G -> A1 -> A2
+///      A1 -> B2 -> B3 -> B4
+///                  B3 -> C4 (invalid state root)
+///      X  -> D3 (unknown parent)
+///
+/// The blocks are ordered so that B3 causes the reorg, A2 is imported twice and B4 follows the
+/// rejected C4 and D3.
+pub fn canned_blocks() -> Vec<runtime::Block> {
+	let client = ::new();
+	let build_on = |parent: runtime::Hash, transfer: Option<(u64, u64)>| {
+		let mut builder = client.new_block_at(&BlockId::Hash(parent))
+			.expect("parent block is imported; qed");
+		// the transfer makes the block different from the siblings
+		if let Some((amount, nonce)) = transfer {
+			builder.push_transfer(Transfer {
+				from: Keyring::Alice.to_raw_public().into(),
+				to: Keyring::Ferdie.to_raw_public().into(),
+				amount,
+				nonce,
+			}).expect("transfer is valid; qed");
+		}
+		let block = builder.bake().expect("block is valid; qed");
+		client.justify_and_import(BlockOrigin::Own, block.clone()).expect("block is valid; qed");
+		block
+	};
+
+	let a1 = build_on(client.genesis_hash(), None);
+	let a2 = build_on(a1.header.hash(), None);
+	let b2 = build_on(a1.header.hash(), Some((41, 0)));
+	let b3 = build_on(b2.header.hash(), None);
+	let b4 = build_on(b3.header.hash(), None);
+	let mut c4 = build_on(b3.header.hash(), Some((1, 1)));
+	c4.header.state_root = Default::default();
+	let mut d3 = b3.clone();
+	d3.header.parent_hash = [1; 32].into();
+
+	vec![a1, a2.clone(), b2, b3, a2, c4, d3, b4]
+}
+
+/// Import the blocks in the given order and record the outcome of every import.
+pub fn simulate_import<B, E>(
+	client: &Client<B, E, runtime::Block>,
+	blocks: &[runtime::Block],
+	mode: ImportMode,
+) -> client::error::Result<Vec<ImportEvent>> where
+	B: backend::Backend<runtime::Block, Blake2Hasher>,
+	E: client::CallExecutor<runtime::Block, Blake2Hasher>,
+	Client<B, E, runtime::Block>: BlockImport<runtime::Block, Error=client::error::Error>,
+{
+	let mut events = Vec::with_capacity(blocks.len());
+	for block in blocks {
+		let number = *block.header.number();
+		let hash = block.header.hash();
+		let best_before = client.info()?.chain.best_hash;
+		let import = ImportBlock {
+			origin: BlockOrigin::NetworkBroadcast,
+			header: block.header.clone(),
+			external_justification: vec![],
+			post_runtime_digests: vec![],
+			body: match mode {
+				ImportMode::Full => Some(block.extrinsics.clone()),
+				ImportMode::HeadersOnly => None,
+			},
+			finalized: false,
+			auxiliary: Vec::new(),
+		};
+
+		let reason = match client.import_block(import, None) {
+			Ok(ImportResult::Queued) => None,
+			Ok(ImportResult::AlreadyQueued) => Some(RejectReason::AlreadyQueued),
+			Ok(ImportResult::AlreadyInChain) => Some(RejectReason::AlreadyInChain),
+			Ok(ImportResult::KnownBad) => Some(RejectReason::KnownBad),
+			Ok(ImportResult::UnknownParent) => Some(RejectReason::UnknownParent),
+			Err(_) => Some(RejectReason::Failed),
+		};
+		if let Some(reason) = reason {
+			events.push(ImportEvent::Reject { number, hash, reason });
+			continue;
+		}
+
+		let best = client.info()?.chain.best_hash;
+		if best != hash || block.header.parent_hash == best_before {
+			events.push(ImportEvent::Accept { number, hash, best: best == hash });
+			continue;
+		}
+
+		let route = tree_route(
+			client.backend().blockchain(),
+			BlockId::Hash(best_before),
+			BlockId::Hash(hash),
+		)?;
+		events.push(ImportEvent::Reorg {
+			number,
+			hash,
+			common: route.common_block().number,
+			retracted: route.retracted().iter().map(|entry| entry.hash).collect(),
+		});
+	}
+	Ok(events)
+}
@@ -31,6 +31,7 @@ pub extern crate substrate_test_runtime as runtime;
 pub extern crate substrate_consensus_common as consensus;
 
 pub mod client_ext;
+pub mod import_simulation;
 pub mod trait_tests;
 mod block_builder_ext;
 
@@ -66,6 +67,19 @@ pub type Executor = client::LocalCallExecutor<
 	executor::NativeExecutor<LocalExecutor>,
 >;
 
+/// Test light client blockchain storage.
+pub type LightStorage = client::in_mem::Blockchain<runtime::Block>;
+
+/// Test light client backend.
+pub type LightBackend<F> = client::light::backend::Backend<LightStorage, F>;
+
+/// Test light client executor.
+pub type LightExecutor<F> = client::light::call_executor::RemoteCallExecutor<
+	client::light::blockchain::Blockchain<LightStorage, F>,
+	F,
+	Blake2Hasher,
+>;
+
 /// Creates new client instance used for tests.
 pub fn new() -> client::Client<Backend, Executor, runtime::Block> {
 	new_with_backend(Arc::new(Backend::new()), false)
@@ -89,6 +103,33 @@ pub fn new_with_backend<B>(
 	client::new_with_backend(backend, executor, genesis_storage(support_changes_trie)).unwrap()
 }
 
+/// Creates new light client instance used for tests, fetching the remote data with given fetcher.
+pub fn new_light<F>(fetcher: Arc<F>) -> client::Client<LightBackend<F>, LightExecutor<F>, runtime::Block>
+	where
+		F: client::light::fetcher::Fetcher<runtime::Block>,
+{
+	let blockchain = client::light::new_light_blockchain(LightStorage::new());
+	let backend = client::light::new_light_backend(blockchain, fetcher.clone(), 0, None).unwrap();
+	let client = client::light::new_light(backend, fetcher, genesis_storage(false)).unwrap();
+	client.set_justification_verifier(Box::new(AcceptAllJustifications));
+	client
+}
+
+/// Justification verifier of the test light client: the test blocks aren't sealed, so every
+/// justification is accepted unless the test registers its own verifier.
+struct AcceptAllJustifications;
+
+impl client::JustificationVerifier<runtime::Block> for AcceptAllJustifications {
+	fn verify_justification(
+		&self,
+		_header: &runtime::Header,
+		_justification: &runtime_primitives::Justification,
+		_authorities: &[primitives::AuthorityId],
+	) -> client::error::Result<()> {
+		Ok(())
+	}
+}
+
 fn genesis_config(support_changes_trie: bool) -> GenesisConfig {
 	GenesisConfig::new(support_changes_trie, vec![
 		Keyring::Alice.to_raw_public().into(),
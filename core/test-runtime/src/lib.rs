@@ -53,7 +53,7 @@ pub mod system;
 use rstd::prelude::*;
 use codec::{Encode, Decode};
 
-use runtime_api::runtime::*;
+use runtime_api::{runtime::*, id::TAGGED_TRANSACTION_QUEUE};
 use runtime_primitives::traits::{BlindCheckable, BlakeTwo256, Block as BlockT, Extrinsic as ExtrinsicT};
 use runtime_primitives::{ApplyResult, Ed25519Signature, transaction_validity::TransactionValidity};
 use runtime_version::RuntimeVersion;
@@ -69,7 +69,9 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	authoring_version: 1,
 	spec_version: 1,
 	impl_version: 1,
-	apis: apis_vec!([]),
+	apis: apis_vec!([
+		(TAGGED_TRANSACTION_QUEUE, 2),
+	]),
 };
 
 fn version() -> RuntimeVersion {
@@ -93,6 +93,8 @@ pub struct Transaction<Hash, Extrinsic> {
 	pub requires: Vec<Tag>,
 	/// Tags that this transaction provides.
 	pub provides: Vec<Tag>,
+	/// Should that transaction be propagated to other peers.
+	pub propagate: bool,
 }
 
 /// Transaction pool.
@@ -316,6 +318,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![],
 			provides: vec![vec![1]],
+			propagate: true,
 		}).unwrap();
 
 		// then
@@ -336,6 +339,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![],
 			provides: vec![vec![1]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![1u8],
@@ -344,6 +348,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![],
 			provides: vec![vec![1]],
+			propagate: true,
 		}).unwrap_err();
 
 		// then
@@ -365,6 +370,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
+			propagate: true,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 0);
 		assert_eq!(pool.ready.len(), 0);
@@ -375,6 +381,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![],
 			provides: vec![vec![0]],
+			propagate: true,
 		}).unwrap();
 
 		// then
@@ -395,6 +402,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -403,6 +411,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![2]],
 			provides: vec![],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![2u8],
@@ -411,6 +420,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![1]],
 			provides: vec![vec![3], vec![2]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![4u8],
@@ -419,6 +429,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![3], vec![4]],
 			provides: vec![],
+			propagate: true,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 0);
 		assert_eq!(pool.ready.len(), 0);
@@ -430,6 +441,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![],
 			provides: vec![vec![0], vec![4]],
+			propagate: true,
 		}).unwrap();
 
 		// then
@@ -460,6 +472,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -468,6 +481,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![1]],
 			provides: vec![vec![2]],
+			propagate: true,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 0);
 		assert_eq!(pool.ready.len(), 0);
@@ -480,6 +494,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![2]],
 			provides: vec![vec![0]],
+			propagate: true,
 		}).unwrap();
 
 		// then
@@ -498,6 +513,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![],
 			provides: vec![vec![0]],
+			propagate: true,
 		}).unwrap();
 		let mut it = pool.ready().into_iter().map(|tx| tx.data[0]);
 		assert_eq!(it.next(), Some(4));
@@ -525,6 +541,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -533,6 +550,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![1]],
 			provides: vec![vec![2]],
+			propagate: true,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 0);
 		assert_eq!(pool.ready.len(), 0);
@@ -545,6 +563,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![2]],
 			provides: vec![vec![0]],
+			propagate: true,
 		}).unwrap();
 
 		// then
@@ -563,6 +582,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![],
 			provides: vec![vec![0]],
+			propagate: true,
 		}).unwrap_err();
 		let mut it = pool.ready().into_iter().map(|tx| tx.data[0]);
 		assert_eq!(it.next(), None);
@@ -585,6 +605,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![],
 			provides: vec![vec![0], vec![4]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![1u8],
@@ -593,6 +614,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -601,6 +623,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![2]],
 			provides: vec![],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![2u8],
@@ -609,6 +632,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![1]],
 			provides: vec![vec![3], vec![2]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![4u8],
@@ -617,6 +641,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![3], vec![4]],
 			provides: vec![],
+			propagate: true,
 		}).unwrap();
 		// future
 		pool.import(Transaction {
@@ -626,6 +651,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![11]],
 			provides: vec![],
+			propagate: true,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 5);
 		assert_eq!(pool.future.len(), 1);
@@ -651,6 +677,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![0]],
 			provides: vec![vec![100]],
+			propagate: true,
 		}).unwrap();
 		// ready
 		pool.import(Transaction {
@@ -660,6 +687,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![],
 			provides: vec![vec![1]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![2u8],
@@ -668,6 +696,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![2]],
 			provides: vec![vec![3]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -676,6 +705,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![1]],
 			provides: vec![vec![2]],
+			propagate: true,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![4u8],
@@ -684,6 +714,7 @@ mod tests {
 			valid_till: 64u64,
 			requires: vec![vec![3], vec![2]],
 			provides: vec![vec![4]],
+			propagate: true,
 		}).unwrap();
 
 		assert_eq!(pool.ready().count(), 4);
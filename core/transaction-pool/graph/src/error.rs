@@ -50,6 +50,16 @@ error_chain! {
 			description("Transaction was not imported because of detected cycle."),
 			display("Cycle Detected"),
 		}
+		/// The node is major syncing, so the transaction is queued until the sync is complete.
+		QueuedWhileSyncing {
+			description("Transaction is queued until the node is synced."),
+			display("Queued while syncing"),
+		}
+		/// The node is major syncing and the queue of the transactions submitted meanwhile is full.
+		SyncQueueFull {
+			description("Transaction queue of the syncing node is full."),
+			display("Sync queue full"),
+		}
 	}
 }
 
@@ -46,6 +46,7 @@ extern crate assert_matches;
 mod future;
 mod listener;
 mod pool;
+mod priority;
 mod ready;
 mod rotator;
 
@@ -55,4 +56,5 @@ pub mod watcher;
 
 pub use self::error::IntoPoolError;
 pub use self::base_pool::{Transaction, Status};
+pub use self::priority::{PriorityHistogram, PriorityStats};
 pub use self::pool::{Pool, Options, ChainApi, EventStream, ExtrinsicFor, BlockHash, ExHash, NumberFor, TransactionFor};
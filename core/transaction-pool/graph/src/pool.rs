@@ -15,26 +15,31 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet},
 	hash,
 	sync::Arc,
 	time,
 };
 
 use base_pool as base;
-use error;
+use error::{self, IntoPoolError};
 use listener::Listener;
+use priority::PriorityStats;
 use rotator::PoolRotator;
 use watcher::Watcher;
 
 use futures::sync::mpsc;
 use parking_lot::{Mutex, RwLock};
 use sr_primitives::{
+	codec::Encode,
 	generic::BlockId,
 	traits::{self, As},
 	transaction_validity::{TransactionValidity, TransactionTag as Tag},
 };
 
+/// Maximal number of extrinsics queued while the node is major syncing.
+pub const MAX_DEFERRED_EXTRINSICS: usize = 4096;
+
 /// Modification notification event stream type;
 pub type EventStream = mpsc::UnboundedReceiver<()>;
 
@@ -85,6 +90,24 @@ pub struct Pool<B: ChainApi> {
 	>>,
 	import_notification_sinks: Mutex<Vec<mpsc::UnboundedSender<()>>>,
 	rotator: PoolRotator<ExHash<B>>,
+	priority_stats: Mutex<PriorityStats>,
+	/// Extrinsics submitted while the node is major syncing. `None` if the node is not major syncing.
+	deferred: Mutex<Option<Deferred<ExHash<B>, ExtrinsicFor<B>>>>,
+}
+
+/// Extrinsics queued while the node is major syncing, with their propagation flags.
+struct Deferred<Hash, Ex> {
+	hashes: HashSet<Hash>,
+	queue: Vec<(Ex, bool)>,
+}
+
+impl<Hash: hash::Hash + Eq, Ex> Default for Deferred<Hash, Ex> {
+	fn default() -> Self {
+		Deferred {
+			hashes: HashSet::new(),
+			queue: Vec::new(),
+		}
+	}
 }
 
 impl<B: ChainApi> Pool<B> {
@@ -92,20 +115,72 @@ impl<B: ChainApi> Pool<B> {
 	/// Imports a bunch of unverified extrinsics to the pool
 	pub fn submit_at<T>(&self, at: &BlockId<B::Block>, xts: T) -> Result<Vec<Result<ExHash<B>, B::Error>>, B::Error> where
 		T: IntoIterator<Item=ExtrinsicFor<B>>
+	{
+		self.submit_or_defer(at, xts.into_iter().map(|xt| (xt, true)))
+	}
+
+	/// Imports one unverified extrinsic to the pool. The extrinsic is never propagated to other peers.
+	pub fn submit_local(&self, at: &BlockId<B::Block>, xt: ExtrinsicFor<B>) -> Result<ExHash<B>, B::Error> {
+		Ok(self.submit_or_defer(at, ::std::iter::once((xt, false)))?
+			.pop()
+			.expect("One extrinsic passed; one result returned; qed")?)
+	}
+
+	/// Imports extrinsics previously exported with `extrinsics`. Extrinsics paired with `false` are
+	/// never propagated to other peers.
+	pub fn submit_exported<T>(&self, at: &BlockId<B::Block>, xts: T) -> Result<Vec<Result<ExHash<B>, B::Error>>, B::Error> where
+		T: IntoIterator<Item=(ExtrinsicFor<B>, bool)>
+	{
+		self.submit_or_defer(at, xts)
+	}
+
+	// Imports extrinsics to the pool. While the node is major syncing the extrinsics can't be
+	// validated against the current state, so they're queued (failing with `QueuedWhileSyncing`)
+	// and imported once the sync is complete. At most `MAX_DEFERRED_EXTRINSICS` are queued, the
+	// rest fail with `SyncQueueFull`.
+	fn submit_or_defer<T>(&self, at: &BlockId<B::Block>, xts: T) -> Result<Vec<Result<ExHash<B>, B::Error>>, B::Error> where
+		T: IntoIterator<Item=(ExtrinsicFor<B>, bool)>
+	{
+		if let Some(ref mut deferred) = *self.deferred.lock() {
+			return Ok(xts
+				.into_iter()
+				.map(|(xt, allow_propagation)| {
+					let hash = self.api.hash(&xt);
+					if !deferred.hashes.contains(&hash) {
+						if deferred.queue.len() >= MAX_DEFERRED_EXTRINSICS {
+							debug!(target: "txpool", "Rejecting transaction {:?}, the sync queue is full", hash);
+							return Err(error::Error::from(error::ErrorKind::SyncQueueFull).into());
+						}
+						debug!(target: "txpool", "Queueing transaction {:?} until the node is synced", hash);
+						deferred.hashes.insert(hash);
+						deferred.queue.push((xt, allow_propagation));
+					}
+					Err(error::Error::from(error::ErrorKind::QueuedWhileSyncing).into())
+				})
+				.collect());
+		}
+
+		self.submit_at_with_propagation(at, xts)
+	}
+
+	// Imports extrinsics to the pool. Extrinsics paired with `false` are never propagated, regardless
+	// of what their validity says.
+	fn submit_at_with_propagation<T>(&self, at: &BlockId<B::Block>, xts: T) -> Result<Vec<Result<ExHash<B>, B::Error>>, B::Error> where
+		T: IntoIterator<Item=(ExtrinsicFor<B>, bool)>
 	{
 		let block_number = self.api.block_id_to_number(at)?
 			.ok_or_else(|| error::ErrorKind::Msg(format!("Invalid block id: {:?}", at)).into())?;
 
 		Ok(xts
 			.into_iter()
-			.map(|xt| -> Result<_, B::Error> {
+			.map(|(xt, allow_propagation)| -> Result<_, B::Error> {
 				let hash = self.api.hash(&xt);
 				if self.rotator.is_banned(&hash) {
 					bail!(error::Error::from(error::ErrorKind::TemporarilyBanned))
 				}
 
 				match self.api.validate_transaction(at, &xt)? {
-					TransactionValidity::Valid { priority, requires, provides, longevity } => {
+					TransactionValidity::Valid { priority, requires, provides, longevity, propagate } => {
 						Ok(base::Transaction {
 							data:  xt,
 							hash,
@@ -113,6 +188,7 @@ impl<B: ChainApi> Pool<B> {
 							requires,
 							provides,
 							valid_till: block_number.as_().saturating_add(longevity),
+							propagate: propagate && allow_propagation,
 						})
 					},
 					TransactionValidity::Invalid => {
@@ -131,6 +207,13 @@ impl<B: ChainApi> Pool<B> {
 					self.import_notification_sinks.lock().retain(|sink| sink.unbounded_send(()).is_ok());
 				}
 
+				if let base::Imported::Ready { ref removed, .. } = imported {
+					let mut stats = self.priority_stats.lock();
+					for tx in removed {
+						stats.dropped.note(tx.priority);
+					}
+				}
+
 				let mut listener = self.listener.write();
 				fire_events(&mut *listener, &imported);
 				Ok(imported.hash().clone())
@@ -147,8 +230,39 @@ impl<B: ChainApi> Pool<B> {
 	pub fn submit_and_watch(&self, at: &BlockId<B::Block>, xt: ExtrinsicFor<B>) -> Result<Watcher<ExHash<B>, BlockHash<B>>, B::Error> {
 		let hash = self.api.hash(&xt);
 		let watcher = self.listener.write().create_watcher(hash);
-		self.submit_one(at, xt)?;
-		Ok(watcher)
+		// the watcher of the queued extrinsic is notified once it's imported
+		match self.submit_one(at, xt).map_err(IntoPoolError::into_pool_error) {
+			Ok(_) | Err(Ok(error::Error(error::ErrorKind::QueuedWhileSyncing, _))) => Ok(watcher),
+			Err(Ok(e)) => Err(e.into()),
+			Err(Err(e)) => Err(e),
+		}
+	}
+
+	/// Notes whether the node is major syncing. Extrinsics submitted while it's syncing are
+	/// queued, and imported at given block once the sync is complete. Returns the import results
+	/// of the queued extrinsics.
+	pub fn set_major_syncing(&self, at: &BlockId<B::Block>, major_syncing: bool) -> Result<Vec<Result<ExHash<B>, B::Error>>, B::Error> {
+		let queued = {
+			let mut deferred = self.deferred.lock();
+			if major_syncing {
+				deferred.get_or_insert_with(Default::default);
+				return Ok(Vec::new());
+			}
+			match deferred.take() {
+				Some(deferred) => deferred.queue,
+				None => return Ok(Vec::new()),
+			}
+		};
+
+		if !queued.is_empty() {
+			debug!(target: "txpool", "Importing {} transactions queued while syncing", queued.len());
+		}
+		self.submit_at_with_propagation(at, queued)
+	}
+
+	/// Returns true if the extrinsics are queued because the node is major syncing.
+	pub fn is_major_syncing(&self) -> bool {
+		self.deferred.lock().is_some()
 	}
 
 	/// Prunes ready transactions that provide given list of tags.
@@ -165,15 +279,23 @@ impl<B: ChainApi> Pool<B> {
 		}
 		// try to re-submit pruned transactions since some of them might be still valid.
 		let hashes = status.pruned.iter().map(|tx| tx.hash.clone()).collect::<Vec<_>>();
-		let results = self.submit_at(at, status.pruned.into_iter().map(|tx| tx.data.clone()))?;
+		let priorities = status.pruned.iter().map(|tx| tx.priority).collect::<Vec<_>>();
+		let results = self.submit_at_with_propagation(at, status.pruned.into_iter().map(|tx| (tx.data.clone(), tx.propagate)))?;
 		// Fire mined event for transactions that became invalid.
-		let hashes = results.into_iter().enumerate().filter_map(|(idx, r)| match r.map_err(error::IntoPoolError::into_pool_error) {
+		let mined = results.into_iter().enumerate().filter_map(|(idx, r)| match r.map_err(error::IntoPoolError::into_pool_error) {
 			Err(Ok(err)) => match err.kind() {
-				error::ErrorKind::InvalidTransaction => Some(hashes[idx].clone()),
+				error::ErrorKind::InvalidTransaction => Some(idx),
 				_ => None,
 			},
 			_ => None,
-		});
+		}).collect::<Vec<_>>();
+		{
+			let mut stats = self.priority_stats.lock();
+			for idx in &mined {
+				stats.included.note(priorities[*idx]);
+			}
+		}
+		let hashes = mined.into_iter().map(|idx| hashes[idx].clone());
 		{
 			let header_hash = self.api.block_id_to_hash(at)?
 				.ok_or_else(|| error::ErrorKind::Msg(format!("Invalid block id: {:?}", at)).into())?;
@@ -187,6 +309,35 @@ impl<B: ChainApi> Pool<B> {
 		Ok(())
 	}
 
+	/// Re-validates all transactions of the pool at given block. Used to resync the pool when the
+	/// notifications of the imported blocks have been missed: the tags provided by the ready
+	/// transactions that are no longer valid, e.g. because they've been included in the missed
+	/// blocks, are pruned, and the future transactions that are no longer valid are removed.
+	pub fn revalidate(&self, at: &BlockId<B::Block>) -> Result<(), B::Error> {
+		let (ready, futures) = {
+			let pool = self.pool.read();
+			let ready = pool.ready().map(|tx| (tx.data.clone(), tx.provides.clone())).collect::<Vec<_>>();
+			let futures = pool.futures().map(|tx| (tx.hash.clone(), tx.data.clone())).collect::<Vec<_>>();
+			(ready, futures)
+		};
+
+		let mut tags = Vec::new();
+		for (xt, provides) in ready {
+			if let TransactionValidity::Invalid = self.api.validate_transaction(at, &xt)? {
+				tags.extend(provides);
+			}
+		}
+		let mut invalid = Vec::new();
+		for (hash, xt) in futures {
+			if let TransactionValidity::Invalid = self.api.validate_transaction(at, &xt)? {
+				invalid.push(hash);
+			}
+		}
+
+		self.remove_invalid(&invalid);
+		self.prune_tags(at, tags)
+	}
+
 	/// Removes stale transactions from the pool.
 	///
 	/// Stale transactions are transaction beyond their longevity period.
@@ -233,6 +384,8 @@ impl<B: ChainApi> Pool<B> {
 			pool: Default::default(),
 			import_notification_sinks: Default::default(),
 			rotator: Default::default(),
+			priority_stats: Default::default(),
+			deferred: Default::default(),
 		}
 	}
 
@@ -258,6 +411,12 @@ impl<B: ChainApi> Pool<B> {
 		self.rotator.ban(&time::Instant::now(), hashes);
 
 		let invalid = self.pool.write().remove_invalid(hashes);
+		{
+			let mut stats = self.priority_stats.lock();
+			for tx in &invalid {
+				stats.dropped.note(tx.priority);
+			}
+		}
 
 		let mut listener = self.listener.write();
 		for tx in &invalid {
@@ -272,22 +431,93 @@ impl<B: ChainApi> Pool<B> {
 		self.pool.read().ready()
 	}
 
+	/// Returns all extrinsics of the pool, paired with the flag telling if they may be propagated
+	/// to other peers. Ready extrinsics come first, ordered by priority, followed by the future ones.
+	pub fn extrinsics(&self) -> Vec<(ExtrinsicFor<B>, bool)> {
+		let pool = self.pool.read();
+		pool.ready()
+			.map(|tx| (tx.data.clone(), tx.propagate))
+			.chain(pool.futures().map(|tx| (tx.data.clone(), tx.propagate)))
+			.collect()
+	}
+
+	/// Returns the encoded size of the extrinsics in the pool, in bytes.
+	pub fn bytes(&self) -> usize {
+		pool_bytes(&*self.pool.read())
+	}
+
+	/// Drops the lowest priority transactions, along with the transactions depending on them,
+	/// until the encoded size of the pool is at most `bytes`. Future transactions are dropped
+	/// before the ready ones. Returns the dropped transactions.
+	pub fn shrink_to(&self, bytes: usize) -> Vec<TransactionFor<B>> {
+		let mut dropped = Vec::new();
+		{
+			let mut pool = self.pool.write();
+			let mut size = pool_bytes(&*pool);
+			if size <= bytes {
+				return dropped;
+			}
+
+			let mut futures: Vec<_> = pool.futures().map(|tx| (tx.priority, tx.hash.clone())).collect();
+			futures.sort_by_key(|&(priority, _)| priority);
+			// ready transactions are ordered by priority, starting from the highest one
+			let ready: Vec<_> = pool.ready().map(|tx| tx.hash.clone()).collect();
+			let candidates = futures.into_iter().map(|(_, hash)| hash).chain(ready.into_iter().rev());
+			for hash in candidates {
+				if size <= bytes {
+					break;
+				}
+				for tx in pool.remove_invalid(&[hash]) {
+					size = size.saturating_sub(tx.data.encode().len());
+					dropped.push(tx);
+				}
+			}
+		}
+
+		debug!(target: "txpool", "Dropped {} transactions to shrink the pool to {} bytes", dropped.len(), bytes);
+		{
+			let mut stats = self.priority_stats.lock();
+			for tx in &dropped {
+				stats.dropped.note(tx.priority);
+			}
+		}
+		let mut listener = self.listener.write();
+		for tx in &dropped {
+			listener.dropped(&tx.hash, None);
+		}
+		dropped
+	}
+
 	/// Returns pool status.
 	pub fn status(&self) -> base::Status {
 		self.pool.read().status()
 	}
 
+	/// Returns priorities of transactions included in blocks and dropped from the pool so far.
+	pub fn priority_stats(&self) -> PriorityStats {
+		self.priority_stats.lock().clone()
+	}
+
 	/// Returns transaction hash
 	pub fn hash_of(&self, xt: &ExtrinsicFor<B>) -> ExHash<B> {
 		self.api.hash(xt)
 	}
 }
 
+fn pool_bytes<H, Ex>(pool: &base::BasePool<H, Ex>) -> usize where
+	H: hash::Hash + traits::Member,
+	Ex: Encode + ::std::fmt::Debug,
+{
+	pool.ready().map(|tx| tx.data.encode().len())
+		.chain(pool.futures().map(|tx| tx.data.encode().len()))
+		.sum()
+}
+
 fn fire_events<H, H2, Ex>(
 	listener: &mut Listener<H, H2>,
 	imported: &base::Imported<H, Ex>,
 ) where
-	H: hash::Hash + Eq + traits::Member,
+	H: hash::Hash + traits::Member,
 	H2: Clone,
 {
 	match *imported {
@@ -335,6 +565,7 @@ mod tests {
 					priority: 4,
 					requires: if nonce > block_number { vec![vec![nonce as u8 - 1]] } else { vec![] },
 					provides: vec![vec![nonce as u8]],
+					propagate: true,
 					longevity: 3,
 				})
 			}
@@ -391,6 +622,189 @@ mod tests {
 		assert_eq!(pool.ready().map(|v| v.hash).collect::<Vec<_>>(), vec![hash]);
 	}
 
+	#[test]
+	fn should_not_propagate_local_transactions() {
+		// given
+		let pool = pool();
+
+		// when
+		let local = pool.submit_local(&BlockId::Number(0), uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 0,
+		})).unwrap();
+		let external = pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 1,
+		})).unwrap();
+
+		// then
+		let propagate = pool.ready().map(|v| (v.hash, v.propagate)).collect::<Vec<_>>();
+		assert_eq!(propagate, vec![(local, false), (external, true)]);
+	}
+
+	#[test]
+	fn should_remove_transactions_invalid_after_revalidation() {
+		// given
+		let pool = pool();
+		for nonce in &[0, 1, 3] {
+			pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+				from: 1.into(),
+				to: 2.into(),
+				amount: 5,
+				nonce: *nonce,
+			})).unwrap();
+		}
+		assert_eq!(pool.status().ready, 2);
+		assert_eq!(pool.status().future, 1);
+
+		// when
+		pool.revalidate(&BlockId::Number(2)).unwrap();
+
+		// then
+		assert_eq!(pool.status().ready, 0);
+		assert_eq!(pool.status().future, 1);
+
+		// when
+		pool.revalidate(&BlockId::Number(4)).unwrap();
+
+		// then
+		assert_eq!(pool.status().future, 0);
+	}
+
+	#[test]
+	fn should_drop_lowest_priority_transactions_when_shrunk() {
+		// given
+		let pool = pool();
+		for nonce in &[0, 1, 3] {
+			pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+				from: 1.into(),
+				to: 2.into(),
+				amount: 5,
+				nonce: *nonce,
+			})).unwrap();
+		}
+		let bytes = pool.bytes();
+		let tx_bytes = bytes / 3;
+		assert_eq!(pool.shrink_to(bytes).len(), 0);
+
+		// when
+		let dropped = pool.shrink_to(2 * tx_bytes);
+
+		// then
+		assert_eq!(dropped.len(), 1);
+		assert_eq!(pool.status().ready, 2);
+		assert_eq!(pool.status().future, 0);
+
+		// when
+		pool.shrink_to(tx_bytes);
+
+		// then
+		assert!(pool.bytes() <= tx_bytes);
+		assert_eq!(pool.status().future, 0);
+	}
+
+	#[test]
+	fn should_export_and_reimport_extrinsics() {
+		// given
+		let pool = pool();
+		let local = uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 0,
+		});
+		let future = uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 2,
+		});
+		pool.submit_local(&BlockId::Number(0), local.clone()).unwrap();
+		pool.submit_one(&BlockId::Number(0), future.clone()).unwrap();
+
+		// when
+		let exported = pool.extrinsics();
+		let restored = self::pool();
+		let imported = restored.submit_exported(&BlockId::Number(0), exported.clone()).unwrap();
+
+		// then
+		assert_eq!(exported, vec![(local, false), (future, true)]);
+		assert!(imported.iter().all(Result::is_ok));
+		assert_eq!(restored.status().ready, 1);
+		assert_eq!(restored.status().future, 1);
+		assert_eq!(restored.extrinsics(), exported);
+	}
+
+	#[test]
+	fn should_queue_transactions_while_major_syncing() {
+		// given
+		let pool = pool();
+		pool.set_major_syncing(&BlockId::Number(0), true).unwrap();
+		let xt = uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 1,
+		});
+
+		// when
+		let submitted = pool.submit_local(&BlockId::Number(0), xt.clone());
+		let resubmitted = pool.submit_one(&BlockId::Number(0), xt.clone());
+
+		// then
+		for result in vec![submitted, resubmitted] {
+			match result.unwrap_err().kind() {
+				error::ErrorKind::QueuedWhileSyncing => (),
+				e => panic!("Unexpected error: {:?}", e),
+			}
+		}
+		assert_eq!(pool.status().ready, 0);
+
+		// when
+		let imported = pool.set_major_syncing(&BlockId::Number(1), false).unwrap();
+
+		// then
+		assert_eq!(imported.len(), 1);
+		assert!(!pool.is_major_syncing());
+		let propagate = pool.ready().map(|v| (v.hash, v.propagate)).collect::<Vec<_>>();
+		assert_eq!(propagate, vec![(*imported[0].as_ref().unwrap(), false)]);
+	}
+
+	#[test]
+	fn should_bound_transactions_queued_while_major_syncing() {
+		// given
+		let pool = pool();
+		pool.set_major_syncing(&BlockId::Number(0), true).unwrap();
+		let xt = |nonce| uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce,
+		});
+		for nonce in 0..MAX_DEFERRED_EXTRINSICS as u64 {
+			pool.submit_one(&BlockId::Number(0), xt(nonce)).unwrap_err();
+		}
+
+		// when
+		let queued = pool.submit_one(&BlockId::Number(0), xt(0));
+		let rejected = pool.submit_one(&BlockId::Number(0), xt(MAX_DEFERRED_EXTRINSICS as u64));
+
+		// then
+		match queued.unwrap_err().kind() {
+			error::ErrorKind::QueuedWhileSyncing => (),
+			e => panic!("Unexpected error: {:?}", e),
+		}
+		match rejected.unwrap_err().kind() {
+			error::ErrorKind::SyncQueueFull => (),
+			e => panic!("Unexpected error: {:?}", e),
+		}
+		assert_eq!(pool.deferred.lock().as_ref().unwrap().queue.len(), MAX_DEFERRED_EXTRINSICS);
+	}
+
 	#[test]
 	fn should_reject_if_temporarily_banned() {
 		// given
@@ -488,6 +902,33 @@ mod tests {
 		assert!(pool.rotator.is_banned(&hash3));
 	}
 
+	#[test]
+	fn should_note_priorities_of_included_and_dropped_transactions() {
+		// given
+		let pool = pool();
+		pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			from: 1.into(),
+			to: 2.into(),
+			amount: 5,
+			nonce: 0,
+		})).unwrap();
+		pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			from: 2.into(),
+			to: 1.into(),
+			amount: 5,
+			nonce: 3,
+		})).unwrap();
+
+		// when
+		pool.prune_tags(&BlockId::Number(2), vec![vec![0u8]]).unwrap();
+		pool.clear_stale(&BlockId::Number(10)).unwrap();
+
+		// then
+		let stats = pool.priority_stats();
+		assert_eq!(stats.included.ranges(), vec![(4, 1)]);
+		assert_eq!(stats.dropped.ranges(), vec![(4, 1)]);
+	}
+
 	mod listener {
 		use super::*;
 
@@ -516,6 +957,28 @@ mod tests {
 			assert_eq!(stream.next(), None);
 		}
 
+		#[test]
+		fn should_trigger_ready_for_transaction_queued_while_syncing() {
+			// given
+			let pool = pool();
+			pool.set_major_syncing(&BlockId::Number(0), true).unwrap();
+			let watcher = pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+				from: 1.into(),
+				to: 2.into(),
+				amount: 5,
+				nonce: 0,
+			})).unwrap();
+			assert_eq!(pool.status().ready, 0);
+
+			// when
+			pool.set_major_syncing(&BlockId::Number(0), false).unwrap();
+			assert_eq!(pool.status().ready, 1);
+
+			// then
+			let mut stream = watcher.into_stream().wait();
+			assert_eq!(stream.next(), Some(Ok(::watcher::Status::Ready)));
+		}
+
 		#[test]
 		fn should_trigger_future_and_ready_after_promoted() {
 			// given
@@ -0,0 +1,73 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Statistics of priorities of transactions leaving the pool.
+
+use std::collections::BTreeMap;
+
+use sr_primitives::transaction_validity::TransactionPriority as Priority;
+
+/// Number of transactions per priority range. Ranges grow in powers of two.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PriorityHistogram {
+	counts: BTreeMap<u32, u64>,
+}
+
+impl PriorityHistogram {
+	/// Note transaction with given priority.
+	pub fn note(&mut self, priority: Priority) {
+		*self.counts.entry(64 - priority.leading_zeros()).or_insert(0) += 1;
+	}
+
+	/// Returns pairs of the lowest priority of the range and the number of transactions in it,
+	/// ordered by priority.
+	pub fn ranges(&self) -> Vec<(Priority, u64)> {
+		self.counts.iter()
+			.map(|(&range, &count)| (if range == 0 { 0 } else { 1 << (range - 1) }, count))
+			.collect()
+	}
+
+	/// Total number of noted transactions.
+	pub fn total(&self) -> u64 {
+		self.counts.values().sum()
+	}
+}
+
+/// Priorities of transactions that have been included in blocks and of those that have been
+/// dropped from the pool without inclusion.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PriorityStats {
+	/// Transactions included in blocks.
+	pub included: PriorityHistogram,
+	/// Transactions replaced by other transactions or removed as invalid.
+	pub dropped: PriorityHistogram,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn should_group_priorities_in_power_of_two_ranges() {
+		let mut histogram = PriorityHistogram::default();
+		for priority in &[0, 1, 2, 3, 4, 7, 8, u64::max_value()] {
+			histogram.note(*priority);
+		}
+
+		assert_eq!(histogram.ranges(), vec![(0, 1), (1, 1), (2, 2), (4, 2), (8, 1), (1 << 63, 1)]);
+		assert_eq!(histogram.total(), 8);
+	}
+}
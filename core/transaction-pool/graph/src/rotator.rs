@@ -118,6 +118,7 @@ mod tests {
 			valid_till: 1,
 			requires: vec![],
 			provides: vec![],
+			propagate: true,
 		};
 
 		(hash, tx)
@@ -182,6 +183,7 @@ mod tests {
 				valid_till,
 				requires: vec![],
 				provides: vec![],
+				propagate: true,
 			}
 		}
 
@@ -41,5 +41,6 @@ mod api;
 mod tests;
 
 pub mod error;
+pub mod persistence;
 
 pub use api::ChainApi;
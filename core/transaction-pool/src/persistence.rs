@@ -0,0 +1,84 @@
+// Copyright 2018 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Persistence of the pool extrinsics across restarts.
+
+use client;
+use parity_codec::{Decode, Encode};
+use sr_primitives::traits;
+use substrate_primitives::{Blake2Hasher, H256};
+use txpool::{self, ExtrinsicFor, Pool};
+
+use error;
+
+/// Aux db key of the saved pool extrinsics.
+pub const POOL_EXTRINSICS_KEY: &[u8] = b"transaction_pool_extrinsics";
+
+/// Save all extrinsics of the pool, both ready and future, to the aux db of the backend, replacing
+/// previously saved ones. Returns the number of saved extrinsics.
+pub fn save<A, B>(pool: &Pool<A>, backend: &B) -> error::Result<usize> where
+	A: txpool::ChainApi,
+	A::Block: traits::Block<Hash=H256>,
+	B: client::backend::Backend<A::Block, Blake2Hasher>,
+{
+	let extrinsics = pool.extrinsics();
+	let encoded = extrinsics.encode();
+	backend.insert_aux(&[(POOL_EXTRINSICS_KEY, &encoded[..])], &[])?;
+	Ok(extrinsics.len())
+}
+
+/// Load the extrinsics saved with `save`, paired with the flag telling if they may be propagated to
+/// other peers. The extrinsics aren't validated; they're meant to be imported back with
+/// `Pool::submit_exported`, which drops the ones that are no longer valid.
+pub fn load<A, B>(backend: &B) -> error::Result<Vec<(ExtrinsicFor<A>, bool)>> where
+	A: txpool::ChainApi,
+	A::Block: traits::Block<Hash=H256>,
+	B: client::backend::Backend<A::Block, Blake2Hasher>,
+{
+	match backend.get_aux(POOL_EXTRINSICS_KEY)? {
+		Some(encoded) => Decode::decode(&mut &encoded[..])
+			.ok_or_else(|| client::error::ErrorKind::Backend("Error decoding saved pool extrinsics".into()).into()),
+		None => Ok(Vec::new()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use keyring::Keyring::Alice;
+	use test_client;
+	use tests::{pool, uxt, TestApi};
+	use sr_primitives::generic::BlockId;
+
+	#[test]
+	fn saved_extrinsics_are_loaded() {
+		let client = test_client::new();
+		let pool = pool();
+		pool.submit_local(&BlockId::number(0), uxt(Alice, 209)).unwrap();
+		pool.submit_one(&BlockId::number(0), uxt(Alice, 211)).unwrap();
+
+		assert!(load::<TestApi, _>(&**client.backend()).unwrap().is_empty());
+		assert_eq!(save(&pool, &**client.backend()).unwrap(), 2);
+
+		let loaded = load::<TestApi, _>(&**client.backend()).unwrap();
+		assert_eq!(loaded, vec![(uxt(Alice, 209), false), (uxt(Alice, 211), true)]);
+
+		let restored = self::pool();
+		restored.submit_exported(&BlockId::number(0), loaded).unwrap();
+		assert_eq!(restored.status().ready, 1);
+		assert_eq!(restored.status().future, 1);
+	}
+}
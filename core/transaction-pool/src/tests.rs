@@ -27,7 +27,7 @@ use sr_primitives::{
 	transaction_validity::TransactionValidity,
 };
 
-struct TestApi;
+pub struct TestApi;
 
 impl TestApi {
 	fn default() -> Self {
@@ -53,7 +53,8 @@ impl txpool::ChainApi for TestApi {
 			priority: 1,
 			requires,
 			provides,
-			longevity: 64
+			longevity: 64,
+			propagate: true,
 		})
 	}
 
@@ -85,7 +86,7 @@ fn number_of(at: &BlockId<Block>) -> u64 {
 	}
 }
 
-fn uxt(who: Keyring, nonce: Index) -> Extrinsic {
+pub fn uxt(who: Keyring, nonce: Index) -> Extrinsic {
 	let transfer = Transfer {
 		from: who.to_raw_public().into(),
 		to: AccountId::default(),
@@ -99,7 +100,7 @@ fn uxt(who: Keyring, nonce: Index) -> Extrinsic {
 	}
 }
 
-fn pool() -> Pool<TestApi> {
+pub fn pool() -> Pool<TestApi> {
 	Pool::new(Default::default(), TestApi::default())
 }
 
@@ -39,6 +39,7 @@ mod node_header;
 mod node_codec;
 mod trie_stream;
 
+use std::collections::HashSet;
 use hash_db::Hasher;
 /// Our `NodeCodec`-specific error.
 pub use error::Error;
@@ -47,7 +48,7 @@ pub use trie_stream::TrieStream;
 /// The Substrate format implementation of `NodeCodec`.
 pub use node_codec::NodeCodec;
 /// Various re-exports from the `trie-db` crate.
-pub use trie_db::{Trie, TrieMut, DBValue, Recorder, Query};
+pub use trie_db::{Trie, TrieMut, DBValue, Recorder, Query, TrieIterator};
 
 /// As in `trie_db`, but less generic, error type for the crate.
 pub type TrieError<H> = trie_db::TrieError<H, Error>;
@@ -65,6 +66,8 @@ pub type TrieDB<'a, H> = trie_db::TrieDB<'a, H, NodeCodec<H>>;
 pub type TrieDBMut<'a, H> = trie_db::TrieDBMut<'a, H, NodeCodec<H>>;
 /// Querying interface, as in `trie_db` but less generic.
 pub type Lookup<'a, H, Q> = trie_db::Lookup<'a, H, NodeCodec<H>, Q>;
+/// Seekable iterator over the trie entries, as in `trie_db` but less generic.
+pub type TrieDBIterator<'a, H> = trie_db::TrieDBIterator<'a, H, NodeCodec<H>>;
 
 /// Determine a trie root given its ordered contents, closed form.
 pub fn trie_root<H: Hasher, I, A, B>(input: I) -> H::Out where
@@ -123,10 +126,349 @@ where
 {
 	trie_root::<H, _, _, _>(input
 		.enumerate()
-		.map(|(i, v)| (codec::Encode::encode(&codec::Compact(i as u32)), v))
+		.map(|(i, v)| (ordered_trie_key(i as u32), v))
 	)
 }
 
+/// Key of the `index`-th item in the trie formed by `ordered_trie_root`.
+pub fn ordered_trie_key(index: u32) -> Vec<u8> {
+	codec::Encode::encode(&codec::Compact(index))
+}
+
+/// Generate proof of the `index`-th item of the trie formed by `ordered_trie_root`.
+/// Returns `None` if there's no item with given index.
+pub fn ordered_trie_proof<H: Hasher, I, A>(input: I, index: u32) -> Result<Option<Vec<Vec<u8>>>, Box<TrieError<H::Out>>>
+where
+	I: IntoIterator<Item = A>,
+	A: AsRef<[u8]>,
+{
+	let mut db = MemoryDB::default();
+	let mut root = H::Out::default();
+	{
+		let mut trie = TrieDBMut::<H>::new(&mut db, &mut root);
+		for (i, value) in input.into_iter().enumerate() {
+			trie.insert(&ordered_trie_key(i as u32), value.as_ref())?;
+		}
+	}
+
+	let mut recorder = Recorder::new();
+	if read_trie_value_with::<H, _>(&db, &root, &ordered_trie_key(index), &mut recorder)?.is_none() {
+		return Ok(None);
+	}
+
+	Ok(Some(recorder.drain().into_iter().map(|n| n.data.to_vec()).collect()))
+}
+
+/// Check proof, generated by `ordered_trie_proof`, returning the `index`-th item of the trie
+/// with given root. Fails if the proof is incomplete.
+pub fn check_ordered_trie_proof<H: Hasher>(root: &H::Out, index: u32, proof: Vec<Vec<u8>>) -> Result<Option<Vec<u8>>, Box<TrieError<H::Out>>> {
+	let mut db = MemoryDB::<H>::default();
+	for item in proof {
+		hash_db::HashDB::insert(&mut db, &item);
+	}
+
+	read_trie_value::<H>(&db, root, &ordered_trie_key(index))
+}
+
+/// Create iterator over the trie entries, starting from the first entry with the key that is
+/// greater than or equal to the `start_key`.
+pub fn trie_iter_from<'a, H: Hasher>(trie: &'a TrieDB<'a, H>, start_key: &[u8]) -> Result<TrieDBIterator<'a, H>, Box<TrieError<H::Out>>> {
+	let mut iter = TrieDBIterator::new(trie)?;
+	iter.seek(start_key)?;
+	Ok(iter)
+}
+
+/// Generate proof of the consecutive trie entries, starting from the first entry with the key that is
+/// greater than or equal to the `start_key`. Entries are proved until their total size (keys and values)
+/// exceeds the `size_limit`, though the first entry is always proved.
+pub fn prove_range<H: Hasher>(db: &HashDB<H>, root: &H::Out, start_key: &[u8], size_limit: usize) -> Result<Vec<Vec<u8>>, Box<TrieError<H::Out>>> {
+	let trie = TrieDB::<H>::new(db, root)?;
+
+	// there's currently no API like iter_with()
+	// => lookup the `start_key` to record nodes visited when seeking
+	// and then lookup every iterated key using get_with
+	let mut recorder = Recorder::new();
+	trie.get_with(start_key, &mut recorder)?;
+
+	let mut size = 0;
+	for entry in trie_iter_from(&trie, start_key)? {
+		let (key, value) = entry?;
+		let entry_size = key.len() + value.len();
+		if size != 0 && size + entry_size > size_limit {
+			break;
+		}
+
+		size += entry_size;
+		trie.get_with(&key, &mut recorder)?;
+	}
+
+	let mut proved_nodes = HashSet::new();
+	Ok(recorder.drain().into_iter()
+		.filter(|record| proved_nodes.insert(record.hash))
+		.map(|record| record.data.to_vec())
+		.collect())
+}
+
+/// Check proof, generated by `prove_range`, returning the consecutive entries of the trie with given
+/// root, starting from the first entry with the key that is greater than or equal to the `start_key`.
+/// The flag is true if there are no entries after the returned ones. Fails if the proof doesn't
+/// allow to find the first entry.
+pub fn check_range_proof<H: Hasher>(root: &H::Out, start_key: &[u8], proof: Vec<Vec<u8>>) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Box<TrieError<H::Out>>> {
+	let mut db = MemoryDB::<H>::default();
+	for item in proof {
+		hash_db::HashDB::insert(&mut db, &item);
+	}
+
+	let trie = TrieDB::<H>::new(&db, root)?;
+	let mut entries = Vec::new();
+	for entry in trie_iter_from(&trie, start_key)? {
+		match entry {
+			Ok((key, value)) => entries.push((key, value.to_vec())),
+			Err(error) => {
+				// proof ends here => all previous entries are proved
+				let is_incomplete = match *error {
+					trie_db::TrieError::IncompleteDatabase(_) => true,
+					_ => false,
+				};
+				if is_incomplete && !entries.is_empty() {
+					return Ok((entries, false));
+				}
+				return Err(error);
+			},
+		}
+	}
+
+	Ok((entries, true))
+}
+
+/// Generate proof of all trie entries with keys starting with the `prefix`.
+pub fn prove_prefix<H: Hasher>(db: &HashDB<H>, root: &H::Out, prefix: &[u8]) -> Result<Vec<Vec<u8>>, Box<TrieError<H::Out>>> {
+	let trie = TrieDB::<H>::new(db, root)?;
+
+	// lookup the `prefix`, every key starting with it and the first key after them, so that
+	// the proof shows there are no other keys with the `prefix`
+	let mut recorder = Recorder::new();
+	trie.get_with(prefix, &mut recorder)?;
+	for entry in trie_iter_from(&trie, prefix)? {
+		let (key, _) = entry?;
+		trie.get_with(&key, &mut recorder)?;
+		if !key.starts_with(prefix) {
+			break;
+		}
+	}
+
+	let mut proved_nodes = HashSet::new();
+	Ok(recorder.drain().into_iter()
+		.filter(|record| proved_nodes.insert(record.hash))
+		.map(|record| record.data.to_vec())
+		.collect())
+}
+
+/// Check proof, generated by `prove_prefix`, returning all entries of the trie with given root
+/// that have keys starting with the `prefix`. Fails if the proof is incomplete.
+pub fn check_prefix_proof<H: Hasher>(root: &H::Out, prefix: &[u8], proof: Vec<Vec<u8>>) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Box<TrieError<H::Out>>> {
+	let mut db = MemoryDB::<H>::default();
+	for item in proof {
+		hash_db::HashDB::insert(&mut db, &item);
+	}
+
+	let trie = TrieDB::<H>::new(&db, root)?;
+	let mut entries = Vec::new();
+	for entry in trie_iter_from(&trie, prefix)? {
+		let (key, value) = entry?;
+		if !key.starts_with(prefix) {
+			break;
+		}
+
+		entries.push((key, value.to_vec()));
+	}
+
+	Ok(entries)
+}
+
+/// Generate proof of the page of trie entries with keys starting with the `prefix`. The page
+/// starts from the first such entry with the key that is greater than or equal to the `start_key`
+/// and contains at most `limit` entries. The entry following the page is proved too, so that the
+/// proof shows whether there are more entries with the `prefix`.
+pub fn prove_prefix_page<H: Hasher>(db: &HashDB<H>, root: &H::Out, prefix: &[u8], start_key: &[u8], limit: usize) -> Result<Vec<Vec<u8>>, Box<TrieError<H::Out>>> {
+	let trie = TrieDB::<H>::new(db, root)?;
+	let start_key = ::std::cmp::max(prefix, start_key);
+
+	let mut recorder = Recorder::new();
+	trie.get_with(start_key, &mut recorder)?;
+	for (index, entry) in trie_iter_from(&trie, start_key)?.enumerate() {
+		let (key, _) = entry?;
+		trie.get_with(&key, &mut recorder)?;
+		if index == limit || !key.starts_with(prefix) {
+			break;
+		}
+	}
+
+	let mut proved_nodes = HashSet::new();
+	Ok(recorder.drain().into_iter()
+		.filter(|record| proved_nodes.insert(record.hash))
+		.map(|record| record.data.to_vec())
+		.collect())
+}
+
+/// Check proof, generated by `prove_prefix_page`, returning the consecutive entries of the trie with
+/// given root that have keys starting with the `prefix`, starting from the first such entry with the
+/// key that is greater than or equal to the `start_key`. The flag is true if there are no entries
+/// with the `prefix` after the returned ones. Fails if the proof doesn't allow to find the first entry.
+pub fn check_prefix_page_proof<H: Hasher>(root: &H::Out, prefix: &[u8], start_key: &[u8], proof: Vec<Vec<u8>>) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Box<TrieError<H::Out>>> {
+	let mut db = MemoryDB::<H>::default();
+	for item in proof {
+		hash_db::HashDB::insert(&mut db, &item);
+	}
+
+	let trie = TrieDB::<H>::new(&db, root)?;
+	let start_key = ::std::cmp::max(prefix, start_key);
+	let mut entries = Vec::new();
+	for entry in trie_iter_from(&trie, start_key)? {
+		match entry {
+			Ok((key, value)) => {
+				if !key.starts_with(prefix) {
+					break;
+				}
+
+				entries.push((key, value.to_vec()));
+			},
+			Err(error) => {
+				// proof ends here => all previous entries are proved
+				let is_incomplete = match *error {
+					trie_db::TrieError::IncompleteDatabase(_) => true,
+					_ => false,
+				};
+				if is_incomplete && !entries.is_empty() {
+					return Ok((entries, false));
+				}
+				return Err(error);
+			},
+		}
+	}
+
+	Ok((entries, true))
+}
+
+/// Call `f` for every key that has different values in the tries with given roots, passing the
+/// values of the key in both tries. Tries are compared node by node, so the subtrees that are
+/// shared by both tries aren't read.
+pub fn diff_tries<H: Hasher, F>(
+	from_db: &HashDB<H>,
+	from_root: &H::Out,
+	to_db: &HashDB<H>,
+	to_root: &H::Out,
+	mut f: F,
+) -> Result<(), Box<TrieError<H::Out>>> where
+	F: FnMut(&[u8], Option<&[u8]>, Option<&[u8]>),
+{
+	let from = DiffNode::Reference(from_root.as_ref().to_vec());
+	let to = DiffNode::Reference(to_root.as_ref().to_vec());
+	diff_nodes::<H, F>(from_db, Some(from), to_db, Some(to), &mut Vec::new(), &mut f)
+}
+
+/// Subtree of the trie, rooted at some nibble of the key.
+#[derive(PartialEq)]
+enum DiffNode {
+	/// Node referenced by its hash or inlined into the parent node.
+	Reference(Vec<u8>),
+	/// Part of the leaf node that follows given nibbles.
+	Leaf(Vec<u8>, Vec<u8>),
+	/// Part of the extension node that follows given nibbles.
+	Extension(Vec<u8>, Vec<u8>),
+}
+
+fn diff_nodes<H: Hasher, F>(
+	from_db: &HashDB<H>,
+	from: Option<DiffNode>,
+	to_db: &HashDB<H>,
+	to: Option<DiffNode>,
+	nibbles: &mut Vec<u8>,
+	f: &mut F,
+) -> Result<(), Box<TrieError<H::Out>>> where
+	F: FnMut(&[u8], Option<&[u8]>, Option<&[u8]>),
+{
+	// equal references (and equal parts of nodes) mean equal subtrees
+	if from == to {
+		return Ok(());
+	}
+
+	let (from_value, mut from_children) = expand_diff_node::<H>(from_db, from)?;
+	let (to_value, mut to_children) = expand_diff_node::<H>(to_db, to)?;
+	if from_value != to_value {
+		let key: Vec<u8> = nibbles.chunks(2).map(|pair| (pair[0] << 4) | pair.get(1).cloned().unwrap_or(0)).collect();
+		f(&key, from_value.as_ref().map(|v| &v[..]), to_value.as_ref().map(|v| &v[..]));
+	}
+
+	for index in 0..16 {
+		let from_child = from_children[index].take();
+		let to_child = to_children[index].take();
+		if from_child.is_none() && to_child.is_none() {
+			continue;
+		}
+
+		nibbles.push(index as u8);
+		diff_nodes::<H, F>(from_db, from_child, to_db, to_child, nibbles, f)?;
+		nibbles.pop();
+	}
+
+	Ok(())
+}
+
+/// Get the value that is stored at the root of the subtree and the subtrees of its children.
+fn expand_diff_node<H: Hasher>(
+	db: &HashDB<H>,
+	node: Option<DiffNode>,
+) -> Result<(Option<Vec<u8>>, [Option<DiffNode>; 16]), Box<TrieError<H::Out>>> {
+	let mut children: [Option<DiffNode>; 16] = Default::default();
+	match node {
+		None => Ok((None, children)),
+		Some(DiffNode::Reference(reference)) => {
+			let data = match <NodeCodec<H> as trie_db::NodeCodec<H>>::try_decode_hash(&reference) {
+				Some(hash) => db.get(&hash).ok_or_else(|| Box::new(trie_db::TrieError::IncompleteDatabase(hash)))?.to_vec(),
+				None => reference,
+			};
+			let node = <NodeCodec<H> as trie_db::NodeCodec<H>>::decode(&data)
+				.map_err(|e| Box::new(trie_db::TrieError::DecoderError(H::hash(&data), e)))?;
+			let nibbles = |slice: &trie_db::NibbleSlice| (0..slice.len()).map(|i| slice.at(i)).collect::<Vec<_>>();
+			match node {
+				trie_db::node::Node::Empty => Ok((None, children)),
+				trie_db::node::Node::Leaf(partial, value) =>
+					expand_diff_node::<H>(db, Some(DiffNode::Leaf(nibbles(&partial), value.to_vec()))),
+				trie_db::node::Node::Extension(partial, child) =>
+					expand_diff_node::<H>(db, Some(DiffNode::Extension(nibbles(&partial), child.to_vec()))),
+				trie_db::node::Node::Branch(node_children, value) => {
+					for (index, child) in node_children.iter().enumerate() {
+						if let Some(child) = *child {
+							children[index] = Some(DiffNode::Reference(child.to_vec()));
+						}
+					}
+					Ok((value.map(|value| value.to_vec()), children))
+				},
+			}
+		},
+		Some(DiffNode::Leaf(partial, value)) => {
+			if partial.is_empty() {
+				return Ok((Some(value), children));
+			}
+
+			children[partial[0] as usize] = Some(DiffNode::Leaf(partial[1..].to_vec(), value));
+			Ok((None, children))
+		},
+		Some(DiffNode::Extension(partial, child)) => {
+			if partial.is_empty() {
+				return expand_diff_node::<H>(db, Some(DiffNode::Reference(child)));
+			}
+
+			children[partial[0] as usize] = Some(match partial.len() {
+				1 => DiffNode::Reference(child),
+				_ => DiffNode::Extension(partial[1..].to_vec(), child),
+			});
+			Ok((None, children))
+		},
+	}
+}
+
 /// Determine whether a child trie key is valid. `child_trie_root` and `child_delta_trie_root` can panic if invalid value is provided to them.
 pub fn is_child_trie_key_valid<H: Hasher>(_storage_key: &[u8]) -> bool {
 	true
@@ -546,4 +888,186 @@ mod tests {
 
 		assert_eq!(pairs, iter_pairs);
 	}
+
+	#[test]
+	fn ordered_trie_proof_works() {
+		let items: Vec<Vec<u8>> = (0..20u8).map(|i| vec![i; 32 + i as usize]).collect();
+		let root = ordered_trie_root::<Blake2Hasher, _, _>(items.iter());
+
+		let proof = ordered_trie_proof::<Blake2Hasher, _, _>(items.iter(), 7).unwrap().unwrap();
+		assert_eq!(check_ordered_trie_proof::<Blake2Hasher>(&root, 7, proof.clone()).unwrap(), Some(items[7].clone()));
+		assert!(check_ordered_trie_proof::<Blake2Hasher>(&Default::default(), 7, proof.clone()).is_err());
+		assert!(check_ordered_trie_proof::<Blake2Hasher>(&root, 8, proof).is_err());
+
+		assert_eq!(ordered_trie_proof::<Blake2Hasher, _, _>(items.iter(), 20).unwrap(), None);
+	}
+
+	#[test]
+	fn range_proof_works() {
+		let entries: Vec<(Vec<u8>, Vec<u8>)> = (0..64u8).map(|i| (vec![i, i / 2, 7], vec![i; 16 + i as usize])).collect();
+		let mut db = MemoryDB::default();
+		let mut root = Default::default();
+		{
+			let mut t = TrieDBMut::<Blake2Hasher>::new(&mut db, &mut root);
+			for (key, value) in &entries {
+				t.insert(key, value).unwrap();
+			}
+		}
+
+		// only the first entry fits the limit
+		let proof = prove_range::<Blake2Hasher>(&db, &root, &[10, 5, 7], 0).unwrap();
+		let (proved, complete) = check_range_proof::<Blake2Hasher>(&root, &[10, 5, 7], proof).unwrap();
+		assert!(!complete);
+		assert_eq!(proved[0], entries[10]);
+
+		// range starts from the missing key
+		let proof = prove_range::<Blake2Hasher>(&db, &root, &[10, 6], 200).unwrap();
+		let (proved, complete) = check_range_proof::<Blake2Hasher>(&root, &[10, 6], proof).unwrap();
+		assert!(!complete);
+		assert!(proved.len() >= 4);
+		assert_eq!(&proved[..], &entries[11..11 + proved.len()]);
+
+		// range up to the end of the trie
+		let proof = prove_range::<Blake2Hasher>(&db, &root, &[60], 10_000).unwrap();
+		assert_eq!(check_range_proof::<Blake2Hasher>(&root, &[60], proof.clone()).unwrap(), (entries[60..].to_vec(), true));
+		assert_eq!(check_range_proof::<Blake2Hasher>(&root, &[64], proof.clone()).unwrap(), (vec![], true));
+		assert!(check_range_proof::<Blake2Hasher>(&Default::default(), &[60], proof).is_err());
+
+		// proof of other range doesn't allow to find the first entry
+		let proof = prove_range::<Blake2Hasher>(&db, &root, &[60], 0).unwrap();
+		assert!(check_range_proof::<Blake2Hasher>(&root, &[0], proof).is_err());
+	}
+
+	#[test]
+	fn trie_iter_from_works() {
+		let mut db = MemoryDB::default();
+		let mut root = Default::default();
+		{
+			let mut t = TrieDBMut::<Blake2Hasher>::new(&mut db, &mut root);
+			t.insert(b"aa", b"1").unwrap();
+			t.insert(b"ab", b"2").unwrap();
+			t.insert(b"b", b"3").unwrap();
+		}
+
+		let t = TrieDB::<Blake2Hasher>::new(&db, &root).unwrap();
+		let keys = |start_key: &[u8]| trie_iter_from(&t, start_key).unwrap()
+			.map(|entry| entry.unwrap().0)
+			.collect::<Vec<_>>();
+		assert_eq!(keys(b""), vec![b"aa".to_vec(), b"ab".to_vec(), b"b".to_vec()]);
+		assert_eq!(keys(b"ab"), vec![b"ab".to_vec(), b"b".to_vec()]);
+		assert_eq!(keys(b"aba"), vec![b"b".to_vec()]);
+		assert_eq!(keys(b"c"), Vec::<Vec<u8>>::new());
+	}
+
+	#[test]
+	fn prefix_proof_works() {
+		let mut db = MemoryDB::default();
+		let mut root = Default::default();
+		{
+			let mut t = TrieDBMut::<Blake2Hasher>::new(&mut db, &mut root);
+			t.insert(b"a", &[1; 40]).unwrap();
+			t.insert(b"prefix1", &[2; 40]).unwrap();
+			t.insert(b"prefix2", &[3; 40]).unwrap();
+			t.insert(b"z", &[4; 40]).unwrap();
+		}
+
+		let proof = prove_prefix::<Blake2Hasher>(&db, &root, b"prefix").unwrap();
+		assert_eq!(check_prefix_proof::<Blake2Hasher>(&root, b"prefix", proof.clone()).unwrap(), vec![
+			(b"prefix1".to_vec(), vec![2; 40]),
+			(b"prefix2".to_vec(), vec![3; 40]),
+		]);
+		assert!(check_prefix_proof::<Blake2Hasher>(&Default::default(), b"prefix", proof).is_err());
+
+		let proof = prove_prefix::<Blake2Hasher>(&db, &root, b"none").unwrap();
+		assert_eq!(check_prefix_proof::<Blake2Hasher>(&root, b"none", proof.clone()).unwrap(), vec![]);
+
+		// proof that doesn't include the last key with the prefix is rejected
+		let proof = prove_range::<Blake2Hasher>(&db, &root, b"prefix", 0).unwrap();
+		assert!(check_prefix_proof::<Blake2Hasher>(&root, b"prefix", proof).is_err());
+	}
+
+	#[test]
+	fn prefix_page_proof_works() {
+		let entries: Vec<(Vec<u8>, Vec<u8>)> = (0..5u8).map(|i| ([&b"prefix"[..], &[i]].concat(), vec![i; 40])).collect();
+		let mut db = MemoryDB::default();
+		let mut root = Default::default();
+		{
+			let mut t = TrieDBMut::<Blake2Hasher>::new(&mut db, &mut root);
+			t.insert(b"a", &[10; 40]).unwrap();
+			for (key, value) in &entries {
+				t.insert(key, value).unwrap();
+			}
+			t.insert(b"z", &[11; 40]).unwrap();
+		}
+
+		// first page is followed by other entries with the prefix
+		let proof = prove_prefix_page::<Blake2Hasher>(&db, &root, b"prefix", b"", 2).unwrap();
+		let (proved, complete) = check_prefix_page_proof::<Blake2Hasher>(&root, b"prefix", b"", proof).unwrap();
+		assert!(!complete);
+		assert!(proved.len() >= 2);
+		assert_eq!(&proved[..], &entries[..proved.len()]);
+
+		// last page
+		let proof = prove_prefix_page::<Blake2Hasher>(&db, &root, b"prefix", &entries[3].0, 10).unwrap();
+		assert_eq!(check_prefix_page_proof::<Blake2Hasher>(&root, b"prefix", &entries[3].0, proof.clone()).unwrap(),
+			(entries[3..].to_vec(), true));
+		assert!(check_prefix_page_proof::<Blake2Hasher>(&Default::default(), b"prefix", &entries[3].0, proof).is_err());
+
+		// page after the last entry with the prefix is empty
+		let proof = prove_prefix_page::<Blake2Hasher>(&db, &root, b"prefix", b"prefix\x05", 10).unwrap();
+		assert_eq!(check_prefix_page_proof::<Blake2Hasher>(&root, b"prefix", b"prefix\x05", proof).unwrap(), (vec![], true));
+
+		// proof of other page doesn't allow to find the first entry
+		let proof = prove_prefix_page::<Blake2Hasher>(&db, &root, b"prefix", &entries[3].0, 0).unwrap();
+		assert!(check_prefix_page_proof::<Blake2Hasher>(&root, b"prefix", b"", proof).is_err());
+	}
+
+	#[test]
+	fn diff_tries_works() {
+		let from_entries: Vec<(Vec<u8>, Vec<u8>)> = (0u8..64).map(|i| (vec![i / 8, i % 8, 1], vec![i; 40])).collect();
+		let mut db = ::MemoryDB::<Blake2Hasher>::default();
+		db.insert(&[0u8][..]);
+		let mut from_root = Default::default();
+		{
+			let mut t = TrieDBMut::<Blake2Hasher>::new(&mut db, &mut from_root);
+			for (key, value) in &from_entries {
+				t.insert(key, value).unwrap();
+			}
+		}
+		let mut to_root = from_root.clone();
+		{
+			let mut t = TrieDBMut::<Blake2Hasher>::from_existing(&mut db, &mut to_root).unwrap();
+			t.insert(&[0, 1, 1], &[100; 40]).unwrap();
+			t.insert(&[0, 1, 1, 5], &[101]).unwrap();
+			t.insert(&[7], &[102]).unwrap();
+			t.remove(&[3, 3, 1]).unwrap();
+			t.remove(&[7, 7, 1]).unwrap();
+		}
+
+		let diff = |from_root, to_root| {
+			let mut diff = Vec::new();
+			diff_tries::<Blake2Hasher, _>(&db, from_root, &db, to_root, |key, from, to|
+				diff.push((key.to_vec(), from.map(|v| v.to_vec()), to.map(|v| v.to_vec())))).unwrap();
+			diff
+		};
+		assert_eq!(diff(&from_root, &to_root), vec![
+			(vec![0, 1, 1], Some(vec![1; 40]), Some(vec![100; 40])),
+			(vec![0, 1, 1, 5], None, Some(vec![101])),
+			(vec![3, 3, 1], Some(vec![27; 40]), None),
+			(vec![7], None, Some(vec![102])),
+			(vec![7, 7, 1], Some(vec![63; 40]), None),
+		]);
+		assert!(diff(&from_root, &from_root).is_empty());
+
+		// diff with the empty trie lists all entries
+		let empty_root = Blake2Hasher::hash(&[0u8][..]);
+		let added: Vec<_> = diff(&empty_root, &from_root).into_iter().map(|(k, _, v)| (k, v.unwrap())).collect();
+		assert_eq!(added, from_entries);
+
+		// missing nodes are reported
+		let mut empty_db = ::MemoryDB::<Blake2Hasher>::default();
+		empty_db.insert(&[0u8][..]);
+		assert!(diff_tries::<Blake2Hasher, _>(&empty_db, &empty_root, &db, &from_root, |_, _, _| ()).is_ok());
+		assert!(diff_tries::<Blake2Hasher, _>(&empty_db, &from_root, &db, &to_root, |_, _, _| ()).is_err());
+	}
 }
@@ -140,6 +140,7 @@ pub fn staging_testnet_config() -> ChainSpec {
 		Some(STAGING_TELEMETRY_URL.into()),
 		None,
 		None,
+		None,
 	)
 }
 
@@ -240,7 +241,7 @@ fn development_config_genesis() -> GenesisConfig {
 
 /// Development config (single validator Alice)
 pub fn development_config() -> ChainSpec {
-	ChainSpec::from_genesis("Development", "development", development_config_genesis, vec![], None, None, None)
+	ChainSpec::from_genesis("Development", "development", development_config_genesis, vec![], None, None, None, None)
 }
 
 fn local_testnet_genesis() -> GenesisConfig {
@@ -254,7 +255,7 @@ fn local_testnet_genesis() -> GenesisConfig {
 
 /// Local testnet config (multivalidator Alice + Bob)
 pub fn local_testnet_config() -> ChainSpec {
-	ChainSpec::from_genesis("Local Testnet", "local_testnet", local_testnet_genesis, vec![], None, None, None)
+	ChainSpec::from_genesis("Local Testnet", "local_testnet", local_testnet_genesis, vec![], None, None, None, None)
 }
 
 #[cfg(test)]
@@ -271,11 +272,60 @@ mod tests {
 
 	/// Local testnet config (multivalidator Alice + Bob)
 	pub fn integration_test_config() -> ChainSpec {
-		ChainSpec::from_genesis("Integration Test", "test", local_testnet_genesis_instant, vec![], None, None, None)
+		ChainSpec::from_genesis("Integration Test", "test", local_testnet_genesis_instant, vec![], None, None, None, None)
+	}
+
+	#[test]
+	fn test_pinned_genesis_state_root_is_verified() {
+		use primitives::H256;
+		use substrate_service::{chain_ops, ErrorKind};
+
+		let root = chain_ops::genesis_state_root::<Factory>(&integration_test_config()).unwrap();
+		assert!(chain_ops::verify_spec::<Factory>(&integration_test_config(), None).is_err());
+
+		// the raw spec has the root pinned
+		let raw = chain_ops::build_spec::<Factory>(integration_test_config(), true).unwrap();
+		let spec = ChainSpec::from_embedded(Box::leak(raw.into_bytes().into_boxed_slice())).unwrap();
+		assert_eq!(spec.genesis_state_root(), Some(root));
+		assert_eq!(chain_ops::verify_spec::<Factory>(&spec, None).unwrap(), root);
+		assert_eq!(chain_ops::verify_spec::<Factory>(&integration_test_config(), Some(root)).unwrap(), root);
+
+		// the root pinned in the spec of another chain doesn't match
+		let mut spec = local_testnet_config();
+		spec.set_genesis_state_root(root);
+		match chain_ops::verify_spec::<Factory>(&spec, None).unwrap_err().kind() {
+			&ErrorKind::GenesisStateRootMismatch(expected, computed) => {
+				assert_eq!(expected, root);
+				assert!(computed != root);
+			},
+			kind => panic!("Unexpected error {:?}", kind),
+		}
+		assert!(chain_ops::verify_spec::<Factory>(&integration_test_config(), Some(H256::zero())).is_err());
 	}
 
 	#[test]
 	fn test_connectivity() {
 		service_test::connectivity::<Factory>(integration_test_config());
 	}
+
+	#[test]
+	fn test_manual_seal() {
+		service_test::manual_seal::<Factory>(integration_test_config(), "Alice".into());
+	}
+
+	#[test]
+	fn test_light_fallback() {
+		service_test::light_fallback::<Factory>(integration_test_config());
+	}
+
+	#[test]
+	fn test_task_manager() {
+		service_test::task_manager::<Factory>(integration_test_config(), local_testnet_config());
+	}
+
+	#[test]
+	fn test_manual_seal_within_block_period() {
+		// blocks are sealed back to back, faster than the 5 seconds period of the chain
+		service_test::manual_seal::<Factory>(local_testnet_config(), "Alice".into());
+	}
 }
@@ -21,6 +21,7 @@
 
 extern crate tokio;
 
+extern crate parity_codec as codec;
 extern crate substrate_cli as cli;
 extern crate substrate_primitives as primitives;
 extern crate node_runtime;
@@ -33,6 +34,10 @@ extern crate substrate_transaction_pool as transaction_pool;
 #[macro_use]
 extern crate substrate_network as network;
 extern crate substrate_consensus_aura as consensus;
+extern crate substrate_consensus_manual_seal as manual_seal;
+extern crate substrate_consensus_common as consensus_common;
+extern crate substrate_client as client;
+extern crate sr_primitives as runtime_primitives;
 extern crate node_primitives;
 #[macro_use]
 extern crate substrate_service;
@@ -108,7 +113,7 @@ pub fn run<I, T, E>(args: I, exit: E, version: cli::VersionInfo) -> error::Resul
 			info!("Roles: {:?}", config.roles);
 			let mut runtime = Runtime::new()?;
 			let executor = runtime.executor();
-			match config.roles == ServiceRoles::LIGHT {
+			match config.roles.contains(ServiceRoles::LIGHT) {
 				true => run_until_exit(&mut runtime, service::Factory::new_light(config, executor)?, exit)?,
 				false => run_until_exit(&mut runtime, service::Factory::new_full(config, executor)?, exit)?,
 			}
@@ -19,16 +19,26 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
 use std::sync::Arc;
+use client::{self, PrefetchHints};
+use codec::{Decode, Encode};
+use consensus_common::Environment;
+use primitives::{twox_128, AuthorityId, ed25519, storage::StorageKey};
+use runtime_primitives::traits::Header as HeaderT;
+use tokio::prelude::Stream;
 use transaction_pool::{self, txpool::{Pool as TransactionPool}};
-use node_primitives::Block;
-use node_runtime::GenesisConfig;
+use network::import_queue::{BasicQueue, ImportQueue, ExtrinsicSignatures, SignedPayload};
+use node_primitives::{AccountId, Balance, Block, BlockId, BlockNumber, Header, Index, Timestamp, UncheckedExtrinsic as OpaqueExtrinsic};
+use node_runtime::{GenesisConfig, UncheckedExtrinsic, RawAddress, Runtime, Call, BalancesCall, StorageValue, StorageMap,
+	BlockPeriod, TimestampNow, FreeBalance, AccountNonce};
 use substrate_service::{
 	FactoryFullConfiguration, LightComponents, FullComponents, FullBackend,
-	FullClient, LightClient, LightBackend, FullExecutor, LightExecutor,
-	Roles, TaskExecutor,
+	FullClient, LightClient, LightBackend, LightVerifier, FullExecutor, LightExecutor,
+	Roles, TaskExecutor, PoolApi, Proposer, ProposerFactory,
 };
 use node_executor;
-use consensus::{import_queue, start_aura, Config as AuraConfig, AuraImportQueue};
+use consensus::{import_queue, start_aura, Config as AuraConfig, AuraImportQueue, AuraJustificationVerifier, AuraVerifier,
+	SystemSlotClock};
+use manual_seal::{run_manual_seal, instant_seal_commands};
 
 const AURA_SLOT_DURATION: u64 = 6;
 
@@ -53,23 +63,47 @@ construct_service_factory! {
 		FullService = Service<FullComponents<Self>>
 			{ |config: FactoryFullConfiguration<Self>, executor: TaskExecutor| {
 				let is_auth = config.roles == Roles::AUTHORITY;
+				let instant_seal = config.instant_seal;
 				Service::<FullComponents<Factory>>::new(config, executor.clone()).map(move |service|{
 					if is_auth {
 						if let Ok(Some(Ok(key))) = service.keystore().contents()
 							.map(|keys| keys.get(0).map(|k| service.keystore().load(k, "")))
 						{
 							info!("Using authority key {}", key.public());
-							let task = start_aura(
-								AuraConfig {
-									local_key:  Some(Arc::new(key)),
-									slot_duration: AURA_SLOT_DURATION,
-								},
-								service.client(),
-								service.proposer(),
-								service.network(),
-							);
-
-							executor.spawn(task);
+							if let Some(commands) = service.manual_seal_commands() {
+								if instant_seal {
+									info!("Authoring a block for every imported transaction");
+									let imports = service.transaction_pool().import_notification_stream();
+									executor.spawn(run_manual_seal(
+										service.client(),
+										Arc::new(ManualSealEnvironment::new(&service)),
+										Arc::new(key),
+										commands.select(instant_seal_commands(imports)),
+									));
+								} else {
+									info!("Authoring blocks on demand");
+									executor.spawn(run_manual_seal(
+										service.client(),
+										Arc::new(ManualSealEnvironment::new(&service)),
+										Arc::new(key),
+										commands,
+									));
+								}
+							} else {
+								let task = start_aura(
+									AuraConfig {
+										local_key:  Some(Arc::new(key)),
+										slot_duration: AURA_SLOT_DURATION,
+										max_future_drift: AURA_SLOT_DURATION,
+									},
+									service.client(),
+									service.proposer(),
+									service.network(),
+									SystemSlotClock::new(service.network()),
+								);
+
+								executor.spawn(task);
+							}
 						}
 					}
 
@@ -80,21 +114,160 @@ construct_service_factory! {
 		LightService = Service<LightComponents<Self>>
 			{ |config, executor| Service::<LightComponents<Factory>>::new(config, executor) },
 		FullImportQueue = AuraImportQueue<Self::Block, FullClient<Self>>
-			{ |config, client| Ok(import_queue(AuraConfig {
-						local_key: None,
-						slot_duration: 5
-					}, client)) },
-		LightImportQueue = AuraImportQueue<Self::Block, LightClient<Self>>
-			{ |config, client| Ok(import_queue(AuraConfig {
-						local_key: None,
-						slot_duration: 5
-					}, client)) },
+			{ |config, client: Arc<FullClient<Self>>| {
+				let queue = import_queue(AuraConfig {
+					local_key: None,
+					slot_duration: 5,
+					max_future_drift: 5,
+				}, client.clone());
+				client.set_prefetch_hints(Box::new(NodePrefetchHints));
+				queue.set_extrinsic_signatures(Arc::new(NodeExtrinsicSignatures { client }));
+				Ok(queue)
+			}},
+		LightImportQueue = BasicQueue<Self::Block, LightVerifier<Self, AuraVerifier<LightClient<Self>>>>
+			{ |config, client: Arc<LightClient<Self>>| {
+				let blockchain = client.backend().blockchain().clone();
+				let verifier = AuraVerifier::new(AuraConfig {
+					local_key: None,
+					slot_duration: 5,
+					max_future_drift: 5,
+				}, client);
+				Ok(BasicQueue::new(Arc::new(LightVerifier::<Self, _>::new(verifier, blockchain))))
+			}},
+		JustificationVerifier = { |config| {
+				let verifier: Box<client::JustificationVerifier<Block>> = Box::new(AuraJustificationVerifier::default());
+				Ok(verifier)
+			}},
+	}
+}
+
+/// Environment of the manual seal engine. Blocks are stamped with at least the timestamp of the
+/// parent block plus the block period, so that they're valid however fast they're sealed.
+struct ManualSealEnvironment {
+	client: Arc<FullClient<Factory>>,
+	proposer: Arc<ProposerFactory<FullClient<Factory>, PoolApi<FullComponents<Factory>>>>,
+}
+
+impl ManualSealEnvironment {
+	fn new(service: &Service<FullComponents<Factory>>) -> Self {
+		ManualSealEnvironment {
+			client: service.client(),
+			proposer: service.proposer(),
+		}
+	}
+
+	fn timestamp_value(&self, at: &BlockId, key: &[u8]) -> client::error::Result<Timestamp> {
+		let key = StorageKey(twox_128(key).to_vec());
+		Ok(self.client.storage(at, &key)?
+			.and_then(|value| Decode::decode(&mut &value.0[..]))
+			.unwrap_or_default())
+	}
+}
+
+impl Environment<Block> for ManualSealEnvironment {
+	type Proposer = Proposer<FullClient<Factory>, PoolApi<FullComponents<Factory>>>;
+	type Error = client::error::Error;
+
+	fn init(&self, parent_header: &Header, _: &[AuthorityId], _: Arc<ed25519::Pair>) -> Result<Self::Proposer, Self::Error> {
+		let at = BlockId::hash(parent_header.hash());
+		let timestamp = self.timestamp_value(&at, <TimestampNow<Runtime> as StorageValue<Timestamp>>::key())?;
+		let period = self.timestamp_value(&at, <BlockPeriod<Runtime> as StorageValue<Timestamp>>::key())?;
+		self.proposer.init_with_timestamp(parent_header, timestamp + period)
+	}
+}
+
+/// Extracts signatures of node transactions for checking them ahead of block execution.
+///
+/// Only transactions sent from an account id, whose birth block is already imported,
+/// are handled; others are checked by the runtime as usual.
+struct NodeExtrinsicSignatures {
+	client: Arc<FullClient<Factory>>,
+}
+
+impl ExtrinsicSignatures<Block> for NodeExtrinsicSignatures {
+	fn signed_payload(&self, number: BlockNumber, extrinsic: &OpaqueExtrinsic) -> Option<SignedPayload> {
+		let extrinsic: UncheckedExtrinsic = Decode::decode(&mut &extrinsic.encode()[..])?;
+		let (signer, signature, index, era) = match extrinsic.signature {
+			Some((RawAddress::Id(signer), signature, index, era)) => (signer, signature, index, era),
+			_ => return None,
+		};
+		let birth = era.birth(number);
+		if birth >= number {
+			return None;
+		}
+		let birth_hash = self.client.block_hash(birth).ok()??;
+		Some(SignedPayload {
+			signature: (signature.0).0,
+			message: (index, extrinsic.function, era, birth_hash).encode(),
+			signer: signer.0,
+		})
 	}
 }
 
+/// Derives the storage read by node transactions from the extrinsics of a block, so that it's
+/// prefetched before the block is executed.
+///
+/// Only accounts referred to by their id are handled: the nonce and balance of the sender and
+/// the balance of the transfer recipient.
+struct NodePrefetchHints;
+
+impl PrefetchHints<Block> for NodePrefetchHints {
+	fn storage_keys(&self, extrinsics: &[OpaqueExtrinsic]) -> Vec<Vec<u8>> {
+		let mut keys = Vec::new();
+		for extrinsic in extrinsics {
+			let extrinsic: UncheckedExtrinsic = match Decode::decode(&mut &extrinsic.encode()[..]) {
+				Some(extrinsic) => extrinsic,
+				None => continue,
+			};
+			if let Some((RawAddress::Id(ref signer), _, _, _)) = extrinsic.signature {
+				keys.push(<AccountNonce<Runtime> as StorageMap<AccountId, Index>>::key_for(signer));
+				keys.push(<FreeBalance<Runtime> as StorageMap<AccountId, Balance>>::key_for(signer));
+			}
+			if let Call::Balances(BalancesCall::transfer(RawAddress::Id(ref dest), _)) = extrinsic.function {
+				keys.push(<FreeBalance<Runtime> as StorageMap<AccountId, Balance>>::key_for(dest));
+			}
+		}
+		keys.into_iter().map(|key| twox_128(&key).to_vec()).collect()
+	}
+}
 
 #[cfg(test)]
 mod tests {
+	use codec::{Decode, Encode};
+	use client::PrefetchHints;
+	use primitives::twox_128;
+	use runtime_primitives::generic::Era;
+	use node_primitives::{AccountId, Balance, Index, UncheckedExtrinsic as OpaqueExtrinsic};
+	use node_runtime::{UncheckedExtrinsic, RawAddress, Runtime, Call, BalancesCall, TimestampCall, StorageMap,
+		FreeBalance, AccountNonce};
+	use super::NodePrefetchHints;
+
+	#[test]
+	fn prefetch_hints_cover_accounts_of_transfer() {
+		let alice = AccountId::from([1; 32]);
+		let bob = AccountId::from([2; 32]);
+		let opaque = |extrinsic: UncheckedExtrinsic| {
+			let encoded = extrinsic.encode();
+			OpaqueExtrinsic(Decode::decode(&mut &encoded[..]).unwrap())
+		};
+		let extrinsics = vec![
+			opaque(UncheckedExtrinsic::new_unsigned(Call::Timestamp(TimestampCall::set(42.into())))),
+			opaque(UncheckedExtrinsic::new_signed(
+				0,
+				Call::Balances(BalancesCall::transfer(RawAddress::Id(bob), 69.into())),
+				RawAddress::Id(alice),
+				Default::default(),
+				Era::immortal(),
+			)),
+		];
+
+		let expected: Vec<Vec<u8>> = vec![
+			<AccountNonce<Runtime> as StorageMap<AccountId, Index>>::key_for(&alice),
+			<FreeBalance<Runtime> as StorageMap<AccountId, Balance>>::key_for(&alice),
+			<FreeBalance<Runtime> as StorageMap<AccountId, Balance>>::key_for(&bob),
+		].into_iter().map(|key| twox_128(&key).to_vec()).collect();
+		assert_eq!(NodePrefetchHints.storage_keys(&extrinsics), expected);
+	}
 	#[cfg(feature = "rhd")]
 	fn test_sync() {
 		use {service_test, Factory};
@@ -79,8 +79,10 @@ pub use consensus::Call as ConsensusCall;
 pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
 pub use runtime_primitives::{Permill, Perbill};
-pub use timestamp::BlockPeriod;
-pub use srml_support::{StorageValue, RuntimeMetadata};
+pub use timestamp::{BlockPeriod, Now as TimestampNow};
+pub use balances::FreeBalance;
+pub use system::AccountNonce;
+pub use srml_support::{StorageValue, StorageMap, RuntimeMetadata};
 
 const TIMESTAMP_SET_POSITION: u32 = 0;
 const NOTE_OFFLINE_POSITION: u32 = 1;
@@ -90,11 +92,11 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: ver_str!("node"),
 	impl_name: ver_str!("substrate-node"),
 	authoring_version: 1,
-	spec_version: 1,
+	spec_version: 6,
 	impl_version: 0,
 	apis: apis_vec!([
 		(BLOCK_BUILDER, 1),
-		(TAGGED_TRANSACTION_QUEUE, 1),
+		(TAGGED_TRANSACTION_QUEUE, 2),
 		(METADATA, 1)
 	]),
 };
@@ -200,7 +202,7 @@ construct_runtime!(
 		Block = Block,
 		UncheckedExtrinsic = UncheckedExtrinsic
 	{
-		System: system::{default, Log(ChangesTrieRoot)},
+		System: system::{default, Log(ChangesTrieRoot, PreRuntime)},
 		Timestamp: timestamp::{Module, Call, Storage, Config<T>, Inherent},
 		Consensus: consensus::{Module, Call, Storage, Config<T>, Log(AuthoritiesChange), Inherent},
 		Balances: balances,
@@ -26,7 +26,7 @@ use mock::{Consensus, System, new_test_ext};
 #[test]
 fn authorities_change_logged() {
 	with_externalities(&mut new_test_ext(vec![1, 2, 3]), || {
-		System::initialise(&1, &Default::default(), &Default::default());
+		System::initialise(&1, &Default::default(), &Default::default(), &Default::default());
 		Consensus::set_authorities(&[4, 5, 6]);
 		Consensus::on_finalise(1);
 		let header = System::finalise();
@@ -41,7 +41,7 @@ fn authorities_change_logged() {
 #[test]
 fn authorities_change_is_not_logged_when_not_changed() {
 	with_externalities(&mut new_test_ext(vec![1, 2, 3]), || {
-		System::initialise(&1, &Default::default(), &Default::default());
+		System::initialise(&1, &Default::default(), &Default::default(), &Default::default());
 		Consensus::on_finalise(1);
 		let header = System::finalise();
 		assert_eq!(header.digest, testing::Digest {
@@ -53,7 +53,7 @@ fn authorities_change_is_not_logged_when_not_changed() {
 #[test]
 fn authorities_change_is_not_logged_when_changed_back_to_original() {
 	with_externalities(&mut new_test_ext(vec![1, 2, 3]), || {
-		System::initialise(&1, &Default::default(), &Default::default());
+		System::initialise(&1, &Default::default(), &Default::default(), &Default::default());
 		Consensus::set_authorities(&[4, 5, 6]);
 		Consensus::set_authorities(&[1, 2, 3]);
 		Consensus::on_finalise(1);
@@ -93,7 +93,7 @@ impl<
 {
 	/// Start the execution of a particular block.
 	pub fn initialise_block(header: &System::Header) {
-		<system::Module<System>>::initialise(header.number(), header.parent_hash(), header.extrinsics_root());
+		<system::Module<System>>::initialise(header.number(), header.parent_hash(), header.extrinsics_root(), header.digest());
 	}
 
 	fn initial_checks(block: &Block) {
@@ -265,6 +265,7 @@ impl<
 				requires: deps,
 				provides: vec![(sender, *index).encode()],
 				longevity: TransactionLongevity::max_value(),
+				propagate: true,
 			}
 		} else {
 			return TransactionValidity::Invalid
@@ -41,7 +41,7 @@ extern crate safe_mix;
 
 use rstd::prelude::*;
 use primitives::traits::{self, CheckEqual, SimpleArithmetic, SimpleBitOps, Zero, One, Bounded, Lookup,
-	Hash, Member, MaybeDisplay, EnsureOrigin, Digest as DigestT, As, CurrentHeight, BlockNumberToHash};
+	Hash, Member, MaybeDisplay, EnsureOrigin, Digest as DigestT, DigestItem as DigestItemT, As, CurrentHeight, BlockNumberToHash};
 use substrate_primitives::storage::well_known_keys;
 use runtime_support::{storage, StorageValue, StorageMap, Parameter};
 use safe_mix::TripletMix;
@@ -163,6 +163,8 @@ pub enum RawLog<Hash> {
 	/// Changes trie has been computed for this block. Contains the root of
 	/// changes trie.
 	ChangesTrieRoot(Hash),
+	/// Data supplied by the block author before the block has been executed.
+	PreRuntime(Vec<u8>),
 }
 
 impl<Hash: Member> RawLog<Hash> {
@@ -170,6 +172,15 @@ impl<Hash: Member> RawLog<Hash> {
 	pub fn as_changes_trie_root(&self) -> Option<&Hash> {
 		match *self {
 			RawLog::ChangesTrieRoot(ref item) => Some(item),
+			_ => None,
+		}
+	}
+
+	/// Try to cast the log entry as PreRuntime log entry.
+	pub fn as_pre_runtime(&self) -> Option<&[u8]> {
+		match *self {
+			RawLog::PreRuntime(ref data) => Some(data),
+			_ => None,
 		}
 	}
 }
@@ -181,6 +192,7 @@ impl From<RawLog<substrate_primitives::H256>> for primitives::testing::DigestIte
 		match log {
 			RawLog::ChangesTrieRoot(root) => primitives::generic::DigestItem::ChangesTrieRoot
 				::<substrate_primitives::H256, u64>(root),
+			RawLog::PreRuntime(data) => primitives::generic::DigestItem::PreRuntime(data),
 		}
 	}
 }
@@ -265,14 +277,20 @@ impl<T: Trait> Module<T> {
 		storage::unhashed::get(well_known_keys::EXTRINSIC_INDEX)
 	}
 
-	/// Start the execution of a particular block.
-	pub fn initialise(number: &T::BlockNumber, parent_hash: &T::Hash, txs_root: &T::Hash) {
+	/// Start the execution of a particular block. Pre-runtime items of the given digest are
+	/// retained, so they are available during execution and are part of the final header.
+	pub fn initialise(number: &T::BlockNumber, parent_hash: &T::Hash, txs_root: &T::Hash, digest: &T::Digest) {
 		// populate environment.
 		storage::unhashed::put(well_known_keys::EXTRINSIC_INDEX, &0u32);
 		<Number<T>>::put(number);
 		<ParentHash<T>>::put(parent_hash);
 		<BlockHash<T>>::insert(*number - One::one(), parent_hash);
 		<ExtrinsicsRoot<T>>::put(txs_root);
+		let mut pre_runtime_digest = T::Digest::default();
+		for item in digest.logs().iter().filter(|item| item.as_pre_runtime().is_some()) {
+			pre_runtime_digest.push(item.clone());
+		}
+		<Digest<T>>::put(pre_runtime_digest);
 		<RandomSeed<T>>::put(Self::calculate_random());
 		<Events<T>>::kill();
 	}
@@ -303,6 +321,14 @@ impl<T: Trait> Module<T> {
 			parent_hash, digest)
 	}
 
+	/// Data of pre-runtime digest items of the current block, in the order supplied by the
+	/// block author.
+	pub fn pre_runtime_digests() -> Vec<Vec<u8>> {
+		Self::digest().logs().iter()
+			.filter_map(|item| item.as_pre_runtime().map(|data| data.to_vec()))
+			.collect()
+	}
+
 	/// Deposits a log and ensures it matches the blocks log data.
 	pub fn deposit_log(item: <T::Digest as traits::Digest>::Item) {
 		let mut l = <Digest<T>>::get();
@@ -473,13 +499,13 @@ mod tests {
 	#[test]
 	fn deposit_event_should_work() {
 		with_externalities(&mut new_test_ext(), || {
-			System::initialise(&1, &[0u8; 32].into(), &[0u8; 32].into());
+			System::initialise(&1, &[0u8; 32].into(), &[0u8; 32].into(), &Default::default());
 			System::note_finished_extrinsics();
 			System::deposit_event(1u16);
 			System::finalise();
 			assert_eq!(System::events(), vec![EventRecord { phase: Phase::Finalization, event: 1u16 }]);
 
-			System::initialise(&2, &[0u8; 32].into(), &[0u8; 32].into());
+			System::initialise(&2, &[0u8; 32].into(), &[0u8; 32].into(), &Default::default());
 			System::deposit_event(42u16);
 			System::note_applied_extrinsic(&Ok(()));
 			System::note_applied_extrinsic(&Err(""));
@@ -494,4 +520,25 @@ mod tests {
 			]);
 		});
 	}
+
+	#[test]
+	fn pre_runtime_digests_are_retained_on_initialise() {
+		with_externalities(&mut new_test_ext(), || {
+			let digest = Digest { logs: vec![
+				DigestItem::Other(vec![1]),
+				DigestItem::PreRuntime(vec![2]),
+				DigestItem::ChangesTrieRoot([3u8; 32].into()),
+				DigestItem::PreRuntime(vec![4]),
+			] };
+			System::initialise(&1, &[0u8; 32].into(), &[0u8; 32].into(), &digest);
+			assert_eq!(System::pre_runtime_digests(), vec![vec![2], vec![4]]);
+
+			System::deposit_log(DigestItem::Other(vec![5]));
+			assert_eq!(System::finalise().digest.logs, vec![
+				DigestItem::PreRuntime(vec![2]),
+				DigestItem::PreRuntime(vec![4]),
+				DigestItem::Other(vec![5]),
+			]);
+		});
+	}
 }